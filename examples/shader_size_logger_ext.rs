@@ -4,7 +4,7 @@ use std::path::Path;
 use wesl_build::extension::wgpu_bindings::WgpuBindingsExtension;
 use wesl_build::{WeslBuildError, extension::WeslBuildExtension, build_shader_dir};
 
-use wesl::{BasicSourceMap, Wesl};
+use wesl_build::prelude::{BasicSourceMap, Wesl};
 
 struct WeslSizeLogger {
     messages: Vec<String>,
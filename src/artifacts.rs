@@ -0,0 +1,301 @@
+//! Where a build writes its artifacts, the built shaders and the files extensions write to the output dir
+//!
+//! Extensions should [`read`] the built shaders and [`write`] their files through this module instead of [`std::fs`],
+//! so they go to the [`ArtifactSink`] of the build, e.g. a [`MemorySink`] in a [check](`crate::WeslBuildConfig::check`).
+//! Rust sources generated into your crate, like bindings, are not artifacts and are written directly.
+//!
+//! Files are written [atomically](write_atomic), so an interrupted build leaves the previous file or the new one, never half of it,
+//! and files whose contents did not change are [left alone](write_if_changed), so their mtime does not trigger rebuilds.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::BTreeMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError, atomic::{AtomicUsize, Ordering}},
+};
+
+/// Stores the artifacts of a build by path, the paths are the same as they would be on disk
+pub trait ArtifactSink: fmt::Debug + Send {
+    /// Write the artifact at `path`, replacing it if it exists
+    fn write(&mut self, path: &Path, contents: &[u8]) -> io::Result<()>;
+
+    /// Read the artifact at `path`
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+}
+
+/// An [`ArtifactSink`] shared between the build and the code that set it up, see [`WeslBuildConfig::artifact_sink`](`crate::WeslBuildConfig::artifact_sink`)
+pub type SharedSink = Arc<Mutex<dyn ArtifactSink>>;
+
+/// Writes artifacts to disk atomically if they changed, creating their parent dirs, the default
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FsSink;
+
+impl ArtifactSink for FsSink {
+    fn write(&mut self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        if let Some(parent) = path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            fs::create_dir_all(parent)?;
+        }
+        write_if_changed(path, contents).map(|_| ())
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+}
+
+/// Write `contents` to a temp file next to `path` and rename it to `path`, replacing the file if it exists
+///
+/// The rename is atomic when both are on the same file system, so readers and interrupted builds
+/// see either the old file or the new one. The parent dir of `path` must exist.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    static NEXT_TEMP: AtomicUsize = AtomicUsize::new(0);
+
+    let file_name = path.file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("{} is not a file path", path.display())))?;
+    let temp_path = path.with_file_name(format!(
+        ".{}.{}.{}.tmp",
+        file_name.to_string_lossy(),
+        std::process::id(),
+        NEXT_TEMP.fetch_add(1, Ordering::Relaxed),
+    ));
+
+    let result = fs::write(&temp_path, contents).and_then(|()| fs::rename(&temp_path, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+/// Write `contents` to `path` with [`write_atomic`] unless the file already has them, returns whether it was written
+///
+/// The write is counted in the [`WriteStats`] of the build running on this thread.
+pub fn write_if_changed(path: &Path, contents: &[u8]) -> io::Result<bool> {
+    let unchanged = fs::metadata(path).is_ok_and(|metadata| metadata.len() == contents.len() as u64)
+        && fs::read(path).is_ok_and(|old| old == contents);
+    if !unchanged {
+        write_atomic(path, contents)?;
+    }
+    record_write(!unchanged);
+    Ok(!unchanged)
+}
+
+/// How many files a build wrote to disk, and how many it skipped as they were up to date
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WriteStats {
+    pub written: usize,
+    pub up_to_date: usize,
+}
+
+/// Count a file of the current build as written, or as up to date if it was not
+pub(crate) fn record_write(written: bool) {
+    WRITE_STATS.with(|stats| {
+        let mut counted = stats.get();
+        match written {
+            true => counted.written += 1,
+            false => counted.up_to_date += 1,
+        }
+        stats.set(counted);
+    });
+}
+
+/// The files written by the build running on this thread so far
+pub(crate) fn write_stats() -> WriteStats {
+    WRITE_STATS.get()
+}
+
+/// Keeps artifacts in memory, nothing is written to disk
+///
+/// ## Example
+/// ```
+/// use std::sync::{Arc, Mutex};
+/// use wesl_build::{WeslBuildConfig, build_shader_dir_with_config, extensions, artifacts::MemorySink};
+///
+/// let sink = Arc::new(Mutex::new(MemorySink::new()));
+/// build_shader_dir_with_config(
+///     &WeslBuildConfig::new("test/src/shaders").out_dir("built").artifact_sink(sink.clone()),
+///     extensions![],
+/// ).unwrap();
+///
+/// let sink = sink.lock().unwrap();
+/// assert!(sink.get_str("built/wesl/test.wgsl").is_some());
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct MemorySink {
+    artifacts: BTreeMap<PathBuf, Vec<u8>>,
+}
+
+impl MemorySink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The artifact at `path`
+    pub fn get(&self, path: impl AsRef<Path>) -> Option<&[u8]> {
+        self.artifacts.get(path.as_ref()).map(Vec::as_slice)
+    }
+
+    /// The artifact at `path`, `None` if it's missing or not UTF-8
+    pub fn get_str(&self, path: impl AsRef<Path>) -> Option<&str> {
+        self.get(path).and_then(|contents| std::str::from_utf8(contents).ok())
+    }
+
+    /// Every artifact by path, sorted by path
+    pub fn iter(&self) -> impl Iterator<Item = (&Path, &[u8])> {
+        self.artifacts.iter().map(|(path, contents)| (path.as_path(), contents.as_slice()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.artifacts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.artifacts.is_empty()
+    }
+
+    pub fn into_inner(self) -> BTreeMap<PathBuf, Vec<u8>> {
+        self.artifacts
+    }
+}
+
+impl ArtifactSink for MemorySink {
+    fn write(&mut self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        self.artifacts.insert(path.to_owned(), contents.to_owned());
+        Ok(())
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.get(path).map(<[u8]>::to_vec).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("no artifact at {}", path.display()))
+        })
+    }
+}
+
+thread_local! {
+    /// The sink of the build running on this thread
+    static CURRENT_SINK: RefCell<Option<SharedSink>> = const { RefCell::new(None) };
+    /// The files written by the build running on this thread
+    static WRITE_STATS: Cell<WriteStats> = const { Cell::new(WriteStats { written: 0, up_to_date: 0 }) };
+}
+
+/// Run `f` with the sink of the build running on this thread, or [`FsSink`] if no build is running
+fn with_sink<T>(f: impl FnOnce(&mut dyn ArtifactSink) -> T) -> T {
+    match CURRENT_SINK.with_borrow(Clone::clone) {
+        Some(sink) => f(&mut *sink.lock().unwrap_or_else(PoisonError::into_inner)),
+        None => f(&mut FsSink),
+    }
+}
+
+/// Write the artifact at `path` to the sink of the current build
+pub fn write(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    with_sink(|sink| sink.write(path.as_ref(), contents.as_ref()))
+}
+
+/// Read the artifact at `path` from the sink of the current build
+pub fn read(path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+    with_sink(|sink| sink.read(path.as_ref()))
+}
+
+/// Read the artifact at `path` from the sink of the current build as UTF-8
+pub fn read_to_string(path: impl AsRef<Path>) -> io::Result<String> {
+    String::from_utf8(read(path)?).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ArtifactWriteError {
+    #[error(transparent)]
+    IoErr(#[from] io::Error),
+    /// An extension tried to write outside of the dirs of the build, e.g. a path with too many `..`
+    #[error(
+        "`{}` is outside of the artifact roots {}, declare its dir with `WeslBuildConfig::artifact_root`",
+        .path.display(), .roots.iter().map(|root| format!("`{}`", root.display())).collect::<Vec<_>>().join(", "),
+    )]
+    OutsideRoots { path: PathBuf, roots: Vec<PathBuf> },
+}
+
+/// Writes artifacts like [`write`], but only inside the output dir and the other artifact roots of the build,
+/// get it with [`BuildContext::artifact_writer`](`crate::BuildContext::artifact_writer`)
+///
+/// Paths are checked after resolving their `.` and `..` components, without following symlinks,
+/// so path traversal bugs in extensions fail the build instead of writing somewhere in the file system.
+///
+/// ## Example
+/// ```
+/// use wesl_build::artifacts::ArtifactWriter;
+///
+/// let writer = ArtifactWriter::new(["target/out"]);
+/// assert!(writer.check("target/out/report.txt").is_ok());
+/// assert!(writer.check("target/out/../../Cargo.toml").is_err());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ArtifactWriter {
+    /// `None` for any path
+    roots: Option<Vec<PathBuf>>,
+}
+
+impl ArtifactWriter {
+    /// A writer of the paths inside `roots`
+    pub fn new(roots: impl IntoIterator<Item = impl AsRef<Path>>) -> Self {
+        Self { roots: Some(roots.into_iter().map(|root| normalize(root.as_ref())).collect()) }
+    }
+
+    /// A writer of any path, as with [`WeslBuildConfig::sandbox_artifacts(false)`](`crate::WeslBuildConfig::sandbox_artifacts`)
+    pub fn unrestricted() -> Self {
+        Self { roots: None }
+    }
+
+    /// The dirs it writes to, `None` if it writes anywhere
+    pub fn roots(&self) -> Option<&[PathBuf]> {
+        self.roots.as_deref()
+    }
+
+    /// `path` without `.` and `..` components, if it is inside one of the roots
+    pub fn check(&self, path: impl AsRef<Path>) -> Result<PathBuf, ArtifactWriteError> {
+        let path = normalize(path.as_ref());
+        match &self.roots {
+            Some(roots) if !roots.iter().any(|root| path.starts_with(root)) => {
+                Err(ArtifactWriteError::OutsideRoots { path, roots: roots.clone() })
+            }
+            _ => Ok(path),
+        }
+    }
+
+    /// [`write`] the artifact at `path` if it is inside one of the roots
+    pub fn write(&self, path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> Result<(), ArtifactWriteError> {
+        Ok(write(self.check(path)?, contents)?)
+    }
+}
+
+/// `path` made absolute, with its `.` and `..` components resolved lexically
+fn normalize(path: &Path) -> PathBuf {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_owned());
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            std::path::Component::CurDir => (),
+            std::path::Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Sets the sink of the current build and counts its [`WriteStats`] from zero until it is dropped
+pub(crate) struct SinkGuard(Option<SharedSink>, WriteStats);
+
+impl SinkGuard {
+    pub(crate) fn set(sink: SharedSink) -> Self {
+        Self(CURRENT_SINK.replace(Some(sink)), WRITE_STATS.replace(WriteStats::default()))
+    }
+}
+
+impl Drop for SinkGuard {
+    fn drop(&mut self) {
+        CURRENT_SINK.set(self.0.take());
+        WRITE_STATS.set(self.1);
+    }
+}
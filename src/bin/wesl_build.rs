@@ -0,0 +1,266 @@
+//! `wesl-build`, runs the `wesl_build` pipeline outside of cargo so shaders can be iterated on without a full cargo build
+//!
+//! ```sh
+//! wesl-build build --root src/shaders --out target/shaders --ext bindings,minify
+//! wesl-build check --root src/shaders
+//! wesl-build check --root src/shaders --message-format json
+//! wesl-build graph --root src/shaders > shader_deps.dot
+//! wesl-build new compute particles/update --snippet
+//! ```
+
+use std::{fmt::Write as _, fs, path::{Path, PathBuf}, process::ExitCode};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use wesl::ManglerKind;
+use wesl_build::{ModuleGraph, WeslBuildConfig, WeslBuildError, build_shader_dir_with_config, extension::{BoxedExtension, ExtensionSet}};
+use wesl_build::messages::JsonOutput;
+use wesl_build::scaffold::{ShaderKind, new_shader};
+
+#[derive(Parser)]
+#[command(name = "wesl-build", version, about = "Build WESL shaders outside of cargo")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Build the shaders, running the given extensions
+    Build {
+        #[command(flatten)]
+        shaders: ShaderArgs,
+        /// Where to write the built shaders and the files of extensions
+        #[arg(long, default_value = "target/shaders")]
+        out: PathBuf,
+        #[command(flatten)]
+        extensions: ExtensionArgs,
+        /// Write how long each module and extension took to `wesl_build_timings.txt`
+        #[arg(long)]
+        timings: bool,
+    },
+    /// Compile every shader and run the extensions' validation, reporting all errors without writing anything
+    Check {
+        #[command(flatten)]
+        shaders: ShaderArgs,
+        #[command(flatten)]
+        extensions: ExtensionArgs,
+    },
+    /// Print which shaders import which as a Graphviz graph
+    Graph {
+        /// Root dir of the shaders
+        #[arg(long, default_value = "src/shaders")]
+        root: PathBuf,
+    },
+    /// Create a shader with an entry point and bind group stubs
+    New {
+        /// The kind of shader
+        #[arg(value_enum)]
+        kind: Kind,
+        /// The module of the new shader, e.g. `particles/update`
+        module: String,
+        /// Root dir of the shaders
+        #[arg(long, default_value = "src/shaders")]
+        root: PathBuf,
+        /// Also print rust code that creates a `wgpu` shader module from it
+        #[arg(long)]
+        snippet: bool,
+    },
+}
+
+#[derive(Args)]
+struct ShaderArgs {
+    /// Root dir of the shaders
+    #[arg(long, default_value = "src/shaders")]
+    root: String,
+    /// The mangler used for item and artifact names
+    #[arg(long, value_enum, default_value_t = Mangler::Escape)]
+    mangler: Mangler,
+    /// A shader package the shaders import by name, as `name=dir`
+    #[arg(long = "package", value_parser = parse_package)]
+    packages: Vec<(String, PathBuf)>,
+    /// How to print diagnostics, `json` prints one JSON object per line to stdout for editors
+    #[arg(long, value_enum, default_value_t = MessageFormat::Human)]
+    message_format: MessageFormat,
+}
+
+impl ShaderArgs {
+    fn config(&self) -> WeslBuildConfig {
+        let config = WeslBuildConfig::new(&self.root)
+            .mangler(match self.mangler {
+                Mangler::Escape => ManglerKind::Escape,
+                Mangler::Hash => ManglerKind::Hash,
+                Mangler::None => ManglerKind::None,
+            })
+            .cargo_instructions(false);
+        let config = match self.message_format {
+            MessageFormat::Human => config,
+            MessageFormat::Json => config.json_diagnostics(JsonOutput::Stdout),
+        };
+        self.packages.iter().fold(config, |config, (name, dir)| config.add_package(name, dir.as_path()))
+    }
+}
+
+fn parse_package(package: &str) -> Result<(String, PathBuf), String> {
+    package.split_once('=')
+        .map(|(name, dir)| (name.to_owned(), PathBuf::from(dir)))
+        .ok_or_else(|| format!("expected `name=dir`, got `{package}`"))
+}
+
+#[derive(Args)]
+struct ExtensionArgs {
+    /// The extensions to run, in order
+    #[arg(long, value_enum, value_delimiter = ',')]
+    ext: Vec<Extension>,
+    /// Where the `bindings` extension writes the rust bindings
+    #[arg(long, default_value = "src/shader_bindings")]
+    bindings_out: String,
+}
+
+impl ExtensionArgs {
+    fn extensions(&self, out: &Path) -> Result<ExtensionSet, WeslBuildError> {
+        self.ext.iter()
+            .map(|ext| ext.extension(&self.bindings_out, out))
+            .collect::<Result<Vec<_>, _>>()
+            .map(ExtensionSet::from)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum MessageFormat {
+    Human,
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Mangler {
+    Escape,
+    Hash,
+    None,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Kind {
+    Compute,
+    Vertex,
+    Fragment,
+    Render,
+}
+
+/// The built-in extensions that can be run from the command line
+#[derive(Clone, Copy, ValueEnum)]
+enum Extension {
+    #[cfg(feature = "wgpu_bindings_ext")]
+    Bindings,
+    #[cfg(feature = "wgsl_minifier_ext")]
+    Minify,
+    #[cfg(feature = "wesl_lint_ext")]
+    Lint,
+    #[cfg(feature = "shader_stats_ext")]
+    Stats,
+    #[cfg(feature = "wgsl_formatter_ext")]
+    Format,
+    #[cfg(feature = "dependency_graph_ext")]
+    Graph,
+    #[cfg(feature = "dead_code_report_ext")]
+    DeadCode,
+}
+
+impl Extension {
+    #[allow(unused_variables)]
+    fn extension(self, bindings_out: &str, out: &Path) -> Result<BoxedExtension, WeslBuildError> {
+        use wesl_build::extension::*;
+
+        Ok(match self {
+            #[cfg(feature = "wgpu_bindings_ext")]
+            Extension::Bindings => Box::new(wgpu_bindings::WgpuBindingsExtension::new(bindings_out)?),
+            #[cfg(feature = "wgsl_minifier_ext")]
+            Extension::Minify => Box::new(wgsl_minifier::WgslMinifierExtension::new()),
+            #[cfg(feature = "wesl_lint_ext")]
+            Extension::Lint => Box::new(wesl_lint::WeslLintExtension::new()),
+            #[cfg(feature = "shader_stats_ext")]
+            Extension::Stats => Box::new(shader_stats::ShaderStatsExtension::new()),
+            #[cfg(feature = "wgsl_formatter_ext")]
+            Extension::Format => Box::new(wgsl_formatter::WgslFormatterExtension::new()),
+            #[cfg(feature = "dependency_graph_ext")]
+            Extension::Graph => Box::new(dependency_graph::DependencyGraphExtension::new().with_output_dir(out)),
+            #[cfg(feature = "dead_code_report_ext")]
+            Extension::DeadCode => Box::new(dead_code_report::DeadCodeReportExtension::new()),
+        })
+    }
+}
+
+fn main() -> ExitCode {
+    let result = match Cli::parse().command {
+        Command::Build { shaders, out, extensions, timings } => build(&shaders, &out, &extensions, timings),
+        Command::Check { shaders, extensions } => check(&shaders, &extensions),
+        Command::Graph { root } => graph(&root),
+        Command::New { kind, module, root, snippet } => new(kind, &module, &root, snippet),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn build(shaders: &ShaderArgs, out: &Path, extensions: &ExtensionArgs, timings: bool) -> Result<(), WeslBuildError> {
+    fs::create_dir_all(out)?;
+    // extensions write their files to `OUT_DIR` like in a build script
+    // SAFETY: no other threads are running yet
+    unsafe { std::env::set_var("OUT_DIR", out) };
+
+    let mut extensions = extensions.extensions(out)?;
+    let plan = build_shader_dir_with_config(&shaders.config().out_dir(out).timings(timings), &mut extensions)?;
+
+    // stdout is only JSON with `--message-format json`
+    if shaders.message_format == MessageFormat::Human {
+        println!("built {} shaders to {}", plan.shaders.len(), out.display());
+    }
+    Ok(())
+}
+
+fn check(shaders: &ShaderArgs, extensions: &ExtensionArgs) -> Result<(), WeslBuildError> {
+    // nothing is written in a check
+    let mut extensions = extensions.extensions(Path::new(""))?;
+    let plan = build_shader_dir_with_config(&shaders.config().continue_on_error(true).check(true), &mut extensions)?;
+
+    if shaders.message_format == MessageFormat::Human {
+        println!("checked {} shaders", plan.shaders.len());
+    }
+    Ok(())
+}
+
+fn graph(root: &Path) -> Result<(), WeslBuildError> {
+    let graph = ModuleGraph::from_dir(root)?;
+
+    let mut dot = String::from("digraph shaders {\n");
+    for module in graph.modules() {
+        writeln!(dot, "    {:?};", module.to_string()).expect("writing to a String can't fail");
+    }
+    for (importer, imported) in graph.edges() {
+        writeln!(dot, "    {:?} -> {:?};", importer.to_string(), imported.to_string()).expect("writing to a String can't fail");
+    }
+    dot.push_str("}\n");
+
+    print!("{dot}");
+    Ok(())
+}
+
+fn new(kind: Kind, module: &str, root: &Path, snippet: bool) -> Result<(), WeslBuildError> {
+    let kind = match kind {
+        Kind::Compute => ShaderKind::Compute,
+        Kind::Vertex => ShaderKind::Vertex,
+        Kind::Fragment => ShaderKind::Fragment,
+        Kind::Render => ShaderKind::Render,
+    };
+    let shader = new_shader(root, kind, module)?;
+
+    println!("created {}", shader.path.display());
+    if snippet {
+        print!("\n{}", shader.rust_snippet());
+    }
+    Ok(())
+}
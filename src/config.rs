@@ -0,0 +1,708 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use wesl::{CompileOptions, ManglerKind};
+
+use crate::WeslBuildError;
+use crate::artifacts::SharedSink;
+use crate::context::try_with_build_context;
+use crate::extension::ExtensionSet;
+use crate::metadata::{CargoToml, MetadataSection};
+use crate::names::FileNames;
+use crate::messages::JsonOutput;
+use crate::package::{PackageSource, ShaderPackage};
+
+/// The configuration of a [`build_shader_dir_with_config`](`crate::build_shader_dir_with_config`) run
+///
+/// Fields can be read directly and set with the builder methods of the same name
+///
+/// ## Example
+/// ```
+/// use wesl_build::WeslBuildConfig;
+///
+/// let config = WeslBuildConfig::new("src/shaders")
+///     .mangler(wesl::ManglerKind::Hash);
+/// assert_eq!(config.shader_path, "src/shaders");
+/// ```
+#[derive(Debug, Clone)]
+pub struct WeslBuildConfig {
+    /// Root dir of all your shaders
+    pub shader_path: String,
+    /// The options passed to the wesl compiler
+    pub compile_options: CompileOptions,
+    /// The mangler used for item and artifact names, `include_wesl!` is told which one was used
+    pub mangler: ManglerKind,
+    /// Where the built shaders are written, `None` for cargo's `OUT_DIR`
+    pub out_dir: Option<PathBuf>,
+    /// Only plan the build, see [`dry_run`](Self::dry_run)
+    pub dry_run: bool,
+    /// Build every shader it can instead of stopping at the first error, see [`continue_on_error`](Self::continue_on_error)
+    pub continue_on_error: bool,
+    /// Emit a `rerun-if-changed` line for every shader file, see [`rerun_per_file`](Self::rerun_per_file)
+    pub rerun_per_file: bool,
+    /// Extra files and dirs that rerun the build script when changed, see [`rerun_if_changed`](Self::rerun_if_changed)
+    pub rerun_if_changed: Vec<PathBuf>,
+    /// Walk into symlinked dirs, see [`follow_symlinks`](Self::follow_symlinks)
+    pub follow_symlinks: bool,
+    /// How many dirs deep below the shader dir to walk, see [`max_depth`](Self::max_depth)
+    pub max_depth: usize,
+    /// The build profile extensions see, `None` for cargo's `PROFILE`, see [`profile`](Self::profile)
+    pub profile: Option<String>,
+    /// Write how long each module compile and extension hook took, see [`timings`](Self::timings)
+    pub timings: bool,
+    /// Write the timings as chrome tracing JSON, see [`chrome_trace`](Self::chrome_trace)
+    pub chrome_trace: bool,
+    /// Print the `cargo::` instructions of the build, see [`cargo_instructions`](Self::cargo_instructions)
+    pub cargo_instructions: bool,
+    /// Only validate the shaders without writing any artifacts, see [`check`](Self::check)
+    pub check: bool,
+    /// Compare generated sources with the files on disk instead of rewriting them, see [`verify`](Self::verify)
+    pub verify: bool,
+    /// Where the artifacts are written, `None` to write them to disk, see [`artifact_sink`](Self::artifact_sink)
+    pub artifact_sink: Option<SharedSink>,
+    /// Shader packages from outside the shader dir, see [`add_package`](Self::add_package)
+    pub packages: Vec<ShaderPackage>,
+    /// The platform whose shader overrides are built, `None` for the one of cargo's `TARGET`, see [`platform`](Self::platform)
+    pub platform: Option<String>,
+    /// Where the lock of the build is written, see [`lock_file`](Self::lock_file)
+    pub lock_file: Option<PathBuf>,
+    /// Generated files and dirs whose hashes are locked, see [`lock_generated`](Self::lock_generated)
+    pub lock_generated: Vec<PathBuf>,
+    /// Where the diagnostics are written as JSON, see [`json_diagnostics`](Self::json_diagnostics)
+    pub json_diagnostics: Option<JsonOutput>,
+    /// The extensions of the sidecar files passed to extensions, see [`asset_extensions`](Self::asset_extensions)
+    pub asset_extensions: Vec<String>,
+    /// Write the built shaders to the output dir by their mangled names, see [`flat_output`](Self::flat_output)
+    pub flat_output: bool,
+    /// Globs of the files to build, empty for all of them, see [`include`](Self::include)
+    pub include: Vec<String>,
+    /// Globs of the files not to build, see [`exclude`](Self::exclude)
+    pub exclude: Vec<String>,
+    /// How long to wait for another build using the same output dirs, see [`lock_timeout`](Self::lock_timeout)
+    pub lock_timeout: Duration,
+    /// What to do when module paths differ only in case, see [`case_collisions`](Self::case_collisions)
+    pub case_collisions: CaseCollisions,
+    /// How the names of shader files and dirs become module names, see [`file_names`](Self::file_names)
+    pub file_names: FileNames,
+    /// Name the built shaders by the hash of their contents, see [`content_hashed_names`](Self::content_hashed_names)
+    pub content_hashed_names: bool,
+    /// The target triple the shaders are built for, `None` for cargo's `TARGET`, see [`target`](Self::target)
+    pub target: Option<String>,
+    /// The features and extensions of the builds for some targets, see [`for_target`](Self::for_target)
+    pub target_configs: Vec<TargetConfig>,
+    /// Dirs besides the output dir the artifact writer writes to, see [`artifact_root`](Self::artifact_root)
+    pub artifact_roots: Vec<PathBuf>,
+    /// Keep the artifact writer of extensions in the artifact roots, see [`sandbox_artifacts`](Self::sandbox_artifacts)
+    pub sandbox_artifacts: bool,
+    /// Warn about built shaders larger than this many bytes, see [`warn_artifact_larger_than`](Self::warn_artifact_larger_than)
+    pub warn_artifact_size: Option<u64>,
+}
+
+/// The WESL features and disabled extensions of the builds for the target triples matching a glob,
+/// see [`WeslBuildConfig::for_target`]
+///
+/// ## Example
+/// ```
+/// use wesl_build::TargetConfig;
+///
+/// // WebGPU has no f16, and the HLSL of DXC is never used on the web
+/// let web = TargetConfig::new("wasm32-*")
+///     .feature("f16", false)
+///     .disable_extension("ToolchainValidationExtension");
+/// assert!(web.matches("wasm32-unknown-unknown"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TargetConfig {
+    /// A glob of the target triples, `*` for any part, e.g. `wasm32-*` or `*-apple-*`
+    pub targets: String,
+    /// WESL features enabled or disabled for the targets, over the ones of [`WeslBuildConfig::compile_options`]
+    pub features: Vec<(String, bool)>,
+    /// The [names](`crate::extension::WeslBuildExtension::name`) of the extensions that don't run for the targets
+    pub disabled_extensions: Vec<String>,
+}
+
+impl TargetConfig {
+    /// The config of the target triples matching `targets`, with no features or disabled extensions
+    pub fn new(targets: impl Into<String>) -> Self {
+        Self { targets: targets.into(), features: Vec::new(), disabled_extensions: Vec::new() }
+    }
+
+    /// Enable or disable the WESL feature `name` for the targets
+    pub fn feature(mut self, name: impl Into<String>, enabled: bool) -> Self {
+        self.features.push((name.into(), enabled));
+        self
+    }
+
+    /// Leave the extension `name` out of the builds for the targets
+    pub fn disable_extension(mut self, name: impl Into<String>) -> Self {
+        self.disabled_extensions.push(name.into());
+        self
+    }
+
+    /// Whether the target triple `target` matches the glob of this config
+    pub fn matches(&self, target: &str) -> bool {
+        crate::walk::glob_match(&self.targets, target)
+    }
+}
+
+/// What a build does when the module paths of shaders or dirs differ only in case, see [`WeslBuildConfig::case_collisions`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CaseCollisions {
+    /// Build them, for shaders that are only ever built on case-sensitive filesystems
+    Allow,
+    /// Print a cargo warning
+    Warn,
+    /// Fail the build with [`WeslBuildError::CaseCollision`]
+    #[default]
+    Deny,
+}
+
+impl WeslBuildConfig {
+    /// The default [`max_depth`](Self::max_depth)
+    pub const DEFAULT_MAX_DEPTH: usize = 32;
+
+    /// The default config for the shaders in `shader_path`
+    pub fn new(shader_path: impl Into<String>) -> Self {
+        Self {
+            shader_path: shader_path.into(),
+            compile_options: CompileOptions::default(),
+            mangler: ManglerKind::Escape,
+            out_dir: None,
+            dry_run: false,
+            continue_on_error: false,
+            rerun_per_file: true,
+            rerun_if_changed: Vec::new(),
+            follow_symlinks: false,
+            max_depth: Self::DEFAULT_MAX_DEPTH,
+            profile: None,
+            timings: false,
+            chrome_trace: false,
+            cargo_instructions: true,
+            check: false,
+            verify: false,
+            artifact_sink: None,
+            packages: Vec::new(),
+            platform: None,
+            lock_file: None,
+            lock_generated: Vec::new(),
+            json_diagnostics: None,
+            asset_extensions: Vec::new(),
+            flat_output: false,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            lock_timeout: crate::dir_lock::DEFAULT_LOCK_TIMEOUT,
+            case_collisions: CaseCollisions::default(),
+            file_names: FileNames::default(),
+            content_hashed_names: false,
+            target: None,
+            target_configs: Vec::new(),
+            artifact_roots: Vec::new(),
+            sandbox_artifacts: true,
+            warn_artifact_size: None,
+        }
+    }
+
+    /// The config and extensions of the `[package.metadata.wesl_build]` section in the `Cargo.toml` of the crate,
+    /// so a build script is two lines
+    ///
+    /// Every key is optional:
+    /// ```toml
+    /// [package.metadata.wesl_build]
+    /// root = "src/shaders"               # relative to the crate
+    /// extensions = ["lint", "stats"]     # bindings, minify, lint, stats, format, graph or dead-code, in order
+    /// minify = true                      # adds the minifier after the other extensions
+    /// mangler = "escape"                 # escape, hash or none
+    /// features = { f16 = true }          # the WESL features of the shaders
+    /// asset_extensions = ["toml"]
+    /// continue_on_error = false
+    ///
+    /// [package.metadata.wesl_build.bindings] # adds the bindings before the other extensions
+    /// out = "src/shader_bindings"
+    /// header = "// generated by wesl_build"
+    /// entry_points = true                # and the other switches of `WgpuBindingsExtension`
+    /// ```
+    /// Unknown keys are errors, and the build reruns when the `Cargo.toml` changes.
+    /// Values are strings, booleans, arrays and tables, other TOML values are errors.
+    ///
+    /// ## Example
+    /// In `build.rs`:
+    /// ```no_run
+    /// fn main() {
+    ///     let (config, mut extensions) = wesl_build::WeslBuildConfig::from_cargo_metadata().unwrap();
+    ///     wesl_build::build_shader_dir_with_config(&config, &mut extensions).unwrap();
+    /// }
+    /// ```
+    pub fn from_cargo_metadata() -> Result<(Self, ExtensionSet), WeslBuildError> {
+        let manifest_dir = std::env::var_os("CARGO_MANIFEST_DIR").map(PathBuf::from).unwrap_or_default();
+        Self::from_cargo_toml(manifest_dir.join("Cargo.toml"))
+    }
+
+    /// The config and extensions of the `[package.metadata.wesl_build]` section of the `Cargo.toml` at `manifest_path`,
+    /// see [`from_cargo_metadata`](Self::from_cargo_metadata)
+    pub fn from_cargo_toml(manifest_path: impl AsRef<Path>) -> Result<(Self, ExtensionSet), WeslBuildError> {
+        let manifest_path = manifest_path.as_ref();
+        let package_dir = manifest_path.parent().unwrap_or(Path::new(""));
+        let toml = std::fs::read_to_string(manifest_path)?;
+
+        let cargo_toml = CargoToml::parse(&toml);
+
+        let package = cargo_toml.as_ref().ok()
+            .and_then(CargoToml::package_name)
+            .map(str::to_owned)
+            .or_else(|| package_dir.file_name().map(|name| name.to_string_lossy().into_owned()))
+            .unwrap_or_default();
+        let table = cargo_toml.and_then(|cargo_toml| cargo_toml.section())
+            .map_err(|message| WeslBuildError::MetadataConfigErr { package: package.clone(), message })?
+            .unwrap_or_default();
+
+        let section = MetadataSection { package: &package, package_dir, table: &table };
+        Ok((section.config()?, section.extensions(None)?))
+    }
+
+    /// The builds of a `wesl_build.toml` config file, one per root, with their extensions
+    ///
+    /// Paths are relative to the file and every key is optional:
+    /// ```toml
+    /// root = "src/shaders"                 # or ["src/shaders", "assets/shaders"]
+    /// out = "target/shaders"               # OUT_DIR by default
+    /// include = ["**/*.wesl"]              # see `WeslBuildConfig::include`
+    /// exclude = ["**/test_*"]
+    /// mangler = "escape"                   # escape, hash or none
+    /// extensions = ["lint", "dead-code"]   # bindings, minify, lint, stats, format, graph or dead-code, in order
+    /// asset_extensions = ["toml"]
+    /// continue_on_error = false
+    ///
+    /// [features]
+    /// f16 = true
+    ///
+    /// [bindings]                           # adds the bindings before the other extensions
+    /// out = "src/shader_bindings"
+    /// matrix_vector_types = "glam"         # rust, glam or nalgebra
+    /// derive_serde = true
+    /// entry_points = true                  # and the other switches of `WgpuBindingsExtension`
+    ///
+    /// [minify]                             # adds the minifier after the other extensions
+    /// when = "release_only"                # always, release_only, { profiles = [..] } or { env_var = ".." }
+    /// preserve_entry_points = true
+    /// preserve_patterns = ["^debug_"]
+    /// ```
+    /// With several roots each one is built to a dir of the output dir named after it, as are its bindings.
+    /// Errors point to the line and column of the invalid value, and the build reruns when the file changes.
+    ///
+    /// ## Example
+    /// In `build.rs`:
+    /// ```no_run
+    /// # fn main() -> Result<(), wesl_build::WeslBuildError> {
+    /// for (config, mut extensions) in wesl_build::WeslBuildConfig::from_file("wesl_build.toml")? {
+    ///     wesl_build::build_shader_dir_with_config(&config, &mut extensions)?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "config_file")]
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Vec<(Self, ExtensionSet)>, WeslBuildError> {
+        crate::config_file::builds(path.as_ref())
+    }
+
+    /// Set the options passed to the wesl compiler
+    pub fn compile_options(mut self, compile_options: CompileOptions) -> Self {
+        self.compile_options = compile_options;
+        self
+    }
+
+    /// Write the built shaders as `<output dir>/<mangled name>.wgsl` instead of at their module paths
+    /// in [`OUTPUT_DIR_NAME`](`crate::OUTPUT_DIR_NAME`), e.g. `wesl/passes/blur.wgsl`
+    ///
+    /// The flat layout is the one of older versions, for build scripts and tools that read the built shaders by their
+    /// mangled names. `include_wesl!` finds the shaders in either layout, only the tree layout has an
+    /// [index](`crate::OUTPUT_INDEX_NAME`).
+    pub fn flat_output(mut self, flat_output: bool) -> Self {
+        self.flat_output = flat_output;
+        self
+    }
+
+    /// Also write every built shader to [`HASHED_DIR_NAME`](`crate::HASHED_DIR_NAME`) named by the hash of its final contents,
+    /// e.g. `hashed/5f0c6a1be3d2e8a4.wgsl`, for web deployments that cache files by name
+    ///
+    /// The shaders are hashed once every extension ran, the [`BuildPlan`](`crate::BuildPlan`), index and `include_wesl!`
+    /// use the hashed files. A lookup table from module path to file is written as rust to
+    /// [`HASHED_TABLE_NAME`](`crate::HASHED_TABLE_NAME`) in the output dir and as JSON to
+    /// [`HASHED_JSON_NAME`](`crate::HASHED_JSON_NAME`) next to the hashed files.
+    /// The shaders are still written to their module paths, as extensions see and may include them there.
+    pub fn content_hashed_names(mut self, content_hashed_names: bool) -> Self {
+        self.content_hashed_names = content_hashed_names;
+        self
+    }
+
+    /// Let the [`ArtifactWriter`](`crate::artifacts::ArtifactWriter`) of extensions write in `dir` as well as in the output dir,
+    /// e.g. a dir of reports in the crate, can be called for several dirs
+    pub fn artifact_root(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.artifact_roots.push(dir.into());
+        self
+    }
+
+    /// Whether the [`ArtifactWriter`](`crate::artifacts::ArtifactWriter`) of extensions fails on paths outside of the
+    /// output dir, the [artifact roots](Self::artifact_root) and the roots extensions declare, defaults to `true`
+    ///
+    /// Turn it off for extensions that write anywhere on purpose.
+    pub fn sandbox_artifacts(mut self, sandbox_artifacts: bool) -> Self {
+        self.sandbox_artifacts = sandbox_artifacts;
+        self
+    }
+
+    /// Print a cargo warning naming every built shader larger than `bytes`, as a huge shader usually means an import exploded
+    ///
+    /// The sizes are of the final shaders, after every extension ran. For limits that fail the build see the
+    /// `ShaderBudgetExtension`.
+    pub fn warn_artifact_larger_than(mut self, bytes: u64) -> Self {
+        self.warn_artifact_size = Some(bytes);
+        self
+    }
+
+    /// Set the mangler, defaults to [`ManglerKind::Escape`]
+    ///
+    /// Note: `Hash` and `None` manglers can't be demangled,
+    /// so items in `WgpuBindingsExtension` bindings will all be in the root module
+    pub fn mangler(mut self, mangler: ManglerKind) -> Self {
+        self.mangler = mangler;
+        self
+    }
+
+    /// Write the built shaders, and the files of extensions, to `out_dir` instead of cargo's `OUT_DIR`
+    pub fn out_dir(mut self, out_dir: impl Into<PathBuf>) -> Self {
+        self.out_dir = Some(out_dir.into());
+        self
+    }
+
+    /// Walk the shader tree and compute the module paths and output locations without building or writing anything
+    ///
+    /// Only the [`would_build`](`crate::extension::WeslBuildExtension::would_build`) hook of extensions is called,
+    /// the plan is returned by [`build_shader_dir_with_config`](`crate::build_shader_dir_with_config`)
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Build every shader it can instead of stopping at the first error,
+    /// all errors are returned together as [`WeslBuildError::Multiple`](`crate::WeslBuildError::Multiple`)
+    pub fn continue_on_error(mut self, continue_on_error: bool) -> Self {
+        self.continue_on_error = continue_on_error;
+        self
+    }
+
+    /// Whether to emit a `cargo::rerun-if-changed` line for every shader file, defaults to `true`
+    ///
+    /// The shader dir itself is always tracked, which already catches changes to the files in it.
+    /// When `false` only imports from outside the shader dir get their own line,
+    /// and a hash of all shader sources is written to the build manifest and `WESL_BUILD_SHADERS_HASH`.
+    pub fn rerun_per_file(mut self, rerun_per_file: bool) -> Self {
+        self.rerun_per_file = rerun_per_file;
+        self
+    }
+
+    /// Also rerun the build script when `path` changes, e.g. a config file or an asset the shaders depend on
+    ///
+    /// `wesl.toml` and `wesl_build.toml` in the crate dir are tracked automatically when they exist
+    pub fn rerun_if_changed(mut self, path: impl Into<PathBuf>) -> Self {
+        self.rerun_if_changed.push(path.into());
+        self
+    }
+
+    /// Walk into symlinked dirs in the shader dir, defaults to `false`
+    ///
+    /// Symlinks that point back up the tree fail the build with [`WeslBuildError::SymlinkCycle`](`crate::WeslBuildError::SymlinkCycle`),
+    /// symlinked shader files are always built.
+    pub fn follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    /// Fail the build with [`WeslBuildError::MaxDepthExceeded`](`crate::WeslBuildError::MaxDepthExceeded`)
+    /// when dirs are nested deeper than `max_depth` below the shader dir, defaults to [`Self::DEFAULT_MAX_DEPTH`]
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Set the build profile extensions see with [`current_profile`], instead of cargo's `PROFILE` env var
+    ///
+    /// Useful with custom profiles, `PROFILE` is only ever `debug` or `release`
+    pub fn profile(mut self, profile: impl Into<String>) -> Self {
+        self.profile = Some(profile.into());
+        self
+    }
+
+    /// Build the `<name>.<platform>.wesl` overrides of shaders for `platform` instead of the platform of cargo's `TARGET`,
+    /// see [`target_platform`](`crate::target_platform`)
+    ///
+    /// An override like `blur.metal.wesl` replaces `blur.wesl` as the source of the module `blur`,
+    /// imports and `include_wesl!("blur")` get the override. Overrides for other platforms are ignored.
+    pub fn platform(mut self, platform: impl Into<String>) -> Self {
+        self.platform = Some(platform.into());
+        self
+    }
+
+    /// Build the shaders for the target triple `target` instead of cargo's `TARGET`, see [`build_target`](Self::build_target)
+    ///
+    /// The target selects the [platform](Self::platform) when it isn't set and the [target configs](Self::for_target),
+    /// extensions get it with [`BuildContext::target`](`crate::BuildContext::target`).
+    pub fn target(mut self, target: impl Into<String>) -> Self {
+        self.target = Some(target.into());
+        self
+    }
+
+    /// Enable or disable WESL features and leave extensions out of the builds for the targets of `target_config`,
+    /// e.g. no `f16` and no DXC validation on `wasm32-*`
+    ///
+    /// Every config matching the [`build_target`](Self::build_target) applies, in the order they were added,
+    /// so a later config overrides the features of an earlier one.
+    ///
+    /// ## Example
+    /// ```
+    /// use wesl_build::{TargetConfig, WeslBuildConfig};
+    ///
+    /// let config = WeslBuildConfig::new("src/shaders")
+    ///     .for_target(TargetConfig::new("wasm32-*").feature("f16", false))
+    ///     .target("wasm32-unknown-unknown");
+    /// assert_eq!(config.disabled_extensions(), Vec::<&str>::new());
+    /// ```
+    pub fn for_target(mut self, target_config: TargetConfig) -> Self {
+        self.target_configs.push(target_config);
+        self
+    }
+
+    /// Build as usual, but extensions that generate sources into your crate, like bindings, compare them with the files
+    /// on disk and fail with a diff instead of rewriting them, see [`is_verify`]
+    ///
+    /// Also on when the `WESL_BUILD_VERIFY` env var is `1`, e.g. in CI, see [`build_verify`](Self::build_verify).
+    pub fn verify(mut self, verify: bool) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Write the [`BuildLock`](`crate::lock::BuildLock`) of the build to `lock_file`, e.g. [`LOCK_FILE_NAME`](`crate::LOCK_FILE_NAME`)
+    /// in your crate dir, with the versions, features and mangler it was built with and the hashes of its inputs and outputs
+    ///
+    /// A [check](Self::check) compares the lock of the build to `lock_file` instead, see [`verify_shader_dir`](`crate::verify_shader_dir`).
+    pub fn lock_file(mut self, lock_file: impl Into<PathBuf>) -> Self {
+        self.lock_file = Some(lock_file.into());
+        self
+    }
+
+    /// How long the build waits for another build writing the same output dir or bindings, 2 minutes by default
+    ///
+    /// Cargo can run the build script of a crate twice at once, e.g. for rust-analyzer and a `cargo build`, so the build
+    /// locks [`DIR_LOCK_NAME`](`crate::DIR_LOCK_NAME`) in the output dir while it runs, and reports that it is waiting.
+    /// Fails with [`LockTimeout`](`crate::WeslBuildError::LockTimeout`) if the other build is still running after `lock_timeout`.
+    pub fn lock_timeout(mut self, lock_timeout: Duration) -> Self {
+        self.lock_timeout = lock_timeout;
+        self
+    }
+
+    /// What to do when the module paths of shaders or dirs differ only in case, e.g. `Blur.wesl` and `blur.wesl`,
+    /// defaults to [`CaseCollisions::Deny`]
+    ///
+    /// They build on case-sensitive filesystems like Linux CI, but on macOS and Windows the files can't both exist
+    /// and their outputs and bindings overwrite each other.
+    pub fn case_collisions(mut self, case_collisions: CaseCollisions) -> Self {
+        self.case_collisions = case_collisions;
+        self
+    }
+
+    /// How the names of shader files and dirs that aren't identifiers, like `blur-pass.wesl` or `光.wesl`,
+    /// become module names, defaults to [`FileNames::Verbatim`]
+    ///
+    /// With [`FileNames::Normalize`] `post-fx/blur-pass.wesl` is the module `post_fx::blur_pass`, imported,
+    /// built to `wesl/post_fx/blur_pass.wgsl`, in the bindings and included with `include_wesl!(post_fx::blur_pass)`
+    /// as such. [`FileNames::Reject`] fails the build instead, to keep the names of the files and modules the same.
+    pub fn file_names(mut self, file_names: FileNames) -> Self {
+        self.file_names = file_names;
+        self
+    }
+
+    /// Lock the hashes of the file or every file in the dir `path`, e.g. bindings generated into your crate,
+    /// so [`verify_shader_dir`](`crate::verify_shader_dir`) fails when they were edited or not regenerated
+    pub fn lock_generated(mut self, path: impl Into<PathBuf>) -> Self {
+        self.lock_generated.push(path.into());
+        self
+    }
+
+    /// Write every error of the build and the warnings of extensions to `output` as newline-delimited JSON,
+    /// with the file, span, severity, message and module of each, for editors and other tools, see [`messages`](`crate::messages`)
+    ///
+    /// Warnings are written instead of printed as `cargo::warning`s, errors are still returned.
+    pub fn json_diagnostics(mut self, output: JsonOutput) -> Self {
+        self.json_diagnostics = Some(output);
+        self
+    }
+
+    /// Pass the files in the shader dir with these extensions, e.g. `["json"]`, to the
+    /// [`on_asset`](`crate::extension::WeslBuildExtension::on_asset`) hook of extensions,
+    /// so they can read sidecar data like the render state of the shader next to it, other files are skipped.
+    /// The assets of a dir are passed before its shaders are built.
+    ///
+    /// With [`rerun_per_file`](Self::rerun_per_file) each asset gets a `rerun-if-changed` line, otherwise
+    /// they are in the hash of the shader sources.
+    pub fn asset_extensions(mut self, asset_extensions: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.asset_extensions = asset_extensions.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Only build the shaders whose path in the shader dir matches one of these globs, e.g. `["passes/**", "*.wesl"]`
+    ///
+    /// `*` and `?` match within a file or dir name and `**` any number of dirs, `/` and `\\` both separate dirs.
+    /// Shaders that aren't built can still be imported.
+    pub fn include(mut self, include: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.include = include.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Don't build the shaders whose path in the shader dir matches one of these globs, e.g. `["**/test_*"]`,
+    /// even if they are [included](Self::include)
+    pub fn exclude(mut self, exclude: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.exclude = exclude.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Whether the file at `path` in the shader dir is built, see [`include`](Self::include) and [`exclude`](Self::exclude)
+    pub fn builds_file(&self, path: &str) -> bool {
+        crate::walk::builds_file(&self.include, &self.exclude, path)
+    }
+
+    /// Measure how long each module takes to compile and each extension hook takes to run,
+    /// the slowest first in a table written to [`TIMINGS_FILE_NAME`](`crate::TIMINGS_FILE_NAME`) in the output dir
+    pub fn timings(mut self, timings: bool) -> Self {
+        self.timings = timings;
+        self
+    }
+
+    /// Write the same measurements as [`timings`](Self::timings) as chrome tracing JSON
+    /// to [`TIMINGS_TRACE_FILE_NAME`](`crate::TIMINGS_TRACE_FILE_NAME`) in the output dir
+    pub fn chrome_trace(mut self, chrome_trace: bool) -> Self {
+        self.chrome_trace = chrome_trace;
+        self
+    }
+
+    /// Whether to print the `cargo::rerun-if-changed` and `cargo::rustc-env` instructions, defaults to `true`
+    ///
+    /// Turn this off when building outside of a build script, extensions may still print their own instructions
+    pub fn cargo_instructions(mut self, cargo_instructions: bool) -> Self {
+        self.cargo_instructions = cargo_instructions;
+        self
+    }
+
+    /// Compile every shader and run all extensions, but write nothing to the output dir or your sources
+    ///
+    /// The artifacts are kept in a [`MemorySink`](`crate::artifacts::MemorySink`) that is dropped after the build,
+    /// extensions see [`is_check`] and only run their validation.
+    /// No `cargo::` instructions, build manifest or timings are written.
+    pub fn check(mut self, check: bool) -> Self {
+        self.check = check;
+        self
+    }
+
+    /// Write the built shaders and the files of extensions to `sink` instead of to disk,
+    /// e.g. a [`MemorySink`](`crate::artifacts::MemorySink`) for tests or a custom sink for packaging
+    ///
+    /// The paths are the same as they would be on disk, see [`artifacts`](`crate::artifacts`)
+    pub fn artifact_sink(mut self, sink: SharedSink) -> Self {
+        self.artifact_sink = Some(sink);
+        self
+    }
+
+    /// Let shaders import the package `name` from `source`, e.g. `import util::color::luma;`
+    ///
+    /// `source` is a dir of shaders or [`PackageSource::links`] for the shaders a dependency exported with
+    /// [`export_shader_package`](`crate::package::export_shader_package`), the build reruns when the package changes.
+    pub fn add_package(mut self, name: impl Into<String>, source: impl Into<PackageSource>) -> Self {
+        self.packages.push(ShaderPackage { name: name.into(), source: source.into() });
+        self
+    }
+
+    /// The build profile, [`profile`](Self::profile) if set otherwise cargo's `PROFILE` env var
+    pub fn build_profile(&self) -> Option<String> {
+        self.profile.clone().or_else(|| std::env::var("PROFILE").ok())
+    }
+
+    /// The platform whose shader overrides are built, [`platform`](Self::platform)
+    /// or the [`target_platform`](`crate::target_platform`) of the [`build_target`](Self::build_target)
+    pub fn build_platform(&self) -> Option<String> {
+        self.platform.clone()
+            .or_else(|| self.build_target().and_then(|target| crate::target_platform(&target)).map(str::to_owned))
+    }
+
+    /// The target triple the shaders are built for, [`target`](Self::target) if set otherwise cargo's `TARGET` env var
+    pub fn build_target(&self) -> Option<String> {
+        self.target.clone().or_else(|| std::env::var("TARGET").ok())
+    }
+
+    /// The [target configs](Self::for_target) matching the [`build_target`](Self::build_target)
+    pub fn matching_target_configs(&self) -> impl Iterator<Item = &TargetConfig> {
+        let target = self.build_target();
+        self.target_configs.iter()
+            .filter(move |target_config| target.as_deref().is_some_and(|target| target_config.matches(target)))
+    }
+
+    /// The extensions the [target configs](Self::for_target) of the [`build_target`](Self::build_target) leave out
+    pub fn disabled_extensions(&self) -> Vec<&str> {
+        self.matching_target_configs()
+            .flat_map(|target_config| target_config.disabled_extensions.iter().map(String::as_str))
+            .collect()
+    }
+
+    /// This config with the features of the [target configs](Self::for_target) of the [`build_target`](Self::build_target)
+    /// in its compile options, `None` if no target config has features for it
+    pub(crate) fn with_target_features(&self) -> Option<Self> {
+        let features = self.matching_target_configs()
+            .flat_map(|target_config| &target_config.features)
+            .collect::<Vec<_>>();
+        if features.is_empty() {
+            return None;
+        }
+
+        let mut config = self.clone();
+        for (name, enabled) in features {
+            config.compile_options.features.flags.insert(
+                name.clone(),
+                if *enabled { wesl::Feature::Enable } else { wesl::Feature::Disable },
+            );
+        }
+        Some(config)
+    }
+
+    /// Whether generated sources are verified, [`verify`](Self::verify) or the `WESL_BUILD_VERIFY` env var set to `1`
+    pub fn build_verify(&self) -> bool {
+        self.verify || std::env::var("WESL_BUILD_VERIFY").is_ok_and(|verify| verify == "1")
+    }
+
+    /// The dir the built shaders are written to, `None` if neither `out_dir` nor `OUT_DIR` is set
+    pub fn output_dir(&self) -> Option<PathBuf> {
+        self.out_dir.clone().or_else(|| std::env::var_os("OUT_DIR").map(PathBuf::from))
+    }
+}
+
+/// The profile of the build running on this thread, for extensions that only run in some builds
+///
+/// This is the [`WeslBuildConfig::profile`] of the build if set, otherwise cargo's `PROFILE` env var
+pub fn current_profile() -> Option<String> {
+    try_with_build_context(|ctx| ctx.profile()).unwrap_or_else(|_| std::env::var("PROFILE").ok())
+}
+
+/// Whether the build running on this thread is a [check](WeslBuildConfig::check),
+/// extensions should validate the shaders but not write any files
+pub fn is_check() -> bool {
+    try_with_build_context(|ctx| ctx.config().check).unwrap_or(false)
+}
+
+/// Whether the build running on this thread [verifies](WeslBuildConfig::verify) generated sources,
+/// extensions should compare the sources they generate into your crate with the files on disk instead of writing them
+pub fn is_verify() -> bool {
+    try_with_build_context(|ctx| ctx.config().build_verify()).unwrap_or(false)
+}
+
+/// The dir extensions write their own files to in the build running on this thread
+///
+/// This is the [`WeslBuildConfig::out_dir`] of the build if set, otherwise cargo's `OUT_DIR`
+pub fn build_out_dir() -> Result<PathBuf, WeslBuildError> {
+    try_with_build_context(|ctx| ctx.out_dir())?.ok_or(WeslBuildError::MissingOutDir)
+}
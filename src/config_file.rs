@@ -0,0 +1,370 @@
+//! The `wesl_build.toml` config file, see [`WeslBuildConfig::from_file`]
+
+use std::{collections::BTreeMap, path::Path};
+
+use serde::Deserialize;
+use toml::Spanned;
+use wesl::ManglerKind;
+
+use crate::{WeslBuildConfig, WeslBuildError};
+use crate::extension::{BoxedExtension, ExtensionSet};
+
+/// The root table of the file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    #[serde(default)]
+    root: Roots,
+    /// Where the built shaders are written, relative to the file
+    out: Option<String>,
+    #[serde(default)]
+    include: Vec<String>,
+    #[serde(default)]
+    exclude: Vec<String>,
+    #[serde(default)]
+    mangler: Mangler,
+    #[serde(default)]
+    features: BTreeMap<String, bool>,
+    #[serde(default)]
+    extensions: Vec<ExtensionName>,
+    #[serde(default)]
+    asset_extensions: Vec<String>,
+    #[serde(default)]
+    continue_on_error: bool,
+    bindings: Option<BindingsConfig>,
+    minify: Option<MinifyConfig>,
+}
+
+/// `root = "src/shaders"` or `root = ["src/shaders", "assets/shaders"]`
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Roots {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Default for Roots {
+    fn default() -> Self {
+        Roots::One("src/shaders".to_owned())
+    }
+}
+
+impl Roots {
+    fn as_slice(&self) -> &[String] {
+        match self {
+            Roots::One(root) => std::slice::from_ref(root),
+            Roots::Many(roots) => roots,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum Mangler {
+    #[default]
+    Escape,
+    Hash,
+    None,
+}
+
+impl From<Mangler> for ManglerKind {
+    fn from(mangler: Mangler) -> Self {
+        match mangler {
+            Mangler::Escape => ManglerKind::Escape,
+            Mangler::Hash => ManglerKind::Hash,
+            Mangler::None => ManglerKind::None,
+        }
+    }
+}
+
+/// The extensions without options, the bindings and minifier are added by their tables
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum ExtensionName {
+    #[cfg(feature = "wgpu_bindings_ext")]
+    Bindings,
+    #[cfg(feature = "wgsl_minifier_ext")]
+    Minify,
+    #[cfg(feature = "wesl_lint_ext")]
+    Lint,
+    #[cfg(feature = "shader_stats_ext")]
+    Stats,
+    #[cfg(feature = "wgsl_formatter_ext")]
+    Format,
+    #[cfg(feature = "dependency_graph_ext")]
+    Graph,
+    #[cfg(feature = "dead_code_report_ext")]
+    DeadCode,
+}
+
+impl ExtensionName {
+    fn as_str(self) -> &'static str {
+        match self {
+            #[cfg(feature = "wgpu_bindings_ext")]
+            ExtensionName::Bindings => "bindings",
+            #[cfg(feature = "wgsl_minifier_ext")]
+            ExtensionName::Minify => "minify",
+            #[cfg(feature = "wesl_lint_ext")]
+            ExtensionName::Lint => "lint",
+            #[cfg(feature = "shader_stats_ext")]
+            ExtensionName::Stats => "stats",
+            #[cfg(feature = "wgsl_formatter_ext")]
+            ExtensionName::Format => "format",
+            #[cfg(feature = "dependency_graph_ext")]
+            ExtensionName::Graph => "graph",
+            #[cfg(feature = "dead_code_report_ext")]
+            ExtensionName::DeadCode => "dead-code",
+        }
+    }
+}
+
+/// The `[bindings]` table, the options of [`WgpuBindingsExtension`](`crate::extension::wgpu_bindings::WgpuBindingsExtension`)
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[cfg_attr(not(feature = "wgpu_bindings_ext"), allow(dead_code))]
+struct BindingsConfig {
+    out: Option<String>,
+    header: Option<String>,
+    /// `rust`, `glam` or `nalgebra`
+    matrix_vector_types: Option<MatrixVectorTypes>,
+    derive_serde: Option<bool>,
+    entry_points: Option<bool>,
+    layout_assertions: Option<bool>,
+    binding_consts: Option<bool>,
+    workgroup_sizes: Option<bool>,
+    shared_layouts: Option<bool>,
+    vertex_layouts: Option<bool>,
+    pipeline_helpers: Option<bool>,
+    pipeline_state: Option<bool>,
+    unchecked_shader_module: Option<bool>,
+    #[cfg(feature = "hot_reload")]
+    hot_reload: Option<bool>,
+    stubs: Option<bool>,
+    format_output: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[cfg_attr(not(feature = "wgpu_bindings_ext"), allow(dead_code))]
+enum MatrixVectorTypes {
+    Rust,
+    Glam,
+    Nalgebra,
+}
+
+/// The `[minify]` table, the options of [`WgslMinifierExtension`](`crate::extension::wgsl_minifier::WgslMinifierExtension`)
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[cfg_attr(not(feature = "wgsl_minifier_ext"), allow(dead_code))]
+struct MinifyConfig {
+    #[serde(default)]
+    when: MinifyWhen,
+    #[serde(default)]
+    preserve_entry_points: bool,
+    #[serde(default)]
+    preserve_overrides: bool,
+    #[serde(default)]
+    preserve: Vec<String>,
+    /// Regexes, spanned so an invalid one points to itself
+    #[serde(default)]
+    preserve_patterns: Vec<Spanned<String>>,
+    #[serde(default)]
+    name_map: bool,
+    #[serde(default)]
+    stats: bool,
+}
+
+/// `"always"`, `"release_only"`, `{ profiles = ["release"] }` or `{ env_var = "MINIFY" }`
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(not(feature = "wgsl_minifier_ext"), allow(dead_code))]
+enum MinifyWhen {
+    #[default]
+    Always,
+    ReleaseOnly,
+    Profiles(Vec<String>),
+    EnvVar(String),
+}
+
+/// The builds of the config file at `path`, one per root
+pub(crate) fn builds(path: &Path) -> Result<Vec<(WeslBuildConfig, ExtensionSet)>, WeslBuildError> {
+    let source = std::fs::read_to_string(path)?;
+    let error = |message: String| WeslBuildError::ConfigFileErr { path: path.to_owned(), message };
+    let file = toml::from_str::<ConfigFile>(&source).map_err(|toml_error| error(toml_error.to_string()))?;
+
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let roots = file.root.as_slice();
+    let mut builds = Vec::with_capacity(roots.len());
+    for root in roots {
+        let shader_path = dir.join(root);
+        let shader_path = shader_path.to_str().ok_or_else(|| WeslBuildError::NonUtf8Path { path: shader_path.clone() })?;
+        // with several roots each one is built to a dir of its own, named after it
+        let sub_dir = match roots.len() {
+            1 => None,
+            _ => Some(Path::new(root).file_name().map_or(Path::new(root), Path::new)),
+        };
+
+        let mut config = WeslBuildConfig::new(shader_path)
+            .mangler(file.mangler.into())
+            .include(file.include.iter().cloned())
+            .exclude(file.exclude.iter().cloned())
+            .asset_extensions(file.asset_extensions.iter().cloned())
+            .continue_on_error(file.continue_on_error)
+            .rerun_if_changed(path);
+        let out_dir = match (&file.out, sub_dir) {
+            (Some(out), None) => Some(dir.join(out)),
+            (Some(out), Some(sub_dir)) => Some(dir.join(out).join(sub_dir)),
+            (None, Some(sub_dir)) => Some(config.output_dir().ok_or(WeslBuildError::MissingOutDir)?.join(sub_dir)),
+            (None, None) => None,
+        };
+        if let Some(out_dir) = &out_dir {
+            config = config.out_dir(out_dir);
+        }
+        for (name, enabled) in &file.features {
+            config.compile_options.features.flags.insert(
+                name.clone(),
+                if *enabled { wesl::Feature::Enable } else { wesl::Feature::Disable },
+            );
+        }
+
+        let extensions = extensions(&file, dir, sub_dir, out_dir.as_deref(), &source).map_err(error)?;
+        builds.push((config, extensions));
+    }
+
+    Ok(builds)
+}
+
+/// The extensions of `extensions` in order, with the bindings first and the minifier last if only their table names them
+// the paths and source are only used by the extensions with options, which are behind features
+#[allow(unused_variables)]
+fn extensions(
+    file: &ConfigFile,
+    dir: &Path,
+    sub_dir: Option<&Path>,
+    out_dir: Option<&Path>,
+    source: &str,
+) -> Result<ExtensionSet, String> {
+    #[cfg(not(feature = "wgpu_bindings_ext"))]
+    if file.bindings.is_some() {
+        return Err("`[bindings]` needs the `wgpu_bindings_ext` feature of wesl_build".to_owned());
+    }
+    #[cfg(not(feature = "wgsl_minifier_ext"))]
+    if file.minify.is_some() {
+        return Err("`[minify]` needs the `wgsl_minifier_ext` feature of wesl_build".to_owned());
+    }
+
+    let mut names = file.extensions.clone();
+    #[cfg(feature = "wgpu_bindings_ext")]
+    if file.bindings.is_some() && !names.contains(&ExtensionName::Bindings) {
+        names.insert(0, ExtensionName::Bindings);
+    }
+    #[cfg(feature = "wgsl_minifier_ext")]
+    if file.minify.is_some() && !names.contains(&ExtensionName::Minify) {
+        names.push(ExtensionName::Minify);
+    }
+
+    let mut extensions = ExtensionSet::new();
+    for name in names {
+        let extension: BoxedExtension = match name {
+            #[cfg(feature = "wgpu_bindings_ext")]
+            ExtensionName::Bindings => bindings_extension(file.bindings.as_ref(), dir, sub_dir)?,
+            #[cfg(feature = "wgsl_minifier_ext")]
+            ExtensionName::Minify => minify_extension(file.minify.as_ref(), source)?,
+            name => crate::metadata::builtin_extension(name.as_str(), out_dir)
+                .expect("the extension names are built-in extensions"),
+        };
+        extensions.insert(extension);
+    }
+
+    Ok(extensions)
+}
+
+#[cfg(feature = "wgpu_bindings_ext")]
+fn bindings_extension(bindings: Option<&BindingsConfig>, dir: &Path, sub_dir: Option<&Path>) -> Result<BoxedExtension, String> {
+    use std::{fs::File, io::BufWriter};
+
+    use crate::extension::wgpu_bindings::{self, WgpuBindingsExtension};
+
+    type Bindings = WgpuBindingsExtension<BufWriter<File>>;
+
+    let default = BindingsConfig::default();
+    let options = bindings.unwrap_or(&default);
+    let out = dir.join(options.out.as_deref().unwrap_or("src/shader_bindings"));
+    let out = match sub_dir {
+        Some(sub_dir) => out.join(sub_dir),
+        None => out,
+    };
+    let mut bindings = Bindings::new(out).map_err(|error| error.to_string())?;
+
+    if let Some(header) = &options.header {
+        bindings = bindings.with_header(header.as_str());
+    }
+    if let Some(types) = options.matrix_vector_types {
+        bindings = bindings.with_matrix_vector_types(match types {
+            MatrixVectorTypes::Rust => wgpu_bindings::MatrixVectorTypes::Rust,
+            MatrixVectorTypes::Glam => wgpu_bindings::MatrixVectorTypes::Glam,
+            MatrixVectorTypes::Nalgebra => wgpu_bindings::MatrixVectorTypes::Nalgebra,
+        });
+    }
+    let switches: &[(Option<bool>, fn(Bindings, bool) -> Bindings)] = &[
+        (options.derive_serde, Bindings::with_derive_serde),
+        (options.entry_points, Bindings::with_entry_points),
+        (options.layout_assertions, Bindings::with_layout_assertions),
+        (options.binding_consts, Bindings::with_binding_consts),
+        (options.workgroup_sizes, Bindings::with_workgroup_sizes),
+        (options.shared_layouts, Bindings::with_shared_layouts),
+        (options.vertex_layouts, Bindings::with_vertex_layouts),
+        (options.pipeline_helpers, Bindings::generate_pipeline_helpers),
+        (options.pipeline_state, Bindings::with_pipeline_state),
+        (options.unchecked_shader_module, Bindings::with_unchecked_shader_module),
+        #[cfg(feature = "hot_reload")]
+        (options.hot_reload, Bindings::with_hot_reload),
+        (options.stubs, Bindings::generate_stubs),
+        (options.format_output, Bindings::format_output),
+    ];
+    for (enabled, switch) in switches {
+        if let Some(enabled) = enabled {
+            bindings = switch(bindings, *enabled);
+        }
+    }
+
+    Ok(Box::new(bindings))
+}
+
+#[cfg(feature = "wgsl_minifier_ext")]
+fn minify_extension(minify: Option<&MinifyConfig>, source: &str) -> Result<BoxedExtension, String> {
+    use crate::extension::wgsl_minifier::{self, WgslMinifierExtension};
+
+    let default = MinifyConfig::default();
+    let options = minify.unwrap_or(&default);
+    let mut minifier = WgslMinifierExtension::new()
+        .with_when(match &options.when {
+            MinifyWhen::Always => wgsl_minifier::MinifyWhen::Always,
+            MinifyWhen::ReleaseOnly => wgsl_minifier::MinifyWhen::ReleaseOnly,
+            MinifyWhen::Profiles(profiles) => wgsl_minifier::MinifyWhen::Profiles(profiles.clone()),
+            MinifyWhen::EnvVar(var) => wgsl_minifier::MinifyWhen::EnvVar(var.clone()),
+        })
+        .with_preserve_entry_points(options.preserve_entry_points)
+        .with_preserve_overrides(options.preserve_overrides)
+        .with_name_map(options.name_map)
+        .with_stats(options.stats);
+    for name in &options.preserve {
+        minifier = minifier.with_preserve(name.as_str());
+    }
+    for pattern in &options.preserve_patterns {
+        let regex = regex::Regex::new(pattern.get_ref())
+            .map_err(|error| format!("{}: invalid `preserve_patterns` regex: {error}", location(source, pattern.span())))?;
+        minifier = minifier.with_preserve_pattern(regex);
+    }
+
+    Ok(Box::new(minifier))
+}
+
+/// `line <line>, column <column>` of the start of `span` in `source`, both from 1 like the errors of `toml`
+#[cfg(feature = "wgsl_minifier_ext")]
+fn location(source: &str, span: std::ops::Range<usize>) -> String {
+    let before = &source[..span.start];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or_default().chars().count() + 1;
+    format!("line {line}, column {column}")
+}
@@ -0,0 +1,79 @@
+//! Built shaders named by the hash of their contents, see [`WeslBuildConfig::content_hashed_names`]
+
+use std::fmt::Write as _;
+use std::path::Path;
+
+use itertools::Itertools;
+
+use crate::{BuildPlan, WeslBuildError, artifact_path, artifacts, messages::json_string};
+
+/// The dir of the output dir the content hashed shaders are written to, e.g. `hashed/5f0c6a1be3d2e8a4.wgsl`
+pub const HASHED_DIR_NAME: &str = "hashed";
+/// The rust lookup table of the content hashed shaders in the output dir, include it with
+/// `include!(concat!(env!("OUT_DIR"), "/hashed_shaders.rs"))`
+pub const HASHED_TABLE_NAME: &str = "hashed_shaders.rs";
+/// The JSON lookup table of the content hashed shaders in [`HASHED_DIR_NAME`], to deploy with them
+pub const HASHED_JSON_NAME: &str = "shaders.json";
+
+/// The 64 bit FNV-1a hash of `contents`, unlike `DefaultHasher` it is the same in every build and rust version
+fn content_hash(contents: &[u8]) -> u64 {
+    contents.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// Copy every built shader of `plan` to [`HASHED_DIR_NAME`] in `out_dir`, named by the hash of its contents,
+/// point the plan at the copies and write the lookup tables of their names
+///
+/// The shaders stay at their module paths as well, the code extensions generated may include them from there.
+pub(crate) fn hash_artifacts(out_dir: &Path, plan: &mut BuildPlan) -> Result<(), WeslBuildError> {
+    for shader in &mut plan.shaders {
+        let Some(output_path) = &shader.output_path else { continue };
+        let contents = artifacts::read(output_path)?;
+        // shaders with the same contents share a file
+        let artifact_name = format!("{HASHED_DIR_NAME}/{:016x}", content_hash(&contents));
+        let hashed_path = artifact_path(out_dir, &artifact_name);
+        artifacts::write(&hashed_path, contents)?;
+
+        shader.artifact_name = artifact_name;
+        shader.output_path = Some(hashed_path);
+    }
+
+    let hashed_files = plan.shaders.iter()
+        .map(|shader| (
+            shader.mod_path.components.join("::"),
+            format!("{}.wgsl", shader.artifact_name.trim_start_matches(&format!("{HASHED_DIR_NAME}/"))),
+        ))
+        .sorted()
+        .collect::<Vec<_>>();
+    artifacts::write(out_dir.join(HASHED_TABLE_NAME), lookup_table(&hashed_files))?;
+    artifacts::write(out_dir.join(HASHED_DIR_NAME).join(HASHED_JSON_NAME), json_table(&hashed_files))?;
+
+    Ok(())
+}
+
+/// The rust source of [`HASHED_TABLE_NAME`] for `(module path, file)` pairs sorted by module path
+fn lookup_table(hashed_files: &[(String, String)]) -> String {
+    let mut source = "// @generated by wesl_build, do not edit\n\n".to_owned();
+    source.push_str("/// The content hashed file of every built shader in the `hashed` dir, sorted by module path\n");
+    source.push_str("pub static HASHED_SHADERS: &[(&str, &str)] = &[\n");
+    for (mod_path, file) in hashed_files {
+        writeln!(source, "    ({mod_path:?}, {file:?}),").expect("writing to a String can't fail");
+    }
+    source.push_str("];\n\n");
+    source.push_str("/// The content hashed file of the shader `mod_path`, e.g. `\"passes::blur\"`\n");
+    source.push_str("pub fn hashed_shader(mod_path: &str) -> Option<&'static str> {\n");
+    source.push_str("    HASHED_SHADERS.binary_search_by_key(&mod_path, |&(key, _)| key).ok().map(|index| HASHED_SHADERS[index].1)\n");
+    source.push_str("}\n");
+    source
+}
+
+/// The JSON object of [`HASHED_JSON_NAME`], module path to file, for `(module path, file)` pairs
+fn json_table(hashed_files: &[(String, String)]) -> String {
+    let entries = hashed_files.iter()
+        .map(|(mod_path, file)| format!("  {}: {}", json_string(mod_path), json_string(file)))
+        .join(",\n");
+    if entries.is_empty() {
+        "{}\n".to_owned()
+    } else {
+        format!("{{\n{entries}\n}}\n")
+    }
+}
@@ -0,0 +1,171 @@
+//! The state of the build running on this thread, shared by its extensions
+
+use std::{
+    any::{Any, TypeId},
+    cell::RefCell,
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{WeslBuildConfig, WeslBuildError};
+use crate::artifacts::ArtifactWriter;
+
+/// The config of the build running on this thread, with a store extensions share data through and a scratch dir
+///
+/// Every hook of an extension runs inside a build, get the context with [`with_build_context`]. E.g. an extension
+/// collecting stats can [`insert`](Self::insert) them for one that runs after it to [`get`](Self::get) in `exit_root`.
+/// The store is keyed by type, so wrap shared values in a type of your extension.
+///
+/// ## Example
+/// ```
+/// use wesl_build::with_build_context;
+///
+/// struct ShaderCount(usize);
+///
+/// // in a hook of one extension
+/// with_build_context(|ctx| ctx.insert(ShaderCount(3)));
+/// // and of another that runs after it
+/// let count = with_build_context(|ctx| ctx.get::<ShaderCount>().map(|count| count.0)).flatten();
+/// # let _ = count;
+/// ```
+#[derive(Debug)]
+pub struct BuildContext {
+    config: WeslBuildConfig,
+    shared: HashMap<TypeId, Box<dyn Any>>,
+    scratch_dir: Option<PathBuf>,
+    /// The roots extensions declared with [`declare_artifact_root`](Self::declare_artifact_root)
+    artifact_roots: Vec<PathBuf>,
+}
+
+impl BuildContext {
+    fn new(config: WeslBuildConfig) -> Self {
+        Self { config, shared: HashMap::new(), scratch_dir: None, artifact_roots: Vec::new() }
+    }
+
+    /// The config of the build, in a [check](WeslBuildConfig::check) with the in memory sink it writes to
+    pub fn config(&self) -> &WeslBuildConfig {
+        &self.config
+    }
+
+    /// The dir the shaders are built to, see [`WeslBuildConfig::output_dir`]
+    pub fn out_dir(&self) -> Option<PathBuf> {
+        self.config.output_dir()
+    }
+
+    /// The build profile, see [`current_profile`](`crate::current_profile`)
+    pub fn profile(&self) -> Option<String> {
+        self.config.build_profile()
+    }
+
+    /// The target triple the shaders are built for, see [`WeslBuildConfig::build_target`]
+    pub fn target(&self) -> Option<String> {
+        self.config.build_target()
+    }
+
+    /// Whether the shaders are built for the web, a `wasm32` target
+    pub fn is_wasm(&self) -> bool {
+        self.target().is_some_and(|target| target.starts_with("wasm32"))
+    }
+
+    /// A temp dir of this build for intermediate files, created on first use and removed when the build ends
+    pub fn scratch_dir(&mut self) -> io::Result<&Path> {
+        static NEXT_DIR: AtomicUsize = AtomicUsize::new(0);
+
+        if self.scratch_dir.is_none() {
+            let idx = NEXT_DIR.fetch_add(1, Ordering::Relaxed);
+            let dir = std::env::temp_dir().join(format!("wesl_build_scratch_{}_{idx}", std::process::id()));
+            fs::create_dir_all(&dir)?;
+            self.scratch_dir = Some(dir);
+        }
+        Ok(self.scratch_dir.as_deref().expect("the scratch dir was just created"))
+    }
+
+    /// Let the [`artifact_writer`](Self::artifact_writer) write in `dir`, e.g. the dir of the bindings of an extension
+    pub fn declare_artifact_root(&mut self, dir: impl Into<PathBuf>) {
+        self.artifact_roots.push(dir.into());
+    }
+
+    /// A writer of the artifacts of extensions that fails on paths outside of the output dir, the scratch dir,
+    /// the [artifact roots](WeslBuildConfig::artifact_root) of the config and the ones extensions declared,
+    /// unless the config turns off [`sandbox_artifacts`](WeslBuildConfig::sandbox_artifacts)
+    pub fn artifact_writer(&self) -> ArtifactWriter {
+        if !self.config.sandbox_artifacts {
+            return ArtifactWriter::unrestricted();
+        }
+        ArtifactWriter::new(self.out_dir().into_iter()
+            .chain(self.scratch_dir.clone())
+            .chain(self.config.artifact_roots.iter().cloned())
+            .chain(self.artifact_roots.iter().cloned()))
+    }
+
+    /// Store `value` for the other extensions of the build, returns the value of the same type it replaced
+    pub fn insert<T: Any>(&mut self, value: T) -> Option<T> {
+        self.shared.insert(TypeId::of::<T>(), Box::new(value))
+            .map(|old| *old.downcast::<T>().expect("the store is keyed by type"))
+    }
+
+    /// The stored value of type `T`
+    pub fn get<T: Any>(&self) -> Option<&T> {
+        self.shared.get(&TypeId::of::<T>()).and_then(|value| value.downcast_ref())
+    }
+
+    pub fn get_mut<T: Any>(&mut self) -> Option<&mut T> {
+        self.shared.get_mut(&TypeId::of::<T>()).and_then(|value| value.downcast_mut())
+    }
+
+    /// The stored value of type `T`, inserting the one of `default` if there is none
+    pub fn get_or_insert_with<T: Any>(&mut self, default: impl FnOnce() -> T) -> &mut T {
+        self.shared.entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(default()))
+            .downcast_mut()
+            .expect("the store is keyed by type")
+    }
+
+    pub fn remove<T: Any>(&mut self) -> Option<T> {
+        self.shared.remove(&TypeId::of::<T>())
+            .map(|value| *value.downcast::<T>().expect("the store is keyed by type"))
+    }
+}
+
+impl Drop for BuildContext {
+    fn drop(&mut self) {
+        if let Some(scratch_dir) = &self.scratch_dir {
+            let _ = fs::remove_dir_all(scratch_dir);
+        }
+    }
+}
+
+thread_local! {
+    /// The context of the build running on this thread
+    static CONTEXT: RefCell<Option<BuildContext>> = const { RefCell::new(None) };
+}
+
+/// Run `f` with the [`BuildContext`] of the build running on this thread, `None` if no build is running
+///
+/// ## Panics
+/// If it's called inside `f`, the context is borrowed mutably.
+pub fn with_build_context<T>(f: impl FnOnce(&mut BuildContext) -> T) -> Option<T> {
+    CONTEXT.with_borrow_mut(|context| context.as_mut().map(f))
+}
+
+/// [`with_build_context`] for hooks that need the context, an error instead of `None` if no build is running
+pub(crate) fn try_with_build_context<T>(f: impl FnOnce(&mut BuildContext) -> T) -> Result<T, WeslBuildError> {
+    with_build_context(f).ok_or(WeslBuildError::NoBuildContext)
+}
+
+/// Sets the context of the build running on this thread until it is dropped, which ends it
+pub(crate) struct ContextGuard(Option<BuildContext>);
+
+impl ContextGuard {
+    pub(crate) fn set(config: &WeslBuildConfig) -> Self {
+        Self(CONTEXT.replace(Some(BuildContext::new(config.clone()))))
+    }
+}
+
+impl Drop for ContextGuard {
+    fn drop(&mut self) {
+        CONTEXT.set(self.0.take());
+    }
+}
@@ -0,0 +1,168 @@
+#![cfg(feature = "rich_diagnostics")]
+
+//! Render errors in built shaders with source excerpts, mapped back to the WESL source with the source map
+
+use std::{fs, ops::Range, path::{Path, PathBuf}};
+
+use codespan_reporting::diagnostic::{Diagnostic, Label};
+use codespan_reporting::files::SimpleFiles;
+use codespan_reporting::term::{self, termcolor::Buffer};
+use wesl::{BasicSourceMap, ModulePath, SourceMap};
+
+/// An error in a built shader, rendered with the offending spans of the built WGSL
+/// and the declarations in the WESL sources they were built from
+#[derive(Debug, Clone)]
+pub struct ShaderDiagnostic {
+    message: String,
+    built_path: PathBuf,
+    built_source: String,
+    /// spans in the built source
+    labels: Vec<(Range<usize>, String)>,
+    origins: Vec<Origin>,
+}
+
+/// A declaration in a WESL source file
+#[derive(Debug, Clone)]
+struct Origin {
+    source_path: PathBuf,
+    source: String,
+    /// the span of the declaration's name
+    span: Range<usize>,
+    name: String,
+}
+
+impl ShaderDiagnostic {
+    pub fn new(message: impl Into<String>, built_path: impl AsRef<Path>, built_source: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            built_path: built_path.as_ref().to_owned(),
+            built_source: built_source.into(),
+            labels: Vec::new(),
+            origins: Vec::new(),
+        }
+    }
+
+    /// Label `span` of the built source
+    pub fn with_label(mut self, span: Range<usize>, message: impl Into<String>) -> Self {
+        self.labels.push((span, message.into()));
+        self
+    }
+
+    /// Label the spans of a naga error, spans that are not in the source are left out
+    pub fn with_naga_labels<'a>(mut self, labels: impl IntoIterator<Item = (naga::Span, &'a str)>) -> Self {
+        self.labels.extend(labels.into_iter()
+            .filter_map(|(span, message)| Some((span.to_range()?, message.to_owned()))));
+        self
+    }
+
+    /// Point to the declarations in the WESL sources that contain the labeled spans
+    ///
+    /// ### Args
+    /// * `mod_path` - the module that was built, items from it are not in the source map
+    /// * `shader_root_path` - the root dir of the shaders
+    pub fn with_source_map(
+        mut self,
+        source_map: Option<&BasicSourceMap>,
+        mod_path: &ModulePath,
+        shader_root_path: &Path,
+    ) -> Self {
+        for (span, _) in &self.labels {
+            let Some(built_decl) = decl_at(&self.built_source, span.start) else { continue };
+            let (decl_mod_path, name) = source_map
+                .and_then(|source_map| source_map.get_decl(built_decl))
+                .map_or_else(
+                    || (mod_path.clone(), built_decl.to_owned()),
+                    |(path, name)| (path.clone(), name.to_owned()),
+                );
+
+            if self.origins.iter().any(|origin| origin.name == name) {
+                continue;
+            }
+            if let Some(origin) = find_decl(shader_root_path, &decl_mod_path, &name) {
+                self.origins.push(origin);
+            }
+        }
+        self
+    }
+
+    /// The diagnostic with source excerpts, without color
+    pub fn render(&self) -> String {
+        let mut files = SimpleFiles::new();
+        let mut labels = Vec::new();
+
+        // the original source first, it's what needs fixing
+        for origin in &self.origins {
+            let file = files.add(origin.source_path.display().to_string(), origin.source.as_str());
+            labels.push(Label::secondary(file, origin.span.clone())
+                .with_message(format!("in `{}` declared here", origin.name)));
+        }
+        let built_file = files.add(self.built_path.display().to_string(), self.built_source.as_str());
+        labels.extend(self.labels.iter()
+            .map(|(span, message)| Label::primary(built_file, span.clone()).with_message(message)));
+
+        let diagnostic = Diagnostic::error()
+            .with_message(&self.message)
+            .with_labels(labels);
+
+        let mut buffer = Buffer::no_color();
+        match term::emit(&mut buffer, &term::Config::default(), &files, &diagnostic) {
+            Ok(()) => String::from_utf8_lossy(buffer.as_slice()).into_owned(),
+            // e.g. a span outside of the source
+            Err(_) => format!("{}: {}", self.built_path.display(), self.message),
+        }
+    }
+}
+
+/// The name of the top level declaration containing `offset` in WGSL from the wesl compiler,
+/// which puts declarations at the start of a line
+fn decl_at(source: &str, offset: usize) -> Option<&str> {
+    let mut line_start = 0;
+    let mut decl = None;
+
+    for line in source.split_inclusive('\n') {
+        if line_start > offset {
+            break;
+        }
+        line_start += line.len();
+
+        if !line.starts_with(|c: char| c.is_alphabetic()) {
+            continue;
+        }
+        let mut words = line.split(|c: char| !(c.is_alphanumeric() || c == '_')).filter(|word| !word.is_empty());
+        if let Some("fn" | "struct" | "const" | "override" | "var" | "alias") = words.next() {
+            // skip the template args of `var<storage, read>`
+            let name = if line.starts_with("var<") {
+                line.split_once('>').and_then(|(_, rest)| rest.split(|c: char| !(c.is_alphanumeric() || c == '_')).find(|word| !word.is_empty()))
+            } else {
+                words.next()
+            };
+            decl = name.or(decl);
+        }
+    }
+
+    decl
+}
+
+/// The declaration `name` in the source file of `mod_path`
+fn find_decl(shader_root_path: &Path, mod_path: &ModulePath, name: &str) -> Option<Origin> {
+    let mut source_path = shader_root_path.to_owned();
+    source_path.extend(&mod_path.components);
+    let source_path = ["wesl", "wgsl"].into_iter()
+        .map(|ext| source_path.with_extension(ext))
+        .find(|path| path.is_file())?;
+    let source = fs::read_to_string(&source_path).ok()?;
+
+    // the first whole word occurrence
+    let start = source.match_indices(name).map(|(idx, _)| idx).find(|&idx| {
+        let before = source[..idx].chars().next_back();
+        let after = source[idx + name.len()..].chars().next();
+        [before, after].into_iter().flatten().all(|c| !(c.is_alphanumeric() || c == '_'))
+    })?;
+
+    Some(Origin {
+        source_path,
+        span: start..start + name.len(),
+        name: name.to_owned(),
+        source,
+    })
+}
@@ -0,0 +1,93 @@
+//! Advisory locks on the output dirs of a build, so concurrent builds of a crate, e.g. rust-analyzer's check
+//! and `cargo build`, take turns writing them instead of interleaving their files
+//!
+//! The locks are held by the OS for the open lock file, so a build that crashes or is killed releases them.
+
+use std::{
+    cell::Cell,
+    fs::{self, File, TryLockError},
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::WeslBuildError;
+use crate::messages::{self, BuildMessage};
+
+/// The lock file of the output dir of a build
+pub const DIR_LOCK_NAME: &str = ".wesl_build.dirlock";
+
+/// How long a build waits for another to release a lock by default, see [`WeslBuildConfig::lock_timeout`](`crate::WeslBuildConfig::lock_timeout`)
+pub(crate) const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// How often a lock held by another build is retried
+const RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+thread_local! {
+    /// The [`WeslBuildConfig::lock_timeout`](`crate::WeslBuildConfig::lock_timeout`) of the build running on this thread
+    static LOCK_TIMEOUT: Cell<Duration> = const { Cell::new(DEFAULT_LOCK_TIMEOUT) };
+}
+
+/// Sets the lock timeout of the build running on this thread until it is dropped
+pub(crate) struct LockTimeoutGuard(Duration);
+
+impl LockTimeoutGuard {
+    pub(crate) fn set(timeout: Duration) -> Self {
+        Self(LOCK_TIMEOUT.replace(timeout))
+    }
+}
+
+impl Drop for LockTimeoutGuard {
+    fn drop(&mut self) {
+        LOCK_TIMEOUT.set(self.0);
+    }
+}
+
+/// An exclusive lock on the lock file at `path`, released when it is dropped
+#[derive(Debug)]
+pub(crate) struct DirLock {
+    _file: File,
+}
+
+impl DirLock {
+    /// Lock the file at `path`, creating it and its parent dirs, waiting for another build holding it
+    /// up to the lock timeout of the build running on this thread
+    pub(crate) fn acquire(path: &Path) -> Result<Self, WeslBuildError> {
+        if let Some(parent) = path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            fs::create_dir_all(parent)?;
+        }
+        let file = File::options().create(true).truncate(false).write(true).open(path)?;
+
+        let timeout = LOCK_TIMEOUT.get();
+        let start = Instant::now();
+        let mut waiting = false;
+        loop {
+            match file.try_lock() {
+                Ok(()) => return Ok(Self { _file: file }),
+                Err(TryLockError::Error(error)) => return Err(error.into()),
+                Err(TryLockError::WouldBlock) if start.elapsed() >= timeout => {
+                    return Err(WeslBuildError::LockTimeout { path: path.to_owned(), timeout });
+                }
+                Err(TryLockError::WouldBlock) => {
+                    if !waiting {
+                        waiting = true;
+                        messages::report(&BuildMessage::warning(format!(
+                            "another build holds `{}`, waiting up to {}s for it to finish",
+                            path.display(),
+                            timeout.as_secs(),
+                        )))?;
+                    }
+                    thread::sleep(RETRY_INTERVAL);
+                }
+            }
+        }
+    }
+}
+
+/// The lock file of the dir `dir`, next to it so it doesn't end up in the dir
+pub(crate) fn sibling_lock_path(dir: &Path) -> PathBuf {
+    let name = dir.file_name().unwrap_or_default().to_string_lossy();
+    dir.with_file_name(format!(".{name}{DIR_LOCK_NAME}"))
+}
@@ -0,0 +1,273 @@
+#![cfg(feature = "tokio")]
+
+//! Extensions with async hooks, for network or heavy IO like uploading debug symbols or fetching remote shader packages
+//!
+//! Implement [`AsyncWeslBuildExtension`], wrap it in an [`AsyncExtension`] and build with [`build_shader_dir_blocking`]
+//! from [`spawn_blocking`](tokio::task::spawn_blocking). The build blocks its thread on each hook before the next,
+//! so extensions run in the same order as in a sync build, while the runtime keeps running its tasks.
+//! A hook can [spawn](tokio::spawn) work and await it in a later hook,
+//! e.g. start an upload in `post_build` and wait for all of them in `exit_root`.
+//! Spawned tasks run on the threads of the runtime, which don't see the [build context](`crate::with_build_context`),
+//! so get what they need from it in the hook.
+
+// the hooks are awaited on the thread of the build, so their futures don't need to be `Send`
+#![allow(async_fn_in_trait)]
+
+use std::{borrow::Cow, error::Error, path::Path};
+
+use wesl::{BasicSourceMap, CompileOptions, ModulePath, Resolver, Wesl};
+
+use super::compat::ExtensionApiVersion;
+use crate::{
+    BuildPlan, BuildResolver, ModuleGraph, PlannedShader, WeslBuildConfig, WeslBuildError, WeslBuildExtension,
+    build_shader_dir_with_config,
+};
+
+/// A [`WeslBuildExtension`] whose hooks are async, run it in a build with [`AsyncExtension`]
+///
+/// The hooks are the same as those of [`WeslBuildExtension`], see them for when each runs,
+/// but every hook does nothing by default.
+pub trait AsyncWeslBuildExtension<WeslResolver: Resolver> {
+    /// The name to report in errors as the source extension
+    fn name<'n>(&self) -> Cow<'n, str>;
+
+    /// The version of the extension API the extension was written against
+    fn extension_api_version(&self) -> ExtensionApiVersion {
+        ExtensionApiVersion::CURRENT
+    }
+
+    async fn init_root(&mut self, _shader_root_path: &str, _res: &mut Wesl<WeslResolver>) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    async fn on_graph(&mut self, _graph: &ModuleGraph) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    async fn exit_root(
+        &mut self,
+        _shader_root_path: &str,
+        _res: &Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    async fn enter_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    async fn exit_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    async fn on_asset(&mut self, _asset_path: &Path) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    async fn preprocess(&mut self, _wesl_path: &ModulePath, _source: &mut String) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    async fn configure(&mut self, _wesl_path: &ModulePath, _options: &mut CompileOptions) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    #[cfg(feature = "naga")]
+    async fn post_parse(&mut self, _wesl_path: &ModulePath, _module: &mut crate::NagaModule) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    async fn post_build(
+        &mut self,
+        _wesl_path: &ModulePath,
+        _wgsl_built_path: &str,
+        _source_map: &Option<BasicSourceMap>,
+    ) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    async fn on_error(&mut self, _wesl_path: &ModulePath, _error: &WeslBuildError) {}
+
+    async fn would_build(&mut self, _shader: &PlannedShader) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    /// The name of the built artifact of `wesl_path`, `None` to keep the name of the layout
+    fn artifact_name(&self, _wesl_path: &ModulePath) -> Option<String> {
+        None
+    }
+
+    /// Whether `post_build` reads the built WGSL file
+    fn reads_built_wgsl(&self) -> bool {
+        false
+    }
+
+    /// Whether `post_build` replaces the built WGSL file
+    fn mutates_built_wgsl(&self) -> bool {
+        false
+    }
+
+    /// The names of the extensions this one must run after, if they are used
+    fn must_run_after(&self) -> &[&str] {
+        &[]
+    }
+
+    /// The names of the extensions this one must run before, if they are used
+    fn must_run_before(&self) -> &[&str] {
+        &[]
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum AsyncExtensionError {
+    #[error("`{extension}` is async, build with `build_shader_dir_blocking` from `spawn_blocking` in a tokio runtime")]
+    NoRuntime { extension: String },
+}
+
+/// Runs an [`AsyncWeslBuildExtension`] as a [`WeslBuildExtension`], blocking on each hook
+///
+/// Only works in a [`build_shader_dir_blocking`] in a tokio runtime,
+/// every hook fails with [`AsyncExtensionError::NoRuntime`] outside of one.
+pub struct AsyncExtension<E> {
+    inner: E,
+}
+
+impl<E> AsyncExtension<E> {
+    pub fn new(inner: E) -> Self {
+        Self { inner }
+    }
+
+    pub fn inner(&self) -> &E {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> E {
+        self.inner
+    }
+}
+
+/// The runtime of the async build running on this thread
+fn runtime(extension: Cow<'_, str>) -> Result<tokio::runtime::Handle, AsyncExtensionError> {
+    tokio::runtime::Handle::try_current()
+        .map_err(|_| AsyncExtensionError::NoRuntime { extension: extension.into_owned() })
+}
+
+impl<R: Resolver, E: AsyncWeslBuildExtension<R>> WeslBuildExtension<R> for AsyncExtension<E> {
+    fn name<'n>(&self) -> Cow<'n, str> {
+        self.inner.name()
+    }
+
+    fn extension_api_version(&self) -> ExtensionApiVersion {
+        self.inner.extension_api_version()
+    }
+
+    fn init_root(&mut self, shader_root_path: &str, res: &mut Wesl<R>) -> Result<(), Box<dyn Error>> {
+        runtime(self.inner.name())?.block_on(self.inner.init_root(shader_root_path, res))
+    }
+
+    fn on_graph(&mut self, graph: &ModuleGraph) -> Result<(), Box<dyn Error>> {
+        runtime(self.inner.name())?.block_on(self.inner.on_graph(graph))
+    }
+
+    fn exit_root(&mut self, shader_root_path: &str, res: &Wesl<R>) -> Result<(), Box<dyn Error>> {
+        runtime(self.inner.name())?.block_on(self.inner.exit_root(shader_root_path, res))
+    }
+
+    fn enter_mod(&mut self, dir_path: &Path) -> Result<(), Box<dyn Error>> {
+        runtime(self.inner.name())?.block_on(self.inner.enter_mod(dir_path))
+    }
+
+    fn exit_mod(&mut self, dir_path: &Path) -> Result<(), Box<dyn Error>> {
+        runtime(self.inner.name())?.block_on(self.inner.exit_mod(dir_path))
+    }
+
+    fn on_asset(&mut self, asset_path: &Path) -> Result<(), Box<dyn Error>> {
+        runtime(self.inner.name())?.block_on(self.inner.on_asset(asset_path))
+    }
+
+    fn preprocess(&mut self, wesl_path: &ModulePath, source: &mut String) -> Result<(), Box<dyn Error>> {
+        runtime(self.inner.name())?.block_on(self.inner.preprocess(wesl_path, source))
+    }
+
+    fn configure(&mut self, wesl_path: &ModulePath, options: &mut CompileOptions) -> Result<(), Box<dyn Error>> {
+        runtime(self.inner.name())?.block_on(self.inner.configure(wesl_path, options))
+    }
+
+    #[cfg(feature = "naga")]
+    fn post_parse(&mut self, wesl_path: &ModulePath, module: &mut crate::NagaModule) -> Result<(), Box<dyn Error>> {
+        runtime(self.inner.name())?.block_on(self.inner.post_parse(wesl_path, module))
+    }
+
+    fn post_build(
+        &mut self,
+        wesl_path: &ModulePath,
+        wgsl_built_path: &str,
+        source_map: &Option<BasicSourceMap>,
+    ) -> Result<(), Box<dyn Error>> {
+        runtime(self.inner.name())?.block_on(self.inner.post_build(wesl_path, wgsl_built_path, source_map))
+    }
+
+    fn on_error(&mut self, wesl_path: &ModulePath, error: &WeslBuildError) {
+        match runtime(self.inner.name()) {
+            Ok(runtime) => runtime.block_on(self.inner.on_error(wesl_path, error)),
+            #[cfg_attr(not(feature = "logging"), allow(unused_variables))]
+            Err(no_runtime) => {
+                #[cfg(feature = "logging")]
+                log::warn!("{no_runtime}");
+            }
+        }
+    }
+
+    fn would_build(&mut self, shader: &PlannedShader) -> Result<(), Box<dyn Error>> {
+        runtime(self.inner.name())?.block_on(self.inner.would_build(shader))
+    }
+
+    fn artifact_name(&self, wesl_path: &ModulePath) -> Option<String> {
+        self.inner.artifact_name(wesl_path)
+    }
+
+    fn reads_built_wgsl(&self) -> bool {
+        self.inner.reads_built_wgsl()
+    }
+
+    fn mutates_built_wgsl(&self) -> bool {
+        self.inner.mutates_built_wgsl()
+    }
+
+    fn must_run_after(&self) -> &[&str] {
+        self.inner.must_run_after()
+    }
+
+    fn must_run_before(&self) -> &[&str] {
+        self.inner.must_run_before()
+    }
+}
+
+/// [`build_shader_dir_with_config`] with [async extensions](self), blocking until the build is done
+///
+/// Call it from [`spawn_blocking`](tokio::task::spawn_blocking) in a tokio runtime of either flavor, not from an async task,
+/// where blocking on the hooks panics. The extensions aren't `Send`, so create them in the closure.
+///
+/// ## Example
+/// ```no_run
+/// use wesl_build::{WeslBuildConfig, extension::async_extension::{AsyncExtension, build_shader_dir_blocking}};
+/// # struct SymbolUpload;
+/// # impl wesl_build::extension::async_extension::AsyncWeslBuildExtension<wesl_build::BuildResolver> for SymbolUpload {
+/// #     fn name<'n>(&self) -> std::borrow::Cow<'n, str> { "SymbolUpload".into() }
+/// # }
+///
+/// # async fn build() {
+/// tokio::task::spawn_blocking(|| {
+///     build_shader_dir_blocking(
+///         &WeslBuildConfig::new("src/shaders"),
+///         wesl_build::extensions![AsyncExtension::new(SymbolUpload)],
+///     ).map_err(|error| error.to_string())
+/// }).await.unwrap().expect("failed to build the shaders");
+/// # }
+/// ```
+pub fn build_shader_dir_blocking(
+    config: &WeslBuildConfig,
+    extensions: &mut [Box<dyn WeslBuildExtension<BuildResolver>>],
+) -> Result<BuildPlan, WeslBuildError> {
+    build_shader_dir_with_config(config, extensions)
+}
@@ -0,0 +1,184 @@
+#![cfg(feature = "bevy_asset_ext")]
+
+//! Write the built shaders into a Bevy `assets` folder, and optionally a plugin that registers them
+
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use wesl::{BasicSourceMap, ModulePath};
+
+use crate::WeslBuildExtension;
+use crate::artifacts;
+
+/// Write every built shader into a Bevy assets folder, at the path of its module
+///
+/// `blur::gaussian` is written to `assets/shaders/blur/gaussian.wgsl`, so it can be loaded with
+/// `asset_server.load("shaders/blur/gaussian.wgsl")`. Each shader starts with a
+/// `#define_import_path blur::gaussian`, so Bevy shaders can `#import` it by the same path as in WESL.
+///
+/// With [`with_plugin`](Self::with_plugin) it also generates a plugin that registers every shader as a
+/// `Shader` asset with a stable handle, the same across builds and machines:
+/// ```ignore
+/// pub const BLUR__GAUSSIAN: Handle<Shader> = uuid_handle!("...");
+///
+/// pub struct WeslShadersPlugin;
+///
+/// impl Plugin for WeslShadersPlugin {
+///     fn build(&self, app: &mut App) {
+///         load_internal_asset!(app, BLUR__GAUSSIAN, "/path/to/assets/shaders/blur/gaussian.wgsl", Shader::from_wgsl);
+///     }
+/// }
+/// ```
+/// The plugin is written for Bevy 0.17, this extension doesn't depend on Bevy.
+pub struct BevyAssetExtension {
+    /// The dir the shaders are written to
+    assets_dir: PathBuf,
+    /// Whether the shaders start with a `#define_import_path`
+    import_paths: bool,
+    /// The path of the generated plugin, if any
+    plugin_path: Option<PathBuf>,
+    /// The name of the generated plugin struct
+    plugin_name: String,
+    /// `(module path, asset path)` for each built shader
+    shaders: Vec<(ModulePath, PathBuf)>,
+}
+
+impl BevyAssetExtension {
+    /// Write the shaders to `assets_dir`, e.g. `assets/shaders`
+    pub fn new(assets_dir: impl AsRef<Path>) -> Self {
+        Self {
+            assets_dir: assets_dir.as_ref().to_owned(),
+            import_paths: true,
+            plugin_path: None,
+            plugin_name: "WeslShadersPlugin".to_owned(),
+            shaders: Vec::new(),
+        }
+    }
+
+    /// Whether the shaders start with a `#define_import_path` of their module path, on by default
+    pub fn with_import_paths(mut self, import_paths: bool) -> Self {
+        self.import_paths = import_paths;
+        self
+    }
+
+    /// Generate a plugin registering the shaders to `plugin_path`, e.g. `src/shaders.rs`
+    pub fn with_plugin(mut self, plugin_path: impl AsRef<Path>) -> Self {
+        self.plugin_path = Some(plugin_path.as_ref().to_owned());
+        self
+    }
+
+    /// Set the name of the generated plugin, defaults to `WeslShadersPlugin`
+    pub fn with_plugin_name(mut self, plugin_name: impl Into<String>) -> Self {
+        self.plugin_name = plugin_name.into();
+        self
+    }
+
+    /// The path `mod_path` is written to, in the assets dir
+    fn asset_path(&self, mod_path: &ModulePath) -> PathBuf {
+        let mut asset_path = self.assets_dir.clone();
+        asset_path.extend(&mod_path.components);
+        asset_path.set_extension("wgsl");
+        asset_path
+    }
+
+    /// The rust source of the plugin for the shaders built so far
+    fn plugin_source(&self) -> Result<String, std::io::Error> {
+        let mut handles = String::new();
+        let mut loads = String::new();
+        for (mod_path, asset_path) in &self.shaders {
+            let module = mod_path.components.join("::");
+            let handle = mod_path.components.join("__").to_uppercase();
+            // `include_str!` in `load_internal_asset!` is relative to the plugin file, absolute paths work anywhere
+            let asset_path = std::path::absolute(asset_path)?;
+            writeln!(handles, "/// `{module}`\npub const {handle}: Handle<Shader> = uuid_handle!(\"{}\");\n", stable_uuid(&module))
+                .expect("writing to a String can't fail");
+            writeln!(loads, "        load_internal_asset!(app, {handle}, {:?}, Shader::from_wgsl);", asset_path.display().to_string())
+                .expect("writing to a String can't fail");
+        }
+
+        Ok(format!(
+            "// @generated by wesl_build, do not edit\n\n\
+            use bevy::asset::{{load_internal_asset, uuid_handle}};\n\
+            use bevy::prelude::*;\n\n\
+            {handles}\
+            /// Registers every shader built by wesl_build as a `Shader` asset with the handles above\n\
+            pub struct {name};\n\n\
+            impl Plugin for {name} {{\n    \
+                fn build(&self, app: &mut App) {{\n\
+                    {loads}    \
+                }}\n\
+            }}\n",
+            name = self.plugin_name,
+        ))
+    }
+}
+
+impl<WeslResolver: wesl::Resolver> WeslBuildExtension<WeslResolver> for BevyAssetExtension {
+    fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+        "BevyAssetExtension".into()
+    }
+
+    fn init_root(
+        &mut self,
+        _shader_path: &str,
+        _res: &mut wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.shaders.clear();
+        Ok(())
+    }
+
+    fn exit_root(
+        &mut self,
+        _shader_root_path: &str,
+        _res: &wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if crate::is_check() {
+            return Ok(());
+        }
+        if let Some(plugin_path) = &self.plugin_path {
+            artifacts::write(plugin_path, self.plugin_source()?)?;
+
+            #[cfg(feature = "logging")]
+            log::debug!("wrote bevy plugin with {} shaders to: {}", self.shaders.len(), plugin_path.display());
+        }
+
+        Ok(())
+    }
+
+    fn enter_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+    fn exit_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+
+    fn post_build(
+        &mut self,
+        mod_path: &ModulePath,
+        wgsl_built_path: &str,
+        _source_map: &Option<BasicSourceMap>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let asset_path = self.asset_path(mod_path);
+        if !crate::is_check() {
+            let mut wgsl = artifacts::read_to_string(wgsl_built_path)?;
+            if self.import_paths {
+                wgsl.insert_str(0, &format!("#define_import_path {}\n\n", mod_path.components.join("::")));
+            }
+            artifacts::write(&asset_path, wgsl)?;
+        }
+        self.shaders.push((mod_path.clone(), asset_path));
+        Ok(())
+    }
+}
+
+/// A UUID derived from `name` with FNV-1a, so the handle of a shader is the same in every build
+fn stable_uuid(name: &str) -> String {
+    let fnv = |offset: u64| name.bytes().fold(offset, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    // the low half starts from the hash of the high half, so the halves differ
+    let high = fnv(0xcbf2_9ce4_8422_2325);
+    let low = fnv(high);
+    let mut uuid = u128::from(high) << 64 | u128::from(low);
+    // mark it as a version 8 (custom) UUID with the RFC 9562 variant
+    uuid = (uuid & !(0xf << 76)) | (0x8 << 76);
+    uuid = (uuid & !(0x3 << 62)) | (0x2 << 62);
+    let hex = format!("{uuid:032x}");
+    format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
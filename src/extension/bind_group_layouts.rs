@@ -0,0 +1,147 @@
+#![cfg(feature = "wgpu_bindings_ext")]
+
+//! Reflect bind group layouts from naga modules as rust code for `wgpu`
+
+use std::collections::BTreeMap;
+
+/// A bind group layout entry of a shader as rust code for the fields of a `wgpu::BindGroupLayoutEntry`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) struct LayoutEntry {
+    pub(crate) binding: u32,
+    /// e.g. `wgpu::ShaderStages::COMPUTE`
+    pub(crate) visibility: String,
+    /// e.g. `wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering)`
+    pub(crate) ty: String,
+}
+
+impl LayoutEntry {
+    /// The rust code for a `wgpu::BindGroupLayoutEntry`
+    pub(crate) fn to_rust(&self) -> String {
+        format!(
+            "wgpu::BindGroupLayoutEntry {{ binding: {}, visibility: {}, ty: {}, count: None }}",
+            self.binding, self.visibility, self.ty,
+        )
+    }
+}
+
+/// The bind group layouts of `module` by group index, with entries sorted by binding
+///
+/// Bindings of unsupported types (e.g. binding arrays) are left out, so those layouts are incomplete
+pub(crate) fn bind_group_layouts(
+    module: &naga::Module,
+    info: &naga::valid::ModuleInfo,
+) -> BTreeMap<u32, Vec<LayoutEntry>> {
+    let mut layouts = BTreeMap::<u32, Vec<LayoutEntry>>::new();
+
+    for (handle, global) in module.global_variables.iter() {
+        let Some(binding) = &global.binding else { continue };
+        let Some(ty) = binding_type(module, global) else {
+            #[cfg(feature = "logging")]
+            log::warn!("unsupported binding type for `{}`, it is left out of the layout", global.name.as_deref().unwrap_or("unnamed"));
+            continue;
+        };
+
+        // visible to each stage that uses it
+        let stages = module.entry_points.iter().enumerate()
+            .filter(|(idx, _)| !info.get_entry_point(*idx)[handle].is_empty())
+            .map(|(_, entry_point)| shader_stage(entry_point.stage))
+            .collect::<Vec<_>>();
+        let visibility = match stages.split_first() {
+            None => "wgpu::ShaderStages::NONE".to_owned(),
+            Some((first, rest)) => rest.iter()
+                .fold(first.clone(), |visibility, stage| format!("{visibility}.union({stage})")),
+        };
+
+        layouts.entry(binding.group).or_default().push(LayoutEntry {
+            binding: binding.binding,
+            visibility,
+            ty,
+        });
+    }
+
+    for entries in layouts.values_mut() {
+        entries.sort();
+    }
+
+    layouts
+}
+
+/// The `wgpu::ShaderStages` of a naga stage, they have the same names in upper case
+pub(crate) fn shader_stage(stage: naga::ShaderStage) -> String {
+    format!("wgpu::ShaderStages::{}", format!("{stage:?}").to_uppercase())
+}
+
+/// The rust code for the `wgpu::BindingType` of `global`, `None` if it's not supported
+fn binding_type(module: &naga::Module, global: &naga::GlobalVariable) -> Option<String> {
+    use naga::{AddressSpace, ImageClass, ScalarKind, StorageAccess, TypeInner};
+
+    match global.space {
+        AddressSpace::Uniform => return Some(
+            "wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None }".to_owned()
+        ),
+        AddressSpace::Storage { access } => return Some(format!(
+            "wgpu::BindingType::Buffer {{ ty: wgpu::BufferBindingType::Storage {{ read_only: {} }}, has_dynamic_offset: false, min_binding_size: None }}",
+            !access.contains(StorageAccess::STORE),
+        )),
+        AddressSpace::Handle => (),
+        _ => return None,
+    }
+
+    match &module.types[global.ty].inner {
+        TypeInner::Sampler { comparison } => Some(format!(
+            "wgpu::BindingType::Sampler(wgpu::SamplerBindingType::{})",
+            if *comparison { "Comparison" } else { "Filtering" },
+        )),
+        TypeInner::Image { dim, arrayed, class } => {
+            let view_dimension = view_dimension(*dim, *arrayed)?;
+
+            match class {
+                ImageClass::Sampled { kind, multi } => {
+                    let sample_type = match kind {
+                        ScalarKind::Float => "wgpu::TextureSampleType::Float { filterable: true }",
+                        ScalarKind::Sint => "wgpu::TextureSampleType::Sint",
+                        ScalarKind::Uint => "wgpu::TextureSampleType::Uint",
+                        _ => return None,
+                    };
+                    Some(format!(
+                        "wgpu::BindingType::Texture {{ sample_type: {sample_type}, view_dimension: {view_dimension}, multisampled: {multi} }}"
+                    ))
+                }
+                ImageClass::Depth { multi } => Some(format!(
+                    "wgpu::BindingType::Texture {{ sample_type: wgpu::TextureSampleType::Depth, view_dimension: {view_dimension}, multisampled: {multi} }}"
+                )),
+                ImageClass::Storage { format, access } => {
+                    let access = if access.contains(StorageAccess::LOAD | StorageAccess::STORE) {
+                        "ReadWrite"
+                    } else if access.contains(StorageAccess::STORE) {
+                        "WriteOnly"
+                    } else {
+                        "ReadOnly"
+                    };
+                    // `TextureFormat` has the same names as naga's storage formats
+                    Some(format!(
+                        "wgpu::BindingType::StorageTexture {{ access: wgpu::StorageTextureAccess::{access}, format: wgpu::TextureFormat::{format:?}, view_dimension: {view_dimension} }}"
+                    ))
+                }
+                #[allow(unreachable_patterns)]
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// The rust code for the `wgpu::TextureViewDimension` of an image
+fn view_dimension(dim: naga::ImageDimension, arrayed: bool) -> Option<&'static str> {
+    use naga::ImageDimension;
+
+    Some(match (dim, arrayed) {
+        (ImageDimension::D1, false) => "wgpu::TextureViewDimension::D1",
+        (ImageDimension::D2, false) => "wgpu::TextureViewDimension::D2",
+        (ImageDimension::D2, true) => "wgpu::TextureViewDimension::D2Array",
+        (ImageDimension::D3, false) => "wgpu::TextureViewDimension::D3",
+        (ImageDimension::Cube, false) => "wgpu::TextureViewDimension::Cube",
+        (ImageDimension::Cube, true) => "wgpu::TextureViewDimension::CubeArray",
+        _ => return None,
+    })
+}
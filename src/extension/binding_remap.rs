@@ -0,0 +1,120 @@
+#![cfg(feature = "binding_remap_ext")]
+
+//! Rewrite the `@group`/`@binding` indices of the built shaders to another binding convention
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use wesl::{BasicSourceMap, ModulePath};
+
+use crate::{NagaModule, WeslBuildError, WeslBuildExtension};
+use crate::resolver::module_path;
+
+/// A `(group, binding)` pair
+pub type Slot = (u32, u32);
+
+#[derive(Debug, thiserror::Error)]
+pub enum BindingRemapError {
+    #[error("`{first}` and `{second}` of `{mod_path}` are both remapped to @group({}) @binding({})", .slot.0, .slot.1)]
+    Collision { mod_path: ModulePath, first: String, second: String, slot: Slot },
+}
+
+/// Rewrite the `@group`/`@binding` of the resources of the built shaders with a mapping,
+/// so shaders written for one binding convention can be built for an engine with another slot layout
+///
+/// A resource takes the first mapping that matches it:
+/// 1. the [slots of its module](Self::with_module_binding)
+/// 2. the [slots of every module](Self::with_binding)
+/// 3. the [groups](Self::with_group), the binding is kept
+///
+/// The build fails if two resources of a shader end up in the same slot. The built WGSL is rewritten,
+/// so extensions running after this one, like the bindings, see the new slots.
+#[derive(Debug, Clone, Default)]
+pub struct BindingRemapExtension {
+    groups: BTreeMap<u32, u32>,
+    bindings: BTreeMap<Slot, Slot>,
+    /// The slots of each module, by its components
+    module_bindings: BTreeMap<Vec<String>, BTreeMap<Slot, Slot>>,
+}
+
+impl BindingRemapExtension {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Move every resource in `@group(from)` to `@group(to)`
+    pub fn with_group(mut self, from: u32, to: u32) -> Self {
+        self.groups.insert(from, to);
+        self
+    }
+
+    /// Move the resource at `from` to `to`, in every module
+    pub fn with_binding(mut self, from: Slot, to: Slot) -> Self {
+        self.bindings.insert(from, to);
+        self
+    }
+
+    /// Move the resource at `from` to `to` in the shader `module`, a path like `blur/gaussian` or `blur::gaussian`
+    pub fn with_module_binding(mut self, module: &str, from: Slot, to: Slot) -> Result<Self, WeslBuildError> {
+        self.module_bindings.entry(module_path(module)?.components).or_default().insert(from, to);
+        Ok(self)
+    }
+
+    /// The slot `slot` of a resource in `mod_path` is remapped to
+    pub fn remap(&self, mod_path: &ModulePath, slot: Slot) -> Slot {
+        self.module_bindings.get(&mod_path.components)
+            .and_then(|bindings| bindings.get(&slot))
+            .or_else(|| self.bindings.get(&slot))
+            .copied()
+            .unwrap_or_else(|| (self.groups.get(&slot.0).copied().unwrap_or(slot.0), slot.1))
+    }
+}
+
+impl<WeslResolver: wesl::Resolver> WeslBuildExtension<WeslResolver> for BindingRemapExtension {
+    fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+        "BindingRemapExtension".into()
+    }
+
+    fn init_root(
+        &mut self,
+        _shader_path: &str,
+        _res: &mut wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    fn enter_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+    fn exit_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+
+    fn post_parse(&mut self, wesl_path: &ModulePath, module: &mut NagaModule) -> Result<(), Box<dyn std::error::Error>> {
+        // the resources by their new slot, with the new slots of the moved ones
+        let mut slots = BTreeMap::new();
+        let mut moved = Vec::new();
+        for (handle, global) in module.global_variables.iter() {
+            let Some(binding) = &global.binding else { continue };
+            let slot = self.remap(wesl_path, (binding.group, binding.binding));
+            let name = global.name.clone().unwrap_or_default();
+            if let Some(first) = slots.insert(slot, name.clone()) {
+                return Err(Box::new(BindingRemapError::Collision { mod_path: wesl_path.clone(), first, second: name, slot }));
+            }
+            if slot != (binding.group, binding.binding) {
+                moved.push((handle, slot));
+            }
+        }
+
+        // only accessed mutably if a slot changed, so unchanged shaders aren't written back
+        for (handle, (group, binding)) in moved {
+            module.global_variables[handle].binding = Some(naga::ResourceBinding { group, binding });
+        }
+        Ok(())
+    }
+
+    fn post_build(
+        &mut self,
+        _mod_path: &ModulePath,
+        _wgsl_built_path: &str,
+        _source_map: &Option<BasicSourceMap>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+}
@@ -0,0 +1,187 @@
+#![cfg(feature = "binding_usage_ext")]
+
+//! Report which entry points use each binding of the built shaders, and strip the unused ones
+
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use wesl::{BasicSourceMap, ModulePath};
+
+use crate::{NagaModule, WeslBuildExtension, artifacts};
+use crate::build_out_dir;
+use crate::messages::{self, BuildMessage};
+
+/// A binding of a built shader and the entry points using it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BindingUsage {
+    pub group: u32,
+    pub binding: u32,
+    /// The name of the global variable
+    pub name: String,
+    /// The entry points using it, directly or through the functions they call
+    pub entry_points: Vec<String>,
+}
+
+impl BindingUsage {
+    /// The bindings of `module` sorted by group and binding, with the entry points using them
+    pub fn of_module(module: &naga::Module) -> Result<Vec<Self>, naga::WithSpan<naga::valid::ValidationError>> {
+        let info = naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::all(),
+        ).validate(module)?;
+
+        let mut usages = module.global_variables.iter()
+            .filter_map(|(handle, global)| global.binding.as_ref().map(|binding| Self {
+                group: binding.group,
+                binding: binding.binding,
+                name: global.name.clone().unwrap_or_default(),
+                entry_points: module.entry_points.iter().enumerate()
+                    .filter(|(index, _)| !info.get_entry_point(*index)[handle].is_empty())
+                    .map(|(_, entry_point)| entry_point.name.clone())
+                    .collect(),
+            }))
+            .collect::<Vec<_>>();
+        usages.sort_by_key(|usage| (usage.group, usage.binding));
+        Ok(usages)
+    }
+
+    /// Whether no entry point uses the binding
+    pub fn is_unused(&self) -> bool {
+        self.entry_points.is_empty()
+    }
+}
+
+/// Report the bindings of every built shader with the entry points using them, and the bindings no entry point uses
+///
+/// Unused bindings are reported as warnings, or stripped from the built WGSL with
+/// [`with_strip_unused`](Self::with_strip_unused) so the bind group layouts generated from it are minimal,
+/// e.g. to stay under the binding limits of mobile GPUs. Once all shaders are built the report is written
+/// to `binding_usage.txt` in the output dir by default:
+/// ```text
+/// lights
+///     @group(0) @binding(0) camera: vs_main, fs_main
+///     @group(0) @binding(1) shadow_map: unused, stripped
+/// ```
+pub struct BindingUsageExtension {
+    strip_unused: bool,
+    /// Where to write the report, `None` for `binding_usage.txt` in the output dir
+    report_path: Option<PathBuf>,
+    /// The bindings of every shader built so far, before any were stripped
+    usages: Vec<(ModulePath, Vec<BindingUsage>)>,
+}
+
+impl Default for BindingUsageExtension {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BindingUsageExtension {
+    pub fn new() -> Self {
+        Self {
+            strip_unused: false,
+            report_path: None,
+            usages: Vec::new(),
+        }
+    }
+
+    /// Remove the bindings no entry point uses from the built WGSL, with the declarations only they used
+    pub fn with_strip_unused(mut self, strip_unused: bool) -> Self {
+        self.strip_unused = strip_unused;
+        self
+    }
+
+    /// Write the report to `report_path` instead of `binding_usage.txt` in the output dir
+    pub fn with_report_path(mut self, report_path: impl AsRef<Path>) -> Self {
+        self.report_path = Some(report_path.as_ref().to_owned());
+        self
+    }
+
+    /// The bindings of every shader built so far
+    pub fn usages(&self) -> &[(ModulePath, Vec<BindingUsage>)] {
+        &self.usages
+    }
+
+    fn report(&self) -> String {
+        let mut report = String::new();
+        for (mod_path, usages) in &self.usages {
+            writeln!(report, "{}", mod_path.components.join("::")).expect("writing to a String can't fail");
+            for usage in usages {
+                let used_by = match (usage.is_unused(), self.strip_unused) {
+                    (true, true) => "unused, stripped".to_owned(),
+                    (true, false) => "unused".to_owned(),
+                    (false, _) => usage.entry_points.join(", "),
+                };
+                writeln!(report, "    @group({}) @binding({}) {}: {used_by}", usage.group, usage.binding, usage.name)
+                    .expect("writing to a String can't fail");
+            }
+        }
+        report
+    }
+}
+
+impl<WeslResolver: wesl::Resolver> WeslBuildExtension<WeslResolver> for BindingUsageExtension {
+    fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+        "BindingUsageExtension".into()
+    }
+
+    fn init_root(
+        &mut self,
+        _shader_path: &str,
+        _res: &mut wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.usages.clear();
+        Ok(())
+    }
+
+    fn exit_root(
+        &mut self,
+        _shader_root_path: &str,
+        _res: &wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let report_path = match &self.report_path {
+            Some(report_path) => report_path.clone(),
+            None => build_out_dir()?.join("binding_usage.txt"),
+        };
+        if !crate::is_check() {
+            artifacts::write(&report_path, self.report())?;
+        }
+
+        #[cfg(feature = "logging")]
+        log::info!("binding usage of {} shaders, see: {}", self.usages.len(), report_path.display());
+
+        Ok(())
+    }
+
+    fn enter_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+    fn exit_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+
+    fn post_parse(&mut self, wesl_path: &ModulePath, module: &mut NagaModule) -> Result<(), Box<dyn std::error::Error>> {
+        let usages = BindingUsage::of_module(module)?;
+
+        let unused = usages.iter().filter(|usage| usage.is_unused()).collect::<Vec<_>>();
+        if !unused.is_empty() && self.strip_unused {
+            // removes everything no entry point uses, shaders without unused bindings are left as is so they aren't written back
+            naga::compact::compact(module, naga::compact::KeepUnused::No);
+        } else {
+            for usage in unused {
+                messages::report(&BuildMessage::warning(format!(
+                    "@group({}) @binding({}) `{}` is not used by any entry point",
+                    usage.group, usage.binding, usage.name,
+                )).with_module(wesl_path))?;
+            }
+        }
+
+        self.usages.push((wesl_path.clone(), usages));
+        Ok(())
+    }
+
+    fn post_build(
+        &mut self,
+        _mod_path: &ModulePath,
+        _wgsl_built_path: &str,
+        _source_map: &Option<BasicSourceMap>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+}
@@ -0,0 +1,305 @@
+#![cfg(feature = "buffer_size_ext")]
+
+//! Report the size and alignment of the uniform and storage buffers of every shader
+
+use std::fmt::{Display, Write as _};
+use std::path::Path;
+
+use wesl::{BasicSourceMap, ModulePath};
+
+use crate::WeslBuildExtension;
+use crate::artifacts;
+use crate::build_out_dir;
+use crate::messages::{self, BuildMessage};
+
+/// The default `maxUniformBufferBindingSize` of WebGPU, 64KiB
+pub const WEBGPU_MAX_UNIFORM_BYTES: u32 = 65536;
+/// The default `maxStorageBufferBindingSize` of WebGPU, 128MiB
+pub const WEBGPU_MAX_STORAGE_BYTES: u32 = 134_217_728;
+
+/// The address space of a [`BufferSize`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BufferSpace {
+    Uniform,
+    Storage,
+}
+
+impl Display for BufferSpace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            BufferSpace::Uniform => "uniform",
+            BufferSpace::Storage => "storage",
+        })
+    }
+}
+
+/// The layout of a uniform or storage buffer of a shader, by naga's layout rules
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BufferSize {
+    pub mod_path: ModulePath,
+    pub group: u32,
+    pub binding: u32,
+    /// The name of the global variable
+    pub name: String,
+    /// The name of its type, e.g. `Camera` or `array<f32>`
+    pub type_name: String,
+    pub space: BufferSpace,
+    /// The size in bytes, without the elements of a runtime-sized array
+    pub size: u32,
+    pub alignment: u32,
+    /// The size of each element of a runtime-sized array at its end
+    pub element_stride: Option<u32>,
+}
+
+impl BufferSize {
+    /// The buffers of `module`, sorted by group and binding
+    pub fn of_module(mod_path: &ModulePath, module: &naga::Module) -> Result<Vec<Self>, naga::proc::LayoutError> {
+        use naga::{AddressSpace, ArraySize, TypeInner};
+
+        let mut layouter = naga::proc::Layouter::default();
+        layouter.update(module.to_ctx())?;
+
+        let mut buffers = Vec::new();
+        for (_, global) in module.global_variables.iter() {
+            let space = match global.space {
+                AddressSpace::Uniform => BufferSpace::Uniform,
+                AddressSpace::Storage { .. } => BufferSpace::Storage,
+                _ => continue,
+            };
+            let Some(binding) = &global.binding else { continue };
+
+            let ty = &module.types[global.ty];
+            let layout = layouter[global.ty];
+            // the runtime-sized array, the type itself or the last member of a struct, and its offset
+            let runtime_array = match &ty.inner {
+                TypeInner::Array { size: ArraySize::Dynamic, stride, .. } => Some((0, *stride)),
+                TypeInner::Struct { members, .. } => members.last().and_then(|member| match module.types[member.ty].inner {
+                    TypeInner::Array { size: ArraySize::Dynamic, stride, .. } => Some((member.offset, stride)),
+                    _ => None,
+                }),
+                _ => None,
+            };
+
+            buffers.push(Self {
+                mod_path: mod_path.clone(),
+                group: binding.group,
+                binding: binding.binding,
+                name: global.name.clone().unwrap_or_default(),
+                type_name: ty.name.clone().unwrap_or_else(|| unnamed_type_name(module, &ty.inner)),
+                space,
+                size: runtime_array.map_or(layout.size, |(offset, _)| offset),
+                // the smallest multiple of the alignment
+                alignment: layout.alignment.round_up(1),
+                element_stride: runtime_array.map(|(_, stride)| stride),
+            });
+        }
+
+        buffers.sort_by_key(|buffer| (buffer.group, buffer.binding));
+        Ok(buffers)
+    }
+
+    /// The size as written in the report, e.g. `16 + 32n` for a runtime-sized array of 32 byte elements
+    fn size_text(&self) -> String {
+        match self.element_stride {
+            Some(stride) => format!("{} + {stride}n", self.size),
+            None => self.size.to_string(),
+        }
+    }
+}
+
+/// A short name of a type without a name, e.g. `array<Light>` or `vec4`
+fn unnamed_type_name(module: &naga::Module, inner: &naga::TypeInner) -> String {
+    use naga::TypeInner;
+
+    match inner {
+        TypeInner::Array { base, .. } | TypeInner::BindingArray { base, .. } => {
+            let base = &module.types[*base];
+            format!("array<{}>", base.name.clone().unwrap_or_else(|| unnamed_type_name(module, &base.inner)))
+        }
+        TypeInner::Scalar(scalar) | TypeInner::Atomic(scalar) => format!("{:?}{}", scalar.kind, scalar.width * 8).to_lowercase(),
+        TypeInner::Vector { size, .. } => format!("vec{}", *size as u8),
+        TypeInner::Matrix { columns, rows, .. } => format!("mat{}x{}", *columns as u8, *rows as u8),
+        _ => "?".to_owned(),
+    }
+}
+
+/// Buffers that don't fit the limits of a [`BufferSizeExtension`]
+#[derive(Debug, thiserror::Error)]
+#[error("`{mod_path}` has buffers over the binding size limit: {}", .buffers.iter().map(|buffer| format!("`{}` ({} bytes)", buffer.name, buffer.size)).collect::<Vec<_>>().join(", "))]
+pub struct BufferSizeError {
+    pub mod_path: ModulePath,
+    pub buffers: Vec<BufferSize>,
+}
+
+/// Report the byte size and alignment of every uniform and storage buffer of every built shader,
+/// and flag the buffers over the binding size limits of the target devices
+///
+/// The limits default to the WebGPU minimums, 64KiB for uniform and 128MiB for storage buffers, so shaders that
+/// run everywhere stay that way. Oversized buffers are reported as warnings, or fail the build with
+/// [`with_deny_oversized`](Self::with_deny_oversized). Once all shaders are built the report is written to
+/// `buffer_sizes.txt` in the output dir:
+/// ```text
+/// module  | group | binding | name   | type   | space   | size | align
+/// ------- | ----- | ------- | ------ | ------ | ------- | ---- | -----
+/// lights  | 0     | 0       | camera | Camera | uniform | 80   | 16
+/// ```
+pub struct BufferSizeExtension {
+    max_uniform_bytes: u32,
+    max_storage_bytes: u32,
+    deny_oversized: bool,
+    buffers: Vec<BufferSize>,
+}
+
+impl Default for BufferSizeExtension {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BufferSizeExtension {
+    /// Flag buffers over the WebGPU limits
+    pub fn new() -> Self {
+        Self {
+            max_uniform_bytes: WEBGPU_MAX_UNIFORM_BYTES,
+            max_storage_bytes: WEBGPU_MAX_STORAGE_BYTES,
+            deny_oversized: false,
+            buffers: Vec::new(),
+        }
+    }
+
+    /// The `maxUniformBufferBindingSize` of the target devices
+    pub fn with_max_uniform_bytes(mut self, max_uniform_bytes: u32) -> Self {
+        self.max_uniform_bytes = max_uniform_bytes;
+        self
+    }
+
+    /// The `maxStorageBufferBindingSize` of the target devices
+    pub fn with_max_storage_bytes(mut self, max_storage_bytes: u32) -> Self {
+        self.max_storage_bytes = max_storage_bytes;
+        self
+    }
+
+    /// Fail the build on buffers over the limits instead of warning
+    pub fn with_deny_oversized(mut self, deny_oversized: bool) -> Self {
+        self.deny_oversized = deny_oversized;
+        self
+    }
+
+    /// The buffers of every shader built so far
+    pub fn buffers(&self) -> &[BufferSize] {
+        &self.buffers
+    }
+
+    /// Whether `buffer` is over the limit of its address space, runtime-sized arrays count with no elements
+    pub fn is_oversized(&self, buffer: &BufferSize) -> bool {
+        buffer.size > self.max_bytes(buffer.space)
+    }
+
+    /// The binding size limit of `space`
+    fn max_bytes(&self, space: BufferSpace) -> u32 {
+        match space {
+            BufferSpace::Uniform => self.max_uniform_bytes,
+            BufferSpace::Storage => self.max_storage_bytes,
+        }
+    }
+
+    fn report(&self) -> String {
+        let header = ["module", "group", "binding", "name", "type", "space", "size", "align"].map(str::to_owned);
+        let rows = self.buffers.iter().map(|buffer| [
+            buffer.mod_path.to_string(),
+            buffer.group.to_string(),
+            buffer.binding.to_string(),
+            buffer.name.clone(),
+            buffer.type_name.clone(),
+            buffer.space.to_string(),
+            match self.is_oversized(buffer) {
+                true => format!("{} (over {})", buffer.size_text(), self.max_bytes(buffer.space)),
+                false => buffer.size_text(),
+            },
+            buffer.alignment.to_string(),
+        ]).collect::<Vec<_>>();
+
+        let mut widths = header.clone().map(|cell| cell.len());
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.len());
+            }
+        }
+        let separator = widths.map(|width| "-".repeat(width));
+
+        let mut report = String::new();
+        for row in [&header, &separator].into_iter().chain(&rows) {
+            let line = row.iter().zip(widths)
+                .map(|(cell, width)| format!("{cell:<width$}"))
+                .collect::<Vec<_>>()
+                .join(" | ");
+            writeln!(report, "{}", line.trim_end()).expect("writing to a String can't fail");
+        }
+
+        report
+    }
+}
+
+impl<WeslResolver: wesl::Resolver> WeslBuildExtension<WeslResolver> for BufferSizeExtension {
+    fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+        "BufferSizeExtension".into()
+    }
+
+    fn reads_built_wgsl(&self) -> bool {
+        true
+    }
+
+    fn init_root(
+        &mut self,
+        _shader_path: &str,
+        _res: &mut wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.buffers.clear();
+        Ok(())
+    }
+
+    fn exit_root(
+        &mut self,
+        _shader_root_path: &str,
+        _res: &wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let report = self.report();
+        #[cfg(feature = "logging")]
+        log::info!("buffer sizes:\n{report}");
+        if !crate::is_check() {
+            artifacts::write(build_out_dir()?.join("buffer_sizes.txt"), report)?;
+        }
+
+        Ok(())
+    }
+
+    fn enter_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+    fn exit_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+
+    fn post_build(
+        &mut self,
+        mod_path: &ModulePath,
+        wgsl_built_path: &str,
+        _source_map: &Option<BasicSourceMap>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let module = naga::front::wgsl::parse_str(&artifacts::read_to_string(wgsl_built_path)?)?;
+        let buffers = BufferSize::of_module(mod_path, &module)?;
+
+        let oversized = buffers.iter().filter(|buffer| self.is_oversized(buffer)).cloned().collect::<Vec<_>>();
+        self.buffers.extend(buffers);
+        if oversized.is_empty() {
+            return Ok(());
+        }
+        if self.deny_oversized {
+            return Err(Box::new(BufferSizeError { mod_path: mod_path.clone(), buffers: oversized }));
+        }
+
+        for buffer in oversized {
+            messages::report(&BuildMessage::warning(format!(
+                "the {} buffer `{}` is {} bytes, over the limit of {} bytes",
+                buffer.space, buffer.name, buffer.size, self.max_bytes(buffer.space),
+            )).with_module(mod_path))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,231 @@
+#![cfg(feature = "command_ext")]
+
+//! Run an external program on the built shaders, for toolchains wesl_build doesn't support itself
+
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Output};
+
+use wesl::{BasicSourceMap, ModulePath};
+
+use crate::context::try_with_build_context;
+use crate::messages::{self, BuildMessage};
+use crate::{WeslBuildExtension, artifacts, with_build_context};
+
+/// The placeholders of the arguments
+const PLACEHOLDERS: &[&str] = &["input", "module", "out_dir"];
+
+/// When a [`CommandExtension`] runs its program
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RunOn {
+    /// In `post_build`, on the WGSL of every built shader
+    #[default]
+    EachShader,
+    /// In `exit_root`, once on the output dir after all shaders are built
+    OutDir,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CommandError {
+    #[error("unknown placeholder `{{{placeholder}}}` in the argument `{arg}`, the placeholders are {{input}}, {{module}} and {{out_dir}}")]
+    UnknownPlaceholder { arg: String, placeholder: String },
+    #[error("`{{module}}` in the argument `{arg}` is only set when the command runs on each shader")]
+    NoModule { arg: String },
+    #[error("OUT_DIR is not set, so `{{out_dir}}` can't be filled in")]
+    NoOutDir,
+    #[error("failed to run `{program}`: {error}")]
+    Spawn { program: String, error: std::io::Error },
+    #[error("`{command}` failed with {status}{}", output_excerpt(.stdout, .stderr))]
+    Failed { command: String, status: ExitStatus, stdout: String, stderr: String },
+}
+
+/// The output of a failed command, to show after its status
+fn output_excerpt(stdout: &str, stderr: &str) -> String {
+    [stdout, stderr].iter()
+        .map(|output| output.trim_end())
+        .filter(|output| !output.is_empty())
+        .map(|output| format!("\n{output}"))
+        .collect()
+}
+
+/// Run an external program on every built shader, or once on the output dir, e.g. a validator or a cross compiler
+///
+/// The arguments are templates, `{input}` is the built WGSL file (the output dir with [`RunOn::OutDir`]),
+/// `{module}` the module of the shader, e.g. `passes::blur`, and `{out_dir}` the output dir of the build:
+/// ```no_run
+/// # use wesl_build::extension::command::{CommandExtension, RunOn};
+/// // validate every shader with naga's CLI
+/// CommandExtension::new("naga", ["{input}"]);
+/// // pack the output dir once all shaders are built
+/// CommandExtension::new("tar", ["-czf", "shaders.tar.gz", "-C", "{out_dir}", "."]).with_run_on(RunOn::OutDir);
+/// ```
+/// The program runs in the working dir of the build, it's not run through a shell. A non-zero exit fails the build
+/// with its stdout and stderr, otherwise each line it writes to stderr is reported as a warning.
+/// In a [check](`crate::is_check`) the built WGSL is written to the scratch dir of the [`BuildContext`](`crate::BuildContext`)
+/// for the program to read, and [`RunOn::OutDir`] commands are skipped.
+#[derive(Debug, Clone)]
+pub struct CommandExtension {
+    name: String,
+    program: String,
+    args: Vec<String>,
+    run_on: RunOn,
+    current_dir: Option<PathBuf>,
+    out_dir: Option<PathBuf>,
+}
+
+impl CommandExtension {
+    pub fn new(program: impl Into<String>, args: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            name: "CommandExtension".to_owned(),
+            program: program.into(),
+            args: args.into_iter().map(Into::into).collect(),
+            run_on: RunOn::default(),
+            current_dir: None,
+            out_dir: None,
+        }
+    }
+
+    /// The name of the extension in errors and for ordering, `CommandExtension` by default, set it when a build runs several commands
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    pub fn with_run_on(mut self, run_on: RunOn) -> Self {
+        self.run_on = run_on;
+        self
+    }
+
+    /// Run the program in `current_dir` instead of the working dir of the build
+    pub fn with_current_dir(mut self, current_dir: impl Into<PathBuf>) -> Self {
+        self.current_dir = Some(current_dir.into());
+        self
+    }
+
+    /// The arguments with the placeholders filled in
+    fn expand_args(&self, input: &Path, module: Option<&ModulePath>) -> Result<Vec<String>, CommandError> {
+        let out_dir = self.out_dir.as_deref().map(|out_dir| out_dir.display().to_string());
+        self.args.iter()
+            .map(|arg| {
+                let mut expanded = arg.clone();
+                if expanded.contains("{input}") {
+                    expanded = expanded.replace("{input}", &input.display().to_string());
+                }
+                if expanded.contains("{module}") {
+                    let module = module.ok_or_else(|| CommandError::NoModule { arg: arg.clone() })?;
+                    expanded = expanded.replace("{module}", &module.components.join("::"));
+                }
+                if expanded.contains("{out_dir}") {
+                    expanded = expanded.replace("{out_dir}", out_dir.as_deref().ok_or(CommandError::NoOutDir)?);
+                }
+                Ok(expanded)
+            })
+            .collect()
+    }
+
+    /// Run the program, reporting what it writes to stderr
+    fn run(&self, input: &Path, module: Option<&ModulePath>) -> Result<(), Box<dyn std::error::Error>> {
+        let args = self.expand_args(input, module)?;
+        let mut command = Command::new(&self.program);
+        command.args(&args);
+        if let Some(current_dir) = &self.current_dir {
+            command.current_dir(current_dir);
+        }
+
+        let Output { status, stdout, stderr } = command.output()
+            .map_err(|error| CommandError::Spawn { program: self.program.clone(), error })?;
+        let (stdout, stderr) = (String::from_utf8_lossy(&stdout), String::from_utf8_lossy(&stderr));
+        if !status.success() {
+            return Err(Box::new(CommandError::Failed {
+                command: std::iter::once(self.program.as_str()).chain(args.iter().map(String::as_str)).collect::<Vec<_>>().join(" "),
+                status,
+                stdout: stdout.into_owned(),
+                stderr: stderr.into_owned(),
+            }));
+        }
+
+        #[cfg(feature = "logging")]
+        if !stdout.trim().is_empty() {
+            log::debug!("{}: {}", self.program, stdout.trim_end());
+        }
+        for line in stderr.lines().filter(|line| !line.trim().is_empty()) {
+            let message = BuildMessage::warning(format!("{}: {line}", self.program));
+            let message = match module {
+                Some(module) => message.with_module(module),
+                None => message,
+            };
+            messages::report(&message)?;
+        }
+        Ok(())
+    }
+}
+
+impl<WeslResolver: wesl::Resolver> WeslBuildExtension<WeslResolver> for CommandExtension {
+    fn name<'n>(&self) -> Cow<'n, str> {
+        self.name.clone().into()
+    }
+
+    fn reads_built_wgsl(&self) -> bool {
+        self.run_on == RunOn::EachShader
+    }
+
+    fn init_root(
+        &mut self,
+        _shader_path: &str,
+        _res: &mut wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        // catch typos before any shader is built
+        for arg in &self.args {
+            let placeholders = arg.split('{').skip(1).filter_map(|rest| rest.split_once('}')).map(|(name, _)| name);
+            for placeholder in placeholders {
+                let is_placeholder = !placeholder.is_empty() && placeholder.chars().all(|c| c.is_ascii_lowercase() || c == '_');
+                if is_placeholder && !PLACEHOLDERS.contains(&placeholder) {
+                    return Err(Box::new(CommandError::UnknownPlaceholder { arg: arg.clone(), placeholder: placeholder.to_owned() }));
+                }
+            }
+        }
+
+        self.out_dir = with_build_context(|ctx| ctx.out_dir()).flatten();
+        Ok(())
+    }
+
+    fn exit_root(
+        &mut self,
+        _shader_root_path: &str,
+        _res: &wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if self.run_on != RunOn::OutDir || crate::is_check() {
+            return Ok(());
+        }
+        let out_dir = self.out_dir.clone().ok_or(CommandError::NoOutDir)?;
+        self.run(&out_dir, None)
+    }
+
+    fn enter_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+    fn exit_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+
+    fn post_build(
+        &mut self,
+        mod_path: &ModulePath,
+        wgsl_built_path: &str,
+        _source_map: &Option<BasicSourceMap>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if self.run_on != RunOn::EachShader {
+            return Ok(());
+        }
+
+        // the artifacts of a check are in memory, the program needs a file
+        let input = if crate::is_check() {
+            let file_name = Path::new(wgsl_built_path).file_name().unwrap_or_default().to_owned();
+            let wgsl = artifacts::read(wgsl_built_path)?;
+            try_with_build_context(|ctx| -> std::io::Result<PathBuf> {
+                let input = ctx.scratch_dir()?.join(file_name);
+                std::fs::write(&input, wgsl)?;
+                Ok(input)
+            })??
+        } else {
+            PathBuf::from(wgsl_built_path)
+        };
+        self.run(&input, Some(mod_path))
+    }
+}
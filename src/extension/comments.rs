@@ -0,0 +1,34 @@
+//! Stripping the comments of WGSL sources, for the extensions that scan them as text
+
+/// Replace `//` and `/* */` comments with spaces
+pub(crate) fn strip_comments(source: &str) -> String {
+    let mut stripped = String::with_capacity(source.len());
+    let mut chars = source.chars().peekable();
+    // block comments nest in WGSL
+    let mut block_depth = 0_usize;
+
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('/', Some('*')) => {
+                chars.next();
+                block_depth += 1;
+            }
+            ('*', Some('/')) if block_depth > 0 => {
+                chars.next();
+                block_depth -= 1;
+            }
+            ('/', Some('/')) if block_depth == 0 => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        stripped.push('\n');
+                        break;
+                    }
+                }
+            }
+            _ if block_depth > 0 => (),
+            _ => stripped.push(c),
+        }
+    }
+
+    stripped
+}
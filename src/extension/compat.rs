@@ -0,0 +1,291 @@
+//! Versions of the extension API, and [`ExtensionCompat`] to keep extensions written against the first version building
+//!
+//! New hooks are added to [`WeslBuildExtension`] with a default, so extensions keep compiling across minor releases,
+//! and bump the minor version of [`ExtensionApiVersion::CURRENT`]. A hook that changes its signature or meaning
+//! bumps the major version, an extension that implements [`WeslBuildExtensionV1`] instead keeps compiling,
+//! as that trait never changes.
+//!
+//! Builds used to run with wesl's [`StandardResolver`] and now run with the [`BuildResolver`],
+//! [`StandardResolverCompat`] runs an extension written for the former in them.
+
+use std::{borrow::Cow, error::Error, fmt, path::Path};
+
+use wesl::{BasicSourceMap, CompileOptions, ModulePath, Resolver, StandardResolver, Wesl};
+
+use super::WeslBuildExtension;
+use crate::context::try_with_build_context;
+use crate::{BuildResolver, ModuleGraph, PlannedShader, WeslBuildError};
+
+/// A version of the extension API, an extension needs one with the same major version and at least its minor version
+///
+/// * `1.0` - the hooks `init_root`, `exit_root`, `enter_mod`, `exit_mod` and `post_build`, see [`WeslBuildExtensionV1`]
+/// * `1.1` - every other hook of [`WeslBuildExtension`], and a default for every hook
+/// * `1.2` - `artifact_name`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ExtensionApiVersion {
+    pub major: u16,
+    pub minor: u16,
+}
+
+impl ExtensionApiVersion {
+    /// The version of [`WeslBuildExtensionV1`]
+    pub const V1_0: Self = Self::new(1, 0);
+    /// The version of this wesl_build's [`WeslBuildExtension`]
+    pub const CURRENT: Self = Self::new(1, 2);
+
+    pub const fn new(major: u16, minor: u16) -> Self {
+        Self { major, minor }
+    }
+
+    /// Whether extensions written against this version run in this wesl_build
+    pub fn is_supported(self) -> bool {
+        self.major == Self::CURRENT.major && self.minor <= Self::CURRENT.minor
+    }
+}
+
+impl fmt::Display for ExtensionApiVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// Fail with [`WeslBuildError::UnsupportedExtensionApi`] if an extension needs a version of the API this wesl_build lacks
+pub(crate) fn check_api_versions(extensions: &[Box<dyn WeslBuildExtension<BuildResolver>>]) -> Result<(), WeslBuildError> {
+    for ext in extensions {
+        let version = ext.extension_api_version();
+        if !version.is_supported() {
+            return Err(WeslBuildError::UnsupportedExtensionApi { extension_name: ext.name().into_owned(), version });
+        }
+    }
+    Ok(())
+}
+
+/// The hooks of version [`1.0`](ExtensionApiVersion::V1_0) of the extension API, run it in a build with [`ExtensionCompat`]
+///
+/// Unlike [`WeslBuildExtension`] this trait never changes, for extensions that must build with every wesl_build
+/// whose extension API has major version 1.
+/// The hooks run as the hooks of the same name of [`WeslBuildExtension`] do.
+pub trait WeslBuildExtensionV1<WeslResolver: Resolver> {
+    /// The name to report in errors as the source extension
+    fn name<'n>(&self) -> Cow<'n, str>;
+
+    fn init_root(&mut self, shader_root_path: &str, res: &mut Wesl<WeslResolver>) -> Result<(), Box<dyn Error>>;
+
+    fn exit_root(&mut self, _shader_root_path: &str, _res: &Wesl<WeslResolver>) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn enter_mod(&mut self, dir_path: &Path) -> Result<(), Box<dyn Error>>;
+
+    fn exit_mod(&mut self, dir_path: &Path) -> Result<(), Box<dyn Error>>;
+
+    fn post_build(
+        &mut self,
+        wesl_path: &ModulePath,
+        wgsl_built_path: &str,
+        source_map: &Option<BasicSourceMap>,
+    ) -> Result<(), Box<dyn Error>>;
+}
+
+/// Runs a [`WeslBuildExtensionV1`] as a [`WeslBuildExtension`], the hooks added since do nothing
+///
+/// ## Example
+/// ```
+/// use wesl_build::extension::compat::{ExtensionCompat, WeslBuildExtensionV1};
+/// # struct Logger;
+/// # impl WeslBuildExtensionV1<wesl_build::BuildResolver> for Logger {
+/// #     fn name<'n>(&self) -> std::borrow::Cow<'n, str> { "Logger".into() }
+/// #     fn init_root(&mut self, _: &str, _: &mut wesl::Wesl<wesl_build::BuildResolver>) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+/// #     fn enter_mod(&mut self, _: &std::path::Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+/// #     fn exit_mod(&mut self, _: &std::path::Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+/// #     fn post_build(&mut self, _: &wesl::ModulePath, _: &str, _: &Option<wesl::BasicSourceMap>) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+/// # }
+///
+/// let extensions = wesl_build::extensions![ExtensionCompat::new(Logger)];
+/// ```
+pub struct ExtensionCompat<E> {
+    inner: E,
+}
+
+impl<E> ExtensionCompat<E> {
+    pub fn new(inner: E) -> Self {
+        Self { inner }
+    }
+
+    pub fn inner(&self) -> &E {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> E {
+        self.inner
+    }
+}
+
+impl<R: Resolver, E: WeslBuildExtensionV1<R>> WeslBuildExtension<R> for ExtensionCompat<E> {
+    fn name<'n>(&self) -> Cow<'n, str> {
+        self.inner.name()
+    }
+
+    fn extension_api_version(&self) -> ExtensionApiVersion {
+        ExtensionApiVersion::V1_0
+    }
+
+    fn init_root(&mut self, shader_root_path: &str, res: &mut Wesl<R>) -> Result<(), Box<dyn Error>> {
+        self.inner.init_root(shader_root_path, res)
+    }
+
+    fn exit_root(&mut self, shader_root_path: &str, res: &Wesl<R>) -> Result<(), Box<dyn Error>> {
+        self.inner.exit_root(shader_root_path, res)
+    }
+
+    fn enter_mod(&mut self, dir_path: &Path) -> Result<(), Box<dyn Error>> {
+        self.inner.enter_mod(dir_path)
+    }
+
+    fn exit_mod(&mut self, dir_path: &Path) -> Result<(), Box<dyn Error>> {
+        self.inner.exit_mod(dir_path)
+    }
+
+    fn post_build(
+        &mut self,
+        wesl_path: &ModulePath,
+        wgsl_built_path: &str,
+        source_map: &Option<BasicSourceMap>,
+    ) -> Result<(), Box<dyn Error>> {
+        self.inner.post_build(wesl_path, wgsl_built_path, source_map)
+    }
+}
+
+/// Runs an extension implementing `WeslBuildExtension<StandardResolver>` in a build, which runs with the [`BuildResolver`]
+///
+/// `init_root` and `exit_root` get a [`StandardResolver`] compiler of the shader dir with the compile options
+/// and mangler of the build, as the builds before the [`BuildResolver`] did. Changes the extension makes to it
+/// don't apply to the build, every other hook is passed through.
+///
+/// ## Example
+/// ```
+/// use wesl_build::extension::{WeslBuildExtension, compat::StandardResolverCompat};
+/// # struct Logger;
+/// # impl WeslBuildExtension<wesl::StandardResolver> for Logger {
+/// #     fn name<'n>(&self) -> std::borrow::Cow<'n, str> { "Logger".into() }
+/// # }
+///
+/// let extensions = wesl_build::extensions![StandardResolverCompat::new(Logger)];
+/// ```
+pub struct StandardResolverCompat<E> {
+    inner: E,
+    /// The compiler `init_root` and `exit_root` get, made in `init_root`
+    wesl: Option<Wesl<StandardResolver>>,
+}
+
+impl<E> StandardResolverCompat<E> {
+    pub fn new(inner: E) -> Self {
+        Self { inner, wesl: None }
+    }
+
+    pub fn inner(&self) -> &E {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> E {
+        self.inner
+    }
+
+    /// A compiler of the shader dir with the options of the build
+    fn standard_wesl(shader_root_path: &str) -> Result<Wesl<StandardResolver>, WeslBuildError> {
+        let (options, mangler) = try_with_build_context(|ctx| (ctx.config().compile_options.clone(), ctx.config().mangler))?;
+        let mut wesl = Wesl::new(shader_root_path);
+        wesl.set_options(options);
+        wesl.set_mangler(mangler);
+        Ok(wesl)
+    }
+}
+
+impl<E: WeslBuildExtension<StandardResolver>> WeslBuildExtension<BuildResolver> for StandardResolverCompat<E> {
+    fn name<'n>(&self) -> Cow<'n, str> {
+        self.inner.name()
+    }
+
+    fn extension_api_version(&self) -> ExtensionApiVersion {
+        self.inner.extension_api_version()
+    }
+
+    fn artifact_name(&self, wesl_path: &ModulePath) -> Option<String> {
+        self.inner.artifact_name(wesl_path)
+    }
+
+    fn init_root(&mut self, shader_root_path: &str, _res: &mut Wesl<BuildResolver>) -> Result<(), Box<dyn Error>> {
+        let wesl = self.wesl.insert(Self::standard_wesl(shader_root_path)?);
+        self.inner.init_root(shader_root_path, wesl)
+    }
+
+    fn on_graph(&mut self, graph: &ModuleGraph) -> Result<(), Box<dyn Error>> {
+        self.inner.on_graph(graph)
+    }
+
+    fn exit_root(&mut self, shader_root_path: &str, _res: &Wesl<BuildResolver>) -> Result<(), Box<dyn Error>> {
+        let wesl = match self.wesl.take() {
+            Some(wesl) => wesl,
+            None => Self::standard_wesl(shader_root_path)?,
+        };
+        self.inner.exit_root(shader_root_path, &wesl)
+    }
+
+    fn enter_mod(&mut self, dir_path: &Path) -> Result<(), Box<dyn Error>> {
+        self.inner.enter_mod(dir_path)
+    }
+
+    fn exit_mod(&mut self, dir_path: &Path) -> Result<(), Box<dyn Error>> {
+        self.inner.exit_mod(dir_path)
+    }
+
+    fn on_asset(&mut self, asset_path: &Path) -> Result<(), Box<dyn Error>> {
+        self.inner.on_asset(asset_path)
+    }
+
+    fn preprocess(&mut self, wesl_path: &ModulePath, source: &mut String) -> Result<(), Box<dyn Error>> {
+        self.inner.preprocess(wesl_path, source)
+    }
+
+    fn configure(&mut self, wesl_path: &ModulePath, options: &mut CompileOptions) -> Result<(), Box<dyn Error>> {
+        self.inner.configure(wesl_path, options)
+    }
+
+    #[cfg(feature = "naga")]
+    fn post_parse(&mut self, wesl_path: &ModulePath, module: &mut crate::NagaModule) -> Result<(), Box<dyn Error>> {
+        self.inner.post_parse(wesl_path, module)
+    }
+
+    fn post_build(
+        &mut self,
+        wesl_path: &ModulePath,
+        wgsl_built_path: &str,
+        source_map: &Option<BasicSourceMap>,
+    ) -> Result<(), Box<dyn Error>> {
+        self.inner.post_build(wesl_path, wgsl_built_path, source_map)
+    }
+
+    fn on_error(&mut self, wesl_path: &ModulePath, error: &WeslBuildError) {
+        self.inner.on_error(wesl_path, error)
+    }
+
+    fn reads_built_wgsl(&self) -> bool {
+        self.inner.reads_built_wgsl()
+    }
+
+    fn mutates_built_wgsl(&self) -> bool {
+        self.inner.mutates_built_wgsl()
+    }
+
+    fn must_run_after(&self) -> &[&str] {
+        self.inner.must_run_after()
+    }
+
+    fn must_run_before(&self) -> &[&str] {
+        self.inner.must_run_before()
+    }
+
+    fn would_build(&mut self, shader: &PlannedShader) -> Result<(), Box<dyn Error>> {
+        self.inner.would_build(shader)
+    }
+}
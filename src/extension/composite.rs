@@ -0,0 +1,204 @@
+#![cfg(feature = "composite_ext")]
+
+use std::{borrow::Cow, error::Error, fmt, path::Path};
+
+use wesl::{BasicSourceMap, CompileOptions, ModulePath, Resolver, Wesl};
+
+use super::compat::ExtensionApiVersion;
+use crate::{BuildResolver, ModuleGraph, PlannedShader, WeslBuildError, WeslBuildExtension};
+
+/// Bundles several extensions as one, e.g. to ship a set of extensions from a crate
+///
+/// Every hook is run on the children in the order they were added,
+/// an error from a child is reported with both names, e.g. `MyBundle > WgslMinifierExtension`.
+///
+/// The children are not reordered by their [`must_run_after`](WeslBuildExtension::must_run_after)
+/// and [`must_run_before`](WeslBuildExtension::must_run_before), set the constraints of the whole bundle
+/// with [`with_must_run_after`](Self::with_must_run_after) and [`with_must_run_before`](Self::with_must_run_before).
+pub struct CompositeExtension<R: Resolver = BuildResolver> {
+    name: String,
+    children: Vec<Box<dyn WeslBuildExtension<R>>>,
+    must_run_after: Vec<&'static str>,
+    must_run_before: Vec<&'static str>,
+}
+
+impl<R: Resolver> CompositeExtension<R> {
+    /// An empty bundle, `name` is reported in errors before the name of the child
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            children: Vec::new(),
+            must_run_after: Vec::new(),
+            must_run_before: Vec::new(),
+        }
+    }
+
+    /// Add a child to the end of the bundle
+    pub fn with(mut self, child: impl WeslBuildExtension<R> + 'static) -> Self {
+        self.children.push(Box::new(child));
+        self
+    }
+
+    /// Add an already boxed child to the end of the bundle
+    pub fn with_boxed(mut self, child: Box<dyn WeslBuildExtension<R>>) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    /// The [names](WeslBuildExtension::name) of the extensions the whole bundle must run after
+    pub fn with_must_run_after(mut self, names: &[&'static str]) -> Self {
+        self.must_run_after.extend_from_slice(names);
+        self
+    }
+
+    /// The [names](WeslBuildExtension::name) of the extensions the whole bundle must run before
+    pub fn with_must_run_before(mut self, names: &[&'static str]) -> Self {
+        self.must_run_before.extend_from_slice(names);
+        self
+    }
+
+    /// The children, in the order they run
+    pub fn children(&self) -> &[Box<dyn WeslBuildExtension<R>>] {
+        &self.children
+    }
+
+    /// Run `hook` on every child in order, naming the child in its error
+    fn each(
+        &mut self,
+        mut hook: impl FnMut(&mut dyn WeslBuildExtension<R>) -> Result<(), Box<dyn Error>>,
+    ) -> Result<(), Box<dyn Error>> {
+        for child in &mut self.children {
+            hook(child.as_mut()).map_err(|error| nested_error(child.as_ref(), error))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: Resolver> WeslBuildExtension<R> for CompositeExtension<R> {
+    fn name<'n>(&self) -> Cow<'n, str> {
+        self.name.clone().into()
+    }
+
+    /// The newest version any child needs
+    fn extension_api_version(&self) -> ExtensionApiVersion {
+        self.children.iter()
+            .map(|child| child.extension_api_version())
+            .max()
+            .unwrap_or(ExtensionApiVersion::CURRENT)
+    }
+
+    /// The name of the first child that renames the artifact
+    fn artifact_name(&self, wesl_path: &ModulePath) -> Option<String> {
+        self.children.iter().find_map(|child| child.artifact_name(wesl_path))
+    }
+
+    fn init_root(&mut self, shader_root_path: &str, res: &mut Wesl<R>) -> Result<(), Box<dyn Error>> {
+        self.each(|child| child.init_root(shader_root_path, res))
+    }
+
+    fn on_graph(&mut self, graph: &ModuleGraph) -> Result<(), Box<dyn Error>> {
+        self.each(|child| child.on_graph(graph))
+    }
+
+    fn exit_root(&mut self, shader_root_path: &str, res: &Wesl<R>) -> Result<(), Box<dyn Error>> {
+        self.each(|child| child.exit_root(shader_root_path, res))
+    }
+
+    fn enter_mod(&mut self, dir_path: &Path) -> Result<(), Box<dyn Error>> {
+        self.each(|child| child.enter_mod(dir_path))
+    }
+
+    fn exit_mod(&mut self, dir_path: &Path) -> Result<(), Box<dyn Error>> {
+        self.each(|child| child.exit_mod(dir_path))
+    }
+
+    fn on_asset(&mut self, asset_path: &Path) -> Result<(), Box<dyn Error>> {
+        self.each(|child| child.on_asset(asset_path))
+    }
+
+    fn preprocess(&mut self, wesl_path: &ModulePath, source: &mut String) -> Result<(), Box<dyn Error>> {
+        self.each(|child| child.preprocess(wesl_path, source))
+    }
+
+    fn configure(&mut self, wesl_path: &ModulePath, options: &mut CompileOptions) -> Result<(), Box<dyn Error>> {
+        self.each(|child| child.configure(wesl_path, options))
+    }
+
+    #[cfg(feature = "naga")]
+    fn post_parse(&mut self, wesl_path: &ModulePath, module: &mut crate::NagaModule) -> Result<(), Box<dyn Error>> {
+        self.each(|child| child.post_parse(wesl_path, module))
+    }
+
+    fn post_build(
+        &mut self,
+        wesl_path: &ModulePath,
+        wgsl_built_path: &str,
+        source_map: &Option<BasicSourceMap>,
+    ) -> Result<(), Box<dyn Error>> {
+        self.each(|child| child.post_build(wesl_path, wgsl_built_path, source_map))
+    }
+
+    fn on_error(&mut self, wesl_path: &ModulePath, error: &WeslBuildError) {
+        for child in &mut self.children {
+            child.on_error(wesl_path, error);
+        }
+    }
+
+    fn reads_built_wgsl(&self) -> bool {
+        self.children.iter().any(|child| child.reads_built_wgsl())
+    }
+
+    fn mutates_built_wgsl(&self) -> bool {
+        self.children.iter().any(|child| child.mutates_built_wgsl())
+    }
+
+    fn must_run_after(&self) -> &[&str] {
+        &self.must_run_after
+    }
+
+    fn must_run_before(&self) -> &[&str] {
+        &self.must_run_before
+    }
+
+    fn would_build(&mut self, shader: &PlannedShader) -> Result<(), Box<dyn Error>> {
+        self.each(|child| child.would_build(shader))
+    }
+}
+
+/// The error of an extension inside a [`CompositeExtension`], unwrapped by
+/// [`extension_error`](super::extension_error) so the names are joined with ` > `
+#[derive(Debug)]
+pub(crate) struct NestedExtensionError {
+    pub(crate) extension_name: String,
+    pub(crate) error: Box<dyn Error>,
+}
+
+impl fmt::Display for NestedExtensionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} error: {}", self.extension_name, self.error)
+    }
+}
+
+impl Error for NestedExtensionError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.error.as_ref())
+    }
+}
+
+/// Name the extension an error came from, joining the names if it came from a nested extension
+pub(crate) fn nested_error(ext: &dyn WeslBuildExtension<impl Resolver>, error: Box<dyn Error>) -> NestedExtensionError {
+    match error.downcast::<NestedExtensionError>() {
+        Ok(nested) => {
+            let NestedExtensionError { extension_name, error } = *nested;
+            NestedExtensionError {
+                extension_name: format!("{} > {extension_name}", ext.name()),
+                error,
+            }
+        }
+        Err(error) => NestedExtensionError {
+            extension_name: ext.name().into_owned(),
+            error,
+        },
+    }
+}
@@ -0,0 +1,173 @@
+#![cfg(feature = "const_data_ext")]
+
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use itertools::Itertools;
+use wesl::{BasicSourceMap, ModulePath};
+
+use crate::WeslBuildExtension;
+
+/// A directive that could not be expanded
+#[derive(Debug, thiserror::Error)]
+pub enum ConstDataError {
+    #[error("`{mod_path}` line {line}: expected `//! include_array(\"<file>\", <f32|u32|i32>[, <NAME>])`")]
+    InvalidDirective { mod_path: ModulePath, line: usize },
+    #[error("`{mod_path}` line {line}: `{ty}` arrays are not supported, use `f32`, `u32` or `i32`")]
+    UnsupportedType { mod_path: ModulePath, line: usize, ty: String },
+    #[error("`{}` is {len} bytes, not a whole number of 4 byte `{ty}`s", .path.display())]
+    InvalidLength { path: PathBuf, len: usize, ty: &'static str },
+    #[error("`{}` contains a NaN or infinite `f32`, which WGSL can't express", .path.display())]
+    NonFinite { path: PathBuf },
+    #[error("`{}` is empty, WGSL arrays can't be empty", .path.display())]
+    Empty { path: PathBuf },
+}
+
+/// Bake binary files into shaders as `const` arrays, e.g. lookup tables and precomputed kernels
+///
+/// Every line like `//! include_array("lut.bin", f32)` in a shader is replaced with
+/// `const LUT: array<f32, N> = array<f32, N>(...);`, the file is read as little endian `f32`, `u32` or `i32`.
+/// The path is relative to the shader, the name defaults to the file name in upper case
+/// and can be set with a third argument, e.g. `//! include_array("kernels/blur.bin", f32, BLUR_WEIGHTS)`.
+/// The build reruns when an included file changes.
+#[derive(Default)]
+pub struct ConstDataExtension {
+    shader_root: PathBuf,
+}
+
+impl ConstDataExtension {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The `const` declaration a directive expands to
+    fn expand(&self, mod_path: &ModulePath, line: usize, args: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let invalid = || ConstDataError::InvalidDirective { mod_path: mod_path.clone(), line };
+
+        let args = args.split(',').map(str::trim).collect::<Vec<_>>();
+        let (file, ty, name) = match args.as_slice() {
+            [file, ty] => (*file, *ty, None),
+            [file, ty, name] => (*file, *ty, Some(*name)),
+            _ => return Err(invalid().into()),
+        };
+        let file = file.strip_prefix('"').and_then(|file| file.strip_suffix('"')).ok_or_else(invalid)?;
+
+        // relative to the dir of the shader
+        let mut path = mod_path.components.iter()
+            .dropping_back(1)
+            .fold(self.shader_root.clone(), |path, component| path.join(component));
+        path.push(file);
+
+        let name = match name {
+            Some(name) if is_ident(name) => name.to_owned(),
+            Some(_) => return Err(invalid().into()),
+            None => const_name(&path).ok_or_else(invalid)?,
+        };
+
+        let bytes = std::fs::read(&path)?;
+        if !crate::is_check() {
+            println!("cargo::rerun-if-changed={}", path.display());
+        }
+
+        let (ty, values) = match ty {
+            "f32" => ("f32", words(&path, &bytes, "f32")?.map(f32::from_le_bytes).map(|value| {
+                value.is_finite().then(|| format!("{value:?}")).ok_or_else(|| ConstDataError::NonFinite { path: path.clone() })
+            }).collect::<Result<Vec<_>, _>>()?),
+            "u32" => ("u32", words(&path, &bytes, "u32")?.map(|word| format!("{}u", u32::from_le_bytes(word))).collect()),
+            "i32" => ("i32", words(&path, &bytes, "i32")?.map(|word| format!("{}i", i32::from_le_bytes(word))).collect()),
+            ty => return Err(ConstDataError::UnsupportedType { mod_path: mod_path.clone(), line, ty: ty.to_owned() }.into()),
+        };
+        if values.is_empty() {
+            return Err(ConstDataError::Empty { path }.into());
+        }
+
+        let mut decl = String::new();
+        write!(decl, "const {name}: array<{ty}, {len}> = array<{ty}, {len}>({});", values.join(", "), len = values.len())
+            .expect("writing to a String can't fail");
+        Ok(decl)
+    }
+}
+
+/// The 4 byte words of `bytes`
+fn words<'b>(path: &Path, bytes: &'b [u8], ty: &'static str) -> Result<impl Iterator<Item = [u8; 4]> + 'b, ConstDataError> {
+    if bytes.len() % 4 != 0 {
+        return Err(ConstDataError::InvalidLength { path: path.to_owned(), len: bytes.len(), ty });
+    }
+    Ok(bytes.chunks_exact(4).map(|word| word.try_into().expect("the chunks are 4 bytes")))
+}
+
+/// The default name of the array of `path`, its file name in upper case
+fn const_name(path: &Path) -> Option<String> {
+    let name = path.file_stem()?.to_str()?
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect::<String>();
+    is_ident(&name).then_some(name)
+}
+
+fn is_ident(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+        && name != "_"
+}
+
+impl WeslBuildExtension<crate::BuildResolver> for ConstDataExtension {
+    fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+        "ConstDataExtension".into()
+    }
+
+    fn init_root(
+        &mut self,
+        shader_path: &str,
+        _res: &mut wesl::Wesl<crate::BuildResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.shader_root = PathBuf::from(shader_path);
+        Ok(())
+    }
+
+    fn enter_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+    fn exit_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+
+    fn preprocess(&mut self, wesl_path: &ModulePath, source: &mut String) -> Result<(), Box<dyn std::error::Error>> {
+        if !source.contains("include_array") {
+            return Ok(());
+        }
+
+        let mut expanded = String::with_capacity(source.len());
+        let mut any_directive = false;
+        for (idx, line) in source.lines().enumerate() {
+            let directive = line.trim_start()
+                .strip_prefix("//!")
+                .map(str::trim)
+                .and_then(|directive| directive.strip_prefix("include_array"))
+                .map(str::trim_start);
+
+            match directive {
+                Some(args) => {
+                    let args = args.strip_prefix('(')
+                        .and_then(|args| args.trim_end().strip_suffix(')'))
+                        .ok_or(ConstDataError::InvalidDirective { mod_path: wesl_path.clone(), line: idx + 1 })?;
+                    expanded.push_str(&self.expand(wesl_path, idx + 1, args)?);
+                    any_directive = true;
+                }
+                None => expanded.push_str(line),
+            }
+            // keep the line numbers of the source
+            expanded.push('\n');
+        }
+
+        if any_directive {
+            *source = expanded;
+        }
+        Ok(())
+    }
+
+    fn post_build(
+        &mut self,
+        _mod_path: &ModulePath,
+        _wgsl_built_path: &str,
+        _source_map: &Option<BasicSourceMap>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+}
@@ -0,0 +1,232 @@
+#![cfg(feature = "dead_code_report_ext")]
+
+use std::collections::HashSet;
+use std::fmt::{Display, Write as _};
+use std::{fs, path::{Path, PathBuf}};
+
+use wesl::{BasicSourceMap, ModulePath, SourceMap};
+use wesl::syntax::{DeclarationKind, GlobalDeclaration, TranslationUnit};
+
+use crate::{WeslBuildError, WeslBuildExtension, artifacts};
+use crate::context::try_with_build_context;
+use crate::graph::source_modules;
+use crate::walk::DirWalk;
+use crate::build_out_dir;
+
+/// The kind of a top level declaration
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DeclKind {
+    Function,
+    Struct,
+    /// `const` and `override`
+    Constant,
+    /// module scope `var`
+    Variable,
+    Alias,
+}
+
+impl Display for DeclKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            DeclKind::Function => "fn",
+            DeclKind::Struct => "struct",
+            DeclKind::Constant => "const",
+            DeclKind::Variable => "var",
+            DeclKind::Alias => "alias",
+        })
+    }
+}
+
+/// The dead code threshold was exceeded
+#[derive(Debug, thiserror::Error)]
+#[error("found {dead_decls} dead declarations, more than the maximum of {max_dead_decls}{}", see_report(.report_path, .report))]
+pub struct DeadCodeThresholdError {
+    pub dead_decls: usize,
+    pub max_dead_decls: usize,
+    /// Where the report was written, `None` in check mode, which writes nothing
+    pub report_path: Option<PathBuf>,
+    pub report: String,
+}
+
+/// Where to read the report of a failed build, the report itself when it wasn't written
+fn see_report(report_path: &Option<PathBuf>, report: &str) -> String {
+    match report_path {
+        Some(report_path) => format!(", see {}", report_path.display()),
+        None => format!(":\n{}", report.trim_end()),
+    }
+}
+
+/// Report the declarations in the source modules that did not make it into any built shader,
+/// i.e. what WESL's import resolution and stripping removed
+///
+/// The report is written once all shaders are built, to `dead_code_report.txt` in the output dir by default.
+pub struct DeadCodeReportExtension {
+    /// Where to write the report, `None` for `dead_code_report.txt` in the output dir
+    report_path: Option<PathBuf>,
+    /// Fail the build if there are more dead declarations than this
+    max_dead_decls: Option<usize>,
+    shader_root_path: PathBuf,
+    /// `(module, name)` of every declaration used in a built shader
+    used_decls: HashSet<(ModulePath, String)>,
+}
+
+impl Default for DeadCodeReportExtension {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DeadCodeReportExtension {
+    pub fn new() -> Self {
+        Self {
+            report_path: None,
+            max_dead_decls: None,
+            shader_root_path: PathBuf::new(),
+            used_decls: HashSet::new(),
+        }
+    }
+
+    /// Write the report to `report_path` instead of `dead_code_report.txt` in the output dir
+    pub fn with_report_path(mut self, report_path: impl AsRef<Path>) -> Self {
+        self.report_path = Some(report_path.as_ref().to_owned());
+        self
+    }
+
+    /// Fail the build if there are more than `max_dead_decls` dead declarations in total
+    pub fn with_max_dead_decls(mut self, max_dead_decls: usize) -> Self {
+        self.max_dead_decls = Some(max_dead_decls);
+        self
+    }
+
+    /// The dead declarations of every source module sorted by module, modules without dead code are left out
+    fn dead_decls(&self) -> Result<Vec<(ModulePath, Vec<(DeclKind, String)>)>, WeslBuildError> {
+        let mut dead_decls = Vec::new();
+        let config = try_with_build_context(|ctx| ctx.config().clone())?;
+
+        for (mod_path, source_path) in source_modules(&self.shader_root_path, DirWalk::of_config(&config))? {
+            let source = fs::read_to_string(&source_path)?;
+            let dead = top_level_decls(&source).into_iter()
+                .filter(|(_, name)| !self.used_decls.contains(&(mod_path.clone(), name.clone())))
+                .collect::<Vec<_>>();
+
+            if !dead.is_empty() {
+                dead_decls.push((mod_path, dead));
+            }
+        }
+        dead_decls.sort_by_cached_key(|(mod_path, _)| mod_path.to_string());
+
+        Ok(dead_decls)
+    }
+}
+
+impl<WeslResolver: wesl::Resolver> WeslBuildExtension<WeslResolver> for DeadCodeReportExtension {
+    fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+        "DeadCodeReportExtension".into()
+    }
+
+    fn reads_built_wgsl(&self) -> bool {
+        true
+    }
+
+    fn init_root(
+        &mut self,
+        shader_root_path: &str,
+        _res: &mut wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.shader_root_path = PathBuf::from(shader_root_path);
+        self.used_decls.clear();
+        Ok(())
+    }
+
+    fn exit_root(
+        &mut self,
+        _shader_root_path: &str,
+        _res: &wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let dead_decls = self.dead_decls()?;
+        let dead_decl_count = dead_decls.iter().map(|(_, decls)| decls.len()).sum::<usize>();
+
+        let mut report = format!("{dead_decl_count} dead declarations\n");
+        for (mod_path, decls) in &dead_decls {
+            writeln!(report, "\n{mod_path}: {} dead", decls.len())?;
+            for (kind, name) in decls {
+                writeln!(report, "    {kind} {name}")?;
+            }
+        }
+
+        // check mode writes nothing, a failed threshold shows the report instead
+        let report_path = if crate::is_check() {
+            None
+        } else {
+            let report_path = match &self.report_path {
+                Some(report_path) => report_path.clone(),
+                None => build_out_dir()?.join("dead_code_report.txt"),
+            };
+            artifacts::write(&report_path, &report)?;
+            Some(report_path)
+        };
+
+        #[cfg(feature = "logging")]
+        if let Some(report_path) = &report_path {
+            log::info!("{dead_decl_count} dead declarations, see: {}", report_path.display());
+        }
+
+        match self.max_dead_decls {
+            Some(max_dead_decls) if dead_decl_count > max_dead_decls => Err(Box::new(DeadCodeThresholdError {
+                dead_decls: dead_decl_count,
+                max_dead_decls,
+                report_path,
+                report,
+            })),
+            _ => Ok(()),
+        }
+    }
+
+    fn enter_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+    fn exit_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+
+    fn post_build(
+        &mut self,
+        mod_path: &ModulePath,
+        wgsl_built_path: &str,
+        source_map: &Option<BasicSourceMap>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let built = artifacts::read_to_string(wgsl_built_path)?;
+
+        for (_, decl) in top_level_decls(&built) {
+            // declarations of the root module are not mangled
+            let used = source_map.as_ref()
+                .and_then(|source_map| source_map.get_decl(&decl))
+                .map_or_else(
+                    || (mod_path.clone(), decl.clone()),
+                    |(decl_mod_path, name)| (decl_mod_path.clone(), name.to_owned()),
+                );
+            self.used_decls.insert(used);
+        }
+
+        Ok(())
+    }
+}
+
+/// The kind and name of every top level declaration in a WGSL/WESL source, none if it doesn't parse
+fn top_level_decls(source: &str) -> Vec<(DeclKind, String)> {
+    let Ok(unit) = source.parse::<TranslationUnit>() else {
+        return Vec::new();
+    };
+
+    unit.global_declarations.iter()
+        .filter_map(|decl| match decl.node() {
+            GlobalDeclaration::Function(function) => Some((DeclKind::Function, &function.ident)),
+            GlobalDeclaration::Struct(strct) => Some((DeclKind::Struct, &strct.ident)),
+            GlobalDeclaration::TypeAlias(alias) => Some((DeclKind::Alias, &alias.ident)),
+            GlobalDeclaration::Declaration(decl) => match decl.kind {
+                DeclarationKind::Const | DeclarationKind::Override => Some((DeclKind::Constant, &decl.ident)),
+                DeclarationKind::Var(_) => Some((DeclKind::Variable, &decl.ident)),
+                // `let` is not allowed at module scope
+                DeclarationKind::Let => None,
+            },
+            GlobalDeclaration::Void | GlobalDeclaration::ConstAssert(_) => None,
+        })
+        .map(|(kind, ident)| (kind, ident.name().to_string()))
+        .collect()
+}
@@ -0,0 +1,202 @@
+#![cfg(feature = "dependency_graph_ext")]
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use wesl::{BasicSourceMap, ModulePath};
+
+use crate::{ModuleGraph, WeslBuildExtension, artifacts};
+use crate::build_out_dir;
+use super::comments::strip_comments;
+
+/// Write the module graph, which modules import which, as `shader_deps.dot` for Graphviz
+/// and optionally as `shader_deps.mmd` for mermaid
+///
+/// Each built module is labeled with its built size and entry points.
+/// The graphs are written once all shaders are built, to the output dir by default.
+pub struct DependencyGraphExtension {
+    /// Where to write the graphs, `None` for the output dir
+    output_dir: Option<PathBuf>,
+    mermaid: bool,
+    /// The graph of the build, from `on_graph`
+    graph: ModuleGraph,
+    built: HashMap<ModulePath, BuiltModule>,
+}
+
+/// The metadata of a built module
+struct BuiltModule {
+    built_bytes: usize,
+    entry_points: Vec<String>,
+}
+
+impl Default for DependencyGraphExtension {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DependencyGraphExtension {
+    pub fn new() -> Self {
+        Self {
+            output_dir: None,
+            mermaid: false,
+            graph: ModuleGraph::default(),
+            built: HashMap::new(),
+        }
+    }
+
+    /// Write the graphs to `output_dir` instead of the output dir
+    pub fn with_output_dir(mut self, output_dir: impl AsRef<Path>) -> Self {
+        self.output_dir = Some(output_dir.as_ref().to_owned());
+        self
+    }
+
+    /// Also write the graph as a mermaid flowchart
+    pub fn with_mermaid(mut self, mermaid: bool) -> Self {
+        self.mermaid = mermaid;
+        self
+    }
+
+    /// The label lines of a module
+    fn label(&self, mod_path: &ModulePath) -> Vec<String> {
+        let mut label = vec![mod_path.components.join("::")];
+        if let Some(built) = self.built.get(mod_path) {
+            label.push(format!("{} bytes", built.built_bytes));
+            if !built.entry_points.is_empty() {
+                label.push(format!("entry points: {}", built.entry_points.join(", ")));
+            }
+        }
+        label
+    }
+
+    fn dot(&self, graph: &ModuleGraph) -> String {
+        let mut dot = String::from("digraph shader_deps {\n    rankdir=LR;\n    node [shape=box];\n\n");
+
+        for mod_path in graph.modules() {
+            let label = self.label(mod_path).iter().map(|line| escape(line)).collect::<Vec<_>>().join("\\n");
+            writeln!(dot, "    \"{}\" [label=\"{label}\"];", escape(&mod_path.components.join("::")))
+                .expect("writing to a String can't fail");
+        }
+        dot.push('\n');
+        for (importer, imported) in graph.edges() {
+            writeln!(
+                dot, "    \"{}\" -> \"{}\";",
+                escape(&importer.components.join("::")), escape(&imported.components.join("::")),
+            ).expect("writing to a String can't fail");
+        }
+        dot.push_str("}\n");
+
+        dot
+    }
+
+    fn mermaid(&self, graph: &ModuleGraph) -> String {
+        let mut mermaid = String::from("graph LR\n");
+        // mermaid ids can't contain `::`, so nodes are numbered
+        let ids = graph.modules().enumerate()
+            .map(|(idx, mod_path)| (mod_path, format!("n{idx}")))
+            .collect::<HashMap<_, _>>();
+
+        for mod_path in graph.modules() {
+            let label = self.label(mod_path).iter().map(|line| line.replace('"', "#quot;")).collect::<Vec<_>>().join("<br/>");
+            writeln!(mermaid, "    {}[\"{label}\"]", ids[mod_path]).expect("writing to a String can't fail");
+        }
+        for (importer, imported) in graph.edges() {
+            writeln!(mermaid, "    {} --> {}", ids[importer], ids[imported]).expect("writing to a String can't fail");
+        }
+
+        mermaid
+    }
+}
+
+impl<WeslResolver: wesl::Resolver> WeslBuildExtension<WeslResolver> for DependencyGraphExtension {
+    fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+        "DependencyGraphExtension".into()
+    }
+
+    fn reads_built_wgsl(&self) -> bool {
+        true
+    }
+
+    fn init_root(
+        &mut self,
+        _shader_path: &str,
+        _res: &mut wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.built.clear();
+        Ok(())
+    }
+
+    fn on_graph(&mut self, graph: &ModuleGraph) -> Result<(), Box<dyn std::error::Error>> {
+        self.graph = graph.clone();
+        Ok(())
+    }
+
+    fn exit_root(
+        &mut self,
+        _shader_root_path: &str,
+        _res: &wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if crate::is_check() {
+            return Ok(());
+        }
+        let output_dir = match &self.output_dir {
+            Some(output_dir) => output_dir.clone(),
+            None => build_out_dir()?,
+        };
+
+        artifacts::write(output_dir.join("shader_deps.dot"), self.dot(&self.graph))?;
+        if self.mermaid {
+            artifacts::write(output_dir.join("shader_deps.mmd"), self.mermaid(&self.graph))?;
+        }
+
+        #[cfg(feature = "logging")]
+        log::info!("shader dependency graph written to: {}", output_dir.join("shader_deps.dot").display());
+
+        Ok(())
+    }
+
+    fn enter_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+    fn exit_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+
+    fn post_build(
+        &mut self,
+        mod_path: &ModulePath,
+        wgsl_built_path: &str,
+        _source_map: &Option<BasicSourceMap>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let built = artifacts::read_to_string(wgsl_built_path)?;
+        self.built.insert(mod_path.clone(), BuiltModule {
+            built_bytes: built.len(),
+            entry_points: entry_points(&built),
+        });
+
+        Ok(())
+    }
+}
+
+/// The names of the functions with a `@vertex`, `@fragment` or `@compute` attribute
+fn entry_points(wgsl: &str) -> Vec<String> {
+    let wgsl = strip_comments(wgsl);
+    let mut entry_points = Vec::new();
+
+    for stage in ["@vertex", "@fragment", "@compute"] {
+        for (idx, _) in wgsl.match_indices(stage) {
+            let Some(fn_idx) = wgsl[idx..].find("fn ") else { continue };
+            let name = wgsl[idx + fn_idx + "fn ".len()..].trim_start()
+                .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .next()
+                .unwrap_or_default();
+            if !name.is_empty() {
+                entry_points.push(name.to_owned());
+            }
+        }
+    }
+
+    entry_points
+}
+
+/// Escape a DOT string
+fn escape(str: &str) -> String {
+    str.replace('\\', "\\\\").replace('"', "\\\"")
+}
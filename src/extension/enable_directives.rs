@@ -0,0 +1,183 @@
+#![cfg(feature = "enable_directives_ext")]
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use wesl::{BasicSourceMap, ModulePath};
+
+use crate::WeslBuildExtension;
+use crate::artifacts;
+
+/// A shader enables extensions that are not in the declared set
+#[derive(Debug, thiserror::Error)]
+#[error("`{mod_path}` enables {}, which the target doesn't support", .enables.iter().map(|enable| format!("`{enable}`")).collect::<Vec<_>>().join(", "))]
+pub struct EnableDirectiveError {
+    pub mod_path: ModulePath,
+    pub enables: Vec<String>,
+}
+
+/// Manage the WGSL `enable` directives of the built shaders, e.g. `enable f16;`
+///
+/// With [`with_allowed`](Self::with_allowed) the build fails when a shader enables an extension outside of the
+/// declared set, so shaders can't depend on what the target devices lack. With [`with_f32_fallback`](Self::with_f32_fallback)
+/// every shader enabling `f16` gets a fallback variant next to it, `<name>.f32.wgsl`, with the `f16` types and literals
+/// rewritten to `f32`, for devices without `SHADER_F16`.
+///
+/// The fallback changes the layout of structs with `f16` members, so the buffers it reads need a layout of their own.
+#[derive(Debug, Clone, Default)]
+pub struct EnableDirectivesExtension {
+    /// The extensions shaders may enable, any if `None`
+    allowed: Option<BTreeSet<String>>,
+    f32_fallback: bool,
+}
+
+impl EnableDirectivesExtension {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fail the build when a shader enables an extension that is not in `allowed`, e.g. `["f16"]`
+    pub fn with_allowed(mut self, allowed: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.allowed = Some(allowed.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Write an `f32` variant of every shader enabling `f16` to `<name>.f32.wgsl`, next to the built shader
+    pub fn with_f32_fallback(mut self, f32_fallback: bool) -> Self {
+        self.f32_fallback = f32_fallback;
+        self
+    }
+}
+
+/// The path of the `f32` fallback of the built shader at `wgsl_built_path`, `<name>.f32.wgsl`
+pub fn f32_fallback_path(wgsl_built_path: &Path) -> PathBuf {
+    let name = wgsl_built_path.file_stem().unwrap_or_default().to_string_lossy();
+    wgsl_built_path.with_file_name(format!("{name}.f32.wgsl"))
+}
+
+/// The extensions enabled by the `enable` directives of `wgsl`, in order
+pub fn enable_directives(wgsl: &str) -> Vec<String> {
+    wgsl.lines()
+        .filter_map(|line| line.trim().strip_prefix("enable "))
+        .flat_map(|enables| enables.trim_end().trim_end_matches(';').split(','))
+        .map(|enable| enable.trim().to_owned())
+        .filter(|enable| !enable.is_empty())
+        .collect()
+}
+
+/// Rewrite the `f16` types and literals of `wgsl` to `f32`, and remove `f16` from its `enable` directives
+///
+/// `f16`, `vec3h` and `mat4x4h` become `f32`, `vec3f` and `mat4x4f`, and literals like `1.5h` become `1.5f`.
+/// Line comments are kept as they are.
+pub fn f16_to_f32(wgsl: &str) -> String {
+    let mut out = String::with_capacity(wgsl.len());
+    for line in wgsl.split_inclusive('\n') {
+        let Some(enables) = line.trim().strip_prefix("enable ") else {
+            rewrite_f16_tokens(line, &mut out);
+            continue;
+        };
+        let enables = enables.trim_end_matches(';').split(',')
+            .map(str::trim)
+            .filter(|enable| !enable.is_empty() && *enable != "f16")
+            .collect::<Vec<_>>();
+        if !enables.is_empty() {
+            out.push_str(&format!("enable {};", enables.join(", ")));
+            out.push_str(&line[line.trim_end().len()..]);
+        }
+    }
+    out
+}
+
+/// Push `line` to `out` with its `f16` tokens rewritten to `f32`
+fn rewrite_f16_tokens(line: &str, out: &mut String) {
+    let mut rest = line;
+    while let Some(start) = rest.find(|c: char| c.is_alphanumeric() || c == '_' || c == '/') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if rest.starts_with("//") {
+            out.push_str(rest);
+            return;
+        }
+        if rest.starts_with('/') {
+            out.push('/');
+            rest = &rest[1..];
+            continue;
+        }
+
+        // numbers keep their `.` so `1.5h` is one token
+        let is_number = rest.starts_with(|c: char| c.is_ascii_digit());
+        let end = rest.find(|c: char| !(c.is_alphanumeric() || c == '_' || (is_number && c == '.'))).unwrap_or(rest.len());
+        let token = &rest[..end];
+        match token {
+            "f16" => out.push_str("f32"),
+            _ if is_number && token.ends_with('h') => {
+                out.push_str(&token[..token.len() - 1]);
+                out.push('f');
+            }
+            _ if is_half_vector_or_matrix(token) => {
+                out.push_str(&token[..token.len() - 1]);
+                out.push('f');
+            }
+            _ => out.push_str(token),
+        }
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+}
+
+/// Whether `token` is a `vecNh` or `matCxRh` alias
+fn is_half_vector_or_matrix(token: &str) -> bool {
+    let is_size = |size: &str| matches!(size, "2" | "3" | "4");
+    match token.strip_suffix('h') {
+        Some(vector) if vector.starts_with("vec") => is_size(&vector[3..]),
+        Some(matrix) if matrix.starts_with("mat") => matrix[3..].split_once('x').is_some_and(|(c, r)| is_size(c) && is_size(r)),
+        _ => false,
+    }
+}
+
+impl<WeslResolver: wesl::Resolver> WeslBuildExtension<WeslResolver> for EnableDirectivesExtension {
+    fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+        "EnableDirectivesExtension".into()
+    }
+
+    fn reads_built_wgsl(&self) -> bool {
+        true
+    }
+
+    fn init_root(
+        &mut self,
+        _shader_path: &str,
+        _res: &mut wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    fn enter_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+    fn exit_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+
+    fn post_build(
+        &mut self,
+        mod_path: &ModulePath,
+        wgsl_built_path: &str,
+        _source_map: &Option<BasicSourceMap>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let wgsl = artifacts::read_to_string(wgsl_built_path)?;
+        let enables = enable_directives(&wgsl);
+
+        if let Some(allowed) = &self.allowed {
+            let denied = enables.iter().filter(|enable| !allowed.contains(*enable)).cloned().collect::<Vec<_>>();
+            if !denied.is_empty() {
+                return Err(Box::new(EnableDirectiveError { mod_path: mod_path.clone(), enables: denied }));
+            }
+        }
+
+        if self.f32_fallback && enables.iter().any(|enable| enable == "f16") {
+            artifacts::write(f32_fallback_path(Path::new(wgsl_built_path)), f16_to_f32(&wgsl))?;
+
+            #[cfg(feature = "logging")]
+            log::debug!("wrote the f32 fallback of `{mod_path}`");
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,161 @@
+#![cfg(feature = "entry_point_split_ext")]
+
+//! Split shaders with many entry points into a module per entry point, so each pipeline only gets the code it runs
+
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use wesl::{BasicSourceMap, ModulePath};
+
+use crate::context::try_with_build_context;
+use crate::graph::source_modules;
+use crate::walk::DirWalk;
+use crate::{BuildResolver, WeslBuildExtension};
+
+#[derive(Debug, thiserror::Error)]
+pub enum EntryPointSplitError {
+    #[error("the entry point `{entry_point}` of `{mod_path}` is invalid on its own: {error}")]
+    Invalid { mod_path: ModulePath, entry_point: String, error: String },
+    #[error("the entry point `{entry_point}` of `{mod_path}` has the module path `{split}` of another module, rename one of them")]
+    ModuleClash { mod_path: ModulePath, entry_point: String, split: ModulePath },
+}
+
+/// Split every shader with several entry points into a module per entry point, for uber-shaders whose
+/// pipelines would otherwise each get a shader module with the code of all of them
+///
+/// In `init_root` every shader the build [includes](`crate::WeslBuildConfig::include`) is compiled, and for each of
+/// its entry points naga removes the other entry points and everything only they use. The result is added as a
+/// [virtual module](BuildResolver::add_virtual_module) named `<module>::<entry point>`, e.g. `uber::vs_main`,
+/// which is built like the shader files, run through the other extensions, e.g. gets bindings, and used with `include_wesl!`.
+/// The shader itself is built as usual, so the combined artifact stays available.
+///
+/// The shaders are compiled with the compile options of the build before the `configure` hook of any extension,
+/// shaders that fail to compile are not split, their error is reported when they are built.
+///
+/// ## Example
+/// ```
+/// use wesl_build::extension::entry_point_split::EntryPointSplitExtension;
+///
+/// let split = EntryPointSplitExtension::new().with_min_entry_points(3).with_module("passes::uber");
+/// ```
+#[derive(Debug, Clone)]
+pub struct EntryPointSplitExtension {
+    min_entry_points: usize,
+    /// The modules to split, `::` separated, all of them if empty
+    modules: BTreeSet<String>,
+    /// The split modules of the build, `(shader, split module)`
+    splits: Vec<(ModulePath, ModulePath)>,
+}
+
+impl Default for EntryPointSplitExtension {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EntryPointSplitExtension {
+    pub fn new() -> Self {
+        Self {
+            min_entry_points: 2,
+            modules: BTreeSet::new(),
+            splits: Vec::new(),
+        }
+    }
+
+    /// Only split shaders with at least `min_entry_points` entry points, defaults to 2
+    pub fn with_min_entry_points(mut self, min_entry_points: usize) -> Self {
+        self.min_entry_points = min_entry_points;
+        self
+    }
+
+    /// Only split the shader `module`, `::` separated, e.g. `passes::uber`, can be called for several modules
+    pub fn with_module(mut self, module: impl Into<String>) -> Self {
+        self.modules.insert(module.into());
+        self
+    }
+
+    /// The `(shader, split module)` of every entry point split off in the last build
+    pub fn splits(&self) -> &[(ModulePath, ModulePath)] {
+        &self.splits
+    }
+
+    fn splits_module(&self, mod_path: &ModulePath) -> bool {
+        self.modules.is_empty() || self.modules.contains(&mod_path.components.join("::"))
+    }
+}
+
+/// The WGSL of `module` with only its entry point at `index`, without what only the other entry points use
+fn split_entry_point(module: &naga::Module, index: usize) -> Result<String, String> {
+    let mut split = module.clone();
+    split.entry_points = vec![module.entry_points[index].clone()];
+    naga::compact::compact(&mut split, naga::compact::KeepUnused::No);
+
+    let info = naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::all())
+        .validate(&split)
+        .map_err(|error| error.into_inner().to_string())?;
+    naga::back::wgsl::write_string(&split, &info, naga::back::wgsl::WriterFlags::empty())
+        .map_err(|error| error.to_string())
+}
+
+impl WeslBuildExtension<BuildResolver> for EntryPointSplitExtension {
+    fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+        "EntryPointSplitExtension".into()
+    }
+
+    fn init_root(
+        &mut self,
+        shader_path: &str,
+        res: &mut wesl::Wesl<BuildResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.splits.clear();
+        let config = try_with_build_context(|ctx| ctx.config().clone())?;
+        let shader_root = Path::new(shader_path);
+
+        for (mod_path, path) in source_modules(shader_root, DirWalk::of_config(&config))? {
+            if !self.splits_module(&mod_path) || !config.builds_file(&crate::walk::relative_path(&path, shader_root)?) {
+                continue;
+            }
+            let Ok(compiled) = res.compile(&mod_path) else { continue };
+            let module = naga::front::wgsl::parse_str(&compiled.to_string())?;
+            if module.entry_points.len() < self.min_entry_points {
+                continue;
+            }
+
+            for (index, entry_point) in module.entry_points.iter().enumerate() {
+                let mut components = mod_path.components.clone();
+                components.push(entry_point.name.clone());
+                let split = ModulePath::new(wesl::syntax::PathOrigin::Absolute, components);
+                if res.resolver().virtual_modules().contains(&split) {
+                    return Err(Box::new(EntryPointSplitError::ModuleClash {
+                        mod_path: mod_path.clone(),
+                        entry_point: entry_point.name.clone(),
+                        split,
+                    }));
+                }
+
+                let wgsl = split_entry_point(&module, index).map_err(|error| EntryPointSplitError::Invalid {
+                    mod_path: mod_path.clone(),
+                    entry_point: entry_point.name.clone(),
+                    error,
+                })?;
+                #[cfg(feature = "logging")]
+                log::debug!("split the entry point `{}` of `{mod_path}` into `{split}`", entry_point.name);
+                res.resolver().add_virtual_module(split.clone(), wgsl);
+                self.splits.push((mod_path.clone(), split));
+            }
+        }
+        Ok(())
+    }
+
+    fn enter_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+    fn exit_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+
+    fn post_build(
+        &mut self,
+        _mod_path: &ModulePath,
+        _wgsl_built_path: &str,
+        _source_map: &Option<BasicSourceMap>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+}
@@ -0,0 +1,129 @@
+use std::ops::{Deref, DerefMut};
+
+use super::WeslBuildExtension;
+use crate::BuildResolver;
+
+/// The env var with a comma separated list of extension names to leave out of an [`extensions!`](`crate::extensions`) set,
+/// e.g. `WESL_BUILD_DISABLE_EXT=WgslMinifierExtension,WgpuBindingsExtension`
+pub const DISABLE_EXT_ENV: &str = "WESL_BUILD_DISABLE_EXT";
+
+/// A boxed extension, as passed to [`build_shader_dir`](`crate::build_shader_dir`)
+pub type BoxedExtension = Box<dyn WeslBuildExtension<BuildResolver>>;
+
+/// The extensions of a build, in the order they run
+///
+/// Derefs to a slice of boxed extensions, so `&mut ExtensionSet` can be passed to
+/// [`build_shader_dir`](`crate::build_shader_dir`), usually made with [`extensions!`](`crate::extensions`)
+#[derive(Default)]
+pub struct ExtensionSet {
+    extensions: Vec<BoxedExtension>,
+}
+
+impl ExtensionSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an extension to the end, it can be a value or already boxed
+    pub fn insert(&mut self, extension: impl IntoExtension) -> &mut Self {
+        self.extensions.push(extension.into_extension());
+        self
+    }
+
+    /// Add an extension to the end
+    pub fn with(mut self, extension: impl IntoExtension) -> Self {
+        self.insert(extension);
+        self
+    }
+
+    /// Remove every extension with this [`name`](`WeslBuildExtension::name`), returning the removed extensions
+    pub fn remove(&mut self, name: &str) -> Vec<BoxedExtension> {
+        self.extensions.extract_if(.., |ext| ext.name() == name).collect()
+    }
+
+    /// Whether an extension with this [`name`](`WeslBuildExtension::name`) is in the set
+    pub fn contains(&self, name: &str) -> bool {
+        self.extensions.iter().any(|ext| ext.name() == name)
+    }
+
+    /// Remove the extensions named in the [`DISABLE_EXT_ENV`] env var, done by [`extensions!`](`crate::extensions`)
+    ///
+    /// The build tells cargo to rerun the build script when the env var changes.
+    pub fn disable_from_env(&mut self) -> &mut Self {
+        if let Ok(names) = std::env::var(DISABLE_EXT_ENV) {
+            self.disable(&names);
+        }
+        self
+    }
+
+    /// Remove the extensions in a comma separated list of names
+    pub(crate) fn disable(&mut self, names: &str) {
+        for name in names.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+            let _removed = self.remove(name);
+            #[cfg(feature = "logging")]
+            if _removed.is_empty() {
+                log::warn!("{DISABLE_EXT_ENV} names {name}, which is not in the extension set");
+            } else {
+                log::info!("Disabled {name} with {DISABLE_EXT_ENV}");
+            }
+        }
+    }
+
+    /// The names of the extensions, in the order they run
+    pub fn names(&self) -> Vec<String> {
+        self.extensions.iter().map(|ext| ext.name().into_owned()).collect()
+    }
+
+    pub fn into_vec(self) -> Vec<BoxedExtension> {
+        self.extensions
+    }
+}
+
+impl Deref for ExtensionSet {
+    type Target = [BoxedExtension];
+
+    fn deref(&self) -> &Self::Target {
+        &self.extensions
+    }
+}
+
+impl DerefMut for ExtensionSet {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.extensions
+    }
+}
+
+impl From<Vec<BoxedExtension>> for ExtensionSet {
+    fn from(extensions: Vec<BoxedExtension>) -> Self {
+        Self { extensions }
+    }
+}
+
+impl<E: IntoExtension> FromIterator<E> for ExtensionSet {
+    fn from_iter<I: IntoIterator<Item = E>>(iter: I) -> Self {
+        Self { extensions: iter.into_iter().map(IntoExtension::into_extension).collect() }
+    }
+}
+
+impl std::fmt::Debug for ExtensionSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.names()).finish()
+    }
+}
+
+/// An extension value or an already boxed extension, accepted by [`ExtensionSet::insert`] and [`extensions!`](`crate::extensions`)
+pub trait IntoExtension {
+    fn into_extension(self) -> BoxedExtension;
+}
+
+impl<E: WeslBuildExtension<BuildResolver> + 'static> IntoExtension for E {
+    fn into_extension(self) -> BoxedExtension {
+        Box::new(self)
+    }
+}
+
+impl IntoExtension for BoxedExtension {
+    fn into_extension(self) -> BoxedExtension {
+        self
+    }
+}
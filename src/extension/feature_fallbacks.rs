@@ -0,0 +1,284 @@
+#![cfg(feature = "feature_fallback_ext")]
+
+//! Build fallback variants of shaders that use optional GPU features, and a function picking the variant a device supports
+
+use std::borrow::Cow;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use wesl::{BasicSourceMap, CompileOptions, ModulePath, ResolveError, Resolver};
+
+use crate::WeslBuildExtension;
+use crate::artifacts;
+
+/// An optional GPU feature a [`FeatureFallbackExtension`] builds fallbacks without
+#[derive(Debug, Clone)]
+pub struct FeatureFallback {
+    /// The WESL feature the shaders test with `@if(...)`, also the suffix of the fallback artifacts
+    pub name: String,
+    /// The `wgpu::Features` flag, e.g. `TEXTURE_BINDING_ARRAY`
+    pub wgpu_feature: String,
+    /// Whether a built shader uses the feature
+    pub uses: fn(&naga::Module) -> bool,
+}
+
+impl FeatureFallback {
+    pub fn new(name: impl Into<String>, wgpu_feature: impl Into<String>, uses: fn(&naga::Module) -> bool) -> Self {
+        Self { name: name.into(), wgpu_feature: wgpu_feature.into(), uses }
+    }
+
+    /// `binding_array`s, behind `@if(texture_arrays)`
+    pub fn texture_arrays() -> Self {
+        Self::new("texture_arrays", "TEXTURE_BINDING_ARRAY", |module| module.global_variables.iter()
+            .any(|(_, global)| matches!(module.types[global.ty].inner, naga::TypeInner::BindingArray { .. })))
+    }
+
+    /// `f16`, behind `@if(f16)`
+    pub fn f16() -> Self {
+        Self::new("f16", "SHADER_F16", |module| uses_float_width(module, 2))
+    }
+
+    /// `f64`, behind `@if(f64)`
+    pub fn f64() -> Self {
+        Self::new("f64", "SHADER_F64", |module| uses_float_width(module, 8))
+    }
+}
+
+/// Whether a type of `module` has floats of `width` bytes
+fn uses_float_width(module: &naga::Module, width: u8) -> bool {
+    module.types.iter().any(|(_, ty)| match ty.inner {
+        naga::TypeInner::Scalar(scalar) | naga::TypeInner::Vector { scalar, .. } | naga::TypeInner::Matrix { scalar, .. } =>
+            scalar.kind == naga::ScalarKind::Float && scalar.width == width,
+        _ => false,
+    })
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum FeatureFallbackError {
+    #[error("the `{fallback}` fallback of `{mod_path}` failed to compile: {error}")]
+    Compile { mod_path: ModulePath, fallback: String, error: wesl::Error },
+    #[error("the `{fallback}` fallback of `{mod_path}` still uses `{fallback}`, put its uses behind `@if({fallback})`")]
+    StillUses { mod_path: ModulePath, fallback: String },
+}
+
+/// A built shader and the optional features it uses
+struct FallbackShader {
+    mod_path: ModulePath,
+    wgsl_built_path: PathBuf,
+    /// The compile options it was built with
+    options: CompileOptions,
+    /// The [`FeatureFallback::name`]s of the features it uses
+    uses: Vec<String>,
+}
+
+/// Build a fallback variant of every shader that uses an optional GPU feature, with the feature's WESL feature disabled,
+/// and generate a function per shader that picks the variant a device supports
+///
+/// Shaders put what needs the feature behind `@if(<name>)`, and a replacement behind `@if(!<name>)`. The shaders are built with
+/// the WESL feature enabled, unless the compile options set it, and for each feature a built shader uses, a fallback is
+/// built with it disabled to `<name>.no_<feature>.wgsl` next to it, e.g. `blur.no_texture_arrays.wgsl`.
+/// Shaders that use several features also get a fallback without all of them, `blur.no_texture_arrays.no_f16.wgsl`.
+///
+/// The selector, `OUT_DIR/shader_fallbacks.rs` by default, has a function per shader named after its module,
+/// include it with `include!(concat!(env!("OUT_DIR"), "/shader_fallbacks.rs"))`:
+/// ```ignore
+/// pub fn blur(features: wgpu::Features) -> &'static str
+/// ```
+///
+/// The fallbacks are compiled in `exit_root` with the resolver of the build, but the mangler of the build and the
+/// `post_build` of other extensions, e.g. a minifier, are not applied to them.
+pub struct FeatureFallbackExtension {
+    fallbacks: Vec<FeatureFallback>,
+    selector_name: String,
+    /// The options of the shader being configured
+    options: Option<CompileOptions>,
+    shaders: Vec<FallbackShader>,
+}
+
+impl Default for FeatureFallbackExtension {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FeatureFallbackExtension {
+    /// No fallbacks, add them with [`with_fallback`](Self::with_fallback)
+    pub fn new() -> Self {
+        Self {
+            fallbacks: Vec::new(),
+            selector_name: "shader_fallbacks.rs".to_owned(),
+            options: None,
+            shaders: Vec::new(),
+        }
+    }
+
+    /// Build fallbacks of the shaders using `fallback`, e.g. [`FeatureFallback::texture_arrays`]
+    pub fn with_fallback(mut self, fallback: FeatureFallback) -> Self {
+        self.fallbacks.push(fallback);
+        self
+    }
+
+    /// Set the file name of the selector in the output dir, defaults to `shader_fallbacks.rs`
+    pub fn with_selector_name(mut self, selector_name: impl Into<String>) -> Self {
+        self.selector_name = selector_name.into();
+        self
+    }
+
+    /// The rust source of the selector of the shaders built so far
+    fn selector_source(&self, variants: &[(usize, Vec<String>, PathBuf)]) -> Result<String, std::io::Error> {
+        let mut source = "// @generated by wesl_build, do not edit\n".to_owned();
+        for (index, shader) in self.shaders.iter().enumerate() {
+            let wgpu_feature = |name: &String| self.fallbacks.iter()
+                .find(|fallback| fallback.name == *name)
+                .map(|fallback| format!("wgpu::Features::{}", fallback.wgpu_feature))
+                .expect("the features used are fallbacks");
+
+            writeln!(
+                source,
+                "\n/// `{}`, without the features `features` lacks\n#[allow(unused)]\npub fn {}(features: wgpu::Features) -> &'static str {{",
+                shader.mod_path.components.join("::"),
+                shader.mod_path.components.join("__"),
+            ).expect("writing to a String can't fail");
+            // the variants without the fewest features first, the last one needs none of them
+            let mut shader_variants = vec![(&[][..], &shader.wgsl_built_path)];
+            shader_variants.extend(variants.iter()
+                .filter(|(shader, ..)| *shader == index)
+                .map(|(_, disabled, path)| (disabled.as_slice(), path)));
+            let (_, last_path) = shader_variants.pop().expect("the shader is a variant");
+            for (disabled, path) in shader_variants {
+                let needed = shader.uses.iter().filter(|name| !disabled.contains(name)).map(wgpu_feature).collect::<Vec<_>>();
+                writeln!(
+                    source,
+                    "    if features.contains({}) {{\n        return include_str!({:?});\n    }}",
+                    needed.join(" | "),
+                    std::path::absolute(path)?.display().to_string(),
+                ).expect("writing to a String can't fail");
+            }
+            writeln!(source, "    include_str!({:?})\n}}", std::path::absolute(last_path)?.display().to_string())
+                .expect("writing to a String can't fail");
+        }
+        Ok(source)
+    }
+}
+
+/// Resolves modules with a borrowed resolver, to compile the fallbacks with the resolver of the build
+struct BorrowedResolver<'r, R>(&'r R);
+
+impl<R: Resolver> Resolver for BorrowedResolver<'_, R> {
+    fn resolve_source<'a>(&'a self, path: &ModulePath) -> Result<Cow<'a, str>, ResolveError> {
+        self.0.resolve_source(path)
+    }
+
+    fn display_name(&self, path: &ModulePath) -> Option<String> {
+        self.0.display_name(path)
+    }
+
+    fn fs_path(&self, path: &ModulePath) -> Option<PathBuf> {
+        self.0.fs_path(path)
+    }
+}
+
+impl<WeslResolver: wesl::Resolver> WeslBuildExtension<WeslResolver> for FeatureFallbackExtension {
+    fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+        "FeatureFallbackExtension".into()
+    }
+
+    fn reads_built_wgsl(&self) -> bool {
+        true
+    }
+
+    fn init_root(
+        &mut self,
+        _shader_path: &str,
+        _res: &mut wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.shaders.clear();
+        Ok(())
+    }
+
+    fn configure(&mut self, _wesl_path: &ModulePath, options: &mut CompileOptions) -> Result<(), Box<dyn std::error::Error>> {
+        for fallback in &self.fallbacks {
+            options.features.flags.entry(fallback.name.clone()).or_insert(wesl::Feature::Enable);
+        }
+        self.options = Some(options.clone());
+        Ok(())
+    }
+
+    fn exit_root(
+        &mut self,
+        _shader_root_path: &str,
+        res: &wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        // (shader, disabled features, path) of every fallback
+        let mut variants = Vec::new();
+        for (index, shader) in self.shaders.iter().enumerate() {
+            let mut disabled_sets = shader.uses.iter().map(|name| vec![name.clone()]).collect::<Vec<_>>();
+            if shader.uses.len() > 1 {
+                disabled_sets.push(shader.uses.clone());
+            }
+
+            for disabled in disabled_sets {
+                let mut options = shader.options.clone();
+                for name in &disabled {
+                    options.features.flags.insert(name.clone(), wesl::Feature::Disable);
+                }
+                let mut compiler = wesl::Wesl::new_barebones().set_custom_resolver(BorrowedResolver(res.resolver()));
+                compiler.set_options(options);
+                let wgsl = compiler.compile(&shader.mod_path)
+                    .map_err(|error| FeatureFallbackError::Compile {
+                        mod_path: shader.mod_path.clone(),
+                        fallback: disabled.join(", "),
+                        error,
+                    })?
+                    .to_string();
+
+                let module = naga::front::wgsl::parse_str(&wgsl)?;
+                if let Some(still_used) = self.fallbacks.iter().find(|fallback| disabled.contains(&fallback.name) && (fallback.uses)(&module)) {
+                    return Err(Box::new(FeatureFallbackError::StillUses {
+                        mod_path: shader.mod_path.clone(),
+                        fallback: still_used.name.clone(),
+                    }));
+                }
+
+                let stem = shader.wgsl_built_path.file_stem().unwrap_or_default().to_string_lossy();
+                let suffix = disabled.iter().map(|name| format!(".no_{name}")).collect::<String>();
+                let path = shader.wgsl_built_path.with_file_name(format!("{stem}{suffix}.wgsl"));
+                artifacts::write(&path, wgsl)?;
+                variants.push((index, disabled, path));
+            }
+        }
+
+        if let Some(out_dir) = self.shaders.first().and_then(|shader| shader.wgsl_built_path.parent()) {
+            artifacts::write(out_dir.join(&self.selector_name), self.selector_source(&variants)?)?;
+
+            #[cfg(feature = "logging")]
+            log::debug!("built {} fallbacks of {} shaders", variants.len(), self.shaders.len());
+        }
+
+        Ok(())
+    }
+
+    fn enter_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+    fn exit_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+
+    fn post_build(
+        &mut self,
+        mod_path: &ModulePath,
+        wgsl_built_path: &str,
+        _source_map: &Option<BasicSourceMap>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let module = naga::front::wgsl::parse_str(&artifacts::read_to_string(wgsl_built_path)?)?;
+        let uses = self.fallbacks.iter()
+            .filter(|fallback| (fallback.uses)(&module))
+            .map(|fallback| fallback.name.clone())
+            .collect::<Vec<_>>();
+
+        self.shaders.push(FallbackShader {
+            mod_path: mod_path.clone(),
+            wgsl_built_path: PathBuf::from(wgsl_built_path),
+            options: self.options.take().unwrap_or_default(),
+            uses,
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,105 @@
+#![cfg(feature = "generated_shaders_ext")]
+
+use std::path::Path;
+
+use wesl::{BasicSourceMap, ModulePath};
+
+use crate::{BuildResolver, WeslBuildExtension, resolver::module_path};
+
+/// Build shaders generated from source strings and templates alongside the shaders in the shader dir
+///
+/// The shaders are [virtual modules](BuildResolver::add_virtual_module), added in `init_root`,
+/// built like shader files and used with `include_wesl!` by their module path.
+///
+/// ## Example
+/// ```
+/// use wesl_build::extension::generated_shaders::GeneratedShaderExtension;
+///
+/// const BLUR: &str = "
+///     const TAPS: u32 = {{TAPS}}u;
+///     @compute @workgroup_size({{SIZE}})
+///     fn main() {}
+/// ";
+///
+/// let generated = GeneratedShaderExtension::new()
+///     .with_module("generated/clear", "@compute @workgroup_size(64) fn main() {}")
+///     .with_permutations("blur/blur_{{TAPS}}", BLUR, [
+///         [("TAPS", "5"), ("SIZE", "64")],
+///         [("TAPS", "9"), ("SIZE", "128")],
+///     ]);
+/// ```
+#[derive(Debug, Default)]
+pub struct GeneratedShaderExtension {
+    /// The module paths, as given, and the sources
+    modules: Vec<(String, String)>,
+}
+
+impl GeneratedShaderExtension {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Generate the shader `module`, a path like `generated/clear` or `generated::clear`
+    ///
+    /// Fails the build if it is not a valid module path, see [`module_path`]
+    pub fn with_module(mut self, module: impl Into<String>, source: impl Into<String>) -> Self {
+        self.modules.push((module.into(), source.into()));
+        self
+    }
+
+    /// Generate a shader from `template` for every permutation, replacing each `{{KEY}}` with its value
+    ///
+    /// The keys are replaced in `module` too, so every permutation has its own module path.
+    pub fn with_permutations<K: AsRef<str>, V: AsRef<str>>(
+        mut self,
+        module: &str,
+        template: &str,
+        permutations: impl IntoIterator<Item = impl IntoIterator<Item = (K, V)>>,
+    ) -> Self {
+        for permutation in permutations {
+            let (mut module, mut source) = (module.to_owned(), template.to_owned());
+            for (key, value) in permutation {
+                let key = format!("{{{{{}}}}}", key.as_ref());
+                module = module.replace(&key, value.as_ref());
+                source = source.replace(&key, value.as_ref());
+            }
+            self.modules.push((module, source));
+        }
+
+        self
+    }
+
+    /// The module paths and sources of the generated shaders, in the order they were added
+    pub fn modules(&self) -> &[(String, String)] {
+        &self.modules
+    }
+}
+
+impl WeslBuildExtension<BuildResolver> for GeneratedShaderExtension {
+    fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+        "GeneratedShaderExtension".into()
+    }
+
+    fn init_root(
+        &mut self,
+        _shader_path: &str,
+        res: &mut wesl::Wesl<BuildResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        for (module, source) in &self.modules {
+            res.resolver().add_virtual_module(module_path(module)?, source.clone());
+        }
+        Ok(())
+    }
+
+    fn enter_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+    fn exit_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+
+    fn post_build(
+        &mut self,
+        _mod_path: &ModulePath,
+        _wgsl_built_path: &str,
+        _source_map: &Option<BasicSourceMap>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+}
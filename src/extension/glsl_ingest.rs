@@ -0,0 +1,126 @@
+#![cfg(feature = "glsl_ingest_ext")]
+
+//! Build legacy GLSL shaders with the WESL shaders, converted to WGSL by naga's GLSL frontend
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use wesl::{BasicSourceMap, ModulePath};
+
+use super::ingest::find_shaders;
+use crate::{BuildResolver, WeslBuildExtension};
+
+/// The file extensions of GLSL shaders and their stages
+const GLSL_STAGES: [(&str, naga::ShaderStage); 3] = [
+    ("vert", naga::ShaderStage::Vertex),
+    ("frag", naga::ShaderStage::Fragment),
+    ("comp", naga::ShaderStage::Compute),
+];
+
+#[derive(Debug, thiserror::Error)]
+pub enum GlslIngestError {
+    #[error("failed to parse the GLSL shader `{}`:\n{error}", .path.display())]
+    Parse { path: PathBuf, error: String },
+    #[error("the GLSL shader `{}` is invalid:\n{error}", .path.display())]
+    Invalid { path: PathBuf, error: String },
+    #[error("failed to convert the GLSL shader `{}` to WGSL: {error}", .path.display())]
+    Wgsl { path: PathBuf, error: String },
+    #[error("the GLSL shader `{}` has the module path `{mod_path}` of a shader file, rename one of them", .path.display())]
+    ModuleClash { path: PathBuf, mod_path: ModulePath },
+}
+
+/// Build the `.vert`, `.frag` and `.comp` GLSL shaders in the shader dir as modules, for projects moving from GLSL to WESL
+///
+/// In `init_root` every GLSL shader the build [includes](`crate::WeslBuildConfig::include`) is converted to WGSL
+/// and added as a [virtual module](BuildResolver::add_virtual_module). They are built like the shader files,
+/// run through the other extensions, e.g. get bindings, used with `include_wesl!` and imported by WESL shaders.
+/// The module of a GLSL shader is its path with the stage as a suffix, so the stages of a program don't clash,
+/// e.g. `post/blur.frag` is `post::blur_frag`. Its entry point is `main`, as in GLSL.
+///
+/// ## Example
+/// ```
+/// use wesl_build::extension::glsl_ingest::GlslIngestExtension;
+///
+/// // `#ifdef USE_FOG` blocks are kept
+/// let glsl = GlslIngestExtension::new().with_define("USE_FOG", "1");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct GlslIngestExtension {
+    defines: BTreeMap<String, String>,
+}
+
+impl GlslIngestExtension {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Define the preprocessor macro `name` in every GLSL shader, as with `#define name value`
+    pub fn with_define(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.defines.insert(name.into(), value.into());
+        self
+    }
+
+    /// The WGSL of the GLSL shader at `path`
+    fn convert(&self, path: &Path, stage: naga::ShaderStage) -> Result<String, Box<dyn std::error::Error>> {
+        let source = std::fs::read_to_string(path)?;
+        let display_path = path.display().to_string();
+
+        let mut options = naga::front::glsl::Options::from(stage);
+        options.defines.extend(self.defines.iter().map(|(name, value)| (name.clone(), value.clone())));
+        let module = naga::front::glsl::Frontend::default()
+            .parse(&options, &source)
+            .map_err(|error| GlslIngestError::Parse {
+                path: path.to_owned(),
+                error: error.emit_to_string_with_path(&source, &display_path),
+            })?;
+        let info = naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::all())
+            .validate(&module)
+            .map_err(|error| GlslIngestError::Invalid {
+                path: path.to_owned(),
+                error: error.emit_to_string_with_path(&source, &display_path),
+            })?;
+
+        let wgsl = naga::back::wgsl::write_string(&module, &info, naga::back::wgsl::WriterFlags::empty())
+            .map_err(|error| GlslIngestError::Wgsl { path: path.to_owned(), error: error.to_string() })?;
+        Ok(wgsl)
+    }
+}
+
+impl WeslBuildExtension<BuildResolver> for GlslIngestExtension {
+    fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+        "GlslIngestExtension".into()
+    }
+
+    fn init_root(
+        &mut self,
+        shader_path: &str,
+        res: &mut wesl::Wesl<BuildResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let extensions = GLSL_STAGES.map(|(extension, _)| extension);
+        for file in find_shaders(Path::new(shader_path), &extensions)? {
+            if file.clashes_with_shader() {
+                return Err(Box::new(GlslIngestError::ModuleClash { path: file.path, mod_path: file.mod_path }));
+            }
+            let (_, stage) = GLSL_STAGES.into_iter()
+                .find(|(extension, _)| *extension == file.extension)
+                .expect("the file has a GLSL extension");
+
+            #[cfg(feature = "logging")]
+            log::debug!("converting GLSL shader `{}` to `{}`", file.path.display(), file.mod_path);
+            res.resolver().add_virtual_module(file.mod_path, self.convert(&file.path, stage)?);
+        }
+        Ok(())
+    }
+
+    fn enter_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+    fn exit_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+
+    fn post_build(
+        &mut self,
+        _mod_path: &ModulePath,
+        _wgsl_built_path: &str,
+        _source_map: &Option<BasicSourceMap>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+}
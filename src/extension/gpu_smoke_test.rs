@@ -0,0 +1,218 @@
+#![cfg(feature = "gpu_tests")]
+
+//! Create the built shaders on a real `wgpu` device, to catch what the driver rejects at build time
+
+use std::borrow::Cow;
+use std::fmt::Write as _;
+
+use wesl::{BasicSourceMap, ModulePath};
+
+use crate::messages::{self, BuildMessage};
+use crate::{WeslBuildExtension, artifacts};
+
+#[derive(Debug, thiserror::Error)]
+pub enum GpuSmokeTestError {
+    #[error("no GPU or fallback adapter was found, skip the smoke test without one with `with_skip_if_no_adapter`")]
+    NoAdapter,
+    #[error("failed to create a device on `{adapter}`: {error}")]
+    Device { adapter: String, error: String },
+    #[error("`{adapter}` rejected the shaders:{}", rejected_list(.rejected))]
+    Rejected { adapter: String, rejected: Vec<(ModulePath, Vec<String>)> },
+}
+
+/// The shaders and errors of [`GpuSmokeTestError::Rejected`], a shader per line followed by its errors
+fn rejected_list(rejected: &[(ModulePath, Vec<String>)]) -> String {
+    let mut list = String::new();
+    for (mod_path, errors) in rejected {
+        write!(list, "\n`{mod_path}`:").expect("writing to a String can't fail");
+        for error in errors {
+            write!(list, "\n    {}", error.trim_end().replace('\n', "\n    ")).expect("writing to a String can't fail");
+        }
+    }
+    list
+}
+
+/// Create every built shader with `wgpu::Device::create_shader_module` in `exit_root`, and optionally a compute
+/// pipeline for each of their compute entry points, so the validation of wgpu and the driver runs at build time
+/// on dev machines, not when the app starts
+///
+/// The device is created on the default adapter, or the fallback (software) adapter if there is none, with
+/// every feature and limit of the adapter. The pipelines get the layouts wgpu derives from the shader, the bind groups
+/// of the layouts `WgpuBindingsExtension` generates.
+/// Render pipelines need vertex buffers and color targets only the app knows, so they are not created.
+/// The errors of every shader fail the build together, warnings of the shader compiler are reported as build warnings.
+///
+/// ## Example
+/// ```no_run
+/// use wesl_build::extension::gpu_smoke_test::GpuSmokeTestExtension;
+///
+/// let smoke_test = GpuSmokeTestExtension::new().with_pipelines(true).with_skip_if_no_adapter(true);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct GpuSmokeTestExtension {
+    force_fallback_adapter: bool,
+    pipelines: bool,
+    skip_if_no_adapter: bool,
+    /// The shaders built so far and their WGSL
+    built: Vec<(ModulePath, String)>,
+}
+
+impl GpuSmokeTestExtension {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Always use the fallback (software) adapter, e.g. for the same results on every machine
+    pub fn with_force_fallback_adapter(mut self, force_fallback_adapter: bool) -> Self {
+        self.force_fallback_adapter = force_fallback_adapter;
+        self
+    }
+
+    /// Also create a compute pipeline for every compute entry point
+    pub fn with_pipelines(mut self, pipelines: bool) -> Self {
+        self.pipelines = pipelines;
+        self
+    }
+
+    /// Warn and skip the smoke test instead of failing when there is no adapter, e.g. on CI machines without a GPU
+    pub fn with_skip_if_no_adapter(mut self, skip_if_no_adapter: bool) -> Self {
+        self.skip_if_no_adapter = skip_if_no_adapter;
+        self
+    }
+
+    /// The adapter to create the shaders on, the fallback adapter if there is no other
+    fn adapter(&self, instance: &wgpu::Instance) -> Option<wgpu::Adapter> {
+        let request = |force_fallback_adapter| pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            force_fallback_adapter,
+            compatible_surface: None,
+        })).ok();
+        match self.force_fallback_adapter {
+            true => request(true),
+            false => request(false).or_else(|| request(true)),
+        }
+    }
+}
+
+/// The errors of creating the shader module of `wgsl` and its compute pipelines on `device`, warnings are reported
+fn smoke_test(device: &wgpu::Device, mod_path: &ModulePath, wgsl: &str, pipelines: bool) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let label = mod_path.components.join("::");
+    let mut errors = Vec::new();
+
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(&label),
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(wgsl)),
+    });
+    let compilation_info = pollster::block_on(module.get_compilation_info());
+    errors.extend(pollster::block_on(device.pop_error_scope()).map(|error| error.to_string()));
+
+    for message in compilation_info.messages {
+        match message.message_type {
+            wgpu::CompilationMessageType::Error => errors.push(message.message),
+            wgpu::CompilationMessageType::Warning => {
+                messages::report(&BuildMessage::warning(message.message).with_module(mod_path))?;
+            }
+            wgpu::CompilationMessageType::Info => (),
+        }
+    }
+    // the entry points of a module that failed to create are not worth creating
+    if !pipelines || !errors.is_empty() {
+        return Ok(errors);
+    }
+
+    let compute_entry_points = naga::front::wgsl::parse_str(wgsl)?.entry_points.into_iter()
+        .filter(|entry_point| entry_point.stage == naga::ShaderStage::Compute);
+    for entry_point in compute_entry_points {
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(&format!("{label}::{}", entry_point.name)),
+            layout: None,
+            module: &module,
+            entry_point: Some(&entry_point.name),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            cache: None,
+        });
+        if let Some(error) = pollster::block_on(device.pop_error_scope()) {
+            errors.push(format!("the compute pipeline of `{}`: {error}", entry_point.name));
+        }
+    }
+    Ok(errors)
+}
+
+impl<WeslResolver: wesl::Resolver> WeslBuildExtension<WeslResolver> for GpuSmokeTestExtension {
+    fn name<'n>(&self) -> Cow<'n, str> {
+        "GpuSmokeTestExtension".into()
+    }
+
+    fn reads_built_wgsl(&self) -> bool {
+        true
+    }
+
+    /// The shaders as the app gets them
+    fn must_run_after(&self) -> &[&str] {
+        &["WgslMinifierExtension", "WgslObfuscatorExtension", "WgslFormatterExtension"]
+    }
+
+    fn init_root(
+        &mut self,
+        _shader_root_path: &str,
+        _res: &mut wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.built.clear();
+        Ok(())
+    }
+
+    fn exit_root(
+        &mut self,
+        _shader_root_path: &str,
+        _res: &wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if self.built.is_empty() {
+            return Ok(());
+        }
+
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::from_env_or_default());
+        let Some(adapter) = self.adapter(&instance) else {
+            if self.skip_if_no_adapter {
+                messages::report(&BuildMessage::warning("no GPU or fallback adapter was found, the shaders are not smoke tested"))?;
+                return Ok(());
+            }
+            return Err(Box::new(GpuSmokeTestError::NoAdapter));
+        };
+        let adapter_name = adapter.get_info().name;
+        let (device, _queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+            label: Some("wesl_build smoke test"),
+            required_features: adapter.features(),
+            required_limits: adapter.limits(),
+            ..Default::default()
+        })).map_err(|error| GpuSmokeTestError::Device { adapter: adapter_name.clone(), error: error.to_string() })?;
+
+        let mut rejected = Vec::new();
+        for (mod_path, wgsl) in &self.built {
+            let errors = smoke_test(&device, mod_path, wgsl, self.pipelines)?;
+            if !errors.is_empty() {
+                rejected.push((mod_path.clone(), errors));
+            }
+        }
+
+        #[cfg(feature = "logging")]
+        log::info!("smoke tested {} shaders on `{adapter_name}`", self.built.len());
+
+        if rejected.is_empty() {
+            Ok(())
+        } else {
+            Err(Box::new(GpuSmokeTestError::Rejected { adapter: adapter_name, rejected }))
+        }
+    }
+
+    fn post_build(
+        &mut self,
+        mod_path: &ModulePath,
+        wgsl_built_path: &str,
+        _source_map: &Option<BasicSourceMap>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.built.push((mod_path.clone(), artifacts::read_to_string(wgsl_built_path)?));
+        Ok(())
+    }
+}
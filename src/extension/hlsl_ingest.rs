@@ -0,0 +1,172 @@
+#![cfg(feature = "hlsl_ingest_ext")]
+
+//! Build HLSL shaders with the WESL shaders, compiled to SPIR-V by DXC through hassle-rs and converted to WGSL by naga
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use wesl::{BasicSourceMap, ModulePath};
+
+use super::ingest::{IngestFile, find_shaders};
+use super::spirv_ingest::{SpirvIngestError, spirv_to_wgsl};
+use crate::{BuildResolver, WeslBuildExtension};
+
+/// The suffixes of the file names that give the stage of an HLSL shader, and the stage of its profile
+const STAGE_SUFFIXES: [(&str, &str); 3] = [("_vs", "vs"), ("_ps", "ps"), ("_cs", "cs")];
+
+#[derive(Debug, thiserror::Error)]
+pub enum HlslIngestError {
+    #[error(
+        "the stage of the HLSL shader `{}` is unknown, end its name with `_vs`, `_ps` or `_cs` or set its profile with `with_profile`",
+        .path.display(),
+    )]
+    UnknownStage { path: PathBuf },
+    #[error("failed to compile the HLSL shader `{}` with DXC:\n{error}", .path.display())]
+    Compile { path: PathBuf, error: String },
+    #[error(transparent)]
+    Spirv(#[from] SpirvIngestError),
+    #[error("the HLSL shader `{}` has the module path `{mod_path}` of a shader file, rename one of them", .path.display())]
+    ModuleClash { path: PathBuf, mod_path: ModulePath },
+}
+
+/// Build the `.hlsl` shaders in the shader dir as modules, to consolidate HLSL and WESL shaders on one build
+///
+/// In `init_root` every HLSL shader the build [includes](`crate::WeslBuildConfig::include`) is compiled to SPIR-V
+/// by DXC and added as a virtual module like the [SPIR-V shaders](super::spirv_ingest). DXC is loaded at runtime,
+/// its library (`dxcompiler.dll` or `libdxcompiler.so`) must be installed.
+///
+/// An HLSL shader has one entry point, `main` unless set with [`with_entry_point`](Self::with_entry_point).
+/// Its stage comes from the end of its file name, `_vs`, `_ps` or `_cs`, e.g. `post/blur_ps.hlsl` is a pixel shader
+/// built as `post::blur_ps_hlsl`, or from the profile set for the file with [`with_profile`](Self::with_profile).
+///
+/// ## Example
+/// ```
+/// use wesl_build::extension::hlsl_ingest::HlslIngestExtension;
+///
+/// let hlsl = HlslIngestExtension::new()
+///     .with_shader_model("6_2")
+///     .with_profile("legacy/tonemap.hlsl", "ps_6_2")
+///     .with_define("USE_ACES", "1");
+/// ```
+#[derive(Debug, Clone)]
+pub struct HlslIngestExtension {
+    entry_point: String,
+    shader_model: String,
+    /// The profile of every file, by its path in the shader dir
+    profiles: BTreeMap<String, String>,
+    defines: BTreeMap<String, String>,
+}
+
+impl Default for HlslIngestExtension {
+    fn default() -> Self {
+        Self {
+            entry_point: "main".to_owned(),
+            shader_model: "6_0".to_owned(),
+            profiles: BTreeMap::new(),
+            defines: BTreeMap::new(),
+        }
+    }
+}
+
+impl HlslIngestExtension {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The name of the entry point of every HLSL shader, `main` by default
+    pub fn with_entry_point(mut self, entry_point: impl Into<String>) -> Self {
+        self.entry_point = entry_point.into();
+        self
+    }
+
+    /// The shader model of the profiles from the file names, e.g. `6_2` for `ps_6_2`, `6_0` by default
+    pub fn with_shader_model(mut self, shader_model: impl Into<String>) -> Self {
+        self.shader_model = shader_model.into();
+        self
+    }
+
+    /// Compile the file at `path` in the shader dir, e.g. `legacy/tonemap.hlsl`, with the DXC profile `profile`
+    pub fn with_profile(mut self, path: impl Into<String>, profile: impl Into<String>) -> Self {
+        self.profiles.insert(path.into().replace('\\', "/"), profile.into());
+        self
+    }
+
+    /// Define the preprocessor macro `name` in every HLSL shader, as with `#define name value`
+    pub fn with_define(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.defines.insert(name.into(), value.into());
+        self
+    }
+
+    /// The DXC profile of `file`
+    fn profile(&self, shader_root_path: &Path, file: &IngestFile) -> Result<String, HlslIngestError> {
+        let relative_path = crate::walk::relative_path(&file.path, shader_root_path)
+            .unwrap_or_else(|_| file.path.display().to_string().replace('\\', "/"));
+        if let Some(profile) = self.profiles.get(&relative_path) {
+            return Ok(profile.clone());
+        }
+
+        let stem = file.path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default();
+        STAGE_SUFFIXES.iter()
+            .find(|(suffix, _)| stem.ends_with(suffix))
+            .map(|(_, stage)| format!("{stage}_{}", self.shader_model))
+            .ok_or_else(|| HlslIngestError::UnknownStage { path: file.path.clone() })
+    }
+
+    /// The WGSL of the HLSL shader `file`
+    fn convert(&self, shader_root_path: &Path, file: &IngestFile) -> Result<String, Box<dyn std::error::Error>> {
+        let profile = self.profile(shader_root_path, file)?;
+        let source = std::fs::read_to_string(&file.path)?;
+        let defines = self.defines.iter()
+            .map(|(name, value)| (name.as_str(), Some(value.as_str())))
+            .collect::<Vec<_>>();
+
+        let spirv = hassle_rs::compile_hlsl(
+            &file.path.display().to_string(),
+            &source,
+            &self.entry_point,
+            &profile,
+            &["-spirv", "-fspv-target-env=vulkan1.1"],
+            &defines,
+        )
+        .map_err(|error| HlslIngestError::Compile { path: file.path.clone(), error: error.to_string() })?;
+
+        Ok(spirv_to_wgsl(&file.path, &spirv).map_err(HlslIngestError::from)?)
+    }
+}
+
+impl WeslBuildExtension<BuildResolver> for HlslIngestExtension {
+    fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+        "HlslIngestExtension".into()
+    }
+
+    fn init_root(
+        &mut self,
+        shader_path: &str,
+        res: &mut wesl::Wesl<BuildResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let shader_root_path = Path::new(shader_path);
+        for file in find_shaders(shader_root_path, &["hlsl"])? {
+            if file.clashes_with_shader() {
+                return Err(Box::new(HlslIngestError::ModuleClash { path: file.path, mod_path: file.mod_path }));
+            }
+
+            #[cfg(feature = "logging")]
+            log::debug!("compiling HLSL shader `{}` to `{}`", file.path.display(), file.mod_path);
+            let wgsl = self.convert(shader_root_path, &file)?;
+            res.resolver().add_virtual_module(file.mod_path, wgsl);
+        }
+        Ok(())
+    }
+
+    fn enter_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+    fn exit_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+
+    fn post_build(
+        &mut self,
+        _mod_path: &ModulePath,
+        _wgsl_built_path: &str,
+        _source_map: &Option<BasicSourceMap>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+}
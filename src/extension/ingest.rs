@@ -0,0 +1,74 @@
+//! Finding the shaders in other languages that ingestion extensions convert to WGSL modules
+
+use std::path::{Path, PathBuf};
+
+use wesl::ModulePath;
+
+use crate::walk::DirWalk;
+use crate::WeslBuildError;
+use crate::context::try_with_build_context;
+
+/// A shader in another language in the shader dir
+pub(crate) struct IngestFile {
+    pub(crate) path: PathBuf,
+    /// Its path with the file extension as a suffix, e.g. `post/blur.frag` is `post::blur_frag`
+    pub(crate) mod_path: ModulePath,
+    pub(crate) extension: &'static str,
+}
+
+impl IngestFile {
+    /// Whether a shader file in the shader dir has the same module path
+    pub(crate) fn clashes_with_shader(&self) -> bool {
+        let name = self.mod_path.components.last().map(String::as_str).unwrap_or_default();
+        ["wesl", "wgsl"].iter().any(|ext| self.path.with_file_name(format!("{name}.{ext}")).is_file())
+    }
+}
+
+/// Every file with one of `extensions` in the shader dir the build running on this thread
+/// [includes](`crate::WeslBuildConfig::include`), sorted by path
+pub(crate) fn find_shaders(shader_root_path: &Path, extensions: &[&'static str]) -> Result<Vec<IngestFile>, WeslBuildError> {
+    let (mut walk, config) = try_with_build_context(|ctx| (DirWalk::of_config(ctx.config()), ctx.config().clone()))?;
+    let mut files = Vec::new();
+    find_in_dir(shader_root_path, shader_root_path, extensions, &mut walk, &mut |relative_path| config.builds_file(relative_path), &mut files)?;
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(files)
+}
+
+fn find_in_dir(
+    shader_root_path: &Path,
+    dir: &Path,
+    extensions: &[&'static str],
+    walk: &mut DirWalk,
+    builds_file: &mut dyn FnMut(&str) -> bool,
+    files: &mut Vec<IngestFile>,
+) -> Result<(), WeslBuildError> {
+    walk.enter(dir)?;
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if walk.is_dir(&path)? {
+            find_in_dir(shader_root_path, &path, extensions, walk, builds_file, files)?;
+            continue;
+        }
+
+        let Some(extension) = extensions.iter().find(|extension| path.extension().is_some_and(|ext| ext == **extension)) else {
+            continue;
+        };
+        let relative_path = crate::walk::relative_path(&path, shader_root_path)?;
+        if !builds_file(&relative_path) {
+            continue;
+        }
+
+        let mut components = crate::module_components(&path, shader_root_path, walk.file_names())?;
+        if let Some(name) = components.last_mut() {
+            name.push_str(&format!("_{extension}"));
+        }
+        files.push(IngestFile {
+            mod_path: ModulePath::new(wesl::syntax::PathOrigin::Absolute, components),
+            path,
+            extension,
+        });
+    }
+    walk.exit();
+
+    Ok(())
+}
@@ -2,18 +2,108 @@ use std::{
     borrow::Cow, error::Error, marker::PhantomData, path::Path
 };
 
-use wesl::{BasicSourceMap, ModulePath, Resolver, Wesl};
+use wesl::{BasicSourceMap, CompileOptions, ModulePath, Resolver, Wesl};
 
-use crate::WeslBuildError;
+use crate::{ModuleGraph, PlannedShader, WeslBuildError};
 
 #[cfg(feature = "wgpu_bindings_ext")]
 pub mod wgpu_bindings;
+#[cfg(feature = "wgpu_bindings_ext")]
+pub(crate) mod bind_group_layouts;
+#[cfg(feature = "wgpu_bindings_ext")]
+pub(crate) mod vertex_layouts;
+#[cfg(feature = "wgpu_bindings_ext")]
+pub(crate) mod pipeline_state;
 
 #[cfg(feature = "wgsl_minifier_ext")]
 pub mod wgsl_minifier;
+#[cfg(feature = "wgsl_obfuscator_ext")]
+pub mod wgsl_obfuscator;
+
+#[cfg(feature = "wesl_lint_ext")]
+pub mod wesl_lint;
+
+#[cfg(feature = "shader_stats_ext")]
+pub mod shader_stats;
+#[cfg(feature = "shader_budget_ext")]
+pub mod shader_budget;
+
+#[cfg(feature = "buffer_size_ext")]
+pub mod buffer_sizes;
+
+#[cfg(feature = "wgsl_target_ext")]
+pub mod wgsl_target;
+#[cfg(feature = "feature_fallback_ext")]
+pub mod feature_fallbacks;
+#[cfg(feature = "binding_remap_ext")]
+pub mod binding_remap;
+#[cfg(feature = "binding_usage_ext")]
+pub mod binding_usage;
+#[cfg(feature = "texture_consistency_ext")]
+pub mod texture_consistency;
+#[cfg(feature = "entry_point_split_ext")]
+pub mod entry_point_split;
+
+#[cfg(feature = "bevy_asset_ext")]
+pub mod bevy_assets;
+
+#[cfg(any(feature = "glsl_ingest_ext", feature = "spirv_ingest_ext"))]
+mod ingest;
+#[cfg(feature = "glsl_ingest_ext")]
+pub mod glsl_ingest;
+#[cfg(feature = "spirv_ingest_ext")]
+pub mod spirv_ingest;
+#[cfg(feature = "hlsl_ingest_ext")]
+pub mod hlsl_ingest;
+#[cfg(feature = "toolchain_validation_ext")]
+pub mod toolchain_validation;
+#[cfg(feature = "gpu_tests")]
+pub mod gpu_smoke_test;
+
+#[cfg(feature = "tokio")]
+pub mod async_extension;
+
+#[cfg(any(feature = "dependency_graph_ext", feature = "uber_shader_ext"))]
+mod comments;
+#[cfg(feature = "shader_registry_ext")]
+pub mod shader_registry;
+#[cfg(feature = "dead_code_report_ext")]
+pub mod dead_code_report;
+#[cfg(feature = "shader_licenses_ext")]
+pub mod shader_licenses;
+#[cfg(feature = "uber_shader_ext")]
+pub mod uber_shader;
+#[cfg(feature = "size_history_ext")]
+pub mod size_history;
+#[cfg(feature = "wgsl_formatter_ext")]
+pub mod wgsl_formatter;
+#[cfg(feature = "dependency_graph_ext")]
+pub mod dependency_graph;
+#[cfg(feature = "shader_package_ext")]
+pub mod shader_package;
+#[cfg(feature = "const_data_ext")]
+pub mod const_data;
+#[cfg(feature = "generated_shaders_ext")]
+pub mod generated_shaders;
+#[cfg(feature = "enable_directives_ext")]
+pub mod enable_directives;
+#[cfg(feature = "composite_ext")]
+pub mod composite;
+#[cfg(feature = "command_ext")]
+pub mod command;
+
+pub mod compat;
+use compat::ExtensionApiVersion;
+
+mod extension_set;
+pub use extension_set::{BoxedExtension, DISABLE_EXT_ENV, ExtensionSet, IntoExtension};
 
 /// A utility that improves the readability of the extensions input into [`build_shader_dir`](`crate::build_shader_dir`)
 ///
+/// Makes an [`ExtensionSet`] and returns a `&mut` to it. Entries can be extension values or already boxed extensions,
+/// gated with `#[cfg(...)]` attributes (e.g. on your crate's features) or a runtime `if condition => extension`.
+/// Extensions named in the [`DISABLE_EXT_ENV`] env var are left out, so heavy ones can be turned off locally.
+///
 /// ## Example
 /// ```no_run
 /// use wesl_build::{build_shader_dir, WeslBuildError};
@@ -25,18 +115,37 @@ pub mod wgsl_minifier;
 ///     extensions![
 ///         WgpuBindingsExtension::new("test/src/shader_bindings").unwrap(),
 //          # Test multiple inputs
-///         # WgpuBindingsExtension::new("test/src/shader_bindings").unwrap()
+///         # WgpuBindingsExtension::new("test/src/shader_bindings").unwrap(),
+///         #[cfg(feature = "wgsl_minifier_ext")]
+///         wesl_build::extension::wgsl_minifier::WgslMinifierExtension::new(),
+///         #[cfg(feature = "wgsl_formatter_ext")]
+///         if std::env::var("PROFILE").as_deref() == Ok("debug") => wesl_build::extension::wgsl_formatter::WgslFormatterExtension::new(),
 ///     ],
 /// ).expect("Building shaders failed");
 /// ```
 #[macro_export]
 macro_rules! extensions {
-    () => (
-        &mut []
-    );
-    // box inputs
-    ($($ext:expr),+ $(,)?) => (
-        &mut [$(::std::boxed::Box::new($ext)),+]
+    (@insert $set:ident;) => {};
+    (@insert $set:ident; $(#[$meta:meta])* if $cond:expr => $ext:expr $(, $($rest:tt)*)?) => {
+        $(#[$meta])*
+        if $cond {
+            $set.insert($ext);
+        }
+        $crate::extensions!(@insert $set; $($($rest)*)?);
+    };
+    (@insert $set:ident; $(#[$meta:meta])* $ext:expr $(, $($rest:tt)*)?) => {
+        $(#[$meta])*
+        $set.insert($ext);
+        $crate::extensions!(@insert $set; $($($rest)*)?);
+    };
+    ($($entries:tt)*) => (
+        &mut {
+            #[allow(unused_mut)]
+            let mut set = $crate::extension::ExtensionSet::new();
+            $crate::extensions!(@insert set; $($entries)*);
+            set.disable_from_env();
+            set
+        }
     );
 }
 
@@ -44,12 +153,25 @@ macro_rules! extensions {
 ///
 /// Extensions are **always** run one at a time (sequentially)
 /// so they can replace `wgsl_built_path` post-build with there output.
-/// But the order is set by how the user orders them,
-/// if your extension needs to run before/after extensions that changes something it must be documented
+/// The order is set by how the user orders them, except that the extensions are reordered
+/// so every [`must_run_after`](Self::must_run_after) and [`must_run_before`](Self::must_run_before) holds
+///
+/// Only [`name`](Self::name) must be implemented, every hook does nothing by default,
+/// so new hooks don't break extensions, see [`compat`] for the versions of this API.
+///
+/// Hooks get the config of the build, a store shared with the other extensions and a scratch dir
+/// from the [`BuildContext`](`crate::BuildContext`), see [`with_build_context`](`crate::with_build_context`)
 pub trait WeslBuildExtension<WeslResolver: Resolver> {
     /// The name to report in errors as the source extension
     fn name<'n>(&self) -> Cow<'n, str>;
 
+    /// The version of the extension API the extension was written against, see [`ExtensionApiVersion`]
+    ///
+    /// The build fails with [`WeslBuildError::UnsupportedExtensionApi`] before any hook runs if it isn't supported.
+    fn extension_api_version(&self) -> ExtensionApiVersion {
+        ExtensionApiVersion::CURRENT
+    }
+
     /// The first time the extension is called this is in the root before any files/modules are entered
     ///
     /// ### Args
@@ -57,9 +179,19 @@ pub trait WeslBuildExtension<WeslResolver: Resolver> {
     /// * `res` - the wesl resolver being used by `wesl_build`
     fn init_root(
         &mut self,
-        shader_root_path: &str,
-        res: &mut Wesl<WeslResolver>,
-    ) -> Result<(), Box<dyn Error>>;
+        _shader_root_path: &str,
+        _res: &mut Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    /// Called once after `init_root` with the modules and their imports, before any shaders are built
+    ///
+    /// ### Args
+    /// * `graph` - every module in the shader dir and the modules it imports
+    fn on_graph(&mut self, _graph: &ModuleGraph) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
 
     /// The last time the extension is called this is in the root after all files/modules are covered
     ///
@@ -78,34 +210,165 @@ pub trait WeslBuildExtension<WeslResolver: Resolver> {
     ///
     /// ### Args
     /// * `dir_path` - the current dir of the mod we are entering
-    fn enter_mod(&mut self, dir_path: &Path) -> Result<(), Box<dyn Error>>;
+    fn enter_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
     /// Go one level out of a shader module
     ///
     /// ### Args
     /// * `dir_path` - the current dir of the mod we are exiting
-    fn exit_mod(&mut self, dir_path: &Path) -> Result<(), Box<dyn Error>>;
+    fn exit_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    /// Run for every sidecar file in the shader dir, a file with one of the
+    /// [`asset_extensions`](`crate::WeslBuildConfig::asset_extensions`), in the walk order of the shaders
+    ///
+    /// e.g. to read the render state of a shader from the `.json` next to it.
+    ///
+    /// ### Args
+    /// * `asset_path` - the path of the file
+    fn on_asset(&mut self, _asset_path: &Path) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    /// Run on the WESL source of every module in the shader dir before any is compiled, to rewrite it
+    ///
+    /// e.g. expand custom directives or generate declarations. The compiler and the extensions after this one
+    /// see the rewritten source, the file itself is left unchanged.
+    ///
+    /// ### Args
+    /// * `wesl_path` - the module of the source
+    /// * `source` - the source, as rewritten by the extensions before this one
+    fn preprocess(&mut self, _wesl_path: &ModulePath, _source: &mut String) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    /// Run before a `wesl` file is compiled, to change the compile options of just that shader
+    ///
+    /// e.g. only enable lowering for some modules, or set different WESL features per dir.
+    /// The options start out as [`WeslBuildConfig::compile_options`](`crate::WeslBuildConfig::compile_options`)
+    /// for every shader and are passed through each extension in order.
+    ///
+    /// ### Args
+    /// * `wesl_path` - the module about to be compiled
+    /// * `options` - the options it will be compiled with
+    fn configure(&mut self, _wesl_path: &ModulePath, _options: &mut CompileOptions) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    /// The name of the built artifact of `wesl_path`, its `/` separated path in the output dir without the `.wgsl`,
+    /// e.g. `assets/3f2a9c` for a hashed asset pipeline, `None` to keep the name of the layout
+    ///
+    /// The first extension to return a name renames the artifact. The name is written to the build manifest,
+    /// so `include_wesl!` finds the artifact, and later hooks get the renamed path, e.g. in `post_build`.
+    ///
+    /// ### Args
+    /// * `wesl_path` - the module about to be built
+    fn artifact_name(&self, _wesl_path: &ModulePath) -> Option<String> {
+        None
+    }
+
+    /// Run after a `wesl` file is compiled and before [`post_build`](Self::post_build), with the naga IR of the built shader
+    ///
+    /// The built WGSL is parsed once and passed to every extension in order,
+    /// if any of them mutated the module the WGSL is written back before `post_build`.
+    ///
+    /// ### Args
+    /// * `wesl_path` - the module that was compiled
+    /// * `module` - the naga IR of the built shader
+    #[cfg(feature = "naga")]
+    fn post_parse(&mut self, _wesl_path: &ModulePath, _module: &mut crate::NagaModule) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
 
     /// Run after a `wesl` file is compiled
     ///
     /// ### Args
     /// * `wesl_path` - the path to the wesl file
-    /// * `wgsl_built_path` - the path to the built wgsl file
+    /// * `wgsl_built_path` - the path to the built wgsl file, read and write it with [`crate::artifacts`]
     fn post_build(
         &mut self,
-        wesl_path: &ModulePath,
-        wgsl_built_path: &str,
-        source_map: &Option<BasicSourceMap>,
-    ) -> Result<(), Box<dyn Error>>;
+        _wesl_path: &ModulePath,
+        _wgsl_built_path: &str,
+        _source_map: &Option<BasicSourceMap>,
+    ) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    /// Run when a shader fails to build, after its compile error or the error of an extension's hook
+    ///
+    /// Only observes the failure, e.g. to show it in an overlay or to clean up what was written for the shader.
+    /// With [`continue_on_error`](`crate::WeslBuildConfig::continue_on_error`) the build goes on with the next shader.
+    ///
+    /// ### Args
+    /// * `wesl_path` - the module that failed to build
+    /// * `error` - why it failed, e.g. a [`WeslBuildError::CompileErr`] with the diagnostics of the compiler
+    fn on_error(&mut self, _wesl_path: &ModulePath, _error: &WeslBuildError) {}
+
+    /// Whether `post_build` reads the built WGSL file
+    fn reads_built_wgsl(&self) -> bool {
+        false
+    }
+
+    /// Whether `post_build` replaces the built WGSL file, e.g. a minifier
+    fn mutates_built_wgsl(&self) -> bool {
+        false
+    }
+
+    /// The [names](Self::name) of the extensions this one must run after, if they are used
+    fn must_run_after(&self) -> &[&str] {
+        &[]
+    }
+
+    /// The [names](Self::name) of the extensions this one must run before, if they are used
+    fn must_run_before(&self) -> &[&str] {
+        &[]
+    }
+
+    /// Run for each shader in a [dry run](`crate::WeslBuildConfig::dry_run`) instead of all other hooks
+    ///
+    /// ### Args
+    /// * `shader` - the shader that would be built and where
+    fn would_build(&mut self, _shader: &PlannedShader) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
 }
+
+/// The visibility of an item in generated rust code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Visibility {
+    /// `pub`
+    Public,
+    /// `pub(crate)`
+    #[default]
+    Crate,
+    /// no visibility modifier
+    Private,
 }
 
-/// Util for wrapping an extensions error in a [`WeslBuildError`]
+impl std::fmt::Display for Visibility {
+    /// Writes the modifier with a trailing space, so it can be put directly before an item
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Visibility::Public => f.write_str("pub "),
+            Visibility::Crate => f.write_str("pub(crate) "),
+            Visibility::Private => Ok(()),
+        }
+    }
+}
+
+/// Util for wrapping the error of an extension's `hook` in a [`WeslBuildError`]
 pub(crate) fn extension_error(
     ext: &dyn WeslBuildExtension<impl Resolver>,
+    hook: &'static str,
+    mod_path: Option<&ModulePath>,
     error: Box<dyn Error>,
 ) -> WeslBuildError {
-    WeslBuildError::ExtensionErr {
-        extension_name: ext.name().into_owned(),
-        error,
-    }
+    // only a composite extension nests the errors of others
+    #[cfg(feature = "composite_ext")]
+    let composite::NestedExtensionError { extension_name, error } = composite::nested_error(ext, error);
+    #[cfg(not(feature = "composite_ext"))]
+    let extension_name = ext.name().into_owned();
+    WeslBuildError::ExtensionErr { extension_name, hook, mod_path: mod_path.cloned(), error }
 }
@@ -0,0 +1,295 @@
+#![cfg(feature = "wgpu_bindings_ext")]
+
+//! Parse the render state sidecar of a shader, `<name>.pipeline.toml`, and generate a `wgpu` pipeline descriptor from it
+
+use std::fmt::Write as _;
+
+/// The suffix of the render state sidecar of a shader, `quad.pipeline.toml` is that of `quad.wgsl`
+pub(crate) const PIPELINE_STATE_SUFFIX: &str = ".pipeline.toml";
+
+/// The render state of a shader, every field is the rust expression of its `wgpu` value
+///
+/// A small subset of TOML, with WebGPU's names for the values:
+/// ```toml
+/// vertex = "vs_main"        # optional with a single vertex entry point
+/// fragment = "fs_main"      # optional with at most one fragment entry point
+/// topology = "triangle-list"
+/// cull = "back"             # none, front or back
+/// front_face = "ccw"
+/// blend = "alpha"           # none, replace, alpha, premultiplied or additive
+///
+/// [depth]
+/// format = "depth32float"
+/// write = true
+/// compare = "less"
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PipelineState {
+    pub(crate) vertex: Option<String>,
+    pub(crate) fragment: Option<String>,
+    pub(crate) topology: &'static str,
+    pub(crate) cull_mode: &'static str,
+    pub(crate) front_face: &'static str,
+    /// `None` if not set, so it can be checked against shaders without a fragment entry point
+    pub(crate) blend: Option<&'static str>,
+    pub(crate) depth: Option<DepthState>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct DepthState {
+    pub(crate) format: &'static str,
+    pub(crate) write: bool,
+    pub(crate) compare: &'static str,
+}
+
+const TOPOLOGIES: [(&str, &str); 5] = [
+    ("point-list", "wgpu::PrimitiveTopology::PointList"),
+    ("line-list", "wgpu::PrimitiveTopology::LineList"),
+    ("line-strip", "wgpu::PrimitiveTopology::LineStrip"),
+    ("triangle-list", "wgpu::PrimitiveTopology::TriangleList"),
+    ("triangle-strip", "wgpu::PrimitiveTopology::TriangleStrip"),
+];
+const CULL_MODES: [(&str, &str); 3] = [
+    ("none", "None"),
+    ("front", "Some(wgpu::Face::Front)"),
+    ("back", "Some(wgpu::Face::Back)"),
+];
+const FRONT_FACES: [(&str, &str); 2] = [("ccw", "wgpu::FrontFace::Ccw"), ("cw", "wgpu::FrontFace::Cw")];
+
+/// A `wgpu::BlendComponent` adding the source to the destination
+macro_rules! additive_component {
+    () => {
+        "wgpu::BlendComponent { src_factor: wgpu::BlendFactor::One, dst_factor: wgpu::BlendFactor::One, operation: wgpu::BlendOperation::Add }"
+    };
+}
+
+const BLENDS: [(&str, &str); 5] = [
+    ("none", "None"),
+    ("replace", "Some(wgpu::BlendState::REPLACE)"),
+    ("alpha", "Some(wgpu::BlendState::ALPHA_BLENDING)"),
+    ("premultiplied", "Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING)"),
+    ("additive", concat!(
+        "Some(wgpu::BlendState { color: ", additive_component!(), ", alpha: ", additive_component!(), " })",
+    )),
+];
+const DEPTH_FORMATS: [(&str, &str); 5] = [
+    ("depth16unorm", "wgpu::TextureFormat::Depth16Unorm"),
+    ("depth24plus", "wgpu::TextureFormat::Depth24Plus"),
+    ("depth24plus-stencil8", "wgpu::TextureFormat::Depth24PlusStencil8"),
+    ("depth32float", "wgpu::TextureFormat::Depth32Float"),
+    ("depth32float-stencil8", "wgpu::TextureFormat::Depth32FloatStencil8"),
+];
+const COMPARE_FUNCTIONS: [(&str, &str); 8] = [
+    ("never", "wgpu::CompareFunction::Never"),
+    ("less", "wgpu::CompareFunction::Less"),
+    ("equal", "wgpu::CompareFunction::Equal"),
+    ("less-equal", "wgpu::CompareFunction::LessEqual"),
+    ("greater", "wgpu::CompareFunction::Greater"),
+    ("not-equal", "wgpu::CompareFunction::NotEqual"),
+    ("greater-equal", "wgpu::CompareFunction::GreaterEqual"),
+    ("always", "wgpu::CompareFunction::Always"),
+];
+
+impl PipelineState {
+    /// Parse a sidecar, unknown keys and values are errors so typos fail the build
+    pub(crate) fn parse(source: &str) -> Result<Self, String> {
+        let mut state = Self {
+            vertex: None,
+            fragment: None,
+            topology: TOPOLOGIES[3].1,
+            cull_mode: CULL_MODES[0].1,
+            front_face: FRONT_FACES[0].1,
+            blend: None,
+            depth: None,
+        };
+        // the depth format has no default, so the section is kept until it ends
+        let mut depth: Option<(Option<&'static str>, bool, &'static str)> = None;
+        let mut section = "";
+
+        for (index, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let at_line = |message: String| format!("line {}: {message}", index + 1);
+
+            if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+                section = match name.trim() {
+                    "depth" => "depth",
+                    name => return Err(at_line(format!("unknown section `[{name}]`, expected `[depth]`"))),
+                };
+                depth = depth.or(Some((None, true, COMPARE_FUNCTIONS[1].1)));
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(at_line(format!("expected `key = value`, found `{line}`")));
+            };
+            let (key, value) = (key.trim(), value.trim());
+
+            match (section, &mut depth, key) {
+                ("", _, "vertex") => state.vertex = Some(string(value).map_err(at_line)?.to_owned()),
+                ("", _, "fragment") => state.fragment = Some(string(value).map_err(at_line)?.to_owned()),
+                ("", _, "topology") => state.topology = choice(value, &TOPOLOGIES).map_err(at_line)?,
+                ("", _, "cull") => state.cull_mode = choice(value, &CULL_MODES).map_err(at_line)?,
+                ("", _, "front_face") => state.front_face = choice(value, &FRONT_FACES).map_err(at_line)?,
+                ("", _, "blend") => state.blend = Some(choice(value, &BLENDS).map_err(at_line)?),
+                (_, Some((format, ..)), "format") => *format = Some(choice(value, &DEPTH_FORMATS).map_err(at_line)?),
+                (_, Some((_, write, _)), "write") => *write = match value {
+                    "true" => true,
+                    "false" => false,
+                    _ => return Err(at_line(format!("expected `true` or `false` for `write`, found `{value}`"))),
+                },
+                (_, Some((.., compare)), "compare") => *compare = choice(value, &COMPARE_FUNCTIONS).map_err(at_line)?,
+                ("", ..) => return Err(at_line(format!(
+                    "unknown key `{key}`, expected `vertex`, `fragment`, `topology`, `cull`, `front_face` or `blend`"
+                ))),
+                _ => return Err(at_line(format!("unknown key `{key}` in `[depth]`, expected `format`, `write` or `compare`"))),
+            }
+        }
+
+        if let Some((format, write, compare)) = depth {
+            let format = format.ok_or("`[depth]` needs a `format`, e.g. `format = \"depth32float\"`")?;
+            state.depth = Some(DepthState { format, write, compare });
+        }
+        Ok(state)
+    }
+
+    /// The `primitive_state`, `depth_stencil_state` and `pipeline_descriptor` functions of the render pipeline of `module`,
+    /// and a `BLEND` const and `color_target` function if it has a fragment entry point
+    ///
+    /// `label` labels the pipeline and `sidecar` is the file name of the state, for the docs.
+    /// Fails if the entry points of the state are not in `module`, or are ambiguous without them.
+    pub(crate) fn to_rust(&self, module: &naga::Module, label: &str, sidecar: &str) -> Result<String, String> {
+        let vertex = entry_point(module, naga::ShaderStage::Vertex, self.vertex.as_deref())?
+            .ok_or("a render pipeline needs a vertex entry point, the shader has none")?;
+        let fragment = entry_point(module, naga::ShaderStage::Fragment, self.fragment.as_deref())?;
+        if fragment.is_none() && self.blend.is_some() {
+            return Err("`blend` is set, but the shader has no fragment entry point".to_owned());
+        }
+
+        let mut rust = format!(
+            "\n/// The primitive state of `{sidecar}`\n\
+            pub fn primitive_state() -> wgpu::PrimitiveState {{\n    \
+                wgpu::PrimitiveState {{\n        \
+                    topology: {},\n        \
+                    front_face: {},\n        \
+                    cull_mode: {},\n        \
+                    ..Default::default()\n    \
+                }}\n\
+            }}\n",
+            self.topology, self.front_face, self.cull_mode,
+        );
+
+        let depth_stencil = match &self.depth {
+            Some(depth) => format!(
+                "Some(wgpu::DepthStencilState {{\n        \
+                    format: {},\n        \
+                    depth_write_enabled: {},\n        \
+                    depth_compare: {},\n        \
+                    stencil: Default::default(),\n        \
+                    bias: Default::default(),\n    \
+                }})",
+                depth.format, depth.write, depth.compare,
+            ),
+            None => "None".to_owned(),
+        };
+        writeln!(
+            rust,
+            "\n/// The depth state of `{sidecar}`\n\
+            pub fn depth_stencil_state() -> Option<wgpu::DepthStencilState> {{\n    \
+                {depth_stencil}\n\
+            }}",
+        ).expect("writing to a String can't fail");
+
+        let (targets_arg, fragment_state) = match fragment {
+            Some(fragment) => {
+                write!(
+                    rust,
+                    "\n/// The blend state of the color targets in `{sidecar}`\n\
+                    pub const BLEND: Option<wgpu::BlendState> = {blend};\n\
+                    \n\
+                    /// A color target of `format` with the blend state of `{sidecar}`\n\
+                    pub fn color_target(format: wgpu::TextureFormat) -> wgpu::ColorTargetState {{\n    \
+                        wgpu::ColorTargetState {{ format, blend: BLEND, write_mask: wgpu::ColorWrites::ALL }}\n\
+                    }}\n",
+                    blend = self.blend.unwrap_or(BLENDS[0].1),
+                ).expect("writing to a String can't fail");
+                (
+                    "\n    targets: &'a [Option<wgpu::ColorTargetState>],",
+                    format!(
+                        "Some(wgpu::FragmentState {{\n            \
+                            module,\n            \
+                            entry_point: Some({:?}),\n            \
+                            compilation_options: Default::default(),\n            \
+                            targets,\n        \
+                        }})",
+                        fragment.name,
+                    ),
+                )
+            }
+            None => ("", "None".to_owned()),
+        };
+
+        writeln!(
+            rust,
+            "\n/// The descriptor of the render pipeline of `{vertex_name}` with the state of `{sidecar}`,\n\
+            /// `module` and `layout` are from `create_shader_module` and `create_pipeline_layout`\n\
+            pub fn pipeline_descriptor<'a>(\n    \
+                module: &'a wgpu::ShaderModule,\n    \
+                layout: &'a wgpu::PipelineLayout,{targets_arg}\n\
+            ) -> wgpu::RenderPipelineDescriptor<'a> {{\n    \
+                wgpu::RenderPipelineDescriptor {{\n        \
+                    label: Some(\"{label} render pipeline\"),\n        \
+                    layout: Some(layout),\n        \
+                    vertex: wgpu::VertexState {{\n            \
+                        module,\n            \
+                        entry_point: Some({vertex_name:?}),\n            \
+                        compilation_options: Default::default(),\n            \
+                        buffers: &{buffers}_VERTEX_BUFFERS,\n        \
+                    }},\n        \
+                    primitive: primitive_state(),\n        \
+                    depth_stencil: depth_stencil_state(),\n        \
+                    multisample: Default::default(),\n        \
+                    fragment: {fragment_state},\n        \
+                    multiview: None,\n        \
+                    cache: None,\n    \
+                }}\n\
+            }}",
+            vertex_name = vertex.name,
+            buffers = vertex.name.to_uppercase(),
+        ).expect("writing to a String can't fail");
+
+        Ok(rust)
+    }
+}
+
+/// The entry point of `stage` named `name`, or the only one if `name` is `None`
+fn entry_point<'m>(module: &'m naga::Module, stage: naga::ShaderStage, name: Option<&str>) -> Result<Option<&'m naga::EntryPoint>, String> {
+    let stage_name = format!("{stage:?}").to_lowercase();
+    let of_stage = module.entry_points.iter()
+        .filter(|entry_point| entry_point.stage == stage)
+        .collect::<Vec<_>>();
+    match (name, of_stage.as_slice()) {
+        (Some(name), _) => of_stage.iter().find(|entry_point| entry_point.name == name)
+            .map(|entry_point| Some(*entry_point))
+            .ok_or_else(|| format!("the shader has no {stage_name} entry point `{name}`")),
+        (None, []) => Ok(None),
+        (None, [entry_point]) => Ok(Some(*entry_point)),
+        (None, _) => Err(format!("the shader has several {stage_name} entry points, pick one with `{stage_name} = \"...\"`")),
+    }
+}
+
+/// The content of the quoted string `value`
+fn string(value: &str) -> Result<&str, String> {
+    value.strip_prefix('"').and_then(|value| value.strip_suffix('"'))
+        .ok_or_else(|| format!("expected a quoted string, found `{value}`"))
+}
+
+/// The rust expression of the choice `value`
+fn choice(value: &str, choices: &[(&str, &'static str)]) -> Result<&'static str, String> {
+    let name = string(value)?;
+    choices.iter().find(|(choice, _)| *choice == name).map(|(_, rust)| *rust).ok_or_else(|| format!(
+        "unknown value `{name}`, expected one of {}",
+        choices.iter().map(|(choice, _)| format!("`{choice}`")).collect::<Vec<_>>().join(", "),
+    ))
+}
@@ -0,0 +1,156 @@
+#![cfg(feature = "shader_budget_ext")]
+
+use std::fmt::Display;
+use std::path::Path;
+
+use wesl::{BasicSourceMap, ModulePath};
+
+use crate::WeslBuildExtension;
+use super::shader_stats::ShaderStats;
+
+/// A limit of a [`ShaderBudgetExtension`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BudgetLimit {
+    /// The size of the built WGSL in bytes
+    CompiledBytes,
+    /// The highest bind group index + 1
+    BindGroups,
+    /// The total size of the `var<workgroup>`s in bytes
+    WorkgroupStorageBytes,
+    /// The `@location` outputs of a vertex entry point
+    Varyings,
+}
+
+impl Display for BudgetLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            BudgetLimit::CompiledBytes => "compiled bytes",
+            BudgetLimit::BindGroups => "bind groups",
+            BudgetLimit::WorkgroupStorageBytes => "workgroup storage bytes",
+            BudgetLimit::Varyings => "varyings",
+        })
+    }
+}
+
+/// A limit that a shader exceeded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BudgetViolation {
+    pub limit: BudgetLimit,
+    pub value: usize,
+    pub max: usize,
+}
+
+impl Display for BudgetViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} > {}", self.value, self.limit, self.max)
+    }
+}
+
+/// A shader exceeded its budget
+#[derive(Debug, thiserror::Error)]
+#[error("`{mod_path}` exceeds the shader budget: {}", .violations.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "))]
+pub struct ShaderBudgetError {
+    pub mod_path: ModulePath,
+    pub violations: Vec<BudgetViolation>,
+}
+
+/// Fail the build when a built shader exceeds any of the declared limits
+///
+/// No limits are set by [`new`](Self::new), use [`webgpu_defaults`](Self::webgpu_defaults)
+/// to stay within the limits every WebGPU implementation supports.
+#[derive(Debug, Clone, Default)]
+pub struct ShaderBudgetExtension {
+    max_compiled_bytes: Option<usize>,
+    max_bind_groups: Option<usize>,
+    max_workgroup_storage_bytes: Option<usize>,
+    max_varyings: Option<usize>,
+}
+
+impl ShaderBudgetExtension {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The default limits of WebGPU: 4 bind groups, 16KiB of workgroup storage and 16 inter-stage variables
+    pub fn webgpu_defaults() -> Self {
+        Self::new()
+            .with_max_bind_groups(4)
+            .with_max_workgroup_storage_bytes(16384)
+            .with_max_varyings(16)
+    }
+
+    pub fn with_max_compiled_bytes(mut self, max_compiled_bytes: usize) -> Self {
+        self.max_compiled_bytes = Some(max_compiled_bytes);
+        self
+    }
+
+    pub fn with_max_bind_groups(mut self, max_bind_groups: usize) -> Self {
+        self.max_bind_groups = Some(max_bind_groups);
+        self
+    }
+
+    pub fn with_max_workgroup_storage_bytes(mut self, max_workgroup_storage_bytes: usize) -> Self {
+        self.max_workgroup_storage_bytes = Some(max_workgroup_storage_bytes);
+        self
+    }
+
+    pub fn with_max_varyings(mut self, max_varyings: usize) -> Self {
+        self.max_varyings = Some(max_varyings);
+        self
+    }
+
+    /// The limits `stats` exceeds
+    pub fn violations(&self, stats: &ShaderStats) -> Vec<BudgetViolation> {
+        [
+            (BudgetLimit::CompiledBytes, self.max_compiled_bytes, stats.built_bytes),
+            (BudgetLimit::BindGroups, self.max_bind_groups, stats.bind_groups() as usize),
+            (BudgetLimit::WorkgroupStorageBytes, self.max_workgroup_storage_bytes, stats.workgroup_storage_bytes as usize),
+            (BudgetLimit::Varyings, self.max_varyings, stats.varyings),
+        ]
+        .into_iter()
+        .filter_map(|(limit, max, value)| max
+            .filter(|max| value > *max)
+            .map(|max| BudgetViolation { limit, value, max }))
+        .collect()
+    }
+}
+
+impl<WeslResolver: wesl::Resolver> WeslBuildExtension<WeslResolver> for ShaderBudgetExtension {
+    fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+        "ShaderBudgetExtension".into()
+    }
+
+    fn reads_built_wgsl(&self) -> bool {
+        true
+    }
+
+    fn init_root(
+        &mut self,
+        _shader_path: &str,
+        _res: &mut wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    fn enter_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+    fn exit_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+
+    fn post_build(
+        &mut self,
+        mod_path: &ModulePath,
+        wgsl_built_path: &str,
+        _source_map: &Option<BasicSourceMap>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let stats = ShaderStats::from_built_shader(mod_path, wgsl_built_path)?;
+        let violations = self.violations(&stats);
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(Box::new(ShaderBudgetError {
+                mod_path: mod_path.clone(),
+                violations,
+            }))
+        }
+    }
+}
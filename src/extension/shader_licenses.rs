@@ -0,0 +1,230 @@
+#![cfg(feature = "shader_licenses_ext")]
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use wesl::{BasicSourceMap, ModulePath};
+
+use crate::{ModuleGraph, WeslBuildExtension, artifacts};
+use crate::build_out_dir;
+
+/// The file name of the consolidated licenses in the output dir
+pub const THIRD_PARTY_SHADERS_NAME: &str = "THIRD_PARTY_SHADERS.txt";
+
+/// Collect the license headers of the source modules, which minifying strips, into one [`THIRD_PARTY_SHADERS_NAME`] file
+///
+/// A license header is the comments at the top of a module, if they mention one of the markers,
+/// `SPDX-License-Identifier`, `Copyright` or `License` by default. Modules with the same header are listed together.
+/// With [`with_attribution`](Self::with_attribution) a short attribution comment is put back at the top of every
+/// built shader that includes a licensed module, after the minifier or obfuscator ran.
+///
+/// ## Example
+/// ```
+/// use wesl_build::extension::shader_licenses::ShaderLicenseExtension;
+///
+/// let licenses = ShaderLicenseExtension::new().with_attribution(true).with_marker("Permission is hereby granted");
+/// ```
+#[derive(Debug, Clone)]
+pub struct ShaderLicenseExtension {
+    markers: Vec<String>,
+    output_path: Option<PathBuf>,
+    attribution: bool,
+    /// The license header of every licensed module
+    headers: BTreeMap<ModulePath, String>,
+    /// The licensed modules every module includes, itself or through its imports
+    licensed_dependencies: BTreeMap<ModulePath, Vec<ModulePath>>,
+}
+
+impl Default for ShaderLicenseExtension {
+    fn default() -> Self {
+        Self {
+            markers: ["SPDX-License-Identifier", "Copyright", "License"].map(str::to_owned).to_vec(),
+            output_path: None,
+            attribution: false,
+            headers: BTreeMap::new(),
+            licensed_dependencies: BTreeMap::new(),
+        }
+    }
+}
+
+impl ShaderLicenseExtension {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also treat the top comments of a module as its license header if they contain `marker`, case insensitive
+    pub fn with_marker(mut self, marker: impl Into<String>) -> Self {
+        self.markers.push(marker.into());
+        self
+    }
+
+    /// Write the licenses to `output_path` instead of `THIRD_PARTY_SHADERS.txt` in the output dir
+    pub fn with_output_path(mut self, output_path: impl AsRef<Path>) -> Self {
+        self.output_path = Some(output_path.as_ref().to_owned());
+        self
+    }
+
+    /// Put an attribution comment at the top of the built shaders that include licensed modules
+    pub fn with_attribution(mut self, attribution: bool) -> Self {
+        self.attribution = attribution;
+        self
+    }
+
+    /// The license header of every licensed module, by module
+    pub fn headers(&self) -> &BTreeMap<ModulePath, String> {
+        &self.headers
+    }
+
+    /// The consolidated licenses, the modules sharing a header listed above it
+    fn licenses(&self) -> String {
+        let mut modules_of_header = BTreeMap::<&str, Vec<&ModulePath>>::new();
+        for (mod_path, header) in &self.headers {
+            modules_of_header.entry(header).or_default().push(mod_path);
+        }
+
+        let mut licenses = "Third-party shader code and its licenses\n".to_owned();
+        for (header, modules) in modules_of_header {
+            let modules = modules.iter().map(|mod_path| mod_path.components.join("::")).collect::<Vec<_>>();
+            write!(licenses, "\n{}\n{}\n{header}\n", modules.join(", "), "-".repeat(40)).expect("writing to a String can't fail");
+        }
+        licenses
+    }
+
+    /// The comment naming the licenses of `licensed` modules
+    fn attribution_comment(&self, licensed: &[ModulePath]) -> String {
+        let summaries = licensed.iter()
+            .filter_map(|mod_path| self.headers.get(mod_path))
+            .map(|header| license_summary(header))
+            .collect::<BTreeSet<_>>();
+        let file_name = self.output_path.as_deref()
+            .and_then(Path::file_name)
+            .map_or(THIRD_PARTY_SHADERS_NAME.into(), |name| name.to_string_lossy());
+        format!(
+            "// Contains third-party code: {}, see {file_name}\n",
+            summaries.into_iter().collect::<Vec<_>>().join("; "),
+        )
+    }
+}
+
+/// The text of the comments at the top of `source` if it mentions one of `markers`, without the comment markers
+pub fn license_header(source: &str, markers: &[String]) -> Option<String> {
+    let mut lines = Vec::new();
+    let mut in_block = false;
+    for line in source.lines() {
+        let line = line.trim();
+        if in_block {
+            let (text, end) = match line.split_once("*/") {
+                Some((text, _)) => (text, true),
+                None => (line, false),
+            };
+            lines.push(text.trim_start_matches('*').trim());
+            in_block = !end;
+        } else if let Some(text) = line.strip_prefix("//") {
+            lines.push(text.trim_start_matches('/').trim());
+        } else if let Some(text) = line.strip_prefix("/*") {
+            let (text, end) = match text.split_once("*/") {
+                Some((text, _)) => (text, true),
+                None => (text, false),
+            };
+            lines.push(text.trim_start_matches('*').trim());
+            in_block = !end;
+        } else if line.is_empty() {
+            lines.push("");
+        } else {
+            break;
+        }
+    }
+
+    let header = lines.join("\n").trim().to_owned();
+    let lowercase = header.to_lowercase();
+    markers.iter()
+        .any(|marker| lowercase.contains(&marker.to_lowercase()))
+        .then_some(header)
+}
+
+/// The SPDX identifier of a license header, or its first line
+fn license_summary(header: &str) -> String {
+    header.lines()
+        .find_map(|line| line.split_once("SPDX-License-Identifier:").map(|(_, id)| id.trim().to_owned()))
+        .unwrap_or_else(|| header.lines().next().unwrap_or_default().to_owned())
+}
+
+impl<WeslResolver: wesl::Resolver> WeslBuildExtension<WeslResolver> for ShaderLicenseExtension {
+    fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+        "ShaderLicenseExtension".into()
+    }
+
+    fn reads_built_wgsl(&self) -> bool {
+        self.attribution
+    }
+
+    fn mutates_built_wgsl(&self) -> bool {
+        self.attribution
+    }
+
+    /// The attribution must survive the extensions that rewrite the built shaders
+    fn must_run_after(&self) -> &[&str] {
+        &["WgslMinifierExtension", "WgslObfuscatorExtension", "WgslFormatterExtension"]
+    }
+
+    fn on_graph(&mut self, graph: &ModuleGraph) -> Result<(), Box<dyn std::error::Error>> {
+        self.headers.clear();
+        for mod_path in graph.modules() {
+            let Some(source_path) = graph.source_path(mod_path) else { continue };
+            if let Some(header) = license_header(&fs::read_to_string(source_path)?, &self.markers) {
+                self.headers.insert(mod_path.clone(), header);
+            }
+        }
+
+        self.licensed_dependencies = graph.modules()
+            .map(|mod_path| {
+                let licensed = std::iter::once(mod_path)
+                    .chain(graph.dependencies(mod_path))
+                    .filter(|dependency| self.headers.contains_key(dependency))
+                    .cloned()
+                    .collect();
+                (mod_path.clone(), licensed)
+            })
+            .collect();
+
+        Ok(())
+    }
+
+    fn exit_root(
+        &mut self,
+        _shader_root_path: &str,
+        _res: &wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if crate::is_check() {
+            return Ok(());
+        }
+        let output_path = match &self.output_path {
+            Some(output_path) => output_path.clone(),
+            None => build_out_dir()?.join(THIRD_PARTY_SHADERS_NAME),
+        };
+        artifacts::write(output_path, self.licenses())?;
+
+        Ok(())
+    }
+
+    fn post_build(
+        &mut self,
+        mod_path: &ModulePath,
+        wgsl_built_path: &str,
+        _source_map: &Option<BasicSourceMap>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.attribution {
+            return Ok(());
+        }
+        let Some(licensed) = self.licensed_dependencies.get(mod_path).filter(|licensed| !licensed.is_empty()) else {
+            return Ok(());
+        };
+
+        let built = artifacts::read_to_string(wgsl_built_path)?;
+        artifacts::write(wgsl_built_path, self.attribution_comment(licensed) + &built)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,142 @@
+#![cfg(feature = "shader_package_ext")]
+
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use wesl::{BasicSourceMap, ModulePath};
+
+use crate::{ModuleGraph, WeslBuildExtension, artifacts};
+use crate::build_out_dir;
+use crate::package::{PACKAGE_MANIFEST_NAME, PACKAGE_SOURCE_DIR};
+
+/// Package the shader sources so other crates can import them as a WESL package
+///
+/// Writes a copy of every shader to `<output dir>/shaders` and a [`PACKAGE_MANIFEST_NAME`] manifest
+/// with the name, version and modules of the package, to `shader_package` in the output dir by default.
+/// Add the output dir with [`WeslBuildConfig::add_package`](`crate::WeslBuildConfig::add_package`),
+/// or export it with [`export_shader_package`](`crate::package::export_shader_package`).
+pub struct ShaderPackageExtension {
+    name: String,
+    version: Option<String>,
+    description: Option<String>,
+    /// Where to write the package, `None` for `shader_package` in the output dir
+    output_dir: Option<PathBuf>,
+    /// The graph of the build, from `on_graph`
+    graph: ModuleGraph,
+}
+
+impl ShaderPackageExtension {
+    /// Package the shaders as `name`, the name downstream shaders import it by
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            version: None,
+            description: None,
+            output_dir: None,
+            graph: ModuleGraph::default(),
+        }
+    }
+
+    /// The version in the manifest, defaults to cargo's `CARGO_PKG_VERSION`
+    pub fn with_version(mut self, version: impl Into<String>) -> Self {
+        self.version = Some(version.into());
+        self
+    }
+
+    /// A description of the package for the manifest
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Write the package to `output_dir` instead of `shader_package` in the output dir, e.g. a `dist/` dir
+    pub fn with_output_dir(mut self, output_dir: impl AsRef<Path>) -> Self {
+        self.output_dir = Some(output_dir.as_ref().to_owned());
+        self
+    }
+
+    /// The `wesl.toml`-style manifest of the package
+    fn manifest(&self, modules: &[String]) -> String {
+        let version = self.version.clone()
+            .or_else(|| std::env::var("CARGO_PKG_VERSION").ok())
+            .unwrap_or_else(|| "0.0.0".to_owned());
+
+        let mut manifest = String::from("[package]\n");
+        writeln!(manifest, "name = {:?}", self.name).expect("writing to a String can't fail");
+        writeln!(manifest, "version = {version:?}").expect("writing to a String can't fail");
+        if let Some(description) = &self.description {
+            writeln!(manifest, "description = {description:?}").expect("writing to a String can't fail");
+        }
+        writeln!(manifest, "edition = \"unstable_2025\"").expect("writing to a String can't fail");
+        writeln!(manifest, "root = {PACKAGE_SOURCE_DIR:?}").expect("writing to a String can't fail");
+        writeln!(manifest, "modules = [").expect("writing to a String can't fail");
+        for module in modules {
+            writeln!(manifest, "    {module:?},").expect("writing to a String can't fail");
+        }
+        manifest.push_str("]\n");
+
+        manifest
+    }
+}
+
+impl<WeslResolver: wesl::Resolver> WeslBuildExtension<WeslResolver> for ShaderPackageExtension {
+    fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+        "ShaderPackageExtension".into()
+    }
+
+    fn init_root(
+        &mut self,
+        _shader_path: &str,
+        _res: &mut wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    fn on_graph(&mut self, graph: &ModuleGraph) -> Result<(), Box<dyn std::error::Error>> {
+        self.graph = graph.clone();
+        Ok(())
+    }
+
+    fn exit_root(
+        &mut self,
+        shader_root_path: &str,
+        _res: &wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if crate::is_check() {
+            return Ok(());
+        }
+        let output_dir = match &self.output_dir {
+            Some(output_dir) => output_dir.clone(),
+            None => build_out_dir()?.join("shader_package"),
+        };
+
+        let mut modules = Vec::new();
+        for mod_path in self.graph.modules() {
+            let Some(source_path) = self.graph.source_path(mod_path) else { continue };
+            let relative = source_path.strip_prefix(shader_root_path)?;
+
+            artifacts::write(output_dir.join(PACKAGE_SOURCE_DIR).join(relative), std::fs::read(source_path)?)?;
+            modules.push(mod_path.components.join("::"));
+        }
+        modules.sort();
+
+        artifacts::write(output_dir.join(PACKAGE_MANIFEST_NAME), self.manifest(&modules))?;
+
+        #[cfg(feature = "logging")]
+        log::info!("shader package `{}` written to: {}", self.name, output_dir.display());
+
+        Ok(())
+    }
+
+    fn enter_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+    fn exit_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+
+    fn post_build(
+        &mut self,
+        _mod_path: &ModulePath,
+        _wgsl_built_path: &str,
+        _source_map: &Option<BasicSourceMap>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+}
@@ -0,0 +1,113 @@
+#![cfg(feature = "shader_registry_ext")]
+
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use wesl::{BasicSourceMap, ModulePath};
+
+use crate::WeslBuildExtension;
+use crate::artifacts;
+use crate::extension::Visibility;
+
+/// Generate a rust file listing every built shader, so they can be iterated at runtime
+///
+/// The generated file contains a single static:
+/// ```ignore
+/// pub(crate) static SHADERS: &[(&str, &str)] = &[
+///     ("test_mod::test_mod_file", include_str!("/path/to/OUT_DIR/wesl/test_mod/test_mod_file.wgsl")),
+/// ];
+/// ```
+/// which is useful for pipeline warmup or debugging UIs
+pub struct ShaderRegistryExtension {
+    /// The path of the generated rust file
+    output_path: PathBuf,
+    /// The name of the generated static
+    static_name: String,
+    /// The visibility of the generated static
+    visibility: Visibility,
+    /// `(module path, built wgsl path)` for each built shader
+    shaders: Vec<(String, String)>,
+}
+
+impl ShaderRegistryExtension {
+    /// Create a registry that will be written to `output_path`, e.g. `src/shaders.rs`
+    pub fn new(output_path: impl AsRef<Path>) -> Self {
+        Self {
+            output_path: output_path.as_ref().to_owned(),
+            static_name: "SHADERS".to_owned(),
+            visibility: Visibility::default(),
+            shaders: Vec::new(),
+        }
+    }
+
+    /// Set the name of the generated static, defaults to `SHADERS`
+    pub fn with_static_name(mut self, static_name: impl Into<String>) -> Self {
+        self.static_name = static_name.into();
+        self
+    }
+
+    /// Set the visibility of the generated static, defaults to `pub(crate)`
+    pub fn with_visibility(mut self, visibility: Visibility) -> Self {
+        self.visibility = visibility;
+        self
+    }
+
+    /// The rust source of the registry for the shaders built so far
+    fn registry_source(&self) -> String {
+        let mut source = String::from("// @generated by wesl_build, do not edit\n\n");
+        writeln!(source, "{}static {}: &[(&str, &str)] = &[", self.visibility, self.static_name)
+            .expect("writing to a String can't fail");
+        for (mod_path, wgsl_built_path) in &self.shaders {
+            // `Debug` of a str is a valid rust string literal
+            writeln!(source, "    ({mod_path:?}, include_str!({wgsl_built_path:?})),")
+                .expect("writing to a String can't fail");
+        }
+        source.push_str("];\n");
+
+        source
+    }
+}
+
+impl<WeslResolver: wesl::Resolver> WeslBuildExtension<WeslResolver> for ShaderRegistryExtension {
+    fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+        "ShaderRegistryExtension".into()
+    }
+
+    fn init_root(
+        &mut self,
+        _shader_path: &str,
+        _res: &mut wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.shaders.clear();
+        Ok(())
+    }
+
+    fn exit_root(
+        &mut self,
+        _shader_root_path: &str,
+        _res: &wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if crate::is_check() {
+            return Ok(());
+        }
+        artifacts::write(&self.output_path, self.registry_source())?;
+
+        #[cfg(feature = "logging")]
+        log::debug!("wrote shader registry with {} shaders to: {}", self.shaders.len(), self.output_path.display());
+
+        Ok(())
+    }
+
+    fn enter_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+    fn exit_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+
+    fn post_build(
+        &mut self,
+        mod_path: &ModulePath,
+        wgsl_built_path: &str,
+        _source_map: &Option<BasicSourceMap>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.shaders.push((mod_path.components.join("::"), wgsl_built_path.to_owned()));
+        Ok(())
+    }
+}
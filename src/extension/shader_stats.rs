@@ -0,0 +1,281 @@
+#![cfg(feature = "shader_stats_ext")]
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::Path;
+
+use wesl::{BasicSourceMap, ModulePath};
+
+use crate::WeslBuildExtension;
+use crate::artifacts;
+use crate::build_out_dir;
+
+/// Statistics of a built shader
+#[derive(Debug, Clone)]
+pub struct ShaderStats {
+    pub mod_path: ModulePath,
+    /// The size of the built WGSL
+    pub built_bytes: usize,
+    pub entry_points: Vec<(String, naga::ShaderStage)>,
+    /// The number of bindings in each bind group
+    pub bindings_per_group: BTreeMap<u32, usize>,
+    /// The most vertex attributes (`@location` inputs) of any vertex entry point
+    pub vertex_attributes: usize,
+    /// The most user defined inter-stage variables (`@location` outputs) of any vertex entry point
+    pub varyings: usize,
+    /// The number of naga expressions in all functions, a rough estimate of the instruction count
+    pub estimated_instructions: usize,
+    pub textures: usize,
+    pub samplers: usize,
+    /// The total size of the `var<workgroup>`s
+    pub workgroup_storage_bytes: u32,
+}
+
+impl ShaderStats {
+    /// Collect the statistics of a built shader from its naga module
+    pub fn from_module(
+        mod_path: &ModulePath,
+        built_bytes: usize,
+        module: &naga::Module,
+    ) -> Result<Self, naga::proc::LayoutError> {
+        use naga::{AddressSpace, ShaderStage, TypeInner};
+
+        let mut layouter = naga::proc::Layouter::default();
+        layouter.update(module.to_ctx())?;
+
+        let mut bindings_per_group = BTreeMap::new();
+        let mut textures = 0;
+        let mut samplers = 0;
+        let mut workgroup_storage_bytes = 0;
+        for (_, global) in module.global_variables.iter() {
+            if let Some(binding) = &global.binding {
+                *bindings_per_group.entry(binding.group).or_default() += 1;
+            }
+            match module.types[global.ty].inner {
+                TypeInner::Image { .. } => textures += 1,
+                TypeInner::Sampler { .. } => samplers += 1,
+                _ => (),
+            }
+            if global.space == AddressSpace::WorkGroup {
+                workgroup_storage_bytes += layouter[global.ty].size;
+            }
+        }
+
+        let vertex_entry_points = module.entry_points.iter()
+            .filter(|entry_point| entry_point.stage == ShaderStage::Vertex);
+        let vertex_attributes = vertex_entry_points.clone()
+            .map(|entry_point| entry_point.function.arguments.iter()
+                .map(|arg| location_count(module, arg.ty, &arg.binding))
+                .sum::<usize>())
+            .max()
+            .unwrap_or(0);
+        let varyings = vertex_entry_points
+            .filter_map(|entry_point| entry_point.function.result.as_ref())
+            .map(|result| location_count(module, result.ty, &result.binding))
+            .max()
+            .unwrap_or(0);
+
+        let estimated_instructions = module.functions.iter().map(|(_, function)| function)
+            .chain(module.entry_points.iter().map(|entry_point| &entry_point.function))
+            .map(|function| function.expressions.len())
+            .sum();
+
+        Ok(Self {
+            mod_path: mod_path.clone(),
+            built_bytes,
+            entry_points: module.entry_points.iter()
+                .map(|entry_point| (entry_point.name.clone(), entry_point.stage))
+                .collect(),
+            bindings_per_group,
+            vertex_attributes,
+            varyings,
+            estimated_instructions,
+            textures,
+            samplers,
+            workgroup_storage_bytes,
+        })
+    }
+
+    /// Parse the built shader at `wgsl_built_path` and collect its statistics
+    pub fn from_built_shader(
+        mod_path: &ModulePath,
+        wgsl_built_path: &str,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let wgsl_source = artifacts::read_to_string(wgsl_built_path)?;
+        let module = naga::front::wgsl::parse_str(&wgsl_source)?;
+
+        Ok(Self::from_module(mod_path, wgsl_source.len(), &module)?)
+    }
+
+    /// The total number of bindings in all groups
+    pub fn bindings(&self) -> usize {
+        self.bindings_per_group.values().sum()
+    }
+
+    /// The number of bind groups in the pipeline layout, i.e. the highest group index + 1
+    pub fn bind_groups(&self) -> u32 {
+        self.bindings_per_group.keys().next_back().map_or(0, |group| group + 1)
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "module": self.mod_path.to_string(),
+            "built_bytes": self.built_bytes,
+            "entry_points": self.entry_points.iter()
+                .map(|(name, stage)| serde_json::json!({ "name": name, "stage": format!("{stage:?}") }))
+                .collect::<Vec<_>>(),
+            "bindings_per_group": self.bindings_per_group,
+            "vertex_attributes": self.vertex_attributes,
+            "varyings": self.varyings,
+            "estimated_instructions": self.estimated_instructions,
+            "textures": self.textures,
+            "samplers": self.samplers,
+            "workgroup_storage_bytes": self.workgroup_storage_bytes,
+        })
+    }
+}
+
+/// The number of `@location`s in a binding, or the members of its struct if it has none
+fn location_count(module: &naga::Module, ty: naga::Handle<naga::Type>, binding: &Option<naga::Binding>) -> usize {
+    match (binding, &module.types[ty].inner) {
+        (Some(naga::Binding::Location { .. }), _) => 1,
+        (None, naga::TypeInner::Struct { members, .. }) => members.iter()
+            .filter(|member| matches!(member.binding, Some(naga::Binding::Location { .. })))
+            .count(),
+        _ => 0,
+    }
+}
+
+/// Report statistics for each built shader: entry points, bindings per group, vertex attributes,
+/// an estimated instruction count and texture/sampler usage
+///
+/// Once all shaders are built the statistics are written to the output dir as a table in `shader_stats.txt`
+/// and/or as JSON in `shader_stats.json`
+pub struct ShaderStatsExtension {
+    table: bool,
+    json: bool,
+    stats: Vec<ShaderStats>,
+}
+
+impl Default for ShaderStatsExtension {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShaderStatsExtension {
+    /// Output both the table and JSON
+    pub fn new() -> Self {
+        Self {
+            table: true,
+            json: true,
+            stats: Vec::new(),
+        }
+    }
+
+    /// Whether to write `shader_stats.txt`
+    pub fn with_table(mut self, table: bool) -> Self {
+        self.table = table;
+        self
+    }
+
+    /// Whether to write `shader_stats.json`
+    pub fn with_json(mut self, json: bool) -> Self {
+        self.json = json;
+        self
+    }
+
+    /// The statistics of every shader built so far
+    pub fn stats(&self) -> &[ShaderStats] {
+        &self.stats
+    }
+
+    fn table(&self) -> String {
+        let header = ["module", "bytes", "entry points", "bindings", "vertex attributes", "varyings", "est. instructions", "textures", "samplers"]
+            .map(str::to_owned);
+        let rows = self.stats.iter().map(|stats| [
+            stats.mod_path.to_string(),
+            stats.built_bytes.to_string(),
+            stats.entry_points.iter().map(|(name, stage)| format!("{name}({stage:?})")).collect::<Vec<_>>().join(" "),
+            stats.bindings_per_group.iter().map(|(group, count)| format!("{group}:{count}")).collect::<Vec<_>>().join(" "),
+            stats.vertex_attributes.to_string(),
+            stats.varyings.to_string(),
+            stats.estimated_instructions.to_string(),
+            stats.textures.to_string(),
+            stats.samplers.to_string(),
+        ]).collect::<Vec<_>>();
+
+        let mut widths = header.clone().map(|cell| cell.len());
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.len());
+            }
+        }
+        let separator = widths.map(|width| "-".repeat(width));
+
+        let mut table = String::new();
+        for row in [&header, &separator].into_iter().chain(&rows) {
+            let line = row.iter().zip(widths)
+                .map(|(cell, width)| format!("{cell:<width$}"))
+                .collect::<Vec<_>>()
+                .join(" | ");
+            writeln!(table, "{}", line.trim_end()).expect("writing to a String can't fail");
+        }
+
+        table
+    }
+}
+
+impl<WeslResolver: wesl::Resolver> WeslBuildExtension<WeslResolver> for ShaderStatsExtension {
+    fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+        "ShaderStatsExtension".into()
+    }
+
+    fn reads_built_wgsl(&self) -> bool {
+        true
+    }
+
+    fn init_root(
+        &mut self,
+        _shader_path: &str,
+        _res: &mut wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.stats.clear();
+        Ok(())
+    }
+
+    fn exit_root(
+        &mut self,
+        _shader_root_path: &str,
+        _res: &wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if self.table {
+            let table = self.table();
+            #[cfg(feature = "logging")]
+            log::info!("shader stats:\n{table}");
+            if !crate::is_check() {
+                artifacts::write(build_out_dir()?.join("shader_stats.txt"), table)?;
+            }
+        }
+        if self.json && !crate::is_check() {
+            let out_dir = build_out_dir()?;
+            let json = serde_json::Value::Array(self.stats.iter().map(ShaderStats::to_json).collect());
+            artifacts::write(out_dir.join("shader_stats.json"), serde_json::to_string_pretty(&json)?)?;
+        }
+
+        Ok(())
+    }
+
+    fn enter_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+    fn exit_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+
+    fn post_build(
+        &mut self,
+        mod_path: &ModulePath,
+        wgsl_built_path: &str,
+        _source_map: &Option<BasicSourceMap>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.stats.push(ShaderStats::from_built_shader(mod_path, wgsl_built_path)?);
+        Ok(())
+    }
+}
@@ -0,0 +1,388 @@
+#![cfg(feature = "size_history_ext")]
+
+//! A history of the sizes and compile times of the built shaders, to track shader bloat across commits
+
+use std::borrow::Cow;
+use std::fmt::{self, Write as _};
+use std::fs;
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use wesl::{BasicSourceMap, CompileOptions, ModulePath};
+
+use crate::messages::{self, BuildMessage, json_string};
+use crate::{WeslBuildExtension, artifacts};
+
+/// The format of a history file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HistoryFormat {
+    /// `build_ms,commit,module,bytes,compile_us` rows under a header
+    #[default]
+    Csv,
+    /// A JSON object per line with the same keys
+    JsonLines,
+}
+
+impl HistoryFormat {
+    /// JSON lines for `.json` and `.jsonl` files, CSV otherwise
+    pub fn of_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json" | "jsonl") => HistoryFormat::JsonLines,
+            _ => HistoryFormat::Csv,
+        }
+    }
+}
+
+/// The size and compile time of a shader in one build
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEntry {
+    /// When the build ran, in milliseconds since the unix epoch, shared by the entries of a build
+    pub build_ms: u64,
+    /// The short hash of the git commit built, empty outside of a git repo
+    pub commit: String,
+    /// `::` separated, e.g. `passes::blur`
+    pub module: String,
+    /// The size of the built shader after every extension that rewrites it
+    pub bytes: u64,
+    /// The time from compiling the shader until the history recorded it
+    pub compile_time: Duration,
+}
+
+impl HistoryEntry {
+    fn to_line(&self, format: HistoryFormat) -> String {
+        match format {
+            HistoryFormat::Csv => format!(
+                "{},{},{},{},{}",
+                self.build_ms, csv_field(&self.commit), csv_field(&self.module), self.bytes, self.compile_time.as_micros(),
+            ),
+            HistoryFormat::JsonLines => format!(
+                "{{\"build_ms\":{},\"commit\":{},\"module\":{},\"bytes\":{},\"compile_us\":{}}}",
+                self.build_ms, json_string(&self.commit), json_string(&self.module), self.bytes, self.compile_time.as_micros(),
+            ),
+        }
+    }
+
+    fn parse(line: &str, format: HistoryFormat) -> Option<Self> {
+        let fields = match format {
+            HistoryFormat::Csv => csv_fields(line),
+            HistoryFormat::JsonLines => ["build_ms", "commit", "module", "bytes", "compile_us"].iter()
+                .map(|key| json_field(line, key))
+                .collect::<Option<Vec<_>>>()?,
+        };
+        let [build_ms, commit, module, bytes, compile_us] = <[String; 5]>::try_from(fields).ok()?;
+        Some(Self {
+            build_ms: build_ms.parse().ok()?,
+            commit,
+            module,
+            bytes: bytes.parse().ok()?,
+            compile_time: Duration::from_micros(compile_us.parse().ok()?),
+        })
+    }
+}
+
+/// `field` quoted if it has a `,` or `"`
+fn csv_field(field: &str) -> Cow<'_, str> {
+    match field.contains([',', '"']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")).into(),
+        false => field.into(),
+    }
+}
+
+/// The fields of a CSV row, unquoted
+fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        let field = fields.last_mut().expect("there is a field");
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            c => field.push(c),
+        }
+    }
+    fields
+}
+
+/// The value of `key` in a JSON object on one line as written by [`HistoryEntry::to_line`], strings unescaped
+fn json_field(line: &str, key: &str) -> Option<String> {
+    let (_, rest) = line.split_once(&format!("\"{key}\":"))?;
+    let Some(rest) = rest.strip_prefix('"') else {
+        return Some(rest.split([',', '}']).next()?.trim().to_owned());
+    };
+
+    let mut value = String::new();
+    let mut chars = rest.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return Some(value),
+            '\\' => match chars.next()? {
+                'n' => value.push('\n'),
+                'r' => value.push('\r'),
+                't' => value.push('\t'),
+                'u' => value.push(char::from_u32(u32::from_str_radix(&chars.by_ref().take(4).collect::<String>(), 16).ok()?)?),
+                c => value.push(c),
+            },
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+/// How much the size of a shader changed between its last two builds in a [`SizeHistory`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SizeDelta {
+    pub module: String,
+    pub previous: u64,
+    pub current: u64,
+}
+
+impl SizeDelta {
+    /// The change in bytes, negative if the shader shrank
+    pub fn bytes(&self) -> i64 {
+        self.current as i64 - self.previous as i64
+    }
+
+    /// The change in percent of the previous size, `0.0` if both are empty
+    pub fn percent(&self) -> f64 {
+        match self.previous {
+            0 if self.current == 0 => 0.0,
+            0 => f64::INFINITY,
+            previous => self.bytes() as f64 / previous as f64 * 100.0,
+        }
+    }
+}
+
+impl fmt::Display for SizeDelta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the size of `{}` changed by {:+.0}%, {} to {} bytes", self.module, self.percent(), self.previous, self.current)
+    }
+}
+
+/// The entries of a history file, oldest first
+///
+/// ## Example
+/// ```no_run
+/// use wesl_build::extension::size_history::SizeHistory;
+///
+/// let history = SizeHistory::read("shader_sizes.csv").unwrap();
+/// for entry in history.last(10) {
+///     println!("{} {}: {} bytes", entry.commit, entry.module, entry.bytes);
+/// }
+/// if let Some(delta) = history.delta("passes::blur") {
+///     println!("{delta}");
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SizeHistory {
+    entries: Vec<HistoryEntry>,
+}
+
+impl SizeHistory {
+    /// Read the history file at `path` in the [format of its extension](HistoryFormat::of_path), empty if it doesn't exist
+    pub fn read(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let format = HistoryFormat::of_path(path);
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(error) => return Err(error),
+        };
+
+        // the CSV header and blank lines are skipped
+        let entries = text.lines()
+            .filter(|line| !line.trim().is_empty() && !line.starts_with("build_ms"))
+            .map(|line| HistoryEntry::parse(line, format).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("invalid entry in `{}`: {line}", path.display()))
+            }))
+            .collect::<io::Result<_>>()?;
+        Ok(Self { entries })
+    }
+
+    pub fn entries(&self) -> &[HistoryEntry] {
+        &self.entries
+    }
+
+    /// The last `n` entries, oldest first
+    pub fn last(&self, n: usize) -> &[HistoryEntry] {
+        &self.entries[self.entries.len().saturating_sub(n)..]
+    }
+
+    /// The entries of `module`, oldest first
+    pub fn of_module<'h>(&'h self, module: &'h str) -> impl Iterator<Item = &'h HistoryEntry> {
+        self.entries.iter().filter(move |entry| entry.module == module)
+    }
+
+    /// The change of the size of `module` between its last two entries, `None` if it has fewer
+    pub fn delta(&self, module: &str) -> Option<SizeDelta> {
+        let mut entries = self.of_module(module).rev();
+        let (current, previous) = (entries.next()?, entries.next()?);
+        Some(SizeDelta { module: module.to_owned(), previous: previous.bytes, current: current.bytes })
+    }
+}
+
+/// Append the size and compile time of every built shader to a history file, a CSV or JSON lines file
+/// in the repo rather than `OUT_DIR`, so teams can track shader bloat across commits
+///
+/// Each build adds an entry per shader with the time of the build and the short hash of the git commit.
+/// The sizes are of the final shaders, the extension runs after the ones that rewrite them, e.g. the minifier.
+/// With [`with_warn_threshold`](Self::with_warn_threshold) shaders whose size changed by more than a percentage since
+/// their last entry get a warning, e.g. "the size of `passes::blur` changed by +12%". Read the history with [`SizeHistory`].
+/// Checks record nothing.
+///
+/// ## Example
+/// ```
+/// use wesl_build::extension::size_history::ShaderSizeHistoryExtension;
+///
+/// let history = ShaderSizeHistoryExtension::new("shader_sizes.csv").with_warn_threshold(10.0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ShaderSizeHistoryExtension {
+    history_path: PathBuf,
+    format: HistoryFormat,
+    warn_threshold: Option<f64>,
+    /// When the shader being built started compiling
+    compile_start: Option<Instant>,
+    /// The entries of the build and the shaders they are of
+    entries: Vec<HistoryEntry>,
+    mod_paths: Vec<ModulePath>,
+}
+
+impl ShaderSizeHistoryExtension {
+    /// Append to the history file at `history_path`, in the [format of its extension](HistoryFormat::of_path)
+    pub fn new(history_path: impl AsRef<Path>) -> Self {
+        let history_path = history_path.as_ref().to_owned();
+        Self {
+            format: HistoryFormat::of_path(&history_path),
+            history_path,
+            warn_threshold: None,
+            compile_start: None,
+            entries: Vec::new(),
+            mod_paths: Vec::new(),
+        }
+    }
+
+    /// Write the history in `format` whatever the extension of its file
+    pub fn with_format(mut self, format: HistoryFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Warn about shaders whose size changed by more than `percent` since their last entry
+    pub fn with_warn_threshold(mut self, percent: f64) -> Self {
+        self.warn_threshold = Some(percent);
+        self
+    }
+
+    /// The entries of the last build
+    pub fn entries(&self) -> &[HistoryEntry] {
+        &self.entries
+    }
+}
+
+/// The short hash of the git commit of the crate being built, empty outside of a git repo
+fn git_commit() -> String {
+    std::process::Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_owned())
+        .unwrap_or_default()
+}
+
+impl<WeslResolver: wesl::Resolver> WeslBuildExtension<WeslResolver> for ShaderSizeHistoryExtension {
+    fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+        "ShaderSizeHistoryExtension".into()
+    }
+
+    fn reads_built_wgsl(&self) -> bool {
+        true
+    }
+
+    /// The sizes are of the final shaders
+    fn must_run_after(&self) -> &[&str] {
+        &["WgslMinifierExtension", "WgslObfuscatorExtension", "WgslFormatterExtension", "ShaderLicenseExtension"]
+    }
+
+    fn init_root(
+        &mut self,
+        _shader_root_path: &str,
+        _res: &mut wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.entries.clear();
+        self.mod_paths.clear();
+        Ok(())
+    }
+
+    /// Run right before the shader is compiled
+    fn configure(&mut self, _wesl_path: &ModulePath, _options: &mut CompileOptions) -> Result<(), Box<dyn std::error::Error>> {
+        self.compile_start = Some(Instant::now());
+        Ok(())
+    }
+
+    fn exit_root(
+        &mut self,
+        _shader_root_path: &str,
+        _res: &wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if crate::is_check() || self.entries.is_empty() {
+            return Ok(());
+        }
+
+        let build_ms = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64;
+        let commit = git_commit();
+        for entry in &mut self.entries {
+            entry.build_ms = build_ms;
+            entry.commit.clone_from(&commit);
+        }
+
+        if let Some(warn_threshold) = self.warn_threshold {
+            let mut history = SizeHistory::read(&self.history_path)?;
+            history.entries.extend(self.entries.iter().cloned());
+            for (entry, mod_path) in self.entries.iter().zip(&self.mod_paths) {
+                if let Some(delta) = history.delta(&entry.module).filter(|delta| delta.percent().abs() > warn_threshold) {
+                    messages::report(&BuildMessage::warning(delta.to_string()).with_module(mod_path))?;
+                }
+            }
+        }
+
+        // the file is in the repo, not an artifact of the build
+        let is_new = !self.history_path.exists();
+        if let Some(parent) = self.history_path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let mut lines = String::new();
+        if is_new && self.format == HistoryFormat::Csv {
+            lines.push_str("build_ms,commit,module,bytes,compile_us\n");
+        }
+        for entry in &self.entries {
+            writeln!(lines, "{}", entry.to_line(self.format))?;
+        }
+        fs::OpenOptions::new().create(true).append(true).open(&self.history_path)?.write_all(lines.as_bytes())?;
+
+        Ok(())
+    }
+
+    fn post_build(
+        &mut self,
+        mod_path: &ModulePath,
+        wgsl_built_path: &str,
+        _source_map: &Option<BasicSourceMap>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.entries.push(HistoryEntry {
+            build_ms: 0,
+            commit: String::new(),
+            module: mod_path.components.join("::"),
+            bytes: artifacts::read(wgsl_built_path)?.len() as u64,
+            compile_time: self.compile_start.take().map(|start| start.elapsed()).unwrap_or_default(),
+        });
+        self.mod_paths.push(mod_path.clone());
+        Ok(())
+    }
+}
@@ -0,0 +1,92 @@
+#![cfg(feature = "spirv_ingest_ext")]
+
+//! Build SPIR-V shaders with the WESL shaders, converted to WGSL by naga's SPIR-V frontend
+
+use std::path::{Path, PathBuf};
+
+use wesl::{BasicSourceMap, ModulePath};
+
+use super::ingest::find_shaders;
+use crate::{BuildResolver, WeslBuildExtension};
+
+#[derive(Debug, thiserror::Error)]
+pub enum SpirvIngestError {
+    #[error("failed to parse the SPIR-V shader `{}`: {error}", .path.display())]
+    Parse { path: PathBuf, error: String },
+    #[error("the SPIR-V shader `{}` is invalid: {error}", .path.display())]
+    Invalid { path: PathBuf, error: String },
+    #[error("failed to convert the SPIR-V shader `{}` to WGSL: {error}", .path.display())]
+    Wgsl { path: PathBuf, error: String },
+    #[error("the SPIR-V shader `{}` has the module path `{mod_path}` of a shader file, rename one of them", .path.display())]
+    ModuleClash { path: PathBuf, mod_path: ModulePath },
+}
+
+/// The WGSL of the SPIR-V binary `spirv`, from the file at `path`
+pub(crate) fn spirv_to_wgsl(path: &Path, spirv: &[u8]) -> Result<String, SpirvIngestError> {
+    let module = naga::front::spv::parse_u8_slice(spirv, &naga::front::spv::Options::default())
+        .map_err(|error| SpirvIngestError::Parse { path: path.to_owned(), error: error.to_string() })?;
+    let info = naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::all())
+        .validate(&module)
+        .map_err(|error| SpirvIngestError::Invalid { path: path.to_owned(), error: error.into_inner().to_string() })?;
+
+    naga::back::wgsl::write_string(&module, &info, naga::back::wgsl::WriterFlags::empty())
+        .map_err(|error| SpirvIngestError::Wgsl { path: path.to_owned(), error: error.to_string() })
+}
+
+/// Build the `.spv` SPIR-V shaders in the shader dir as modules, to consolidate precompiled shaders on one build
+///
+/// In `init_root` every SPIR-V shader the build [includes](`crate::WeslBuildConfig::include`) is converted to WGSL
+/// and added as a [virtual module](BuildResolver::add_virtual_module), built and used like the
+/// [GLSL shaders](super::glsl_ingest) are. Its module is its path with `_spv` as a suffix,
+/// e.g. `post/blur.spv` is `post::blur_spv`, and it keeps the entry points of the binary.
+///
+/// ## Example
+/// ```
+/// use wesl_build::extension::spirv_ingest::SpirvIngestExtension;
+///
+/// let spirv = SpirvIngestExtension::new();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SpirvIngestExtension;
+
+impl SpirvIngestExtension {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl WeslBuildExtension<BuildResolver> for SpirvIngestExtension {
+    fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+        "SpirvIngestExtension".into()
+    }
+
+    fn init_root(
+        &mut self,
+        shader_path: &str,
+        res: &mut wesl::Wesl<BuildResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        for file in find_shaders(Path::new(shader_path), &["spv"])? {
+            if file.clashes_with_shader() {
+                return Err(Box::new(SpirvIngestError::ModuleClash { path: file.path, mod_path: file.mod_path }));
+            }
+
+            #[cfg(feature = "logging")]
+            log::debug!("converting SPIR-V shader `{}` to `{}`", file.path.display(), file.mod_path);
+            let wgsl = spirv_to_wgsl(&file.path, &std::fs::read(&file.path)?)?;
+            res.resolver().add_virtual_module(file.mod_path, wgsl);
+        }
+        Ok(())
+    }
+
+    fn enter_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+    fn exit_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+
+    fn post_build(
+        &mut self,
+        _mod_path: &ModulePath,
+        _wgsl_built_path: &str,
+        _source_map: &Option<BasicSourceMap>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+}
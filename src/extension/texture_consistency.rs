@@ -0,0 +1,196 @@
+#![cfg(feature = "texture_consistency_ext")]
+
+//! Check that the textures bound to the same slot by different shaders agree on their type
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::path::Path;
+
+use wesl::{BasicSourceMap, ModulePath};
+
+use crate::{NagaModule, WeslBuildExtension};
+
+/// A texture binding of a built shader
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextureBinding {
+    pub group: u32,
+    pub binding: u32,
+    /// The name of the global variable
+    pub name: String,
+    /// The WGSL type, e.g. `texture_2d<f32>` or `texture_storage_2d<rgba8unorm, write>`,
+    /// its dimension, sample type and format
+    pub texture_type: String,
+}
+
+impl TextureBinding {
+    /// The texture bindings of `module`, sorted by group and binding
+    pub fn of_module(module: &naga::Module) -> Vec<Self> {
+        let mut textures = module.global_variables.iter()
+            .filter_map(|(_, global)| {
+                let binding = global.binding.as_ref()?;
+                let naga::TypeInner::Image { dim, arrayed, class } = module.types[global.ty].inner else {
+                    return None;
+                };
+                Some(Self {
+                    group: binding.group,
+                    binding: binding.binding,
+                    name: global.name.clone().unwrap_or_default(),
+                    texture_type: texture_type(dim, arrayed, class),
+                })
+            })
+            .collect::<Vec<_>>();
+        textures.sort_by_key(|texture| (texture.group, texture.binding));
+        textures
+    }
+}
+
+/// The WGSL name of a texture type
+fn texture_type(dim: naga::ImageDimension, arrayed: bool, class: naga::ImageClass) -> String {
+    use naga::{ImageClass, ImageDimension, ScalarKind};
+
+    let dim = match (dim, arrayed) {
+        (ImageDimension::D1, _) => "1d",
+        (ImageDimension::D2, false) => "2d",
+        (ImageDimension::D2, true) => "2d_array",
+        (ImageDimension::D3, _) => "3d",
+        (ImageDimension::Cube, false) => "cube",
+        (ImageDimension::Cube, true) => "cube_array",
+    };
+    match class {
+        ImageClass::Sampled { kind, multi } => {
+            let sample_type = match kind {
+                ScalarKind::Sint => "i32",
+                ScalarKind::Uint => "u32",
+                _ => "f32",
+            };
+            let multisampled = if multi { "multisampled_" } else { "" };
+            format!("texture_{multisampled}{dim}<{sample_type}>")
+        }
+        ImageClass::Depth { multi: true } => format!("texture_depth_multisampled_{dim}"),
+        ImageClass::Depth { multi: false } => format!("texture_depth_{dim}"),
+        ImageClass::Storage { format, access } => {
+            let access = match (access.contains(naga::StorageAccess::LOAD), access.contains(naga::StorageAccess::STORE)) {
+                (true, true) => "read_write",
+                (true, false) => "read",
+                _ => "write",
+            };
+            format!("texture_storage_{dim}<{}, {access}>", format!("{format:?}").to_lowercase())
+        }
+        ImageClass::External => "texture_external".to_owned(),
+    }
+}
+
+/// Shaders bind textures of different types to the same slot, with the same name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextureMismatch {
+    pub group: u32,
+    pub binding: u32,
+    pub name: String,
+    /// The shaders of each type
+    pub types: BTreeMap<String, Vec<ModulePath>>,
+}
+
+impl Display for TextureMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "@group({}) @binding({}) `{}` is", self.group, self.binding, self.name)?;
+        for (index, (texture_type, mod_paths)) in self.types.iter().enumerate() {
+            let separator = if index == 0 { "" } else { "," };
+            let mod_paths = mod_paths.iter().map(|mod_path| format!("`{}`", mod_path.components.join("::"))).collect::<Vec<_>>();
+            write!(f, "{separator} `{texture_type}` in {}", mod_paths.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("textures bound to the same slot have different types:\n{}", .mismatches.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n"))]
+pub struct TextureConsistencyError {
+    pub mismatches: Vec<TextureMismatch>,
+}
+
+/// Fail the build when shaders bind textures of different types, dimension, sample type or storage format,
+/// to the same `@group`/`@binding` with the same name
+///
+/// Bindings shared by shaders usually come from one bind group, so a shader declaring `texture_2d<f32>` where
+/// another declares `texture_2d<u32>` or `texture_depth_2d` only fails when wgpu validates the bind group
+/// against the pipeline. Once all shaders are built, every mismatch is reported:
+/// ```text
+/// @group(1) @binding(0) `albedo` is `texture_2d<f32>` in `pbr`, `texture_2d<u32>` in `gbuffer::resolve`
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TextureConsistencyExtension {
+    /// The shaders and type of each texture by its slot and name
+    textures: BTreeMap<(u32, u32, String), BTreeMap<String, Vec<ModulePath>>>,
+}
+
+impl TextureConsistencyExtension {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The textures bound to the same slot with different types, by the shaders built so far
+    pub fn mismatches(&self) -> Vec<TextureMismatch> {
+        self.textures.iter()
+            .filter(|(_, types)| types.len() > 1)
+            .map(|((group, binding, name), types)| TextureMismatch {
+                group: *group,
+                binding: *binding,
+                name: name.clone(),
+                types: types.clone(),
+            })
+            .collect()
+    }
+}
+
+impl<WeslResolver: wesl::Resolver> WeslBuildExtension<WeslResolver> for TextureConsistencyExtension {
+    fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+        "TextureConsistencyExtension".into()
+    }
+
+    fn init_root(
+        &mut self,
+        _shader_path: &str,
+        _res: &mut wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.textures.clear();
+        Ok(())
+    }
+
+    fn exit_root(
+        &mut self,
+        _shader_root_path: &str,
+        _res: &wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mismatches = self.mismatches();
+        if mismatches.is_empty() {
+            #[cfg(feature = "logging")]
+            log::debug!("the {} texture bindings of the shaders are consistent", self.textures.len());
+            Ok(())
+        } else {
+            Err(Box::new(TextureConsistencyError { mismatches }))
+        }
+    }
+
+    fn enter_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+    fn exit_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+
+    fn post_parse(&mut self, wesl_path: &ModulePath, module: &mut NagaModule) -> Result<(), Box<dyn std::error::Error>> {
+        for texture in TextureBinding::of_module(module) {
+            self.textures.entry((texture.group, texture.binding, texture.name))
+                .or_default()
+                .entry(texture.texture_type)
+                .or_default()
+                .push(wesl_path.clone());
+        }
+        Ok(())
+    }
+
+    fn post_build(
+        &mut self,
+        _mod_path: &ModulePath,
+        _wgsl_built_path: &str,
+        _source_map: &Option<BasicSourceMap>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+}
@@ -0,0 +1,323 @@
+#![cfg(feature = "toolchain_validation_ext")]
+
+//! Validate the built shaders with the compilers of other platforms, Tint (Dawn) or DXC, on top of naga
+
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use wesl::{BasicSourceMap, ModulePath};
+
+use crate::context::try_with_build_context;
+use crate::diagnostics::ShaderDiagnostic;
+use crate::messages::{self, BuildMessage};
+use crate::{WeslBuildExtension, artifacts};
+
+/// The compiler a [`ToolchainValidationExtension`] validates with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Toolchain {
+    /// Dawn's WGSL compiler, run on the built WGSL
+    Tint,
+    /// The DirectX shader compiler, run on every entry point of the HLSL naga generates from the built WGSL
+    Dxc,
+}
+
+impl Toolchain {
+    /// The binary run if none is set
+    fn default_binary(self) -> &'static str {
+        match self {
+            Toolchain::Tint => "tint",
+            Toolchain::Dxc => "dxc",
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ToolchainValidationError {
+    #[error("failed to run `{binary}`, install it or set its path with `with_binary`: {error}")]
+    Spawn { binary: String, error: std::io::Error },
+    #[error("failed to generate HLSL for DXC: {0}")]
+    Hlsl(String),
+    #[error("{tool:?} rejected `{mod_path}`:\n{}", .diagnostics.join("\n"))]
+    Rejected { tool: Toolchain, mod_path: ModulePath, diagnostics: Vec<String> },
+}
+
+/// A diagnostic of a compiler, `<file>:<line>:<column>: error: <message>` as both Tint and DXC write them
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ToolDiagnostic {
+    is_error: bool,
+    /// 1-based
+    line: usize,
+    /// 1-based
+    column: usize,
+    message: String,
+}
+
+impl ToolDiagnostic {
+    /// The diagnostics in the output of a compiler, `file` is the path it was given
+    fn parse_all(output: &str, file: &str) -> Vec<Self> {
+        output.lines().filter_map(|line| Self::parse(line.strip_prefix(file)?)).collect()
+    }
+
+    /// A diagnostic after the file name, `:3:5 error: ...` or `:3:5: error: ...`, notes are left out
+    fn parse(rest: &str) -> Option<Self> {
+        let (line, rest) = rest.strip_prefix(':')?.split_once(':')?;
+        let (column, rest) = rest.split_at(rest.find(|c: char| !c.is_ascii_digit())?);
+        let rest = rest.strip_prefix(':').unwrap_or(rest).trim_start();
+        let (severity, message) = rest.split_once(':')?;
+        let is_error = match severity.trim() {
+            "error" | "fatal error" => true,
+            "warning" => false,
+            _ => return None,
+        };
+
+        Some(Self {
+            is_error,
+            line: line.trim().parse().ok()?,
+            column: column.parse().ok()?,
+            message: message.trim().to_owned(),
+        })
+    }
+
+    /// The byte span of the diagnostic in `source`, to the end of the word it points to
+    fn span(&self, source: &str) -> std::ops::Range<usize> {
+        let line_start = source.split_inclusive('\n').take(self.line.saturating_sub(1)).map(str::len).sum::<usize>();
+        let line = source[line_start..].lines().next().unwrap_or_default();
+        let start = line_start + (self.column.saturating_sub(1)).min(line.len());
+        let len = source[start..].find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(0).max(1);
+        start..(start + len).min(source.len())
+    }
+}
+
+/// Validate every built shader with Tint or DXC, for teams shipping on platforms whose compilers are stricter than naga
+///
+/// The compiler must be installed, the binary is found on the `PATH` unless set with [`with_binary`](Self::with_binary).
+/// Errors fail the build, rendered like the errors of the build with the declaration in the WESL source they come from,
+/// warnings are reported as build warnings. Tint validates the built WGSL itself. For DXC naga translates the shader
+/// to HLSL, shader model 6.0, and every entry point is compiled with its stage's profile, e.g. `ps_6_0`,
+/// its diagnostics point into the HLSL.
+///
+/// ```no_run
+/// # use wesl_build::extension::toolchain_validation::{Toolchain, ToolchainValidationExtension};
+/// ToolchainValidationExtension::new(Toolchain::Tint).with_skip_if_missing(true);
+/// ToolchainValidationExtension::new(Toolchain::Dxc).with_binary("C:/dxc/bin/dxc.exe").with_args(["-Wno-ignored-attributes"]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ToolchainValidationExtension {
+    toolchain: Toolchain,
+    binary: Option<PathBuf>,
+    args: Vec<String>,
+    skip_if_missing: bool,
+    /// The root dir of the shaders, to point errors to the source files
+    shader_root_path: PathBuf,
+    /// Whether the missing binary was reported
+    reported_missing: bool,
+}
+
+impl ToolchainValidationExtension {
+    pub fn new(toolchain: Toolchain) -> Self {
+        Self {
+            toolchain,
+            binary: None,
+            args: Vec::new(),
+            skip_if_missing: false,
+            shader_root_path: PathBuf::new(),
+            reported_missing: false,
+        }
+    }
+
+    /// Run the compiler at `binary` instead of `tint`/`dxc` from the `PATH`
+    pub fn with_binary(mut self, binary: impl Into<PathBuf>) -> Self {
+        self.binary = Some(binary.into());
+        self
+    }
+
+    /// Pass `args` to the compiler before the file, e.g. to turn off a warning
+    pub fn with_args(mut self, args: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Warn once and skip the validation instead of failing when the compiler is not installed, e.g. for local builds
+    pub fn with_skip_if_missing(mut self, skip_if_missing: bool) -> Self {
+        self.skip_if_missing = skip_if_missing;
+        self
+    }
+
+    fn binary(&self) -> Cow<'_, Path> {
+        match &self.binary {
+            Some(binary) => Cow::Borrowed(binary),
+            None => Cow::Owned(PathBuf::from(self.toolchain.default_binary())),
+        }
+    }
+
+    /// Run the compiler with `args` and then `file`, whether it succeeded and its output, `None` if it's missing and skipped
+    fn run(&mut self, args: &[String], file: &Path) -> Result<Option<(bool, String)>, ToolchainValidationError> {
+        let binary = self.binary().into_owned();
+        let output = Command::new(&binary).args(&self.args).args(args).arg(file).output();
+        let output = match output {
+            Ok(output) => output,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound && self.skip_if_missing => {
+                if !std::mem::replace(&mut self.reported_missing, true) {
+                    let _ = messages::report(&BuildMessage::warning(format!(
+                        "`{}` is not installed, the shaders are not validated with {:?}", binary.display(), self.toolchain,
+                    )));
+                }
+                return Ok(None);
+            }
+            Err(error) => return Err(ToolchainValidationError::Spawn { binary: binary.display().to_string(), error }),
+        };
+
+        let text = format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+        Ok(Some((output.status.success(), text)))
+    }
+
+    /// Report the warnings in `output` and fail with its errors, rendered over `source`
+    fn check_output(
+        &self,
+        (succeeded, output): (bool, String),
+        file: &Path,
+        source: &str,
+        mod_path: &ModulePath,
+        source_map: Option<&BasicSourceMap>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut errors = Vec::new();
+        for diagnostic in ToolDiagnostic::parse_all(&output, &file.display().to_string()) {
+            if !diagnostic.is_error {
+                messages::report(&BuildMessage::warning(format!("{:?}: {}", self.toolchain, diagnostic.message))
+                    .with_module(mod_path))?;
+                continue;
+            }
+            errors.push(ShaderDiagnostic::new(&diagnostic.message, file, source)
+                .with_label(diagnostic.span(source), &diagnostic.message)
+                .with_source_map(source_map, mod_path, &self.shader_root_path)
+                .render());
+        }
+
+        // a failure without diagnostics in the expected format, e.g. a crash or a bad argument
+        if !succeeded && errors.is_empty() {
+            errors.push(output.trim_end().to_owned());
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Box::new(ToolchainValidationError::Rejected { tool: self.toolchain, mod_path: mod_path.clone(), diagnostics: errors }))
+        }
+    }
+
+    fn validate_tint(
+        &mut self,
+        mod_path: &ModulePath,
+        wgsl_path: &Path,
+        wgsl: &str,
+        source_map: Option<&BasicSourceMap>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(output) = self.run(&["--format".to_owned(), "wgsl".to_owned()], wgsl_path)? else {
+            return Ok(());
+        };
+        self.check_output(output, wgsl_path, wgsl, mod_path, source_map)
+    }
+
+    fn validate_dxc(
+        &mut self,
+        mod_path: &ModulePath,
+        wgsl: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let (hlsl, entry_points) = to_hlsl(wgsl)?;
+        let file_name = format!("{}.hlsl", mod_path.components.join("_"));
+        let hlsl_path = try_with_build_context(|ctx| -> std::io::Result<PathBuf> {
+            let hlsl_path = ctx.scratch_dir()?.join(file_name);
+            std::fs::write(&hlsl_path, &hlsl)?;
+            Ok(hlsl_path)
+        })??;
+
+        for (entry_point, profile) in entry_points {
+            let Some(output) = self.run(&["-T".to_owned(), profile, "-E".to_owned(), entry_point], &hlsl_path)? else {
+                return Ok(());
+            };
+            // HLSL is not in the source map
+            self.check_output(output, &hlsl_path, &hlsl, mod_path, None)?;
+        }
+        Ok(())
+    }
+}
+
+/// The HLSL of `wgsl`, with the name and DXC profile of every entry point
+fn to_hlsl(wgsl: &str) -> Result<(String, Vec<(String, String)>), ToolchainValidationError> {
+    use naga::back::hlsl;
+
+    let module = naga::front::wgsl::parse_str(wgsl).map_err(|error| ToolchainValidationError::Hlsl(error.to_string()))?;
+    let info = naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::all())
+        .validate(&module)
+        .map_err(|error| ToolchainValidationError::Hlsl(error.to_string()))?;
+
+    let options = hlsl::Options { shader_model: hlsl::ShaderModel::V6_0, ..Default::default() };
+    let mut hlsl = String::new();
+    let reflection = hlsl::Writer::new(&mut hlsl, &options, &hlsl::PipelineOptions::default())
+        .write(&module, &info, None)
+        .map_err(|error| ToolchainValidationError::Hlsl(error.to_string()))?;
+
+    let entry_points = module.entry_points.iter().zip(reflection.entry_point_names)
+        .map(|(entry_point, name)| {
+            let stage = match entry_point.stage {
+                naga::ShaderStage::Vertex => "vs",
+                naga::ShaderStage::Fragment => "ps",
+                _ => "cs",
+            };
+            let name = name.map_err(|error| ToolchainValidationError::Hlsl(error.to_string()))?;
+            Ok((name, format!("{stage}_6_0")))
+        })
+        .collect::<Result<_, ToolchainValidationError>>()?;
+
+    Ok((hlsl, entry_points))
+}
+
+impl<WeslResolver: wesl::Resolver> WeslBuildExtension<WeslResolver> for ToolchainValidationExtension {
+    fn name<'n>(&self) -> Cow<'n, str> {
+        "ToolchainValidationExtension".into()
+    }
+
+    fn reads_built_wgsl(&self) -> bool {
+        true
+    }
+
+    fn init_root(
+        &mut self,
+        shader_path: &str,
+        _res: &mut wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.shader_root_path = PathBuf::from(shader_path);
+        self.reported_missing = false;
+        Ok(())
+    }
+
+    fn enter_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+    fn exit_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+
+    fn post_build(
+        &mut self,
+        mod_path: &ModulePath,
+        wgsl_built_path: &str,
+        source_map: &Option<BasicSourceMap>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let wgsl = artifacts::read_to_string(wgsl_built_path)?;
+        match self.toolchain {
+            Toolchain::Tint => {
+                // the artifacts of a check are in memory, the compiler needs a file
+                let wgsl_path = if crate::is_check() {
+                    let file_name = Path::new(wgsl_built_path).file_name().unwrap_or_default().to_owned();
+                    try_with_build_context(|ctx| -> std::io::Result<PathBuf> {
+                        let wgsl_path = ctx.scratch_dir()?.join(file_name);
+                        std::fs::write(&wgsl_path, &wgsl)?;
+                        Ok(wgsl_path)
+                    })??
+                } else {
+                    PathBuf::from(wgsl_built_path)
+                };
+                self.validate_tint(mod_path, &wgsl_path, &wgsl, source_map.as_ref())
+            }
+            Toolchain::Dxc => self.validate_dxc(mod_path, &wgsl),
+        }
+    }
+}
@@ -0,0 +1,235 @@
+#![cfg(feature = "uber_shader_ext")]
+
+//! Combine several built shaders into one WGSL artifact with all of their entry points
+
+use wesl::{BasicSourceMap, ModulePath, SourceMap};
+
+use crate::build_out_dir;
+use crate::{WeslBuildExtension, artifacts};
+use super::comments::strip_comments;
+
+#[derive(Debug, thiserror::Error)]
+pub enum UberShaderError {
+    /// Two shaders declare different items of the same name, e.g. both have an entry point `main`
+    #[error(
+        "`{name}` of `{first}` and `{name}` of `{second}` are different declarations, \
+        rename one of them to combine the shaders into `{artifact}`"
+    )]
+    NameClash { artifact: String, name: String, first: ModulePath, second: ModulePath },
+    #[error("the shader `{module}` of `{artifact}` was not built")]
+    MissingModule { artifact: String, module: String },
+}
+
+/// A top level item of a built shader
+#[derive(Debug, Clone)]
+struct Item {
+    /// `None` for directives and `const_assert`s
+    name: Option<String>,
+    text: String,
+    /// The module it was declared in, from the source map
+    origin: ModulePath,
+}
+
+/// Combine a set of built shaders into one WGSL artifact with all of their entry points,
+/// for engines that create many pipelines from a single `ShaderModule`
+///
+/// Every item of the shaders is put in the combined shader once. Items the shaders import have the same mangled name
+/// and declaration in each of them, e.g. a shared `Light` struct, and are kept once, as are identical directives.
+/// Different items of the same name fail the build with [`UberShaderError::NameClash`], so the entry points and
+/// the items declared in the shaders themselves, which are not mangled, need unique names across the set.
+///
+/// The shaders are read in `post_build` before a minifier or obfuscator renames their items, and the combined
+/// shader is written in `exit_root` to `<name>.wgsl` in the output dir, e.g. for `include_str!(concat!(env!("OUT_DIR"), "/uber.wgsl"))`.
+/// The shaders are still built on their own.
+///
+/// ## Example
+/// ```
+/// use wesl_build::extension::uber_shader::UberShaderExtension;
+///
+/// let uber = UberShaderExtension::new("uber").with_module("passes::blur").with_module("passes::bloom");
+/// ```
+#[derive(Debug, Clone)]
+pub struct UberShaderExtension {
+    artifact: String,
+    /// `::` separated, in the order their items are combined
+    modules: Vec<String>,
+    /// The modules built so far and their items
+    built: Vec<(String, Vec<Item>)>,
+}
+
+impl UberShaderExtension {
+    /// Combine shaders into the artifact `<artifact>.wgsl`, add them with [`with_module`](Self::with_module)
+    pub fn new(artifact: impl Into<String>) -> Self {
+        Self { artifact: artifact.into(), modules: Vec::new(), built: Vec::new() }
+    }
+
+    /// Combine the shader `module`, `::` separated, e.g. `passes::blur`, after the ones added before
+    pub fn with_module(mut self, module: impl Into<String>) -> Self {
+        self.modules.push(module.into());
+        self
+    }
+
+    /// The WGSL of the combined shader, the directives first and then the items in the order of the modules
+    fn combined(&self) -> Result<String, UberShaderError> {
+        let mut directives = Vec::<&Item>::new();
+        let mut items = Vec::<&Item>::new();
+        for module in &self.modules {
+            let Some((_, module_items)) = self.built.iter().find(|(built, _)| built == module) else {
+                return Err(UberShaderError::MissingModule { artifact: self.artifact.clone(), module: module.clone() });
+            };
+
+            for item in module_items {
+                let combined = if is_directive(&item.text) { &mut directives } else { &mut items };
+                match combined.iter().find(|combined| combined.name.is_some() && combined.name == item.name).copied() {
+                    Some(same_name) if same_name.text == item.text => (),
+                    Some(same_name) => return Err(UberShaderError::NameClash {
+                        artifact: self.artifact.clone(),
+                        name: item.name.clone().unwrap_or_default(),
+                        first: same_name.origin.clone(),
+                        second: item.origin.clone(),
+                    }),
+                    None if item.name.is_none() && combined.iter().any(|combined| combined.text == item.text) => (),
+                    None => combined.push(item),
+                }
+            }
+        }
+
+        let directives = directives.iter().map(|item| item.text.as_str()).collect::<Vec<_>>().join("\n");
+        let items = items.iter().map(|item| item.text.as_str()).collect::<Vec<_>>().join("\n\n");
+        Ok(match directives.is_empty() {
+            true => format!("{items}\n"),
+            false => format!("{directives}\n\n{items}\n"),
+        })
+    }
+}
+
+/// Whether a top level item is a directive, which WGSL needs before every declaration
+fn is_directive(text: &str) -> bool {
+    ["enable", "requires", "diagnostic"].iter()
+        .any(|directive| text.strip_prefix(directive).is_some_and(|rest| rest.starts_with(|c: char| c.is_whitespace() || c == '(')))
+}
+
+/// The top level items of a WGSL source without comments, found without a full parse
+fn top_level_items(source: &str) -> Vec<String> {
+    let source = strip_comments(source);
+    let mut items = Vec::new();
+    let mut depth = 0_usize;
+    let mut start = 0;
+
+    for (idx, c) in source.char_indices() {
+        let end = match c {
+            '{' | '(' | '[' => {
+                depth += 1;
+                continue;
+            }
+            // the end of a function or struct
+            '}' => {
+                depth = depth.saturating_sub(1);
+                depth == 0
+            }
+            ')' | ']' => {
+                depth = depth.saturating_sub(1);
+                continue;
+            }
+            ';' => depth == 0,
+            _ => continue,
+        };
+        if end {
+            let item = source[start..=idx].trim();
+            // the optional `;` after a struct
+            if item != ";" {
+                items.push(item.to_owned());
+            }
+            start = idx + 1;
+        }
+    }
+    items
+}
+
+/// The name a top level item declares, `None` for directives and `const_assert`s
+fn item_name(item: &str) -> Option<String> {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    let mut rest = item;
+    while !rest.is_empty() {
+        let word_start = rest.find(is_ident)?;
+        rest = &rest[word_start..];
+        let word_end = rest.find(|c: char| !is_ident(c)).unwrap_or(rest.len());
+        let (word, after) = rest.split_at(word_end);
+        // attribute arguments like `@builtin(position)` are never keywords
+        if ["fn", "struct", "var", "const", "override", "alias"].contains(&word) {
+            let mut after = after.trim_start();
+            // the address space of a `var<storage, read>`
+            if word == "var" && after.starts_with('<') {
+                after = after[after.find('>')? + 1..].trim_start();
+            }
+            let name_end = after.find(|c: char| !is_ident(c)).unwrap_or(after.len());
+            return Some(after[..name_end].to_owned()).filter(|name| !name.is_empty());
+        }
+        rest = after;
+    }
+    None
+}
+
+impl<WeslResolver: wesl::Resolver> WeslBuildExtension<WeslResolver> for UberShaderExtension {
+    fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+        "UberShaderExtension".into()
+    }
+
+    fn reads_built_wgsl(&self) -> bool {
+        true
+    }
+
+    /// The items of the shaders must have the names the mangler gave them
+    fn must_run_before(&self) -> &[&str] {
+        &["WgslMinifierExtension", "WgslObfuscatorExtension"]
+    }
+
+    fn init_root(
+        &mut self,
+        _shader_root_path: &str,
+        _res: &mut wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.built.clear();
+        Ok(())
+    }
+
+    fn exit_root(
+        &mut self,
+        _shader_root_path: &str,
+        _res: &wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let combined = self.combined()?;
+        artifacts::write(crate::artifact_path(&build_out_dir()?, &self.artifact), combined)?;
+
+        #[cfg(feature = "logging")]
+        log::info!("combined {} shaders into `{}`", self.modules.len(), self.artifact);
+
+        Ok(())
+    }
+
+    fn post_build(
+        &mut self,
+        mod_path: &ModulePath,
+        wgsl_built_path: &str,
+        source_map: &Option<BasicSourceMap>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let module = mod_path.components.join("::");
+        if !self.modules.contains(&module) {
+            return Ok(());
+        }
+
+        let items = top_level_items(&artifacts::read_to_string(wgsl_built_path)?).into_iter()
+            .map(|text| {
+                let name = item_name(&text).filter(|_| !is_directive(&text));
+                // declarations of the root module are not mangled, so they aren't in the source map
+                let origin = name.as_ref()
+                    .and_then(|name| source_map.as_ref()?.get_decl(name))
+                    .map_or_else(|| mod_path.clone(), |(origin, _)| origin.clone());
+                Item { name, text, origin }
+            })
+            .collect();
+        self.built.push((module, items));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,123 @@
+#![cfg(feature = "wgpu_bindings_ext")]
+
+//! Reflect vertex buffer layouts from the inputs of vertex entry points as rust code for `wgpu`
+
+use std::fmt::Write as _;
+
+/// A vertex buffer of an entry point, from one of its arguments with `@location`s
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct VertexBuffer {
+    pub(crate) instance: bool,
+    /// The size of a vertex in bytes
+    pub(crate) array_stride: u64,
+    pub(crate) attributes: Vec<VertexAttribute>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct VertexAttribute {
+    /// e.g. `Float32x3`
+    pub(crate) format: String,
+    pub(crate) offset: u64,
+    pub(crate) location: u32,
+}
+
+impl VertexBuffer {
+    /// The rust code for a `wgpu::VertexBufferLayout<'static>`
+    pub(crate) fn to_rust(&self) -> String {
+        let mut attributes = String::new();
+        for attribute in &self.attributes {
+            writeln!(
+                attributes,
+                "            wgpu::VertexAttribute {{ format: wgpu::VertexFormat::{}, offset: {}, shader_location: {} }},",
+                attribute.format, attribute.offset, attribute.location,
+            ).expect("writing to a String can't fail");
+        }
+
+        format!(
+            "wgpu::VertexBufferLayout {{\n        \
+                array_stride: {},\n        \
+                step_mode: wgpu::VertexStepMode::{},\n        \
+                attributes: &[\n{attributes}        ],\n    \
+            }}",
+            self.array_stride,
+            if self.instance { "Instance" } else { "Vertex" },
+        )
+    }
+}
+
+/// The vertex buffers of `entry_point`, one per argument that is or has `@location`s, in order
+///
+/// A struct argument is a buffer of all its `@location` members, tightly packed like the structs `wgsl_to_wgpu`
+/// generates. `is_instance` is called with the argument name and its struct name, if any, to pick the step mode.
+/// Attributes of types that can't be vertex formats are left out, so those layouts are incomplete.
+pub(crate) fn vertex_buffers(
+    module: &naga::Module,
+    entry_point: &naga::EntryPoint,
+    is_instance: impl Fn(&str, Option<&str>) -> bool,
+) -> Vec<VertexBuffer> {
+    let mut buffers = Vec::new();
+
+    for argument in &entry_point.function.arguments {
+        let ty = &module.types[argument.ty];
+        // the typed locations of the buffer
+        let locations = match (&argument.binding, &ty.inner) {
+            (Some(naga::Binding::Location { location, .. }), _) => vec![(*location, argument.ty)],
+            (None, naga::TypeInner::Struct { members, .. }) => members.iter()
+                .filter_map(|member| match member.binding {
+                    Some(naga::Binding::Location { location, .. }) => Some((location, member.ty)),
+                    _ => None,
+                })
+                .collect(),
+            _ => continue,
+        };
+        if locations.is_empty() {
+            continue;
+        }
+
+        let mut attributes = Vec::new();
+        let mut offset = 0;
+        for (location, ty) in locations {
+            let Some((format, size)) = vertex_format(&module.types[ty].inner) else {
+                #[cfg(feature = "logging")]
+                log::warn!("`@location({location})` of `{}` can't be a vertex attribute, it is left out of the layout", entry_point.name);
+                continue;
+            };
+            attributes.push(VertexAttribute { format, offset, location });
+            offset += size;
+        }
+
+        let struct_name = matches!(ty.inner, naga::TypeInner::Struct { .. }).then_some(ty.name.as_deref()).flatten();
+        buffers.push(VertexBuffer {
+            instance: is_instance(argument.name.as_deref().unwrap_or_default(), struct_name),
+            array_stride: offset,
+            attributes,
+        });
+    }
+
+    buffers
+}
+
+/// The `wgpu::VertexFormat` variant and size in bytes of a vertex attribute of type `inner`
+fn vertex_format(inner: &naga::TypeInner) -> Option<(String, u64)> {
+    use naga::{ScalarKind, TypeInner};
+
+    let (scalar, count) = match *inner {
+        TypeInner::Scalar(scalar) => (scalar, 1),
+        TypeInner::Vector { size, scalar } => (scalar, size as u8),
+        _ => return None,
+    };
+    let kind = match (scalar.kind, scalar.width) {
+        (ScalarKind::Float, 4) => "Float32",
+        (ScalarKind::Sint, 4) => "Sint32",
+        (ScalarKind::Uint, 4) => "Uint32",
+        // there is no `Float16x3`
+        (ScalarKind::Float, 2) if count != 3 => "Float16",
+        _ => return None,
+    };
+
+    let format = match count {
+        1 => kind.to_owned(),
+        count => format!("{kind}x{count}"),
+    };
+    Some((format, u64::from(scalar.width) * u64::from(count)))
+}
@@ -0,0 +1,394 @@
+#![cfg(feature = "wesl_lint_ext")]
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Display;
+use std::{fs, path::{Path, PathBuf}};
+
+use wesl::{BasicSourceMap, ModulePath, SourceMap};
+
+use crate::WeslBuildExtension;
+use crate::artifacts;
+use crate::messages::{self, BuildMessage, Severity};
+
+/// A lint [`WeslLintExtension`] can run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Lint {
+    /// A binding that no entry point uses
+    UnusedBindings,
+    /// A function that no entry point calls
+    UnreachableFunctions,
+    /// A vertex `@builtin(position)` output without `@invariant`,
+    /// which can cause z-fighting when the same geometry is drawn by multiple pipelines
+    MissingInvariant,
+    /// Use of reduced precision types like `f16`
+    PrecisionPitfalls,
+    /// Structs not in `UpperCamelCase` or functions and bindings not in `snake_case`
+    NamingConventions,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 5] = [
+        Lint::UnusedBindings,
+        Lint::UnreachableFunctions,
+        Lint::MissingInvariant,
+        Lint::PrecisionPitfalls,
+        Lint::NamingConventions,
+    ];
+}
+
+impl Display for Lint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Lint::UnusedBindings => "unused_bindings",
+            Lint::UnreachableFunctions => "unreachable_functions",
+            Lint::MissingInvariant => "missing_invariant",
+            Lint::PrecisionPitfalls => "precision_pitfalls",
+            Lint::NamingConventions => "naming_conventions",
+        })
+    }
+}
+
+/// What to do when a [`Lint`] is triggered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LintLevel {
+    /// Ignore it
+    Allow,
+    /// Print a cargo warning
+    #[default]
+    Warn,
+    /// Fail the build
+    Deny,
+}
+
+/// A triggered lint
+#[derive(Debug, Clone)]
+pub struct LintDiagnostic {
+    pub lint: Lint,
+    pub level: LintLevel,
+    /// `file:line` of the declaration if it could be found, or else its module
+    pub location: String,
+    pub message: String,
+}
+
+impl LintDiagnostic {
+    /// The diagnostic as a [`BuildMessage`] about `mod_path`, with the file and line of its location
+    pub fn to_message(&self, mod_path: &ModulePath) -> BuildMessage {
+        let severity = match self.level {
+            LintLevel::Deny => Severity::Error,
+            _ => Severity::Warning,
+        };
+        let message = BuildMessage::new(severity, format!("{} [{}]", self.message, self.lint)).with_module(mod_path);
+        match self.location.rsplit_once(':').and_then(|(file, line)| Some((file, line.parse().ok()?))) {
+            Some((file, line)) => message.with_file(file).with_line(line),
+            None if Path::new(&self.location).is_file() => message.with_file(&self.location),
+            None => message,
+        }
+    }
+}
+
+impl Display for LintDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {} [{}]", self.location, self.message, self.lint)
+    }
+}
+
+/// Errors for every denied lint in a shader
+#[derive(Debug, thiserror::Error)]
+#[error("denied lints in `{mod_path}`:\n{}", .diagnostics.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n"))]
+pub struct DeniedLintsError {
+    pub mod_path: ModulePath,
+    pub diagnostics: Vec<LintDiagnostic>,
+}
+
+/// Lint the built shaders, reporting the declarations in the source files with the source map
+///
+/// Lints run on the compiled module, so add this before any extensions that change the built shader.
+/// All lints are [`LintLevel::Warn`] by default.
+///
+/// ## Example
+/// ```
+/// use wesl_build::extension::wesl_lint::{WeslLintExtension, Lint, LintLevel};
+///
+/// let lints = WeslLintExtension::new()
+///     .lint(Lint::MissingInvariant, LintLevel::Deny)
+///     .lint(Lint::NamingConventions, LintLevel::Allow);
+/// ```
+pub struct WeslLintExtension {
+    levels: BTreeMap<Lint, LintLevel>,
+    shader_root_path: PathBuf,
+}
+
+impl Default for WeslLintExtension {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WeslLintExtension {
+    pub fn new() -> Self {
+        Self {
+            levels: Lint::ALL.into_iter().map(|lint| (lint, LintLevel::default())).collect(),
+            shader_root_path: PathBuf::new(),
+        }
+    }
+
+    /// Set the level of `lint`
+    pub fn lint(mut self, lint: Lint, level: LintLevel) -> Self {
+        self.levels.insert(lint, level);
+        self
+    }
+
+    fn level(&self, lint: Lint) -> LintLevel {
+        self.levels.get(&lint).copied().unwrap_or_default()
+    }
+
+    /// Run all lints that are not allowed on `module`
+    fn run_lints(
+        &self,
+        mod_path: &ModulePath,
+        module: &naga::Module,
+        info: &naga::valid::ModuleInfo,
+        source_map: Option<&BasicSourceMap>,
+    ) -> Vec<LintDiagnostic> {
+        let mut diagnostics = Vec::new();
+        let mut report = |lint: Lint, decl: &str, message: String| {
+            let level = self.level(lint);
+            if level != LintLevel::Allow {
+                diagnostics.push(LintDiagnostic {
+                    lint,
+                    level,
+                    location: self.locate(mod_path, decl, source_map),
+                    message,
+                });
+            }
+        };
+
+        if self.level(Lint::UnusedBindings) != LintLevel::Allow {
+            for (handle, global) in module.global_variables.iter() {
+                let is_used = (0..module.entry_points.len())
+                    .any(|idx| !info.get_entry_point(idx)[handle].is_empty());
+                if global.binding.is_some() && !is_used {
+                    let name = global.name.as_deref().unwrap_or("unnamed");
+                    report(Lint::UnusedBindings, name, format!("binding `{}` is never used", original_name(name, source_map)));
+                }
+            }
+        }
+
+        if self.level(Lint::UnreachableFunctions) != LintLevel::Allow {
+            let reachable = reachable_functions(module);
+            for (handle, function) in module.functions.iter() {
+                if !reachable.contains(&handle) {
+                    let name = function.name.as_deref().unwrap_or("unnamed");
+                    report(Lint::UnreachableFunctions, name, format!("function `{}` is never called by an entry point", original_name(name, source_map)));
+                }
+            }
+        }
+
+        if self.level(Lint::MissingInvariant) != LintLevel::Allow {
+            for entry_point in module.entry_points.iter().filter(|ep| ep.stage == naga::ShaderStage::Vertex) {
+                if outputs_variant_position(module, &entry_point.function) {
+                    report(Lint::MissingInvariant, entry_point.name.as_str(), format!(
+                        "vertex entry point `{}` outputs `@builtin(position)` without `@invariant`", entry_point.name
+                    ));
+                }
+            }
+        }
+
+        if self.level(Lint::PrecisionPitfalls) != LintLevel::Allow {
+            for (_, ty) in module.types.iter() {
+                if is_half_precision(&ty.inner) {
+                    let name = ty.name.as_deref().unwrap_or("f16");
+                    report(Lint::PrecisionPitfalls, name, format!(
+                        "`{}` uses `f16`, which has only about 3 decimal digits of precision", original_name(name, source_map)
+                    ));
+                }
+            }
+        }
+
+        if self.level(Lint::NamingConventions) != LintLevel::Allow {
+            for (_, ty) in module.types.iter() {
+                if let (Some(name), naga::TypeInner::Struct { .. }) = (&ty.name, &ty.inner) {
+                    let original = original_name(name, source_map);
+                    if !is_upper_camel_case(&original) {
+                        report(Lint::NamingConventions, name.as_str(), format!("struct `{original}` should be in UpperCamelCase"));
+                    }
+                }
+            }
+            let function_names = module.functions.iter().filter_map(|(_, function)| function.name.as_deref());
+            let binding_names = module.global_variables.iter().filter_map(|(_, global)| global.name.as_deref());
+            for name in function_names.chain(binding_names) {
+                let original = original_name(name, source_map);
+                if !is_snake_case(&original) {
+                    report(Lint::NamingConventions, name, format!("`{original}` should be in snake_case"));
+                }
+            }
+        }
+
+        diagnostics
+    }
+
+    /// `file:line` of the declaration `decl` in the source files if it can be found, or else its module
+    fn locate(&self, mod_path: &ModulePath, decl: &str, source_map: Option<&BasicSourceMap>) -> String {
+        let (decl_mod_path, decl_name) = source_map
+            .and_then(|source_map| source_map.get_decl(decl))
+            .map_or((mod_path.clone(), decl.to_owned()), |(path, name)| (path.clone(), name.to_owned()));
+
+        let mut source_path = self.shader_root_path.clone();
+        source_path.extend(&decl_mod_path.components);
+        let source_path = ["wesl", "wgsl"].into_iter()
+            .map(|ext| source_path.with_extension(ext))
+            .find(|path| path.is_file());
+
+        let line = source_path.as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|source| source.lines()
+                .position(|line| line.split(|c: char| !(c.is_alphanumeric() || c == '_')).any(|word| word == decl_name))
+            );
+
+        match (source_path, line) {
+            (Some(path), Some(line)) => format!("{}:{}", path.display(), line + 1),
+            (Some(path), None) => path.display().to_string(),
+            _ => decl_mod_path.to_string(),
+        }
+    }
+}
+
+/// The name of a declaration before mangling
+fn original_name(decl: &str, source_map: Option<&BasicSourceMap>) -> String {
+    source_map
+        .and_then(|source_map| source_map.get_decl(decl))
+        .map_or_else(|| decl.to_owned(), |(_, name)| name.to_owned())
+}
+
+/// All functions called directly or indirectly from an entry point
+fn reachable_functions(module: &naga::Module) -> HashSet<naga::Handle<naga::Function>> {
+    let mut reachable = HashSet::new();
+    let mut to_visit = module.entry_points.iter()
+        .flat_map(|entry_point| called_functions(&entry_point.function.body))
+        .collect::<Vec<_>>();
+
+    while let Some(handle) = to_visit.pop() {
+        if reachable.insert(handle) {
+            to_visit.extend(called_functions(&module.functions[handle].body));
+        }
+    }
+
+    reachable
+}
+
+/// The functions called in `block`, not including the functions they call
+fn called_functions(block: &naga::Block) -> Vec<naga::Handle<naga::Function>> {
+    use naga::Statement;
+
+    let mut called = Vec::new();
+    for statement in block.iter() {
+        match statement {
+            Statement::Call { function, .. } => called.push(*function),
+            Statement::Block(block) => called.extend(called_functions(block)),
+            Statement::If { accept, reject, .. } => {
+                called.extend(called_functions(accept));
+                called.extend(called_functions(reject));
+            }
+            Statement::Switch { cases, .. } => {
+                for case in cases {
+                    called.extend(called_functions(&case.body));
+                }
+            }
+            Statement::Loop { body, continuing, .. } => {
+                called.extend(called_functions(body));
+                called.extend(called_functions(continuing));
+            }
+            _ => (),
+        }
+    }
+
+    called
+}
+
+/// Whether `function` outputs `@builtin(position)` without `@invariant`
+fn outputs_variant_position(module: &naga::Module, function: &naga::Function) -> bool {
+    let is_variant_position = |binding: &Option<naga::Binding>| matches!(
+        binding,
+        Some(naga::Binding::BuiltIn(naga::BuiltIn::Position { invariant: false }))
+    );
+
+    let Some(result) = &function.result else { return false };
+    if is_variant_position(&result.binding) {
+        return true;
+    }
+
+    match &module.types[result.ty].inner {
+        naga::TypeInner::Struct { members, .. } => members.iter()
+            .any(|member| is_variant_position(&member.binding)),
+        _ => false,
+    }
+}
+
+fn is_half_precision(ty: &naga::TypeInner) -> bool {
+    use naga::{Scalar, ScalarKind, TypeInner};
+
+    let is_f16 = |scalar: &Scalar| scalar.kind == ScalarKind::Float && scalar.width == 2;
+    match ty {
+        TypeInner::Scalar(scalar) => is_f16(scalar),
+        TypeInner::Vector { scalar, .. } | TypeInner::Matrix { scalar, .. } => is_f16(scalar),
+        _ => false,
+    }
+}
+
+fn is_upper_camel_case(name: &str) -> bool {
+    name.chars().next().is_some_and(char::is_uppercase) && !name.contains('_')
+}
+
+fn is_snake_case(name: &str) -> bool {
+    !name.chars().any(char::is_uppercase)
+}
+
+impl<WeslResolver: wesl::Resolver> WeslBuildExtension<WeslResolver> for WeslLintExtension {
+    fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+        "WeslLintExtension".into()
+    }
+
+    fn reads_built_wgsl(&self) -> bool {
+        true
+    }
+
+    fn init_root(
+        &mut self,
+        shader_root_path: &str,
+        _res: &mut wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.shader_root_path = PathBuf::from(shader_root_path);
+        Ok(())
+    }
+
+    fn enter_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+    fn exit_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+
+    fn post_build(
+        &mut self,
+        mod_path: &ModulePath,
+        wgsl_built_path: &str,
+        source_map: &Option<BasicSourceMap>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let wgsl_source = artifacts::read_to_string(wgsl_built_path)?;
+        let module = naga::front::wgsl::parse_str(&wgsl_source)?;
+        let info = naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::all(),
+        ).validate(&module)?;
+
+        let (denied, warnings): (Vec<_>, Vec<_>) = self.run_lints(mod_path, &module, &info, source_map.as_ref())
+            .into_iter()
+            .partition(|diagnostic| diagnostic.level == LintLevel::Deny);
+
+        for warning in warnings {
+            messages::report(&warning.to_message(mod_path))?;
+        }
+
+        if denied.is_empty() {
+            Ok(())
+        } else {
+            Err(Box::new(DeniedLintsError { mod_path: mod_path.clone(), diagnostics: denied }))
+        }
+    }
+}
@@ -1,62 +1,645 @@
 #![cfg(feature = "wgpu_bindings_ext")]
 
-use std::fmt::Display;
+use std::fmt::{Display, Write as _};
 use std::io::{BufWriter, Write};
+use std::collections::BTreeMap;
 use std::{fs, path::{Path, PathBuf}};
 
 use wesl::{BasicSourceMap, Mangler};
 use wesl::ModulePath;
 use wgsl_to_wgpu::WriteOptions;
+pub use wgsl_to_wgpu::MatrixVectorTypes;
 
 use crate::WeslBuildExtension;
+use crate::artifacts;
+use crate::dir_lock::{self, DirLock};
+use crate::extension::Visibility;
+use crate::extension::bind_group_layouts::{LayoutEntry, bind_group_layouts, shader_stage};
+use crate::extension::pipeline_state::{PIPELINE_STATE_SUFFIX, PipelineState};
+use crate::extension::vertex_layouts::vertex_buffers;
 
 /// Generate bindings for your wgsl/wesl with wgpu_to_wgsl
 ///
-/// Note this will set the `ManglerKind` to `Escape`
+/// Note items are only put in the module matching their shader with the `Escape` mangler (the default),
+/// other manglers can't be demangled, so all items end up in the root module
 pub struct WgpuBindingsExtension<W: Write> {
     /// The path to output the rust bindings for shaders
-    binding_root_path: &'static str,
-    /// The courrent module
-    bindings_mod_file: W,
+    binding_root_path: PathBuf,
+    /// Where the bindings are written, a [staging dir](staging_dir) moved into the binding root once they are all written,
+    /// or a scratch dir when [verifying](`crate::is_verify`) them
+    output_root: PathBuf,
+    /// The courrent module, `None` before the build starts and in a [check](`crate::is_check`)
+    bindings_mod_file: Option<W>,
     /// The courrent modules path
     bindings_mod_path: PathBuf,
+    /// Extra codegen on top of `wgsl_to_wgpu`'s bindings
+    options: BindingsOptions,
+    /// The bind group layouts of each shader, only collected if `options.shared_layouts` is set
+    shader_layouts: Vec<ShaderLayouts>,
+    /// The root dir of the shaders, to point errors to the source files
+    shader_root_path: PathBuf,
+    /// Every file written this build, they get the header and are formatted once all are written
+    written_files: Vec<PathBuf>,
+    /// The modules declared in each module file, to catch names that collide
+    mod_names: ModNames,
+    /// The render state sidecars, with their path, by the path of their shader without the extension
+    pipeline_states: BTreeMap<PathBuf, (PathBuf, PipelineState)>,
+    /// The lock on the binding root while the bindings are written, so concurrent builds don't interleave them
+    dir_lock: Option<DirLock>,
+}
+
+/// The bind group layouts of a built shader
+struct ShaderLayouts {
+    mod_path: ModulePath,
+    binding_path: PathBuf,
+    layouts: BTreeMap<u32, Vec<LayoutEntry>>,
+}
+
+/// Extra codegen on top of `wgsl_to_wgpu`'s bindings
+#[derive(Debug, Clone, Default)]
+struct BindingsOptions {
+    /// Generate an `ENTRY_POINTS` table in each binding module
+    entry_points: bool,
+    /// Generate `common_layouts.rs` with the bind group layouts shared by shaders
+    shared_layouts: bool,
+    /// Generate a `pipeline_constants` module with the immediates and overrides of each shader
+    pipeline_constants: bool,
+    /// Generate the vertex buffer layouts of each vertex entry point
+    vertex_layouts: bool,
+    /// The arguments or structs that are per instance buffers, empty to use the naming convention
+    instance_buffers: Vec<String>,
+    /// Generate `create_render_pipeline` and `create_compute_pipeline` functions
+    pipeline_helpers: bool,
+    /// Generate a `pipeline_descriptor` function for shaders with a `<name>.pipeline.toml`
+    pipeline_state: bool,
+    /// Derive `bytemuck` for uniform and storage structs and assert their Rust layout is their WGSL layout
+    layout_assertions: bool,
+    /// Generate a `#[cfg(test)]` module checking the embedded shader against the bindings
+    test_harness: bool,
+    /// Generate a `bindings` module with the `(group, binding)` of every resource
+    binding_consts: bool,
+    /// Generate `WORKGROUP_SIZE` consts and `dispatch_count` functions for the compute entry points
+    workgroup_sizes: bool,
+    /// Generate a `create_shader_module_unchecked` function that skips wgpu's validation
+    unchecked_shader_module: bool,
+    /// Generate a `load_latest` function that compiles the shader at runtime in debug builds
+    #[cfg(feature = "hot_reload")]
+    hot_reload: bool,
+    /// Write a stub module for every shader that fails to build
+    stubs: bool,
+    /// A banner, e.g. a license, put above the `@generated` line of every file
+    header: Option<String>,
+    /// Format every file with `prettyplease`
+    format_output: bool,
+    /// The visibility of every generated module
+    mod_visibility: Visibility,
+    /// How dir and shader names are turned into module names
+    mod_name_case: ModNameCase,
+    /// Put before and after the name of every dir and shader module
+    mod_prefix: String,
+    mod_suffix: String,
+    /// Appended to module names that are Rust keywords instead of making them raw identifiers
+    keyword_suffix: Option<String>,
+    /// The Rust types of WGSL vectors and matrices, `None` for `nalgebra`
+    matrix_vector_types: Option<MatrixVectorTypes>,
+    /// Derive `serde` for the generated structs
+    derive_serde: bool,
+}
+
+impl BindingsOptions {
+    /// Whether the codegen needs the shader parsed by naga
+    fn needs_naga_module(&self) -> bool {
+        self.entry_points || self.shared_layouts || self.pipeline_constants || self.vertex_layouts || self.pipeline_helpers || self.workgroup_sizes
+            || self.binding_consts || self.layout_assertions || self.test_harness
+    }
+
+    /// Whether the vertex buffer of the entry point argument `argument` of type `struct_name` is per instance
+    fn is_instance_buffer(&self, argument: &str, struct_name: Option<&str>) -> bool {
+        let mut names = std::iter::once(argument).chain(struct_name);
+        if self.instance_buffers.is_empty() {
+            names.any(|name| name.to_lowercase().contains("instance"))
+        } else {
+            // struct names may be mangled
+            names.any(|name| self.instance_buffers.iter().any(|instance| name.ends_with(instance.as_str())))
+        }
+    }
+
+    /// The name of the module, and its file, of the dir or shader `name`
+    ///
+    /// Keywords become raw identifiers, `type` is `mod r#type;` in `type.rs`, or get the [`keyword_suffix`](Self::keyword_suffix).
+    fn mod_name(&self, name: &str) -> Result<ModName, WgpuBindingsError> {
+        let converted = match self.mod_name_case {
+            ModNameCase::Verbatim => name.to_owned(),
+            ModNameCase::SnakeCase => snake_case(name),
+        };
+        let file = format!("{}{converted}{}", self.mod_prefix, self.mod_suffix);
+        if !is_ident(&file) {
+            return Err(WgpuBindingsError::InvalidModName { name: name.to_owned(), mod_name: file });
+        }
+        if !KEYWORDS.contains(&file.as_str()) {
+            return Ok(ModName { ident: file.clone(), file });
+        }
+
+        match &self.keyword_suffix {
+            None if !NOT_RAW_KEYWORDS.contains(&file.as_str()) => Ok(ModName { ident: format!("r#{file}"), file }),
+            suffix => {
+                let renamed = format!("{file}{}", suffix.as_deref().unwrap_or("_"));
+                Ok(ModName { ident: renamed.clone(), file: renamed })
+            }
+        }
+    }
+}
+
+/// The name of a generated module, and of its file without the extension
+struct ModName {
+    ident: String,
+    file: String,
+}
+
+/// The module names declared in each module file, by the file
+#[derive(Debug, Default)]
+struct ModNames(BTreeMap<PathBuf, BTreeMap<String, String>>);
+
+impl ModNames {
+    /// Declare the module `ident` of the dir or shader `name` in `mod_file`,
+    /// fails if another dir or shader already has a module with that name there
+    fn claim(&mut self, mod_file: &Path, ident: &str, name: &str) -> Result<(), WgpuBindingsError> {
+        let names = self.0.entry(mod_file.to_owned()).or_default();
+        match names.get(ident) {
+            Some(first) => Err(WgpuBindingsError::ModNameCollision {
+                mod_name: ident.to_owned(),
+                names: [first.clone(), name.to_owned()],
+                mod_file: mod_file.to_owned(),
+            }),
+            None => {
+                names.insert(ident.to_owned(), name.to_owned());
+                Ok(())
+            }
+        }
+    }
+
+    /// Whether the module `ident` in `mod_file` was declared for the dir or shader `name`
+    fn is_claimed_by(&self, mod_file: &Path, ident: &str, name: &str) -> bool {
+        self.0.get(mod_file).and_then(|names| names.get(ident)).is_some_and(|first| first == name)
+    }
+}
+
+/// Strict and reserved keywords of the 2024 edition
+const KEYWORDS: [&str; 52] = [
+    "Self", "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let", "loop", "macro", "match",
+    "mod", "move", "mut", "override", "priv", "pub", "ref", "return", "self", "static", "struct", "super", "trait",
+    "true", "try", "type", "typeof", "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+/// Keywords that can't be raw identifiers
+const NOT_RAW_KEYWORDS: [&str; 4] = ["Self", "crate", "self", "super"];
+
+fn is_ident(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+        && name != "_"
+}
+
+/// How dir and shader names are turned into module names, see [`WgpuBindingsExtension::with_mod_name_case`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ModNameCase {
+    /// Use the names as they are, the default
+    #[default]
+    Verbatim,
+    /// `snake_case`, e.g. `post-fx` and `PostFx` become `post_fx`
+    SnakeCase,
+}
+
+/// `name` in `snake_case`, words are split at `-`, spaces and lower to upper case changes
+fn snake_case(name: &str) -> String {
+    let mut snake = String::with_capacity(name.len());
+    let mut prev_lower = false;
+    for c in name.chars() {
+        if c == '-' || c == ' ' || c == '_' {
+            if !snake.ends_with('_') {
+                snake.push('_');
+            }
+            prev_lower = false;
+        } else if c.is_uppercase() {
+            if prev_lower {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+            prev_lower = false;
+        } else {
+            snake.push(c);
+            prev_lower = c.is_lowercase() || c.is_ascii_digit();
+        }
+    }
+    snake
 }
 
 impl WgpuBindingsExtension<BufWriter<fs::File>> {
     // todo take `wgsl_to_wgpu` options as args, storing `WriteOptions` in struct
-    pub fn new(binding_root_path: &'static str) -> Result<Self, std::io::Error> {
-        let bindings_mod_path = Path::new(binding_root_path).join("mod.rs");
+    pub fn new(binding_root_path: impl Into<PathBuf>) -> Result<Self, std::io::Error> {
+        let binding_root_path = binding_root_path.into();
+        let bindings_mod_path = binding_root_path.join("mod.rs");
         println!("root: {}", bindings_mod_path.display());
 
         Ok(Self {
+            output_root: binding_root_path.clone(),
             binding_root_path,
-            bindings_mod_file: BufWriter::new(fs::File::create(
-                &bindings_mod_path,
-            )?),
+            bindings_mod_file: None,
             bindings_mod_path,
+            options: BindingsOptions::default(),
+            shader_layouts: Vec::new(),
+            shader_root_path: PathBuf::new(),
+            written_files: Vec::new(),
+            mod_names: ModNames::default(),
+            pipeline_states: BTreeMap::new(),
+            dir_lock: None,
         })
     }
 }
 
+impl<W: Write> WgpuBindingsExtension<W> {
+    /// Generate a `pub const ENTRY_POINTS: &[EntryPoint]` table with the name, stage and workgroup size
+    /// of every entry point in each binding module, `EntryPoint` is defined in the root bindings module
+    pub fn with_entry_points(mut self, entry_points: bool) -> Self {
+        self.options.entry_points = entry_points;
+        self
+    }
+
+    /// Generate compile time assertions that the `size_of` and field `offset_of!` of every struct used in a uniform
+    /// or storage buffer are the size and offsets naga computed for the WGSL struct, so host and shader layouts can't drift
+    ///
+    /// The structs derive `bytemuck` with explicit padding instead of `encase`, so their Rust layout is the WGSL layout.
+    /// Alignment isn't asserted, the Rust structs may be less aligned, buffers are aligned by wgpu.
+    /// Structs ending in a runtime sized array have no Rust equivalent and are not asserted.
+    pub fn with_layout_assertions(mut self, layout_assertions: bool) -> Self {
+        self.options.layout_assertions = layout_assertions;
+        self
+    }
+
+    /// Generate a `#[cfg(test)] mod wesl_build_tests` in every binding module, so `cargo test` in your crate catches
+    /// bindings that drifted from the shader they embed, without a GPU
+    ///
+    /// Its tests parse and validate the embedded `SOURCE` with naga and assert that it still has the entry points and
+    /// `(group, binding)`s of the bindings, and that the structs in its uniform and storage buffers still have their size
+    /// and member offsets, which [`with_layout_assertions`](Self::with_layout_assertions) asserts the Rust structs have.
+    /// Your crate needs `naga` with its `wgsl-in` feature as a dev-dependency.
+    pub fn with_test_harness(mut self, test_harness: bool) -> Self {
+        self.options.test_harness = test_harness;
+        self
+    }
+
+    /// Generate a `pub mod bindings` with a `(group, binding)` const for every resource of the shader, named after its
+    /// demangled WESL name, e.g. `pub const CAMERA: (u32, u32) = (0, 0);` for `camera`, for bind groups created by hand
+    ///
+    /// Resources with the same name from different modules are prefixed with their module, e.g. `LIGHTS_CAMERA`.
+    pub fn with_binding_consts(mut self, binding_consts: bool) -> Self {
+        self.options.binding_consts = binding_consts;
+        self
+    }
+
+    /// Generate a `pub const WORKGROUP_SIZE: [u32; 3]` and a `pub const fn dispatch_count(dims: [u32; 3]) -> [u32; 3]`,
+    /// the number of workgroups to dispatch to cover `dims` invocations, in the bindings of each compute shader
+    ///
+    /// Shaders with more than one compute entry point get them per entry point instead,
+    /// e.g. `MAIN_WORKGROUP_SIZE` and `main_dispatch_count`.
+    pub fn with_workgroup_sizes(mut self, workgroup_sizes: bool) -> Self {
+        self.options.workgroup_sizes = workgroup_sizes;
+        self
+    }
+
+    /// Find structurally identical bind group layouts across all shaders, once all are built,
+    /// and generate them in a shared `common_layouts.rs` in the bindings root
+    ///
+    /// Each shader with a shared layout gets a `shared_layouts` module re-exporting them by group,
+    /// e.g. `shared_layouts::Group0` with `ENTRIES` and `get_bind_group_layout(device)`
+    pub fn with_shared_layouts(mut self, shared_layouts: bool) -> Self {
+        self.options.shared_layouts = shared_layouts;
+        self
+    }
+
+    /// Generate a `pipeline_constants` module in each binding module with the typed immediates (push constants)
+    /// and overrides of the shader, so they can't drift from it
+    ///
+    /// It has an `Immediates` alias of the type of the `var<immediate>`, with its `IMMEDIATE_SIZE` for the pipeline layout,
+    /// and an `OverrideConstants` struct with a field and setter per `override`, whose `constants()` are the map for
+    /// `wgpu::PipelineCompilationOptions::constants`, keyed by the `@id` of an override or its name.
+    pub fn with_pipeline_constants(mut self, pipeline_constants: bool) -> Self {
+        self.options.pipeline_constants = pipeline_constants;
+        self
+    }
+
+    /// Generate a `<ENTRY_POINT>_VERTEX_BUFFERS` const with the `wgpu::VertexBufferLayout`s of each vertex entry point,
+    /// one per argument that is or has `@location`s, with the attribute locations and formats from the shader
+    ///
+    /// Buffers are per vertex, or per instance if the argument or its struct has `instance` in its name,
+    /// e.g. `instance: Instance`, unless [`with_instance_buffer`](Self::with_instance_buffer) is used.
+    pub fn with_vertex_layouts(mut self, vertex_layouts: bool) -> Self {
+        self.options.vertex_layouts = vertex_layouts;
+        self
+    }
+
+    /// Make the vertex buffers of entry point arguments named `name`, or of a struct named `name`, per instance,
+    /// instead of the `instance` naming convention of [`with_vertex_layouts`](Self::with_vertex_layouts)
+    pub fn with_instance_buffer(mut self, name: impl Into<String>) -> Self {
+        self.options.instance_buffers.push(name.into());
+        self
+    }
+
+    /// Generate a `create_render_pipeline(device, targets, options)` in the bindings of shaders with one vertex
+    /// and at most one fragment entry point, and a `create_compute_pipeline(device)` in those with one compute entry point
+    ///
+    /// The pipelines use the shader's pipeline layout and [vertex buffer layouts](Self::with_vertex_layouts),
+    /// which are generated too. The rest of the render pipeline state is a `RenderPipelineOptions`,
+    /// defined in the root bindings module.
+    pub fn generate_pipeline_helpers(mut self, pipeline_helpers: bool) -> Self {
+        self.options.pipeline_helpers = pipeline_helpers;
+        self
+    }
+
+    /// Generate a `pipeline_descriptor(module, layout, targets)` in the bindings of every shader with a render state sidecar,
+    /// `<name>.pipeline.toml` next to it, with its blend, depth, cull and topology state, and the pipeline and
+    /// [vertex buffer layouts](Self::with_vertex_layouts) reflected from the shader
+    ///
+    /// The sidecars are [assets](`crate::WeslBuildConfig::asset_extensions`), so `"toml"` must be one of the asset extensions.
+    /// Unknown keys or values, and entry points the shader doesn't have, fail the build:
+    /// ```toml
+    /// vertex = "vs_main"        # optional with a single vertex entry point
+    /// fragment = "fs_main"      # optional with at most one fragment entry point
+    /// topology = "triangle-list"
+    /// cull = "back"             # none, front or back
+    /// front_face = "ccw"
+    /// blend = "alpha"           # none, replace, alpha, premultiplied or additive
+    ///
+    /// [depth]
+    /// format = "depth32float"
+    /// write = true
+    /// compare = "less"
+    /// ```
+    /// The bindings also get `primitive_state()`, `depth_stencil_state()`, and with a fragment entry point
+    /// a `BLEND` const and `color_target(format)`.
+    pub fn with_pipeline_state(mut self, pipeline_state: bool) -> Self {
+        self.options.pipeline_state = pipeline_state;
+        self
+    }
+
+    /// Generate an `unsafe fn create_shader_module_unchecked(device)` next to `create_shader_module`, which passes
+    /// the shader through to the backend with `wgpu::ShaderModuleDescriptorPassthrough`, skipping wgpu's validation
+    ///
+    /// For shaders validated at build time, e.g. by naga, that are created often enough for validation to matter.
+    /// The device needs `wgpu::Features::EXPERIMENTAL_PASSTHROUGH_SHADERS`.
+    pub fn with_unchecked_shader_module(mut self, unchecked_shader_module: bool) -> Self {
+        self.options.unchecked_shader_module = unchecked_shader_module;
+        self
+    }
+
+    /// Generate a `SHADER_FILE` const with the path of the shader and a `load_latest()` function that compiles
+    /// its current source with `wesl` in debug builds, so shaders can be reloaded while the app runs
+    ///
+    /// In release builds `load_latest` returns the embedded `SOURCE`. Your crate needs `wesl` as a dependency,
+    /// and the rewrites of extensions, e.g. [`preprocess`](`crate::extension::WeslBuildExtension::preprocess`), are not applied.
+    #[cfg(feature = "hot_reload")]
+    pub fn with_hot_reload(mut self, hot_reload: bool) -> Self {
+        self.options.hot_reload = hot_reload;
+        self
+    }
+
+    /// Write a stub module, with an empty `SOURCE`, for every shader that fails to build,
+    /// so the module tree stays complete and rust-analyzer resolves the modules of the bindings
+    ///
+    /// See [`write_stubs`](Self::write_stubs) for stubs before the first build.
+    pub fn generate_stubs(mut self, stubs: bool) -> Self {
+        self.options.stubs = stubs;
+        self
+    }
+
+    /// Quickly write a stub module for every shader in `graph`, e.g. [`ModuleGraph::from_dir`](`crate::ModuleGraph::from_dir`)
+    /// of the shader dir, without compiling or validating anything, if the bindings were never generated
+    ///
+    /// Call it before the build, so rust-analyzer resolves the modules of the bindings before the first build
+    /// finishes, or even if it fails. The full build replaces the stubs. Returns the files written,
+    /// none if the root `mod.rs` of the bindings exists.
+    pub fn write_stubs(&self, graph: &crate::ModuleGraph) -> Result<Vec<PathBuf>, WgpuBindingsError> {
+        let root_mod = self.binding_root_path.join("mod.rs");
+        if root_mod.exists() {
+            return Ok(Vec::new());
+        }
+
+        // the `mod` lines of each module file
+        let mut mod_files = BTreeMap::from([(root_mod, vec!["#![allow(unused)]\n".to_owned()])]);
+        let mut written = Vec::new();
+        for mod_path in graph.modules().filter(|mod_path| graph.source_path(mod_path).is_some()) {
+            let mut dir = self.binding_root_path.clone();
+            for component in &mod_path.components {
+                let name = self.options.mod_name(component)?;
+                let line = format!("{}mod {};", self.options.mod_visibility, name.ident);
+                let lines = mod_files.entry(dir.join("mod.rs")).or_default();
+                if !lines.contains(&line) {
+                    lines.push(line);
+                }
+                dir.push(&name.file);
+            }
+
+            let stub_path = dir.with_extension("rs");
+            fs::create_dir_all(stub_path.parent().expect("a stub is in the bindings root"))?;
+            artifacts::write_atomic(&stub_path, stub(mod_path).as_bytes())?;
+            written.push(stub_path);
+        }
+        for (mod_file, lines) in mod_files {
+            fs::create_dir_all(mod_file.parent().expect("a module file is in the bindings root"))?;
+            artifacts::write_atomic(&mod_file, (lines.join("\n") + "\n").as_bytes())?;
+            written.push(mod_file);
+        }
+
+        for path in &written {
+            finish_file(path, &self.options)?;
+        }
+        Ok(written)
+    }
+
+    /// Put `header`, e.g. a license, at the top of every generated file, above the `@generated by wesl_build` line
+    ///
+    /// Lines that are not comments are turned into `//` comments.
+    pub fn with_header(mut self, header: impl Into<String>) -> Self {
+        self.options.header = Some(header.into());
+        self
+    }
+
+    /// Format every generated file with `prettyplease`, so the parts added on top of `wgsl_to_wgpu`'s bindings
+    /// and the module files are formatted too and diffs of the bindings stay small
+    pub fn format_output(mut self, format_output: bool) -> Self {
+        self.options.format_output = format_output;
+        self
+    }
+
+    /// Set the visibility of every generated module, `pub(crate)` by default
+    pub fn with_mod_visibility(mut self, visibility: Visibility) -> Self {
+        self.options.mod_visibility = visibility;
+        self
+    }
+
+    /// Convert dir and shader names to module names, e.g. [`ModNameCase::SnakeCase`] for `kebab-case` dirs,
+    /// the files of the bindings are named after the modules
+    pub fn with_mod_name_case(mut self, case: ModNameCase) -> Self {
+        self.options.mod_name_case = case;
+        self
+    }
+
+    /// Put `prefix` before the name of every dir and shader module, e.g. `shader_`
+    pub fn with_mod_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.options.mod_prefix = prefix.into();
+        self
+    }
+
+    /// Put `suffix` after the name of every dir and shader module, e.g. `_bindings`
+    pub fn with_mod_suffix(mut self, suffix: impl Into<String>) -> Self {
+        self.options.mod_suffix = suffix.into();
+        self
+    }
+
+    /// Rename modules named after Rust keywords by appending `suffix`, e.g. `type` to `type_`,
+    /// instead of making them raw identifiers like `r#type`
+    ///
+    /// `self`, `super`, `crate` and `Self` can't be raw identifiers and always get a suffix, `_` if none is set.
+    pub fn with_keyword_suffix(mut self, suffix: impl Into<String>) -> Self {
+        self.options.keyword_suffix = Some(suffix.into());
+        self
+    }
+
+    /// Use `types` for WGSL vectors and matrices in the bindings, [`MatrixVectorTypes::Nalgebra`] by default
+    pub fn with_matrix_vector_types(mut self, types: MatrixVectorTypes) -> Self {
+        self.options.matrix_vector_types = Some(types);
+        self
+    }
+
+    /// Derive `serde::Serialize` and `serde::Deserialize` for the generated structs
+    pub fn with_derive_serde(mut self, derive_serde: bool) -> Self {
+        self.options.derive_serde = derive_serde;
+        self
+    }
+
+    /// Write a line to the current bindings module, nothing is written in a check
+    fn write_mod_line(&mut self, line: impl Display) -> std::io::Result<()> {
+        match &mut self.bindings_mod_file {
+            Some(bindings_mod_file) => writeln!(bindings_mod_file, "{line}"),
+            None => Ok(()),
+        }
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum WgpuBindingsError {
     IoErr(#[from] std::io::Error),
+    /// The generated bindings differ from the files on disk in a [verify](`crate::is_verify`), with a diff of each file
+    Drift {
+        files: Vec<(PathBuf, String)>,
+    },
+    /// The module name of the dir or shader `name` is not a Rust identifier, e.g. it starts with a digit
+    InvalidModName {
+        name: String,
+        mod_name: String,
+    },
+    /// Two dirs or shaders have the same module name in `mod_file`, e.g. `post-fx` and `post_fx` in snake case
+    ModNameCollision {
+        mod_name: String,
+        names: [String; 2],
+        mod_file: PathBuf,
+    },
+    /// A render state sidecar is invalid, or doesn't match its shader, see [`WgpuBindingsExtension::with_pipeline_state`]
+    PipelineStateErr {
+        path: PathBuf,
+        message: String,
+    },
+    /// A generated file could not be parsed to be formatted, see [`WgpuBindingsExtension::format_output`]
+    FormatErr {
+        inner: syn::Error,
+        path: PathBuf,
+    },
     /// spans and paths are that of the compiled files
     CreateBindingsModuleErr {
         inner: wgsl_to_wgpu::CreateModuleError,
         wgsl_source: String,
         path: PathBuf,
     },
+    /// spans and paths are that of the compiled files
+    ParseErr {
+        inner: naga::front::wgsl::ParseError,
+        wgsl_source: String,
+        path: PathBuf,
+        /// the error rendered with the source files, with the `rich_diagnostics` feature
+        rendered: Option<String>,
+    },
+    /// spans and paths are that of the compiled files
+    ValidationErr {
+        inner: naga::WithSpan<naga::valid::ValidationError>,
+        wgsl_source: String,
+        path: PathBuf,
+        /// the error rendered with the source files, with the `rich_diagnostics` feature
+        rendered: Option<String>,
+    },
+}
+
+impl WgpuBindingsError {
+    /// Render naga errors pointing to the declarations in the source files they were built from
+    #[cfg(feature = "rich_diagnostics")]
+    fn with_source_map(
+        mut self,
+        source_map: Option<&BasicSourceMap>,
+        mod_path: &ModulePath,
+        shader_root_path: &Path,
+    ) -> Self {
+        use crate::diagnostics::ShaderDiagnostic;
+
+        match &mut self {
+            WgpuBindingsError::ParseErr { inner, wgsl_source, path, rendered } => {
+                *rendered = Some(ShaderDiagnostic::new(inner.message(), &*path, wgsl_source.as_str())
+                    .with_naga_labels(inner.labels())
+                    .with_source_map(source_map, mod_path, shader_root_path)
+                    .render());
+            }
+            WgpuBindingsError::ValidationErr { inner, wgsl_source, path, rendered } => {
+                *rendered = Some(ShaderDiagnostic::new(inner.as_inner().to_string(), &*path, wgsl_source.as_str())
+                    .with_naga_labels(inner.spans().map(|(span, label)| (*span, label.as_str())))
+                    .with_source_map(source_map, mod_path, shader_root_path)
+                    .render());
+            }
+            _ => (),
+        }
+
+        self
+    }
 }
 
 impl Display for WgpuBindingsError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             WgpuBindingsError::IoErr(io_err) => io_err.fmt(f),
+            WgpuBindingsError::InvalidModName { name, mod_name } =>
+                write!(f, "`{name}` can't be used as a bindings module, `{mod_name}` is not a Rust identifier"),
+            WgpuBindingsError::ModNameCollision { mod_name, names: [first, second], mod_file } => write!(
+                f,
+                "`{first}` and `{second}` are both the bindings module `{mod_name}` in `{}`, rename one of them",
+                mod_file.display(),
+            ),
+            WgpuBindingsError::PipelineStateErr { path, message } => write!(f, "{}: {message}", path.display()),
+            WgpuBindingsError::FormatErr { inner, path } =>
+                write!(f, "failed to format the generated `{}`: {inner}", path.display()),
+            WgpuBindingsError::Drift { files } => {
+                writeln!(f, "the bindings are out of date, build without `WESL_BUILD_VERIFY` to regenerate them")?;
+                for (path, diff) in files {
+                    write!(f, "--- {}\n{diff}", path.display())?;
+                }
+                Ok(())
+            }
             // use `emit_to_string_with_path` for output with span and labels
-            // todo use source map to modify span and path
             WgpuBindingsError::CreateBindingsModuleErr { inner, wgsl_source, path } =>
                 inner.emit_to_string_with_path(wgsl_source, path).fmt(f),
+            WgpuBindingsError::ParseErr { rendered: Some(rendered), .. }
+            | WgpuBindingsError::ValidationErr { rendered: Some(rendered), .. } => f.write_str(rendered),
+            WgpuBindingsError::ParseErr { inner, wgsl_source, path, .. } =>
+                inner.emit_to_string_with_path(wgsl_source, path).fmt(f),
+            WgpuBindingsError::ValidationErr { inner, wgsl_source, path, .. } =>
+                write!(f, "{}: {}", path.display(), inner.emit_to_string(wgsl_source)),
         }
     }
 }
@@ -66,14 +649,89 @@ impl<WeslResolver: wesl::Resolver> WeslBuildExtension<WeslResolver> for WgpuBind
         "WgpuBindingsExtension".into()
     }
 
+    fn reads_built_wgsl(&self) -> bool {
+        true
+    }
+
     fn init_root(
         &mut self,
-        _shader_path: &str,
-        res: &mut wesl::Wesl<WeslResolver>,
+        shader_path: &str,
+        _res: &mut wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.shader_root_path = PathBuf::from(shader_path);
+        crate::with_build_context(|ctx| ctx.declare_artifact_root(&self.binding_root_path));
+        // released first, in case the last build failed before `exit_root`
+        self.dir_lock = None;
+        if !crate::is_check() && !crate::is_verify() {
+            self.dir_lock = Some(DirLock::acquire(&dir_lock::sibling_lock_path(&self.binding_root_path))?);
+        }
+        self.output_root = if crate::is_verify() {
+            verify_scratch_dir()
+        } else {
+            staging_dir(&self.binding_root_path)
+        };
+        // left over by a build that failed or was interrupted
+        if !crate::is_check() && self.output_root.exists() {
+            fs::remove_dir_all(&self.output_root)?;
+        }
+        self.bindings_mod_path = self.output_root.join("mod.rs");
+        self.bindings_mod_file = if crate::is_check() {
+            None
+        } else {
+            fs::create_dir_all(&self.output_root)?;
+            Some(BufWriter::new(fs::File::create(&self.bindings_mod_path)?))
+        };
+        self.written_files = self.bindings_mod_file.iter().map(|_| self.bindings_mod_path.clone()).collect();
+        self.write_mod_line("#![allow(unused)]\n")?;
+
+        if self.options.entry_points {
+            self.write_mod_line(ENTRY_POINT_TYPE)?;
+        }
+        if self.options.pipeline_helpers {
+            self.write_mod_line(RENDER_PIPELINE_OPTIONS_TYPE)?;
+        }
+        if self.options.shared_layouts {
+            self.write_mod_line(format_args!("{}mod common_layouts;", self.options.mod_visibility))?;
+        }
+        self.shader_layouts.clear();
+        self.mod_names = ModNames::default();
+        self.pipeline_states.clear();
+        if self.options.shared_layouts {
+            self.mod_names.claim(&self.bindings_mod_path, "common_layouts", "the shared layouts")?;
+        }
+
+        Ok(())
+    }
+
+    fn exit_root(
+        &mut self,
+        _shader_root_path: &str,
+        _res: &wesl::Wesl<WeslResolver>,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        res.set_mangler(wesl::ManglerKind::Escape);
+        if crate::is_check() {
+            return Ok(());
+        }
+        if self.options.shared_layouts {
+            self.written_files.push(write_shared_layouts(&self.output_root, &self.shader_layouts)?);
+        }
+
+        // flush the root module
+        self.bindings_mod_file = None;
+        for path in &self.written_files {
+            finish_file(path, &self.options)?;
+        }
 
-        writeln!(self.bindings_mod_file, "#![allow(unused)]\n")?;
+        if crate::is_verify() {
+            let drifted = drifted_files(&self.output_root, &self.binding_root_path);
+            fs::remove_dir_all(&self.output_root)?;
+            let drifted = drifted?;
+            if !drifted.is_empty() {
+                return Err(WgpuBindingsError::Drift { files: drifted }.into());
+            }
+        } else {
+            commit_staged(&self.output_root, &self.binding_root_path, &self.written_files)?;
+            self.dir_lock = None;
+        }
 
         Ok(())
     }
@@ -81,14 +739,22 @@ impl<WeslResolver: wesl::Resolver> WeslBuildExtension<WeslResolver> for WgpuBind
     fn enter_mod(&mut self, dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
         let dir_name = dir_path.file_stem().expect("module must have a name in path")
             .to_str().expect("mod path must be valid UTF-8");
-        writeln!(self.bindings_mod_file, "pub(crate) mod {dir_name};")?;
+        // the module name of the dir in the module paths of its shaders
+        let file_names = crate::with_build_context(|ctx| ctx.config().file_names).unwrap_or_default();
+        let dir_name = &file_names.module_name(dir_name, dir_path)?;
+        let mod_name = self.options.mod_name(dir_name)?;
+        self.mod_names.claim(&self.bindings_mod_path, &mod_name.ident, dir_name)?;
+        self.write_mod_line(format_args!("{}mod {};", self.options.mod_visibility, mod_name.ident))?;
 
         // remove last mod
         self.bindings_mod_path.pop();
-        // move into {dir_name}/"mod.rs
-        self.bindings_mod_path.push(dir_name);
+        // move into {mod_name}/"mod.rs
+        self.bindings_mod_path.push(&mod_name.file);
         self.bindings_mod_path.push("mod.rs");
 
+        if crate::is_check() {
+            return Ok(());
+        }
         if let Some(dir_to_mod) = self.bindings_mod_path.parent() {
             fs::create_dir_all(dir_to_mod)?;
         }
@@ -96,7 +762,8 @@ impl<WeslResolver: wesl::Resolver> WeslBuildExtension<WeslResolver> for WgpuBind
         log::trace!("creating wgpu binding module for: {}", self.bindings_mod_path.display());
 
         let sub_bindings_mod_file = BufWriter::new(fs::File::create(&self.bindings_mod_path)?);
-        self.bindings_mod_file = sub_bindings_mod_file;
+        self.bindings_mod_file = Some(sub_bindings_mod_file);
+        self.written_files.push(self.bindings_mod_path.clone());
 
         Ok(())
     }
@@ -112,61 +779,259 @@ impl<WeslResolver: wesl::Resolver> WeslBuildExtension<WeslResolver> for WgpuBind
         self.bindings_mod_path.pop();
         self.bindings_mod_path.push("mod.rs");
 
-        self.bindings_mod_file = BufWriter::new(fs::File::open(&self.bindings_mod_path)?);
+        if !crate::is_check() {
+            self.bindings_mod_file = Some(BufWriter::new(fs::OpenOptions::new().append(true).open(&self.bindings_mod_path)?));
+        }
+
+        Ok(())
+    }
+
+    fn on_asset(&mut self, asset_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let shader_name = asset_path.file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_suffix(PIPELINE_STATE_SUFFIX));
+        let Some(shader_name) = shader_name.filter(|_| self.options.pipeline_state) else {
+            return Ok(());
+        };
 
+        let state = PipelineState::parse(&fs::read_to_string(asset_path)?)
+            .map_err(|message| WgpuBindingsError::PipelineStateErr { path: asset_path.to_owned(), message })?;
+        self.pipeline_states.insert(asset_path.with_file_name(shader_name), (asset_path.to_owned(), state));
         Ok(())
     }
 
+    fn on_error(&mut self, mod_path: &ModulePath, _error: &crate::WeslBuildError) {
+        if !self.options.stubs || self.bindings_mod_file.is_none() {
+            return;
+        }
+
+        let mut write_stub = || -> Result<(), WgpuBindingsError> {
+            let names = mod_path.components.iter()
+                .map(|component| self.options.mod_name(component))
+                .collect::<Result<Vec<_>, _>>()?;
+            let stub_path = names.iter()
+                .fold(self.output_root.clone(), |path, name| path.join(&name.file))
+                .with_extension("rs");
+            // the bindings were written, another extension failed after this one
+            if self.written_files.contains(&stub_path) {
+                return Ok(());
+            }
+
+            let stub_mod = names.last().expect("a module path has a name");
+            let shader_name = mod_path.components.last().expect("a module path has a name");
+            // the failed build may have claimed the name already
+            let mod_file = stub_path.with_file_name("mod.rs");
+            if !self.mod_names.is_claimed_by(&mod_file, &stub_mod.ident, shader_name) {
+                self.mod_names.claim(&mod_file, &stub_mod.ident, shader_name)?;
+            }
+            fs::write(&stub_path, stub(mod_path))?;
+            self.write_mod_line(format_args!("{}mod {};", self.options.mod_visibility, stub_mod.ident))?;
+            self.written_files.push(stub_path);
+            Ok(())
+        };
+        #[cfg_attr(not(feature = "logging"), allow(unused_variables))]
+        if let Err(error) = write_stub() {
+            #[cfg(feature = "logging")]
+            log::warn!("failed to write the bindings stub of `{mod_path}`: {error}");
+        }
+    }
+
     fn post_build(
         &mut self,
         mod_path: &ModulePath,
         wgsl_source_path: &str,
-        _source_map: &Option<BasicSourceMap>,
+        source_map: &Option<BasicSourceMap>,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        generate_bindings(
-            self.binding_root_path,
-            &mut self.bindings_mod_file,
+        let shader_path = mod_path.components.iter().fold(self.shader_root_path.clone(), |path, component| path.join(component));
+        let binding_path = generate_bindings(
+            &self.output_root,
+            self.bindings_mod_file.as_mut(),
             mod_path,
             wgsl_source_path,
+            &self.options,
+            &mut self.shader_layouts,
+            &mut self.mod_names,
+            self.pipeline_states.get(&shader_path),
         )
-        // todo don't double box
-        .map_err(Box::<_>::from)
+        .map_err(|error| {
+            #[cfg(feature = "rich_diagnostics")]
+            let error = Box::new(error.with_source_map(source_map.as_ref(), mod_path, &self.shader_root_path));
+            #[cfg(not(feature = "rich_diagnostics"))]
+            let _ = source_map;
+
+            // todo don't double box
+            Box::<_>::from(error)
+        })?;
+
+        #[cfg(feature = "hot_reload")]
+        if self.options.hot_reload
+            && let Some(binding_path) = &binding_path
+        {
+            let mut binding_file = fs::OpenOptions::new().append(true).open(binding_path)?;
+            binding_file.write_all(hot_reload_fns(&self.shader_root_path, mod_path)?.as_bytes())?;
+        }
+        self.written_files.extend(binding_path);
+
+        Ok(())
     }
 }
 
+/// The `SHADER_FILE` const and `load_latest` functions of the shader `mod_path`, see [`WgpuBindingsExtension::with_hot_reload`]
+#[cfg(feature = "hot_reload")]
+fn hot_reload_fns(shader_root_path: &Path, mod_path: &ModulePath) -> std::io::Result<String> {
+    // the bindings are compiled in another dir
+    let shader_root = fs::canonicalize(shader_root_path)?;
+    let shader_file = mod_path.components.iter().fold(shader_root.clone(), |path, component| path.join(component));
+    let shader_file = match shader_file.with_extension("wesl").is_file() {
+        true => shader_file.with_extension("wesl"),
+        false => shader_file.with_extension("wgsl"),
+    };
+
+    Ok(format!(
+        "\n/// The shader file these bindings were generated from, e.g. to watch for changes\n\
+        pub const SHADER_FILE: &str = {shader_file:?};\n\
+        \n\
+        /// Compile the current source of the shader, to reload it while the app runs, release builds return the embedded `SOURCE`\n\
+        #[cfg(debug_assertions)]\n\
+        pub fn load_latest() -> Result<String, wesl::Error> {{\n    \
+            let compiler = wesl::Wesl::new({shader_root:?});\n    \
+            let mod_path = wesl::ModulePath::new(wesl::syntax::PathOrigin::Absolute, vec![{components}]);\n    \
+            compiler.compile(&mod_path).map(|wgsl| wgsl.to_string())\n\
+        }}\n\
+        \n\
+        /// The embedded `SOURCE`, debug builds compile the current source of the shader\n\
+        #[cfg(not(debug_assertions))]\n\
+        pub fn load_latest() -> Result<String, wesl::Error> {{\n    \
+            Ok(SOURCE.to_owned())\n\
+        }}\n",
+        shader_file = shader_file.display().to_string(),
+        shader_root = shader_root.display().to_string(),
+        components = mod_path.components.iter().map(|component| format!("{component:?}.to_owned()")).collect::<Vec<_>>().join(", "),
+    ))
+}
+
+#[allow(clippy::too_many_arguments)]
 fn generate_bindings(
-    binding_root_path: &str,
-    bindings_mod_file: &mut impl Write,
+    binding_root_path: &Path,
+    bindings_mod_file: Option<&mut impl Write>,
     mod_path: &ModulePath,
     wgsl_source_path: &str,
-) -> Result<(), Box<WgpuBindingsError>> {
-    use wgsl_to_wgpu::MatrixVectorTypes;
-
-    let wgsl_source = fs::read_to_string(wgsl_source_path)
+    bindings_options: &BindingsOptions,
+    shader_layouts: &mut Vec<ShaderLayouts>,
+    mod_names: &mut ModNames,
+    pipeline_state: Option<&(PathBuf, PipelineState)>,
+) -> Result<Option<PathBuf>, Box<WgpuBindingsError>> {
+    let wgsl_source = artifacts::read_to_string(wgsl_source_path)
         .map_err(|e| Box::new(WgpuBindingsError::IoErr(e)))?;
 
     // Configure the output based on the dependencies for the project
     let options = WriteOptions {
         derive_bytemuck_vertex: true,
-        derive_encase_host_shareable: true,
-        matrix_vector_types: MatrixVectorTypes::Nalgebra,
+        // the layout of `encase` structs is only the WGSL layout once written to a buffer
+        derive_bytemuck_host_shareable: bindings_options.layout_assertions,
+        derive_encase_host_shareable: !bindings_options.layout_assertions,
+        derive_serde: bindings_options.derive_serde,
+        matrix_vector_types: bindings_options.matrix_vector_types.unwrap_or(MatrixVectorTypes::Nalgebra),
         ..Default::default()
     };
 
+    let names = mod_path.components.iter()
+        .map(|component| bindings_options.mod_name(component))
+        .collect::<Result<Vec<_>, _>>()?;
+    let binding_path = names.iter()
+        .fold(binding_root_path.to_owned(), |path, name| path.join(&name.file))
+        .with_extension("rs");
+    let binding_mod = names.last().expect("a module path has a name");
+    let shader_name = mod_path.components.last().expect("a module path has a name");
+    mod_names.claim(&binding_path.with_file_name("mod.rs"), &binding_mod.ident, shader_name)?;
+
     // Generate the bindings
-    let text = create_shader_module(
+    let mut text = create_shader_module(
+        mod_path,
         &wgsl_source,
         wgsl_source_path,
         options,
     )?;
+    label_shader_module(&mut text, mod_path);
+    if bindings_options.unchecked_shader_module {
+        text.push_str(&unchecked_shader_module(mod_path));
+    }
 
-    let binding_path = format!(
-        "{}/{}.rs",
-        binding_root_path.to_owned(),
-        mod_path.components.join("/")
-    );
-    let binding_path = PathBuf::from(binding_path);
+    if bindings_options.needs_naga_module() || pipeline_state.is_some() {
+        let module = naga::front::wgsl::parse_str(&wgsl_source)
+            .map_err(|e| Box::new(WgpuBindingsError::ParseErr {
+                inner: e,
+                wgsl_source: wgsl_source.clone(),
+                path: PathBuf::from(wgsl_source_path),
+                rendered: None,
+            }))?;
+        // the binding module is this many modules below the root bindings module
+        let mod_depth = mod_path.components.len();
+
+        if bindings_options.entry_points {
+            text.push_str(&entry_points_table(&module, mod_depth));
+        }
+
+        if bindings_options.pipeline_constants {
+            text.push_str(&pipeline_constants(&module, mod_path));
+        }
+
+        if bindings_options.layout_assertions {
+            text.push_str(&layout_assertions(&module, mod_path, wgsl_source_path));
+        }
+
+        if bindings_options.binding_consts {
+            text.push_str(&binding_consts(&module, mod_path, wgsl_source_path));
+        }
+
+        if bindings_options.test_harness {
+            text.push_str(&test_harness(&module, mod_path));
+        }
+
+        if bindings_options.workgroup_sizes {
+            text.push_str(&workgroup_sizes(&module));
+        }
+
+        if bindings_options.vertex_layouts || bindings_options.pipeline_helpers || pipeline_state.is_some() {
+            text.push_str(&vertex_buffers_consts(&module, bindings_options));
+        }
+
+        if bindings_options.pipeline_helpers {
+            text.push_str(&pipeline_helpers(&module, mod_path, mod_depth));
+        }
 
+        if let Some((state_path, state)) = pipeline_state {
+            let sidecar = state_path.file_name().unwrap_or_default().to_string_lossy();
+            let descriptor = state.to_rust(&module, &mod_path.components.join("::"), &sidecar)
+                .map_err(|message| Box::new(WgpuBindingsError::PipelineStateErr { path: state_path.clone(), message }))?;
+            text.push_str(&descriptor);
+        }
+
+        if bindings_options.shared_layouts {
+            let info = naga::valid::Validator::new(
+                naga::valid::ValidationFlags::all(),
+                naga::valid::Capabilities::all(),
+            )
+            .validate(&module)
+            .map_err(|e| Box::new(WgpuBindingsError::ValidationErr {
+                inner: e,
+                wgsl_source: wgsl_source.clone(),
+                path: PathBuf::from(wgsl_source_path),
+                rendered: None,
+            }))?;
+
+            shader_layouts.push(ShaderLayouts {
+                mod_path: mod_path.clone(),
+                binding_path: binding_path.clone(),
+                layouts: bind_group_layouts(&module, &info),
+            });
+        }
+    }
+
+    // in a check the bindings are only generated to validate them
+    let Some(bindings_mod_file) = bindings_mod_file else {
+        return Ok(None);
+    };
     fs::create_dir_all(
         binding_path.parent().expect("binding must have a parent mod or be in root")
     ).map_err(|e| Box::from(WgpuBindingsError::IoErr(e)))?;
@@ -176,27 +1041,715 @@ fn generate_bindings(
     // Add entry to `mod.rs`
     writeln!(
         bindings_mod_file,
-        "pub(crate) mod {};",
-        binding_path.file_stem().expect("binding must have a name in path")
-            .to_str().expect("mod path must be valid UTF-8")
+        "{}mod {};",
+        bindings_options.mod_visibility,
+        binding_mod.ident,
     ).map_err(|e| Box::from(WgpuBindingsError::IoErr(e)))?;
 
+    Ok(Some(binding_path))
+}
+
+/// The bindings module of a shader that was not built, see [`WgpuBindingsExtension::generate_stubs`]
+fn stub(mod_path: &ModulePath) -> String {
+    format!(
+        "//! A stub, `{}` has not been built yet, the next build that builds it replaces this\n\
+        \n\
+        pub const SOURCE: &str = \"\";\n",
+        mod_path.components.join("::"),
+    )
+}
+
+/// The first line of every generated file
+const GENERATED_HEADER: &str = "// @generated by wesl_build, do not edit";
+
+/// Format the generated file `path` if enabled and put the header on top
+fn finish_file(path: &Path, options: &BindingsOptions) -> Result<(), WgpuBindingsError> {
+    let mut text = fs::read_to_string(path)?;
+    if options.format_output {
+        let file = syn::parse_file(&text)
+            .map_err(|inner| WgpuBindingsError::FormatErr { inner, path: path.to_owned() })?;
+        // comments are not kept, so the header is added after
+        text = prettyplease::unparse(&file);
+    }
+
+    let mut finished = String::with_capacity(text.len());
+    for line in options.header.iter().flat_map(|header| header.lines()) {
+        let line = match line.trim_start().starts_with("//") {
+            true => line.to_owned(),
+            false => format!("// {line}"),
+        };
+        writeln!(finished, "{}", line.trim_end()).expect("writing to a String can't fail");
+    }
+    writeln!(finished, "{GENERATED_HEADER}").expect("writing to a String can't fail");
+    finished.push_str(&text);
+
+    artifacts::write_atomic(path, finished.as_bytes())?;
     Ok(())
 }
 
+/// Write every bind group layout used more than once to `common_layouts.rs`,
+/// and re-export them in the `shared_layouts` module of the shaders using them
+fn write_shared_layouts(
+    binding_root_path: &Path,
+    shader_layouts: &[ShaderLayouts],
+) -> Result<PathBuf, WgpuBindingsError> {
+    // every layout with the shaders and groups using it, in order of first use
+    let mut layout_uses: Vec<(&[LayoutEntry], Vec<(&ShaderLayouts, u32)>)> = Vec::new();
+    for shader in shader_layouts {
+        for (group, entries) in &shader.layouts {
+            match layout_uses.iter_mut().find(|(layout, _)| *layout == entries.as_slice()) {
+                Some((_, uses)) => uses.push((shader, *group)),
+                None => layout_uses.push((entries.as_slice(), vec![(shader, *group)])),
+            }
+        }
+    }
+
+    let mut common_layouts = String::new();
+    // the re-exports for each shaders `shared_layouts` module
+    let mut shader_reexports = BTreeMap::<&Path, Vec<String>>::new();
+
+    let shared_layouts = layout_uses.iter().filter(|(_, uses)| uses.len() > 1);
+    for (layout_idx, (entries, uses)) in shared_layouts.enumerate() {
+        let layout_name = format!("SharedLayout{layout_idx}");
+
+        writeln!(common_layouts).expect("writing to a String can't fail");
+        for (shader, group) in uses {
+            writeln!(common_layouts, "/// Used by `{}` group {group}", shader.mod_path)
+                .expect("writing to a String can't fail");
+
+            shader_reexports.entry(shader.binding_path.as_path()).or_default().push(format!(
+                "    pub use {}common_layouts::{layout_name} as Group{group};",
+                // the bindings module and `shared_layouts`
+                "super::".repeat(shader.mod_path.components.len() + 1),
+            ));
+        }
+        writeln!(
+            common_layouts,
+            "pub struct {layout_name};\n\
+            \n\
+            impl {layout_name} {{\n    \
+                pub const ENTRIES: &'static [wgpu::BindGroupLayoutEntry] = &[\n{}    ];\n\
+                \n    \
+                pub fn get_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {{\n        \
+                    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {{\n            \
+                        label: Some({layout_name:?}),\n            \
+                        entries: Self::ENTRIES,\n        \
+                    }})\n    \
+                }}\n\
+            }}",
+            entries.iter().map(|entry| format!("        {},\n", entry.to_rust())).collect::<String>(),
+        ).expect("writing to a String can't fail");
+    }
+
+    let common_layouts_path = binding_root_path.join("common_layouts.rs");
+    fs::write(&common_layouts_path, common_layouts)?;
+
+    for (binding_path, reexports) in shader_reexports {
+        let mut binding_file = fs::OpenOptions::new().append(true).open(binding_path)?;
+        writeln!(
+            binding_file,
+            "\n/// Bind group layouts shared with other shaders, see `common_layouts`\npub mod shared_layouts {{\n{}\n}}",
+            reexports.join("\n"),
+        )?;
+    }
+
+    Ok(common_layouts_path)
+}
+
+/// The dir the bindings of `binding_root` are generated in, next to it so they can be renamed into it
+fn staging_dir(binding_root: &Path) -> PathBuf {
+    let name = binding_root.file_name().unwrap_or_default().to_string_lossy();
+    binding_root.with_file_name(format!(".{name}.wesl_build_staging"))
+}
+
+/// Move the `written` files from the staging dir into `binding_root`, each is renamed atomically,
+/// so a build that fails before this leaves the previous bindings as they were
+///
+/// Files identical to the ones in `binding_root` are not moved, so their mtime doesn't trigger a rebuild of the crate.
+fn commit_staged(staging: &Path, binding_root: &Path, written: &[PathBuf]) -> std::io::Result<()> {
+    for path in written {
+        let target = binding_root.join(path.strip_prefix(staging).expect("the file is in the staging dir"));
+        let unchanged = fs::read(&target).is_ok_and(|old| fs::read(path).is_ok_and(|new| old == new));
+        if !unchanged {
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::rename(path, &target)?;
+        }
+        artifacts::record_write(!unchanged);
+    }
+    fs::remove_dir_all(staging)
+}
+
+/// A new dir to generate the bindings in when verifying them, unique to this extension
+fn verify_scratch_dir() -> PathBuf {
+    static NEXT_DIR: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+    let idx = NEXT_DIR.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    std::env::temp_dir().join(format!("wesl_build_verify_{}_{idx}", std::process::id()))
+}
+
+/// Every file generated in `generated` that differs from the one at the same path in `on_disk`, with a diff of each
+fn drifted_files(generated: &Path, on_disk: &Path) -> std::io::Result<Vec<(PathBuf, String)>> {
+    let mut drifted = Vec::new();
+    let mut dirs = vec![generated.to_owned()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+
+            let disk_path = on_disk.join(path.strip_prefix(generated).expect("the file is in the generated dir"));
+            let new = fs::read_to_string(&path)?;
+            let old = match fs::read_to_string(&disk_path) {
+                Ok(old) => old,
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => String::new(),
+                Err(error) => return Err(error),
+            };
+            if old != new {
+                drifted.push((disk_path, line_diff(&old, &new)));
+            }
+        }
+    }
+    drifted.sort();
+
+    Ok(drifted)
+}
+
+/// The lines removed from `old` as `-` and added in `new` as `+`, each run of changes after the line it starts at
+fn line_diff(old: &str, new: &str) -> String {
+    let (old, new) = (old.lines().collect::<Vec<_>>(), new.lines().collect::<Vec<_>>());
+
+    // the length of the longest common subsequence of `old[i..]` and `new[j..]`
+    let mut lcs = vec![vec![0_usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+
+    let mut diff = String::new();
+    let (mut i, mut j, mut in_change) = (0, 0, false);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            (i, j, in_change) = (i + 1, j + 1, false);
+            continue;
+        }
+        if !in_change {
+            writeln!(diff, "@@ line {} @@", i + 1).expect("writing to a String can't fail");
+            in_change = true;
+        }
+        if j == new.len() || (i < old.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            writeln!(diff, "-{}", old[i]).expect("writing to a String can't fail");
+            i += 1;
+        } else {
+            writeln!(diff, "+{}", new[j]).expect("writing to a String can't fail");
+            j += 1;
+        }
+    }
+
+    diff
+}
+
+/// The type of [`entry_points_table`]s entries, written in the root bindings module
+const ENTRY_POINT_TYPE: &str = "/// An entry point of a shader
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EntryPoint {
+    pub name: &'static str,
+    pub stage: wgpu::ShaderStages,
+    /// `[0, 0, 0]` for non compute entry points
+    pub workgroup_size: [u32; 3],
+}
+";
+
+/// An `ENTRY_POINTS` table with every entry point in `module`
+fn entry_points_table(module: &naga::Module, mod_depth: usize) -> String {
+    let entry_point_type = format!("{}EntryPoint", "super::".repeat(mod_depth));
+
+    let mut table = format!("\npub const ENTRY_POINTS: &[{entry_point_type}] = &[\n");
+    for entry_point in &module.entry_points {
+        writeln!(
+            table,
+            "    {entry_point_type} {{ name: {:?}, stage: {}, workgroup_size: {:?} }},",
+            entry_point.name,
+            shader_stage(entry_point.stage),
+            entry_point.workgroup_size,
+        ).expect("writing to a String can't fail");
+    }
+    table.push_str("];\n");
+
+    table
+}
+
+/// A `pipeline_constants` module with the immediates and overrides of `module`,
+/// see [`WgpuBindingsExtension::with_pipeline_constants`]
+fn pipeline_constants(module: &naga::Module, mod_path: &ModulePath) -> String {
+    use naga::{Scalar, TypeInner};
+
+    let mut constants = String::from("\n/// The immediates and overrides of the shader\npub mod pipeline_constants {\n");
+
+    let immediates = module.global_variables.iter()
+        .find(|(_, global)| global.space == naga::AddressSpace::Immediate)
+        .map(|(_, global)| global.ty);
+    if let Some(ty) = immediates {
+        writeln!(
+            constants,
+            "    /// The size of the immediates in bytes, the `immediate_size` of the pipeline layout\n    \
+            pub const IMMEDIATE_SIZE: u32 = {};",
+            module.types[ty].inner.size(module.to_ctx()),
+        ).expect("writing to a String can't fail");
+        // only structs get a Rust type
+        if let (TypeInner::Struct { .. }, Some(name)) = (&module.types[ty].inner, module.types[ty].name.as_deref()) {
+            let path = demangle_wesl(name, mod_path);
+            let rust_path = path.parent.components.iter().chain([&path.name]).cloned().collect::<Vec<_>>().join("::");
+            writeln!(constants, "    /// The type of the immediates\n    pub type Immediates = super::{rust_path};")
+                .expect("writing to a String can't fail");
+        }
+    }
+
+    // `(field, key, rust type, to f64)`
+    let overrides = module.overrides.iter()
+        .filter_map(|(_, override_)| {
+            let name = override_.name.as_deref()?;
+            let (rust_type, to_f64) = match module.types[override_.ty].inner {
+                TypeInner::Scalar(Scalar::BOOL) => ("bool", "f64::from(u8::from(value))"),
+                TypeInner::Scalar(Scalar::I32) => ("i32", "f64::from(value)"),
+                TypeInner::Scalar(Scalar::U32) => ("u32", "f64::from(value)"),
+                // `f16` overrides are set from `f32`s too
+                TypeInner::Scalar(Scalar::F32 | Scalar::F16) => ("f32", "f64::from(value)"),
+                _ => return None,
+            };
+            let key = override_.id.map_or_else(|| name.to_owned(), |id| id.to_string());
+            let field = snake_case(&demangle_wesl(name, mod_path).name);
+            Some((field, key, rust_type, to_f64))
+        })
+        .collect::<Vec<_>>();
+
+    constants.push_str(
+        "\n    /// The overrides of the shader, `None` keeps the default of the shader\n    \
+        #[derive(Debug, Clone, Copy, Default, PartialEq)]\n    \
+        pub struct OverrideConstants {\n",
+    );
+    for (field, key, rust_type, _) in &overrides {
+        writeln!(constants, "        /// `{key}`\n        pub {field}: Option<{rust_type}>,").expect("writing to a String can't fail");
+    }
+    constants.push_str("    }\n\n    impl OverrideConstants {\n");
+    for (field, _, rust_type, _) in &overrides {
+        writeln!(
+            constants,
+            "        pub fn set_{field}(&mut self, value: {rust_type}) -> &mut Self {{\n            \
+                self.{field} = Some(value);\n            \
+                self\n        \
+            }}\n",
+        ).expect("writing to a String can't fail");
+    }
+    constants.push_str(
+        "        /// The overrides that are set, for `wgpu::PipelineCompilationOptions::constants`\n        \
+        pub fn constants(&self) -> Vec<(&'static str, f64)> {\n            \
+            let mut constants = Vec::new();\n",
+    );
+    for (field, key, _, to_f64) in &overrides {
+        writeln!(
+            constants,
+            "            if let Some(value) = self.{field} {{\n                \
+                constants.push(({key:?}, {to_f64}));\n            \
+            }}",
+        ).expect("writing to a String can't fail");
+    }
+    constants.push_str("            constants\n        }\n    }\n}\n");
+
+    constants
+}
+
+/// The name, members and size of every named struct in a uniform or storage buffer of `module`, including the structs
+/// in those structs, except the ones ending in a runtime sized array
+fn buffer_structs(module: &naga::Module) -> Vec<(&str, &[naga::StructMember], u32)> {
+    use naga::{AddressSpace, TypeInner};
+
+    // the structs in buffers and the structs and arrays of structs in them
+    let mut pending = module.global_variables.iter()
+        .filter(|(_, global)| matches!(global.space, AddressSpace::Uniform | AddressSpace::Storage { .. }))
+        .map(|(_, global)| global.ty)
+        .collect::<Vec<_>>();
+    let mut structs = BTreeMap::new();
+    while let Some(ty) = pending.pop() {
+        match &module.types[ty].inner {
+            TypeInner::Struct { members, span } => {
+                pending.extend(members.iter().map(|member| member.ty));
+                structs.insert(ty.index(), (ty, members, *span));
+            }
+            TypeInner::Array { base, .. } => pending.push(*base),
+            _ => (),
+        }
+    }
+
+    structs.into_values()
+        .filter(|(_, members, _)| !members.last().is_some_and(|member| matches!(
+            module.types[member.ty].inner,
+            TypeInner::Array { size: naga::ArraySize::Dynamic, .. },
+        )))
+        .filter_map(|(ty, members, span)| Some((module.types[ty].name.as_deref()?, members.as_slice(), span)))
+        .collect()
+}
+
+/// `size_of` and `offset_of!` assertions for every struct in a uniform or storage buffer of `module`,
+/// see [`WgpuBindingsExtension::with_layout_assertions`]
+fn layout_assertions(module: &naga::Module, mod_path: &ModulePath, wgsl_source_path: &str) -> String {
+    #[cfg(feature = "wgsl_minifier_ext")]
+    let name_map = super::wgsl_minifier::NameMap::read_for(wgsl_source_path).ok().flatten();
+    #[cfg(not(feature = "wgsl_minifier_ext"))]
+    let _ = wgsl_source_path;
+
+    let mut assertions = String::new();
+    for (name, members, span) in buffer_structs(module) {
+        #[cfg(feature = "wgsl_minifier_ext")]
+        let name = name_map.as_ref().and_then(|name_map| name_map.original(name)).unwrap_or(name);
+        let path = demangle_wesl(name, mod_path);
+        let rust_path = path.parent.components.iter().chain([&path.name]).cloned().collect::<Vec<_>>().join("::");
+
+        writeln!(
+            assertions,
+            "\nconst _: () = assert!(std::mem::size_of::<{rust_path}>() == {span}, \"the size of `{rust_path}` is not its WGSL size\");",
+        ).expect("writing to a String can't fail");
+        for member in members {
+            let Some(field) = member.name.as_deref() else { continue };
+            writeln!(
+                assertions,
+                "const _: () = assert!(std::mem::offset_of!({rust_path}, {field}) == {}, \"the offset of `{rust_path}::{field}` is not its WGSL offset\");",
+                member.offset,
+            ).expect("writing to a String can't fail");
+        }
+    }
+
+    assertions
+}
+
+/// A `#[cfg(test)]` module checking the embedded `SOURCE` against what the bindings of `module` were generated from,
+/// see [`WgpuBindingsExtension::with_test_harness`]
+fn test_harness(module: &naga::Module, mod_path: &ModulePath) -> String {
+    let shader = mod_path.components.join("::");
+    let mut harness = format!(
+        "\n#[cfg(test)]\n\
+        mod wesl_build_tests {{\n    \
+            use super::*;\n\
+            \n    \
+            fn module() -> naga::Module {{\n        \
+                naga::front::wgsl::parse_str(SOURCE).expect(\"the shader `{shader}` doesn't parse\")\n    \
+            }}\n\
+            \n    \
+            #[test]\n    \
+            fn shader_is_valid() {{\n        \
+                naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::all())\n            \
+                    .validate(&module())\n            \
+                    .expect(\"the shader `{shader}` is invalid\");\n    \
+            }}\n",
+    );
+
+    let resources = module.global_variables.iter()
+        .filter_map(|(_, global)| Some((global.name.as_deref()?, global.binding.as_ref()?)))
+        .collect::<Vec<_>>();
+    if !resources.is_empty() || !module.entry_points.is_empty() {
+        harness.push_str(
+            "\n    #[test]\n    \
+            fn bindings_match_shader() {\n        \
+                let module = module();\n        \
+                let binding = |name: &str| module.global_variables.iter()\n            \
+                    .find(|(_, global)| global.name.as_deref() == Some(name))\n            \
+                    .and_then(|(_, global)| Some((global.binding.as_ref()?.group, global.binding.as_ref()?.binding)));\n        \
+                let stage = |name: &str| module.entry_points.iter().find(|entry_point| entry_point.name == name).map(|entry_point| entry_point.stage);\n",
+        );
+        for (name, binding) in resources {
+            writeln!(
+                harness,
+                "        assert_eq!(binding({name:?}), Some(({}, {})), \"the binding of `{name}` changed in the shader `{shader}`\");",
+                binding.group, binding.binding,
+            ).expect("writing to a String can't fail");
+        }
+        for entry_point in &module.entry_points {
+            writeln!(
+                harness,
+                "        assert_eq!(stage({name:?}), Some(naga::ShaderStage::{stage:?}), \"the entry point `{name}` changed in the shader `{shader}`\");",
+                name = entry_point.name,
+                stage = entry_point.stage,
+            ).expect("writing to a String can't fail");
+        }
+        harness.push_str("    }\n");
+    }
+
+    let structs = buffer_structs(module);
+    if !structs.is_empty() {
+        harness.push_str(
+            "\n    #[test]\n    \
+            fn layouts_match_shader() {\n        \
+                let module = module();\n        \
+                let layout = |name: &str| match module.types.iter().find(|(_, ty)| ty.name.as_deref() == Some(name)).map(|(_, ty)| &ty.inner) {\n            \
+                    Some(naga::TypeInner::Struct { members, span }) => Some((*span, members.iter().map(|member| member.offset).collect::<Vec<_>>())),\n            \
+                    _ => None,\n        \
+                };\n",
+        );
+        for (name, members, span) in structs {
+            writeln!(
+                harness,
+                "        assert_eq!(layout({name:?}), Some(({span}, vec!{:?})), \"the layout of `{name}` changed in the shader `{shader}`\");",
+                members.iter().map(|member| member.offset).collect::<Vec<_>>(),
+            ).expect("writing to a String can't fail");
+        }
+        harness.push_str("    }\n");
+    }
+
+    harness.push_str("}\n");
+    harness
+}
+
+/// A `bindings` module with a `(group, binding)` const for every resource of `module`, sorted by group and binding
+fn binding_consts(module: &naga::Module, mod_path: &ModulePath, wgsl_source_path: &str) -> String {
+    #[cfg(feature = "wgsl_minifier_ext")]
+    let name_map = super::wgsl_minifier::NameMap::read_for(wgsl_source_path).ok().flatten();
+    #[cfg(not(feature = "wgsl_minifier_ext"))]
+    let _ = wgsl_source_path;
+
+    let mut resources = module.global_variables.iter()
+        .filter_map(|(_, global)| Some((global.binding.as_ref()?, global.name.as_deref()?)))
+        .map(|(binding, name)| {
+            #[cfg(feature = "wgsl_minifier_ext")]
+            let name = name_map.as_ref().and_then(|name_map| name_map.original(name)).unwrap_or(name);
+            (binding.group, binding.binding, demangle_wesl(name, mod_path))
+        })
+        .collect::<Vec<_>>();
+    resources.sort_by_key(|(group, binding, _)| (*group, *binding));
+
+    let const_name = |path: &wgsl_to_wgpu::TypePath, qualified: bool| {
+        let mut words = match qualified {
+            true => path.parent.components.clone(),
+            false => Vec::new(),
+        };
+        words.push(path.name.clone());
+        words.iter().map(|word| snake_case(word).to_uppercase()).collect::<Vec<_>>().join("_")
+    };
+
+    let mut consts = String::from("\n/// The `(group, binding)` of every resource of the shader, by its WESL name\npub mod bindings {\n");
+    for (group, binding, path) in &resources {
+        let collides = resources.iter().filter(|(.., other)| const_name(other, false) == const_name(path, false)).count() > 1;
+        let wesl_name = path.parent.components.iter().chain([&path.name]).cloned().collect::<Vec<_>>().join("::");
+        writeln!(
+            consts,
+            "    /// `{wesl_name}`\n    pub const {}: (u32, u32) = ({group}, {binding});",
+            const_name(path, collides),
+        ).expect("writing to a String can't fail");
+    }
+    consts.push_str("}\n");
+
+    consts
+}
+
+/// `WORKGROUP_SIZE` consts and `dispatch_count` functions for the compute entry points of `module`,
+/// named after the entry point if there are several
+fn workgroup_sizes(module: &naga::Module) -> String {
+    let compute_entry_points = module.entry_points.iter()
+        .filter(|entry_point| entry_point.stage == naga::ShaderStage::Compute)
+        .collect::<Vec<_>>();
+    let single = compute_entry_points.len() == 1;
+
+    let mut consts = String::new();
+    for entry_point in compute_entry_points {
+        // the size isn't known until the overrides are set, when the pipeline is created
+        if entry_point.workgroup_size_overrides.is_some() {
+            #[cfg(feature = "logging")]
+            log::warn!("the workgroup size of `{}` depends on overrides, it gets no `WORKGROUP_SIZE`", entry_point.name);
+            continue;
+        }
+
+        let (size_const, dispatch_fn) = match single {
+            true => ("WORKGROUP_SIZE".to_owned(), "dispatch_count".to_owned()),
+            false => (format!("{}_WORKGROUP_SIZE", entry_point.name.to_uppercase()), format!("{}_dispatch_count", entry_point.name)),
+        };
+        let [x, y, z] = entry_point.workgroup_size;
+        writeln!(
+            consts,
+            "\n/// The workgroup size of `{name}`\n\
+            pub const {size_const}: [u32; 3] = [{x}, {y}, {z}];\n\
+            \n\
+            /// The number of workgroups of `{name}` to dispatch to cover `dims` invocations, rounded up\n\
+            pub const fn {dispatch_fn}(dims: [u32; 3]) -> [u32; 3] {{\n    \
+                [\n        \
+                    dims[0].div_ceil({size_const}[0]),\n        \
+                    dims[1].div_ceil({size_const}[1]),\n        \
+                    dims[2].div_ceil({size_const}[2]),\n    \
+                ]\n\
+            }}",
+            name = entry_point.name,
+        ).expect("writing to a String can't fail");
+    }
+
+    consts
+}
+
+/// The type of the options of the [`pipeline_helpers`], written in the root bindings module
+const RENDER_PIPELINE_OPTIONS_TYPE: &str = "/// The state of a render pipeline that is not in its shader, for the generated `create_render_pipeline`s
+#[derive(Debug, Clone, Default)]
+pub struct RenderPipelineOptions {
+    pub primitive: wgpu::PrimitiveState,
+    pub depth_stencil: Option<wgpu::DepthStencilState>,
+    pub multisample: wgpu::MultisampleState,
+}
+";
+
+/// `create_render_pipeline` and `create_compute_pipeline` functions for the entry points of `module`,
+/// nothing for the kinds of pipelines with more than one possible set of entry points
+fn pipeline_helpers(module: &naga::Module, mod_path: &ModulePath, mod_depth: usize) -> String {
+    let entry_points_of = |stage| module.entry_points.iter()
+        .filter(move |entry_point: &&naga::EntryPoint| entry_point.stage == stage)
+        .collect::<Vec<_>>();
+    let (vertex, fragment, compute) = (
+        entry_points_of(naga::ShaderStage::Vertex),
+        entry_points_of(naga::ShaderStage::Fragment),
+        entry_points_of(naga::ShaderStage::Compute),
+    );
+
+    let mut helpers = String::new();
+    if let ([vertex], fragment @ ([] | [_])) = (vertex.as_slice(), fragment.as_slice()) {
+        let fragment = match fragment.first() {
+            Some(fragment) => format!(
+                "Some(wgpu::FragmentState {{\n            \
+                    module: &module,\n            \
+                    entry_point: Some({:?}),\n            \
+                    compilation_options: Default::default(),\n            \
+                    targets,\n        \
+                }})",
+                fragment.name,
+            ),
+            None => "None".to_owned(),
+        };
+        writeln!(
+            helpers,
+            "\n/// Create the render pipeline of `{vertex_name}`, with the targets and state the shader doesn't know\n\
+            pub fn create_render_pipeline(\n    \
+                device: &wgpu::Device,\n    \
+                targets: &[Option<wgpu::ColorTargetState>],\n    \
+                options: &{options_type},\n\
+            ) -> wgpu::RenderPipeline {{\n    \
+                let module = create_shader_module(device);\n    \
+                let layout = create_pipeline_layout(device);\n    \
+                device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {{\n        \
+                    label: Some(\"{mod_path} render pipeline\"),\n        \
+                    layout: Some(&layout),\n        \
+                    vertex: wgpu::VertexState {{\n            \
+                        module: &module,\n            \
+                        entry_point: Some({vertex_name:?}),\n            \
+                        compilation_options: Default::default(),\n            \
+                        buffers: &{buffers}_VERTEX_BUFFERS,\n        \
+                    }},\n        \
+                    primitive: options.primitive,\n        \
+                    depth_stencil: options.depth_stencil.clone(),\n        \
+                    multisample: options.multisample,\n        \
+                    fragment: {fragment},\n        \
+                    multiview: None,\n        \
+                    cache: None,\n    \
+                }})\n\
+            }}",
+            vertex_name = vertex.name,
+            options_type = format_args!("{}RenderPipelineOptions", "super::".repeat(mod_depth)),
+            buffers = vertex.name.to_uppercase(),
+        ).expect("writing to a String can't fail");
+    }
+
+    if let [compute] = compute.as_slice() {
+        writeln!(
+            helpers,
+            "\n/// Create the compute pipeline of `{0}`\n\
+            pub fn create_compute_pipeline(device: &wgpu::Device) -> wgpu::ComputePipeline {{\n    \
+                compute::create_{0}_pipeline(device)\n\
+            }}",
+            compute.name,
+        ).expect("writing to a String can't fail");
+    }
+
+    helpers
+}
+
+/// Label the shader module of `wgsl_to_wgpu`'s `create_shader_module` with `mod_path`,
+/// so it is named in wgpu's errors and graphics debuggers
+fn label_shader_module(bindings: &mut String, mod_path: &ModulePath) {
+    let Some(label_start) = bindings.find("pub fn create_shader_module(")
+        .and_then(|fn_start| Some(fn_start + bindings[fn_start..].find("label: None")?))
+    else {
+        #[cfg(feature = "logging")]
+        log::warn!("no `create_shader_module` in the bindings of `{mod_path}`, its shader module is not labeled");
+        return;
+    };
+    bindings.replace_range(label_start..label_start + "label: None".len(), &format!("label: Some({:?})", mod_path.components.join("::")));
+}
+
+/// A `create_shader_module_unchecked` function, see [`WgpuBindingsExtension::with_unchecked_shader_module`]
+fn unchecked_shader_module(mod_path: &ModulePath) -> String {
+    format!(
+        "\n/// Create the shader module without wgpu's validation, the shader was validated when it was built\n\
+        ///\n\
+        /// # Safety\n\
+        /// The device must have `wgpu::Features::EXPERIMENTAL_PASSTHROUGH_SHADERS`, and the shader must be valid for it,\n\
+        /// see `wgpu::Device::create_shader_module_passthrough`\n\
+        pub unsafe fn create_shader_module_unchecked(device: &wgpu::Device) -> wgpu::ShaderModule {{\n    \
+            let source = std::borrow::Cow::Borrowed(SOURCE);\n    \
+            unsafe {{\n        \
+                device.create_shader_module_passthrough(wgpu::ShaderModuleDescriptorPassthrough {{\n            \
+                    label: Some({label:?}),\n            \
+                    wgsl: Some(source),\n            \
+                    ..Default::default()\n        \
+                }})\n    \
+            }}\n\
+        }}\n",
+        label = mod_path.components.join("::"),
+    )
+}
+
+/// A `<ENTRY_POINT>_VERTEX_BUFFERS` const with the vertex buffer layouts of every vertex entry point in `module`
+fn vertex_buffers_consts(module: &naga::Module, options: &BindingsOptions) -> String {
+    let mut consts = String::new();
+    let vertex_entry_points = module.entry_points.iter()
+        .filter(|entry_point| entry_point.stage == naga::ShaderStage::Vertex);
+    for entry_point in vertex_entry_points {
+        let buffers = vertex_buffers(module, entry_point, |argument, struct_name| options.is_instance_buffer(argument, struct_name));
+        writeln!(
+            consts,
+            "\n/// The vertex buffers of `{}`, one per argument with `@location`s\n\
+            pub const {}_VERTEX_BUFFERS: [wgpu::VertexBufferLayout<'static>; {}] = [\n{}];",
+            entry_point.name,
+            entry_point.name.to_uppercase(),
+            buffers.len(),
+            buffers.iter().map(|buffer| format!("    {},\n", buffer.to_rust())).collect::<String>(),
+        ).expect("writing to a String can't fail");
+    }
+
+    consts
+}
+
 fn create_shader_module(
+    mod_path: &ModulePath,
     wgsl_source: &str,
     // path to the compiled file
     wgsl_include_path: &str,
     options: WriteOptions,
 ) -> Result<String, Box<WgpuBindingsError>> {
+    // name the generated items after the names from before minification
+    #[cfg(feature = "wgsl_minifier_ext")]
+    let name_map = super::wgsl_minifier::NameMap::read_for(wgsl_include_path).ok().flatten();
+
     let mut root = wgsl_to_wgpu::Module::default();
     root.add_shader_module(
         wgsl_source,
         Some(wgsl_include_path),
         options,
         wgsl_to_wgpu::ModulePath::default(),
-        demangle_wesl,
+        {
+            let mod_path = mod_path.clone();
+            move |name| {
+                #[cfg(feature = "wgsl_minifier_ext")]
+                let name = name_map.as_ref().and_then(|name_map| name_map.original(name)).unwrap_or(name);
+                demangle_wesl(name, &mod_path)
+            }
+        },
         // |str| source_map.unwrap().get_decl(str).unwrap().0
     ).map_err(|e| {
         let home_dir = std::env::home_dir();
@@ -218,17 +1771,16 @@ fn create_shader_module(
     Ok(root.to_generated_bindings(options))
 }
 
-fn demangle_wesl(name: &str) -> wgsl_to_wgpu::TypePath {
-    // todo handle `super` paths
-    // Assume all paths are absolute paths.
-    if name.starts_with("package_") {
+/// Demangle a name from the module at `mod_path`, relative paths are resolved from `mod_path`
+fn demangle_wesl(name: &str, mod_path: &ModulePath) -> wgsl_to_wgpu::TypePath {
+    if ["package_", "self_", "super_"].iter().any(|origin| name.starts_with(origin)) {
         // Use the root module if unmangle fails.
         let mangler = wesl::EscapeMangler;
         let (path, name) = mangler
             .unmangle(name)
+            .and_then(|(path, name)| Some((crate::resolve_relative(mod_path, &path)?, name)))
             .unwrap_or((ModulePath::new_root(), name.to_string()));
 
-        // Assume all wesl paths are absolute paths.
         wgsl_to_wgpu::TypePath {
             parent: wgsl_to_wgpu::ModulePath {
                 components: path.components,
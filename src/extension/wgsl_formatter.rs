@@ -0,0 +1,304 @@
+#![cfg(feature = "wgsl_formatter_ext")]
+
+use std::path::Path;
+
+use wesl::{BasicSourceMap, ModulePath};
+
+use crate::WeslBuildExtension;
+use crate::artifacts;
+
+const INDENT: &str = "    ";
+
+/// Reformat the built WGSL with consistent indentation and one statement per line, so it's readable when debugging
+///
+/// Shaders are formatted in place, so add it before the extensions that should see the formatted output.
+pub struct WgslFormatterExtension {
+    sort_struct_members: bool,
+    debug_only: bool,
+}
+
+impl Default for WgslFormatterExtension {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WgslFormatterExtension {
+    pub fn new() -> Self {
+        Self {
+            sort_struct_members: false,
+            debug_only: false,
+        }
+    }
+
+    /// Sort the members of structs by name
+    ///
+    /// This changes the memory layout of the structs, so only use it when the layout doesn't matter,
+    /// e.g. when the built shaders are only read for debugging
+    pub fn with_sort_struct_members(mut self, sort_struct_members: bool) -> Self {
+        self.sort_struct_members = sort_struct_members;
+        self
+    }
+
+    /// Only format in the `debug` profile
+    pub fn with_debug_only(mut self, debug_only: bool) -> Self {
+        self.debug_only = debug_only;
+        self
+    }
+}
+
+impl<WeslResolver: wesl::Resolver> WeslBuildExtension<WeslResolver> for WgslFormatterExtension {
+    fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+        "WgslFormatterExtension".into()
+    }
+
+    fn reads_built_wgsl(&self) -> bool {
+        true
+    }
+
+    fn mutates_built_wgsl(&self) -> bool {
+        true
+    }
+
+    fn init_root(
+        &mut self,
+        _shader_path: &str,
+        _res: &mut wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    fn enter_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+    fn exit_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+
+    fn post_build(
+        &mut self,
+        _mod_path: &ModulePath,
+        wgsl_built_path: &str,
+        _source_map: &Option<BasicSourceMap>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if self.debug_only && crate::current_profile().is_some_and(|profile| profile != "debug") {
+            return Ok(());
+        }
+
+        let wgsl = artifacts::read_to_string(wgsl_built_path)?;
+        artifacts::write(wgsl_built_path, format_wgsl(&wgsl, self.sort_struct_members))?;
+
+        Ok(())
+    }
+}
+
+/// Reformat WGSL with consistent indentation and one statement per line, comments are kept
+///
+/// `sort_struct_members` sorts the members of structs without comments by name
+pub fn format_wgsl(source: &str, sort_struct_members: bool) -> String {
+    let mut f = Formatter::default();
+    let mut chars = source.char_indices().peekable();
+    let mut paren_depth = 0_usize;
+    // template lists in struct members, e.g. `array<f32, 4>`
+    let mut angle_depth = 0_usize;
+    let mut blocks = Vec::<Block>::new();
+    // a `struct` keyword was found and its body has not been opened yet
+    let mut pending_struct = false;
+
+    while let Some((idx, c)) = chars.next() {
+        let next = chars.peek().map(|(_, c)| *c);
+        let in_struct = blocks.last().is_some_and(|block| block.is_struct);
+
+        match c {
+            '/' if next == Some('/') => {
+                let end = source[idx..].find('\n').map_or(source.len(), |end| idx + end);
+                f.space();
+                f.line.push_str(source[idx..end].trim_end());
+                f.flush();
+                while chars.next_if(|(i, _)| *i < end).is_some() {}
+            }
+            '/' if next == Some('*') => {
+                let end = block_comment_end(source, idx);
+                f.space();
+                f.line.push_str(&source[idx..end]);
+                while chars.next_if(|(i, _)| *i < end).is_some() {}
+            }
+            c if c.is_whitespace() => {
+                let mut newlines = usize::from(c == '\n');
+                while let Some((_, c)) = chars.next_if(|(_, c)| c.is_whitespace()) {
+                    newlines += usize::from(c == '\n');
+                }
+                // keep single blank lines between statements
+                if newlines >= 2 && f.line.trim().is_empty() {
+                    f.blank_line = true;
+                }
+                f.space();
+            }
+            '(' | '[' => {
+                paren_depth += 1;
+                f.line.push(c);
+            }
+            ')' | ']' => {
+                paren_depth = paren_depth.saturating_sub(1);
+                f.line.push(c);
+            }
+            '<' if in_struct => {
+                angle_depth += 1;
+                f.line.push(c);
+            }
+            '>' if in_struct => {
+                angle_depth = angle_depth.saturating_sub(1);
+                f.line.push(c);
+            }
+            '{' => {
+                f.line.truncate(f.line.trim_end().len());
+                f.space();
+                f.line.push('{');
+                f.flush();
+                blocks.push(Block {
+                    is_struct: std::mem::take(&mut pending_struct),
+                    start: f.out.len(),
+                });
+                f.indent += 1;
+                f.blank_line = false;
+            }
+            '}' => {
+                f.flush();
+                f.blank_line = false;
+                f.indent = f.indent.saturating_sub(1);
+                if let Some(block) = blocks.pop()
+                    && block.is_struct
+                    && sort_struct_members
+                {
+                    sort_members(&mut f.out, block.start);
+                }
+                f.line.push('}');
+
+                // keep `} else {` and `};` on one line
+                let rest = source[idx + 1..].trim_start();
+                let is_else = rest.strip_prefix("else")
+                    .is_some_and(|rest| !rest.starts_with(|c: char| c.is_alphanumeric() || c == '_'));
+                if is_else {
+                    f.line.push(' ');
+                } else if !rest.starts_with([';', ',', ')']) {
+                    f.flush();
+                    if f.indent == 0 {
+                        f.blank_line = true;
+                    }
+                }
+            }
+            ';' => {
+                f.line.push(';');
+                // `for (;;)` headers stay on one line
+                if paren_depth == 0 {
+                    f.flush();
+                }
+            }
+            ',' => {
+                f.line.push(',');
+                if in_struct && paren_depth == 0 && angle_depth == 0 {
+                    f.flush();
+                }
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut end = idx + c.len_utf8();
+                while let Some((i, c)) = chars.next_if(|(_, c)| c.is_alphanumeric() || *c == '_') {
+                    end = i + c.len_utf8();
+                }
+                let word = &source[idx..end];
+                if word == "struct" && paren_depth == 0 {
+                    pending_struct = true;
+                }
+                f.line.push_str(word);
+            }
+            _ => f.line.push(c),
+        }
+    }
+    f.flush();
+
+    f.out
+}
+
+#[derive(Default)]
+struct Formatter {
+    out: String,
+    /// The line being built, without indentation
+    line: String,
+    indent: usize,
+    /// Put a blank line before the next line
+    blank_line: bool,
+}
+
+impl Formatter {
+    /// Write the current line to the output if it's not empty
+    fn flush(&mut self) {
+        let line = self.line.trim();
+        if !line.is_empty() {
+            if self.blank_line && !self.out.is_empty() {
+                self.out.push('\n');
+            }
+            self.blank_line = false;
+            self.out.push_str(&INDENT.repeat(self.indent));
+            self.out.push_str(line);
+            self.out.push('\n');
+        }
+        self.line.clear();
+    }
+
+    /// Separate the next token with a space
+    fn space(&mut self) {
+        if !self.line.is_empty() && !self.line.ends_with(' ') {
+            self.line.push(' ');
+        }
+    }
+}
+
+struct Block {
+    is_struct: bool,
+    /// The position in the output after the opening line
+    start: usize,
+}
+
+/// The index after the end of the block comment starting at `start`, block comments nest in WGSL
+fn block_comment_end(source: &str, start: usize) -> usize {
+    let mut depth = 0_usize;
+    let mut idx = start;
+
+    while idx < source.len() {
+        if source[idx..].starts_with("/*") {
+            depth += 1;
+            idx += 2;
+        } else if source[idx..].starts_with("*/") {
+            depth -= 1;
+            idx += 2;
+            if depth == 0 {
+                return idx;
+            }
+        } else {
+            idx += source[idx..].chars().next().map_or(1, char::len_utf8);
+        }
+    }
+
+    source.len()
+}
+
+/// Sort the member lines of the struct body at `out[start..]` by name, bodies with comments are left as is
+fn sort_members(out: &mut String, start: usize) {
+    let body = &out[start..];
+    if body.contains("//") || body.contains("/*") {
+        return;
+    }
+
+    let mut members = body.lines()
+        .map(|member| {
+            let member = member.trim_end();
+            if member.ends_with(',') { member.to_owned() } else { format!("{member},") }
+        })
+        .collect::<Vec<_>>();
+    // the name is the last word before the `:`, after any attributes
+    members.sort_by_cached_key(|member| {
+        member.split(':').next().and_then(|decl| decl.split_whitespace().last()).unwrap_or_default().to_owned()
+    });
+
+    out.truncate(start);
+    for member in members {
+        out.push_str(&member);
+        out.push('\n');
+    }
+}
@@ -1,15 +1,292 @@
 #![cfg(feature = "wgsl_minifier_ext")]
 
-use std::{fs, path::Path};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
 
+use naga::{Handle, Module};
+use regex::Regex;
 use wesl::{ModulePath, BasicSourceMap};
 
 use crate::WeslBuildExtension;
+use crate::artifacts;
+use crate::build_out_dir;
+
+/// When [`WgslMinifierExtension`] minifies the built shaders
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum MinifyWhen {
+    #[default]
+    Always,
+    /// Only when the [`current_profile`](`crate::current_profile`) is `release`
+    ReleaseOnly,
+    /// Only when the [`current_profile`](`crate::current_profile`) is one of these
+    Profiles(Vec<String>),
+    /// Only when this env var is set to anything other than empty, `0` or `false`
+    EnvVar(String),
+}
+
+impl MinifyWhen {
+    /// Whether the shaders should be minified in this build
+    pub fn should_minify(&self) -> bool {
+        match self {
+            MinifyWhen::Always => true,
+            MinifyWhen::ReleaseOnly => crate::current_profile().is_some_and(|profile| profile == "release"),
+            MinifyWhen::Profiles(profiles) => crate::current_profile()
+                .is_some_and(|profile| profiles.contains(&profile)),
+            MinifyWhen::EnvVar(var) => std::env::var(var)
+                .is_ok_and(|value| !matches!(value.as_str(), "" | "0" | "false")),
+        }
+    }
+}
 
 /// Removes all the characters it can from our built shaders.
+///
+/// Minification renames entry points, functions, globals, constants and overrides,
+/// keep the names your engine looks up by reflection with the `preserve` options.
+#[derive(Debug, Clone, Default)]
 pub struct WgslMinifierExtension {
-    /// Whether shaders should only be minified in release builds
-    pub release_only: bool,
+    /// When shaders are minified
+    pub when: MinifyWhen,
+    /// Keep the names of entry points
+    pub preserve_entry_points: bool,
+    /// Keep the names of pipeline overrides
+    pub preserve_overrides: bool,
+    /// Module scope names to keep
+    pub preserve: Vec<String>,
+    /// Keep the module scope names matching any of these patterns
+    pub preserve_patterns: Vec<Regex>,
+    /// Write a [`NameMap`] next to each minified shader
+    pub name_map: bool,
+    /// Write the size of each shader before and after minification to `OUT_DIR/minify_stats.json`
+    pub stats: bool,
+    minified: Vec<NameMap>,
+}
+
+/// The names the minifier changed in a shader and its size before and after,
+/// written to `{artifact}.map.json` next to the built shader with [`WgslMinifierExtension::with_name_map`]
+///
+/// Extensions that run after the minifier can read it with [`NameMap::read_for`],
+/// e.g. `WgpuBindingsExtension` uses it to name the generated items after the original names
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameMap {
+    pub mod_path: String,
+    /// Original name to minified name, names that were kept are left out
+    pub names: BTreeMap<String, String>,
+    pub original_bytes: usize,
+    pub minified_bytes: usize,
+}
+
+impl NameMap {
+    /// Where the name map of the built shader at `wgsl_built_path` is written
+    pub fn path_for(wgsl_built_path: impl AsRef<Path>) -> PathBuf {
+        wgsl_built_path.as_ref().with_extension("map.json")
+    }
+
+    /// Read the name map of the built shader at `wgsl_built_path`, `None` if it was not minified with a name map
+    pub fn read_for(wgsl_built_path: impl AsRef<Path>) -> Result<Option<Self>, Box<dyn std::error::Error>> {
+        let path = Self::path_for(wgsl_built_path);
+        let json = match artifacts::read_to_string(&path) {
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            json => serde_json::from_str::<serde_json::Value>(&json?)?,
+        };
+
+        let invalid = || format!("invalid name map: {}", path.display());
+        let names = json["names"].as_object().ok_or_else(invalid)?.iter()
+            .map(|(original, minified)| Some((original.clone(), minified.as_str()?.to_owned())))
+            .collect::<Option<_>>()
+            .ok_or_else(invalid)?;
+
+        Ok(Some(Self {
+            mod_path: json["module"].as_str().ok_or_else(invalid)?.to_owned(),
+            names,
+            original_bytes: json["original_bytes"].as_u64().ok_or_else(invalid)? as usize,
+            minified_bytes: json["minified_bytes"].as_u64().ok_or_else(invalid)? as usize,
+        }))
+    }
+
+    /// The original name of a `minified` name
+    pub fn original(&self, minified: &str) -> Option<&str> {
+        self.names.iter()
+            .find(|(_, name)| *name == minified)
+            .map(|(original, _)| original.as_str())
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "module": self.mod_path,
+            "names": self.names,
+            "original_bytes": self.original_bytes,
+            "minified_bytes": self.minified_bytes,
+        })
+    }
+}
+
+/// The names of every module scope item and entry point, in arena order
+fn module_scope_names(module: &Module) -> Vec<Option<String>> {
+    module.types.iter().map(|(_, ty)| ty.name.clone())
+        .chain(module.functions.iter().map(|(_, function)| function.name.clone()))
+        .chain(module.global_variables.iter().map(|(_, global)| global.name.clone()))
+        .chain(module.constants.iter().map(|(_, constant)| constant.name.clone()))
+        .chain(module.overrides.iter().map(|(_, r#override)| r#override.name.clone()))
+        .chain(module.entry_points.iter().map(|entry_point| Some(entry_point.name.clone())))
+        .collect()
+}
+
+impl WgslMinifierExtension {
+    /// Always minify and keep no names
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set when shaders are minified, defaults to [`MinifyWhen::Always`]
+    pub fn with_when(mut self, when: MinifyWhen) -> Self {
+        self.when = when;
+        self
+    }
+
+    /// Write a [`NameMap`] of the original to minified names next to each minified shader
+    pub fn with_name_map(mut self, name_map: bool) -> Self {
+        self.name_map = name_map;
+        self
+    }
+
+    /// Write the size of each shader before and after minification to `OUT_DIR/minify_stats.json`
+    pub fn with_stats(mut self, stats: bool) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    /// The name maps of every shader minified so far
+    pub fn minified(&self) -> &[NameMap] {
+        &self.minified
+    }
+
+    /// Keep the names of entry points, e.g. for `wgpu::RenderPipelineDescriptor::entry_point`
+    pub fn with_preserve_entry_points(mut self, preserve_entry_points: bool) -> Self {
+        self.preserve_entry_points = preserve_entry_points;
+        self
+    }
+
+    /// Keep the names of overrides, so they can be set by name with `wgpu::PipelineCompilationOptions::constants`
+    pub fn with_preserve_overrides(mut self, preserve_overrides: bool) -> Self {
+        self.preserve_overrides = preserve_overrides;
+        self
+    }
+
+    /// Keep the name of the module scope function, global, constant or override called `name`
+    pub fn with_preserve(mut self, name: impl Into<String>) -> Self {
+        self.preserve.push(name.into());
+        self
+    }
+
+    /// Keep the names of the module scope functions, globals, constants and overrides matching `pattern`
+    pub fn with_preserve_pattern(mut self, pattern: Regex) -> Self {
+        self.preserve_patterns.push(pattern);
+        self
+    }
+
+    fn keeps(&self, name: &str) -> bool {
+        self.preserve.iter().any(|preserved| preserved == name)
+            || self.preserve_patterns.iter().any(|pattern| pattern.is_match(name))
+    }
+
+    /// The names to restore after the module is minified
+    fn preserved_names(&self, module: &Module) -> PreservedNames {
+        fn named<T>(
+            items: impl Iterator<Item = (Handle<T>, Option<String>)>,
+            keep: impl Fn(&str) -> bool,
+        ) -> Vec<(Handle<T>, String)> {
+            items.filter_map(|(handle, name)| name.filter(|name| keep(name)).map(|name| (handle, name)))
+                .collect()
+        }
+
+        PreservedNames {
+            entry_points: module.entry_points.iter().enumerate()
+                .filter(|(_, entry_point)| self.preserve_entry_points || self.keeps(&entry_point.name))
+                .map(|(idx, entry_point)| (idx, entry_point.name.clone()))
+                .collect(),
+            functions: named(
+                module.functions.iter().map(|(handle, function)| (handle, function.name.clone())),
+                |name| self.keeps(name),
+            ),
+            global_variables: named(
+                module.global_variables.iter().map(|(handle, global)| (handle, global.name.clone())),
+                |name| self.keeps(name),
+            ),
+            constants: named(
+                module.constants.iter().map(|(handle, constant)| (handle, constant.name.clone())),
+                |name| self.keeps(name),
+            ),
+            overrides: named(
+                module.overrides.iter().map(|(handle, r#override)| (handle, r#override.name.clone())),
+                |name| self.preserve_overrides || self.keeps(name),
+            ),
+        }
+    }
+}
+
+/// The original names of the module scope items to keep, by handle (or index for entry points)
+///
+/// `wgsl_minifier::minify_module` renames in place, so the handles stay valid
+struct PreservedNames {
+    entry_points: Vec<(usize, String)>,
+    functions: Vec<(Handle<naga::Function>, String)>,
+    global_variables: Vec<(Handle<naga::GlobalVariable>, String)>,
+    constants: Vec<(Handle<naga::Constant>, String)>,
+    overrides: Vec<(Handle<naga::Override>, String)>,
+}
+
+impl PreservedNames {
+    /// Put the original names back, renaming minified items that now clash with them
+    fn restore(self, module: &mut Module) {
+        let preserved = self.entry_points.iter().map(|(_, name)| name)
+            .chain(self.functions.iter().map(|(_, name)| name))
+            .chain(self.global_variables.iter().map(|(_, name)| name))
+            .chain(self.constants.iter().map(|(_, name)| name))
+            .chain(self.overrides.iter().map(|(_, name)| name))
+            .cloned()
+            .collect::<HashSet<_>>();
+        let unclash = |name: &mut Option<String>| {
+            if let Some(name) = name {
+                while preserved.contains(name) {
+                    name.push('_');
+                }
+            }
+        };
+
+        for (_, function) in module.functions.iter_mut() {
+            unclash(&mut function.name);
+        }
+        for (_, global) in module.global_variables.iter_mut() {
+            unclash(&mut global.name);
+        }
+        for (_, constant) in module.constants.iter_mut() {
+            unclash(&mut constant.name);
+        }
+        for (_, r#override) in module.overrides.iter_mut() {
+            unclash(&mut r#override.name);
+        }
+        for entry_point in &mut module.entry_points {
+            let mut name = Some(std::mem::take(&mut entry_point.name));
+            unclash(&mut name);
+            entry_point.name = name.unwrap_or_default();
+        }
+
+        for (idx, name) in self.entry_points {
+            module.entry_points[idx].name = name;
+        }
+        for (handle, name) in self.functions {
+            module.functions.get_mut(handle).name = Some(name);
+        }
+        for (handle, name) in self.global_variables {
+            module.global_variables.get_mut(handle).name = Some(name);
+        }
+        for (handle, name) in self.constants {
+            module.constants.get_mut(handle).name = Some(name);
+        }
+        for (handle, name) in self.overrides {
+            module.overrides.get_mut(handle).name = Some(name);
+        }
+    }
 }
 
 impl<WeslResolver: wesl::Resolver> WeslBuildExtension<WeslResolver> for WgslMinifierExtension {
@@ -17,35 +294,89 @@ impl<WeslResolver: wesl::Resolver> WeslBuildExtension<WeslResolver> for WgslMini
         "WgslMinifierExtension".into()
     }
 
+    fn reads_built_wgsl(&self) -> bool {
+        true
+    }
+
+    fn mutates_built_wgsl(&self) -> bool {
+        true
+    }
+
+    /// Bindings must be generated from the names before minification
+    fn must_run_after(&self) -> &[&str] {
+        &["WgpuBindingsExtension"]
+    }
+
     fn init_root(
         &mut self,
         _shader_path: &str,
         _res: &mut wesl::Wesl<WeslResolver>,
-    ) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if let MinifyWhen::EnvVar(var) = &self.when {
+            crate::rerun_if_env_changed(var);
+        }
+        self.minified.clear();
+        Ok(())
+    }
+
+    fn exit_root(
+        &mut self,
+        _shader_root_path: &str,
+        _res: &wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.stats || self.minified.is_empty() {
+            return Ok(());
+        }
+
+        let original_bytes = self.minified.iter().map(|map| map.original_bytes).sum::<usize>();
+        let minified_bytes = self.minified.iter().map(|map| map.minified_bytes).sum::<usize>();
+        #[cfg(feature = "logging")]
+        log::info!(
+            "minified {} shaders from {original_bytes} to {minified_bytes} bytes ({:.1}% smaller)",
+            self.minified.len(),
+            100.0 * (1.0 - minified_bytes as f64 / original_bytes.max(1) as f64),
+        );
+
+        let stats = serde_json::json!({
+            "original_bytes": original_bytes,
+            "minified_bytes": minified_bytes,
+            "shaders": self.minified.iter()
+                .map(|map| serde_json::json!({
+                    "module": map.mod_path,
+                    "original_bytes": map.original_bytes,
+                    "minified_bytes": map.minified_bytes,
+                }))
+                .collect::<Vec<_>>(),
+        });
+        if crate::is_check() {
+            return Ok(());
+        }
+        artifacts::write(build_out_dir()?.join("minify_stats.json"), serde_json::to_string_pretty(&stats)?)?;
+
+        Ok(())
+    }
 
     fn enter_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
     fn exit_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
 
     fn post_build(
         &mut self,
-        _mod_path: &ModulePath,
+        mod_path: &ModulePath,
         wgsl_source_path: &str,
         _source_map: &Option<BasicSourceMap>,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        if self.release_only {
-            let profile = std::env::var("PROFILE")?;
-            match profile.as_str() {
-                "debug" => return Ok(()),
-                "release" => (),
-                _ => return Ok(()),
-            };
+        if !self.when.should_minify() {
+            return Ok(());
         }
-        let wgsl_source = fs::read_to_string(wgsl_source_path)?;
+        let wgsl_source = artifacts::read_to_string(wgsl_source_path)?;
 
         let mut module = naga::front::wgsl::parse_str(&wgsl_source)?;
 
         // strip and minify
+        let original_names = module_scope_names(&module);
+        let preserved_names = self.preserved_names(&module);
         wgsl_minifier::minify_module(&mut module);
+        preserved_names.restore(&mut module);
 
         // Write to WGSL string
         let mut validator = naga::valid::Validator::new(
@@ -58,8 +389,22 @@ impl<WeslResolver: wesl::Resolver> WeslBuildExtension<WeslResolver> for WgslMini
         // remove whitespace and minify string
         let output = wgsl_minifier::minify_wgsl_source(&output);
 
+        let name_map = NameMap {
+            mod_path: mod_path.to_string(),
+            names: original_names.into_iter().zip(module_scope_names(&module))
+                .filter_map(|(original, minified)| Some((original?, minified?)))
+                .filter(|(original, minified)| original != minified)
+                .collect(),
+            original_bytes: wgsl_source.len(),
+            minified_bytes: output.len(),
+        };
+        if self.name_map {
+            artifacts::write(NameMap::path_for(wgsl_source_path), serde_json::to_string_pretty(&name_map.to_json())?)?;
+        }
+        self.minified.push(name_map);
+
         // replace built file with minified file
-        fs::write(wgsl_source_path, output)?;
+        artifacts::write(wgsl_source_path, output)?;
 
         Ok(())
     }
@@ -0,0 +1,377 @@
+#![cfg(feature = "wgsl_obfuscator_ext")]
+
+//! Obfuscate the built shaders of shipped games: random names, stripped comments and an XOR scrambled bundle
+
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::path::Path;
+
+use naga::{Module, UniqueArena};
+use wesl::{BasicSourceMap, ModulePath};
+
+use crate::context::try_with_build_context;
+use crate::{WeslBuildExtension, artifacts};
+
+/// The env var with the seed of the names and the bundle, a random seed is used for every build if it's not set
+pub const OBFUSCATION_SEED_ENV: &str = "WESL_BUILD_OBFUSCATION_SEED";
+
+/// The file name of the scrambled bundle in the output dir
+pub const BUNDLE_NAME: &str = "shader_bundle.bin";
+/// The file name of the decoder of the bundle in the output dir, `include!` it in your crate
+pub const BUNDLE_DECODER_NAME: &str = "shader_bundle.rs";
+
+/// How much [`WgslObfuscatorExtension`] obfuscates, each level does everything the ones before it do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum ObfuscationStrength {
+    /// Strip comments and whitespace, and give functions, variables, arguments and structs random names
+    Names,
+    /// Also give struct members random names, their layout is kept so host code is unaffected
+    #[default]
+    Members,
+    /// Also write every shader XOR scrambled to [`BUNDLE_NAME`], read with the decoder in [`BUNDLE_DECODER_NAME`]
+    Bundle,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum WgslObfuscatorError {
+    #[error("`{OBFUSCATION_SEED_ENV}` must be a number, it is `{value}`")]
+    InvalidSeed { value: String },
+}
+
+/// Obfuscates the built shaders beyond [minification](super::wgsl_minifier), for shipped commercial titles
+///
+/// Every name is replaced with a random one from a per-build seed, set it with [`with_seed`](Self::with_seed)
+/// or the [`OBFUSCATION_SEED_ENV`] env var for reproducible builds. Entry points and overrides keep their names
+/// by default, as pipelines look them up by name. It runs after the bindings are generated from the original names.
+///
+/// With [`ObfuscationStrength::Bundle`] the shaders are also written XOR scrambled to one bundle,
+/// so their source isn't in the binary as text. Scrambling only hides the source from casual inspection,
+/// the key is in the decoder. Load the shaders with the generated decoder instead of `include_wesl!`:
+/// ```ignore
+/// include!(concat!(env!("OUT_DIR"), "/shader_bundle.rs"));
+///
+/// let source = obfuscated_shader("passes::blur").unwrap();
+/// ```
+///
+/// ## Example
+/// ```
+/// use wesl_build::extension::wgsl_obfuscator::{ObfuscationStrength, WgslObfuscatorExtension};
+///
+/// let obfuscator = WgslObfuscatorExtension::new()
+///     .with_strength(ObfuscationStrength::Bundle)
+///     .with_preserve("light_count");
+/// ```
+#[derive(Debug, Clone)]
+pub struct WgslObfuscatorExtension {
+    strength: ObfuscationStrength,
+    seed: Option<u64>,
+    preserve_entry_points: bool,
+    preserve_overrides: bool,
+    preserve: Vec<String>,
+    /// The seed of this build
+    build_seed: u64,
+    /// The module path and obfuscated source of every shader for the bundle
+    bundled: Vec<(String, String)>,
+}
+
+impl Default for WgslObfuscatorExtension {
+    fn default() -> Self {
+        Self {
+            strength: ObfuscationStrength::default(),
+            seed: None,
+            preserve_entry_points: true,
+            preserve_overrides: true,
+            preserve: Vec::new(),
+            build_seed: 0,
+            bundled: Vec::new(),
+        }
+    }
+}
+
+impl WgslObfuscatorExtension {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set how much is obfuscated, defaults to [`ObfuscationStrength::Members`]
+    pub fn with_strength(mut self, strength: ObfuscationStrength) -> Self {
+        self.strength = strength;
+        self
+    }
+
+    /// The seed of the random names and the scrambling, instead of a random one for each build
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Keep the names of entry points, on by default
+    pub fn with_preserve_entry_points(mut self, preserve_entry_points: bool) -> Self {
+        self.preserve_entry_points = preserve_entry_points;
+        self
+    }
+
+    /// Keep the names of overrides, so they can be set by name, on by default
+    pub fn with_preserve_overrides(mut self, preserve_overrides: bool) -> Self {
+        self.preserve_overrides = preserve_overrides;
+        self
+    }
+
+    /// Keep the name of the module scope item called `name`
+    pub fn with_preserve(mut self, name: impl Into<String>) -> Self {
+        self.preserve.push(name.into());
+        self
+    }
+
+    /// The seed of this build, from [`with_seed`](Self::with_seed), the env var or the clock
+    fn build_seed(&self) -> Result<u64, WgslObfuscatorError> {
+        if let Some(seed) = self.seed {
+            return Ok(seed);
+        }
+        crate::rerun_if_env_changed(OBFUSCATION_SEED_ENV);
+        match std::env::var(OBFUSCATION_SEED_ENV) {
+            Ok(value) => value.trim().parse().map_err(|_| WgslObfuscatorError::InvalidSeed { value }),
+            Err(_) => {
+                let nanos = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_nanos();
+                Ok(nanos as u64 ^ u64::from(std::process::id()).rotate_left(32))
+            }
+        }
+    }
+
+    /// Give `name` a random name, unless it's kept
+    fn rename(&self, name: &mut Option<String>, names: &mut RandomNames) {
+        if name.as_deref().is_some_and(|name| !self.preserve.iter().any(|preserved| preserved == name)) {
+            *name = Some(names.next());
+        }
+    }
+
+    /// Rename the arguments and variables of `function`
+    fn rename_locals(&self, function: &mut naga::Function, names: &mut RandomNames) {
+        for argument in &mut function.arguments {
+            self.rename(&mut argument.name, names);
+        }
+        for (_, local) in function.local_variables.iter_mut() {
+            self.rename(&mut local.name, names);
+        }
+        for name in function.named_expressions.values_mut() {
+            *name = names.next();
+        }
+    }
+
+    /// Rename everything in `module` that isn't kept
+    fn rename_module(&self, module: &mut Module, names: &mut RandomNames) {
+        // types can't be changed in place, renamed they are inserted in the same order and keep their handles
+        let special = module.special_types.predeclared_types.values().copied().collect::<HashSet<_>>();
+        let mut types = UniqueArena::new();
+        for (handle, ty) in module.types.iter() {
+            let mut ty = ty.clone();
+            // naga declares its special types itself, by their names
+            if !special.contains(&handle) && !ty.name.as_deref().is_some_and(|name| name.starts_with("__")) {
+                self.rename(&mut ty.name, names);
+                if let naga::TypeInner::Struct { members, .. } = &mut ty.inner
+                    && self.strength >= ObfuscationStrength::Members
+                {
+                    for member in members {
+                        self.rename(&mut member.name, names);
+                    }
+                }
+            }
+            let renamed = types.insert(ty, module.types.get_span(handle));
+            debug_assert_eq!(renamed, handle, "the renamed types are unique");
+        }
+        module.types = types;
+
+        for (_, function) in module.functions.iter_mut() {
+            self.rename(&mut function.name, names);
+            self.rename_locals(function, names);
+        }
+        for entry_point in &mut module.entry_points {
+            if !self.preserve_entry_points && !self.preserve.contains(&entry_point.name) {
+                entry_point.name = names.next();
+            }
+            self.rename_locals(&mut entry_point.function, names);
+        }
+        for (_, global) in module.global_variables.iter_mut() {
+            self.rename(&mut global.name, names);
+        }
+        for (_, constant) in module.constants.iter_mut() {
+            self.rename(&mut constant.name, names);
+        }
+        if !self.preserve_overrides {
+            for (_, r#override) in module.overrides.iter_mut() {
+                self.rename(&mut r#override.name, names);
+            }
+        }
+    }
+
+    /// The rust source of the decoder of the bundle at `bundle_path`, with the `(module, offset, len, key)` of every shader
+    fn decoder_source(bundle_path: &Path, shaders: &[(String, usize, usize, u64)]) -> Result<String, std::io::Error> {
+        let mut source = "// @generated by wesl_build, do not edit\n\n".to_owned();
+        writeln!(source, "const SHADER_BUNDLE: &[u8] = include_bytes!({:?});", std::path::absolute(bundle_path)?.display().to_string())
+            .expect("writing to a String can't fail");
+        source.push_str("\n/// The module, offset, length and key of every shader in the bundle\nconst SHADER_BUNDLE_INDEX: &[(&str, usize, usize, u64)] = &[\n");
+        for (mod_path, offset, len, key) in shaders {
+            writeln!(source, "    ({mod_path:?}, {offset}, {len}, {key:#018x}),").expect("writing to a String can't fail");
+        }
+        source.push_str("];\n");
+        source.push_str(DECODER);
+        Ok(source)
+    }
+}
+
+/// The functions of the generated decoder
+///
+/// !! keep in sync with `scramble` and `next_random` !!
+const DECODER: &str = r#"
+/// The source of the obfuscated shader `module`, e.g. `passes::blur`
+#[allow(unused)]
+pub fn obfuscated_shader(module: &str) -> Option<String> {
+    let &(_, offset, len, mut state) = SHADER_BUNDLE_INDEX.iter().find(|(name, ..)| *name == module)?;
+    let bytes = SHADER_BUNDLE[offset..offset + len].iter()
+        .map(|byte| {
+            state = state.wrapping_add(0x9e3779b97f4a7c15);
+            let mut z = state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+            byte ^ (z ^ (z >> 31)) as u8
+        })
+        .collect();
+    String::from_utf8(bytes).ok()
+}
+"#;
+
+/// The next number of the splitmix64 sequence of `state`
+///
+/// !! keep in sync with the generated `DECODER` !!
+fn next_random(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// XOR `bytes` with the sequence of `key`, scrambling and unscrambling are the same
+pub fn scramble(key: u64, bytes: &[u8]) -> Vec<u8> {
+    let mut state = key;
+    bytes.iter().map(|byte| byte ^ next_random(&mut state) as u8).collect()
+}
+
+/// Unique random identifiers
+struct RandomNames {
+    state: u64,
+    used: HashSet<String>,
+}
+
+impl RandomNames {
+    /// Names from `seed`, never one of `taken`
+    fn new(seed: u64, taken: impl IntoIterator<Item = String>) -> Self {
+        Self { state: seed, used: taken.into_iter().collect() }
+    }
+
+    fn next(&mut self) -> String {
+        loop {
+            // a letter first, so it's an identifier and never a keyword
+            let name = format!("o{:07x}", next_random(&mut self.state) & 0xfff_ffff);
+            if self.used.insert(name.clone()) {
+                return name;
+            }
+        }
+    }
+}
+
+/// The seed of the names and key of the shader `mod_path` in a build with `seed`
+fn shader_seed(seed: u64, mod_path: &str) -> u64 {
+    mod_path.bytes().fold(seed, |state, byte| {
+        let mut state = state ^ u64::from(byte);
+        next_random(&mut state)
+    })
+}
+
+impl<WeslResolver: wesl::Resolver> WeslBuildExtension<WeslResolver> for WgslObfuscatorExtension {
+    fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+        "WgslObfuscatorExtension".into()
+    }
+
+    fn reads_built_wgsl(&self) -> bool {
+        true
+    }
+
+    fn mutates_built_wgsl(&self) -> bool {
+        true
+    }
+
+    /// Bindings must be generated from the original names, and the minifier would undo the names
+    fn must_run_after(&self) -> &[&str] {
+        &["WgpuBindingsExtension", "WgslMinifierExtension"]
+    }
+
+    fn init_root(
+        &mut self,
+        _shader_root_path: &str,
+        _res: &mut wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.build_seed = self.build_seed()?;
+        self.bundled.clear();
+        Ok(())
+    }
+
+    fn exit_root(
+        &mut self,
+        _shader_root_path: &str,
+        _res: &wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if self.strength < ObfuscationStrength::Bundle {
+            return Ok(());
+        }
+        let Some(out_dir) = try_with_build_context(|ctx| ctx.out_dir())? else {
+            return Ok(());
+        };
+
+        let mut bundle = Vec::new();
+        let mut shaders = Vec::with_capacity(self.bundled.len());
+        for (mod_path, source) in &self.bundled {
+            let key = shader_seed(self.build_seed, mod_path).rotate_left(17);
+            shaders.push((mod_path.clone(), bundle.len(), source.len(), key));
+            bundle.extend(scramble(key, source.as_bytes()));
+        }
+        let bundle_path = out_dir.join(BUNDLE_NAME);
+        artifacts::write(&bundle_path, bundle)?;
+        artifacts::write(out_dir.join(BUNDLE_DECODER_NAME), Self::decoder_source(&bundle_path, &shaders)?)?;
+
+        Ok(())
+    }
+
+    fn post_build(
+        &mut self,
+        mod_path: &ModulePath,
+        wgsl_built_path: &str,
+        _source_map: &Option<BasicSourceMap>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let wgsl_source = artifacts::read_to_string(wgsl_built_path)?;
+        // naga drops the comments
+        let mut module = naga::front::wgsl::parse_str(&wgsl_source)?;
+
+        let mod_path = mod_path.components.join("::");
+        let kept = module.entry_points.iter().map(|entry_point| entry_point.name.clone())
+            .chain(module.overrides.iter().filter_map(|(_, r#override)| r#override.name.clone()))
+            .chain(self.preserve.iter().cloned());
+        let mut names = RandomNames::new(shader_seed(self.build_seed, &mod_path), kept);
+        self.rename_module(&mut module, &mut names);
+
+        let info = naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::all())
+            .validate(&module)?;
+        let output = naga::back::wgsl::write_string(&module, &info, naga::back::wgsl::WriterFlags::empty())?;
+        let output = wgsl_minifier::minify_wgsl_source(&output);
+
+        if self.strength >= ObfuscationStrength::Bundle {
+            self.bundled.push((mod_path, output.clone()));
+        }
+        artifacts::write(wgsl_built_path, output)?;
+
+        Ok(())
+    }
+}
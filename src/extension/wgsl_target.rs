@@ -0,0 +1,204 @@
+#![cfg(feature = "wgsl_target_ext")]
+
+//! Validate built shaders against what a target profile supports
+
+use std::fmt::Display;
+use std::path::Path;
+
+use naga::valid::Capabilities;
+use wesl::{BasicSourceMap, ModulePath};
+
+use crate::WeslBuildExtension;
+use crate::artifacts;
+
+/// The profile a [`WgslTargetExtension`] validates shaders against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WgslTarget {
+    /// WebGPU without optional features
+    WebGpu,
+    /// The WebGPU compatibility mode, for devices with OpenGL ES 3.1 or D3D11 level hardware
+    ///
+    /// On top of WebGPU it has no cube array textures, `sample_index` and `sample_mask` built-ins,
+    /// `linear` or `sample` interpolation, or `rg32` storage textures.
+    WebGpuCompat,
+    /// wgpu on native with the naga capabilities of the enabled `wgpu::Features`,
+    /// any storage texture format is allowed
+    Native(Capabilities),
+}
+
+impl WgslTarget {
+    /// The naga capabilities of the profile
+    pub fn capabilities(&self) -> Capabilities {
+        match self {
+            WgslTarget::WebGpu => Capabilities::MULTISAMPLED_SHADING | Capabilities::CUBE_ARRAY_TEXTURES,
+            WgslTarget::WebGpuCompat => Capabilities::empty(),
+            WgslTarget::Native(capabilities) => *capabilities,
+        }
+    }
+
+    /// Whether storage textures of `format` are supported
+    pub fn supports_storage_format(&self, format: naga::StorageFormat) -> bool {
+        use naga::StorageFormat::*;
+
+        match self {
+            WgslTarget::Native(_) => true,
+            WgslTarget::WebGpu => WEBGPU_STORAGE_FORMATS.contains(&format),
+            WgslTarget::WebGpuCompat => WEBGPU_STORAGE_FORMATS.contains(&format)
+                && !matches!(format, Rg32Uint | Rg32Sint | Rg32Float),
+        }
+    }
+}
+
+impl Display for WgslTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WgslTarget::WebGpu => f.write_str("WebGPU"),
+            WgslTarget::WebGpuCompat => f.write_str("WebGPU compatibility mode"),
+            WgslTarget::Native(capabilities) => write!(f, "native with {capabilities:?}"),
+        }
+    }
+}
+
+/// The storage texture formats of WebGPU without optional features
+const WEBGPU_STORAGE_FORMATS: [naga::StorageFormat; 16] = {
+    use naga::StorageFormat::*;
+    [
+        Rgba8Unorm, Rgba8Snorm, Rgba8Uint, Rgba8Sint, Rgba16Uint, Rgba16Sint, Rgba16Float, R32Uint, R32Sint,
+        R32Float, Rg32Uint, Rg32Sint, Rg32Float, Rgba32Uint, Rgba32Sint, Rgba32Float,
+    ]
+};
+
+/// A shader uses what its target doesn't support
+#[derive(Debug, thiserror::Error)]
+#[error("`{mod_path}` is not supported by {target}:\n{}", .violations.join("\n"))]
+pub struct WgslTargetError {
+    pub mod_path: ModulePath,
+    pub target: WgslTarget,
+    pub violations: Vec<String>,
+}
+
+/// Fail the build when a built shader uses what the [`WgslTarget`] doesn't support,
+/// e.g. `f16` or cube array textures, instead of when the pipeline is created on a device that can't run it
+///
+/// The built shader is validated by naga with the capabilities of the target, then its storage texture formats,
+/// and for [`WebGpuCompat`](WgslTarget::WebGpuCompat) its entry point inputs and outputs, are checked.
+pub struct WgslTargetExtension {
+    target: WgslTarget,
+    /// Capabilities of optional features on top of the target's
+    extra_capabilities: Capabilities,
+}
+
+impl WgslTargetExtension {
+    pub fn new(target: WgslTarget) -> Self {
+        Self { target, extra_capabilities: Capabilities::empty() }
+    }
+
+    /// Allow `f16`, for devices with the `shader-f16` feature
+    pub fn with_f16(mut self, f16: bool) -> Self {
+        self.extra_capabilities.set(Capabilities::SHADER_FLOAT16, f16);
+        self
+    }
+
+    /// Allow `capabilities` on top of the target's, for its optional features
+    pub fn with_capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.extra_capabilities |= capabilities;
+        self
+    }
+
+    /// What `module` uses that the target doesn't support, `wgsl_source` is used for the naga errors
+    pub fn violations(&self, module: &naga::Module, wgsl_source: &str) -> Vec<String> {
+        let mut violations = Vec::new();
+        let mut validator = naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            self.target.capabilities() | self.extra_capabilities,
+        );
+        if let Err(error) = validator.validate(module) {
+            violations.push(error.emit_to_string(wgsl_source).trim_end().to_owned());
+        }
+
+        for (_, global) in module.global_variables.iter() {
+            if let naga::TypeInner::Image { class: naga::ImageClass::Storage { format, .. }, .. } = module.types[global.ty].inner
+                && !self.target.supports_storage_format(format)
+            {
+                violations.push(format!(
+                    "storage texture `{}` has the unsupported format {format:?}",
+                    global.name.as_deref().unwrap_or_default(),
+                ));
+            }
+        }
+
+        if self.target == WgslTarget::WebGpuCompat {
+            for entry_point in &module.entry_points {
+                let function = &entry_point.function;
+                let bindings = function.arguments.iter().map(|argument| (argument.ty, &argument.binding))
+                    .chain(function.result.iter().map(|result| (result.ty, &result.binding)));
+                for (ty, binding) in bindings {
+                    let members = match (&module.types[ty].inner, binding) {
+                        (naga::TypeInner::Struct { members, .. }, None) => members.iter().map(|member| &member.binding).collect(),
+                        (_, binding) => vec![binding],
+                    };
+                    violations.extend(members.into_iter().flatten()
+                        .filter_map(compat_violation)
+                        .map(|violation| format!("`{}` uses {violation}", entry_point.name)));
+                }
+            }
+        }
+
+        violations
+    }
+}
+
+/// What `binding` of an entry point uses that the WebGPU compatibility mode doesn't support
+fn compat_violation(binding: &naga::Binding) -> Option<&'static str> {
+    use naga::{Binding, BuiltIn, Interpolation, Sampling};
+
+    match binding {
+        Binding::BuiltIn(BuiltIn::SampleIndex) => Some("the `sample_index` built-in"),
+        Binding::BuiltIn(BuiltIn::SampleMask) => Some("the `sample_mask` built-in"),
+        Binding::Location { interpolation: Some(Interpolation::Linear), .. } => Some("`linear` interpolation"),
+        Binding::Location { sampling: Some(Sampling::Sample), .. } => Some("`sample` interpolation"),
+        _ => None,
+    }
+}
+
+impl<WeslResolver: wesl::Resolver> WeslBuildExtension<WeslResolver> for WgslTargetExtension {
+    fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+        "WgslTargetExtension".into()
+    }
+
+    fn reads_built_wgsl(&self) -> bool {
+        true
+    }
+
+    fn init_root(
+        &mut self,
+        _shader_path: &str,
+        _res: &mut wesl::Wesl<WeslResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    fn enter_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+    fn exit_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+
+    fn post_build(
+        &mut self,
+        mod_path: &ModulePath,
+        wgsl_built_path: &str,
+        _source_map: &Option<BasicSourceMap>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let wgsl_source = artifacts::read_to_string(wgsl_built_path)?;
+        let module = naga::front::wgsl::parse_str(&wgsl_source)?;
+        let violations = self.violations(&module, &wgsl_source);
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(Box::new(WgslTargetError {
+                mod_path: mod_path.clone(),
+                target: self.target,
+                violations,
+            }))
+        }
+    }
+}
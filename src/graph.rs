@@ -0,0 +1,310 @@
+use std::collections::{HashMap, HashSet};
+use std::{fs, path::{Path, PathBuf}};
+
+use itertools::Itertools;
+use wesl::ModulePath;
+use wesl::syntax::{ImportContent, PathOrigin, TranslationUnit};
+
+use crate::messages::{self, BuildMessage};
+use crate::{CaseCollisions, WeslBuildError, resolve_relative};
+use crate::platform::ShaderFile;
+use crate::walk::DirWalk;
+
+/// The modules in the shader dir and the modules each one imports
+///
+/// Imports are found in the `import` statements of the sources, imports of external packages
+/// and inline qualified paths (e.g. `package::util::f()`) are not included.
+#[derive(Debug, Clone, Default)]
+pub struct ModuleGraph {
+    /// sorted by module path
+    nodes: Vec<ModuleNode>,
+    index: HashMap<ModulePath, usize>,
+}
+
+#[derive(Debug, Clone)]
+struct ModuleNode {
+    mod_path: ModulePath,
+    source_path: PathBuf,
+    /// indices of the imported modules
+    imports: Vec<usize>,
+}
+
+impl ModuleGraph {
+    /// Find every module under `shader_root_path` and its imports, symlinked dirs are not followed
+    pub fn from_dir(shader_root_path: &Path) -> Result<Self, WeslBuildError> {
+        Self::from_walk(shader_root_path, DirWalk::default())
+    }
+
+    pub(crate) fn from_walk(shader_root_path: &Path, walk: DirWalk) -> Result<Self, WeslBuildError> {
+        let mut modules = source_modules(shader_root_path, walk)?;
+        modules.sort_by_cached_key(|(mod_path, _)| mod_path.to_string());
+
+        let index = modules.iter().enumerate()
+            .map(|(idx, (mod_path, _))| (mod_path.clone(), idx))
+            .collect::<HashMap<_, _>>();
+
+        let mut nodes = Vec::with_capacity(modules.len());
+        for (idx, (mod_path, source_path)) in modules.into_iter().enumerate() {
+            let source = fs::read_to_string(&source_path)?;
+            let mut imports = import_paths(&source).iter()
+                .filter_map(|import| resolve_import(&index, &mod_path, import))
+                .filter(|import| *import != idx)
+                .collect::<Vec<_>>();
+            imports.sort_unstable();
+            imports.dedup();
+
+            nodes.push(ModuleNode { mod_path, source_path, imports });
+        }
+
+        Ok(Self { nodes, index })
+    }
+
+    /// Every module, sorted by module path
+    pub fn modules(&self) -> impl Iterator<Item = &ModulePath> {
+        self.nodes.iter().map(|node| &node.mod_path)
+    }
+
+    /// Every `(importer, imported)` pair
+    pub fn edges(&self) -> impl Iterator<Item = (&ModulePath, &ModulePath)> {
+        self.nodes.iter().flat_map(|node| node.imports.iter()
+            .map(|import| (&node.mod_path, &self.nodes[*import].mod_path)))
+    }
+
+    pub fn contains(&self, mod_path: &ModulePath) -> bool {
+        self.index.contains_key(mod_path)
+    }
+
+    /// The source file of `mod_path`
+    pub fn source_path(&self, mod_path: &ModulePath) -> Option<&Path> {
+        self.node(mod_path).map(|node| node.source_path.as_path())
+    }
+
+    /// The modules `mod_path` imports directly
+    pub fn imports(&self, mod_path: &ModulePath) -> Vec<&ModulePath> {
+        self.node(mod_path).into_iter()
+            .flat_map(|node| &node.imports)
+            .map(|import| &self.nodes[*import].mod_path)
+            .collect()
+    }
+
+    /// The modules that import `mod_path` directly
+    pub fn importers(&self, mod_path: &ModulePath) -> Vec<&ModulePath> {
+        let Some(&idx) = self.index.get(mod_path) else { return Vec::new() };
+
+        self.nodes.iter()
+            .filter(|node| node.imports.contains(&idx))
+            .map(|node| &node.mod_path)
+            .collect()
+    }
+
+    /// The modules `mod_path` depends on directly or through other modules
+    pub fn dependencies(&self, mod_path: &ModulePath) -> Vec<&ModulePath> {
+        self.reachable(mod_path, |node| node.imports.clone())
+    }
+
+    /// The modules that depend on `mod_path` directly or through other modules,
+    /// i.e. the modules to rebuild when it changes
+    pub fn dependents(&self, mod_path: &ModulePath) -> Vec<&ModulePath> {
+        self.reachable(mod_path, |node| {
+            let idx = self.index[&node.mod_path];
+            self.nodes.iter().enumerate()
+                .filter(|(_, importer)| importer.imports.contains(&idx))
+                .map(|(importer_idx, _)| importer_idx)
+                .collect()
+        })
+    }
+
+    fn node(&self, mod_path: &ModulePath) -> Option<&ModuleNode> {
+        self.index.get(mod_path).map(|idx| &self.nodes[*idx])
+    }
+
+    /// The modules reachable from `mod_path` following `next`, sorted by module path
+    fn reachable(&self, mod_path: &ModulePath, next: impl Fn(&ModuleNode) -> Vec<usize>) -> Vec<&ModulePath> {
+        let Some(&start) = self.index.get(mod_path) else { return Vec::new() };
+        let mut visited = vec![false; self.nodes.len()];
+        visited[start] = true;
+        let mut to_visit = next(&self.nodes[start]);
+
+        while let Some(idx) = to_visit.pop() {
+            if !visited[idx] {
+                visited[idx] = true;
+                to_visit.extend(next(&self.nodes[idx]));
+            }
+        }
+        visited[start] = false;
+
+        visited.iter().enumerate()
+            .filter(|(_, visited)| **visited)
+            .map(|(idx, _)| &self.nodes[idx].mod_path)
+            .collect()
+    }
+}
+
+/// The module imported by `import` from `mod_path`, the longest prefix of the import that is a module
+fn resolve_import(index: &HashMap<ModulePath, usize>, mod_path: &ModulePath, import: &ModulePath) -> Option<usize> {
+    let import = match &import.origin {
+        // an external package, or a module in the root
+        PathOrigin::Package(name) => ModulePath::new(
+            PathOrigin::Absolute,
+            std::iter::once(name).chain(&import.components).cloned().collect::<Vec<_>>(),
+        ),
+        _ => resolve_relative(mod_path, import)?,
+    };
+
+    (1..=import.components.len()).rev()
+        .map(|len| ModulePath::new(PathOrigin::Absolute, import.components[..len].to_vec()))
+        .find_map(|prefix| index.get(&prefix).copied())
+}
+
+/// The full paths of every `import` statement in `source`, with collections expanded and aliases removed,
+/// none if it doesn't parse, the build reports the error
+fn import_paths(source: &str) -> Vec<ModulePath> {
+    let Ok(unit) = source.parse::<TranslationUnit>() else {
+        return Vec::new();
+    };
+
+    let mut paths = Vec::new();
+    for import in &unit.imports {
+        let (origin, prefix) = match &import.path {
+            Some(path) => (path.origin.clone(), path.components.clone()),
+            None => (PathOrigin::Absolute, Vec::new()),
+        };
+        expand_import(&origin, prefix, &import.content, &mut paths);
+    }
+    paths
+}
+
+/// Expand the `content` of an import after `prefix` into full paths, through nested collections
+fn expand_import(origin: &PathOrigin, prefix: Vec<String>, content: &ImportContent, paths: &mut Vec<ModulePath>) {
+    match content {
+        ImportContent::Item(item) => {
+            let mut components = prefix;
+            components.push(item.ident.name().to_string());
+            paths.push(ModulePath::new(origin.clone(), components));
+        }
+        ImportContent::Collection(imports) => {
+            for import in imports {
+                expand_import(origin, [prefix.as_slice(), &import.path].concat(), &import.content, paths);
+            }
+        }
+    }
+}
+
+/// Every shader file the walk builds in the tree at `shader_root_path` with its module path,
+/// the override of a shader for the platform of the walk replaces it
+pub(crate) fn source_modules(
+    shader_root_path: &Path,
+    mut walk: DirWalk,
+) -> Result<Vec<(ModulePath, PathBuf)>, WeslBuildError> {
+    let mut modules = Vec::new();
+    walk_dir(shader_root_path, shader_root_path, &mut walk, &mut modules)?;
+    check_collisions(shader_root_path, &modules)?;
+    Ok(modules)
+}
+
+/// Fail with [`WeslBuildError::ModuleCollision`] if two shader files have the same module path,
+/// or a shader file has the module path of a dir of shaders
+fn check_collisions(shader_root_path: &Path, modules: &[(ModulePath, PathBuf)]) -> Result<(), WeslBuildError> {
+    // the read order of dirs is not stable, so the files are reported in the order of their paths
+    let sorted = modules.iter().sorted_by(|(_, a), (_, b)| a.cmp(b)).collect::<Vec<_>>();
+
+    let mut paths = HashMap::with_capacity(modules.len());
+    for (mod_path, path) in &sorted {
+        if let Some(first) = paths.insert(mod_path, path) {
+            return Err(WeslBuildError::ModuleCollision { first: first.clone(), second: path.clone() });
+        }
+    }
+    for (mod_path, _) in &sorted {
+        for len in 1..mod_path.components.len() {
+            let dir_mod_path = ModulePath::new(PathOrigin::Absolute, mod_path.components[..len].to_vec());
+            if let Some(file) = paths.get(&dir_mod_path) {
+                return Err(WeslBuildError::ModuleCollision {
+                    first: (*file).clone(),
+                    second: shader_root_path.join(mod_path.components[..len].iter().collect::<PathBuf>()),
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn walk_dir(
+    shader_root_path: &Path,
+    dir: &Path,
+    walk: &mut DirWalk,
+    modules: &mut Vec<(ModulePath, PathBuf)>,
+) -> Result<(), WeslBuildError> {
+    walk.enter(dir)?;
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if walk.is_dir(&path)? {
+            walk_dir(shader_root_path, &path, walk, modules)?;
+        } else if path.extension().is_some_and(|ext| ext == "wesl" || ext == "wgsl") {
+            // the globs match the file, like in the build
+            if !walk.builds_file(&crate::walk::relative_path(&path, shader_root_path)?) {
+                continue;
+            }
+            let module_file = match ShaderFile::of(&path, walk.platform()) {
+                ShaderFile::Shader => path.clone(),
+                ShaderFile::Override { base } => base,
+                ShaderFile::Overridden | ShaderFile::OtherPlatform => continue,
+            };
+            // files with invalid names fail when they are built
+            let Ok(components) = crate::module_components(&module_file, shader_root_path, walk.file_names()) else { continue };
+            modules.push((ModulePath::new(PathOrigin::Absolute, components), path));
+        }
+    }
+    walk.exit();
+
+    Ok(())
+}
+
+/// Fail with [`WeslBuildError::CaseCollision`] or warn, as set by `level`, if the module paths of two of `modules`
+/// or of their dirs differ only in case
+pub(crate) fn check_case_collisions(
+    shader_root_path: &Path,
+    modules: &[(ModulePath, PathBuf)],
+    level: CaseCollisions,
+) -> Result<(), WeslBuildError> {
+    if level == CaseCollisions::Allow {
+        return Ok(());
+    }
+
+    // the lowercase path of every module and dir, with its path as written and its file or dir
+    let mut seen = HashMap::<Vec<String>, (&[String], PathBuf)>::new();
+    let mut reported = HashSet::new();
+    for (mod_path, path) in modules.iter().sorted_by(|(_, a), (_, b)| a.cmp(b)) {
+        for len in 1..=mod_path.components.len() {
+            let components = &mod_path.components[..len];
+            let lowercase = components.iter().map(|component| component.to_lowercase()).collect::<Vec<_>>();
+            let file = match len == mod_path.components.len() {
+                true => path.clone(),
+                false => shader_root_path.join(components.iter().collect::<PathBuf>()),
+            };
+
+            match seen.get(&lowercase) {
+                Some((first_components, _)) if *first_components == components => (),
+                Some((_, first)) => {
+                    let (first, second) = (first.clone(), file);
+                    if level == CaseCollisions::Deny {
+                        return Err(WeslBuildError::CaseCollision { first, second });
+                    }
+                    // a dir is reported once, not for every shader in it
+                    if reported.insert(lowercase) {
+                        messages::report(&BuildMessage::warning(format!(
+                            "`{}` and `{}` differ only in case, they collide on case-insensitive filesystems like macOS and Windows",
+                            first.display(), second.display(),
+                        )))?;
+                    }
+                    break;
+                }
+                None => {
+                    seen.insert(lowercase, (components, file));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -1,16 +1,88 @@
 #![doc = include_str!("../README.md")]
 
 use std::{
+    collections::BTreeSet,
     ffi::OsStr,
+    hash::{DefaultHasher, Hash, Hasher},
     path::{Path, PathBuf},
     fs,
+    sync::{Arc, Mutex},
+    time::Duration,
 };
 
 use itertools::Itertools;
-use wesl::{BasicSourceMap, Mangler, ModulePath, Resolver, StandardResolver, Wesl};
+use wesl::{BasicSourceMap, Mangler, ManglerKind, ModulePath, Resolver, Wesl};
+/// The `wesl` wesl_build is built with, see [`prelude`]
+pub use wesl;
 
+pub mod prelude;
 pub mod extension;
-use extension::{WeslBuildExtension, extension_error};
+pub mod diagnostics;
+pub mod scaffold;
+pub mod artifacts;
+pub mod package;
+pub mod lock;
+pub mod messages;
+use messages::BuildMessage;
+pub mod names;
+use names::FileNames;
+#[cfg(feature = "workspace")]
+pub mod workspace;
+pub use lock::LOCK_FILE_NAME;
+use lock::BuildLock;
+use artifacts::{FsSink, MemorySink, SinkGuard};
+use extension::{DISABLE_EXT_ENV, WeslBuildExtension, extension_error};
+
+mod config;
+pub use config::{CaseCollisions, TargetConfig, WeslBuildConfig, build_out_dir, current_profile, is_check, is_verify};
+
+mod plan;
+pub use plan::{BuildPlan, PlannedShader};
+
+mod context;
+pub use context::{BuildContext, with_build_context};
+use context::ContextGuard;
+
+mod metadata;
+#[cfg(feature = "config_file")]
+mod config_file;
+
+mod graph;
+pub use graph::ModuleGraph;
+
+mod walk;
+
+mod dir_lock;
+pub use dir_lock::DIR_LOCK_NAME;
+use dir_lock::{DirLock, LockTimeoutGuard};
+
+mod platform;
+pub use platform::{current_platform, target_platform};
+use platform::{PlatformGuard, ShaderFile};
+use walk::DirWalk;
+
+mod order;
+
+pub mod resolver;
+pub use resolver::BuildResolver;
+
+mod timings;
+use timings::Timings;
+pub use timings::{TIMINGS_FILE_NAME, TIMINGS_TRACE_FILE_NAME};
+
+mod content_hash;
+pub use content_hash::{HASHED_DIR_NAME, HASHED_JSON_NAME, HASHED_TABLE_NAME};
+
+#[cfg(feature = "naga")]
+mod naga_module;
+#[cfg(feature = "naga")]
+pub use naga_module::NagaModule;
+
+#[cfg(feature = "testing")]
+pub mod testing;
+
+#[cfg(feature = "tokio")]
+pub use extension::async_extension::build_shader_dir_blocking;
 
 #[cfg(test)]
 mod tests;
@@ -22,12 +94,115 @@ pub enum WeslBuildError {
     IoErr(#[from] std::io::Error),
     #[error(transparent)]
     StripPrefixErr(#[from] std::path::StripPrefixError),
-    #[error("Extension {} error: {}", .extension_name, .error)]
+    /// The `hook` of an extension failed, while building `mod_path` if the hook runs per shader
+    #[error(
+        "Extension {extension_name} error in `{hook}`{}: {error}",
+        .mod_path.as_ref().map(|mod_path| format!(" of `{mod_path}`")).unwrap_or_default(),
+    )]
     ExtensionErr {
         extension_name: String,
+        hook: &'static str,
+        mod_path: Option<ModulePath>,
         error: Box<dyn std::error::Error>,
-        // source: ExtensionErrorSource,
     },
+    #[error("The {0:?} mangler is not supported by wesl_build")]
+    UnsupportedMangler(ManglerKind),
+    #[error("failed to build WESL shader `{mod_path}`.\n{error}")]
+    CompileErr {
+        mod_path: ModulePath,
+        error: Box<wesl::Error>,
+    },
+    /// An error while building or processing `mod_path`, only used with [`WeslBuildConfig::continue_on_error`]
+    #[error("`{mod_path}`: {error}")]
+    ModuleErr {
+        mod_path: ModulePath,
+        error: Box<WeslBuildError>,
+    },
+    /// Neither [`WeslBuildConfig::out_dir`] nor cargo's `OUT_DIR` is set, i.e. not run from a build script
+    #[error("OUT_DIR is not set, run the build from a build script or set `WeslBuildConfig::out_dir`")]
+    MissingOutDir,
+    /// A hook that needs the [`BuildContext`] was called outside of a build, e.g. directly instead of by [`build_shader_dir`]
+    #[error("an extension hook that needs the build context was called outside of a build")]
+    NoBuildContext,
+    /// The name of a shader file or dir can't be used as a module name, e.g. it is empty or contains a `.`,
+    /// or isn't an identifier with [`FileNames::Reject`]
+    #[error("`{}` can't be used as a module name", .path.display())]
+    InvalidFileName { path: PathBuf },
+    /// Two shader files have the same module path, e.g. `foo.wgsl` and `foo.wesl`,
+    /// or a shader file has the module path of a dir of shaders, e.g. `foo.wesl` and `foo/`
+    #[error("`{}` and `{}` have the same module path, their outputs would overwrite each other, rename one of them", .first.display(), .second.display())]
+    ModuleCollision { first: PathBuf, second: PathBuf },
+    /// The module paths of two shaders or dirs differ only in case, e.g. `Blur.wesl` and `blur.wesl`,
+    /// see [`WeslBuildConfig::case_collisions`]
+    #[error(
+        "`{}` and `{}` differ only in case, they collide on case-insensitive filesystems like macOS and Windows, \
+        rename one of them or allow it with `WeslBuildConfig::case_collisions`",
+        .first.display(), .second.display(),
+    )]
+    CaseCollision { first: PathBuf, second: PathBuf },
+    #[error("`{}` is not valid UTF-8", .path.display())]
+    NonUtf8Path { path: PathBuf },
+    /// A symlinked dir points to one of the dirs it is in, only with [`WeslBuildConfig::follow_symlinks`]
+    #[error("the symlink `{}` points back up the shader tree", .path.display())]
+    SymlinkCycle { path: PathBuf },
+    #[error("`{}` is nested deeper than the max depth of {max_depth} dirs", .path.display())]
+    MaxDepthExceeded { path: PathBuf, max_depth: usize },
+    /// The built WGSL of `mod_path` could not be parsed by naga or written back after it was modified
+    #[cfg(feature = "naga")]
+    #[error("failed to process the naga module of `{mod_path}`: {error}")]
+    NagaErr {
+        mod_path: ModulePath,
+        error: Box<dyn std::error::Error>,
+    },
+    /// The [`must_run_after`](`WeslBuildExtension::must_run_after`) and [`must_run_before`](`WeslBuildExtension::must_run_before`)
+    /// of these extensions contradict each other
+    #[error("the extensions {} can't be ordered, they must each run after another", .extensions.join(", "))]
+    ExtensionOrderErr { extensions: Vec<String> },
+    /// An extension needs a [version of the extension API](`extension::compat::ExtensionApiVersion`) this wesl_build lacks
+    #[error(
+        "the extension {extension_name} needs version {version} of the extension API, wesl_build has version {}",
+        extension::compat::ExtensionApiVersion::CURRENT,
+    )]
+    UnsupportedExtensionApi { extension_name: String, version: extension::compat::ExtensionApiVersion },
+    /// An extension [renamed](`WeslBuildExtension::artifact_name`) the artifact of `mod_path` to a name that isn't a relative path
+    #[error("the extension {extension_name} named the artifact of `{mod_path}` `{name}`, which isn't a `/` separated relative path")]
+    InvalidArtifactName { extension_name: String, mod_path: ModulePath, name: String },
+    /// Two shaders would be built to the same artifact, e.g. an extension renamed them to the same name
+    #[error("the shaders `{first}` and `{second}` are both built to the artifact `{name}`")]
+    ArtifactCollision { name: String, first: ModulePath, second: ModulePath },
+    /// The dependency of a [`PackageSource::links`](`package::PackageSource::links`) package did not export its shaders
+    #[error("the shader package `{name}` was not found, `{env}` is not set, is the crate a dependency that calls `export_shader_package`?")]
+    MissingPackage { name: String, env: String },
+    #[error("`{name}` can't be used as a package name")]
+    InvalidPackageName { name: String },
+    /// The lock of a [check](`WeslBuildConfig::check`) differs from its [`WeslBuildConfig::lock_file`], see [`verify_shader_dir`]
+    #[error("the shader build drifted from `{}`, rebuild to update it:\n{}", .lock_file.display(), .drift.join("\n"))]
+    LockDrift { lock_file: PathBuf, drift: Vec<String> },
+    /// Another build held the lock on an output dir for longer than [`WeslBuildConfig::lock_timeout`]
+    #[error(
+        "another build of the shaders held `{}` for more than {}s, if none is running raise `WeslBuildConfig::lock_timeout`",
+        .path.display(), .timeout.as_secs(),
+    )]
+    LockTimeout { path: PathBuf, timeout: Duration },
+    /// The `[package.metadata.wesl_build]` section of `package` is invalid, see [`WeslBuildConfig::from_cargo_metadata`]
+    #[error("invalid `[package.metadata.wesl_build]` in `{package}`: {message}")]
+    MetadataConfigErr { package: String, message: String },
+    /// A `wesl_build.toml` is invalid, see [`WeslBuildConfig::from_file`]
+    #[cfg(feature = "config_file")]
+    #[error("invalid config file `{}`: {message}", .path.display())]
+    ConfigFileErr { path: PathBuf, message: String },
+    /// `cargo metadata` failed or its output is not the JSON of a workspace, see [`workspace::workspace_shaders`]
+    #[cfg(feature = "workspace")]
+    #[error("failed to read the workspace with `cargo metadata`: {0}")]
+    CargoMetadataErr(String),
+
+    /// The shaders of a workspace member failed to build, see [`workspace::build_workspace_shaders`]
+    #[cfg(feature = "workspace")]
+    #[error("failed to build the shaders of `{package}`: {error}")]
+    WorkspaceMemberErr { package: String, error: Box<WeslBuildError> },
+    /// Every error of a [`WeslBuildConfig::continue_on_error`] build
+    #[error("{} errors:\n{}", .0.len(), .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n"))]
+    Multiple(Vec<WeslBuildError>),
 }
 
 // #[derive(Debug, thiserror::Error)]
@@ -107,73 +282,569 @@ pub fn init_build_logger() {
 pub fn build_shader_dir(
     shader_path: &str,
     wesl_config: wesl::CompileOptions,
-    extensions: &mut [Box<dyn WeslBuildExtension<StandardResolver>>],
+    extensions: &mut [Box<dyn WeslBuildExtension<BuildResolver>>],
 ) -> Result<(), WeslBuildError> {
-    let mut wesl = Wesl::new(shader_path);
-    wesl.set_options(wesl_config);
+    build_shader_dir_with_config(
+        &WeslBuildConfig::new(shader_path).compile_options(wesl_config),
+        extensions,
+    )?;
+
+    Ok(())
+}
+
+/// [`build_shader_dir`] with a full [`WeslBuildConfig`], returns every shader that was built
+///
+/// In a [dry run](`WeslBuildConfig::dry_run`) nothing is built or written, only the plan is returned.
+///
+/// ## Example
+/// In `build.rs`:
+/// ```
+/// use wesl_build::{build_shader_dir_with_config, extensions, WeslBuildConfig};
+///
+/// build_shader_dir_with_config(
+///     # &WeslBuildConfig::new("test/src/shaders")
+///     # /*
+///     &WeslBuildConfig::new("src/shaders")
+///     # */
+///         .mangler(wesl::ManglerKind::Hash),
+///     extensions![/* Extension::new() */],
+/// ).expect("Building shaders failed");
+/// ```
+pub fn build_shader_dir_with_config(
+    config: &WeslBuildConfig,
+    extensions: &mut [Box<dyn WeslBuildExtension<BuildResolver>>],
+) -> Result<BuildPlan, WeslBuildError> {
+    let _json_output = messages::JsonOutputGuard::set(config.json_diagnostics.clone())?;
+    let result = build_with_config(config, extensions);
+    if let Err(error) = &result {
+        messages::report_error(error, Path::new(&config.shader_path))?;
+    }
+    result
+}
+
+fn build_with_config(
+    config: &WeslBuildConfig,
+    extensions: &mut [Box<dyn WeslBuildExtension<BuildResolver>>],
+) -> Result<BuildPlan, WeslBuildError> {
+    let target_config = config.with_target_features();
+    let config = target_config.as_ref().unwrap_or(config);
+    let extensions = without_disabled(extensions, &config.disabled_extensions());
+
+    let shader_path = config.shader_path.as_str();
+    let (mangler_name, _) = mangler_of(config.mangler)
+        .ok_or(WeslBuildError::UnsupportedMangler(config.mangler))?;
+
+    let _platform = PlatformGuard::set(config.build_platform());
+    let _lock_timeout = LockTimeoutGuard::set(config.lock_timeout);
+    extension::compat::check_api_versions(extensions)?;
+    order::order_extensions(extensions)?;
+
+    // checks and dry runs write nothing, so they can run next to another build
+    let _out_dir_lock = match config.output_dir() {
+        Some(out_dir) if !config.check && !config.dry_run => Some(DirLock::acquire(&out_dir.join(DIR_LOCK_NAME))?),
+        _ => None,
+    };
+
+    // check builds keep the artifacts in memory and print nothing for cargo
+    let check_config = config.check.then(|| {
+        config.clone()
+            .out_dir(config.output_dir().unwrap_or_default())
+            .artifact_sink(Arc::new(Mutex::new(MemorySink::new())))
+            .cargo_instructions(false)
+    });
+    let config = check_config.as_ref().unwrap_or(config);
+    let _sink = SinkGuard::set(config.artifact_sink.clone().unwrap_or_else(|| Arc::new(Mutex::new(FsSink))));
+    let _context = ContextGuard::set(config);
+
+    let mut wesl = resolver::build_wesl(config)?;
     // todo allow `use_sourcemap` override
 
+    let mut plan = BuildPlan::default();
+    let mut errors = Vec::new();
+    let mut timings = Timings::of_config(config);
+    // before a dry run returns, so it fails on colliding modules like the build
+    let graph = ModuleGraph::from_walk(Path::new(shader_path), DirWalk::of_config(config))?;
+    if config.dry_run {
+        build_all_in_dir::<BuildResolver>(
+            config, Path::new(shader_path),
+            &mut wesl, extensions,
+            &mut DirWalk::of_config(config), &mut plan, &mut errors, &mut timings,
+        )?;
+        return Ok(plan);
+    }
+
     for ext in extensions.iter_mut() {
         #[cfg(feature = "logging")]
         log::debug!("initializing extension: {}", ext.name());
 
-        ext.init_root(shader_path, &mut wesl)
-            .map_err(|e| extension_error(ext.as_ref(), e))?;
+        timings.hook(ext.as_mut(), "init_root", None, |ext| ext.init_root(shader_path, &mut wesl))
+            .map_err(|e| extension_error(ext.as_ref(), "init_root", None, e))?;
     }
 
+    for ext in extensions.iter_mut() {
+        timings.hook(ext.as_mut(), "on_graph", None, |ext| ext.on_graph(&graph))
+            .map_err(|e| extension_error(ext.as_ref(), "on_graph", None, e))?;
+    }
+
+    preprocess(&graph, wesl.resolver(), extensions, &mut timings)?;
+
     // todo delete all in BINDING_ROOT_PATH before regen add some cashing(if wgsl_to_wgpu does not have it built-in),
     // so bindings for deleted shaders are removed
 
-    build_all_in_dir::<StandardResolver>(
-        shader_path, Path::new(shader_path),
-        &wesl, extensions,
-        &wesl,
+    build_all_in_dir::<BuildResolver>(
+        config, Path::new(shader_path),
+        &mut wesl, extensions,
+        &mut DirWalk::of_config(config), &mut plan, &mut errors, &mut timings,
     )?;
+    // virtual modules that replace a shader file were built with it
+    for mod_path in wesl.resolver().virtual_modules() {
+        if !graph.contains(&mod_path) {
+            build_module(config, mod_path, PathBuf::new(), &mut wesl, extensions, &mut plan, &mut errors, &mut timings)?;
+        }
+    }
 
     for ext in extensions.iter_mut() {
-        ext.exit_root(shader_path, &wesl)
-            .map_err(|e| extension_error(ext.as_ref(), e))?;
+        let result = timings.hook(ext.as_mut(), "exit_root", None, |ext| ext.exit_root(shader_path, &wesl))
+            .map_err(|e| extension_error(ext.as_ref(), "exit_root", None, e));
+        collect_error(config, &mut errors, None, result)?;
+    }
+    timings.report(config)?;
+    warn_large_artifacts(config, &plan)?;
+
+    // after every hook, so the hashes are of the final shaders
+    if config.content_hashed_names && let Some(out_dir) = config.output_dir() {
+        content_hash::hash_artifacts(&out_dir, &mut plan)?;
+    }
+
+    if errors.is_empty() && let Some(lock_file) = &config.lock_file {
+        let lock = BuildLock::of_build(config, &plan, mangler_name)?;
+        if !config.check {
+            artifacts::write_if_changed(lock_file, lock.to_string().as_bytes())?;
+        } else {
+            let locked = match fs::read_to_string(lock_file) {
+                Ok(locked) => BuildLock::parse(&locked),
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => BuildLock::default(),
+                Err(error) => return Err(error.into()),
+            };
+            let drift = lock.drift_from(&locked);
+            if !drift.is_empty() {
+                return Err(WeslBuildError::LockDrift { lock_file: lock_file.clone(), drift });
+            }
+        }
+    }
+
+    // the macro runs with a different working dir, so the root must be absolute
+    let shader_root = fs::canonicalize(shader_path)?;
+    let shaders_hash = emit_rerun_if_changed(config, &shader_root, &plan)?;
+    // a failed build keeps the manifest of the last good one, it's only written once every extension succeeded
+    if !config.check && errors.is_empty() {
+        write_build_manifest(config, &shader_root, mangler_name, shaders_hash, &plan)?;
+        write_output_index(config, &plan)?;
     }
 
-    // output shader_path to OUT_DIR/wesl_build_tree.path
-    // fs::write(
-    //     PathBuf::from(std::env::var_os("OUT_DIR").expect("wesl_build must be run in build.rs or in an env with the OUT_DIR environment variable set")).join("wesl_build_tree.path"),
-    //     shader_path,
-    // )?;
-    // This env var should only used by wesl_build_import's derive macro after build scripts are run
-    unsafe { std::env::set_var("WESL_BUILD_DIR_ROOT_PATH", shader_path) };
+    // These env vars are only used by wesl_build_import's macro in the crate being built
+    if config.cargo_instructions {
+        println!("cargo::rustc-env=WESL_BUILD_DIR_ROOT_PATH={}", shader_root.display());
+        println!("cargo::rustc-env=WESL_BUILD_MANGLER={mangler_name}");
+        println!("cargo::rustc-env=WESL_BUILD_LAYOUT={}", layout_name(config));
+        if let Some(shaders_hash) = shaders_hash {
+            println!("cargo::rustc-env=WESL_BUILD_SHADERS_HASH={shaders_hash:016x}");
+        }
+        if let Some(platform) = config.build_platform() {
+            println!("cargo::rustc-env=WESL_BUILD_PLATFORM={platform}");
+        }
+        if config.file_names != FileNames::Verbatim {
+            println!("cargo::rustc-env=WESL_BUILD_FILE_NAMES={}", config.file_names.as_str());
+        }
+    }
+
+    plan.writes = artifacts::write_stats();
+    #[cfg(feature = "logging")]
+    log::info!(
+        "built {} shaders, {} files written, {} up to date",
+        plan.shaders.len(), plan.writes.written, plan.writes.up_to_date,
+    );
+
+    if errors.is_empty() {
+        Ok(plan)
+    } else {
+        Err(WeslBuildError::Multiple(errors))
+    }
+}
+
+/// `extensions` without the ones named in `disabled`, which are moved behind the others
+fn without_disabled<'e>(
+    extensions: &'e mut [Box<dyn WeslBuildExtension<BuildResolver>>],
+    disabled: &[&str],
+) -> &'e mut [Box<dyn WeslBuildExtension<BuildResolver>>] {
+    let mut enabled = 0;
+    for idx in 0..extensions.len() {
+        if disabled.contains(&extensions[idx].name().as_ref()) {
+            #[cfg(feature = "logging")]
+            log::info!("{} is disabled for this target", extensions[idx].name());
+            continue;
+        }
+        extensions.swap(enabled, idx);
+        enabled += 1;
+    }
+    &mut extensions[..enabled]
+}
+
+/// [`build_shader_dir`] as a [check](WeslBuildConfig::check), compiles every shader and runs all extensions
+/// without writing anything to `OUT_DIR` or your sources
+///
+/// Every shader is built even if some fail, all errors are returned together.
+///
+/// ## Example
+/// In a test or CI job:
+/// ```
+/// use wesl_build::{build_shader_dir_check, extensions};
+///
+/// build_shader_dir_check(
+///     # "test/src/shaders",
+///     # /*
+///     "src/shaders",
+///     # */
+///     wesl::CompileOptions::default(),
+///     extensions![/* Extension::new() */],
+/// ).expect("shaders are invalid");
+/// ```
+pub fn build_shader_dir_check(
+    shader_path: &str,
+    wesl_config: wesl::CompileOptions,
+    extensions: &mut [Box<dyn WeslBuildExtension<BuildResolver>>],
+) -> Result<BuildPlan, WeslBuildError> {
+    build_shader_dir_with_config(
+        &WeslBuildConfig::new(shader_path)
+            .compile_options(wesl_config)
+            .continue_on_error(true)
+            .check(true),
+        extensions,
+    )
+}
+
+/// Check that building the shaders of `config` would give the lock in its [`lock_file`](WeslBuildConfig::lock_file),
+/// [`LOCK_FILE_NAME`] in the crate dir if it is not set, without writing anything
+///
+/// Fails with [`WeslBuildError::LockDrift`] listing every entry that changed, e.g. a shader, the wesl version,
+/// or a [generated file](WeslBuildConfig::lock_generated) that no longer matches its shaders.
+///
+/// ## Example
+/// In a test or CI job:
+/// ```no_run
+/// use wesl_build::{WeslBuildConfig, verify_shader_dir, extensions};
+///
+/// verify_shader_dir(
+///     &WeslBuildConfig::new("src/shaders").lock_generated("src/bindings"),
+///     extensions![/* Extension::new() */],
+/// ).expect("the shaders changed since the last build");
+/// ```
+pub fn verify_shader_dir(
+    config: &WeslBuildConfig,
+    extensions: &mut [Box<dyn WeslBuildExtension<BuildResolver>>],
+) -> Result<BuildPlan, WeslBuildError> {
+    let lock_file = config.lock_file.clone().unwrap_or_else(|| {
+        std::env::var_os("CARGO_MANIFEST_DIR").map(PathBuf::from).unwrap_or_default().join(LOCK_FILE_NAME)
+    });
+    build_shader_dir_with_config(&config.clone().lock_file(lock_file).check(true), extensions)
+}
+
+/// Collect the error of `result` with [`WeslBuildConfig::continue_on_error`], otherwise return it
+///
+/// Errors are wrapped in [`WeslBuildError::ModuleErr`] with `mod_path`, unless they already have the module path
+fn collect_error(
+    config: &WeslBuildConfig,
+    errors: &mut Vec<WeslBuildError>,
+    mod_path: Option<&ModulePath>,
+    result: Result<(), WeslBuildError>,
+) -> Result<(), WeslBuildError> {
+    match result {
+        Err(error) if config.continue_on_error => {
+            errors.push(match (mod_path, error) {
+                (_, error @ WeslBuildError::CompileErr { .. }) | (None, error) => error,
+                (Some(mod_path), error) => WeslBuildError::ModuleErr {
+                    mod_path: mod_path.clone(),
+                    error: Box::new(error),
+                },
+            });
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+/// The name of the manifest `wesl_build_import` reads when `WESL_BUILD_DIR_ROOT_PATH` is not set
+///
+/// !! keep in sync with `BUILD_MANIFEST_NAME` in wesl_build_import !!
+pub const BUILD_MANIFEST_NAME: &str = "wesl_build.manifest";
+
+/// The dir of the output dir the built shaders are written to, at their module paths, e.g. `wesl/passes/blur.wgsl`
+///
+/// !! keep in sync with `OUTPUT_DIR_NAME` in wesl_build_import !!
+pub const OUTPUT_DIR_NAME: &str = "wesl";
+
+/// The index of the built shaders in [`OUTPUT_DIR_NAME`], a `module::path=path/of/file.wgsl` line per shader,
+/// the path relative to the index
+pub const OUTPUT_INDEX_NAME: &str = "index.txt";
+
+/// The name of the output layout of `config` as reported to `include_wesl!`
+///
+/// !! keep in sync with the layouts supported by `include_wesl!` in wesl_build_import !!
+fn layout_name(config: &WeslBuildConfig) -> &'static str {
+    if config.flat_output { "flat" } else { "tree" }
+}
+
+/// The name of the built artifact of `mod_path`, its path in the output dir without the `.wgsl`
+///
+/// !! keep in sync with the paths of `include_wesl!` in wesl_build_import !!
+fn artifact_name(config: &WeslBuildConfig, mod_path: &ModulePath, mangled_name: &str) -> String {
+    if config.flat_output {
+        mangled_name.to_owned()
+    } else {
+        format!("{OUTPUT_DIR_NAME}/{}", mod_path.components.join("/"))
+    }
+}
+
+/// The name of the built artifact of `mod_path` in the layout of `config`, unless an extension renames it
+fn default_artifact_name(config: &WeslBuildConfig, mod_path: &ModulePath) -> String {
+    // !! keep in sync with mangling in wesl_build_import !!
+    let (_, name_mangler) = mangler_of(config.mangler)
+        .expect("the mangler is checked before building");
+    let name = mod_path.components.last().map(String::as_str).unwrap_or_default();
+    artifact_name(config, mod_path, &name_mangler.mangle(mod_path, name))
+}
+
+/// The [`artifact_name`](`WeslBuildExtension::artifact_name`) the first extension gives `mod_path`,
+/// checked to be a relative path
+fn renamed_artifact(
+    mod_path: &ModulePath,
+    extensions: &[Box<dyn WeslBuildExtension<BuildResolver>>],
+) -> Result<Option<String>, WeslBuildError> {
+    let Some((ext, name)) = extensions.iter().find_map(|ext| ext.artifact_name(mod_path).map(|name| (ext, name))) else {
+        return Ok(None);
+    };
+
+    let is_relative_path = name.split('/').all(|component| !component.is_empty() && component != "." && component != "..")
+        && !name.contains(['\\', ':']);
+    if !is_relative_path {
+        return Err(WeslBuildError::InvalidArtifactName { extension_name: ext.name().into_owned(), mod_path: mod_path.clone(), name });
+    }
+
+    Ok(Some(name))
+}
+
+/// The path of the built artifact `artifact_name` in `out_dir`, its `/` separated components joined with the
+/// platform's separator
+///
+/// `include_wesl!` joins `OUT_DIR` and the name with `/`, which names the same file on every platform.
+pub(crate) fn artifact_path(out_dir: &Path, artifact_name: &str) -> PathBuf {
+    let mut path = out_dir.to_owned();
+    path.extend(artifact_name.split('/'));
+    // not `set_extension`, mangled names may contain a `.`
+    path.as_mut_os_string().push(".wgsl");
+    path
+}
+
+/// Write the index of the shaders built in the tree layout to [`OUTPUT_DIR_NAME`]/[`OUTPUT_INDEX_NAME`]
+fn write_output_index(config: &WeslBuildConfig, plan: &BuildPlan) -> Result<(), WeslBuildError> {
+    let Some(out_dir) = config.output_dir().filter(|_| !config.flat_output) else {
+        return Ok(());
+    };
+
+    let mut index = String::new();
+    for shader in plan.shaders.iter().sorted_by(|a, b| a.mod_path.components.cmp(&b.mod_path.components)) {
+        // relative to the index, renamed artifacts can be outside of its dir
+        let path = match shader.artifact_name.strip_prefix(&format!("{OUTPUT_DIR_NAME}/")) {
+            Some(path) => path.to_owned(),
+            None => format!("../{}", shader.artifact_name),
+        };
+        index.push_str(&format!("{}={path}.wgsl\n", shader.mod_path.components.join("::")));
+    }
+    artifacts::write(out_dir.join(OUTPUT_DIR_NAME).join(OUTPUT_INDEX_NAME), index)?;
 
     Ok(())
 }
 
+/// Warn about the built shaders larger than [`warn_artifact_larger_than`](WeslBuildConfig::warn_artifact_larger_than)
+fn warn_large_artifacts(config: &WeslBuildConfig, plan: &BuildPlan) -> Result<(), WeslBuildError> {
+    let Some(max_size) = config.warn_artifact_size else {
+        return Ok(());
+    };
+
+    for shader in &plan.shaders {
+        let Some(output_path) = &shader.output_path else { continue };
+        let size = artifacts::read(output_path)?.len() as u64;
+        if size > max_size {
+            messages::report(&BuildMessage::warning(format!(
+                "the built shader `{}` is {size} bytes, larger than {max_size}, did an import pull in more than it should?",
+                shader.mod_path,
+            )).with_module(&shader.mod_path))?;
+        }
+    }
+    Ok(())
+}
+
+/// The mangler of `kind` and its name as reported to `include_wesl!`, `None` if it's not supported
+///
+/// !! keep in sync with the manglers supported by `include_wesl!` in wesl_build_import !!
+fn mangler_of(kind: ManglerKind) -> Option<(&'static str, &'static dyn Mangler)> {
+    match kind {
+        ManglerKind::Escape => Some(("escape", &wesl::EscapeMangler)),
+        ManglerKind::Hash => Some(("hash", &wesl::HashMangler)),
+        ManglerKind::None => Some(("none", &wesl::NoMangler)),
+        #[allow(unreachable_patterns)]
+        _ => None,
+    }
+}
+
+/// Config files in the crate dir that rerun the build script when changed
+const RERUN_CONFIG_FILES: [&str; 2] = ["wesl.toml", "wesl_build.toml"];
+
+/// Emit the `cargo::rerun-if-changed` lines of a build, and the `cargo::rerun-if-env-changed` of [`DISABLE_EXT_ENV`]
+///
+/// The shader root dir, config files, [`WeslBuildConfig::rerun_if_changed`] paths and the imports
+/// outside the shader root are always emitted, every shader file only with [`WeslBuildConfig::rerun_per_file`].
+/// Without it the hash of all shader sources is returned instead.
+/// Nothing is printed without [`WeslBuildConfig::cargo_instructions`].
+fn emit_rerun_if_changed(
+    config: &WeslBuildConfig,
+    shader_root: &Path,
+    plan: &BuildPlan,
+) -> Result<Option<u64>, WeslBuildError> {
+    let rerun_if_changed = |path: &Path| {
+        if config.cargo_instructions {
+            println!("cargo::rerun-if-changed={}", path.display());
+        }
+    };
+
+    // cargo scans dirs recursively, so this also catches added and removed shaders
+    rerun_if_changed(shader_root);
+
+    if let Some(manifest_dir) = std::env::var_os("CARGO_MANIFEST_DIR") {
+        for config_file in RERUN_CONFIG_FILES {
+            let config_path = Path::new(&manifest_dir).join(config_file);
+            if config_path.is_file() {
+                rerun_if_changed(&config_path);
+            }
+        }
+    }
+    for path in &config.rerun_if_changed {
+        rerun_if_changed(path);
+    }
+    // `extensions!` leaves out the extensions it names
+    if config.cargo_instructions {
+        println!("cargo::rerun-if-env-changed={DISABLE_EXT_ENV}");
+    }
+    for package in &config.packages {
+        rerun_if_changed(&package.source.dir(&package.name)?);
+    }
+
+    // generated modules have no source file
+    let source_paths = plan.shaders.iter()
+        .flat_map(|shader| std::iter::once(&shader.source_path).chain(&shader.dependencies))
+        .chain(&plan.assets)
+        .filter(|path| !path.as_os_str().is_empty())
+        .map(|path| fs::canonicalize(path).unwrap_or_else(|_| path.clone()))
+        .collect::<BTreeSet<_>>();
+    for path in &source_paths {
+        if config.rerun_per_file || !path.starts_with(shader_root) {
+            rerun_if_changed(path);
+        }
+    }
+
+    if config.rerun_per_file {
+        return Ok(None);
+    }
+    let mut hasher = DefaultHasher::new();
+    for path in &source_paths {
+        path.hash(&mut hasher);
+        fs::read(path)?.hash(&mut hasher);
+    }
+
+    Ok(Some(hasher.finish()))
+}
+
+/// Emit the `cargo::rerun-if-env-changed` of an env var an extension reads, with [`WeslBuildConfig::cargo_instructions`]
+pub(crate) fn rerun_if_env_changed(var: &str) {
+    if with_build_context(|ctx| ctx.config().cargo_instructions).unwrap_or(true) {
+        println!("cargo::rerun-if-env-changed={var}");
+    }
+}
+
+/// Write the `key=value` build manifest to `OUT_DIR`, so `include_wesl!` can find the shaders
+/// even when the environment variables from the build script are not available (e.g. rust-analyzer)
+fn write_build_manifest(
+    config: &WeslBuildConfig,
+    shader_root: &Path,
+    mangler_name: &str,
+    shaders_hash: Option<u64>,
+    plan: &BuildPlan,
+) -> Result<(), WeslBuildError> {
+    let Some(out_dir) = config.output_dir() else {
+        #[cfg(feature = "logging")]
+        log::warn!("OUT_DIR is not set, so no build manifest will be written");
+        return Ok(());
+    };
+
+    let mut manifest = format!("root={}\nmangler={mangler_name}\nlayout={}\n", shader_root.display(), layout_name(config));
+    if let Some(shaders_hash) = shaders_hash {
+        manifest.push_str(&format!("shaders_hash={shaders_hash:016x}\n"));
+    }
+    if let Some(platform) = config.build_platform() {
+        manifest.push_str(&format!("platform={platform}\n"));
+    }
+    if config.file_names != FileNames::Verbatim {
+        manifest.push_str(&format!("file_names={}\n", config.file_names.as_str()));
+    }
+    // !! keep in sync with the `artifact.` keys read by `include_wesl!` in wesl_build_import !!
+    for shader in &plan.shaders {
+        if shader.artifact_name != default_artifact_name(config, &shader.mod_path) {
+            manifest.push_str(&format!("artifact.{}={}\n", shader.mod_path.components.join("::"), shader.artifact_name));
+        }
+    }
+    artifacts::write(out_dir.join(BUILD_MANIFEST_NAME), manifest)?;
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
 fn build_all_in_dir<WeslResolver: Resolver>(
-    root_shader_path: &str,
+    config: &WeslBuildConfig,
     path: &Path,
-    wesl: &Wesl<WeslResolver>,
-    extensions: &mut [Box<dyn WeslBuildExtension<StandardResolver>>],
-    res: &Wesl<WeslResolver>,
+    wesl: &mut Wesl<WeslResolver>,
+    extensions: &mut [Box<dyn WeslBuildExtension<BuildResolver>>],
+    walk: &mut DirWalk,
+    plan: &mut BuildPlan,
+    errors: &mut Vec<WeslBuildError>,
+    timings: &mut Timings,
 ) -> Result<(), WeslBuildError> {
-    fs::read_dir(path)?.filter_map(|entry| entry.ok().map(|en| (en.metadata(), en)))
-    // run dirs after files to insure correct recursion
-    .sorted_by(|(a_meta, _), (b_meta, _)| {
-        (a_meta.as_ref().ok().is_some_and(fs::Metadata::is_file)).cmp(
-            &b_meta.as_ref().ok().is_some_and(fs::Metadata::is_file)
-        ).reverse()
-    }).try_for_each(|(metadata, entry)| -> Result<(), WeslBuildError> {
-        if metadata?.is_dir() {
+    let root_shader_path = config.shader_path.as_str();
+    walk.enter(path)?;
+
+    let result = fs::read_dir(path)?.filter_map(|entry| entry.ok().map(|en| (walk.is_dir(&en.path()), en)))
+    // run dirs after files to insure correct recursion, and assets before shaders so their hooks can use them
+    .sorted_by_key(|(is_dir, entry)| (
+        !is_dir.as_ref().is_ok_and(|is_dir| !is_dir),
+        matches!(entry.path().extension().and_then(OsStr::to_str), Some("wgsl" | "wesl")),
+    )).try_for_each(|(is_dir, entry)| -> Result<(), WeslBuildError> {
+        if is_dir? {
             // make new mod per dir recurce to use mod structure
             let dir_path = entry.path();
-            for ext in extensions.iter_mut() {
-                ext.enter_mod(&dir_path)
-                    .map_err(|e| extension_error(ext.as_ref(), e))?;
+            if !config.dry_run {
+                for ext in extensions.iter_mut() {
+                    let result = timings.hook(ext.as_mut(), "enter_mod", None, |ext| ext.enter_mod(&dir_path))
+                        .map_err(|e| extension_error(ext.as_ref(), "enter_mod", None, e));
+                    collect_error(config, errors, None, result)?;
+                }
             }
 
-            build_all_in_dir(root_shader_path, &dir_path, wesl, extensions, res)?;
+            build_all_in_dir(config, &dir_path, wesl, extensions, walk, plan, errors, timings)?;
 
-            if path != Path::new(root_shader_path) {
+            if !config.dry_run && path != Path::new(root_shader_path) {
                 for ext in extensions.iter_mut() {
-                    ext.exit_mod(&dir_path)
-                        .map_err(|e| extension_error(ext.as_ref(), e))?;
+                    let result = timings.hook(ext.as_mut(), "exit_mod", None, |ext| ext.exit_mod(&dir_path))
+                        .map_err(|e| extension_error(ext.as_ref(), "exit_mod", None, e));
+                    collect_error(config, errors, None, result)?;
                 }
             }
             Ok(())
@@ -183,65 +854,224 @@ fn build_all_in_dir<WeslResolver: Resolver>(
             if !(entry_path.extension() == Some(OsStr::new("wgsl"))
                 || entry_path.extension() == Some(OsStr::new("wesl")))
             {
+                let is_asset = entry_path.extension()
+                    .is_some_and(|extension| config.asset_extensions.iter().any(|asset| OsStr::new(asset) == extension));
+                if !is_asset {
+                    return Ok(());
+                }
+                if !config.dry_run {
+                    for ext in extensions.iter_mut() {
+                        let result = timings.hook(ext.as_mut(), "on_asset", None, |ext| ext.on_asset(&entry_path))
+                            .map_err(|e| extension_error(ext.as_ref(), "on_asset", None, e));
+                        collect_error(config, errors, None, result)?;
+                    }
+                }
+                plan.assets.push(entry_path);
                 return Ok(());
             }
-            println!("cargo::rerun-if-changed={}", entry_path.display());
+
+            // the globs match the file, so an override can be excluded on its own
+            let relative_path = walk::relative_path(&entry_path, Path::new(root_shader_path))?;
+            if !walk.builds_file(&relative_path) {
+                return Ok(());
+            }
+
+            // an override is built as the module of its base, from the override file
+            let module_file = match ShaderFile::of(&entry_path, walk.platform()) {
+                ShaderFile::Shader => entry_path.clone(),
+                ShaderFile::Override { base } => base,
+                ShaderFile::Overridden | ShaderFile::OtherPlatform => return Ok(()),
+            };
 
             // module from root(absolute) path to entry
-            let mut out_name = entry_path.strip_prefix(root_shader_path)?.to_owned();
-            out_name.pop();
-            out_name = PathBuf::from(
-                out_name
-                    .join(PathBuf::from(entry.file_name()).file_stem()
-                        .expect("shader file must have a name in path")
-                    )
-                    .to_str()
-                    .unwrap()
-                    // todo mangle in place of :: use wesl mangler
-                    .replace('/', "::"),
-            );
-
-            let out_name_str = out_name.to_str().unwrap();
-            let mod_path = ModulePath::new(
+            let mod_path = match module_components(&module_file, Path::new(root_shader_path), walk.file_names()) {
+                Ok(components) => ModulePath::new(wesl::syntax::PathOrigin::Absolute, components),
+                Err(error) => return collect_error(config, errors, None, Err(error)),
+            };
+
+            build_module(config, mod_path, entry_path, wesl, extensions, plan, errors, timings)
+        }
+    });
+    walk.exit();
+
+    result
+}
+
+/// Build the module `mod_path` from `source_path` and run the extensions on it, or only plan it in a dry run
+#[allow(clippy::too_many_arguments)]
+fn build_module<WeslResolver: Resolver>(
+    config: &WeslBuildConfig,
+    mod_path: ModulePath,
+    source_path: PathBuf,
+    wesl: &mut Wesl<WeslResolver>,
+    extensions: &mut [Box<dyn WeslBuildExtension<BuildResolver>>],
+    plan: &mut BuildPlan,
+    errors: &mut Vec<WeslBuildError>,
+    timings: &mut Timings,
+) -> Result<(), WeslBuildError> {
+    if mod_path.components.is_empty() {
+        return collect_error(config, errors, None, Err(WeslBuildError::InvalidFileName { path: source_path }));
+    }
+    let artifact_name = &match renamed_artifact(&mod_path, extensions) {
+        Ok(artifact_name) => artifact_name.unwrap_or_else(|| default_artifact_name(config, &mod_path)),
+        Err(error) => return collect_error(config, errors, Some(&mod_path), Err(error)),
+    };
+    if let Some(shader) = plan.shaders.iter().find(|shader| shader.artifact_name == *artifact_name) {
+        let error = WeslBuildError::ArtifactCollision {
+            name: artifact_name.clone(),
+            first: shader.mod_path.clone(),
+            second: mod_path.clone(),
+        };
+        return collect_error(config, errors, Some(&mod_path), Err(error));
+    }
+
+    let out_dir = config.output_dir();
+    let mut planned = PlannedShader {
+        mod_path: mod_path.clone(),
+        source_path,
+        artifact_name: artifact_name.clone(),
+        output_path: out_dir.as_ref().map(|out_dir| artifact_path(out_dir, artifact_name)),
+        dependencies: Vec::new(),
+    };
+
+    if config.dry_run {
+        for ext in &mut *extensions {
+            ext.would_build(&planned)
+                .map_err(|e| extension_error(ext.as_ref(), "would_build", Some(&planned.mod_path), e))?;
+        }
+        plan.shaders.push(planned);
+        return Ok(());
+    }
+
+    let out_dir = out_dir.ok_or(WeslBuildError::MissingOutDir)?;
+    let mut build = || -> Result<Vec<PathBuf>, WeslBuildError> {
+        let mut compile_options = config.compile_options.clone();
+        for ext in &mut *extensions {
+            timings.hook(ext.as_mut(), "configure", Some(&mod_path), |ext| ext.configure(&mod_path, &mut compile_options))
+                .map_err(|e| extension_error(ext.as_ref(), "configure", Some(&mod_path), e))?;
+        }
+        wesl.set_options(compile_options);
+
+        let (source_map, dependencies) = timings.compile(&mod_path, || build_artifact(
+            wesl, &mod_path, &out_dir, artifact_name
+        ))?;
+        #[cfg(feature = "logging")]
+        log::info!("built: {}", &mod_path);
+
+        let wgsl_source_path = artifact_path(&out_dir, artifact_name);
+        #[cfg(feature = "naga")]
+        post_parse(&mod_path, &wgsl_source_path, &mut *extensions, timings)?;
+        let wgsl_source_path = wgsl_source_path.to_str()
+            .ok_or_else(|| WeslBuildError::NonUtf8Path { path: wgsl_source_path.clone() })?;
+
+        for ext in &mut *extensions {
+            timings.hook(ext.as_mut(), "post_build", Some(&mod_path), |ext| ext.post_build(&mod_path, wgsl_source_path, &source_map))
+                .map_err(|e| extension_error(ext.as_ref(), "post_build", Some(&mod_path), e))?;
+        }
+        Ok(dependencies)
+    };
+    // shaders that failed to build are left out of the plan
+    let result = build().map(|dependencies| {
+        planned.dependencies = dependencies;
+        plan.shaders.push(planned);
+    });
+    if let Err(error) = &result {
+        for ext in &mut *extensions {
+            ext.on_error(&mod_path, error);
+        }
+    }
+    collect_error(config, errors, Some(&mod_path), result)
+}
+
+/// Run the `preprocess` hook of every extension on the source of every module,
+/// the rewritten sources are put in the resolver
+fn preprocess(
+    graph: &ModuleGraph,
+    resolver: &BuildResolver,
+    extensions: &mut [Box<dyn WeslBuildExtension<BuildResolver>>],
+    timings: &mut Timings,
+) -> Result<(), WeslBuildError> {
+    for mod_path in graph.modules() {
+        let Some(source_path) = graph.source_path(mod_path) else { continue };
+        let original = fs::read_to_string(source_path)?;
+
+        let mut source = original.clone();
+        for ext in extensions.iter_mut() {
+            timings.hook(ext.as_mut(), "preprocess", Some(mod_path), |ext| ext.preprocess(mod_path, &mut source))
+                .map_err(|e| extension_error(ext.as_ref(), "preprocess", Some(mod_path), e))?;
+        }
+        if source != original {
+            resolver.set_preprocessed(mod_path.clone(), source);
+        }
+    }
+
+    Ok(())
+}
+
+/// Parse the built shader once and run the `post_parse` hook of every extension,
+/// the WGSL is only written back if an extension modified the module
+#[cfg(feature = "naga")]
+fn post_parse(
+    mod_path: &ModulePath,
+    wgsl_built_path: &Path,
+    extensions: &mut [Box<dyn WeslBuildExtension<BuildResolver>>],
+    timings: &mut Timings,
+) -> Result<(), WeslBuildError> {
+    let naga_error = |error: Box<dyn std::error::Error>| WeslBuildError::NagaErr { mod_path: mod_path.clone(), error };
+
+    let mut module = NagaModule::parse(&artifacts::read_to_string(wgsl_built_path)?)
+        .map_err(|error| naga_error(Box::new(error)))?;
+    for ext in extensions.iter_mut() {
+        timings.hook(ext.as_mut(), "post_parse", Some(mod_path), |ext| ext.post_parse(mod_path, &mut module))
+            .map_err(|e| extension_error(ext.as_ref(), "post_parse", Some(mod_path), e))?;
+    }
+
+    if module.is_modified() {
+        artifacts::write(wgsl_built_path, module.to_wgsl().map_err(naga_error)?)?;
+    }
+
+    Ok(())
+}
+
+/// The module path components of the shader file at `entry_path` in `shader_root`, its names turned into
+/// module names with `file_names`
+fn module_components(entry_path: &Path, shader_root: &Path, file_names: FileNames) -> Result<Vec<String>, WeslBuildError> {
+    entry_path.strip_prefix(shader_root)?.with_extension("").iter()
+        .map(|component| {
+            let name = component.to_str()
+                .ok_or_else(|| WeslBuildError::NonUtf8Path { path: entry_path.to_owned() })?;
+            // `.` and `:` would make the module path ambiguous
+            if name.is_empty() || name.contains(['.', ':']) {
+                return Err(WeslBuildError::InvalidFileName { path: entry_path.to_owned() });
+            }
+            file_names.module_name(name, entry_path)
+        })
+        .collect()
+}
+
+/// Resolve a `self`/`super` relative `path` from the module `base`, absolute paths are returned as is
+///
+/// `None` if the path goes above the root.
+///
+/// !! keep in sync with `resolve_relative` in wesl_build_import !!
+pub fn resolve_relative(base: &ModulePath, path: &ModulePath) -> Option<ModulePath> {
+    match path.origin {
+        wesl::syntax::PathOrigin::Relative(supers) => {
+            let keep = base.components.len().checked_sub(supers)?;
+
+            Some(ModulePath::new(
                 wesl::syntax::PathOrigin::Absolute,
-                out_name_str
-                    .split("::")
-                    .map(|str| str.to_owned())
+                base.components[..keep].iter()
+                    .chain(&path.components)
+                    .cloned()
                     .collect::<Vec<_>>(),
-            );
-            // wesl::emit_rerun_if_changed(&[mod_path], res);
-
-            // !! keep in sync with mangler used in wesl_build_import !!
-            let name_mangler = wesl::EscapeMangler;
-            let mangled_name = &name_mangler.mangle(
-                &mod_path,
-                out_name.file_stem().and_then(|os_str| os_str.to_str()).unwrap()
-            );
-
-            let source_map = build_artifact(
-                wesl, &mod_path, mangled_name
-            );
-            #[cfg(feature = "logging")]
-            log::info!("built: {}", &mod_path);
-
-            let wgsl_source_path = format!(
-                "{}/{}.wgsl",
-                std::env::var("OUT_DIR").expect(
-                    "OUT_DIR env var must be set by cargo, any project with a build.rs will have this set"
-                ),
-                mangled_name,
-            );
-
-            for ext in &mut *extensions {
-                ext.post_build(&mod_path, &wgsl_source_path, &source_map)
-                    .map_err(|e| extension_error(ext.as_ref(), e))?;
-            }
-            Ok(())
+            ))
         }
-    })
+        _ => Some(path.clone()),
+    }
 }
 
-/// Compile a WESL program from a root file and output the result in Rust's `OUT_DIR`.
+/// Compile a WESL program from a root file and output the result in `out_dir`, usually Rust's `OUT_DIR`.
 ///
 /// This function is meant to be used in a `build.rs` workflow. The output WGSL will
 /// be accessed with the [`include_wesl`] macro. See the crate documentation for a
@@ -251,22 +1081,24 @@ fn build_all_in_dir<WeslResolver: Resolver>(
 ///   directory.
 /// * The second argument is the name of the artifact, used in [`include_wesl`].
 ///
-/// Will emit `rerun-if-changed` instructions so the build script reruns only if the
-/// shader files are modified.
-///
-/// # Panics
-/// Panics when compilation fails or if the output file cannot be written.
-/// Pretty-prints the WESL error message to stderr.
-fn build_artifact(res: &Wesl<impl Resolver>, root: &ModulePath, artifact_name: &str) -> Option<BasicSourceMap> {
+/// Returns the files of every module used in the program, as reported by the resolver,
+/// the `rerun-if-changed` instructions are emitted once all shaders are built.
+fn build_artifact(
+    res: &Wesl<impl Resolver>,
+    root: &ModulePath,
+    out_dir: &Path,
+    artifact_name: &str,
+) -> Result<(Option<BasicSourceMap>, Vec<PathBuf>), WeslBuildError> {
     let compiled = res
         .compile(root)
-        .inspect_err(|e| {
-            eprintln!("failed to build WESL shader `{root}`.\n{e}");
-            panic!();
-        })
-        .unwrap();
-    wesl::emit_rerun_if_changed(&compiled.modules, &res.resolver());
-    compiled.write_artifact(artifact_name);
+        .map_err(|error| WeslBuildError::CompileErr {
+            mod_path: root.clone(),
+            error: Box::new(error),
+        })?;
+    let dependencies = compiled.modules.iter()
+        .filter_map(|module| res.resolver().fs_path(module))
+        .collect();
+    artifacts::write(artifact_path(out_dir, artifact_name), compiled.to_string())?;
 
-    compiled.sourcemap
+    Ok((compiled.sourcemap, dependencies))
 }
@@ -0,0 +1,184 @@
+//! The lock file of a build, what it was built with and the hashes of its inputs and outputs
+//!
+//! Written by builds with a [`lock_file`](`crate::WeslBuildConfig::lock_file`) and checked by
+//! [`verify_shader_dir`](`crate::verify_shader_dir`), commit it to catch builds that drifted,
+//! e.g. a shader changed without regenerating the bindings committed with it.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt, fs,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::{BuildPlan, WeslBuildConfig, WeslBuildError};
+
+/// The default name of the lock file, see [`WeslBuildConfig::lock_file`](`crate::WeslBuildConfig::lock_file`)
+pub const LOCK_FILE_NAME: &str = "wesl_build.lock";
+
+// !! keep in sync with Cargo.toml !!
+const WESL_VERSION: &str = "0.3";
+#[cfg(feature = "naga")]
+const NAGA_VERSION: &str = "28.0";
+
+/// The features of wesl_build that change what a build writes, `logging`, `testing` and `cli` don't
+const OUTPUT_FEATURES: [(&str, bool); 15] = [
+    ("bevy_asset_ext", cfg!(feature = "bevy_asset_ext")),
+    ("binding_remap_ext", cfg!(feature = "binding_remap_ext")),
+    ("binding_usage_ext", cfg!(feature = "binding_usage_ext")),
+    ("buffer_size_ext", cfg!(feature = "buffer_size_ext")),
+    ("feature_fallback_ext", cfg!(feature = "feature_fallback_ext")),
+    ("hot_reload", cfg!(feature = "hot_reload")),
+    ("naga", cfg!(feature = "naga")),
+    ("rich_diagnostics", cfg!(feature = "rich_diagnostics")),
+    ("shader_budget_ext", cfg!(feature = "shader_budget_ext")),
+    ("shader_stats_ext", cfg!(feature = "shader_stats_ext")),
+    ("texture_consistency_ext", cfg!(feature = "texture_consistency_ext")),
+    ("wesl_lint_ext", cfg!(feature = "wesl_lint_ext")),
+    ("wgpu_bindings_ext", cfg!(feature = "wgpu_bindings_ext")),
+    ("wgsl_target_ext", cfg!(feature = "wgsl_target_ext")),
+    ("wgsl_minifier_ext", cfg!(feature = "wgsl_minifier_ext")),
+];
+
+/// The `key=value` entries of a lock file, sorted by key
+///
+/// - `wesl_build`, `wesl`, `naga`: the versions the shaders were built with
+/// - `features`, `mangler`, `platform`: how they were built
+/// - `input <path>`: the hash of every shader file and import
+/// - `output <artifact name>`: the hash of every built shader
+/// - `generated <path>`: the hash of every [generated file](`crate::WeslBuildConfig::lock_generated`)
+///
+/// Paths are relative to the dir of the lock file, with `/` separators.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BuildLock {
+    entries: BTreeMap<String, String>,
+}
+
+impl BuildLock {
+    /// Parse a lock file, lines without a `=` and `#` comments are ignored
+    pub fn parse(lock: &str) -> Self {
+        let entries = lock.lines()
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(|line| line.rsplit_once('='))
+            .map(|(key, value)| (key.to_owned(), value.to_owned()))
+            .collect();
+        Self { entries }
+    }
+
+    /// The value of `key`
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries.get(key).map(String::as_str)
+    }
+
+    /// Every entry, sorted by key
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+
+    /// What changed from `locked` to `self`, one line per entry that was added, removed or changed
+    pub fn drift_from(&self, locked: &Self) -> Vec<String> {
+        let keys = locked.entries.keys().chain(self.entries.keys()).collect::<BTreeSet<_>>();
+        keys.into_iter()
+            .filter_map(|key| match (locked.entries.get(key), self.entries.get(key)) {
+                (Some(locked), Some(current)) if locked == current => None,
+                (Some(locked), Some(current)) => Some(format!("`{key}` changed from {locked} to {current}")),
+                (None, Some(_)) => Some(format!("`{key}` is new")),
+                (Some(_), None) => Some(format!("`{key}` was removed")),
+                (None, None) => None,
+            })
+            .collect()
+    }
+
+    /// The lock of the build of `plan` with `config`, its lock file must be set
+    ///
+    /// The built shaders are read from the artifact sink of the build, so it must run during the build.
+    pub(crate) fn of_build(config: &WeslBuildConfig, plan: &BuildPlan, mangler_name: &str) -> Result<Self, WeslBuildError> {
+        let lock_file = config.lock_file.as_deref().expect("only builds with a lock file are locked");
+        let lock_dir = lock_file.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let lock_dir = fs::canonicalize(lock_dir)?;
+
+        let mut entries = BTreeMap::new();
+        entries.insert("wesl_build".to_owned(), env!("CARGO_PKG_VERSION").to_owned());
+        entries.insert("wesl".to_owned(), WESL_VERSION.to_owned());
+        #[cfg(feature = "naga")]
+        entries.insert("naga".to_owned(), NAGA_VERSION.to_owned());
+        let features = OUTPUT_FEATURES.iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(feature, _)| *feature)
+            .collect::<Vec<_>>();
+        entries.insert("features".to_owned(), features.join(","));
+        entries.insert("mangler".to_owned(), mangler_name.to_owned());
+        if let Some(platform) = config.build_platform() {
+            entries.insert("platform".to_owned(), platform);
+        }
+
+        // generated modules have no source file
+        let inputs = plan.shaders.iter()
+            .flat_map(|shader| std::iter::once(&shader.source_path).chain(&shader.dependencies))
+            .filter(|path| !path.as_os_str().is_empty());
+        for path in inputs {
+            entries.insert(format!("input {}", lock_path(&lock_dir, path)?), hash_hex(&fs::read(path)?));
+        }
+        for shader in &plan.shaders {
+            if let Some(output_path) = &shader.output_path {
+                entries.insert(format!("output {}", shader.artifact_name), hash_hex(&crate::artifacts::read(output_path)?));
+            }
+        }
+        for path in &config.lock_generated {
+            for file in files_in(path)? {
+                entries.insert(format!("generated {}", lock_path(&lock_dir, &file)?), hash_hex(&fs::read(&file)?));
+            }
+        }
+
+        Ok(Self { entries })
+    }
+}
+
+impl fmt::Display for BuildLock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# written by wesl_build, check it with `wesl_build::verify_shader_dir`")?;
+        for (key, value) in &self.entries {
+            writeln!(f, "{key}={value}")?;
+        }
+        Ok(())
+    }
+}
+
+/// `path` relative to `lock_dir` with `/` separators, or absolute if it's outside of it
+fn lock_path(lock_dir: &Path, path: &Path) -> Result<String, WeslBuildError> {
+    let path = fs::canonicalize(path)?;
+    let relative = path.strip_prefix(lock_dir).unwrap_or(&path);
+    if relative.is_absolute() {
+        return Ok(relative.display().to_string());
+    }
+
+    Ok(relative.components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/"))
+}
+
+/// The file `path`, or every file in the dir `path`, sorted
+fn files_in(path: &Path) -> Result<Vec<PathBuf>, WeslBuildError> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_owned()]);
+    }
+
+    let mut files = Vec::new();
+    for entry in fs::read_dir(path)? {
+        files.extend(files_in(&entry?.path())?);
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// The FNV-1a hash of `bytes` in hex, unlike [`DefaultHasher`](`std::hash::DefaultHasher`)
+/// it is the same on every platform and Rust version, so committed lock files stay valid
+fn hash_hex(bytes: &[u8]) -> String {
+    let hash = bytes.iter().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("{hash:016x}")
+}
@@ -0,0 +1,215 @@
+//! Build diagnostics as newline-delimited JSON, like `cargo --message-format=json`, for editors and other tools
+//!
+//! Enable it with [`WeslBuildConfig::json_diagnostics`](`crate::WeslBuildConfig::json_diagnostics`), every error
+//! of the build and every warning extensions [`report`] is then written as one JSON object per line:
+//! ```json
+//! {"severity":"warning","message":"`Light` is not PascalCase [naming_conventions]","module":"lights","file":"src/shaders/lights.wesl","line":3}
+//! ```
+//! Fields that are not known are left out.
+
+use std::{
+    cell::RefCell,
+    fmt::{self, Write as _},
+    fs,
+    io::{self, Write as _},
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use wesl::ModulePath;
+
+use crate::WeslBuildError;
+
+/// How bad a [`BuildMessage`] is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The build failed
+    Error,
+    /// The build went on
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        })
+    }
+}
+
+/// A diagnostic of the build, see [`report`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildMessage {
+    pub severity: Severity,
+    pub message: String,
+    /// The module the message is about, e.g. `lights::point`
+    pub module: Option<String>,
+    pub file: Option<PathBuf>,
+    /// The 1-based line in `file`
+    pub line: Option<usize>,
+    /// The byte range in `file`
+    pub span: Option<Range<usize>>,
+}
+
+impl BuildMessage {
+    pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Self { severity, message: message.into(), module: None, file: None, line: None, span: None }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, message)
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, message)
+    }
+
+    pub fn with_module(mut self, mod_path: &ModulePath) -> Self {
+        self.module = Some(mod_path.components.join("::"));
+        self
+    }
+
+    pub fn with_file(mut self, file: impl Into<PathBuf>) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+
+    pub fn with_line(mut self, line: usize) -> Self {
+        self.line = Some(line);
+        self
+    }
+
+    pub fn with_span(mut self, span: Range<usize>) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    /// The message as a single line JSON object
+    pub fn to_json(&self) -> String {
+        let mut json = format!("{{\"severity\":\"{}\",\"message\":{}", self.severity, json_string(&self.message));
+        if let Some(module) = &self.module {
+            write!(json, ",\"module\":{}", json_string(module)).expect("writing to a String can't fail");
+        }
+        if let Some(file) = &self.file {
+            write!(json, ",\"file\":{}", json_string(&file.display().to_string())).expect("writing to a String can't fail");
+        }
+        if let Some(line) = self.line {
+            write!(json, ",\"line\":{line}").expect("writing to a String can't fail");
+        }
+        if let Some(span) = &self.span {
+            write!(json, ",\"span\":{{\"start\":{},\"end\":{}}}", span.start, span.end).expect("writing to a String can't fail");
+        }
+        json.push('}');
+        json
+    }
+}
+
+impl fmt::Display for BuildMessage {
+    /// `file:line: message`, or `module: message` if the file is not known
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.file, self.line, &self.module) {
+            (Some(file), Some(line), _) => write!(f, "{}:{line}: ", file.display())?,
+            (Some(file), None, _) => write!(f, "{}: ", file.display())?,
+            (None, _, Some(module)) => write!(f, "{module}: ")?,
+            (None, _, None) => (),
+        }
+        f.write_str(&self.message)
+    }
+}
+
+/// Where [`WeslBuildConfig::json_diagnostics`](`crate::WeslBuildConfig::json_diagnostics`) writes the messages
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JsonOutput {
+    Stderr,
+    /// Not in build scripts, cargo reads their stdout for instructions
+    Stdout,
+    /// A file that is truncated when the build starts
+    File(PathBuf),
+}
+
+thread_local! {
+    /// The [`JsonOutput`] of the build running on this thread
+    static JSON_OUTPUT: RefCell<Option<JsonOutput>> = const { RefCell::new(None) };
+}
+
+/// Report a diagnostic of the build running on this thread
+///
+/// With [JSON diagnostics](`crate::WeslBuildConfig::json_diagnostics`) it is written as JSON, otherwise warnings are
+/// printed as `cargo::warning`s and errors are left to be returned by the extension.
+pub fn report(message: &BuildMessage) -> io::Result<()> {
+    match JSON_OUTPUT.with_borrow(Clone::clone) {
+        Some(JsonOutput::Stderr) => writeln!(io::stderr(), "{}", message.to_json()),
+        Some(JsonOutput::Stdout) => writeln!(io::stdout(), "{}", message.to_json()),
+        Some(JsonOutput::File(path)) => writeln!(fs::OpenOptions::new().append(true).create(true).open(path)?, "{}", message.to_json()),
+        None => {
+            if message.severity == Severity::Warning {
+                println!("cargo::warning={message}");
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Report the errors in `error`, one message per error of a [`Multiple`](WeslBuildError::Multiple),
+/// only with JSON diagnostics as the error is returned too
+pub(crate) fn report_error(error: &WeslBuildError, shader_path: &Path) -> io::Result<()> {
+    if JSON_OUTPUT.with_borrow(Option::is_none) {
+        return Ok(());
+    }
+
+    match error {
+        WeslBuildError::Multiple(errors) => errors.iter().try_for_each(|error| report_error(error, shader_path)),
+        WeslBuildError::ModuleErr { mod_path, error } => report(&module_message(error, mod_path, shader_path)),
+        WeslBuildError::CompileErr { mod_path, .. } => report(&module_message(error, mod_path, shader_path)),
+        WeslBuildError::ExtensionErr { mod_path: Some(mod_path), .. } => report(&module_message(error, mod_path, shader_path)),
+        error => report(&BuildMessage::error(error.to_string())),
+    }
+}
+
+/// The message of `error` in the module `mod_path`, with its source file if it is in the shader dir
+fn module_message(error: &WeslBuildError, mod_path: &ModulePath, shader_path: &Path) -> BuildMessage {
+    let message = BuildMessage::error(error.to_string()).with_module(mod_path);
+    let file = mod_path.components.iter().fold(shader_path.to_owned(), |path, component| path.join(component));
+    match ["wesl", "wgsl"].map(|ext| file.with_extension(ext)).into_iter().find(|file| file.is_file()) {
+        Some(file) => message.with_file(file),
+        None => message,
+    }
+}
+
+/// `value` as a JSON string literal
+pub(crate) fn json_string(value: &str) -> String {
+    let mut json = String::with_capacity(value.len() + 2);
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => write!(json, "\\u{:04x}", c as u32).expect("writing to a String can't fail"),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// Sets the JSON output of the messages until it is dropped, truncating its file
+pub(crate) struct JsonOutputGuard(Option<JsonOutput>);
+
+impl JsonOutputGuard {
+    pub(crate) fn set(output: Option<JsonOutput>) -> io::Result<Self> {
+        if let Some(JsonOutput::File(path)) = &output {
+            fs::write(path, "")?;
+        }
+        Ok(Self(JSON_OUTPUT.replace(output)))
+    }
+}
+
+impl Drop for JsonOutputGuard {
+    fn drop(&mut self) {
+        JSON_OUTPUT.set(self.0.take());
+    }
+}
@@ -0,0 +1,283 @@
+//! The `[package.metadata.wesl_build]` section of a `Cargo.toml`, see [`WeslBuildConfig::from_cargo_metadata`]
+
+use std::{collections::BTreeMap, path::Path};
+
+use wesl::ManglerKind;
+
+use crate::{WeslBuildConfig, WeslBuildError};
+use crate::extension::{BoxedExtension, ExtensionSet};
+
+/// The header of the section
+const METADATA_SECTION: &str = "package.metadata.wesl_build";
+
+/// The keys of the section
+const METADATA_KEYS: [&str; 8] = [
+    "root", "extensions", "minify", "bindings", "mangler", "features", "asset_extensions", "continue_on_error",
+];
+
+/// A value of the section, numbers and dates are not supported since no key takes them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum MetadataValue {
+    String(String),
+    Bool(bool),
+    Array(Vec<MetadataValue>),
+    Table(MetadataTable),
+}
+
+pub(crate) type MetadataTable = BTreeMap<String, MetadataValue>;
+
+impl MetadataValue {
+    /// The value of the section from the `Cargo.toml`
+    fn from_toml(value: &toml::Value) -> Result<Self, String> {
+        use toml::Value;
+
+        match value {
+            Value::String(string) => Ok(Self::String(string.clone())),
+            Value::Boolean(bool) => Ok(Self::Bool(*bool)),
+            Value::Array(values) => values.iter().map(Self::from_toml).collect::<Result<_, _>>().map(Self::Array),
+            Value::Table(values) => values.iter()
+                .map(|(key, value)| Ok((key.clone(), Self::from_toml(value)?)))
+                .collect::<Result<_, String>>()
+                .map(Self::Table),
+            value => Err(format!("unsupported value `{value}`, expected a string, boolean, array or table")),
+        }
+    }
+
+    /// The value of the section from the JSON of `cargo metadata`
+    #[cfg(feature = "workspace")]
+    pub(crate) fn from_json(value: &serde_json::Value) -> Result<Self, String> {
+        use serde_json::Value;
+
+        match value {
+            Value::String(string) => Ok(Self::String(string.clone())),
+            Value::Bool(bool) => Ok(Self::Bool(*bool)),
+            Value::Array(values) => values.iter().map(Self::from_json).collect::<Result<_, _>>().map(Self::Array),
+            Value::Object(values) => values.iter()
+                .map(|(key, value)| Ok((key.clone(), Self::from_json(value)?)))
+                .collect::<Result<_, String>>()
+                .map(Self::Table),
+            value => Err(format!("unsupported value `{value}`, expected a string, boolean, array or table")),
+        }
+    }
+}
+
+/// A `Cargo.toml` parsed with `toml`, for its package name and section
+pub(crate) struct CargoToml(toml::Table);
+
+impl CargoToml {
+    pub(crate) fn parse(source: &str) -> Result<Self, String> {
+        toml::from_str(source).map(Self).map_err(|error| error.to_string())
+    }
+
+    /// The `name` of the `[package]`
+    pub(crate) fn package_name(&self) -> Option<&str> {
+        self.0.get("package")?.get("name")?.as_str()
+    }
+
+    /// The `[package.metadata.wesl_build]` table, `None` if there is none
+    pub(crate) fn section(&self) -> Result<Option<MetadataTable>, String> {
+        let section = METADATA_SECTION.split('.').try_fold(&self.0, |table, key| table.get(key)?.as_table());
+        match section {
+            Some(section) => section.iter()
+                .map(|(key, value)| Ok((key.clone(), MetadataValue::from_toml(value)?)))
+                .collect::<Result<_, String>>()
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// A `[package.metadata.wesl_build]` section of the package `package` in `package_dir`
+pub(crate) struct MetadataSection<'a> {
+    pub(crate) package: &'a str,
+    pub(crate) package_dir: &'a Path,
+    pub(crate) table: &'a MetadataTable,
+}
+
+impl MetadataSection<'_> {
+    /// The config of the build, it reruns when the `Cargo.toml` changes
+    pub(crate) fn config(&self) -> Result<WeslBuildConfig, WeslBuildError> {
+        if let Some(key) = self.table.keys().find(|key| !METADATA_KEYS.contains(&key.as_str())) {
+            return Err(self.error(format!("unknown key `{key}`, expected one of {}", METADATA_KEYS.join(", "))));
+        }
+
+        let root = self.package_dir.join(self.str("root")?.unwrap_or("src/shaders"));
+        let root = root.to_str().ok_or_else(|| WeslBuildError::NonUtf8Path { path: root.clone() })?;
+        let mut config = WeslBuildConfig::new(root).rerun_if_changed(self.package_dir.join("Cargo.toml"));
+
+        if let Some(mangler) = self.str("mangler")? {
+            config = config.mangler(match mangler {
+                "escape" => ManglerKind::Escape,
+                "hash" => ManglerKind::Hash,
+                "none" => ManglerKind::None,
+                _ => return Err(self.error(format!("unknown mangler `{mangler}`, expected `escape`, `hash` or `none`"))),
+            });
+        }
+        if let Some(continue_on_error) = self.bool("continue_on_error")? {
+            config = config.continue_on_error(continue_on_error);
+        }
+        if let Some(asset_extensions) = self.strings("asset_extensions")? {
+            config = config.asset_extensions(asset_extensions);
+        }
+        for (name, enabled) in self.table("features")?.into_iter().flatten() {
+            let MetadataValue::Bool(enabled) = enabled else {
+                return Err(self.error(format!("the feature `{name}` must be `true` or `false`")));
+            };
+            config.compile_options.features.flags.insert(
+                name.clone(),
+                if *enabled { wesl::Feature::Enable } else { wesl::Feature::Disable },
+            );
+        }
+
+        Ok(config)
+    }
+
+    /// The built-in extensions of the section, in order, the files of extensions go to `out_dir` or `OUT_DIR`
+    ///
+    /// A `bindings` table adds the bindings before the other extensions, and `minify = true` the minifier after them,
+    /// if `extensions` doesn't name them.
+    pub(crate) fn extensions(&self, out_dir: Option<&Path>) -> Result<ExtensionSet, WeslBuildError> {
+        let mut names = self.strings("extensions")?.unwrap_or_default();
+        if self.table.contains_key("bindings") && !names.iter().any(|name| name == "bindings") {
+            names.insert(0, "bindings".to_owned());
+        }
+        if self.bool("minify")? == Some(true) && !names.iter().any(|name| name == "minify") {
+            names.push("minify".to_owned());
+        }
+
+        names.iter()
+            .map(|name| self.extension(name, out_dir))
+            .collect::<Result<Vec<_>, _>>()
+            .map(ExtensionSet::from)
+    }
+
+    fn extension(&self, name: &str, out_dir: Option<&Path>) -> Result<BoxedExtension, WeslBuildError> {
+        #[cfg(feature = "wgpu_bindings_ext")]
+        if name == "bindings" {
+            return self.bindings_extension();
+        }
+        builtin_extension(name, out_dir)
+            .ok_or_else(|| self.error(format!("unknown extension `{name}`, or its feature of wesl_build is not enabled")))
+    }
+
+    /// The bindings with the options of the `bindings` table
+    #[cfg(feature = "wgpu_bindings_ext")]
+    fn bindings_extension(&self) -> Result<BoxedExtension, WeslBuildError> {
+        use std::{fs::File, io::BufWriter};
+
+        use crate::extension::wgpu_bindings::WgpuBindingsExtension;
+
+        type Bindings = WgpuBindingsExtension<BufWriter<File>>;
+        let switches: &[(&str, fn(Bindings, bool) -> Bindings)] = &[
+            ("entry_points", Bindings::with_entry_points),
+            ("layout_assertions", Bindings::with_layout_assertions),
+            ("binding_consts", Bindings::with_binding_consts),
+            ("workgroup_sizes", Bindings::with_workgroup_sizes),
+            ("shared_layouts", Bindings::with_shared_layouts),
+            ("vertex_layouts", Bindings::with_vertex_layouts),
+            ("pipeline_helpers", Bindings::generate_pipeline_helpers),
+            ("pipeline_state", Bindings::with_pipeline_state),
+            ("unchecked_shader_module", Bindings::with_unchecked_shader_module),
+            #[cfg(feature = "hot_reload")]
+            ("hot_reload", Bindings::with_hot_reload),
+            ("stubs", Bindings::generate_stubs),
+            ("format_output", Bindings::format_output),
+        ];
+
+        let options = self.table("bindings")?.cloned().unwrap_or_default();
+        let out = match options.get("out") {
+            None => "src/shader_bindings",
+            Some(MetadataValue::String(out)) => out.as_str(),
+            Some(_) => return Err(self.error("`bindings.out` must be a string".to_owned())),
+        };
+        let mut bindings = Bindings::new(self.package_dir.join(out))?;
+
+        for (key, value) in &options {
+            match (key.as_str(), value) {
+                ("out", _) => (),
+                ("header", MetadataValue::String(header)) => bindings = bindings.with_header(header.as_str()),
+                (key, value) => {
+                    let Some((_, switch)) = switches.iter().find(|(name, _)| *name == key) else {
+                        return Err(self.error(format!(
+                            "unknown bindings option `{key}`, expected `out`, `header` or one of {}",
+                            switches.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(", "),
+                        )));
+                    };
+                    let MetadataValue::Bool(enabled) = value else {
+                        return Err(self.error(format!("`bindings.{key}` must be `true` or `false`")));
+                    };
+                    bindings = switch(bindings, *enabled);
+                }
+            }
+        }
+
+        Ok(Box::new(bindings))
+    }
+
+    fn error(&self, message: String) -> WeslBuildError {
+        WeslBuildError::MetadataConfigErr { package: self.package.to_owned(), message }
+    }
+
+    fn str(&self, key: &str) -> Result<Option<&str>, WeslBuildError> {
+        match self.table.get(key) {
+            None => Ok(None),
+            Some(MetadataValue::String(value)) => Ok(Some(value)),
+            Some(_) => Err(self.error(format!("`{key}` must be a string"))),
+        }
+    }
+
+    fn bool(&self, key: &str) -> Result<Option<bool>, WeslBuildError> {
+        match self.table.get(key) {
+            None => Ok(None),
+            Some(MetadataValue::Bool(value)) => Ok(Some(*value)),
+            Some(_) => Err(self.error(format!("`{key}` must be `true` or `false`"))),
+        }
+    }
+
+    fn strings(&self, key: &str) -> Result<Option<Vec<String>>, WeslBuildError> {
+        let Some(value) = self.table.get(key) else {
+            return Ok(None);
+        };
+        let strings = match value {
+            MetadataValue::Array(values) => values.iter()
+                .map(|value| match value {
+                    MetadataValue::String(value) => Some(value.clone()),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>(),
+            _ => None,
+        };
+        strings.map(Some).ok_or_else(|| self.error(format!("`{key}` must be an array of strings")))
+    }
+
+    fn table(&self, key: &str) -> Result<Option<&MetadataTable>, WeslBuildError> {
+        match self.table.get(key) {
+            None => Ok(None),
+            Some(MetadataValue::Table(table)) => Ok(Some(table)),
+            Some(_) => Err(self.error(format!("`{key}` must be a table"))),
+        }
+    }
+}
+
+/// The built-in extension called `name` with its default options, the files of extensions go to `out_dir` or `OUT_DIR`
+///
+/// `None` if there is none or its feature is not enabled, the bindings need a dir and are made by the callers.
+pub(crate) fn builtin_extension(name: &str, out_dir: Option<&Path>) -> Option<BoxedExtension> {
+    Some(match (name, out_dir) {
+        #[cfg(feature = "wgsl_minifier_ext")]
+        ("minify", _) => Box::new(crate::extension::wgsl_minifier::WgslMinifierExtension::new()),
+        #[cfg(feature = "wesl_lint_ext")]
+        ("lint", _) => Box::new(crate::extension::wesl_lint::WeslLintExtension::new()),
+        #[cfg(feature = "shader_stats_ext")]
+        ("stats", _) => Box::new(crate::extension::shader_stats::ShaderStatsExtension::new()),
+        #[cfg(feature = "wgsl_formatter_ext")]
+        ("format", _) => Box::new(crate::extension::wgsl_formatter::WgslFormatterExtension::new()),
+        #[cfg(feature = "dependency_graph_ext")]
+        ("graph", Some(out_dir)) => Box::new(crate::extension::dependency_graph::DependencyGraphExtension::new().with_output_dir(out_dir)),
+        #[cfg(feature = "dependency_graph_ext")]
+        ("graph", None) => Box::new(crate::extension::dependency_graph::DependencyGraphExtension::new()),
+        #[cfg(feature = "dead_code_report_ext")]
+        ("dead-code", _) => Box::new(crate::extension::dead_code_report::DeadCodeReportExtension::new()),
+        _ => return None,
+    })
+}
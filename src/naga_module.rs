@@ -0,0 +1,54 @@
+#![cfg(feature = "naga")]
+
+use std::ops::{Deref, DerefMut};
+
+/// The naga IR of a built shader, shared by the [`post_parse`](`crate::extension::WeslBuildExtension::post_parse`)
+/// hook of every extension
+///
+/// Derefs to [`naga::Module`], any mutable access marks it as modified so the WGSL is written back after the hooks
+#[derive(Debug)]
+pub struct NagaModule {
+    module: naga::Module,
+    modified: bool,
+}
+
+impl NagaModule {
+    /// Parse the WGSL `source`
+    pub fn parse(source: &str) -> Result<Self, naga::front::wgsl::ParseError> {
+        Ok(Self {
+            module: naga::front::wgsl::parse_str(source)?,
+            modified: false,
+        })
+    }
+
+    /// Whether the module was mutably accessed since it was parsed
+    pub fn is_modified(&self) -> bool {
+        self.modified
+    }
+
+    /// Validate the module and write it as WGSL
+    pub fn to_wgsl(&self) -> Result<String, Box<dyn std::error::Error>> {
+        let mut validator = naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::all(),
+        );
+        let info = validator.validate(&self.module)?;
+
+        Ok(naga::back::wgsl::write_string(&self.module, &info, naga::back::wgsl::WriterFlags::empty())?)
+    }
+}
+
+impl Deref for NagaModule {
+    type Target = naga::Module;
+
+    fn deref(&self) -> &Self::Target {
+        &self.module
+    }
+}
+
+impl DerefMut for NagaModule {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.modified = true;
+        &mut self.module
+    }
+}
@@ -0,0 +1,51 @@
+//! How the names of shader files and dirs become the names of modules
+
+use std::path::Path;
+
+use crate::WeslBuildError;
+
+pub use wesl_build_names::normalize_name;
+
+/// How the names of shader files and dirs that aren't identifiers, like `blur-pass.wesl` or `光.wesl`,
+/// become module names, see [`WeslBuildConfig::file_names`](`crate::WeslBuildConfig::file_names`)
+///
+/// The module names are used everywhere a shader is named: imports, artifacts, bindings and `include_wesl!`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FileNames {
+    /// Use the names as they are, `blur-pass.wesl` is the module `blur-pass`, which can't be imported
+    #[default]
+    Verbatim,
+    /// Turn the names into identifiers with [`normalize_name`], `blur-pass.wesl` is the module `blur_pass`
+    Normalize,
+    /// Fail the build with [`WeslBuildError::InvalidFileName`] for names that aren't identifiers
+    Reject,
+}
+
+impl FileNames {
+    /// The name reported to `include_wesl!`
+    ///
+    /// !! keep in sync with the file names supported by `include_wesl!` in wesl_build_import !!
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            FileNames::Verbatim => "verbatim",
+            FileNames::Normalize => "normalize",
+            FileNames::Reject => "reject",
+        }
+    }
+
+    /// The module name of the file or dir `name`, without its extension, at `path`
+    pub fn module_name(self, name: &str, path: &Path) -> Result<String, WeslBuildError> {
+        match self {
+            FileNames::Verbatim => Ok(name.to_owned()),
+            FileNames::Normalize => Ok(normalize_name(name)),
+            FileNames::Reject if is_identifier(name) => Ok(name.to_owned()),
+            FileNames::Reject => Err(WeslBuildError::InvalidFileName { path: path.to_owned() }),
+        }
+    }
+}
+
+/// Whether `name` is an ASCII identifier, a letter or `_` followed by letters, digits and `_`
+pub fn is_identifier(name: &str) -> bool {
+    name.chars().next().is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
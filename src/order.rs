@@ -0,0 +1,68 @@
+use itertools::Itertools;
+
+use crate::{BuildResolver, WeslBuildError, extension::WeslBuildExtension};
+
+/// Reorder `extensions` so every [`must_run_after`](`WeslBuildExtension::must_run_after`) and
+/// [`must_run_before`](`WeslBuildExtension::must_run_before`) is satisfied, otherwise keeping the order they were given in
+///
+/// Errors with [`WeslBuildError::ExtensionOrderErr`] if the constraints form a cycle.
+pub(crate) fn order_extensions(
+    extensions: &mut [Box<dyn WeslBuildExtension<BuildResolver>>],
+) -> Result<(), WeslBuildError> {
+    let names = extensions.iter().map(|ext| ext.name().into_owned()).collect::<Vec<_>>();
+
+    // runs_after[idx] are the extensions that must run before `idx`
+    let mut runs_after = vec![Vec::new(); extensions.len()];
+    for (idx, ext) in extensions.iter().enumerate() {
+        for &name in ext.must_run_after() {
+            runs_after[idx].extend(names.iter().positions(|ext_name| ext_name == name));
+        }
+        for &name in ext.must_run_before() {
+            for later in names.iter().positions(|ext_name| ext_name == name) {
+                runs_after[later].push(idx);
+            }
+        }
+    }
+
+    // stable topological sort, the first extension in the given order that can run goes next
+    let mut order = Vec::with_capacity(extensions.len());
+    let mut placed = vec![false; extensions.len()];
+    while order.len() < extensions.len() {
+        let Some(next) = (0..extensions.len())
+            .find(|&idx| !placed[idx] && runs_after[idx].iter().all(|&before| placed[before] || before == idx))
+        else {
+            return Err(WeslBuildError::ExtensionOrderErr {
+                extensions: (0..extensions.len())
+                    .filter(|&idx| !placed[idx])
+                    .map(|idx| names[idx].clone())
+                    .collect(),
+            });
+        };
+        placed[next] = true;
+        order.push(next);
+    }
+
+    #[cfg(feature = "logging")]
+    for (pos, &idx) in order.iter().enumerate() {
+        if extensions[idx].reads_built_wgsl()
+            && let Some(&mutator) = order[..pos].iter().find(|&&before| extensions[before].mutates_built_wgsl())
+        {
+            log::debug!("{} reads the built WGSL after {} changed it", names[idx], names[mutator]);
+        }
+    }
+
+    // position[idx] is where the extension at idx goes
+    let mut position = vec![0; extensions.len()];
+    for (pos, &idx) in order.iter().enumerate() {
+        position[idx] = pos;
+    }
+    for idx in 0..extensions.len() {
+        while position[idx] != idx {
+            let target = position[idx];
+            extensions.swap(idx, target);
+            position.swap(idx, target);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,181 @@
+//! Shader packages from outside the shader dir, imported by name like `import util::color::luma;`
+//!
+//! A package is a dir of shaders, either a path or the shader dir of another crate.
+//! A crate shares its shaders by setting `links` in its `Cargo.toml` and calling [`export_shader_package`] in its `build.rs`,
+//! cargo then passes the dir to the build scripts of its direct dependents.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::WeslBuildError;
+
+/// The `cargo::metadata` key [`export_shader_package`] sets, dependents see it as `DEP_<LINKS>_WESL_SHADERS`
+pub const PACKAGE_METADATA_KEY: &str = "WESL_SHADERS";
+
+/// The manifest [`ShaderPackageExtension`](`crate::extension::shader_package::ShaderPackageExtension`) writes next to the shaders
+pub const PACKAGE_MANIFEST_NAME: &str = "wesl.toml";
+/// The dir of the shaders in a packaged shader dir
+pub const PACKAGE_SOURCE_DIR: &str = "shaders";
+
+/// Where the shaders of a package are
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PackageSource {
+    /// A dir of shaders, or a dir with a [`PACKAGE_MANIFEST_NAME`] manifest and its shaders in `root`
+    Dir(PathBuf),
+    /// The shader dir a dependency exported with [`export_shader_package`], by the `links` name of the dependency
+    Links(String),
+}
+
+impl PackageSource {
+    /// The shaders exported by the dependency with `links = "<links>"`
+    pub fn links(links: impl Into<String>) -> Self {
+        Self::Links(links.into())
+    }
+
+    /// The env var cargo sets to the exported dir of a [`Links`](Self::Links) package
+    pub fn env_var(links: &str) -> String {
+        format!("DEP_{}_{PACKAGE_METADATA_KEY}", links.to_uppercase().replace('-', "_"))
+    }
+
+    /// The dir of the package's shaders
+    pub fn dir(&self, name: &str) -> Result<PathBuf, WeslBuildError> {
+        let dir = match self {
+            Self::Dir(dir) => dir.clone(),
+            Self::Links(links) => {
+                let env = Self::env_var(links);
+                std::env::var_os(&env)
+                    .map(PathBuf::from)
+                    .ok_or_else(|| WeslBuildError::MissingPackage { name: name.to_owned(), env })?
+            }
+        };
+
+        match fs::read_to_string(dir.join(PACKAGE_MANIFEST_NAME)) {
+            Ok(manifest) => Ok(dir.join(manifest_root(&manifest).unwrap_or(PACKAGE_SOURCE_DIR))),
+            Err(_) => Ok(dir),
+        }
+    }
+}
+
+impl From<PathBuf> for PackageSource {
+    fn from(dir: PathBuf) -> Self {
+        Self::Dir(dir)
+    }
+}
+
+impl From<&Path> for PackageSource {
+    fn from(dir: &Path) -> Self {
+        Self::Dir(dir.to_owned())
+    }
+}
+
+impl From<&str> for PackageSource {
+    fn from(dir: &str) -> Self {
+        Self::Dir(dir.into())
+    }
+}
+
+/// A package added with [`WeslBuildConfig::add_package`](`crate::WeslBuildConfig::add_package`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderPackage {
+    /// The name shaders import it by
+    pub name: String,
+    pub source: PackageSource,
+}
+
+impl ShaderPackage {
+    /// Read every shader of the package into a tree of modules, whose root is named after the package
+    pub(crate) fn load(&self) -> Result<PackageModule, WeslBuildError> {
+        if self.name.is_empty()
+            || !self.name.chars().all(|c| c.is_alphanumeric() || c == '_')
+            || matches!(self.name.as_str(), "package" | "self" | "super")
+        {
+            return Err(WeslBuildError::InvalidPackageName { name: self.name.clone() });
+        }
+        let dir = self.source.dir(&self.name)?;
+
+        #[cfg(feature = "logging")]
+        log::debug!("loading shader package `{}` from {}", self.name, dir.display());
+
+        load_module(self.name.clone(), String::new(), None, &dir)
+    }
+}
+
+/// A module of a loaded [`ShaderPackage`], with a submodule for every shader and dir in its dir
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PackageModule {
+    pub name: String,
+    /// Empty for a dir without a shader of the same name
+    pub source: String,
+    /// The shader file of the module, `None` for a dir
+    pub path: Option<PathBuf>,
+    pub submodules: Vec<PackageModule>,
+}
+
+impl PackageModule {
+    /// The submodule at `components` below this module, itself for none
+    pub fn module(&self, components: &[String]) -> Option<&PackageModule> {
+        components.iter().try_fold(self, |module, component| module.submodules.iter().find(|submodule| &submodule.name == component))
+    }
+}
+
+/// The `root` of the `[package]` in a manifest
+fn manifest_root(manifest: &str) -> Option<&str> {
+    manifest.lines()
+        .skip_while(|line| line.trim() != "[package]")
+        .skip(1)
+        .take_while(|line| !line.trim_start().starts_with('['))
+        .find_map(|line| line.split_once('=').filter(|(key, _)| key.trim() == "root"))
+        .map(|(_, root)| root.trim().trim_matches('"'))
+}
+
+/// The module `name` with `source` from the shader file `path`, with a submodule for every shader and dir in `dir`
+fn load_module(name: String, source: String, path: Option<PathBuf>, dir: &Path) -> Result<PackageModule, WeslBuildError> {
+    let mut submodules = Vec::new();
+
+    if dir.is_dir() {
+        let mut entries = fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+
+        for entry in entries {
+            let path = entry.path();
+            let is_shader = path.extension().is_some_and(|ext| ext == "wesl" || ext == "wgsl");
+            let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                return Err(WeslBuildError::NonUtf8Path { path });
+            };
+
+            if entry.file_type()?.is_dir() {
+                // a dir with a shader of the same name is loaded with the shader
+                if !dir.join(format!("{stem}.wesl")).exists() && !dir.join(format!("{stem}.wgsl")).exists() {
+                    submodules.push(load_module(stem.to_owned(), String::new(), None, &path)?);
+                }
+            } else if is_shader {
+                let source = fs::read_to_string(&path)?;
+                submodules.push(load_module(stem.to_owned(), source, Some(path.clone()), &path.with_extension(""))?);
+            }
+        }
+    }
+
+    Ok(PackageModule { name, source, path, submodules })
+}
+
+/// Share the shaders in `shader_path` with the crates that depend on this one, call it from `build.rs`
+///
+/// `shader_path` is a shader dir or a package written by
+/// [`ShaderPackageExtension`](`crate::extension::shader_package::ShaderPackageExtension`).
+/// The crate must set `links` in its `Cargo.toml`, dependents add the package with
+/// [`PackageSource::links`] and the same name.
+///
+/// ## Example
+/// In the `build.rs` of a crate with `links = "my_shader_lib"`:
+/// ```no_run
+/// wesl_build::package::export_shader_package("src/shaders").expect("the shader dir exists");
+/// ```
+pub fn export_shader_package(shader_path: impl AsRef<Path>) -> Result<(), WeslBuildError> {
+    let shader_root = fs::canonicalize(shader_path)?;
+    println!("cargo::metadata={PACKAGE_METADATA_KEY}={}", shader_root.display());
+    println!("cargo::rerun-if-changed={}", shader_root.display());
+
+    Ok(())
+}
@@ -0,0 +1,30 @@
+use std::path::PathBuf;
+
+use wesl::ModulePath;
+
+use crate::artifacts::WriteStats;
+
+/// A shader that is (or in a [dry run](`crate::WeslBuildConfig::dry_run`) would be) built
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedShader {
+    pub mod_path: ModulePath,
+    /// The source `.wesl`/`.wgsl` file, empty for a [virtual module](`crate::BuildResolver::add_virtual_module`)
+    pub source_path: PathBuf,
+    /// The mangled name of the built artifact, as used by `include_wesl!`, `/` separated on every platform
+    pub artifact_name: String,
+    /// Where the built WGSL is written, `None` if no output dir is set (only possible in a dry run)
+    pub output_path: Option<PathBuf>,
+    /// The files the shader was built from as reported by the resolver, including imports from outside
+    /// the shader dir, empty in a dry run
+    pub dependencies: Vec<PathBuf>,
+}
+
+/// Every shader of a [`build_shader_dir_with_config`](`crate::build_shader_dir_with_config`) run, in build order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BuildPlan {
+    pub shaders: Vec<PlannedShader>,
+    /// The sidecar files in the shader dir, see [`asset_extensions`](`crate::WeslBuildConfig::asset_extensions`)
+    pub assets: Vec<PathBuf>,
+    /// The files written to disk and those left alone as they were up to date, none in a check or dry run
+    pub writes: WriteStats,
+}
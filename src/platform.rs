@@ -0,0 +1,93 @@
+use std::{cell::RefCell, path::{Path, PathBuf}};
+
+/// The platform of a cargo target triple, `None` if it has no platform specific shaders
+///
+/// | target | platform |
+/// |---|---|
+/// | `wasm32-*` | `webgpu` |
+/// | `*-apple-*` | `metal` |
+/// | `*-windows-*` | `dx12` |
+/// | `*-linux-*`, `*-android*` | `vulkan` |
+pub fn target_platform(target: &str) -> Option<&'static str> {
+    if target.starts_with("wasm32") {
+        Some("webgpu")
+    } else if target.contains("-apple-") {
+        Some("metal")
+    } else if target.contains("-windows") {
+        Some("dx12")
+    } else if target.contains("-linux") || target.contains("-android") {
+        Some("vulkan")
+    } else {
+        None
+    }
+}
+
+/// A shader file, maybe the override of another for a platform
+pub(crate) enum ShaderFile {
+    /// A shader of its own
+    Shader,
+    /// A shader with an override for the platform being built, which is built instead
+    Overridden,
+    /// The override for the platform being built of the shader at `base` without an extension,
+    /// e.g. `blur.metal.wesl` of `blur`
+    Override { base: PathBuf },
+    /// The override of a shader for another platform
+    OtherPlatform,
+}
+
+impl ShaderFile {
+    /// Whether the shader file `path` is an override, `blur.<platform>.wesl` for `blur.wesl`
+    ///
+    /// A shader name with more than one `.` is not an override and is reported as an invalid name by the build
+    pub(crate) fn of(path: &Path, platform: Option<&str>) -> Self {
+        let Some((base_name, file_platform)) = path.file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.split_once('.'))
+            .filter(|(base_name, file_platform)| is_name(base_name) && is_name(file_platform))
+        else {
+            let has_override = platform.zip(path.file_stem().and_then(|stem| stem.to_str()))
+                .is_some_and(|(platform, stem)| ["wesl", "wgsl"].iter()
+                    .any(|ext| path.with_file_name(format!("{stem}.{platform}.{ext}")).is_file()));
+            return if has_override { Self::Overridden } else { Self::Shader };
+        };
+
+        if platform == Some(file_platform) {
+            Self::Override { base: path.with_file_name(base_name) }
+        } else {
+            Self::OtherPlatform
+        }
+    }
+}
+
+fn is_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+}
+
+thread_local! {
+    /// The [`WeslBuildConfig::platform`](`crate::WeslBuildConfig::platform`) of the build running on this thread
+    static CURRENT_PLATFORM: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// The platform the shaders are built for, which selects the `<name>.<platform>.wesl` overrides
+///
+/// This is the [`WeslBuildConfig::platform`](`crate::WeslBuildConfig::platform`) of the build if set,
+/// otherwise the [`target_platform`] of cargo's `TARGET` env var
+pub fn current_platform() -> Option<String> {
+    CURRENT_PLATFORM.with_borrow(Clone::clone)
+        .or_else(|| std::env::var("TARGET").ok().and_then(|target| target_platform(&target)).map(str::to_owned))
+}
+
+/// Sets the [`current_platform`] until it is dropped
+pub(crate) struct PlatformGuard(Option<String>);
+
+impl PlatformGuard {
+    pub(crate) fn set(platform: Option<String>) -> Self {
+        Self(CURRENT_PLATFORM.replace(platform))
+    }
+}
+
+impl Drop for PlatformGuard {
+    fn drop(&mut self) {
+        CURRENT_PLATFORM.set(self.0.take());
+    }
+}
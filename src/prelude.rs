@@ -0,0 +1,19 @@
+//! Everything a build script needs in one `use`, including the `wesl` types of the wesl_build API
+//!
+//! ```
+//! use wesl_build::prelude::*;
+//!
+//! let mut options = CompileOptions::default();
+//! options.features.flags.insert("f16".to_owned(), Feature::Enable);
+//! let config = WeslBuildConfig::new("src/shaders").compile_options(options).mangler(ManglerKind::Escape);
+//! ```
+//!
+//! The `wesl` types are those wesl_build is built with, so a build script doesn't need a `wesl` dependency
+//! of its own, whose version could differ. The rest of `wesl` is at [`wesl_build::wesl`](`crate::wesl`).
+
+pub use wesl::{BasicSourceMap, CompileOptions, Feature, Features, ManglerKind, ModulePath, Wesl};
+
+pub use crate::extension::WeslBuildExtension;
+pub use crate::{
+    BuildResolver, WeslBuildConfig, WeslBuildError, build_shader_dir, build_shader_dir_with_config, extensions,
+};
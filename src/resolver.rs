@@ -0,0 +1,334 @@
+//! The resolver of a build, and resolvers for modules that only exist in memory
+
+use std::{borrow::Cow, cell::RefCell, collections::{BTreeMap, HashMap}, path::{Path, PathBuf}};
+
+use wesl::{ModulePath, ResolveError, Resolver, StandardResolver, Wesl, syntax::PathOrigin};
+
+use crate::{WeslBuildConfig, WeslBuildError, package::PackageModule, platform::ShaderFile, walk::DirWalk};
+
+/// The resolver of a build, a stack of layers where the first that has a module resolves it
+///
+/// From the top:
+/// 1. the sources extensions rewrote in [`preprocess`](`crate::extension::WeslBuildExtension::preprocess`)
+/// 2. the [virtual modules](Self::add_virtual_module) extensions added
+/// 3. the [layers](Self::push_layer) extensions pushed, the last pushed first
+/// 4. the [platform overrides](`crate::WeslBuildConfig::platform`) of shaders in the shader dir
+/// 5. the shaders whose module names are not their [file names](`crate::WeslBuildConfig::file_names`)
+/// 6. the shader dir and [packages](`crate::WeslBuildConfig::add_package`)
+///
+/// Extensions get it from the `res` of their hooks with [`Wesl::resolver`](`wesl::Wesl::resolver`).
+pub struct BuildResolver {
+    base: StandardResolver,
+    /// Modules generated by extensions, they are built like the shaders in the shader dir
+    virtual_modules: RefCell<VirtualResolver>,
+    /// The layers pushed by extensions, over the shader dir
+    layers: RefCell<ResolverStack>,
+    /// The override file of every shader with one for the platform being built
+    platform_overrides: BTreeMap<ModulePath, PathBuf>,
+    /// The file of every shader whose module name is not its file name, see [`WeslBuildConfig::file_names`]
+    renamed_files: BTreeMap<ModulePath, PathBuf>,
+    /// The preprocessed source of every module an extension rewrote
+    preprocessed: RefCell<HashMap<ModulePath, String>>,
+    /// The root module of every package, named after it
+    packages: Vec<PackageModule>,
+}
+
+impl BuildResolver {
+    pub(crate) fn new(shader_path: &str) -> Self {
+        Self {
+            base: StandardResolver::new(shader_path),
+            virtual_modules: RefCell::new(VirtualResolver::new()),
+            layers: RefCell::new(ResolverStack::new()),
+            platform_overrides: BTreeMap::new(),
+            renamed_files: BTreeMap::new(),
+            preprocessed: RefCell::new(HashMap::new()),
+            packages: Vec::new(),
+        }
+    }
+
+    /// Let shaders import the package of `root` by its name
+    pub(crate) fn add_package(&mut self, root: PackageModule) {
+        self.packages.push(root);
+    }
+
+    /// The module of the package `path` imports, `None` if it isn't in a package
+    fn package_module(&self, path: &ModulePath) -> Option<&PackageModule> {
+        let PathOrigin::Package(name) = &path.origin else {
+            return None;
+        };
+        self.packages.iter()
+            .find(|root| &root.name == name)
+            .and_then(|root| root.module(&path.components))
+    }
+
+    /// The override file `mod_path` is resolved from, `None` if it has no override for the platform being built
+    pub fn platform_override(&self, mod_path: &ModulePath) -> Option<&Path> {
+        self.platform_overrides.get(mod_path).map(PathBuf::as_path)
+    }
+
+    /// The file of `mod_path` if its module name is not its file name, e.g. `blur-pass.wesl` for `blur_pass`,
+    /// see [`WeslBuildConfig::file_names`]
+    pub fn renamed_file(&self, mod_path: &ModulePath) -> Option<&Path> {
+        self.renamed_files.get(mod_path).map(PathBuf::as_path)
+    }
+
+    /// The resolver of the shader dir, without the packages, preprocessed sources and virtual modules
+    pub fn standard(&self) -> &StandardResolver {
+        &self.base
+    }
+
+    /// Add a module that only exists in memory, e.g. a generated shader, call it from
+    /// [`init_root`](`crate::extension::WeslBuildExtension::init_root`)
+    ///
+    /// It is built after the shaders in the shader dir, which can import it.
+    /// A virtual module at the path of a shader file replaces the source of the file.
+    pub fn add_virtual_module(&self, mod_path: ModulePath, source: impl Into<String>) {
+        self.virtual_modules.borrow_mut().add_module(mod_path, source);
+    }
+
+    /// Resolve modules with `layer` before the shader dir and the layers pushed before it, call it from
+    /// [`init_root`](`crate::extension::WeslBuildExtension::init_root`)
+    ///
+    /// e.g. to override shaders with fixtures in tests, or with tuned sources per platform.
+    /// Unlike [virtual modules](Self::add_virtual_module), modules only in a layer are not built on their own.
+    pub fn push_layer(&self, layer: impl Resolver + 'static) {
+        self.layers.borrow_mut().push(layer);
+    }
+
+    /// The path of every virtual module, sorted
+    pub fn virtual_modules(&self) -> Vec<ModulePath> {
+        self.virtual_modules.borrow().modules().cloned().collect()
+    }
+
+    /// The preprocessed source of `mod_path`, `None` if no extension rewrote it
+    pub fn preprocessed(&self, mod_path: &ModulePath) -> Option<String> {
+        self.preprocessed.borrow().get(mod_path).cloned()
+    }
+
+    pub(crate) fn set_preprocessed(&self, mod_path: ModulePath, source: String) {
+        self.preprocessed.borrow_mut().insert(mod_path, source);
+    }
+}
+
+/// A compiler of the shaders of `config` with a [`BuildResolver`] that has its packages
+pub(crate) fn build_wesl(config: &WeslBuildConfig) -> Result<Wesl<BuildResolver>, WeslBuildError> {
+    let mut resolver = BuildResolver::new(&config.shader_path);
+    for package in &config.packages {
+        resolver.add_package(package.load()?);
+    }
+    let walk = DirWalk::of_config(config);
+    let platform = walk.platform().map(str::to_owned);
+    let shader_root = Path::new(&config.shader_path);
+    let modules = crate::graph::source_modules(shader_root, walk)?;
+    crate::graph::check_case_collisions(shader_root, &modules, config.case_collisions)?;
+    for (mod_path, path) in modules {
+        if matches!(ShaderFile::of(&path, platform.as_deref()), ShaderFile::Override { .. }) {
+            resolver.platform_overrides.insert(mod_path, path);
+            continue;
+        }
+        let at_module_path = path.strip_prefix(shader_root)
+            .is_ok_and(|relative| relative.with_extension("") == mod_path.components.iter().collect::<PathBuf>());
+        if !at_module_path {
+            resolver.renamed_files.insert(mod_path, path);
+        }
+    }
+
+    let mut wesl = Wesl::new_barebones().set_custom_resolver(resolver);
+    wesl.set_options(config.compile_options.clone());
+    wesl.set_mangler(config.mangler);
+    Ok(wesl)
+}
+
+impl Resolver for BuildResolver {
+    fn resolve_source<'a>(&'a self, path: &ModulePath) -> Result<Cow<'a, str>, ResolveError> {
+        if let Some(source) = self.preprocessed(path) {
+            return Ok(Cow::Owned(source));
+        }
+        if let Some(source) = self.virtual_modules.borrow().source(path) {
+            return Ok(Cow::Owned(source.to_owned()));
+        }
+        match self.layers.borrow().layer_of(path) {
+            Some(layer) => layer.resolve_source(path).map(|source| Cow::Owned(source.into_owned())),
+            None => match self.platform_overrides.get(path).or_else(|| self.renamed_files.get(path)) {
+                Some(file_path) => std::fs::read_to_string(file_path)
+                    .map(Cow::Owned)
+                    .map_err(|error| ResolveError::ModuleNotFound(path.clone(), error.to_string())),
+                None if matches!(path.origin, PathOrigin::Package(_)) => self.package_module(path)
+                    .map(|module| Cow::Borrowed(module.source.as_str()))
+                    .ok_or_else(|| ResolveError::ModuleNotFound(path.clone(), "no package has this module".to_owned())),
+                None => self.base.resolve_source(path),
+            },
+        }
+    }
+
+    fn display_name(&self, path: &ModulePath) -> Option<String> {
+        if let Some(name) = self.virtual_modules.borrow().display_name(path) {
+            return Some(name);
+        }
+        match self.layers.borrow().layer_of(path) {
+            Some(layer) => layer.display_name(path),
+            None => match self.platform_overrides.get(path).or_else(|| self.renamed_files.get(path)) {
+                Some(file_path) => Some(file_path.display().to_string()),
+                None => match self.package_module(path) {
+                    Some(module) => module.path.as_ref().map(|file_path| file_path.display().to_string()),
+                    None => self.base.display_name(path),
+                },
+            },
+        }
+    }
+
+    fn fs_path(&self, path: &ModulePath) -> Option<PathBuf> {
+        if self.virtual_modules.borrow().source(path).is_some() {
+            return None;
+        }
+        match self.layers.borrow().layer_of(path) {
+            Some(layer) => layer.fs_path(path),
+            None => match self.platform_overrides.get(path).or_else(|| self.renamed_files.get(path)) {
+                Some(file_path) => Some(file_path.clone()),
+                None => self.base.fs_path(path),
+            },
+        }
+    }
+}
+
+/// Layers resolvers, a module is resolved by the last pushed layer that has it
+///
+/// ## Example
+/// ```
+/// use wesl::{Resolver, StandardResolver};
+/// use wesl_build::resolver::{ResolverStack, VirtualResolver, module_path};
+///
+/// let mut overrides = VirtualResolver::new();
+/// overrides.add_module(module_path("blur").unwrap(), "const TAPS: u32 = 9u;");
+///
+/// let stack = ResolverStack::new()
+///     .with(StandardResolver::new("src/shaders"))
+///     .with(overrides);
+/// assert_eq!(stack.resolve_source(&module_path("blur").unwrap()).unwrap(), "const TAPS: u32 = 9u;");
+/// ```
+#[derive(Default)]
+pub struct ResolverStack {
+    /// From the bottom
+    layers: Vec<Box<dyn Resolver>>,
+}
+
+impl ResolverStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Push `layer` on top
+    pub fn with(mut self, layer: impl Resolver + 'static) -> Self {
+        self.push(layer);
+        self
+    }
+
+    /// Push `layer` on top
+    pub fn push(&mut self, layer: impl Resolver + 'static) {
+        self.layers.push(Box::new(layer));
+    }
+
+    pub fn len(&self) -> usize {
+        self.layers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    /// The top layer that has the module `path`
+    pub fn layer_of(&self, path: &ModulePath) -> Option<&dyn Resolver> {
+        self.layers.iter().rev()
+            .find(|layer| layer.resolve_source(path).is_ok())
+            .map(Box::as_ref)
+    }
+}
+
+impl Resolver for ResolverStack {
+    fn resolve_source<'a>(&'a self, path: &ModulePath) -> Result<Cow<'a, str>, ResolveError> {
+        let mut not_found = None;
+        for layer in self.layers.iter().rev() {
+            match layer.resolve_source(path) {
+                Ok(source) => return Ok(source),
+                // the error of the top layer is the most relevant
+                Err(error) => {
+                    not_found.get_or_insert(error);
+                }
+            }
+        }
+
+        Err(not_found.unwrap_or_else(|| ResolveError::ModuleNotFound(path.clone(), "the resolver stack is empty".to_owned())))
+    }
+
+    fn display_name(&self, path: &ModulePath) -> Option<String> {
+        self.layer_of(path).and_then(|layer| layer.display_name(path))
+    }
+
+    fn fs_path(&self, path: &ModulePath) -> Option<PathBuf> {
+        self.layer_of(path).and_then(|layer| layer.fs_path(path))
+    }
+}
+
+/// Resolves modules from sources in memory
+///
+/// ## Example
+/// ```
+/// use wesl_build::resolver::{VirtualResolver, module_path};
+///
+/// let mut resolver = VirtualResolver::new();
+/// resolver.add_module(module_path("blur/blur_5").unwrap(), "const TAPS: u32 = 5;");
+/// assert!(resolver.source(&module_path("blur::blur_5").unwrap()).is_some());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct VirtualResolver {
+    modules: BTreeMap<ModulePath, String>,
+}
+
+impl VirtualResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the module `mod_path`, replacing it if it exists
+    pub fn add_module(&mut self, mod_path: ModulePath, source: impl Into<String>) {
+        self.modules.insert(mod_path, source.into());
+    }
+
+    /// The source of `mod_path`
+    pub fn source(&self, mod_path: &ModulePath) -> Option<&str> {
+        self.modules.get(mod_path).map(String::as_str)
+    }
+
+    /// Every module path, sorted
+    pub fn modules(&self) -> impl Iterator<Item = &ModulePath> {
+        self.modules.keys()
+    }
+}
+
+impl Resolver for VirtualResolver {
+    fn resolve_source<'a>(&'a self, path: &ModulePath) -> Result<Cow<'a, str>, ResolveError> {
+        self.source(path)
+            .map(Cow::Borrowed)
+            .ok_or_else(|| ResolveError::ModuleNotFound(path.clone(), "no virtual module at this path".to_owned()))
+    }
+
+    fn display_name(&self, path: &ModulePath) -> Option<String> {
+        self.source(path).map(|_| format!("<generated {path}>"))
+    }
+}
+
+/// The absolute module path of `module`, a path like `blur/blur_5` or `blur::blur_5`
+///
+/// Fails with [`WeslBuildError::InvalidFileName`] if a component is empty or contains a `.` or `:`,
+/// the same rules as the names of shader files.
+pub fn module_path(module: &str) -> Result<ModulePath, WeslBuildError> {
+    let components = module.split(['/', '\\'])
+        .flat_map(|part| part.split("::"))
+        .map(|component| match component.is_empty() || component.contains(['.', ':']) {
+            true => Err(WeslBuildError::InvalidFileName { path: Path::new(module).to_owned() }),
+            false => Ok(component.to_owned()),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(ModulePath::new(wesl::syntax::PathOrigin::Absolute, components))
+}
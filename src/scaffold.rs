@@ -0,0 +1,166 @@
+//! Create new shaders with a skeleton for their stage
+
+use std::{fs, io::Write as _, path::{Path, PathBuf}};
+
+use crate::WeslBuildError;
+
+/// The kind of shader [`new_shader`] creates, each with an entry point and bind group stubs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaderKind {
+    /// A compute entry point with a uniform and a storage buffer
+    Compute,
+    /// A vertex entry point with a camera uniform
+    Vertex,
+    /// A fragment entry point with a texture and sampler
+    Fragment,
+    /// A vertex and a fragment entry point
+    Render,
+}
+
+impl ShaderKind {
+    /// The WESL source of a new shader of this kind
+    pub fn skeleton(self) -> &'static str {
+        match self {
+            ShaderKind::Compute => COMPUTE_SKELETON,
+            ShaderKind::Vertex => VERTEX_SKELETON,
+            ShaderKind::Fragment => FRAGMENT_SKELETON,
+            ShaderKind::Render => RENDER_SKELETON,
+        }
+    }
+}
+
+const COMPUTE_SKELETON: &str = "\
+struct Params {
+    count: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read_write> data: array<f32>;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= params.count {
+        return;
+    }
+
+    data[id.x] = data[id.x] * 2.0;
+}
+";
+
+const VERTEX_SKELETON: &str = "\
+struct Camera {
+    view_proj: mat4x4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> @builtin(position) vec4<f32> {
+    return camera.view_proj * vec4(in.position, 1.0);
+}
+";
+
+const FRAGMENT_SKELETON: &str = "\
+@group(0) @binding(0) var color_texture: texture_2d<f32>;
+@group(0) @binding(1) var color_sampler: sampler;
+
+@fragment
+fn fs_main(@location(0) uv: vec2<f32>) -> @location(0) vec4<f32> {
+    return textureSample(color_texture, color_sampler, uv);
+}
+";
+
+const RENDER_SKELETON: &str = "\
+struct Camera {
+    view_proj: mat4x4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+@group(1) @binding(0) var color_texture: texture_2d<f32>;
+@group(1) @binding(1) var color_sampler: sampler;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) uv: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = camera.view_proj * vec4(in.position, 1.0);
+    out.uv = in.uv;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(color_texture, color_sampler, in.uv);
+}
+";
+
+/// A shader created by [`new_shader`]
+#[derive(Debug, Clone)]
+pub struct NewShader {
+    /// The created `.wesl` file
+    pub path: PathBuf,
+    /// The module path of the shader, as used in `include_wesl!`
+    pub module: String,
+}
+
+impl NewShader {
+    /// Rust code that creates a `wgpu` shader module from the built shader
+    pub fn rust_snippet(&self) -> String {
+        let name = self.module.rsplit("::").next().unwrap_or(&self.module);
+
+        format!(
+            "let {name}_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {{
+    label: Some(\"{module}\"),
+    source: wgpu::ShaderSource::Wgsl(wesl_build_import::include_wesl!({module}).into()),
+}});
+",
+            module = self.module,
+        )
+    }
+}
+
+/// Create the shader `module` of `kind` in `shader_root`, with the dirs of its parent modules
+///
+/// `module` is a path like `particles/update` or `particles::update`,
+/// fails with [`std::io::ErrorKind::AlreadyExists`] if a `.wesl` or `.wgsl` shader of that module exists.
+pub fn new_shader(shader_root: &Path, kind: ShaderKind, module: &str) -> Result<NewShader, WeslBuildError> {
+    let components = module.split(['/', '\\']).flat_map(|part| part.split("::")).collect::<Vec<_>>();
+    let mut path = shader_root.to_owned();
+    for component in &components {
+        // same rules as the module paths of built shaders
+        if component.is_empty() || component.contains(['.', ':']) {
+            return Err(WeslBuildError::InvalidFileName { path: path.join(component) });
+        }
+        path.push(component);
+    }
+
+    let path = path.with_extension("wesl");
+    if path.with_extension("wgsl").exists() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!("{} already exists", path.with_extension("wgsl").display()),
+        ).into());
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::File::create_new(&path)?.write_all(kind.skeleton().as_bytes())?;
+
+    #[cfg(feature = "logging")]
+    log::info!("created {:?} shader {}", kind, path.display());
+
+    Ok(NewShader { path, module: components.join("::") })
+}
@@ -0,0 +1,179 @@
+//! Snapshot testing of built shaders
+//!
+//! Build a shader dir into a temp dir and compare the built WGSL against committed snapshots.
+//! Missing or changed snapshots fail the test and are written next to the snapshot as `<name>.wgsl.new`,
+//! run the tests with `WESL_BUILD_UPDATE_SNAPSHOTS=1` to accept them.
+//!
+//! ## Example
+//! ```no_run
+//! use wesl_build::{testing, WeslBuildConfig};
+//!
+//! #[test]
+//! fn shaders_unchanged() {
+//!     testing::build_shaders(&WeslBuildConfig::new("src/shaders"))
+//!         .expect("Building shaders failed")
+//!         .assert_snapshots("tests/shader_snapshots");
+//! }
+//! ```
+
+use std::{cell::RefCell, fmt::Write as _, fs, path::{Path, PathBuf}, rc::Rc};
+
+use wesl::{BasicSourceMap, ModulePath};
+
+use crate::{build_shader_dir_with_config, BuildResolver, WeslBuildConfig, WeslBuildError};
+use crate::extension::WeslBuildExtension;
+
+/// Set to accept new and changed snapshots
+pub const UPDATE_SNAPSHOTS_ENV: &str = "WESL_BUILD_UPDATE_SNAPSHOTS";
+
+/// The shaders built by [`build_shaders`], they are removed with it
+pub struct BuiltShaders {
+    out_dir: tempfile::TempDir,
+    shaders: Vec<(ModulePath, String)>,
+}
+
+/// Build the shaders of `config` into a temp dir, the `out_dir` of `config` is ignored
+pub fn build_shaders(config: &WeslBuildConfig) -> Result<BuiltShaders, WeslBuildError> {
+    build_shaders_with(config, Vec::new())
+}
+
+/// [`build_shaders`] running `extensions` after building each shader
+pub fn build_shaders_with(
+    config: &WeslBuildConfig,
+    mut extensions: Vec<Box<dyn WeslBuildExtension<BuildResolver>>>,
+) -> Result<BuiltShaders, WeslBuildError> {
+    let out_dir = tempfile::tempdir()?;
+    let built = Rc::new(RefCell::new(Vec::new()));
+    extensions.push(Box::new(BuiltShaderCollector { built: Rc::clone(&built) }));
+
+    build_shader_dir_with_config(&config.clone().out_dir(out_dir.path()), &mut extensions)?;
+    drop(extensions);
+
+    let mut shaders = Rc::into_inner(built)
+        .expect("the collector was dropped with the extensions")
+        .into_inner();
+    shaders.sort_by_cached_key(|(mod_path, _)| mod_path.to_string());
+
+    Ok(BuiltShaders { out_dir, shaders })
+}
+
+impl BuiltShaders {
+    /// The temp dir the shaders were built in
+    pub fn out_dir(&self) -> &Path {
+        self.out_dir.path()
+    }
+
+    /// The module path and WGSL of every built shader, sorted by module path
+    pub fn shaders(&self) -> &[(ModulePath, String)] {
+        &self.shaders
+    }
+
+    /// The WGSL of the shader at `mod_path`, e.g. `"test_mod::test_mod_file"`
+    pub fn get(&self, mod_path: &str) -> Option<&str> {
+        self.shaders.iter()
+            .find(|(path, _)| path.components.join("::") == mod_path)
+            .map(|(_, wgsl)| wgsl.as_str())
+    }
+
+    /// Compare every shader against its snapshot in `snapshot_dir`, at the module path as a file path,
+    /// e.g. `test_mod/test_mod_file.wgsl`
+    ///
+    /// # Panics
+    /// If a snapshot is missing or differs, unless [`UPDATE_SNAPSHOTS_ENV`] is set
+    #[track_caller]
+    pub fn assert_snapshots(&self, snapshot_dir: impl AsRef<Path>) {
+        let update = std::env::var_os(UPDATE_SNAPSHOTS_ENV).is_some_and(|update| update != "0");
+        let mut failures = String::new();
+
+        for (mod_path, wgsl) in &self.shaders {
+            let snapshot_path = snapshot_path(snapshot_dir.as_ref(), mod_path);
+            let new_path = snapshot_path.with_extension("wgsl.new");
+
+            let failure = match fs::read_to_string(&snapshot_path) {
+                Ok(snapshot) if snapshot == *wgsl => None,
+                _ if update => {
+                    write_snapshot(&snapshot_path, wgsl);
+                    let _ = fs::remove_file(&new_path);
+                    None
+                }
+                Ok(snapshot) => Some(format!("`{mod_path}` changed, {}", first_difference(&snapshot, wgsl))),
+                Err(_) => Some(format!("`{mod_path}` has no snapshot")),
+            };
+
+            if let Some(failure) = failure {
+                write_snapshot(&new_path, wgsl);
+                writeln!(failures, "{failure}, see {}", new_path.display()).expect("writing to a String can't fail");
+            }
+        }
+
+        assert!(
+            failures.is_empty(),
+            "shader snapshots don't match, run with `{UPDATE_SNAPSHOTS_ENV}=1` to accept the changes:\n{failures}"
+        );
+    }
+}
+
+/// The snapshot of `mod_path` in `snapshot_dir`
+fn snapshot_path(snapshot_dir: &Path, mod_path: &ModulePath) -> PathBuf {
+    let mut path = snapshot_dir.to_owned();
+    path.extend(&mod_path.components);
+    path.set_extension("wgsl");
+    path
+}
+
+fn write_snapshot(path: &Path, wgsl: &str) {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).expect("failed to create the snapshot dir");
+    }
+    fs::write(path, wgsl).expect("failed to write the snapshot");
+}
+
+/// A description of the first line that differs between `old` and `new`
+fn first_difference(old: &str, new: &str) -> String {
+    let mut old_lines = old.lines();
+    let mut new_lines = new.lines();
+
+    for line in 1.. {
+        match (old_lines.next(), new_lines.next()) {
+            (Some(old), Some(new)) if old == new => (),
+            (old, new) => return format!(
+                "first difference at line {line}:\n  - {}\n  + {}",
+                old.unwrap_or("<end of file>"),
+                new.unwrap_or("<end of file>"),
+            ),
+        }
+    }
+    unreachable!("the lines of a str are finite")
+}
+
+/// Records the built shaders of a [`build_shaders`] run
+struct BuiltShaderCollector {
+    built: Rc<RefCell<Vec<(ModulePath, String)>>>,
+}
+
+impl WeslBuildExtension<BuildResolver> for BuiltShaderCollector {
+    fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+        "BuiltShaderCollector".into()
+    }
+
+    fn init_root(
+        &mut self,
+        _shader_path: &str,
+        _res: &mut wesl::Wesl<BuildResolver>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    fn enter_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+    fn exit_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+
+    fn post_build(
+        &mut self,
+        mod_path: &ModulePath,
+        wgsl_built_path: &str,
+        _source_map: &Option<BasicSourceMap>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.built.borrow_mut().push((mod_path.clone(), crate::artifacts::read_to_string(wgsl_built_path)?));
+        Ok(())
+    }
+}
@@ -73,25 +73,3119 @@ fn test_bindings_ext() {
     }
 }
 
+#[cfg(feature = "wgpu_bindings_ext")]
+#[test]
+fn test_bindings_ext_entry_points() {
+    let tmp = tempfile::tempdir().unwrap();
+
+    build_shader_dir(
+        "./test/src/shaders",
+        wesl::CompileOptions::default(),
+        &mut [Box::new(
+            WgpuBindingsExtension::new(tmp.path()).unwrap().with_entry_points(true),
+        )],
+    )
+    .unwrap();
+
+    let root_mod = read_to_string(tmp.path().join("mod.rs")).unwrap();
+    assert!(root_mod.contains("pub struct EntryPoint {"), "EntryPoint was not defined in the root module:\n{root_mod}");
+
+    let test2 = read_to_string(tmp.path().join("test2.rs")).unwrap();
+    assert!(
+        test2.contains(r#"super::EntryPoint { name: "main", stage: wgpu::ShaderStages::COMPUTE, workgroup_size: [5, 6, 1] },"#),
+        "test2 is missing its entry point:\n{test2}"
+    );
+}
+
+#[cfg(feature = "wgpu_bindings_ext")]
+#[test]
+fn test_bindings_ext_shared_layouts() {
+    let tmp = tempfile::tempdir().unwrap();
+    let shader_root = tmp.path().join("shaders");
+    let bindings_root = tmp.path().join("bindings");
+    std::fs::create_dir_all(&shader_root).unwrap();
+    std::fs::create_dir_all(&bindings_root).unwrap();
+
+    let shader = "
+        struct Camera { view_proj: mat4x4<f32> }
+        @group(0) @binding(0) var<uniform> camera: Camera;
+
+        @vertex
+        fn vs_main(@builtin(vertex_index) idx: u32) -> @builtin(position) vec4<f32> {
+            return camera.view_proj * vec4(f32(idx), 0.0, 0.0, 1.0);
+        }
+    ";
+    std::fs::write(shader_root.join("shared_layout_a.wgsl"), shader).unwrap();
+    std::fs::write(shader_root.join("shared_layout_b.wgsl"), shader).unwrap();
+
+    build_shader_dir(
+        shader_root.to_str().unwrap(),
+        wesl::CompileOptions::default(),
+        &mut [Box::new(
+            WgpuBindingsExtension::new(&bindings_root)
+                .unwrap()
+                .with_shared_layouts(true),
+        )],
+    )
+    .unwrap();
+
+    let common_layouts = read_to_string(bindings_root.join("common_layouts.rs")).unwrap();
+    assert!(common_layouts.contains("pub struct SharedLayout0;"), "the shared layout was not generated:\n{common_layouts}");
+    assert!(
+        common_layouts.contains("wgpu::BindGroupLayoutEntry { binding: 0, visibility: wgpu::ShaderStages::VERTEX, ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform"),
+        "the shared layout has the wrong entries:\n{common_layouts}"
+    );
+
+    for binding in ["shared_layout_a.rs", "shared_layout_b.rs"] {
+        let binding = read_to_string(bindings_root.join(binding)).unwrap();
+        assert!(
+            binding.contains("pub use super::super::common_layouts::SharedLayout0 as Group0;"),
+            "the shared layout is not re-exported:\n{binding}"
+        );
+    }
+}
+
+#[cfg(feature = "wgpu_bindings_ext")]
+#[test]
+fn test_bindings_ext_header() {
+    let tmp = tempfile::tempdir().unwrap();
+    let bindings_root = tmp.path().join("bindings");
+
+    build_shader_dir_with_config(
+        &WeslBuildConfig::new("./test/src/shaders").out_dir(tmp.path().join("out")).cargo_instructions(false),
+        &mut [Box::new(
+            WgpuBindingsExtension::new(&bindings_root)
+                .unwrap()
+                .with_entry_points(true)
+                .with_header("Copyright The Authors\n// SPDX-License-Identifier: MIT")
+                .format_output(true),
+        )],
+    )
+    .unwrap();
+
+    for binding in ["mod.rs", "test2.rs", "test_mod/mod.rs", "test_mod/test_mod_file.rs"] {
+        let binding = read_to_string(bindings_root.join(binding)).unwrap();
+        let body = binding.strip_prefix(
+            "// Copyright The Authors\n// SPDX-License-Identifier: MIT\n// @generated by wesl_build, do not edit\n"
+        ).unwrap_or_else(|| panic!("the header is missing:\n{binding}"));
+        assert_eq!(body, prettyplease::unparse(&syn::parse_file(body).unwrap()), "the binding is not formatted");
+    }
+}
+
+#[cfg(feature = "wgpu_bindings_ext")]
+#[test]
+fn test_bindings_ext_mod_names() {
+    use crate::extension::Visibility;
+    use crate::extension::wgpu_bindings::ModNameCase;
+
+    let tmp = tempfile::tempdir().unwrap();
+    let (shader_root, bindings_root) = (tmp.path().join("shaders"), tmp.path().join("bindings"));
+    std::fs::create_dir_all(shader_root.join("PostFx")).unwrap();
+    std::fs::write(shader_root.join("PostFx/BloomPass.wgsl"), "@compute @workgroup_size(1) fn main() {}").unwrap();
+
+    build_shader_dir_with_config(
+        &WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(tmp.path().join("out")).cargo_instructions(false),
+        &mut [Box::new(
+            WgpuBindingsExtension::new(&bindings_root)
+                .unwrap()
+                .with_mod_visibility(Visibility::Public)
+                .with_mod_name_case(ModNameCase::SnakeCase)
+                .with_mod_suffix("_shaders"),
+        )],
+    )
+    .unwrap();
+
+    let root_mod = read_to_string(bindings_root.join("mod.rs")).unwrap();
+    assert!(root_mod.contains("\npub mod post_fx_shaders;"), "{root_mod}");
+    let post_fx_mod = read_to_string(bindings_root.join("post_fx_shaders/mod.rs")).unwrap();
+    assert!(post_fx_mod.contains("\npub mod bloom_pass_shaders;"), "{post_fx_mod}");
+    assert!(bindings_root.join("post_fx_shaders/bloom_pass_shaders.rs").is_file());
+}
+
+#[cfg(feature = "wgpu_bindings_ext")]
+#[test]
+fn test_failed_build_keeps_outputs() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (shader_root, bindings_root, out_dir) = (tmp.path().join("shaders"), tmp.path().join("bindings"), tmp.path().join("out"));
+    std::fs::create_dir_all(&shader_root).unwrap();
+    std::fs::write(shader_root.join("kept.wgsl"), "@compute @workgroup_size(1) fn main() {}").unwrap();
+    let config = WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(&out_dir).cargo_instructions(false);
+    let bindings = || WgpuBindingsExtension::new(&bindings_root).unwrap();
+
+    build_shader_dir_with_config(&config, &mut [Box::new(bindings())]).unwrap();
+    let root_mod = read_to_string(bindings_root.join("mod.rs")).unwrap();
+    let manifest = read_to_string(out_dir.join(BUILD_MANIFEST_NAME)).unwrap();
+    let staging = tmp.path().join(".bindings.wesl_build_staging");
+    assert!(!staging.exists());
+
+    // the bindings are only moved into place once every shader built
+    std::fs::write(shader_root.join("added.wgsl"), "@compute @workgroup_size(1) fn main() {}").unwrap();
+    std::fs::write(shader_root.join("broken.wgsl"), "fn a() -> f32 { return missing; }").unwrap();
+    build_shader_dir_with_config(&config, &mut [Box::new(bindings())]).unwrap_err();
+    assert_eq!(read_to_string(bindings_root.join("mod.rs")).unwrap(), root_mod);
+    assert!(!bindings_root.join("added.rs").exists());
+
+    // and the manifest once every extension succeeded
+    build_shader_dir_with_config(&config.clone().continue_on_error(true), &mut [Box::new(bindings())]).unwrap_err();
+    assert_eq!(read_to_string(out_dir.join(BUILD_MANIFEST_NAME)).unwrap(), manifest);
+
+    std::fs::remove_file(shader_root.join("broken.wgsl")).unwrap();
+    build_shader_dir_with_config(&config, &mut [Box::new(bindings())]).unwrap();
+    assert!(bindings_root.join("added.rs").is_file());
+    assert!(!staging.exists());
+    assert_ne!(read_to_string(out_dir.join(BUILD_MANIFEST_NAME)).unwrap(), manifest);
+
+    let temp_files = [&out_dir, &out_dir.join(OUTPUT_DIR_NAME), &bindings_root]
+        .into_iter()
+        .flat_map(|dir| std::fs::read_dir(dir).unwrap())
+        .filter(|entry| entry.as_ref().unwrap().file_name().to_string_lossy().ends_with(".tmp"))
+        .count();
+    assert_eq!(temp_files, 0);
+}
+
+#[cfg(feature = "wgpu_bindings_ext")]
+#[test]
+fn test_unchanged_outputs_are_not_rewritten() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (shader_root, bindings_root, out_dir) = (tmp.path().join("shaders"), tmp.path().join("bindings"), tmp.path().join("out"));
+    std::fs::create_dir_all(&shader_root).unwrap();
+    std::fs::write(shader_root.join("same.wgsl"), "@compute @workgroup_size(1) fn main() {}").unwrap();
+    std::fs::write(shader_root.join("edited.wgsl"), "@compute @workgroup_size(1) fn main() {}").unwrap();
+    let config = WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(&out_dir).cargo_instructions(false);
+    let bindings = || WgpuBindingsExtension::new(&bindings_root).unwrap();
+    let modified = |path: PathBuf| std::fs::metadata(path).unwrap().modified().unwrap();
+
+    let plan = build_shader_dir_with_config(&config, &mut [Box::new(bindings())]).unwrap();
+    assert_eq!(plan.writes.up_to_date, 0);
+    let written = plan.writes.written;
+    let (binding_mtime, wgsl_mtime) = (modified(bindings_root.join("same.rs")), modified(output_path(&plan, "same")));
+
+    let plan = build_shader_dir_with_config(&config, &mut [Box::new(bindings())]).unwrap();
+    assert_eq!(plan.writes, artifacts::WriteStats { written: 0, up_to_date: written });
+    assert_eq!(modified(bindings_root.join("same.rs")), binding_mtime);
+    assert_eq!(modified(output_path(&plan, "same")), wgsl_mtime);
+
+    std::fs::write(shader_root.join("edited.wgsl"), "@compute @workgroup_size(2) fn main() {}").unwrap();
+    let plan = build_shader_dir_with_config(&config, &mut [Box::new(bindings())]).unwrap();
+    assert!(plan.writes.written > 0 && plan.writes.up_to_date > 0, "{:?}", plan.writes);
+    assert_eq!(plan.writes.written + plan.writes.up_to_date, written);
+    assert_eq!(modified(bindings_root.join("same.rs")), binding_mtime);
+    assert_eq!(modified(output_path(&plan, "same")), wgsl_mtime);
+}
+
+#[test]
+fn test_concurrent_build_lock() {
+    let tmp = tempfile::tempdir().unwrap();
+    let out_dir = tmp.path().join("out");
+    std::fs::write(tmp.path().join("locked.wgsl"), "fn f() {}").unwrap();
+    let config = WeslBuildConfig::new(tmp.path().to_str().unwrap())
+        .out_dir(&out_dir)
+        .cargo_instructions(false)
+        .lock_timeout(std::time::Duration::from_millis(200));
+
+    // another build is running
+    std::fs::create_dir_all(&out_dir).unwrap();
+    let other_build = std::fs::File::create(out_dir.join(DIR_LOCK_NAME)).unwrap();
+    other_build.lock().unwrap();
+
+    let error = build_shader_dir_with_config(&config, &mut []).unwrap_err();
+    assert!(matches!(&error, WeslBuildError::LockTimeout { path, .. } if *path == out_dir.join(DIR_LOCK_NAME)), "{error}");
+    assert!(!out_dir.join(OUTPUT_DIR_NAME).exists(), "nothing must be written while the other build runs");
+    // checks write nothing, so they don't wait
+    build_shader_dir_with_config(&config.clone().check(true), &mut []).unwrap();
+
+    drop(other_build);
+    build_shader_dir_with_config(&config, &mut []).unwrap();
+}
+
+#[cfg(feature = "wgpu_bindings_ext")]
+#[test]
+fn test_bindings_ext_keyword_mod_names() {
+    use crate::extension::wgpu_bindings::{ModNameCase, WgpuBindingsError};
+
+    let tmp = tempfile::tempdir().unwrap();
+    let (shader_root, bindings_root) = (tmp.path().join("shaders"), tmp.path().join("bindings"));
+    std::fs::create_dir_all(shader_root.join("type")).unwrap();
+    std::fs::write(shader_root.join("type/match.wgsl"), "@compute @workgroup_size(1) fn main() {}").unwrap();
+    let config = WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(tmp.path().join("out")).cargo_instructions(false);
+    let bindings = || WgpuBindingsExtension::new(&bindings_root).unwrap();
+
+    build_shader_dir_with_config(&config, &mut [Box::new(bindings())]).unwrap();
+    assert!(read_to_string(bindings_root.join("mod.rs")).unwrap().contains("pub(crate) mod r#type;"));
+    assert!(read_to_string(bindings_root.join("type/mod.rs")).unwrap().contains("pub(crate) mod r#match;"));
+    assert!(bindings_root.join("type/match.rs").is_file());
+
+    build_shader_dir_with_config(&config, &mut [Box::new(bindings().with_keyword_suffix("_shader"))]).unwrap();
+    assert!(read_to_string(bindings_root.join("type_shader/mod.rs")).unwrap().contains("pub(crate) mod match_shader;"));
+
+    std::fs::write(shader_root.join("Type.wgsl"), "@compute @workgroup_size(1) fn main() {}").unwrap();
+    let error = build_shader_dir_with_config(&config, &mut [Box::new(bindings().with_mod_name_case(ModNameCase::SnakeCase))])
+        .unwrap_err();
+    let WeslBuildError::ExtensionErr { error, .. } = error else { panic!("{error:?}") };
+    assert!(
+        matches!(error.downcast_ref(), Some(WgpuBindingsError::ModNameCollision { mod_name, .. }) if mod_name == "r#type"),
+        "{error}"
+    );
+}
+
+#[cfg(feature = "wgpu_bindings_ext")]
+#[test]
+fn test_bindings_ext_vertex_layouts() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (shader_root, bindings_root) = (tmp.path().join("shaders"), tmp.path().join("bindings"));
+    std::fs::create_dir_all(&shader_root).unwrap();
+    std::fs::write(shader_root.join("sprite.wgsl"), "
+        struct Vertex { @location(0) position: vec3<f32>, @location(1) uv: vec2<f32> }
+        struct Sprite { @location(2) offset: vec4<f32>, @location(3) layer: u32 }
+        @vertex
+        fn vs_main(@builtin(vertex_index) idx: u32, vertex: Vertex, sprite: Sprite) -> @builtin(position) vec4<f32> {
+            return vec4(vertex.position, 1.0) + sprite.offset;
+        }
+    ").unwrap();
+    let config = WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(tmp.path().join("out")).cargo_instructions(false);
+    let bindings = || WgpuBindingsExtension::new(&bindings_root)
+        .unwrap()
+        .with_vertex_layouts(true);
+
+    build_shader_dir_with_config(&config, &mut [Box::new(bindings())]).unwrap();
+    let sprite = read_to_string(bindings_root.join("sprite.rs")).unwrap();
+    assert!(sprite.contains("pub const VS_MAIN_VERTEX_BUFFERS: [wgpu::VertexBufferLayout<'static>; 2] = ["), "{sprite}");
+    assert!(sprite.contains("array_stride: 20,\n        step_mode: wgpu::VertexStepMode::Vertex,"), "{sprite}");
+    assert!(sprite.contains("wgpu::VertexAttribute { format: wgpu::VertexFormat::Float32x2, offset: 12, shader_location: 1 },"), "{sprite}");
+    assert!(sprite.contains("wgpu::VertexAttribute { format: wgpu::VertexFormat::Uint32, offset: 16, shader_location: 3 },"), "{sprite}");
+    assert!(!sprite.contains("VertexStepMode::Instance"), "no buffer is named after instances:\n{sprite}");
+
+    build_shader_dir_with_config(&config, &mut [Box::new(bindings().with_instance_buffer("Sprite"))]).unwrap();
+    let sprite = read_to_string(bindings_root.join("sprite.rs")).unwrap();
+    assert!(sprite.contains("array_stride: 20,\n        step_mode: wgpu::VertexStepMode::Instance,"), "{sprite}");
+}
+
+#[cfg(feature = "wgpu_bindings_ext")]
+#[test]
+fn test_bindings_ext_pipeline_helpers() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (shader_root, bindings_root) = (tmp.path().join("shaders"), tmp.path().join("bindings"));
+    std::fs::create_dir_all(&shader_root).unwrap();
+    std::fs::write(shader_root.join("quad.wgsl"), "
+        struct Vertex { @location(0) position: vec2<f32> }
+        @vertex
+        fn vs_main(vertex: Vertex) -> @builtin(position) vec4<f32> { return vec4(vertex.position, 0.0, 1.0); }
+        @fragment
+        fn fs_main() -> @location(0) vec4<f32> { return vec4(1.0); }
+    ").unwrap();
+    std::fs::write(shader_root.join("clear.wgsl"), "@compute @workgroup_size(64) fn clear() {}").unwrap();
+
+    build_shader_dir_with_config(
+        &WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(tmp.path().join("out")).cargo_instructions(false),
+        &mut [Box::new(
+            WgpuBindingsExtension::new(&bindings_root)
+                .unwrap()
+                .generate_pipeline_helpers(true),
+        )],
+    )
+    .unwrap();
+
+    let root_mod = read_to_string(bindings_root.join("mod.rs")).unwrap();
+    assert!(root_mod.contains("pub struct RenderPipelineOptions {"), "{root_mod}");
+
+    let quad = read_to_string(bindings_root.join("quad.rs")).unwrap();
+    assert!(quad.contains("options: &super::RenderPipelineOptions,"), "{quad}");
+    assert!(quad.contains("entry_point: Some(\"vs_main\"),"), "{quad}");
+    assert!(quad.contains("buffers: &VS_MAIN_VERTEX_BUFFERS,"), "the vertex layouts must be generated with the helpers:\n{quad}");
+    assert!(quad.contains("entry_point: Some(\"fs_main\"),"), "{quad}");
+    assert!(!quad.contains("create_compute_pipeline"), "{quad}");
+
+    let clear = read_to_string(bindings_root.join("clear.rs")).unwrap();
+    assert!(clear.contains("compute::create_clear_pipeline(device)"), "{clear}");
+    assert!(!clear.contains("create_render_pipeline"), "{clear}");
+}
+
+#[cfg(feature = "wgpu_bindings_ext")]
+#[test]
+fn test_bindings_ext_pipeline_state() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (shader_root, bindings_root) = (tmp.path().join("shaders"), tmp.path().join("bindings"));
+    std::fs::create_dir_all(&shader_root).unwrap();
+    std::fs::write(shader_root.join("quad.wgsl"), "
+        struct Vertex { @location(0) position: vec2<f32> }
+        @vertex
+        fn vs_main(vertex: Vertex) -> @builtin(position) vec4<f32> { return vec4(vertex.position, 0.0, 1.0); }
+        @fragment
+        fn fs_main() -> @location(0) vec4<f32> { return vec4(1.0); }
+    ").unwrap();
+    std::fs::write(shader_root.join("quad.pipeline.toml"), "
+        cull = \"back\" # no backs
+        blend = \"alpha\"
+
+        [depth]
+        format = \"depth32float\"
+        write = false
+    ").unwrap();
+    let config = WeslBuildConfig::new(shader_root.to_str().unwrap())
+        .out_dir(tmp.path().join("out"))
+        .cargo_instructions(false)
+        .asset_extensions(["toml"]);
+    let bindings = || WgpuBindingsExtension::new(&bindings_root)
+        .unwrap()
+        .with_pipeline_state(true);
+
+    build_shader_dir_with_config(&config, &mut [Box::new(bindings())]).unwrap();
+    let quad = read_to_string(bindings_root.join("quad.rs")).unwrap();
+    assert!(quad.contains("pub fn pipeline_descriptor<'a>("), "{quad}");
+    assert!(quad.contains("cull_mode: Some(wgpu::Face::Back),"), "{quad}");
+    assert!(quad.contains("topology: wgpu::PrimitiveTopology::TriangleList,"), "{quad}");
+    assert!(quad.contains("pub const BLEND: Option<wgpu::BlendState> = Some(wgpu::BlendState::ALPHA_BLENDING);"), "{quad}");
+    assert!(quad.contains("depth_write_enabled: false,"), "{quad}");
+    assert!(quad.contains("buffers: &VS_MAIN_VERTEX_BUFFERS,"), "{quad}");
+    assert!(quad.contains("entry_point: Some(\"fs_main\"),"), "{quad}");
+
+    // typos and missing entry points fail the build
+    std::fs::write(shader_root.join("quad.pipeline.toml"), "topology = \"triangles\"").unwrap();
+    let result = build_shader_dir_with_config(&config, &mut [Box::new(bindings())]);
+    assert!(
+        matches!(&result, Err(WeslBuildError::ExtensionErr { error, .. }) if error.to_string().contains("unknown value `triangles`")),
+        "{result:?}",
+    );
+    std::fs::write(shader_root.join("quad.pipeline.toml"), "fragment = \"fs_missing\"").unwrap();
+    let result = build_shader_dir_with_config(&config, &mut [Box::new(bindings())]);
+    assert!(
+        matches!(&result, Err(WeslBuildError::ExtensionErr { error, .. }) if error.to_string().contains("no fragment entry point `fs_missing`")),
+        "{result:?}",
+    );
+}
+
+#[cfg(feature = "hot_reload")]
+#[test]
+fn test_bindings_ext_hot_reload() {
+    let tmp = tempfile::tempdir().unwrap();
+    let bindings_root = tmp.path().join("bindings");
+
+    build_shader_dir_with_config(
+        &WeslBuildConfig::new("./test/src/shaders").out_dir(tmp.path().join("out")).cargo_instructions(false),
+        &mut [Box::new(
+            WgpuBindingsExtension::new(&bindings_root)
+                .unwrap()
+                .with_hot_reload(true),
+        )],
+    )
+    .unwrap();
+
+    let binding = read_to_string(bindings_root.join("test_mod/test_mod_file.rs")).unwrap();
+    let shader_file = std::fs::canonicalize("test/src/shaders/test_mod/test_mod_file.wgsl").unwrap();
+    assert!(binding.contains(&format!("pub const SHADER_FILE: &str = {:?};", shader_file.display().to_string())), "{binding}");
+    assert!(binding.contains(r#"vec!["test_mod".to_owned(), "test_mod_file".to_owned()]"#), "{binding}");
+    assert_eq!(binding.matches("pub fn load_latest() -> Result<String, wesl::Error> {").count(), 2, "{binding}");
+}
+
+#[cfg(feature = "wgpu_bindings_ext")]
+#[test]
+fn test_bindings_ext_shader_module() {
+    let tmp = tempfile::tempdir().unwrap();
+    let bindings_root = tmp.path().join("bindings");
+
+    build_shader_dir_with_config(
+        &WeslBuildConfig::new("./test/src/shaders").out_dir(tmp.path().join("out")).cargo_instructions(false),
+        &mut [Box::new(
+            WgpuBindingsExtension::new(&bindings_root)
+                .unwrap()
+                .with_unchecked_shader_module(true),
+        )],
+    )
+    .unwrap();
+
+    let binding = read_to_string(bindings_root.join("test_mod/test_mod_file.rs")).unwrap();
+    assert_eq!(binding.matches(r#"label: Some("test_mod::test_mod_file"),"#).count(), 2, "{binding}");
+    assert!(binding.contains("pub unsafe fn create_shader_module_unchecked(device: &wgpu::Device) -> wgpu::ShaderModule {"), "{binding}");
+    assert!(binding.contains("device.create_shader_module_passthrough(wgpu::ShaderModuleDescriptorPassthrough {"), "{binding}");
+}
+
+#[cfg(feature = "wgpu_bindings_ext")]
+#[test]
+fn test_bindings_ext_stubs() {
+    let tmp = tempfile::tempdir().unwrap();
+    let shader_root = tmp.path().join("shaders");
+    std::fs::create_dir_all(shader_root.join("effects")).unwrap();
+    std::fs::write(shader_root.join("effects/blur.wgsl"), "fn blur() -> f32 { return missing; }").unwrap();
+    std::fs::write(shader_root.join("valid.wgsl"), "fn valid() -> f32 { return 1.0; }").unwrap();
+    let bindings_root = tmp.path().join("bindings");
+    let bindings = || WgpuBindingsExtension::new(&bindings_root)
+        .unwrap()
+        .generate_stubs(true);
+
+    // before the first build
+    let written = bindings().write_stubs(&crate::ModuleGraph::from_dir(&shader_root).unwrap()).unwrap();
+    assert_eq!(written.len(), 4, "{written:?}");
+    assert!(read_to_string(bindings_root.join("mod.rs")).unwrap().contains("pub mod effects;"));
+    assert!(read_to_string(bindings_root.join("effects/mod.rs")).unwrap().contains("pub mod blur;"));
+    assert!(read_to_string(bindings_root.join("valid.rs")).unwrap().contains(r#"pub const SOURCE: &str = "";"#));
+    // the stubs don't replace bindings
+    assert!(bindings().write_stubs(&crate::ModuleGraph::from_dir(&shader_root).unwrap()).unwrap().is_empty());
+
+    let result = build_shader_dir_with_config(
+        &WeslBuildConfig::new(shader_root.to_str().unwrap())
+            .out_dir(tmp.path().join("out"))
+            .cargo_instructions(false)
+            .continue_on_error(true),
+        &mut [Box::new(bindings())],
+    );
+    assert!(result.is_err());
+
+    let valid = read_to_string(bindings_root.join("valid.rs")).unwrap();
+    assert!(valid.contains("pub fn create_shader_module("), "{valid}");
+    let blur = read_to_string(bindings_root.join("effects/blur.rs")).unwrap();
+    assert!(blur.contains("`effects::blur` has not been built yet"), "{blur}");
+    assert!(read_to_string(bindings_root.join("effects/mod.rs")).unwrap().contains("pub mod blur;"));
+}
+
+#[cfg(feature = "wgpu_bindings_ext")]
+#[test]
+fn test_bindings_ext_workgroup_sizes() {
+    let tmp = tempfile::tempdir().unwrap();
+    let shader_root = tmp.path().join("shaders");
+    std::fs::create_dir_all(&shader_root).unwrap();
+    std::fs::write(shader_root.join("single.wgsl"), "@compute @workgroup_size(8, 4) fn main() {}").unwrap();
+    std::fs::write(shader_root.join("multi.wgsl"), "
+        @compute @workgroup_size(64) fn clear() {}
+        @compute @workgroup_size(16, 16) fn blur() {}
+    ").unwrap();
+    let bindings_root = tmp.path().join("bindings");
+
+    build_shader_dir_with_config(
+        &WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(tmp.path().join("out")).cargo_instructions(false),
+        &mut [Box::new(
+            WgpuBindingsExtension::new(&bindings_root)
+                .unwrap()
+                .with_workgroup_sizes(true),
+        )],
+    )
+    .unwrap();
+
+    let single = read_to_string(bindings_root.join("single.rs")).unwrap();
+    assert!(single.contains("pub const WORKGROUP_SIZE: [u32; 3] = [8, 4, 1];"), "{single}");
+    assert!(single.contains("pub const fn dispatch_count(dims: [u32; 3]) -> [u32; 3] {"), "{single}");
+
+    let multi = read_to_string(bindings_root.join("multi.rs")).unwrap();
+    assert!(multi.contains("pub const CLEAR_WORKGROUP_SIZE: [u32; 3] = [64, 1, 1];"), "{multi}");
+    assert!(multi.contains("pub const BLUR_WORKGROUP_SIZE: [u32; 3] = [16, 16, 1];"), "{multi}");
+    assert!(multi.contains("pub const fn blur_dispatch_count(dims: [u32; 3]) -> [u32; 3] {"), "{multi}");
+    assert!(!multi.contains("pub const WORKGROUP_SIZE"), "{multi}");
+}
+
+#[cfg(feature = "wgpu_bindings_ext")]
+#[test]
+fn test_bindings_ext_binding_consts() {
+    let tmp = tempfile::tempdir().unwrap();
+    let shader_root = tmp.path().join("shaders");
+    std::fs::create_dir_all(&shader_root).unwrap();
+    std::fs::write(shader_root.join("camera.wesl"), "
+        struct Camera { view_proj: mat4x4f }
+        @group(0) @binding(0) var<uniform> camera: Camera;
+        @group(1) @binding(2) var albedoTexture: texture_2d<f32>;
+    ").unwrap();
+    std::fs::write(shader_root.join("main.wesl"), "
+        import package::camera::{camera, albedoTexture};
+        @fragment fn main() -> @location(0) vec4f {
+            return camera.view_proj[0] + textureLoad(albedoTexture, vec2u(0), 0);
+        }
+    ").unwrap();
+    let bindings_root = tmp.path().join("bindings");
+
+    build_shader_dir_with_config(
+        &WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(tmp.path().join("out")).cargo_instructions(false),
+        &mut [Box::new(
+            WgpuBindingsExtension::new(&bindings_root)
+                .unwrap()
+                .with_binding_consts(true),
+        )],
+    )
+    .unwrap();
+
+    let binding = read_to_string(bindings_root.join("main.rs")).unwrap();
+    assert!(binding.contains("pub mod bindings {"), "{binding}");
+    assert!(binding.contains("/// `camera::camera`\n    pub const CAMERA: (u32, u32) = (0, 0);"), "{binding}");
+    assert!(binding.contains("pub const ALBEDO_TEXTURE: (u32, u32) = (1, 2);"), "{binding}");
+}
+
+#[cfg(feature = "wgpu_bindings_ext")]
+#[test]
+fn test_bindings_ext_test_harness() {
+    let tmp = tempfile::tempdir().unwrap();
+    let shader_root = tmp.path().join("shaders");
+    std::fs::create_dir_all(&shader_root).unwrap();
+    std::fs::write(shader_root.join("light.wesl"), "
+        struct Light { color: vec3f, intensity: f32 }
+        @group(0) @binding(1) var<uniform> light: Light;
+        @fragment fn main() -> @location(0) vec4f { return vec4f(light.color * light.intensity, 1.0); }
+    ").unwrap();
+    let bindings_root = tmp.path().join("bindings");
+
+    build_shader_dir_with_config(
+        &WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(tmp.path().join("out")).cargo_instructions(false),
+        &mut [Box::new(
+            WgpuBindingsExtension::new(&bindings_root)
+                .unwrap()
+                .with_test_harness(true),
+        )],
+    )
+    .unwrap();
+
+    let binding = read_to_string(bindings_root.join("light.rs")).unwrap();
+    assert!(binding.contains("#[cfg(test)]"), "{binding}");
+    assert!(binding.contains("mod wesl_build_tests {"), "{binding}");
+    assert!(binding.contains("fn shader_is_valid()"), "{binding}");
+    assert!(binding.contains(r#"assert_eq!(binding("light"), Some((0, 1)),"#), "{binding}");
+    assert!(binding.contains(r#"assert_eq!(stage("main"), Some(naga::ShaderStage::Fragment),"#), "{binding}");
+    assert!(binding.contains(r#"assert_eq!(layout("Light"), Some((16, vec![0, 12])),"#), "{binding}");
+    syn::parse_file(&binding).expect("the bindings are valid rust");
+}
+
+#[cfg(feature = "wgpu_bindings_ext")]
+#[test]
+fn test_bindings_ext_layout_assertions() {
+    let tmp = tempfile::tempdir().unwrap();
+    let shader_root = tmp.path().join("shaders");
+    std::fs::create_dir_all(&shader_root).unwrap();
+    std::fs::write(shader_root.join("lights.wgsl"), "
+        struct Light { position: vec3f, intensity: f32, color: vec3f }
+        struct Lights { count: u32, lights: array<Light, 4> }
+        @group(0) @binding(0) var<uniform> lights: Lights;
+        @fragment fn main() -> @location(0) vec4f {
+            return vec4f(lights.lights[0].color * f32(lights.count), 1.0);
+        }
+    ").unwrap();
+    let bindings_root = tmp.path().join("bindings");
+
+    build_shader_dir_with_config(
+        &WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(tmp.path().join("out")).cargo_instructions(false),
+        &mut [Box::new(
+            WgpuBindingsExtension::new(&bindings_root)
+                .unwrap()
+                .with_layout_assertions(true),
+        )],
+    )
+    .unwrap();
+
+    let binding = read_to_string(bindings_root.join("lights.rs")).unwrap();
+    assert!(binding.contains("assert!(std::mem::size_of::<Light>() == 32,"), "{binding}");
+    assert!(binding.contains("assert!(std::mem::offset_of!(Light, color) == 16,"), "{binding}");
+    assert!(binding.contains("assert!(std::mem::size_of::<Lights>() == 144,"), "{binding}");
+    assert!(binding.contains("assert!(std::mem::offset_of!(Lights, lights) == 16,"), "{binding}");
+}
+
+#[cfg(feature = "wgpu_bindings_ext")]
+#[test]
+fn test_bindings_ext_pipeline_constants() {
+    let tmp = tempfile::tempdir().unwrap();
+    let shader_root = tmp.path().join("shaders");
+    std::fs::create_dir_all(&shader_root).unwrap();
+    std::fs::write(shader_root.join("particles.wesl"), "
+        struct Push { time: f32, count: u32 }
+        var<immediate> push: Push;
+        @id(0) override blockSize: u32 = 64;
+        override gravity: f32 = 9.8;
+        override wrap: bool = true;
+        @compute @workgroup_size(1) fn main() {
+            let x = push.time * gravity + f32(push.count + blockSize) + select(0.0, 1.0, wrap);
+        }
+    ").unwrap();
+    let bindings_root = tmp.path().join("bindings");
+
+    build_shader_dir_with_config(
+        &WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(tmp.path().join("out")).cargo_instructions(false),
+        &mut [Box::new(
+            WgpuBindingsExtension::new(&bindings_root)
+                .unwrap()
+                .with_pipeline_constants(true),
+        )],
+    )
+    .unwrap();
+
+    let binding = read_to_string(bindings_root.join("particles.rs")).unwrap();
+    assert!(binding.contains("pub mod pipeline_constants {"), "{binding}");
+    assert!(binding.contains("pub const IMMEDIATE_SIZE: u32 = 8;"), "{binding}");
+    assert!(binding.contains("pub type Immediates = super::Push;"), "{binding}");
+    assert!(binding.contains("pub block_size: Option<u32>,"), "{binding}");
+    assert!(binding.contains("pub fn set_gravity(&mut self, value: f32) -> &mut Self {"), "{binding}");
+    assert!(binding.contains(r#"constants.push(("0", f64::from(value)));"#), "{binding}");
+    assert!(binding.contains(r#"constants.push(("wrap", f64::from(u8::from(value))));"#), "{binding}");
+}
+
+#[cfg(feature = "wgpu_bindings_ext")]
+#[test]
+fn test_bindings_ext_verify() {
+    use crate::extension::wgpu_bindings::WgpuBindingsError;
+
+    let tmp = tempfile::tempdir().unwrap();
+    let bindings_root = tmp.path().join("bindings");
+    let bindings = || WgpuBindingsExtension::new(&bindings_root).unwrap();
+    let config = WeslBuildConfig::new("./test/src/shaders").out_dir(tmp.path().join("out")).cargo_instructions(false);
+
+    build_shader_dir_with_config(&config, &mut [Box::new(bindings())]).unwrap();
+    build_shader_dir_with_config(&config.clone().verify(true), &mut [Box::new(bindings())])
+        .expect("freshly generated bindings must verify");
+
+    let test2_path = bindings_root.join("test2.rs");
+    let edited = read_to_string(&test2_path).unwrap() + "pub const EDITED: bool = true;\n";
+    std::fs::write(&test2_path, &edited).unwrap();
+
+    let error = build_shader_dir_with_config(&config.verify(true), &mut [Box::new(bindings())]).unwrap_err();
+    let WeslBuildError::ExtensionErr { error, .. } = error else { panic!("{error:?}") };
+    let Some(WgpuBindingsError::Drift { files }) = error.downcast_ref() else { panic!("{error}") };
+    assert_eq!(files.len(), 1, "{error}");
+    assert!(files[0].0.ends_with("test2.rs"));
+    assert_eq!(files[0].1.lines().filter(|line| line.starts_with('-')).collect::<Vec<_>>(), ["-pub const EDITED: bool = true;"]);
+    assert_eq!(read_to_string(&test2_path).unwrap(), edited, "a verify must not rewrite the bindings");
+}
+
+#[cfg(feature = "wgsl_minifier_ext")]
+#[test]
+fn test_minifier_ext() {
+    #[cfg(feature = "logging")]
+    crate::init_build_logger();
+
+    build_shader_dir(
+        "./test/src/shaders",
+        wesl::CompileOptions::default(),
+        &mut [Box::new(crate::extension::wgsl_minifier::WgslMinifierExtension::new())],
+    )
+    .unwrap();
+
+    // shaders
+    assert!(std::fs::exists("test/src/shaders/test.wgsl").unwrap(), "test_bindings_ext requires shaders/test.wgsl to exist");
+    assert!(std::fs::exists("test/src/shaders/test2.wgsl").unwrap(), "test_bindings_ext requires shaders/test2.wgsl to exist");
+    assert!(std::fs::exists("test/src/shaders/test_mod/test_mod_file.wgsl").unwrap(), "test_bindings_ext requires shaders/test_mod/test_mod_file.wgsl to exist");
+
+    // todo test that output size is <= pre-minification, using extension to log before size and another for after size
+}
+
+#[cfg(feature = "wgsl_minifier_ext")]
+#[test]
+fn test_minifier_name_map() {
+    use crate::extension::wgsl_minifier::{NameMap, WgslMinifierExtension};
+
+    let tmp = tempfile::tempdir().unwrap();
+    let shader_root = tmp.path().join("shaders");
+    std::fs::create_dir_all(&shader_root).unwrap();
+    std::fs::write(shader_root.join("minify_map.wgsl"), "
+        @group(0) @binding(0) var<storage, read_write> long_output_name: array<f32>;
+
+        fn long_helper_name(value: f32) -> f32 { return value * 2.0; }
+
+        @compute @workgroup_size(1)
+        fn cs_main() { long_output_name[0] = long_helper_name(2.0); }
+    ").unwrap();
+
+    let plan = build_shader_dir_with_config(
+        &WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(tmp.path()),
+        &mut [Box::new(WgslMinifierExtension::new().with_name_map(true).with_stats(true))],
+    )
+    .unwrap();
+    let built_path = plan.shaders[0].output_path.as_ref().unwrap();
+
+    let name_map = NameMap::read_for(built_path).unwrap().expect("the name map was not written");
+    assert_eq!(name_map.minified_bytes, read_to_string(built_path).unwrap().len());
+    assert!(name_map.minified_bytes < name_map.original_bytes, "nothing was minified: {name_map:?}");
+    for (original, minified) in &name_map.names {
+        assert_eq!(name_map.original(minified), Some(original.as_str()));
+    }
+
+    let stats = read_to_string(tmp.path().join("minify_stats.json")).unwrap();
+    assert!(stats.contains("\"minified_bytes\""), "missing stats:\n{stats}");
+}
+
+#[cfg(feature = "wgsl_obfuscator_ext")]
+#[test]
+fn test_obfuscator() {
+    use crate::extension::wgsl_obfuscator::{
+        BUNDLE_DECODER_NAME, BUNDLE_NAME, ObfuscationStrength, WgslObfuscatorExtension, scramble,
+    };
+
+    let tmp = tempfile::tempdir().unwrap();
+    let shader_root = tmp.path().join("shaders");
+    std::fs::create_dir_all(&shader_root).unwrap();
+    std::fs::write(shader_root.join("secret.wgsl"), "
+        // the secret sauce
+        struct LightData { intensity: f32, falloff: f32 }
+        override light_scale: f32 = 1.0;
+        @group(0) @binding(0) var<storage, read_write> shaded: array<LightData>;
+
+        fn shade_light(light: LightData) -> f32 {
+            let attenuated = light.intensity / light.falloff;
+            return attenuated * light_scale;
+        }
+
+        @compute @workgroup_size(1)
+        fn cs_main() { shaded[0].intensity = shade_light(shaded[1]); }
+    ").unwrap();
+    let out_dir = tmp.path().join("out");
+    let config = WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(&out_dir).cargo_instructions(false);
+
+    let build = |obfuscator: WgslObfuscatorExtension| {
+        let plan = build_shader_dir_with_config(&config, &mut [Box::new(obfuscator)]).unwrap();
+        read_to_string(plan.shaders[0].output_path.as_ref().unwrap()).unwrap()
+    };
+
+    let obfuscated = build(WgslObfuscatorExtension::new().with_seed(7));
+    for hidden in ["secret sauce", "LightData", "intensity", "falloff", "shade_light", "attenuated", "shaded"] {
+        assert!(!obfuscated.contains(hidden), "`{hidden}` was not obfuscated:\n{obfuscated}");
+    }
+    for kept in ["cs_main", "light_scale"] {
+        assert!(obfuscated.contains(kept), "`{kept}` must be kept:\n{obfuscated}");
+    }
+    naga::front::wgsl::parse_str(&obfuscated).expect("the obfuscated shader must be valid WGSL");
+    assert_eq!(obfuscated, build(WgslObfuscatorExtension::new().with_seed(7)), "the same seed must give the same names");
+    assert_ne!(obfuscated, build(WgslObfuscatorExtension::new().with_seed(8)));
+
+    let names_only = build(WgslObfuscatorExtension::new().with_seed(7).with_strength(ObfuscationStrength::Names));
+    assert!(names_only.contains("intensity") && !names_only.contains("LightData"), "{names_only}");
+
+    let bundled = build(WgslObfuscatorExtension::new().with_seed(7).with_strength(ObfuscationStrength::Bundle));
+    let bundle = std::fs::read(out_dir.join(BUNDLE_NAME)).unwrap();
+    assert_eq!(bundle.len(), bundled.len());
+    assert_ne!(bundle, bundled.as_bytes(), "the bundle must be scrambled");
+    let decoder = read_to_string(out_dir.join(BUNDLE_DECODER_NAME)).unwrap();
+    assert!(decoder.contains("pub fn obfuscated_shader(module: &str) -> Option<String>"), "{decoder}");
+
+    // the key of the shader is in the index of the decoder
+    let key = decoder.lines()
+        .find_map(|line| line.trim().strip_prefix("(\"secret\", 0, "))
+        .and_then(|rest| rest.split(", ").nth(1))
+        .map(|key| u64::from_str_radix(key.trim_end_matches("),").trim_start_matches("0x"), 16).unwrap())
+        .unwrap_or_else(|| panic!("the shader is not in the index:\n{decoder}"));
+    assert_eq!(scramble(key, &bundle), bundled.as_bytes());
+}
+
+#[cfg(feature = "wgsl_minifier_ext")]
+#[test]
+fn test_minify_when() {
+    use crate::extension::wgsl_minifier::MinifyWhen;
+
+    let config = WeslBuildConfig::new("shaders").profile("bench");
+    assert_eq!(config.build_profile().as_deref(), Some("bench"));
+
+    {
+        let _context = ContextGuard::set(&config);
+        assert_eq!(current_profile().as_deref(), Some("bench"));
+        assert!(MinifyWhen::Always.should_minify());
+        assert!(MinifyWhen::Profiles(vec!["bench".to_owned()]).should_minify());
+        assert!(!MinifyWhen::Profiles(vec!["release".to_owned()]).should_minify());
+        assert!(!MinifyWhen::ReleaseOnly.should_minify());
+    }
+    assert_eq!(current_profile(), std::env::var("PROFILE").ok(), "the profile must be reset after the build");
+
+    assert!(!MinifyWhen::EnvVar("WESL_BUILD_TEST_UNSET_MINIFY_VAR".to_owned()).should_minify());
+}
+
 #[cfg(feature = "wgsl_minifier_ext")]
 #[test]
-fn test_minifier_ext() {
-    #[cfg(feature = "logging")]
-    crate::init_build_logger();
+fn test_minifier_preserve() {
+    use crate::extension::wgsl_minifier::WgslMinifierExtension;
+
+    let tmp = tempfile::tempdir().unwrap();
+    let shader_root = tmp.path().join("shaders");
+    std::fs::create_dir_all(&shader_root).unwrap();
+    std::fs::write(shader_root.join("minify_preserve.wgsl"), "
+        override light_scale: f32 = 1.0;
+        @group(0) @binding(0) var<storage, read_write> reflect_output: array<f32>;
+
+        fn reflect_helper(value: f32) -> f32 { return value * light_scale; }
+        fn renamed_helper(value: f32) -> f32 { return value + 1.0; }
+
+        @compute @workgroup_size(1)
+        fn cs_main() { reflect_output[0] = renamed_helper(reflect_helper(2.0)); }
+    ").unwrap();
+
+    let plan = build_shader_dir_with_config(
+        &WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(tmp.path()),
+        &mut [Box::new(
+            WgslMinifierExtension::new()
+                .with_preserve_entry_points(true)
+                .with_preserve_overrides(true)
+                .with_preserve("reflect_helper")
+                .with_preserve_pattern(regex::Regex::new("^reflect_").unwrap()),
+        )],
+    )
+    .unwrap();
+
+    let minified = read_to_string(plan.shaders[0].output_path.as_ref().unwrap()).unwrap();
+    for name in ["fn cs_main(", "light_scale", "fn reflect_helper(", "reflect_output"] {
+        assert!(minified.contains(name), "`{name}` was not preserved:\n{minified}");
+    }
+}
+
+#[cfg(feature = "wesl_lint_ext")]
+#[test]
+fn test_lint_ext() {
+    use crate::extension::wesl_lint::{Lint, LintLevel, WeslLintExtension};
+
+    let tmp = tempfile::tempdir().unwrap();
+    std::fs::write(tmp.path().join("lint_missing_invariant.wgsl"), "
+        @vertex
+        fn vs_main(@builtin(vertex_index) idx: u32) -> @builtin(position) vec4<f32> {
+            return vec4(f32(idx), 0.0, 0.0, 1.0);
+        }
+    ").unwrap();
+
+    let result = build_shader_dir(
+        tmp.path().to_str().unwrap(),
+        wesl::CompileOptions::default(),
+        &mut [Box::new(WeslLintExtension::new().lint(Lint::MissingInvariant, LintLevel::Deny))],
+    );
+
+    match result {
+        Err(WeslBuildError::ExtensionErr { extension_name, error, .. }) => {
+            assert_eq!(extension_name, "WeslLintExtension");
+            let error = error.to_string();
+            assert!(error.contains("[missing_invariant]"), "unexpected lint error: {error}");
+            assert!(error.contains("lint_missing_invariant.wgsl:3"), "the lint was not located in the source: {error}");
+        }
+        other => panic!("expected the denied lint to fail the build, got {other:?}"),
+    }
+
+    // allowed lints don't fail the build
+    build_shader_dir(
+        tmp.path().to_str().unwrap(),
+        wesl::CompileOptions::default(),
+        &mut [Box::new(WeslLintExtension::new().lint(Lint::MissingInvariant, LintLevel::Allow))],
+    )
+    .unwrap();
+}
+
+#[cfg(feature = "shader_stats_ext")]
+#[test]
+fn test_shader_stats_ext() {
+    use crate::extension::shader_stats::ShaderStatsExtension;
+
+    let tmp = tempfile::tempdir().unwrap();
+    std::fs::write(tmp.path().join("stats_textured_quad.wgsl"), "
+        struct VertexIn { @location(0) pos: vec2<f32>, @location(1) uv: vec2<f32> }
+        struct VertexOut { @builtin(position) pos: vec4<f32>, @location(0) uv: vec2<f32> }
+
+        @group(0) @binding(0) var tex: texture_2d<f32>;
+        @group(0) @binding(1) var samp: sampler;
+        @group(1) @binding(0) var<uniform> tint: vec4<f32>;
+
+        @vertex
+        fn vs_main(in: VertexIn) -> VertexOut {
+            return VertexOut(vec4(in.pos, 0.0, 1.0), in.uv);
+        }
+
+        @fragment
+        fn fs_main(in: VertexOut) -> @location(0) vec4<f32> {
+            return textureSample(tex, samp, in.uv) * tint;
+        }
+    ").unwrap();
+
+    build_shader_dir(
+        tmp.path().to_str().unwrap(),
+        wesl::CompileOptions::default(),
+        &mut [Box::new(ShaderStatsExtension::new())],
+    )
+    .unwrap();
+
+    let out_dir = std::path::PathBuf::from(std::env::var("OUT_DIR").unwrap());
+    let table = read_to_string(out_dir.join("shader_stats.txt")).unwrap();
+    assert!(table.contains("vs_main(Vertex) fs_main(Fragment)"), "missing entry points:\n{table}");
+    assert!(table.contains("0:2 1:1"), "wrong bindings per group:\n{table}");
+
+    let json = read_to_string(out_dir.join("shader_stats.json")).unwrap();
+    for field in [r#""vertex_attributes": 2"#, r#""varyings": 1"#, r#""textures": 1"#, r#""samplers": 1"#] {
+        assert!(json.contains(field), "`{field}` is missing from the JSON:\n{json}");
+    }
+}
+
+#[cfg(feature = "shader_budget_ext")]
+#[test]
+fn test_shader_budget_ext() {
+    use crate::extension::shader_budget::ShaderBudgetExtension;
+
+    let tmp = tempfile::tempdir().unwrap();
+    std::fs::write(tmp.path().join("budget_heavy_compute.wgsl"), "
+        @group(2) @binding(0) var<storage, read_write> data: array<f32>;
+        var<workgroup> scratch: array<f32, 64>;
+
+        @compute @workgroup_size(64)
+        fn main(@builtin(local_invocation_index) idx: u32) {
+            scratch[idx] = data[idx];
+            workgroupBarrier();
+            data[idx] = scratch[63u - idx];
+        }
+    ").unwrap();
+
+    let result = build_shader_dir(
+        tmp.path().to_str().unwrap(),
+        wesl::CompileOptions::default(),
+        &mut [Box::new(
+            ShaderBudgetExtension::new()
+                .with_max_bind_groups(2)
+                .with_max_workgroup_storage_bytes(128),
+        )],
+    );
+    match result {
+        Err(WeslBuildError::ExtensionErr { extension_name, error, .. }) => {
+            assert_eq!(extension_name, "ShaderBudgetExtension");
+            let error = error.to_string();
+            assert!(error.contains("budget_heavy_compute"), "the module is not named: {error}");
+            assert!(error.contains("3 bind groups > 2"), "missing bind group violation: {error}");
+            assert!(error.contains("256 workgroup storage bytes > 128"), "missing workgroup storage violation: {error}");
+        }
+        other => panic!("expected the budget to fail the build, got {other:?}"),
+    }
+
+    build_shader_dir(
+        tmp.path().to_str().unwrap(),
+        wesl::CompileOptions::default(),
+        &mut [Box::new(ShaderBudgetExtension::webgpu_defaults())],
+    )
+    .unwrap();
+}
+
+#[cfg(feature = "buffer_size_ext")]
+#[test]
+fn test_buffer_size_ext() {
+    use crate::extension::buffer_sizes::BufferSizeExtension;
+
+    let tmp = tempfile::tempdir().unwrap();
+    std::fs::write(tmp.path().join("buffer_sized_lights.wgsl"), "
+        struct Camera { view_proj: mat4x4<f32>, position: vec3<f32> }
+        struct Lights { count: u32, colors: array<vec4<f32>> }
+
+        @group(0) @binding(0) var<uniform> camera: Camera;
+        @group(0) @binding(1) var<storage, read> lights: Lights;
+        @group(1) @binding(0) var<uniform> palette: array<vec4<f32>, 5000>;
+
+        @fragment
+        fn main() -> @location(0) vec4<f32> {
+            return camera.view_proj * lights.colors[lights.count] + palette[0];
+        }
+    ").unwrap();
+
+    build_shader_dir(
+        tmp.path().to_str().unwrap(),
+        wesl::CompileOptions::default(),
+        &mut [Box::new(BufferSizeExtension::new())],
+    )
+    .unwrap();
+
+    let out_dir = std::path::PathBuf::from(std::env::var("OUT_DIR").unwrap());
+    let report = read_to_string(out_dir.join("buffer_sizes.txt")).unwrap();
+    let row = |name: &str| report.lines().find(|line| line.contains(&format!("| {name} "))).unwrap_or_else(|| panic!("no `{name}`:\n{report}"))
+        .split('|').map(str::trim).collect::<Vec<_>>();
+    assert_eq!(row("camera")[4..], ["Camera", "uniform", "80", "16"], "{report}");
+    assert_eq!(row("lights")[4..], ["Lights", "storage", "16 + 16n", "16"], "{report}");
+    assert_eq!(row("palette")[4..], ["array<vec4>", "uniform", "80000 (over 65536)", "16"], "{report}");
+
+    let result = build_shader_dir(
+        tmp.path().to_str().unwrap(),
+        wesl::CompileOptions::default(),
+        &mut [Box::new(BufferSizeExtension::new().with_deny_oversized(true))],
+    );
+    match result {
+        Err(WeslBuildError::ExtensionErr { extension_name, error, .. }) => {
+            assert_eq!(extension_name, "BufferSizeExtension");
+            assert!(error.to_string().contains("`palette` (80000 bytes)"), "{error}");
+        }
+        other => panic!("expected the oversized buffer to fail the build, got {other:?}"),
+    }
+}
+
+#[cfg(feature = "wgsl_target_ext")]
+#[test]
+fn test_wgsl_target_ext() {
+    use crate::extension::wgsl_target::{WgslTarget, WgslTargetExtension};
+
+    let tmp = tempfile::tempdir().unwrap();
+    std::fs::write(tmp.path().join("target_cube_sky.wgsl"), "
+        @group(0) @binding(0) var sky: texture_cube_array<f32>;
+        @group(0) @binding(1) var samp: sampler;
+        @group(0) @binding(2) var out: texture_storage_2d<rg32float, write>;
+
+        @fragment
+        fn main(@builtin(sample_index) sample: u32, @location(0) @interpolate(linear) dir: vec3<f32>) -> @location(0) vec4<f32> {
+            textureStore(out, vec2(0), vec4(f32(sample)));
+            return textureSample(sky, samp, dir, 0);
+        }
+    ").unwrap();
+    let build = |target| build_shader_dir(
+        tmp.path().to_str().unwrap(),
+        wesl::CompileOptions::default(),
+        &mut [Box::new(WgslTargetExtension::new(target))],
+    );
+
+    build(WgslTarget::WebGpu).unwrap();
+    match build(WgslTarget::WebGpuCompat) {
+        Err(WeslBuildError::ExtensionErr { extension_name, error, .. }) => {
+            assert_eq!(extension_name, "WgslTargetExtension");
+            let error = error.to_string();
+            assert!(error.contains("is not supported by WebGPU compatibility mode"), "{error}");
+            assert!(error.contains("`out` has the unsupported format Rg32Float"), "{error}");
+            assert!(error.contains("`main` uses the `sample_index` built-in"), "{error}");
+            assert!(error.contains("`main` uses `linear` interpolation"), "{error}");
+        }
+        other => panic!("expected compatibility mode to fail the build, got {other:?}"),
+    }
+    assert!(build(WgslTarget::Native(naga::valid::Capabilities::empty())).is_err());
+}
+
+#[cfg(feature = "enable_directives_ext")]
+#[test]
+fn test_enable_directives_ext() {
+    use crate::extension::enable_directives::{EnableDirectivesExtension, enable_directives, f16_to_f32};
+
+    let half = "enable f16, clip_distances;\n\nfn scale(v: vec3h) -> mat2x2h { // f16 math\n    let f16_count = 2.5h * f16(1e-3h);\n    return mat2x2h(v.xy, v.yz) * f16_count;\n}\n";
+    assert_eq!(enable_directives(half), ["f16", "clip_distances"]);
+    assert_eq!(
+        f16_to_f32(half),
+        "enable clip_distances;\n\nfn scale(v: vec3f) -> mat2x2f { // f16 math\n    let f16_count = 2.5f * f32(1e-3f);\n    return mat2x2f(v.xy, v.yz) * f16_count;\n}\n",
+    );
+    assert_eq!(f16_to_f32("enable f16;\nconst x: f16 = 1h;\n"), "const x: f32 = 1f;\n");
+
+    let tmp = tempfile::tempdir().unwrap();
+    std::fs::write(tmp.path().join("enable_half_tint.wgsl"), "
+        enable f16;
+
+        @fragment
+        fn main() -> @location(0) vec4<f32> {
+            return vec4<f32>(vec4h(0.5h));
+        }
+    ").unwrap();
+    let out_dir = tmp.path().join("out");
+    let config = WeslBuildConfig::new(tmp.path().to_str().unwrap()).out_dir(&out_dir).cargo_instructions(false);
+
+    build_shader_dir_with_config(&config, &mut [Box::new(EnableDirectivesExtension::new().with_allowed(["f16"]).with_f32_fallback(true))]).unwrap();
+    let fallback = read_to_string(out_dir.join("wesl/enable_half_tint.f32.wgsl")).unwrap();
+    assert!(!fallback.contains("f16") && fallback.contains("0.5f"), "{fallback}");
+
+    let result = build_shader_dir_with_config(&config, &mut [Box::new(EnableDirectivesExtension::new().with_allowed(Vec::<String>::new()))]);
+    match result {
+        Err(WeslBuildError::ExtensionErr { error, .. }) => assert!(error.to_string().contains("enables `f16`"), "{error}"),
+        other => panic!("expected `enable f16` to fail the build, got {other:?}"),
+    }
+}
+
+#[cfg(feature = "feature_fallback_ext")]
+#[test]
+fn test_feature_fallback_ext() {
+    use crate::extension::feature_fallbacks::{FeatureFallback, FeatureFallbackExtension};
+
+    let tmp = tempfile::tempdir().unwrap();
+    std::fs::write(tmp.path().join("fallback_sprites.wesl"), "
+        @if(texture_arrays) @group(0) @binding(0) var sprites: binding_array<texture_2d<f32>, 4>;
+        @if(!texture_arrays) @group(0) @binding(0) var sprites: texture_2d_array<f32>;
+        @group(0) @binding(1) var samp: sampler;
+
+        @fragment
+        fn main(@location(0) uv: vec2<f32>) -> @location(0) vec4<f32> {
+            @if(texture_arrays) { return textureSample(sprites[0], samp, uv); }
+            @if(!texture_arrays) { return textureSample(sprites, samp, uv, 0); }
+        }
+    ").unwrap();
+    std::fs::write(tmp.path().join("fallback_plain.wgsl"), "@fragment fn main() -> @location(0) vec4<f32> { return vec4(1.0); }").unwrap();
+    let out_dir = tmp.path().join("out");
+
+    build_shader_dir_with_config(
+        &WeslBuildConfig::new(tmp.path().to_str().unwrap()).out_dir(&out_dir).cargo_instructions(false),
+        &mut [Box::new(FeatureFallbackExtension::new().with_fallback(FeatureFallback::texture_arrays()))],
+    )
+    .unwrap();
+
+    assert!(read_to_string(out_dir.join("wesl/fallback_sprites.wgsl")).unwrap().contains("binding_array"));
+    let fallback = read_to_string(out_dir.join("wesl/fallback_sprites.no_texture_arrays.wgsl")).unwrap();
+    assert!(fallback.contains("texture_2d_array") && !fallback.contains("binding_array"), "{fallback}");
+    assert!(!out_dir.join("wesl/fallback_plain.no_texture_arrays.wgsl").exists());
+
+    let selector = read_to_string(out_dir.join("shader_fallbacks.rs")).unwrap();
+    assert!(selector.contains("pub fn fallback_sprites(features: wgpu::Features) -> &'static str {"), "{selector}");
+    assert!(selector.contains("if features.contains(wgpu::Features::TEXTURE_BINDING_ARRAY) {"), "{selector}");
+    assert!(selector.contains("fallback_sprites.no_texture_arrays.wgsl"), "{selector}");
+    assert!(selector.contains("pub fn fallback_plain(features: wgpu::Features) -> &'static str {"), "{selector}");
+}
+
+#[cfg(feature = "binding_remap_ext")]
+#[test]
+fn test_binding_remap_ext() {
+    use crate::extension::binding_remap::BindingRemapExtension;
+
+    let tmp = tempfile::tempdir().unwrap();
+    std::fs::write(tmp.path().join("remap_lit.wgsl"), "
+        @group(0) @binding(0) var<uniform> camera: vec4<f32>;
+        @group(1) @binding(0) var tex: texture_2d<f32>;
+        @group(1) @binding(1) var samp: sampler;
+
+        @fragment
+        fn main() -> @location(0) vec4<f32> {
+            return textureSample(tex, samp, camera.xy);
+        }
+    ").unwrap();
+    let out_dir = tmp.path().join("out");
+    let build = |remap: BindingRemapExtension| build_shader_dir_with_config(
+        &WeslBuildConfig::new(tmp.path().to_str().unwrap()).out_dir(&out_dir).cargo_instructions(false),
+        &mut [Box::new(remap)],
+    );
+
+    let remap = BindingRemapExtension::new()
+        .with_group(1, 2)
+        .with_binding((0, 0), (3, 5))
+        .with_module_binding("remap_lit", (1, 1), (2, 7))
+        .unwrap();
+    assert_eq!(remap.remap(&crate::resolver::module_path("other").unwrap(), (1, 1)), (2, 1));
+    build(remap).unwrap();
+    let built = read_to_string(out_dir.join("wesl/remap_lit.wgsl")).unwrap();
+    for slot in ["@group(3) @binding(5)", "@group(2) @binding(0)", "@group(2) @binding(7)"] {
+        assert!(built.contains(slot), "`{slot}` is missing:\n{built}");
+    }
+
+    match build(BindingRemapExtension::new().with_binding((1, 1), (1, 0))) {
+        Err(WeslBuildError::ExtensionErr { error, .. }) => {
+            let error = error.to_string();
+            assert!(error.contains("`tex` and `samp` of `"), "{error}");
+            assert!(error.contains("remap_lit` are both remapped to @group(1) @binding(0)"), "{error}");
+        }
+        other => panic!("expected the collision to fail the build, got {other:?}"),
+    }
+}
+
+#[cfg(feature = "binding_usage_ext")]
+#[test]
+fn test_binding_usage_ext() {
+    use crate::extension::binding_usage::{BindingUsage, BindingUsageExtension};
+
+    let source = "
+        @group(0) @binding(0) var<uniform> camera: vec4<f32>;
+        @group(0) @binding(1) var<uniform> tint: vec4<f32>;
+        @group(1) @binding(0) var<uniform> unused: vec4<f32>;
+
+        fn tinted() -> vec4<f32> { return tint; }
+
+        @vertex fn vs_main() -> @builtin(position) vec4<f32> { return camera; }
+        @fragment fn fs_main() -> @location(0) vec4<f32> { return camera * tinted(); }
+    ";
+    let module = crate::NagaModule::parse(source).unwrap();
+    let usages = BindingUsage::of_module(&module).unwrap();
+    let used_by = usages.iter().map(|usage| (usage.name.as_str(), usage.entry_points.clone())).collect::<Vec<_>>();
+    assert_eq!(used_by, [
+        ("camera", vec!["vs_main".to_owned(), "fs_main".to_owned()]),
+        ("tint", vec!["fs_main".to_owned()]),
+        ("unused", vec![]),
+    ]);
+
+    let mod_path = crate::resolver::module_path("usage_lit").unwrap();
+    let mut extension = BindingUsageExtension::new().with_strip_unused(true);
+    let mut module = crate::NagaModule::parse(source).unwrap();
+    WeslBuildExtension::<BuildResolver>::post_parse(&mut extension, &mod_path, &mut module).unwrap();
+    assert!(module.is_modified());
+    let names = module.global_variables.iter().filter_map(|(_, global)| global.name.as_deref()).collect::<Vec<_>>();
+    assert_eq!(names, ["camera", "tint"]);
+
+    // shaders using all their bindings are not rewritten
+    let mut module = crate::NagaModule::parse("@group(0) @binding(0) var<uniform> c: vec4<f32>; @fragment fn main() -> @location(0) vec4<f32> { return c; }").unwrap();
+    WeslBuildExtension::<BuildResolver>::post_parse(&mut extension, &mod_path, &mut module).unwrap();
+    assert!(!module.is_modified());
+    assert_eq!(extension.usages().len(), 2);
+}
+
+#[cfg(feature = "texture_consistency_ext")]
+#[test]
+fn test_texture_consistency_ext() {
+    use crate::extension::texture_consistency::{TextureBinding, TextureConsistencyExtension};
+
+    let module = crate::NagaModule::parse("
+        @group(0) @binding(0) var albedo: texture_2d<f32>;
+        @group(0) @binding(1) var shadows: texture_depth_2d_array;
+        @group(0) @binding(2) var output: texture_storage_2d<rgba8unorm, write>;
+        @group(0) @binding(3) var linear: sampler;
+    ").unwrap();
+    let types = TextureBinding::of_module(&module).into_iter().map(|texture| texture.texture_type).collect::<Vec<_>>();
+    assert_eq!(types, ["texture_2d<f32>", "texture_depth_2d_array", "texture_storage_2d<rgba8unorm, write>"]);
+
+    let tmp = tempfile::tempdir().unwrap();
+    std::fs::write(tmp.path().join("lit.wgsl"), "
+        @group(1) @binding(0) var albedo: texture_2d<f32>;
+        @fragment fn main() -> @location(0) vec4<f32> { return textureLoad(albedo, vec2(0), 0); }
+    ").unwrap();
+    std::fs::write(tmp.path().join("unlit.wgsl"), "
+        @group(1) @binding(0) var albedo: texture_2d<f32>;
+        @fragment fn main() -> @location(0) vec4<f32> { return textureLoad(albedo, vec2(0), 0); }
+    ").unwrap();
+    build_shader_dir(tmp.path().to_str().unwrap(), wesl::CompileOptions::default(), &mut [Box::new(TextureConsistencyExtension::new())]).unwrap();
+
+    std::fs::write(tmp.path().join("ids.wgsl"), "
+        @group(1) @binding(0) var albedo: texture_2d<u32>;
+        @fragment fn main() -> @location(0) vec4<u32> { return textureLoad(albedo, vec2(0), 0); }
+    ").unwrap();
+    let result = build_shader_dir(tmp.path().to_str().unwrap(), wesl::CompileOptions::default(), &mut [Box::new(TextureConsistencyExtension::new())]);
+    let Err(WeslBuildError::ExtensionErr { extension_name, error, .. }) = result else { panic!("{result:?}") };
+    assert_eq!(extension_name, "TextureConsistencyExtension");
+    let error = error.to_string();
+    assert!(error.contains("@group(1) @binding(0) `albedo` is `texture_2d<f32>` in "), "{error}");
+    assert!(error.contains("`texture_2d<u32>` in `ids`"), "{error}");
+}
+
+#[cfg(feature = "wgsl_formatter_ext")]
+#[test]
+fn test_wgsl_formatter() {
+    use crate::extension::wgsl_formatter::format_wgsl;
+
+    let minified = "struct S{b:f32,a:array<f32, 4>}\n// keep me\nfn f(x:f32)->f32{if x>1.0{return x;}else{for(var i=0;i<2;i++){let y=x;}}return 2.0;}";
+
+    assert_eq!(format_wgsl(minified, false), "\
+struct S {
+    b:f32,
+    a:array<f32, 4>
+}
+
+// keep me
+fn f(x:f32)->f32 {
+    if x>1.0 {
+        return x;
+    } else {
+        for(var i=0;i<2;i++) {
+            let y=x;
+        }
+    }
+    return 2.0;
+}
+");
+    assert!(
+        format_wgsl(minified, true).starts_with("struct S {\n    a:array<f32, 4>,\n    b:f32,\n}\n"),
+        "struct members were not sorted"
+    );
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn test_snapshots() {
+    let tmp = tempfile::tempdir().unwrap();
+    let shader_root = tmp.path().join("shaders");
+    std::fs::create_dir_all(shader_root.join("snapshot_mod")).unwrap();
+    std::fs::write(shader_root.join("snapshot_mod/snapshot_shader.wgsl"), "
+        @compute @workgroup_size(1)
+        fn main() {}
+    ").unwrap();
+    let snapshot_dir = tmp.path().join("snapshots");
+
+    let built = crate::testing::build_shaders(&WeslBuildConfig::new(shader_root.to_str().unwrap())).unwrap();
+    let wgsl = built.get("snapshot_mod::snapshot_shader").expect("the shader was not built");
+    assert!(wgsl.contains("fn main()"), "unexpected built shader:\n{wgsl}");
+
+    let snapshot_path = snapshot_dir.join("snapshot_mod/snapshot_shader.wgsl");
+    std::fs::create_dir_all(snapshot_path.parent().unwrap()).unwrap();
+    std::fs::write(&snapshot_path, wgsl).unwrap();
+    built.assert_snapshots(&snapshot_dir);
+
+    std::fs::write(&snapshot_path, "outdated").unwrap();
+    let mismatch = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| built.assert_snapshots(&snapshot_dir)));
+    assert!(mismatch.is_err(), "a changed shader must fail the snapshot test");
+    assert_eq!(read_to_string(snapshot_path.with_extension("wgsl.new")).unwrap(), wgsl);
+}
+
+#[cfg(feature = "shader_registry_ext")]
+#[test]
+fn test_dry_run() {
+    use crate::extension::shader_registry::ShaderRegistryExtension;
+
+    let tmp = tempfile::tempdir().unwrap();
+    let shader_root = tmp.path().join("shaders");
+    std::fs::create_dir_all(shader_root.join("dry_mod")).unwrap();
+    std::fs::write(shader_root.join("dry_mod/dry_run_shader.wgsl"), "fn f() {}").unwrap();
+    let out_dir = tmp.path().join("out");
+    std::fs::create_dir_all(&out_dir).unwrap();
+
+    let plan = build_shader_dir_with_config(
+        &WeslBuildConfig::new(shader_root.to_str().unwrap())
+            .out_dir(&out_dir)
+            .dry_run(true),
+        &mut [Box::new(ShaderRegistryExtension::new(tmp.path().join("registry.rs")))],
+    )
+    .unwrap();
+
+    assert_eq!(plan.shaders.len(), 1);
+    let planned = &plan.shaders[0];
+    assert_eq!(planned.mod_path.components, ["dry_mod", "dry_run_shader"]);
+    assert_eq!(planned.source_path, shader_root.join("dry_mod/dry_run_shader.wgsl"));
+    assert_eq!(planned.output_path, Some(out_dir.join(format!("{}.wgsl", planned.artifact_name))));
+
+    assert_eq!(std::fs::read_dir(&out_dir).unwrap().count(), 0, "a dry run must not write any output");
+    assert!(!tmp.path().join("registry.rs").exists(), "a dry run must not run the other extension hooks");
+}
+
+#[test]
+fn test_include_exclude() {
+    let tmp = tempfile::tempdir().unwrap();
+    let shader_root = tmp.path().join("shaders");
+    std::fs::create_dir_all(shader_root.join("passes/debug")).unwrap();
+    for file in ["passes/blur.wgsl", "passes/test_blur.wgsl", "passes/debug/lines.wesl", "lights.wesl", "util.wgsl"] {
+        std::fs::write(shader_root.join(file), "fn f() {}").unwrap();
+    }
+
+    let plan = build_shader_dir_with_config(
+        &WeslBuildConfig::new(shader_root.to_str().unwrap())
+            .out_dir(tmp.path().join("out"))
+            .include(["passes/**", "*.wesl"])
+            .exclude(["**/test_*"])
+            .dry_run(true),
+        &mut [],
+    )
+    .unwrap();
+
+    let built = plan.shaders.iter().map(|shader| shader.mod_path.components.join("::")).sorted().collect::<Vec<_>>();
+    assert_eq!(built, ["lights", "passes::blur", "passes::debug::lines"]);
+
+    let config = WeslBuildConfig::new("shaders").exclude(["passes/**/*.wes?"]);
+    assert!(!config.builds_file("passes/debug/lines.wesl"));
+    assert!(!config.builds_file("passes/lines.wesl"));
+    assert!(config.builds_file("passes/lines.wgsl"));
+}
+
+#[test]
+fn test_path_separators() {
+    let root = Path::new("shaders");
+    let file = root.join("passes").join("debug").join("lines.wesl");
+    assert_eq!(walk::relative_path(&file, root).unwrap(), "passes/debug/lines.wesl");
+    assert_eq!(
+        module_components(&file, root, FileNames::Verbatim).unwrap(),
+        ["passes", "debug", "lines"]
+    );
+
+    let out_dir = Path::new("out");
+    assert_eq!(
+        artifact_path(out_dir, "wesl/passes/blur"),
+        out_dir.join("wesl").join("passes").join("blur.wgsl")
+    );
+    assert_eq!(artifact_path(out_dir, "passes_blur.v2"), out_dir.join("passes_blur.v2.wgsl"));
+
+    let config = WeslBuildConfig::new("shaders").include(["passes\\**"]);
+    assert!(config.builds_file("passes/debug/lines.wesl"));
+    assert!(config.builds_file("passes\\lines.wesl"));
+    assert!(!config.builds_file("lights.wesl"));
+}
+
+// `\` only separates dirs on windows, elsewhere it's part of the name
+#[cfg(windows)]
+#[test]
+fn test_windows_paths() {
+    let tmp = tempfile::tempdir().unwrap();
+    let shader_root = tmp.path().join("shaders");
+    std::fs::create_dir_all(shader_root.join("passes\\debug")).unwrap();
+    std::fs::write(shader_root.join("passes\\debug\\lines.wesl"), "fn f() {}").unwrap();
+    let out_dir = tmp.path().join("out");
+
+    let plan = build_shader_dir_with_config(
+        &WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(&out_dir).cargo_instructions(false),
+        &mut [],
+    )
+    .unwrap();
+
+    let shader = &plan.shaders[0];
+    assert_eq!(shader.mod_path.components, ["passes", "debug", "lines"]);
+    assert_eq!(shader.artifact_name, "wesl/passes/debug/lines");
+    assert_eq!(shader.output_path.as_deref(), Some(out_dir.join("wesl\\passes\\debug\\lines.wgsl").as_path()));
+    assert!(out_dir.join("wesl\\passes\\debug\\lines.wgsl").is_file());
+}
+
+#[cfg(all(feature = "config_file", feature = "wgpu_bindings_ext", feature = "wgsl_formatter_ext", feature = "dependency_graph_ext"))]
+#[test]
+fn test_config_from_file() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("wesl_build.toml");
+    std::fs::write(&path, r#"
+root = ["shaders", "assets/ui_shaders"]
+out = "target/shaders"
+exclude = ["**/test_*"]
+mangler = "hash"
+extensions = ["format", "graph"]
+
+[features]
+f16 = true
+
+[bindings]
+out = "src/bindings"
+matrix_vector_types = "glam"
+entry_points = true
+"#).unwrap();
+
+    let builds = WeslBuildConfig::from_file(&path).unwrap();
+    assert_eq!(builds.len(), 2);
+    let (config, extensions) = &builds[1];
+    assert_eq!(Path::new(&config.shader_path), tmp.path().join("assets/ui_shaders"));
+    assert_eq!(config.out_dir.as_deref(), Some(tmp.path().join("target/shaders/ui_shaders").as_path()));
+    assert_eq!(config.exclude, ["**/test_*"]);
+    assert!(matches!(config.mangler, wesl::ManglerKind::Hash));
+    assert!(matches!(config.compile_options.features.flags.get("f16"), Some(wesl::Feature::Enable)));
+    assert!(config.rerun_if_changed.contains(&path));
+    assert_eq!(extensions.names(), ["WgpuBindingsExtension", "WgslFormatterExtension", "DependencyGraphExtension"]);
+
+    // errors point to the invalid value
+    std::fs::write(&path, "root = \"shaders\"\nmangler = \"hashed\"\n").unwrap();
+    let error = WeslBuildConfig::from_file(&path).unwrap_err();
+    assert!(matches!(&error, WeslBuildError::ConfigFileErr { .. }), "{error}");
+    assert!(error.to_string().contains("line 2"), "{error}");
+    assert!(error.to_string().contains("hashed"), "{error}");
+
+    std::fs::write(&path, "[bindings]\nentry_point = true\n").unwrap();
+    let error = WeslBuildConfig::from_file(&path).unwrap_err();
+    assert!(error.to_string().contains("entry_point"), "{error}");
+}
+
+#[test]
+fn test_continue_on_error() {
+    let tmp = tempfile::tempdir().unwrap();
+    for (name, shader) in [
+        ("collect_broken_a.wgsl", "fn a() -> f32 { return missing_a; }"),
+        ("collect_broken_b.wgsl", "fn b() -> f32 { return missing_b; }"),
+        ("collect_valid.wgsl", "fn c() -> f32 { return 1.0; }"),
+    ] {
+        std::fs::write(tmp.path().join(name), shader).unwrap();
+    }
+    let config = WeslBuildConfig::new(tmp.path().to_str().unwrap());
+
+    let fail_fast = build_shader_dir_with_config(&config, &mut []);
+    assert!(
+        matches!(fail_fast, Err(WeslBuildError::CompileErr { .. })),
+        "the first error must stop the build, got {fail_fast:?}"
+    );
+
+    match build_shader_dir_with_config(&config.continue_on_error(true), &mut []) {
+        Err(WeslBuildError::Multiple(errors)) => {
+            let mut failed = errors.iter()
+                .map(|error| match error {
+                    WeslBuildError::CompileErr { mod_path, .. } => mod_path.to_string(),
+                    other => panic!("unexpected error: {other}"),
+                })
+                .collect::<Vec<_>>();
+            failed.sort();
+            assert_eq!(failed.len(), 2);
+            assert!(failed[0].contains("collect_broken_a") && failed[1].contains("collect_broken_b"), "wrong modules failed: {failed:?}");
+        }
+        other => panic!("expected both errors to be collected, got {other:?}"),
+    }
+}
+
+#[test]
+fn test_json_diagnostics() {
+    use crate::messages::JsonOutput;
+
+    let tmp = tempfile::tempdir().unwrap();
+    let shader_root = tmp.path().join("shaders");
+    std::fs::create_dir_all(&shader_root).unwrap();
+    std::fs::write(shader_root.join("json_broken.wgsl"), "fn a() -> f32 { return \"missing\"; }").unwrap();
+    std::fs::write(shader_root.join("json_valid.wgsl"), "fn b() -> f32 { return 1.0; }").unwrap();
+    let json_path = tmp.path().join("diagnostics.json");
+
+    let result = build_shader_dir_with_config(
+        &WeslBuildConfig::new(shader_root.to_str().unwrap())
+            .out_dir(tmp.path().join("out"))
+            .cargo_instructions(false)
+            .continue_on_error(true)
+            .json_diagnostics(JsonOutput::File(json_path.clone())),
+        &mut [],
+    );
+    assert!(result.is_err(), "the broken shader must fail the build");
+
+    let json = read_to_string(&json_path).unwrap();
+    let lines = json.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 1, "{json}");
+    assert!(lines[0].starts_with(r#"{"severity":"error","message":"#), "{json}");
+    assert!(lines[0].contains(r#","module":"json_broken","#), "{json}");
+    assert!(lines[0].contains("json_broken.wgsl"), "{json}");
+    assert!(!lines[0].contains('\n'), "a message must be a single line: {json}");
+}
+
+#[test]
+fn test_on_error_hook() {
+    use std::{cell::RefCell, rc::Rc};
+
+    /// Records the modules that failed and whether they failed to compile
+    struct FailureObserver(Rc<RefCell<Vec<(String, bool)>>>);
+
+    impl WeslBuildExtension<BuildResolver> for FailureObserver {
+        fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+            "FailureObserver".into()
+        }
+
+        fn init_root(&mut self, _shader_root_path: &str, _res: &mut Wesl<BuildResolver>) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+        fn enter_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+        fn exit_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+        fn post_build(&mut self, _wesl_path: &ModulePath, _wgsl_built_path: &str, _source_map: &Option<BasicSourceMap>) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+
+        fn on_error(&mut self, wesl_path: &ModulePath, error: &WeslBuildError) {
+            let compile_error = matches!(error, WeslBuildError::CompileErr { .. });
+            self.0.borrow_mut().push((wesl_path.components.join("::"), compile_error));
+        }
+    }
+
+    let tmp = tempfile::tempdir().unwrap();
+    std::fs::write(tmp.path().join("observed_broken.wgsl"), "fn a() -> f32 { return missing; }").unwrap();
+    std::fs::write(tmp.path().join("observed_valid.wgsl"), "fn b() -> f32 { return 1.0; }").unwrap();
+
+    let failures = Rc::new(RefCell::new(Vec::new()));
+    let result = build_shader_dir_with_config(
+        &WeslBuildConfig::new(tmp.path().to_str().unwrap())
+            .out_dir(tmp.path().join("out"))
+            .cargo_instructions(false)
+            .continue_on_error(true),
+        &mut [Box::new(FailureObserver(failures.clone()))],
+    );
+
+    assert!(matches!(result, Err(WeslBuildError::Multiple(_))), "{result:?}");
+    assert_eq!(*failures.borrow(), [("observed_broken".to_owned(), true)]);
+}
+
+#[test]
+fn test_build_context() {
+    use std::{cell::RefCell, rc::Rc};
+
+    /// The modules built, shared through the context
+    struct BuiltModules(Vec<String>);
+
+    /// Stores the modules it sees in the context, and writes them to the scratch dir
+    struct Producer;
+    /// Reads what the producer stored once the shaders are built
+    struct Consumer(Rc<RefCell<Option<(Vec<String>, PathBuf)>>>);
+
+    impl WeslBuildExtension<BuildResolver> for Producer {
+        fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+            "Producer".into()
+        }
+
+        fn init_root(&mut self, _shader_root_path: &str, _res: &mut Wesl<BuildResolver>) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+        fn enter_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+        fn exit_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+
+        fn post_build(&mut self, wesl_path: &ModulePath, _wgsl_built_path: &str, _source_map: &Option<BasicSourceMap>) -> Result<(), Box<dyn std::error::Error>> {
+            with_build_context(|ctx| -> std::io::Result<()> {
+                ctx.get_or_insert_with(|| BuiltModules(Vec::new())).0.push(wesl_path.components.join("::"));
+                std::fs::write(ctx.scratch_dir()?.join("last.txt"), wesl_path.to_string())
+            }).expect("hooks run in a build")?;
+            Ok(())
+        }
+    }
+
+    impl WeslBuildExtension<BuildResolver> for Consumer {
+        fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+            "Consumer".into()
+        }
+
+        fn must_run_after(&self) -> &[&str] {
+            &["Producer"]
+        }
+
+        fn init_root(&mut self, _shader_root_path: &str, _res: &mut Wesl<BuildResolver>) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+        fn enter_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+        fn exit_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+        fn post_build(&mut self, _wesl_path: &ModulePath, _wgsl_built_path: &str, _source_map: &Option<BasicSourceMap>) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+
+        fn exit_root(&mut self, _shader_root_path: &str, _res: &Wesl<BuildResolver>) -> Result<(), Box<dyn std::error::Error>> {
+            *self.0.borrow_mut() = with_build_context(|ctx| {
+                assert_eq!(ctx.profile().as_deref(), Some("shipping"));
+                let modules = ctx.remove::<BuiltModules>().map(|modules| modules.0).unwrap_or_default();
+                Some((modules, ctx.scratch_dir().ok()?.to_owned()))
+            }).flatten();
+            Ok(())
+        }
+    }
+
+    let tmp = tempfile::tempdir().unwrap();
+    std::fs::write(tmp.path().join("shared_a.wgsl"), "fn a() {}").unwrap();
+    std::fs::write(tmp.path().join("shared_b.wgsl"), "fn b() {}").unwrap();
+
+    let consumed = Rc::new(RefCell::new(None));
+    build_shader_dir_with_config(
+        &WeslBuildConfig::new(tmp.path().to_str().unwrap())
+            .out_dir(tmp.path().join("out"))
+            .cargo_instructions(false)
+            .profile("shipping"),
+        &mut [Box::new(Consumer(consumed.clone())), Box::new(Producer)],
+    )
+    .unwrap();
+
+    let (modules, scratch_dir) = consumed.take().expect("the consumer ran");
+    assert_eq!(modules.into_iter().sorted().collect::<Vec<_>>(), ["shared_a", "shared_b"]);
+    assert!(!scratch_dir.exists(), "the scratch dir must be removed with the build");
+    assert!(with_build_context(|_| ()).is_none(), "there is no context outside a build");
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn test_async_extension() {
+    use std::sync::{Arc, Mutex};
+    use crate::extension::async_extension::{AsyncExtension, AsyncExtensionError, AsyncWeslBuildExtension, build_shader_dir_blocking};
+
+    /// Uploads every built shader in a task, and waits for the uploads once all are built
+    #[derive(Default)]
+    struct Uploader {
+        uploads: Vec<tokio::task::JoinHandle<String>>,
+        uploaded: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl AsyncWeslBuildExtension<BuildResolver> for Uploader {
+        fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+            "Uploader".into()
+        }
+
+        async fn post_build(&mut self, wesl_path: &ModulePath, _wgsl_built_path: &str, _source_map: &Option<BasicSourceMap>) -> Result<(), Box<dyn std::error::Error>> {
+            let module = wesl_path.components.join("::");
+            self.uploads.push(tokio::spawn(async move {
+                tokio::task::yield_now().await;
+                module
+            }));
+            Ok(())
+        }
+
+        async fn exit_root(&mut self, _shader_root_path: &str, _res: &Wesl<BuildResolver>) -> Result<(), Box<dyn std::error::Error>> {
+            for upload in self.uploads.drain(..) {
+                self.uploaded.lock().unwrap().push(upload.await?);
+            }
+            Ok(())
+        }
+    }
+
+    let tmp = tempfile::tempdir().unwrap();
+    std::fs::write(tmp.path().join("upload_a.wgsl"), "fn a() {}").unwrap();
+    std::fs::write(tmp.path().join("upload_b.wgsl"), "fn b() {}").unwrap();
+    let config = WeslBuildConfig::new(tmp.path().to_str().unwrap()).out_dir(tmp.path().join("out")).cargo_instructions(false);
+
+    let build = |runtime: tokio::runtime::Runtime| {
+        let uploaded = Arc::new(Mutex::new(Vec::new()));
+        let (shader_path, out_dir) = (config.shader_path.clone(), tmp.path().join("out"));
+        let uploader_uploaded = uploaded.clone();
+        runtime.block_on(tokio::task::spawn_blocking(move || {
+            let config = WeslBuildConfig::new(shader_path).out_dir(out_dir).cargo_instructions(false);
+            let uploader = Uploader { uploaded: uploader_uploaded, ..Uploader::default() };
+            build_shader_dir_blocking(&config, &mut [Box::new(AsyncExtension::new(uploader))]).map_err(|error| error.to_string())
+        })).unwrap().unwrap();
+        uploaded.lock().unwrap().iter().sorted().cloned().collect::<Vec<_>>()
+    };
+    assert_eq!(build(tokio::runtime::Builder::new_multi_thread().worker_threads(2).build().unwrap()), ["upload_a", "upload_b"]);
+    // the spawned uploads run on the runtime's only thread while the build blocks another
+    assert_eq!(build(tokio::runtime::Builder::new_current_thread().build().unwrap()), ["upload_a", "upload_b"]);
+
+    // the hooks need the runtime of an async build
+    let error = build_shader_dir_with_config(&config, &mut [Box::new(AsyncExtension::new(Uploader::default()))]).unwrap_err();
+    let WeslBuildError::ExtensionErr { error, .. } = error else { panic!("{error:?}") };
+    assert!(matches!(error.downcast_ref(), Some(AsyncExtensionError::NoRuntime { .. })), "{error}");
+}
+
+#[cfg(all(unix, feature = "command_ext"))]
+#[test]
+fn test_command_ext() {
+    use crate::extension::command::{CommandError, CommandExtension, RunOn};
+
+    let tmp = tempfile::tempdir().unwrap();
+    let (shader_root, out_dir) = (tmp.path().join("shaders"), tmp.path().join("out"));
+    std::fs::create_dir_all(&shader_root).unwrap();
+    std::fs::write(shader_root.join("cmd_shader.wgsl"), "fn f() {}").unwrap();
+    let config = WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(&out_dir).cargo_instructions(false);
+    let build = |ext: CommandExtension| build_shader_dir_with_config(&config, &mut [Box::new(ext)]);
+
+    build(CommandExtension::new("sh", ["-c", "cp \"$0\" \"$1\"", "{input}", "{out_dir}/{module}.copy"])).unwrap();
+    assert_eq!(
+        read_to_string(out_dir.join("cmd_shader.copy")).unwrap(),
+        read_to_string(out_dir.join("wesl/cmd_shader.wgsl")).unwrap(),
+    );
+    build(CommandExtension::new("sh", ["-c", "ls \"$0\" > \"$0/../listing.txt\"", "{input}"]).with_run_on(RunOn::OutDir)).unwrap();
+    assert!(read_to_string(tmp.path().join("listing.txt")).unwrap().contains("wesl"));
+
+    let error = build(CommandExtension::new("sh", ["-c", "echo broken shader >&2; exit 3"])).unwrap_err();
+    let WeslBuildError::ExtensionErr { error, .. } = error else { panic!("{error:?}") };
+    assert!(matches!(error.downcast_ref(), Some(CommandError::Failed { stderr, .. }) if stderr == "broken shader\n"), "{error}");
+
+    let error = build(CommandExtension::new("sh", ["{inptu}"])).unwrap_err();
+    let WeslBuildError::ExtensionErr { error, hook, .. } = error else { panic!("{error:?}") };
+    assert_eq!(hook, "init_root");
+    assert!(matches!(error.downcast_ref(), Some(CommandError::UnknownPlaceholder { placeholder, .. }) if placeholder == "inptu"), "{error}");
+
+    // the built WGSL of a check is only in memory, the command gets a copy
+    build_shader_dir_with_config(
+        &config.clone().check(true),
+        &mut [Box::new(CommandExtension::new("sh", ["-c", "grep -q \"fn f\" \"$0\"", "{input}"]))],
+    )
+    .unwrap();
+}
+
+#[cfg(all(unix, feature = "toolchain_validation_ext"))]
+#[test]
+fn test_toolchain_validation_ext() {
+    use std::os::unix::fs::PermissionsExt;
+    use crate::extension::toolchain_validation::{Toolchain, ToolchainValidationError, ToolchainValidationExtension};
+
+    let tmp = tempfile::tempdir().unwrap();
+    let (shader_root, out_dir) = (tmp.path().join("shaders"), tmp.path().join("out"));
+    std::fs::create_dir_all(&shader_root).unwrap();
+    std::fs::write(shader_root.join("tint_shader.wgsl"), "fn bad_fn() {}\n").unwrap();
+    let config = WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(&out_dir).cargo_instructions(false);
+
+    // a stand-in for tint, printing diagnostics in its format for the file it's given last
+    let fake_tint = |name: &str, script: &str| {
+        let path = tmp.path().join(name);
+        std::fs::write(&path, format!("#!/bin/sh\nfor input; do :; done\n{script}\n")).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        ToolchainValidationExtension::new(Toolchain::Tint).with_binary(path)
+    };
+
+    build_shader_dir_with_config(&config, &mut [Box::new(fake_tint("ok_tint", "echo \"$input:1:4 warning: unused\" >&2"))]).unwrap();
+
+    let error = build_shader_dir_with_config(
+        &config,
+        &mut [Box::new(fake_tint("bad_tint", "echo \"$input:1:4 error: bad thing\" >&2; exit 1"))],
+    )
+    .unwrap_err();
+    let WeslBuildError::ExtensionErr { error, .. } = error else { panic!("{error:?}") };
+    let Some(ToolchainValidationError::Rejected { diagnostics, .. }) = error.downcast_ref() else { panic!("{error}") };
+    assert_eq!(diagnostics.len(), 1);
+    assert!(diagnostics[0].contains("bad thing") && diagnostics[0].contains("tint_shader.wgsl"), "{}", diagnostics[0]);
+
+    let error = build_shader_dir_with_config(&config, &mut [Box::new(fake_tint("crashing_tint", "echo segfault; exit 139"))])
+        .unwrap_err();
+    assert!(error.to_string().contains("segfault"), "{error}");
+
+    let missing = || ToolchainValidationExtension::new(Toolchain::Tint).with_binary(tmp.path().join("no_tint"));
+    build_shader_dir_with_config(&config, &mut [Box::new(missing().with_skip_if_missing(true))]).unwrap();
+    let error = build_shader_dir_with_config(&config, &mut [Box::new(missing())]).unwrap_err();
+    let WeslBuildError::ExtensionErr { error, .. } = error else { panic!("{error:?}") };
+    assert!(matches!(error.downcast_ref(), Some(ToolchainValidationError::Spawn { .. })), "{error}");
+}
+
+#[cfg(feature = "glsl_ingest_ext")]
+#[test]
+fn test_glsl_ingest_ext() {
+    use crate::extension::glsl_ingest::{GlslIngestError, GlslIngestExtension};
+
+    let tmp = tempfile::tempdir().unwrap();
+    let (shader_root, out_dir) = (tmp.path().join("shaders"), tmp.path().join("out"));
+    std::fs::create_dir_all(shader_root.join("legacy")).unwrap();
+    std::fs::write(shader_root.join("legacy/fill.frag"), "#version 450
+layout(location = 0) out vec4 color;
+void main() {
+#ifdef RED
+    color = vec4(1.0, 0.0, 0.0, 1.0);
+#else
+    color = vec4(0.0);
+#endif
+}
+").unwrap();
+    std::fs::write(shader_root.join("legacy/fill.vert"), "#version 450
+void main() { gl_Position = vec4(0.0, 0.0, 0.0, 1.0); }
+").unwrap();
+    let config = WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(&out_dir).cargo_instructions(false);
+
+    let plan = build_shader_dir_with_config(&config, &mut [Box::new(GlslIngestExtension::new().with_define("RED", "1"))]).unwrap();
+    let built = plan.shaders.iter().map(|shader| shader.mod_path.components.join("::")).sorted().collect::<Vec<_>>();
+    assert_eq!(built, ["legacy::fill_frag", "legacy::fill_vert"]);
+    let fill_frag = read_to_string(out_dir.join("wesl/legacy/fill_frag.wgsl")).unwrap();
+    assert!(fill_frag.contains("@fragment") && fill_frag.contains("1f"), "{fill_frag}");
+
+    std::fs::write(shader_root.join("legacy/fill_vert.wgsl"), "fn f() {}").unwrap();
+    let error = build_shader_dir_with_config(&config, &mut [Box::new(GlslIngestExtension::new())]).unwrap_err();
+    let WeslBuildError::ExtensionErr { error, .. } = error else { panic!("{error:?}") };
+    assert!(matches!(error.downcast_ref(), Some(GlslIngestError::ModuleClash { .. })), "{error}");
+    std::fs::remove_file(shader_root.join("legacy/fill_vert.wgsl")).unwrap();
+
+    std::fs::write(shader_root.join("legacy/broken.comp"), "#version 450\nvoid main() { undefined_fn(); }").unwrap();
+    let error = build_shader_dir_with_config(&config, &mut [Box::new(GlslIngestExtension::new())]).unwrap_err();
+    let WeslBuildError::ExtensionErr { error, .. } = error else { panic!("{error:?}") };
+    assert!(matches!(error.downcast_ref(), Some(GlslIngestError::Parse { path, .. }) if path.ends_with("broken.comp")), "{error}");
+}
+
+#[cfg(feature = "entry_point_split_ext")]
+#[test]
+fn test_entry_point_split() {
+    use crate::extension::entry_point_split::EntryPointSplitExtension;
+
+    let tmp = tempfile::tempdir().unwrap();
+    let (shader_root, out_dir) = (tmp.path().join("shaders"), tmp.path().join("out"));
+    std::fs::create_dir_all(&shader_root).unwrap();
+    std::fs::write(shader_root.join("uber.wesl"), "
+        @group(0) @binding(0) var<storage, read_write> particles: array<f32>;
+
+        fn simulate(i: u32) { particles[i] += 1.0; }
+        fn shade() -> vec4<f32> { return vec4(1.0); }
+
+        @compute @workgroup_size(64) fn cs_simulate(@builtin(global_invocation_id) id: vec3<u32>) { simulate(id.x); }
+        @vertex fn vs_main() -> @builtin(position) vec4<f32> { return vec4(0.0); }
+        @fragment fn fs_main() -> @location(0) vec4<f32> { return shade(); }
+    ").unwrap();
+    std::fs::write(shader_root.join("single.wesl"), "@compute @workgroup_size(1) fn main() {}").unwrap();
+    let config = WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(&out_dir).cargo_instructions(false);
+
+    let plan = build_shader_dir_with_config(&config, &mut [Box::new(EntryPointSplitExtension::new())]).unwrap();
+    let built = plan.shaders.iter().map(|shader| shader.mod_path.components.join("::")).sorted().collect::<Vec<_>>();
+    assert_eq!(built, ["single", "uber", "uber::cs_simulate", "uber::fs_main", "uber::vs_main"]);
+
+    let combined = read_to_string(out_dir.join("wesl/uber.wgsl")).unwrap();
+    assert!(["cs_simulate", "vs_main", "fs_main"].iter().all(|entry| combined.contains(entry)), "the combined shader keeps every entry point:\n{combined}");
+    let simulate = read_to_string(out_dir.join("wesl/uber/cs_simulate.wgsl")).unwrap();
+    assert!(simulate.contains("particles") && !simulate.contains("fs_main") && !simulate.contains("shade"), "{simulate}");
+    let fragment = read_to_string(out_dir.join("wesl/uber/fs_main.wgsl")).unwrap();
+    assert!(fragment.contains("shade") && !fragment.contains("particles") && !fragment.contains("simulate"), "{fragment}");
+
+    let plan = build_shader_dir_with_config(&config, &mut [Box::new(EntryPointSplitExtension::new().with_min_entry_points(4))]).unwrap();
+    assert_eq!(plan.shaders.len(), 2, "shaders with fewer entry points aren't split");
+}
+
+#[cfg(feature = "spirv_ingest_ext")]
+#[test]
+fn test_spirv_ingest_ext() {
+    use crate::extension::spirv_ingest::{SpirvIngestError, SpirvIngestExtension};
+
+    /// An empty compute shader, `main` with a workgroup size of 1
+    const COMPUTE_SPIRV: [u32; 35] = [
+        0x0723_0203, 0x0001_0000, 0, 5, 0,
+        0x0002_0011, 1,
+        0x0003_000E, 0, 1,
+        0x0005_000F, 5, 3, u32::from_le_bytes(*b"main"), 0,
+        0x0006_0010, 3, 17, 1, 1, 1,
+        0x0002_0013, 1,
+        0x0003_0021, 2, 1,
+        0x0005_0036, 1, 3, 0, 2,
+        0x0002_00F8, 4,
+        0x0001_00FD,
+        0x0001_0038,
+    ];
+
+    let tmp = tempfile::tempdir().unwrap();
+    let (shader_root, out_dir) = (tmp.path().join("shaders"), tmp.path().join("out"));
+    std::fs::create_dir_all(&shader_root).unwrap();
+    let spirv = COMPUTE_SPIRV.iter().flat_map(|word| word.to_le_bytes()).collect::<Vec<_>>();
+    std::fs::write(shader_root.join("clear.spv"), spirv).unwrap();
+    let config = WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(&out_dir).cargo_instructions(false);
+
+    let plan = build_shader_dir_with_config(&config, &mut [Box::new(SpirvIngestExtension::new())]).unwrap();
+    assert_eq!(plan.shaders.iter().map(|shader| shader.mod_path.components.join("::")).collect::<Vec<_>>(), ["clear_spv"]);
+    let clear = read_to_string(out_dir.join("wesl/clear_spv.wgsl")).unwrap();
+    assert!(clear.contains("@compute @workgroup_size(1, 1, 1)"), "{clear}");
+
+    std::fs::write(shader_root.join("broken.spv"), b"not spir-v").unwrap();
+    let error = build_shader_dir_with_config(&config, &mut [Box::new(SpirvIngestExtension::new())]).unwrap_err();
+    let WeslBuildError::ExtensionErr { error, .. } = error else { panic!("{error:?}") };
+    assert!(matches!(error.downcast_ref(), Some(SpirvIngestError::Parse { path, .. }) if path.ends_with("broken.spv")), "{error}");
+}
+
+#[cfg(feature = "hlsl_ingest_ext")]
+#[test]
+fn test_hlsl_ingest_stage() {
+    use crate::extension::hlsl_ingest::{HlslIngestError, HlslIngestExtension};
+
+    let tmp = tempfile::tempdir().unwrap();
+    let (shader_root, out_dir) = (tmp.path().join("shaders"), tmp.path().join("out"));
+    std::fs::create_dir_all(&shader_root).unwrap();
+    std::fs::write(shader_root.join("tonemap.hlsl"), "float4 main() : SV_Target { return 0; }").unwrap();
+    let config = WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(&out_dir).cargo_instructions(false);
+
+    // the stage is checked before DXC is loaded
+    let error = build_shader_dir_with_config(&config, &mut [Box::new(HlslIngestExtension::new())]).unwrap_err();
+    let WeslBuildError::ExtensionErr { error, .. } = error else { panic!("{error:?}") };
+    assert!(matches!(error.downcast_ref(), Some(HlslIngestError::UnknownStage { .. })), "{error}");
+}
+
+#[test]
+fn test_on_asset_hook() {
+    use std::{cell::RefCell, rc::Rc};
+
+    /// Records the file names of the assets
+    struct AssetObserver(Rc<RefCell<Vec<String>>>);
+
+    impl WeslBuildExtension<BuildResolver> for AssetObserver {
+        fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+            "AssetObserver".into()
+        }
+
+        fn init_root(&mut self, _shader_root_path: &str, _res: &mut Wesl<BuildResolver>) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+        fn enter_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+        fn exit_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+        fn post_build(&mut self, _wesl_path: &ModulePath, _wgsl_built_path: &str, _source_map: &Option<BasicSourceMap>) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+
+        fn on_asset(&mut self, asset_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+            self.0.borrow_mut().push(asset_path.file_name().unwrap().to_string_lossy().into_owned());
+            Ok(())
+        }
+    }
+
+    let tmp = tempfile::tempdir().unwrap();
+    std::fs::create_dir(tmp.path().join("shaders")).unwrap();
+    std::fs::write(tmp.path().join("shaders/blend.wgsl"), "fn b() -> f32 { return 1.0; }").unwrap();
+    std::fs::write(tmp.path().join("shaders/blend.json"), r#"{ "blend": "additive" }"#).unwrap();
+    std::fs::write(tmp.path().join("shaders/notes.txt"), "not an asset").unwrap();
+
+    let assets = Rc::new(RefCell::new(Vec::new()));
+    let config = WeslBuildConfig::new(tmp.path().join("shaders").to_str().unwrap())
+        .out_dir(tmp.path().join("out"))
+        .cargo_instructions(false)
+        .asset_extensions(["json"]);
+    build_shader_dir_with_config(&config, &mut [Box::new(AssetObserver(assets.clone()))]).unwrap();
+
+    assert_eq!(*assets.borrow(), ["blend.json"]);
+}
+
+#[cfg(all(feature = "wgpu_bindings_ext", feature = "wgsl_formatter_ext", feature = "dependency_graph_ext"))]
+#[test]
+fn test_config_from_cargo_toml() {
+    let tmp = tempfile::tempdir().unwrap();
+    let manifest_path = tmp.path().join("Cargo.toml");
+    fs::write(&manifest_path, r#"
+[package]
+name = "game_render" # the crate
+edition = "2024"
+
+[package.metadata.wesl_build]
+root = "shaders"
+extensions = [
+    "format",
+    "graph",
+]
+mangler = "hash"
+features = { f16 = true, debug = false }
+
+[package.metadata.wesl_build.bindings]
+out = "src/bindings"
+entry_points = true
+
+[dependencies]
+wesl = "0.3"
+"#).unwrap();
+
+    let (config, extensions) = WeslBuildConfig::from_cargo_toml(&manifest_path).unwrap();
+    assert_eq!(Path::new(&config.shader_path), tmp.path().join("shaders"));
+    assert!(matches!(config.mangler, wesl::ManglerKind::Hash));
+    assert!(matches!(config.compile_options.features.flags.get("f16"), Some(wesl::Feature::Enable)));
+    assert!(matches!(config.compile_options.features.flags.get("debug"), Some(wesl::Feature::Disable)));
+    assert!(config.rerun_if_changed.contains(&manifest_path));
+    assert_eq!(extensions.names(), ["WgpuBindingsExtension", "WgslFormatterExtension", "DependencyGraphExtension"]);
+
+    // without the section it is the default config
+    fs::write(&manifest_path, "[package]\nname = \"game_logic\"\n").unwrap();
+    let (config, extensions) = WeslBuildConfig::from_cargo_toml(&manifest_path).unwrap();
+    assert_eq!(Path::new(&config.shader_path), tmp.path().join("src/shaders"));
+    assert!(extensions.names().is_empty());
+
+    fs::write(&manifest_path, "[package]\nname = \"game_ui\"\n\n[package.metadata.wesl_build]\nshader_root = \"shaders\"\n").unwrap();
+    let error = WeslBuildConfig::from_cargo_toml(&manifest_path).unwrap_err();
+    assert!(matches!(&error, WeslBuildError::MetadataConfigErr { package, .. } if package == "game_ui"), "{error}");
+}
+
+#[cfg(all(feature = "workspace", feature = "wgsl_formatter_ext", feature = "dependency_graph_ext"))]
+#[test]
+fn test_workspace_shaders() {
+    use crate::workspace::workspace_shaders_of;
+
+    let metadata = serde_json::json!({
+        "packages": [
+            {
+                "name": "game_render",
+                "manifest_path": "/workspace/render/Cargo.toml",
+                "metadata": { "wesl_build": {
+                    "root": "shaders",
+                    "extensions": ["format", "graph"],
+                    "mangler": "hash",
+                    "features": { "f16": true },
+                },
+            } },
+            { "name": "game_logic", "manifest_path": "/workspace/logic/Cargo.toml", "metadata": null },
+            {
+                "name": "game_ui",
+                "manifest_path": "/workspace/ui/Cargo.toml",
+                "metadata": { "wesl_build": { "extensions": ["bindings-typo"] } },
+            },
+        ],
+    });
+    let members = workspace_shaders_of(&metadata).unwrap();
+    assert_eq!(members.iter().map(|member| member.package.as_str()).collect::<Vec<_>>(), ["game_render", "game_ui"]);
+
+    let out_dir = Path::new("/target/out");
+    let render = &members[0];
+    let config = render.config(out_dir).unwrap();
+    assert_eq!(Path::new(&config.shader_path), Path::new("/workspace/render/shaders"));
+    assert_eq!(config.out_dir.as_deref(), Some(Path::new("/target/out/game_render")));
+    assert!(matches!(config.mangler, wesl::ManglerKind::Hash));
+    assert!(matches!(config.compile_options.features.flags.get("f16"), Some(wesl::Feature::Enable)));
+    assert_eq!(render.extensions(out_dir).unwrap().names(), ["WgslFormatterExtension", "DependencyGraphExtension"]);
+
+    let ui = &members[1];
+    assert_eq!(ui.root().unwrap(), Path::new("/workspace/ui/src/shaders"));
+    let error = ui.extensions(out_dir).unwrap_err();
+    assert!(matches!(&error, WeslBuildError::MetadataConfigErr { package, .. } if package == "game_ui"), "{error}");
+}
+
+#[test]
+fn test_configure_hook() {
+    use std::{cell::RefCell, rc::Rc};
+
+    /// Enables lowering for `lowered` and records the options each module starts with
+    struct LowerOne(Rc<RefCell<Vec<(String, bool)>>>);
+
+    impl WeslBuildExtension<BuildResolver> for LowerOne {
+        fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+            "LowerOne".into()
+        }
+
+        fn init_root(&mut self, _shader_root_path: &str, _res: &mut Wesl<BuildResolver>) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+        fn enter_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+        fn exit_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+
+        fn configure(&mut self, wesl_path: &ModulePath, options: &mut wesl::CompileOptions) -> Result<(), Box<dyn std::error::Error>> {
+            let name = wesl_path.to_string();
+            self.0.borrow_mut().push((name.clone(), options.lower));
+            if name.ends_with("lowered") {
+                options.lower = true;
+            }
+            Ok(())
+        }
+
+        fn post_build(&mut self, _wesl_path: &ModulePath, _wgsl_built_path: &str, _source_map: &Option<BasicSourceMap>) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+    }
+
+    let tmp = tempfile::tempdir().unwrap();
+    let shader_root = tmp.path().join("shaders");
+    std::fs::create_dir_all(&shader_root).unwrap();
+    std::fs::write(shader_root.join("a_lowered.wgsl"), "fn a() {}").unwrap();
+    std::fs::write(shader_root.join("b_plain.wgsl"), "fn b() {}").unwrap();
+
+    let configured = Rc::new(RefCell::new(Vec::new()));
+    build_shader_dir_with_config(
+        &WeslBuildConfig::new(shader_root.to_str().unwrap())
+            .out_dir(tmp.path())
+            .compile_options(wesl::CompileOptions { lower: false, ..Default::default() }),
+        &mut [Box::new(LowerOne(configured.clone()))],
+    )
+    .unwrap();
+
+    let configured = configured.borrow();
+    assert_eq!(configured.len(), 2, "configure must run once per shader: {configured:?}");
+    assert!(
+        configured.iter().all(|(_, lower)| !lower),
+        "every shader must start from the config's options: {configured:?}"
+    );
+}
+
+#[test]
+fn test_new_shader() {
+    use crate::scaffold::{ShaderKind, new_shader};
+
+    let tmp = tempfile::tempdir().unwrap();
+    let shader_root = tmp.path().join("shaders");
+
+    let kinds = [
+        (ShaderKind::Compute, "particles/update"),
+        (ShaderKind::Vertex, "mesh::vertex"),
+        (ShaderKind::Fragment, "post/blur"),
+        (ShaderKind::Render, "sprite"),
+    ];
+    for (kind, module) in kinds {
+        let shader = new_shader(&shader_root, kind, module).unwrap();
+        assert_eq!(std::fs::read_to_string(&shader.path).unwrap(), kind.skeleton());
+    }
+
+    let shader = new_shader(&shader_root, ShaderKind::Compute, "particles/spawn").unwrap();
+    assert_eq!(shader.path, shader_root.join("particles/spawn.wesl"));
+    assert_eq!(shader.module, "particles::spawn");
+    assert!(shader.rust_snippet().contains("include_wesl!(particles::spawn)"));
+
+    let existing = new_shader(&shader_root, ShaderKind::Compute, "particles/spawn").unwrap_err();
+    assert!(matches!(existing, WeslBuildError::IoErr(err) if err.kind() == std::io::ErrorKind::AlreadyExists));
+    assert!(matches!(
+        new_shader(&shader_root, ShaderKind::Compute, "particles//spawn.v2"),
+        Err(WeslBuildError::InvalidFileName { .. })
+    ));
+
+    // every skeleton builds
+    let plan = build_shader_dir_with_config(
+        &WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(tmp.path()),
+        &mut [],
+    )
+    .unwrap();
+    assert_eq!(plan.shaders.len(), 5);
+}
+
+#[cfg(all(feature = "shader_registry_ext", feature = "dependency_graph_ext"))]
+#[test]
+fn test_check_mode() {
+    use crate::extension::{
+        BoxedExtension, dependency_graph::DependencyGraphExtension, shader_registry::ShaderRegistryExtension,
+    };
+
+    let tmp = tempfile::tempdir().unwrap();
+    let shader_root = tmp.path().join("shaders");
+    std::fs::create_dir_all(shader_root.join("nested")).unwrap();
+    std::fs::write(shader_root.join("a.wgsl"), "@fragment fn fs_main() -> @location(0) vec4<f32> { return vec4(1.0); }").unwrap();
+    std::fs::write(shader_root.join("nested/b.wgsl"), "fn b() {}").unwrap();
+    let out_dir = tmp.path().join("out");
+
+    let mut extensions: Vec<BoxedExtension> = vec![
+        Box::new(DependencyGraphExtension::new().with_output_dir(&out_dir)),
+        Box::new(ShaderRegistryExtension::new(out_dir.join("registry.rs"))),
+    ];
+    #[cfg(feature = "wgpu_bindings_ext")]
+    extensions.push(Box::new(
+        WgpuBindingsExtension::new(out_dir.join("bindings")).unwrap(),
+    ));
+    let plan = build_shader_dir_with_config(
+        &WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(&out_dir).check(true),
+        &mut extensions,
+    )
+    .unwrap();
+
+    assert_eq!(plan.shaders.len(), 2);
+    assert!(!out_dir.exists(), "a check must not write anything");
+    assert!(!is_check(), "the check must only last for the build");
+
+    std::fs::write(shader_root.join("broken.wgsl"), "fn broken( {}").unwrap();
+    let error = build_shader_dir_check(shader_root.to_str().unwrap(), wesl::CompileOptions::default(), &mut []).unwrap_err();
+    assert!(matches!(&error, WeslBuildError::Multiple(errors) if errors.len() == 1), "{error:?}");
+    assert!(!out_dir.exists(), "a failed check must not write anything");
+}
+
+/// The output path of the shader `module`, e.g. `nested::b`, in `plan`
+fn output_path(plan: &BuildPlan, module: &str) -> PathBuf {
+    plan.shaders.iter()
+        .find(|shader| shader.mod_path.components.join("::") == module)
+        .and_then(|shader| shader.output_path.clone())
+        .unwrap_or_else(|| panic!("`{module}` was not built"))
+}
+
+#[test]
+fn test_output_layout() {
+    let tmp = tempfile::tempdir().unwrap();
+    let shader_root = tmp.path().join("shaders");
+    std::fs::create_dir_all(shader_root.join("passes")).unwrap();
+    std::fs::write(shader_root.join("lights.wgsl"), "fn lights() {}").unwrap();
+    std::fs::write(shader_root.join("passes/blur.wgsl"), "fn blur() {}").unwrap();
+    let out_dir = tmp.path().join("out");
+    let config = WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(&out_dir).cargo_instructions(false);
+
+    let plan = build_shader_dir_with_config(&config, &mut []).unwrap();
+    assert_eq!(output_path(&plan, "passes::blur"), out_dir.join("wesl/passes/blur.wgsl"));
+    assert!(read_to_string(out_dir.join("wesl/passes/blur.wgsl")).unwrap().contains("fn blur()"));
+    assert_eq!(
+        read_to_string(out_dir.join(OUTPUT_DIR_NAME).join(OUTPUT_INDEX_NAME)).unwrap(),
+        "lights=lights.wgsl\npasses::blur=passes/blur.wgsl\n",
+    );
+    assert!(read_to_string(out_dir.join(BUILD_MANIFEST_NAME)).unwrap().contains("layout=tree\n"));
+
+    let flat_dir = tmp.path().join("flat");
+    let plan = build_shader_dir_with_config(&config.clone().out_dir(&flat_dir).flat_output(true), &mut []).unwrap();
+    let blur = plan.shaders.iter().find(|shader| shader.mod_path.components == ["passes", "blur"]).unwrap();
+    assert!(!blur.artifact_name.contains('/'), "{}", blur.artifact_name);
+    assert!(flat_dir.join(format!("{}.wgsl", blur.artifact_name)).is_file());
+    assert!(!flat_dir.join(OUTPUT_DIR_NAME).exists());
+    assert!(read_to_string(flat_dir.join(BUILD_MANIFEST_NAME)).unwrap().contains("layout=flat\n"));
+}
+
+#[test]
+fn test_artifact_name_hook() {
+    struct Rename {
+        name: fn(&ModulePath) -> Option<String>,
+        built: std::rc::Rc<std::cell::RefCell<Vec<String>>>,
+    }
+
+    impl WeslBuildExtension<BuildResolver> for Rename {
+        fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+            "Rename".into()
+        }
+
+        fn artifact_name(&self, wesl_path: &ModulePath) -> Option<String> {
+            (self.name)(wesl_path)
+        }
+
+        fn post_build(&mut self, _wesl_path: &ModulePath, wgsl_built_path: &str, _source_map: &Option<BasicSourceMap>) -> Result<(), Box<dyn std::error::Error>> {
+            self.built.borrow_mut().push(wgsl_built_path.replace('\\', "/"));
+            Ok(())
+        }
+    }
+
+    let tmp = tempfile::tempdir().unwrap();
+    let shader_root = tmp.path().join("shaders");
+    std::fs::create_dir_all(shader_root.join("passes")).unwrap();
+    std::fs::write(shader_root.join("lights.wgsl"), "fn lights() {}").unwrap();
+    std::fs::write(shader_root.join("passes/blur.wgsl"), "fn blur() {}").unwrap();
+    let out_dir = tmp.path().join("out");
+    let config = WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(&out_dir).cargo_instructions(false);
+
+    let rename = |name: fn(&ModulePath) -> Option<String>| -> Box<dyn WeslBuildExtension<BuildResolver>> { Box::new(Rename { name, built: Default::default() }) };
+
+    let built = std::rc::Rc::default();
+    let plan = build_shader_dir_with_config(&config, &mut [Box::new(Rename {
+        name: |mod_path| (mod_path.components.join("::") == "passes::blur").then(|| "assets/3f2a9c".to_owned()),
+        built: std::rc::Rc::clone(&built),
+    })])
+    .unwrap();
+    assert_eq!(output_path(&plan, "passes::blur"), out_dir.join("assets/3f2a9c.wgsl"));
+    assert!(read_to_string(out_dir.join("assets/3f2a9c.wgsl")).unwrap().contains("fn blur()"));
+    assert!(built.borrow().iter().any(|path| path.ends_with("assets/3f2a9c.wgsl")), "{built:?}");
+    assert_eq!(
+        read_to_string(out_dir.join(OUTPUT_DIR_NAME).join(OUTPUT_INDEX_NAME)).unwrap(),
+        "lights=lights.wgsl\npasses::blur=../assets/3f2a9c.wgsl\n",
+    );
+    let manifest = read_to_string(out_dir.join(BUILD_MANIFEST_NAME)).unwrap();
+    assert!(manifest.contains("artifact.passes::blur=assets/3f2a9c\n"), "{manifest}");
+    assert!(!manifest.contains("artifact.lights"), "{manifest}");
+
+    let result = build_shader_dir_with_config(&config, &mut [rename(|_| Some("../outside".to_owned()))]);
+    assert!(
+        matches!(&result, Err(WeslBuildError::InvalidArtifactName { name, .. }) if name == "../outside"),
+        "expected the name to be rejected, got {result:?}"
+    );
+
+    let result = build_shader_dir_with_config(&config, &mut [rename(|_| Some("assets/same".to_owned()))]);
+    assert!(
+        matches!(&result, Err(WeslBuildError::ArtifactCollision { name, .. }) if name == "assets/same"),
+        "expected the collision to be reported, got {result:?}"
+    );
+}
+
+#[cfg(feature = "dependency_graph_ext")]
+#[test]
+fn test_memory_sink() {
+    use crate::artifacts::MemorySink;
+    use crate::extension::dependency_graph::DependencyGraphExtension;
+
+    let tmp = tempfile::tempdir().unwrap();
+    let shader_root = tmp.path().join("shaders");
+    std::fs::create_dir_all(shader_root.join("nested")).unwrap();
+    std::fs::write(shader_root.join("a.wgsl"), "import package::nested::b::b; fn a() { b(); }").unwrap();
+    std::fs::write(shader_root.join("nested/b.wgsl"), "fn b() {}").unwrap();
+    let out_dir = tmp.path().join("out");
+
+    let sink = Arc::new(Mutex::new(MemorySink::new()));
+    let plan = build_shader_dir_with_config(
+        &WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(&out_dir).artifact_sink(sink.clone()),
+        &mut [Box::new(DependencyGraphExtension::new().with_output_dir(&out_dir))],
+    )
+    .unwrap();
+
+    let sink = sink.lock().unwrap();
+    assert!(sink.get_str(output_path(&plan, "a")).unwrap().contains("fn a()"));
+    assert!(sink.get(output_path(&plan, "nested::b")).is_some());
+    assert!(sink.get(out_dir.join(BUILD_MANIFEST_NAME)).is_some());
+    assert!(sink.iter().any(|(path, _)| path.extension().is_some_and(|ext| ext == "dot")), "extensions must write through the sink");
+    assert!(!out_dir.exists(), "nothing may be written to disk with a MemorySink");
+}
+
+#[test]
+fn test_packages() {
+    use crate::package::PackageSource;
+
+    let tmp = tempfile::tempdir().unwrap();
+    let (shader_root, util_root, out_dir) = (tmp.path().join("shaders"), tmp.path().join("util"), tmp.path().join("out"));
+    std::fs::create_dir_all(&shader_root).unwrap();
+    std::fs::create_dir_all(util_root.join("color")).unwrap();
+    std::fs::write(util_root.join("color/luma.wesl"), "fn luma(c: vec3<f32>) -> f32 { return dot(c, vec3(0.2126, 0.7152, 0.0722)); }").unwrap();
+    std::fs::write(shader_root.join("tonemap.wesl"), "
+        import util::color::luma::luma;
+        @fragment
+        fn fs_main() -> @location(0) vec4<f32> { return vec4(luma(vec3(1.0))); }
+    ").unwrap();
+
+    let config = WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(&out_dir).cargo_instructions(false);
+    let plan = build_shader_dir_with_config(&config.clone().add_package("util", util_root.as_path()), &mut []).unwrap();
+    assert!(read_to_string(output_path(&plan, "tonemap")).unwrap().contains("0.7152"), "the package was not linked");
+
+    let error = build_shader_dir_with_config(&config.clone().add_package("util", PackageSource::links("not_a_dependency")), &mut []).unwrap_err();
+    assert!(matches!(&error, WeslBuildError::MissingPackage { env, .. } if env == "DEP_NOT_A_DEPENDENCY_WESL_SHADERS"), "{error:?}");
+
+    let error = build_shader_dir_with_config(&config.add_package("super", util_root.as_path()), &mut []).unwrap_err();
+    assert!(matches!(error, WeslBuildError::InvalidPackageName { .. }), "{error:?}");
+}
+
+#[cfg(feature = "shader_package_ext")]
+#[test]
+fn test_shader_package_ext() {
+    use crate::extension::shader_package::ShaderPackageExtension;
+    use crate::package::PACKAGE_MANIFEST_NAME;
+
+    let tmp = tempfile::tempdir().unwrap();
+    let (lib_root, dist) = (tmp.path().join("lib"), tmp.path().join("dist"));
+    std::fs::create_dir_all(lib_root.join("color")).unwrap();
+    std::fs::write(lib_root.join("color/luma.wesl"), "fn luma(c: vec3<f32>) -> f32 { return dot(c, vec3(0.2126, 0.7152, 0.0722)); }").unwrap();
+
+    build_shader_dir_with_config(
+        &WeslBuildConfig::new(lib_root.to_str().unwrap()).out_dir(tmp.path().join("lib_out")).cargo_instructions(false),
+        &mut [Box::new(ShaderPackageExtension::new("util").with_version("1.2.3").with_output_dir(&dist))],
+    )
+    .unwrap();
+
+    let manifest = read_to_string(dist.join(PACKAGE_MANIFEST_NAME)).unwrap();
+    assert!(manifest.contains("name = \"util\"") && manifest.contains("version = \"1.2.3\""), "{manifest}");
+    assert!(manifest.contains("\"color::luma\""), "{manifest}");
+
+    // the package can be imported downstream
+    let app_root = tmp.path().join("app");
+    std::fs::create_dir_all(&app_root).unwrap();
+    std::fs::write(app_root.join("main.wesl"), "import util::color::luma::luma; fn main() -> f32 { return luma(vec3(1.0)); }").unwrap();
+    let plan = build_shader_dir_with_config(
+        &WeslBuildConfig::new(app_root.to_str().unwrap())
+            .out_dir(tmp.path().join("app_out"))
+            .cargo_instructions(false)
+            .add_package("util", dist.as_path()),
+        &mut [],
+    )
+    .unwrap();
+    assert!(read_to_string(output_path(&plan, "main")).unwrap().contains("0.7152"));
+}
+
+#[cfg(feature = "const_data_ext")]
+#[test]
+fn test_const_data_ext() {
+    use crate::extension::const_data::{ConstDataError, ConstDataExtension};
+
+    let tmp = tempfile::tempdir().unwrap();
+    let (shader_root, out_dir) = (tmp.path().join("shaders"), tmp.path().join("out"));
+    std::fs::create_dir_all(shader_root.join("post")).unwrap();
+    let lut = [0.5f32, 1.0, 2.25].iter().flat_map(|value| value.to_le_bytes()).collect::<Vec<_>>();
+    std::fs::write(shader_root.join("post/lut.bin"), lut).unwrap();
+    std::fs::write(shader_root.join("post/tonemap.wesl"), "
+        //! include_array(\"lut.bin\", f32)
+        @fragment
+        fn fs_main() -> @location(0) vec4<f32> { return vec4(LUT[2]); }
+    ").unwrap();
+
+    let config = WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(&out_dir).cargo_instructions(false);
+    let plan = build_shader_dir_with_config(&config, &mut [Box::new(ConstDataExtension::new())]).unwrap();
+    let built = read_to_string(output_path(&plan, "post::tonemap")).unwrap();
+    assert!(built.contains("array<f32, 3>(0.5, 1.0, 2.25)"), "{built}");
+
+    std::fs::write(shader_root.join("post/lut.bin"), [0u8; 3]).unwrap();
+    let error = build_shader_dir_with_config(&config, &mut [Box::new(ConstDataExtension::new())]).unwrap_err();
+    let WeslBuildError::ExtensionErr { error, .. } = error else { panic!("{error:?}") };
+    assert!(matches!(error.downcast_ref(), Some(ConstDataError::InvalidLength { len: 3, .. })), "{error}");
+}
+
+#[cfg(feature = "generated_shaders_ext")]
+#[test]
+fn test_generated_shaders_ext() {
+    use crate::extension::generated_shaders::GeneratedShaderExtension;
+
+    let tmp = tempfile::tempdir().unwrap();
+    let (shader_root, out_dir) = (tmp.path().join("shaders"), tmp.path().join("out"));
+    std::fs::create_dir_all(&shader_root).unwrap();
+    std::fs::write(shader_root.join("uses_generated.wesl"), "
+        import package::blur::blur_5::TAPS;
+        fn taps() -> u32 { return TAPS; }
+    ").unwrap();
+
+    let generated = GeneratedShaderExtension::new().with_permutations(
+        "blur/blur_{{TAPS}}",
+        "const TAPS: u32 = {{TAPS}}u; @compute @workgroup_size(TAPS) fn main() {}",
+        [[("TAPS", "5")], [("TAPS", "9")]],
+    );
+    let plan = build_shader_dir_with_config(
+        &WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(&out_dir).cargo_instructions(false),
+        &mut [Box::new(generated)],
+    )
+    .unwrap();
+
+    assert_eq!(plan.shaders.len(), 3);
+    assert!(read_to_string(output_path(&plan, "blur::blur_9")).unwrap().contains("9u"));
+    assert!(read_to_string(output_path(&plan, "uses_generated")).unwrap().contains("5u"), "the file can import a generated shader");
+    assert!(plan.shaders.iter().any(|shader| shader.source_path.as_os_str().is_empty()));
+
+    let invalid = GeneratedShaderExtension::new().with_module("blur/blur.5", "fn f() {}");
+    let error = build_shader_dir_with_config(&WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(&out_dir), &mut [Box::new(invalid)]).unwrap_err();
+    assert!(matches!(error, WeslBuildError::ExtensionErr { .. }), "{error:?}");
+}
+
+#[test]
+fn test_resolver_layers() {
+    use crate::resolver::{VirtualResolver, module_path};
+
+    /// Overrides the source of `kernel` in the shader dir
+    struct KernelFixture;
+
+    impl WeslBuildExtension<BuildResolver> for KernelFixture {
+        fn name<'n>(&self) -> std::borrow::Cow<'n, str> { "KernelFixture".into() }
+        fn init_root(&mut self, _shader_root_path: &str, res: &mut Wesl<BuildResolver>) -> Result<(), Box<dyn std::error::Error>> {
+            let mut fixture = VirtualResolver::new();
+            fixture.add_module(module_path("kernel")?, "const WEIGHT: f32 = 0.25;");
+            res.resolver().push_layer(fixture);
+            Ok(())
+        }
+        fn enter_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+        fn exit_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+        fn post_build(&mut self, _mod_path: &ModulePath, _wgsl_built_path: &str, _source_map: &Option<BasicSourceMap>) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+    }
+
+    let tmp = tempfile::tempdir().unwrap();
+    let (shader_root, out_dir) = (tmp.path().join("shaders"), tmp.path().join("out"));
+    std::fs::create_dir_all(&shader_root).unwrap();
+    std::fs::write(shader_root.join("kernel.wesl"), "const WEIGHT: f32 = 0.5;").unwrap();
+    std::fs::write(shader_root.join("blur.wesl"), "import package::kernel::WEIGHT; fn weight() -> f32 { return WEIGHT; }").unwrap();
+
+    let plan = build_shader_dir_with_config(
+        &WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(&out_dir).cargo_instructions(false),
+        &mut [Box::new(KernelFixture)],
+    )
+    .unwrap();
+
+    assert_eq!(plan.shaders.len(), 2, "layers must not add modules to build");
+    assert!(read_to_string(output_path(&plan, "blur")).unwrap().contains("0.25"), "the layer must override the shader dir");
+}
+
+#[test]
+fn test_lock_file() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (shader_root, out_dir) = (tmp.path().join("shaders"), tmp.path().join("out"));
+    std::fs::create_dir_all(&shader_root).unwrap();
+    std::fs::write(shader_root.join("a.wesl"), "import package::b::b; fn a() { b(); }").unwrap();
+    std::fs::write(shader_root.join("b.wesl"), "fn b() {}").unwrap();
+    let generated = tmp.path().join("bindings.rs");
+    std::fs::write(&generated, "pub const A: &str = \"a\";").unwrap();
+
+    let lock_file = tmp.path().join(LOCK_FILE_NAME);
+    let config = WeslBuildConfig::new(shader_root.to_str().unwrap())
+        .out_dir(&out_dir)
+        .cargo_instructions(false)
+        .lock_file(&lock_file)
+        .lock_generated(&generated);
+    build_shader_dir_with_config(&config, &mut []).unwrap();
+
+    let lock = crate::lock::BuildLock::parse(&read_to_string(&lock_file).unwrap());
+    assert_eq!(lock.get("wesl_build"), Some(env!("CARGO_PKG_VERSION")));
+    assert_eq!(lock.get("mangler"), Some("escape"));
+    assert!(lock.get("input shaders/b.wesl").is_some(), "{lock}");
+    assert!(lock.get("generated bindings.rs").is_some(), "{lock}");
+    assert_eq!(lock.iter().filter(|(key, _)| key.starts_with("output ")).count(), 2, "{lock}");
+
+    verify_shader_dir(&config, &mut []).unwrap();
+
+    std::fs::write(shader_root.join("b.wesl"), "fn b() { let x = 1; }").unwrap();
+    std::fs::write(&generated, "pub const A: &str = \"edited\";").unwrap();
+    let error = verify_shader_dir(&config, &mut []).unwrap_err();
+    let WeslBuildError::LockDrift { drift, .. } = &error else { panic!("{error:?}") };
+    assert!(drift.iter().any(|drift| drift.starts_with("`input shaders/b.wesl` changed")), "{drift:?}");
+    assert!(drift.iter().any(|drift| drift.starts_with("`generated bindings.rs` changed")), "{drift:?}");
+    assert_eq!(read_to_string(&lock_file).unwrap(), lock.to_string(), "a verify must not rewrite the lock");
+}
+
+#[test]
+fn test_platform_overrides() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (shader_root, out_dir) = (tmp.path().join("shaders"), tmp.path().join("out"));
+    std::fs::create_dir_all(shader_root.join("post")).unwrap();
+    std::fs::write(shader_root.join("blur.wesl"), "const TAPS: u32 = 5u;").unwrap();
+    std::fs::write(shader_root.join("blur.metal.wesl"), "const TAPS: u32 = 9u;").unwrap();
+    std::fs::write(shader_root.join("blur.webgpu.wesl"), "const TAPS: u32 = 3u;").unwrap();
+    std::fs::write(shader_root.join("post/tonemap.metal.wgsl"), "fn tonemap() {}").unwrap();
+    std::fs::write(shader_root.join("uses_blur.wesl"), "import package::blur::TAPS; fn taps() -> u32 { return TAPS; }").unwrap();
+
+    let config = WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(&out_dir).cargo_instructions(false);
+    let plan = build_shader_dir_with_config(&config.clone().platform("metal"), &mut []).unwrap();
+
+    assert_eq!(plan.shaders.len(), 3, "{plan:?}");
+    let blur = plan.shaders.iter().find(|shader| shader.mod_path.components == ["blur"]).unwrap();
+    assert!(blur.source_path.ends_with("blur.metal.wesl"));
+    assert!(read_to_string(output_path(&plan, "blur")).unwrap().contains("9u"));
+    assert!(read_to_string(output_path(&plan, "uses_blur")).unwrap().contains("9u"), "imports must get the override");
+    output_path(&plan, "post::tonemap");
+
+    let plan = build_shader_dir_with_config(&config.platform("vulkan"), &mut []).unwrap();
+    assert_eq!(plan.shaders.len(), 2, "overrides for other platforms must be ignored: {plan:?}");
+    assert!(read_to_string(output_path(&plan, "blur")).unwrap().contains("5u"));
+
+    assert_eq!(target_platform("wasm32-unknown-unknown"), Some("webgpu"));
+    assert_eq!(target_platform("aarch64-apple-darwin"), Some("metal"));
+    assert_eq!(target_platform("x86_64-pc-windows-msvc"), Some("dx12"));
+    assert_eq!(target_platform("x86_64-unknown-linux-gnu"), Some("vulkan"));
+    assert_eq!(target_platform("riscv32imc-unknown-none-elf"), None);
+}
+
+#[cfg(feature = "dependency_graph_ext")]
+#[test]
+fn test_timings() {
+    let tmp = tempfile::tempdir().unwrap();
+    let shader_root = tmp.path().join("shaders");
+    std::fs::create_dir_all(shader_root.join("nested")).unwrap();
+    std::fs::write(shader_root.join("a.wgsl"), "fn a() {}").unwrap();
+    std::fs::write(shader_root.join("nested/b.wgsl"), "fn b() {}").unwrap();
+
+    build_shader_dir_with_config(
+        &WeslBuildConfig::new(shader_root.to_str().unwrap())
+            .out_dir(tmp.path())
+            .timings(true)
+            .chrome_trace(true),
+        &mut [Box::new(crate::extension::dependency_graph::DependencyGraphExtension::new().with_output_dir(tmp.path()))],
+    )
+    .unwrap();
+
+    let table = std::fs::read_to_string(tmp.path().join(TIMINGS_FILE_NAME)).unwrap();
+    assert!(table.starts_with("total"), "{table}");
+    assert!(table.contains("compile (2 modules)"), "{table}");
+    assert!(table.contains("nested::b"), "{table}");
+    assert!(table.contains("DependencyGraphExtension"), "{table}");
+    assert!(table.contains("post_build (2 calls)"), "{table}");
+    assert!(table.contains("init_root (1 calls)"), "{table}");
+
+    let trace = std::fs::read_to_string(tmp.path().join(TIMINGS_TRACE_FILE_NAME)).unwrap();
+    assert!(trace.starts_with("{\"traceEvents\":["), "{trace}");
+    assert!(trace.contains("\"name\":\"DependencyGraphExtension::post_build\""), "{trace}");
+    assert_eq!(trace.matches("\"cat\":\"compile\"").count(), 2, "{trace}");
+}
+
+#[cfg(feature = "naga")]
+#[test]
+fn test_post_parse_hook() {
+    /// Renames every entry point to `renamed_{name}` when `rename` is set, otherwise only reads the module
+    struct RenameEntryPoints {
+        rename: bool,
+    }
+
+    impl WeslBuildExtension<BuildResolver> for RenameEntryPoints {
+        fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+            "RenameEntryPoints".into()
+        }
+
+        fn init_root(&mut self, _shader_root_path: &str, _res: &mut Wesl<BuildResolver>) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+        fn enter_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+        fn exit_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+
+        fn post_parse(&mut self, _wesl_path: &ModulePath, module: &mut crate::NagaModule) -> Result<(), Box<dyn std::error::Error>> {
+            assert_eq!(module.entry_points.len(), 1);
+            if self.rename {
+                for entry_point in &mut module.entry_points {
+                    entry_point.name = format!("renamed_{}", entry_point.name);
+                }
+            }
+            Ok(())
+        }
+
+        fn post_build(&mut self, _wesl_path: &ModulePath, _wgsl_built_path: &str, _source_map: &Option<BasicSourceMap>) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+    }
+
+    let tmp = tempfile::tempdir().unwrap();
+    let shader_root = tmp.path().join("shaders");
+    std::fs::create_dir_all(&shader_root).unwrap();
+    std::fs::write(shader_root.join("post_parse.wgsl"), "
+        // kept unless the module is written back
+        @compute @workgroup_size(1)
+        fn cs_main() {}
+    ").unwrap();
+    let config = WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(tmp.path());
+
+    let build = |rename| {
+        let plan = build_shader_dir_with_config(&config, &mut [Box::new(RenameEntryPoints { rename })]).unwrap();
+        read_to_string(plan.shaders[0].output_path.as_ref().unwrap()).unwrap()
+    };
+
+    let unmodified = build(false);
+    assert!(unmodified.contains("kept unless"), "an unmodified module must not be written back:\n{unmodified}");
+
+    let renamed = build(true);
+    assert!(renamed.contains("fn renamed_cs_main("), "the modified module was not written back:\n{renamed}");
+}
+
+#[test]
+fn test_extension_order() {
+    struct Ordered {
+        name: &'static str,
+        after: &'static [&'static str],
+        before: &'static [&'static str],
+    }
+
+    impl WeslBuildExtension<BuildResolver> for Ordered {
+        fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+            self.name.into()
+        }
+
+        fn must_run_after(&self) -> &[&str] {
+            self.after
+        }
+
+        fn must_run_before(&self) -> &[&str] {
+            self.before
+        }
+
+        fn init_root(&mut self, _shader_root_path: &str, _res: &mut Wesl<BuildResolver>) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+        fn enter_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+        fn exit_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+        fn post_build(&mut self, _wesl_path: &ModulePath, _wgsl_built_path: &str, _source_map: &Option<BasicSourceMap>) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+    }
+
+    let ext = |name, after, before| -> Box<dyn WeslBuildExtension<BuildResolver>> {
+        Box::new(Ordered { name, after, before })
+    };
+    let names = |extensions: &[Box<dyn WeslBuildExtension<BuildResolver>>]| extensions.iter()
+        .map(|ext| ext.name().into_owned())
+        .collect::<Vec<_>>();
+
+    let mut extensions = [
+        ext("minifier", &["bindings"], &[]),
+        ext("stats", &[], &[]),
+        ext("bindings", &[], &[]),
+        ext("lint", &[], &["stats"]),
+        ext("missing_dependency", &["not_used"], &[]),
+    ];
+    order::order_extensions(&mut extensions).unwrap();
+    assert_eq!(names(&extensions), ["bindings", "minifier", "lint", "stats", "missing_dependency"]);
+
+    let mut cycle = [ext("a", &["b"], &[]), ext("b", &["a"], &[]), ext("c", &[], &[])];
+    let result = order::order_extensions(&mut cycle);
+    assert!(
+        matches!(&result, Err(WeslBuildError::ExtensionOrderErr { extensions }) if *extensions == ["a", "b"]),
+        "expected the cycle to be reported, got {result:?}"
+    );
+}
+
+#[cfg(feature = "composite_ext")]
+#[test]
+fn test_extension_api_version() {
+    use crate::extension::compat::{ExtensionApiVersion, ExtensionCompat, WeslBuildExtensionV1};
+    use crate::extension::composite::CompositeExtension;
+
+    // only the name, every hook has a default
+    struct Versioned(ExtensionApiVersion);
+
+    impl WeslBuildExtension<BuildResolver> for Versioned {
+        fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+            "Versioned".into()
+        }
+
+        fn extension_api_version(&self) -> ExtensionApiVersion {
+            self.0
+        }
+    }
+
+    struct Counter {
+        built: std::rc::Rc<std::cell::Cell<usize>>,
+        exited: std::rc::Rc<std::cell::Cell<bool>>,
+    }
+
+    impl WeslBuildExtensionV1<BuildResolver> for Counter {
+        fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+            "Counter".into()
+        }
+
+        fn init_root(&mut self, _shader_root_path: &str, _res: &mut Wesl<BuildResolver>) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+        fn exit_root(&mut self, _shader_root_path: &str, _res: &Wesl<BuildResolver>) -> Result<(), Box<dyn std::error::Error>> {
+            self.exited.set(true);
+            Ok(())
+        }
+        fn enter_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+        fn exit_mod(&mut self, _dir_path: &Path) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+        fn post_build(&mut self, _wesl_path: &ModulePath, _wgsl_built_path: &str, _source_map: &Option<BasicSourceMap>) -> Result<(), Box<dyn std::error::Error>> {
+            self.built.set(self.built.get() + 1);
+            Ok(())
+        }
+    }
+
+    let tmp = tempfile::tempdir().unwrap();
+    std::fs::write(tmp.path().join("a.wesl"), "fn a() {}").unwrap();
+    std::fs::write(tmp.path().join("b.wesl"), "fn b() {}").unwrap();
+    let config = WeslBuildConfig::new(tmp.path().to_str().unwrap()).out_dir(tmp.path().join("out")).cargo_instructions(false);
+
+    let built = std::rc::Rc::new(std::cell::Cell::new(0));
+    let exited = std::rc::Rc::new(std::cell::Cell::new(false));
+    build_shader_dir_with_config(&config, &mut [
+        Box::new(Versioned(ExtensionApiVersion::CURRENT)) as Box<dyn WeslBuildExtension<BuildResolver>>,
+        Box::new(ExtensionCompat::new(Counter { built: built.clone(), exited: exited.clone() })),
+    ])
+    .unwrap();
+    assert_eq!(built.get(), 2);
+    assert!(exited.get(), "exit_root of a 1.0 extension must run");
+
+    let newer = ExtensionApiVersion::new(ExtensionApiVersion::CURRENT.major + 1, 0);
+    let result = build_shader_dir_with_config(&config, &mut [
+        Box::new(CompositeExtension::new("Bundle").with(Versioned(ExtensionApiVersion::V1_0)).with(Versioned(newer))),
+    ]);
+    assert!(
+        matches!(&result, Err(WeslBuildError::UnsupportedExtensionApi { extension_name, version })
+            if extension_name == "Bundle" && *version == newer),
+        "expected the newer API version to be rejected, got {result:?}"
+    );
+}
+
+#[test]
+fn test_standard_resolver_compat() {
+    use crate::extension::compat::StandardResolverCompat;
+
+    // written for the builds before the `BuildResolver`
+    struct Legacy {
+        built: std::rc::Rc<std::cell::Cell<usize>>,
+        roots: std::rc::Rc<std::cell::Cell<usize>>,
+    }
+
+    impl WeslBuildExtension<wesl::StandardResolver> for Legacy {
+        fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+            "Legacy".into()
+        }
+
+        fn init_root(&mut self, _shader_root_path: &str, _res: &mut Wesl<wesl::StandardResolver>) -> Result<(), Box<dyn std::error::Error>> {
+            self.roots.set(self.roots.get() + 1);
+            Ok(())
+        }
+
+        fn exit_root(&mut self, _shader_root_path: &str, _res: &Wesl<wesl::StandardResolver>) -> Result<(), Box<dyn std::error::Error>> {
+            self.roots.set(self.roots.get() + 1);
+            Ok(())
+        }
+
+        fn post_build(&mut self, _wesl_path: &ModulePath, _wgsl_built_path: &str, _source_map: &Option<BasicSourceMap>) -> Result<(), Box<dyn std::error::Error>> {
+            self.built.set(self.built.get() + 1);
+            Ok(())
+        }
+    }
+
+    let tmp = tempfile::tempdir().unwrap();
+    std::fs::write(tmp.path().join("a.wesl"), "fn a() {}").unwrap();
+    let config = WeslBuildConfig::new(tmp.path().to_str().unwrap()).out_dir(tmp.path().join("out")).cargo_instructions(false);
+
+    let built = std::rc::Rc::new(std::cell::Cell::new(0));
+    let roots = std::rc::Rc::new(std::cell::Cell::new(0));
+    build_shader_dir_with_config(&config, &mut [
+        Box::new(StandardResolverCompat::new(Legacy { built: built.clone(), roots: roots.clone() })) as Box<dyn WeslBuildExtension<BuildResolver>>,
+    ])
+    .unwrap();
+    assert_eq!(built.get(), 1);
+    assert_eq!(roots.get(), 2, "init_root and exit_root must run with a StandardResolver");
+}
+
+#[cfg(all(feature = "wgsl_formatter_ext", feature = "dependency_graph_ext"))]
+#[test]
+fn test_extensions_macro() {
+    use crate::extension::{
+        BoxedExtension, ExtensionSet, dependency_graph::DependencyGraphExtension, wgsl_formatter::WgslFormatterExtension,
+    };
+
+    let boxed: BoxedExtension = Box::new(DependencyGraphExtension::new());
+    let include_formatter = false;
+    let set: &mut ExtensionSet = extensions![
+        WgslFormatterExtension::new(),
+        boxed,
+        #[cfg(any())]
+        DependencyGraphExtension::new(),
+        if include_formatter => WgslFormatterExtension::new(),
+        if !include_formatter => DependencyGraphExtension::new().with_mermaid(true),
+    ];
+    assert_eq!(set.names(), ["WgslFormatterExtension", "DependencyGraphExtension", "DependencyGraphExtension"]);
+
+    let empty: &mut [BoxedExtension] = extensions![];
+    assert!(empty.is_empty());
+}
+
+#[cfg(all(feature = "wgsl_formatter_ext", feature = "dependency_graph_ext"))]
+#[test]
+fn test_extension_set_disable() {
+    use crate::extension::{
+        ExtensionSet, dependency_graph::DependencyGraphExtension, wgsl_formatter::WgslFormatterExtension,
+    };
+
+    let mut set = ExtensionSet::new()
+        .with(WgslFormatterExtension::new())
+        .with(DependencyGraphExtension::new())
+        .with(WgslFormatterExtension::new());
+    assert!(set.contains("WgslFormatterExtension"));
+
+    assert_eq!(set.remove("WgslFormatterExtension").len(), 2);
+    assert!(!set.contains("WgslFormatterExtension"));
+    assert!(set.remove("WgslFormatterExtension").is_empty());
+
+    set.insert(WgslFormatterExtension::new());
+    set.disable(" DependencyGraphExtension, ,NotAnExtension");
+    assert_eq!(set.names(), ["WgslFormatterExtension"]);
+}
+
+#[test]
+fn test_invalid_file_names() {
+    let tmp = tempfile::tempdir().unwrap();
+    let config = WeslBuildConfig::new(tmp.path().to_str().unwrap());
+    std::fs::write(tmp.path().join("name.with.dots.wgsl"), "fn a() {}").unwrap();
+
+    let result = build_shader_dir_with_config(&config, &mut []);
+    assert!(
+        matches!(&result, Err(WeslBuildError::InvalidFileName { path }) if path.ends_with("name.with.dots.wgsl")),
+        "expected an invalid file name error, got {result:?}"
+    );
+
+    std::fs::write(tmp.path().join("..wesl"), "fn b() {}").unwrap();
+    std::fs::write(tmp.path().join("valid.wgsl"), "fn c() {}").unwrap();
+    match build_shader_dir_with_config(&config.continue_on_error(true), &mut []) {
+        Err(WeslBuildError::Multiple(errors)) => {
+            assert_eq!(errors.len(), 2, "both invalid names must be reported: {errors:?}");
+            assert!(errors.iter().all(|error| matches!(error, WeslBuildError::InvalidFileName { .. })));
+        }
+        other => panic!("expected the invalid names to be collected, got {other:?}"),
+    }
+}
+
+#[test]
+fn test_module_collisions() {
+    let tmp = tempfile::tempdir().unwrap();
+    let config = WeslBuildConfig::new(tmp.path().to_str().unwrap()).out_dir(tmp.path().join("out")).cargo_instructions(false);
+    std::fs::write(tmp.path().join("foo.wgsl"), "fn a() {}").unwrap();
+    std::fs::write(tmp.path().join("foo.wesl"), "fn b() {}").unwrap();
+
+    let result = build_shader_dir_with_config(&config, &mut []);
+    assert!(
+        matches!(&result, Err(WeslBuildError::ModuleCollision { first, second })
+            if first.ends_with("foo.wesl") && second.ends_with("foo.wgsl")),
+        "expected a collision of the files, got {result:?}"
+    );
+    let result = build_shader_dir_with_config(&config.clone().dry_run(true), &mut []);
+    assert!(matches!(result, Err(WeslBuildError::ModuleCollision { .. })), "expected a dry run to collide too, got {result:?}");
+    // an excluded file is not built, so it can't collide
+    build_shader_dir_with_config(&config.clone().exclude(["foo.wgsl"]), &mut []).unwrap();
+
+    std::fs::remove_file(tmp.path().join("foo.wgsl")).unwrap();
+    std::fs::create_dir(tmp.path().join("foo")).unwrap();
+    std::fs::write(tmp.path().join("foo/bar.wesl"), "fn c() {}").unwrap();
+    let result = build_shader_dir_with_config(&config, &mut []);
+    assert!(
+        matches!(&result, Err(WeslBuildError::ModuleCollision { first, second })
+            if first.ends_with("foo.wesl") && *second == tmp.path().join("foo")),
+        "expected a collision of the file and the dir, got {result:?}"
+    );
+
+    std::fs::rename(tmp.path().join("foo.wesl"), tmp.path().join("foo_main.wesl")).unwrap();
+    build_shader_dir_with_config(&config, &mut []).unwrap();
+}
+
+// the files can't both exist on case-insensitive filesystems
+#[cfg(target_os = "linux")]
+#[test]
+fn test_case_collisions() {
+    use crate::messages::JsonOutput;
+
+    let tmp = tempfile::tempdir().unwrap();
+    let shader_root = tmp.path().join("shaders");
+    std::fs::create_dir_all(shader_root.join("Passes")).unwrap();
+    std::fs::create_dir_all(shader_root.join("passes")).unwrap();
+    std::fs::write(shader_root.join("Blur.wesl"), "fn a() {}").unwrap();
+    std::fs::write(shader_root.join("blur.wesl"), "fn b() {}").unwrap();
+    let config = WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(tmp.path().join("out")).cargo_instructions(false);
+
+    let result = build_shader_dir_with_config(&config, &mut []);
+    assert!(
+        matches!(&result, Err(WeslBuildError::CaseCollision { first, second })
+            if first.ends_with("Blur.wesl") && second.ends_with("blur.wesl")),
+        "expected a case collision, got {result:?}"
+    );
+    let result = build_shader_dir_with_config(&config.clone().dry_run(true), &mut []);
+    assert!(matches!(result, Err(WeslBuildError::CaseCollision { .. })), "expected a dry run to collide too, got {result:?}");
+
+    // dirs collide too, and each collision is reported once
+    std::fs::write(shader_root.join("Passes/a.wesl"), "fn c() {}").unwrap();
+    std::fs::write(shader_root.join("passes/b.wesl"), "fn d() {}").unwrap();
+    std::fs::write(shader_root.join("passes/c.wesl"), "fn e() {}").unwrap();
+    let json_path = tmp.path().join("diagnostics.json");
+    build_shader_dir_with_config(
+        &config.clone().case_collisions(CaseCollisions::Warn).json_diagnostics(JsonOutput::File(json_path.clone())),
+        &mut [],
+    )
+    .unwrap();
+    let json = read_to_string(&json_path).unwrap();
+    assert_eq!(json.lines().count(), 2, "{json}");
+    assert!(json.lines().all(|line| line.contains("differ only in case")), "{json}");
+
+    build_shader_dir_with_config(&config.case_collisions(CaseCollisions::Allow), &mut []).unwrap();
+}
+
+#[test]
+fn test_file_names() {
+    let tmp = tempfile::tempdir().unwrap();
+    let shader_root = tmp.path().join("shaders");
+    std::fs::create_dir_all(shader_root.join("post-fx")).unwrap();
+    std::fs::write(shader_root.join("post-fx/blur-pass.wesl"), "fn blur() {}").unwrap();
+    std::fs::write(shader_root.join("光.wesl"), "fn light() {}").unwrap();
+    std::fs::write(
+        shader_root.join("main.wesl"),
+        "import package::post_fx::blur_pass::blur;\n@compute @workgroup_size(1) fn main() { blur(); }",
+    )
+    .unwrap();
+    let out_dir = tmp.path().join("out");
+    let config = WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(&out_dir).cargo_instructions(false);
+
+    build_shader_dir_with_config(&config.clone().file_names(FileNames::Normalize), &mut []).unwrap();
+    assert!(out_dir.join("wesl/post_fx/blur_pass.wgsl").is_file());
+    assert!(out_dir.join("wesl/u5149.wgsl").is_file());
+    let index = read_to_string(out_dir.join("wesl/index.txt")).unwrap();
+    assert!(index.contains("post_fx::blur_pass="), "{index}");
+
+    let result = build_shader_dir_with_config(&config.file_names(FileNames::Reject), &mut []);
+    assert!(
+        matches!(&result, Err(WeslBuildError::InvalidFileName { path })
+            if path.ends_with("post-fx/blur-pass.wesl") || path.ends_with("光.wesl")),
+        "expected the file names to be rejected, got {result:?}"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_non_utf8_file_name() {
+    use std::os::unix::ffi::OsStrExt;
+
+    let tmp = tempfile::tempdir().unwrap();
+    let file_name = std::ffi::OsStr::from_bytes(b"bad_\xff_name.wgsl");
+    // some file systems (e.g. on macOS) reject non UTF-8 names
+    if std::fs::write(tmp.path().join(file_name), "fn a() {}").is_err() {
+        return;
+    }
+
+    let result = build_shader_dir_with_config(&WeslBuildConfig::new(tmp.path().to_str().unwrap()), &mut []);
+    assert!(
+        matches!(&result, Err(WeslBuildError::NonUtf8Path { path }) if path.file_name() == Some(file_name)),
+        "expected a non UTF-8 path error, got {result:?}"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_symlink_cycle() {
+    let tmp = tempfile::tempdir().unwrap();
+    let shader_root = tmp.path().join("shaders");
+    std::fs::create_dir_all(shader_root.join("sub")).unwrap();
+    std::fs::write(shader_root.join("sub/symlinked.wgsl"), "fn a() {}").unwrap();
+    std::os::unix::fs::symlink(&shader_root, shader_root.join("sub/loop")).unwrap();
+    let config = WeslBuildConfig::new(shader_root.to_str().unwrap())
+        .out_dir(tmp.path());
+
+    build_shader_dir_with_config(&config, &mut []).expect("symlinked dirs must be skipped by default");
+
+    let result = build_shader_dir_with_config(&config.follow_symlinks(true), &mut []);
+    assert!(
+        matches!(&result, Err(WeslBuildError::SymlinkCycle { path }) if path.ends_with("sub/loop")),
+        "expected the cycle to be detected, got {result:?}"
+    );
+}
+
+#[test]
+fn test_max_depth() {
+    let tmp = tempfile::tempdir().unwrap();
+    let shader_root = tmp.path().join("shaders");
+    std::fs::create_dir_all(shader_root.join("a/b")).unwrap();
+    std::fs::write(shader_root.join("a/b/deep.wgsl"), "fn a() {}").unwrap();
+    let config = WeslBuildConfig::new(shader_root.to_str().unwrap())
+        .out_dir(tmp.path());
+
+    build_shader_dir_with_config(&config.clone().max_depth(2), &mut []).unwrap();
+
+    let result = build_shader_dir_with_config(&config.max_depth(1), &mut []);
+    assert!(
+        matches!(&result, Err(WeslBuildError::MaxDepthExceeded { path, max_depth: 1 }) if path.ends_with("a/b")),
+        "expected the max depth to be exceeded, got {result:?}"
+    );
+}
+
+#[cfg(feature = "rich_diagnostics")]
+#[test]
+fn test_shader_diagnostic() {
+    use crate::diagnostics::ShaderDiagnostic;
+
+    let tmp = tempfile::tempdir().unwrap();
+    let source = "// the helper\nfn helper() -> f32 {\n    return 1.0;\n}\n";
+    std::fs::write(tmp.path().join("diag_source.wgsl"), source).unwrap();
+    let built = "fn helper() -> f32 {\n    return 1.0;\n}\n";
+    let value = built.find("1.0").unwrap();
+
+    let rendered = ShaderDiagnostic::new("wrong value", "built/diag_source.wgsl", built)
+        .with_label(value..value + 3, "this value")
+        .with_source_map(
+            None,
+            &ModulePath::new(wesl::syntax::PathOrigin::Absolute, vec!["diag_source".to_owned()]),
+            tmp.path(),
+        )
+        .render();
+
+    assert!(rendered.contains("error: wrong value"), "missing message:\n{rendered}");
+    assert!(rendered.contains("this value"), "missing built label:\n{rendered}");
+    assert!(rendered.contains("in `helper` declared here"), "missing source label:\n{rendered}");
+    assert!(
+        rendered.contains(&format!("{}:2:4", tmp.path().join("diag_source.wgsl").display())),
+        "the source file location is wrong:\n{rendered}"
+    );
+}
+
+#[test]
+fn test_module_graph() {
+    let tmp = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(tmp.path().join("util")).unwrap();
+    std::fs::write(tmp.path().join("util/math.wesl"), "fn square(x: f32) -> f32 { return x * x; }").unwrap();
+    std::fs::write(tmp.path().join("util/color.wesl"), "
+        import super::math::square;
+        fn luma(c: vec3<f32>) -> f32 { return square(dot(c, vec3(0.3, 0.6, 0.1))); }
+    ").unwrap();
+    std::fs::write(tmp.path().join("main.wesl"), "
+        // import package::unused;
+        import package::{util::{color::{luma}, math as m}};
+        fn main() -> f32 { return luma(vec3(1.0)); }
+    ").unwrap();
+
+    let graph = ModuleGraph::from_dir(tmp.path()).unwrap();
+    let path = |components: &[&str]| ModulePath::new(
+        wesl::syntax::PathOrigin::Absolute,
+        components.iter().map(|c| c.to_string()).collect::<Vec<_>>(),
+    );
+    let (main, color, math) = (path(&["main"]), path(&["util", "color"]), path(&["util", "math"]));
+
+    assert_eq!(graph.modules().count(), 3);
+    assert_eq!(graph.imports(&main), [&color, &math]);
+    assert_eq!(graph.imports(&color), [&math]);
+    assert_eq!(graph.importers(&math), [&main, &color]);
+    assert_eq!(graph.dependents(&math), [&main, &color]);
+    assert_eq!(graph.dependencies(&main), [&color, &math]);
+    assert_eq!(graph.source_path(&color), Some(tmp.path().join("util/color.wesl").as_path()));
+}
+
+#[cfg(feature = "dependency_graph_ext")]
+#[test]
+fn test_dependency_graph_ext() {
+    use crate::extension::dependency_graph::DependencyGraphExtension;
+
+    let tmp = tempfile::tempdir().unwrap();
+    let shader_root = tmp.path().join("shaders");
+    std::fs::create_dir_all(&shader_root).unwrap();
+    std::fs::write(shader_root.join("deps_lib.wesl"), "fn helper() -> f32 { return 1.0; }").unwrap();
+    std::fs::write(shader_root.join("deps_main.wesl"), "
+        import package::deps_lib::helper;
+        @compute @workgroup_size(1)
+        fn cs_main() { let x = helper(); }
+    ").unwrap();
 
     build_shader_dir(
-        "./test/src/shaders",
+        shader_root.to_str().unwrap(),
         wesl::CompileOptions::default(),
-        &mut [Box::new(crate::extension::wgsl_minifier::WgslMinifierExtension { release_only: false })],
+        &mut [Box::new(DependencyGraphExtension::new().with_output_dir(tmp.path()).with_mermaid(true))],
     )
     .unwrap();
 
-    // shaders
-    assert!(std::fs::exists("test/src/shaders/test.wgsl").unwrap(), "test_bindings_ext requires shaders/test.wgsl to exist");
-    assert!(std::fs::exists("test/src/shaders/test2.wgsl").unwrap(), "test_bindings_ext requires shaders/test2.wgsl to exist");
-    assert!(std::fs::exists("test/src/shaders/test_mod/test_mod_file.wgsl").unwrap(), "test_bindings_ext requires shaders/test_mod/test_mod_file.wgsl to exist");
+    let dot = read_to_string(tmp.path().join("shader_deps.dot")).unwrap();
+    assert!(dot.contains(r#""deps_main" -> "deps_lib";"#), "missing import edge:\n{dot}");
+    assert!(dot.contains("entry points: cs_main"), "missing entry point metadata:\n{dot}");
 
-    // todo test that output size is <= pre-minification, using extension to log before size and another for after size
+    let mermaid = read_to_string(tmp.path().join("shader_deps.mmd")).unwrap();
+    assert!(mermaid.contains("n1 --> n0"), "missing import edge:\n{mermaid}");
+}
+
+#[test]
+fn test_rerun_manifest_hash() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (shader_root, out_dir) = (tmp.path().join("shaders"), tmp.path().join("out"));
+    std::fs::create_dir_all(&shader_root).unwrap();
+    std::fs::create_dir_all(&out_dir).unwrap();
+    std::fs::write(shader_root.join("rerun_lib.wesl"), "fn helper() -> f32 { return 1.0; }").unwrap();
+    std::fs::write(shader_root.join("rerun_main.wesl"), "
+        import package::rerun_lib::helper;
+        fn main() -> f32 { return helper(); }
+    ").unwrap();
+    let config = WeslBuildConfig::new(shader_root.to_str().unwrap())
+        .out_dir(&out_dir)
+        .rerun_per_file(false);
+
+    let shaders_hash = || {
+        build_shader_dir_with_config(&config, &mut []).unwrap();
+        read_to_string(out_dir.join(BUILD_MANIFEST_NAME)).unwrap()
+            .lines()
+            .find_map(|line| line.strip_prefix("shaders_hash=").map(str::to_owned))
+            .expect("the manifest must have the shaders hash")
+    };
+
+    let plan = build_shader_dir_with_config(&config, &mut []).unwrap();
+    let main = plan.shaders.iter().find(|shader| shader.artifact_name.contains("rerun_main")).unwrap();
+    assert!(
+        main.dependencies.iter().any(|path| path.ends_with("rerun_lib.wesl")),
+        "the imported module must be a dependency: {:?}", main.dependencies
+    );
+
+    let before = shaders_hash();
+    assert_eq!(before, shaders_hash(), "the hash must be stable");
+    std::fs::write(shader_root.join("rerun_lib.wesl"), "fn helper() -> f32 { return 2.0; }").unwrap();
+    assert_ne!(before, shaders_hash(), "changing an imported module must change the hash");
 }
 
 #[test]
@@ -110,6 +3204,395 @@ fn test_build_shader_dir() {
     assert!(std::fs::exists("test/src/shaders/test.wgsl").unwrap(), "test_bindings_ext requires shaders/test.wgsl to exist");
     assert!(std::fs::exists("test/src/shaders/test2.wgsl").unwrap(), "test_bindings_ext requires shaders/test2.wgsl to exist");
     assert!(std::fs::exists("test/src/shaders/test_mod/test_mod_file.wgsl").unwrap(), "test_bindings_ext requires shaders/test_mod/test_mod_file.wgsl to exist");
+
+    // manifest
+    let manifest = read_to_string(Path::new(&std::env::var("OUT_DIR").unwrap()).join(BUILD_MANIFEST_NAME)).unwrap();
+    let root = manifest.lines().find_map(|line| line.strip_prefix("root=")).expect("manifest must contain the shader root");
+    assert_eq!(Path::new(root), fs::canonicalize("./test/src/shaders").unwrap());
+}
+
+#[cfg(feature = "shader_registry_ext")]
+#[test]
+fn test_shader_registry_ext() {
+    use crate::extension::{Visibility, shader_registry::ShaderRegistryExtension};
+
+    let tmp = tempfile::tempdir().unwrap();
+    let registry_path = tmp.path().join("shaders.rs");
+
+    build_shader_dir(
+        "./test/src/shaders",
+        wesl::CompileOptions::default(),
+        &mut [Box::new(
+            ShaderRegistryExtension::new(&registry_path)
+                .with_static_name("ALL_SHADERS")
+                .with_visibility(Visibility::Public),
+        )],
+    )
+    .unwrap();
+
+    let registry = read_to_string(&registry_path).unwrap();
+    assert!(registry.contains("pub static ALL_SHADERS: &[(&str, &str)] = &["), "registry static was not generated:\n{registry}");
+    for shader in ["\"test\"", "\"test2\"", "\"test_mod::test_mod_file\""] {
+        assert!(registry.contains(&format!("({shader}, include_str!(")), "{shader} is missing from the registry:\n{registry}");
+    }
+}
+
+#[cfg(feature = "bevy_asset_ext")]
+#[test]
+fn test_bevy_asset_ext() {
+    use crate::extension::bevy_assets::BevyAssetExtension;
+
+    let tmp = tempfile::tempdir().unwrap();
+    let assets_dir = tmp.path().join("assets/shaders");
+    let plugin_path = tmp.path().join("shaders.rs");
+
+    build_shader_dir_with_config(
+        &WeslBuildConfig::new("./test/src/shaders").out_dir(tmp.path().join("out")).cargo_instructions(false),
+        &mut [Box::new(BevyAssetExtension::new(&assets_dir).with_plugin(&plugin_path).with_plugin_name("ShadersPlugin"))],
+    )
+    .unwrap();
+
+    let asset = read_to_string(assets_dir.join("test_mod/test_mod_file.wgsl")).unwrap();
+    assert!(asset.starts_with("#define_import_path test_mod::test_mod_file\n"), "import path is missing:\n{asset}");
+
+    let plugin = read_to_string(&plugin_path).unwrap();
+    assert!(plugin.contains("impl Plugin for ShadersPlugin {"), "plugin was not generated:\n{plugin}");
+    assert!(plugin.contains("pub const TEST_MOD__TEST_MOD_FILE: Handle<Shader> = uuid_handle!(\""), "handle is missing:\n{plugin}");
+    assert!(plugin.contains("load_internal_asset!(app, TEST_MOD__TEST_MOD_FILE, "), "shader is not registered:\n{plugin}");
+
+    // the handles are stable across builds
+    build_shader_dir_with_config(
+        &WeslBuildConfig::new("./test/src/shaders").out_dir(tmp.path().join("out")).cargo_instructions(false),
+        &mut [Box::new(BevyAssetExtension::new(&assets_dir).with_plugin(&plugin_path).with_plugin_name("ShadersPlugin"))],
+    )
+    .unwrap();
+    assert_eq!(read_to_string(&plugin_path).unwrap(), plugin);
+}
+
+#[cfg(feature = "dead_code_report_ext")]
+#[test]
+fn test_dead_code_report_ext() {
+    use crate::extension::dead_code_report::DeadCodeReportExtension;
+
+    let tmp = tempfile::tempdir().unwrap();
+    let shader_root = tmp.path().join("shaders");
+    std::fs::create_dir_all(&shader_root).unwrap();
+    std::fs::write(shader_root.join("dead_code.wgsl"), "
+        // fn commented_out() {}
+        const used = 1;
+        const unused = 2;
+        struct Unused { a: f32 }
+        fn unused_fn() -> f32 { return 1.0; }
+
+        @compute @workgroup_size(used)
+        fn main() {}
+    ").unwrap();
+    let report_path = tmp.path().join("report.txt");
+
+    let result = build_shader_dir(
+        shader_root.to_str().unwrap(),
+        wesl::CompileOptions::default(),
+        &mut [Box::new(
+            DeadCodeReportExtension::new()
+                .with_report_path(&report_path)
+                .with_max_dead_decls(1),
+        )],
+    );
+    assert!(
+        matches!(result, Err(WeslBuildError::ExtensionErr { .. })),
+        "exceeding the dead code threshold must fail the build"
+    );
+
+    let report = read_to_string(&report_path).unwrap();
+    assert!(report.starts_with("3 dead declarations"), "wrong dead code count:\n{report}");
+    for dead in ["const unused", "struct Unused", "fn unused_fn"] {
+        assert!(report.contains(dead), "`{dead}` is missing from the report:\n{report}");
+    }
+    assert!(!report.contains("commented_out"), "comments must not be reported:\n{report}");
+
+    // a check writes no report, its error shows the dead declarations instead
+    std::fs::remove_file(&report_path).unwrap();
+    let error = build_shader_dir_check(
+        shader_root.to_str().unwrap(),
+        wesl::CompileOptions::default(),
+        &mut [Box::new(
+            DeadCodeReportExtension::new()
+                .with_report_path(&report_path)
+                .with_max_dead_decls(1),
+        )],
+    )
+    .unwrap_err()
+    .to_string();
+    assert!(!report_path.exists(), "a check must not write the report");
+    assert!(error.contains("fn unused_fn") && !error.contains("report.txt"), "{error}");
+}
+
+#[cfg(feature = "shader_licenses_ext")]
+#[test]
+fn test_shader_licenses() {
+    use crate::extension::shader_licenses::ShaderLicenseExtension;
+
+    let tmp = tempfile::tempdir().unwrap();
+    let shader_root = tmp.path().join("shaders");
+    std::fs::create_dir_all(shader_root.join("vendor")).unwrap();
+    std::fs::write(shader_root.join("vendor/noise.wesl"), "
+        // SPDX-License-Identifier: MIT
+        // Copyright (c) 2024 Noise Authors
+
+        fn noise(p: f32) -> f32 { return fract(sin(p) * 43758.5453); }
+    ").unwrap();
+    std::fs::write(shader_root.join("vendor/hash.wesl"), "
+        /*
+         * Copyright (c) 2023 Hash Authors
+         * Licensed under the Apache License, Version 2.0
+         */
+        fn hash(p: u32) -> u32 { return p * 747796405u; }
+    ").unwrap();
+    std::fs::write(shader_root.join("terrain.wesl"), "
+        // our own terrain shader
+        import package::vendor::noise::noise;
+
+        @compute @workgroup_size(1)
+        fn main() { let height = noise(1.0); }
+    ").unwrap();
+    std::fs::write(shader_root.join("plain.wesl"), "
+        @compute @workgroup_size(1)
+        fn main() {}
+    ").unwrap();
+    let out_dir = tmp.path().join("out");
+    let licenses_path = tmp.path().join("THIRD_PARTY_SHADERS.txt");
+    let config = WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(&out_dir).cargo_instructions(false);
+
+    let plan = build_shader_dir_with_config(
+        &config,
+        &mut [Box::new(ShaderLicenseExtension::new().with_output_path(&licenses_path).with_attribution(true))],
+    ).unwrap();
+
+    let licenses = read_to_string(&licenses_path).unwrap();
+    assert!(licenses.contains("vendor::noise\n"), "the noise module is missing:\n{licenses}");
+    assert!(licenses.contains("Copyright (c) 2024 Noise Authors"), "the noise header is missing:\n{licenses}");
+    assert!(licenses.contains("Licensed under the Apache License, Version 2.0"), "the block comment header is missing:\n{licenses}");
+    assert!(!licenses.contains("terrain"), "comments without a license marker aren't headers:\n{licenses}");
+
+    let built = |name: &str| {
+        let shader = plan.shaders.iter().find(|shader| shader.mod_path.components.last().is_some_and(|last| last == name)).unwrap();
+        read_to_string(shader.output_path.as_ref().unwrap()).unwrap()
+    };
+    let terrain = built("terrain");
+    assert!(
+        terrain.starts_with("// Contains third-party code: MIT, see THIRD_PARTY_SHADERS.txt\n"),
+        "shaders importing licensed modules are attributed:\n{terrain}"
+    );
+    let plain = built("plain");
+    assert!(!plain.contains("third-party"), "shaders without licensed modules aren't attributed:\n{plain}");
+}
+
+#[cfg(feature = "uber_shader_ext")]
+#[test]
+fn test_uber_shader() {
+    use crate::extension::uber_shader::{UberShaderError, UberShaderExtension};
+
+    let tmp = tempfile::tempdir().unwrap();
+    let shader_root = tmp.path().join("shaders");
+    std::fs::create_dir_all(shader_root.join("passes")).unwrap();
+    std::fs::write(shader_root.join("common.wesl"), "struct Light { color: vec3<f32>, intensity: f32 }").unwrap();
+    std::fs::write(shader_root.join("passes/blur.wesl"), "
+        import package::common::Light;
+        @group(0) @binding(0) var<uniform> blur_light: Light;
+        @compute @workgroup_size(8) fn blur_main() { let light = blur_light.intensity; }
+    ").unwrap();
+    std::fs::write(shader_root.join("passes/bloom.wesl"), "
+        import package::common::Light;
+        @group(0) @binding(1) var<uniform> bloom_light: Light;
+        @compute @workgroup_size(8) fn bloom_main() { let light = bloom_light.color; }
+    ").unwrap();
+    let out_dir = tmp.path().join("out");
+    let config = WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(&out_dir).cargo_instructions(false);
+
+    build_shader_dir_with_config(&config, &mut [Box::new(
+        UberShaderExtension::new("uber").with_module("passes::blur").with_module("passes::bloom"),
+    )]).unwrap();
+    let uber = read_to_string(out_dir.join("uber.wgsl")).unwrap();
+    assert!(uber.contains("fn blur_main") && uber.contains("fn bloom_main"), "every entry point is combined:\n{uber}");
+    assert_eq!(uber.matches("struct ").count(), 1, "the shared struct is kept once:\n{uber}");
+
+    std::fs::write(shader_root.join("passes/bloom.wesl"), "@compute @workgroup_size(1) fn blur_main() {}").unwrap();
+    let result = build_shader_dir_with_config(&config, &mut [Box::new(
+        UberShaderExtension::new("uber").with_module("passes::blur").with_module("passes::bloom"),
+    )]);
+    let Err(WeslBuildError::ExtensionErr { error, .. }) = result else { panic!("{result:?}") };
+    assert!(matches!(error.downcast_ref(), Some(UberShaderError::NameClash { name, .. }) if name == "blur_main"), "{error}");
+
+    let result = build_shader_dir_with_config(&config, &mut [Box::new(UberShaderExtension::new("uber").with_module("passes::missing"))]);
+    let Err(WeslBuildError::ExtensionErr { error, .. }) = result else { panic!("{result:?}") };
+    assert!(matches!(error.downcast_ref(), Some(UberShaderError::MissingModule { .. })), "{error}");
+}
+
+#[test]
+fn test_artifact_writer() {
+    use std::{cell::RefCell, rc::Rc};
+
+    use crate::artifacts::ArtifactWriteError;
+
+    /// Writes its report to a path relative to the output dir
+    struct Reporter(&'static str, Rc<RefCell<Option<Result<(), String>>>>);
+
+    impl WeslBuildExtension<BuildResolver> for Reporter {
+        fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+            "Reporter".into()
+        }
+
+        fn exit_root(&mut self, _shader_root_path: &str, _res: &Wesl<BuildResolver>) -> Result<(), Box<dyn std::error::Error>> {
+            let (writer, out_dir) = with_build_context(|ctx| (ctx.artifact_writer(), ctx.out_dir().unwrap())).unwrap();
+            let result = writer.write(out_dir.join(self.0), "report");
+            assert!(matches!(&result, Ok(()) | Err(ArtifactWriteError::OutsideRoots { .. })), "{result:?}");
+            *self.1.borrow_mut() = Some(result.map_err(|error| error.to_string()));
+            Ok(())
+        }
+    }
+
+    let tmp = tempfile::tempdir().unwrap();
+    let shader_root = tmp.path().join("shaders");
+    std::fs::create_dir_all(&shader_root).unwrap();
+    std::fs::write(shader_root.join("a.wesl"), "fn a() {}").unwrap();
+    let config = WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(tmp.path().join("out")).cargo_instructions(false);
+
+    let write = |config: &WeslBuildConfig, path: &'static str| {
+        let result = Rc::new(RefCell::new(None));
+        build_shader_dir_with_config(config, &mut [Box::new(Reporter(path, result.clone()))]).unwrap();
+        result.take().unwrap()
+    };
+
+    assert_eq!(write(&config, "reports/./a.txt"), Ok(()));
+    assert_eq!(read_to_string(tmp.path().join("out/reports/a.txt")).unwrap(), "report");
+    let escaped = write(&config, "../../escaped.txt").unwrap_err();
+    assert!(escaped.contains("is outside of the artifact roots"), "{escaped}");
+    assert!(!tmp.path().parent().unwrap().join("escaped.txt").exists());
+
+    assert_eq!(write(&config.clone().artifact_root(tmp.path().join("reports")), "../reports/b.txt"), Ok(()));
+    assert_eq!(write(&config.clone().sandbox_artifacts(false), "../c.txt"), Ok(()));
+    assert!(tmp.path().join("c.txt").is_file());
+}
+
+#[test]
+fn test_content_hashed_names() {
+    use crate::{HASHED_DIR_NAME, HASHED_JSON_NAME, HASHED_TABLE_NAME};
+
+    let tmp = tempfile::tempdir().unwrap();
+    let shader_root = tmp.path().join("shaders");
+    std::fs::create_dir_all(shader_root.join("passes")).unwrap();
+    std::fs::write(shader_root.join("passes/blur.wesl"), "@compute @workgroup_size(8) fn main() {}").unwrap();
+    std::fs::write(shader_root.join("passes/copy.wesl"), "@compute @workgroup_size(8) fn main() {}").unwrap();
+    std::fs::write(shader_root.join("tonemap.wesl"), "@compute @workgroup_size(1) fn main() {}").unwrap();
+    let out_dir = tmp.path().join("out");
+    let config = WeslBuildConfig::new(shader_root.to_str().unwrap())
+        .out_dir(&out_dir)
+        .cargo_instructions(false)
+        .content_hashed_names(true);
+
+    let plan = build_shader_dir_with_config(&config, &mut []).unwrap();
+    let hashed = |name: &str| plan.shaders.iter().find(|shader| shader.mod_path.components.last().is_some_and(|last| last == name)).unwrap();
+    let blur = hashed("blur");
+    assert!(blur.artifact_name.starts_with(&format!("{HASHED_DIR_NAME}/")), "not a hashed name: {}", blur.artifact_name);
+    assert_eq!(blur.artifact_name, hashed("copy").artifact_name, "shaders with the same contents share their file");
+    assert_ne!(blur.artifact_name, hashed("tonemap").artifact_name);
+    assert_eq!(
+        read_to_string(blur.output_path.as_ref().unwrap()).unwrap(),
+        read_to_string(out_dir.join("wesl/passes/blur.wgsl")).unwrap(),
+        "the hashed file is the built shader",
+    );
+
+    let file = format!("{}.wgsl", blur.artifact_name.trim_start_matches(&format!("{HASHED_DIR_NAME}/")));
+    let table = read_to_string(out_dir.join(HASHED_TABLE_NAME)).unwrap();
+    assert!(table.contains(&format!("(\"passes::blur\", \"{file}\")")), "the rust table lacks blur:\n{table}");
+    let json = read_to_string(out_dir.join(HASHED_DIR_NAME).join(HASHED_JSON_NAME)).unwrap();
+    assert!(json.contains(&format!("\"passes::blur\": \"{file}\"")), "the JSON table lacks blur:\n{json}");
+    let manifest = read_to_string(out_dir.join(BUILD_MANIFEST_NAME)).unwrap();
+    assert!(manifest.contains(&format!("artifact.passes::blur={}", blur.artifact_name)), "include_wesl! can't find blur:\n{manifest}");
+
+    // the hashes only change with the contents
+    let rebuilt = build_shader_dir_with_config(&config, &mut []).unwrap();
+    assert_eq!(plan.shaders.iter().map(|shader| &shader.artifact_name).collect::<Vec<_>>(), rebuilt.shaders.iter().map(|shader| &shader.artifact_name).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_target_configs() {
+    use std::{cell::RefCell, rc::Rc};
+
+    struct TargetObserver(&'static str, Rc<RefCell<Vec<(&'static str, Option<String>)>>>);
+
+    impl WeslBuildExtension<BuildResolver> for TargetObserver {
+        fn name<'n>(&self) -> std::borrow::Cow<'n, str> {
+            self.0.into()
+        }
+
+        fn on_graph(&mut self, _graph: &ModuleGraph) -> Result<(), Box<dyn std::error::Error>> {
+            let target = with_build_context(|ctx| ctx.is_wasm().then(|| ctx.target()).flatten()).expect("hooks run in a build");
+            self.1.borrow_mut().push((self.0, target));
+            Ok(())
+        }
+    }
+
+    let tmp = tempfile::tempdir().unwrap();
+    let shader_root = tmp.path().join("shaders");
+    std::fs::create_dir_all(&shader_root).unwrap();
+    std::fs::write(shader_root.join("blur.wesl"), "
+        @if(half) const TAPS: u32 = 4u;
+        @if(!half) const TAPS: u32 = 8u;
+        @compute @workgroup_size(1) fn main() { let taps = TAPS; }
+    ").unwrap();
+    let mut compile_options = wesl::CompileOptions::default();
+    compile_options.features.flags.insert("half".to_owned(), wesl::Feature::Enable);
+    let config = WeslBuildConfig::new(shader_root.to_str().unwrap())
+        .out_dir(tmp.path().join("out"))
+        .cargo_instructions(false)
+        .compile_options(compile_options)
+        .for_target(TargetConfig::new("wasm32-*").feature("half", false).disable_extension("NativeOnly"))
+        .for_target(TargetConfig::new("*-apple-*").disable_extension("Everywhere"));
+
+    let build = |target: &str| {
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let plan = build_shader_dir_with_config(&config.clone().target(target), &mut [
+            Box::new(TargetObserver("NativeOnly", seen.clone())) as Box<dyn WeslBuildExtension<BuildResolver>>,
+            Box::new(TargetObserver("Everywhere", seen.clone())),
+        ]).unwrap();
+        let built = read_to_string(plan.shaders[0].output_path.as_ref().unwrap()).unwrap();
+        (built, seen.take())
+    };
+
+    let (built, seen) = build("wasm32-unknown-unknown");
+    assert!(built.contains("8u") && !built.contains("4u"), "the wasm target disables `half`:\n{built}");
+    assert_eq!(seen, [("Everywhere", Some("wasm32-unknown-unknown".to_owned()))]);
+    assert_eq!(config.clone().target("wasm32-unknown-unknown").build_platform().as_deref(), Some("webgpu"));
+
+    let (built, seen) = build("x86_64-unknown-linux-gnu");
+    assert!(built.contains("4u") && !built.contains("8u"), "other targets keep `half`:\n{built}");
+    assert_eq!(seen, [("NativeOnly", None), ("Everywhere", None)]);
+}
+
+#[test]
+fn test_resolve_relative() {
+    use wesl::syntax::PathOrigin;
+
+    let base = ModulePath::new(PathOrigin::Absolute, vec!["post".to_owned(), "blur".to_owned()]);
+    let path = |origin, components: &[&str]| ModulePath::new(
+        origin,
+        components.iter().map(|c| c.to_string()).collect::<Vec<_>>(),
+    );
+
+    assert_eq!(
+        resolve_relative(&base, &path(PathOrigin::Relative(0), &["kernel"])),
+        Some(path(PathOrigin::Absolute, &["post", "blur", "kernel"])),
+    );
+    assert_eq!(
+        resolve_relative(&base, &path(PathOrigin::Relative(1), &["bloom"])),
+        Some(path(PathOrigin::Absolute, &["post", "bloom"])),
+    );
+    assert_eq!(
+        resolve_relative(&base, &path(PathOrigin::Absolute, &["test"])),
+        Some(path(PathOrigin::Absolute, &["test"])),
+    );
+    assert_eq!(resolve_relative(&base, &path(PathOrigin::Relative(3), &["test"])), None);
 }
 
 mod build_tests {
@@ -128,7 +3611,7 @@ mod build_tests {
     use crate::*;
     use crate::extension::WeslBuildExtension;
 
-    use wesl::{BasicSourceMap, ModulePath, StandardResolver, Wesl};
+    use wesl::{BasicSourceMap, ModulePath, Wesl};
 
     /// MockExtension records lifecycle calls into a shared Arc<Mutex<Vec<String>>>,
     /// so tests can both hand the extension to the build system and still inspect
@@ -176,7 +3659,7 @@ mod build_tests {
         }
     }
 
-    impl WeslBuildExtension<StandardResolver> for MockExtension {
+    impl WeslBuildExtension<BuildResolver> for MockExtension {
         fn name<'n>(&self) -> Cow<'n, str> {
             "MockExtension".into()
         }
@@ -184,7 +3667,7 @@ mod build_tests {
         fn init_root(
             &mut self,
             shader_root_path: &str,
-            _res: &mut Wesl<StandardResolver>,
+            _res: &mut Wesl<BuildResolver>,
         ) -> Result<(), Box<dyn Error>> {
             self.record(format!("init_root:{}", shader_root_path));
             if self.should_fail("init_root") {
@@ -196,7 +3679,7 @@ mod build_tests {
         fn exit_root(
             &mut self,
             shader_root_path: &str,
-            _res: &Wesl<StandardResolver>,
+            _res: &Wesl<BuildResolver>,
         ) -> Result<(), Box<dyn Error>> {
             self.record(format!("exit_root:{}", shader_root_path));
             if self.should_fail("exit_root") {
@@ -253,7 +3736,7 @@ mod build_tests {
         let (mut ext, calls) = MockExtension::new();
 
         // create a dummy Wesl instance (no compiling done here)
-        let mut wesl = Wesl::<StandardResolver>::new("shaders");
+        let mut wesl = crate::resolver::build_wesl(&WeslBuildConfig::new("shaders")).unwrap();
 
         ext.init_root("shaders", &mut wesl).unwrap();
         ext.enter_mod(Path::new("shaders/foo")).unwrap();
@@ -277,10 +3760,10 @@ mod build_tests {
         // another direct invocation test demonstrating the shared buffer API
         let (mut ext, calls) = MockExtension::new();
 
-        ext.init_root("root/path", &mut Wesl::new("root/path")).unwrap();
+        ext.init_root("root/path", &mut crate::resolver::build_wesl(&WeslBuildConfig::new("root/path")).unwrap()).unwrap();
         ext.enter_mod(Path::new("root/path/submod")).unwrap();
         ext.exit_mod(Path::new("root/path/submod")).unwrap();
-        ext.exit_root("root/path", &Wesl::new("root/path")).unwrap();
+        ext.exit_root("root/path", &crate::resolver::build_wesl(&WeslBuildConfig::new("root/path")).unwrap()).unwrap();
 
         let calls_vec = calls.lock().unwrap().clone();
         assert_eq!(calls_vec.len(), 4, "expected four lifecycle calls recorded");
@@ -329,4 +3812,143 @@ mod build_tests {
         let recorded = calls.lock().unwrap().clone();
         assert!(recorded.iter().any(|c| c.starts_with("init_root:")), "init_root should have been recorded");
     }
+
+    #[test]
+    fn extension_error_names_the_hook_and_module() {
+        let tmp = tempdir().unwrap();
+        let root = tmp.path().join("shaders");
+        fs::create_dir_all(root.join("blur")).unwrap();
+        fs::write(root.join("blur/gaussian.wgsl"), "@compute @workgroup_size(1) fn main() {}").unwrap();
+
+        let (ext, _calls) = MockExtension::new_failing("post_build");
+        let result = build_shader_dir_with_config(
+            &WeslBuildConfig::new(root.to_str().unwrap()).out_dir(tmp.path().join("out")).cargo_instructions(false),
+            &mut [Box::new(ext)],
+        );
+
+        match result {
+            Err(error @ WeslBuildError::ExtensionErr { .. }) => {
+                let WeslBuildError::ExtensionErr { hook, mod_path, .. } = &error else { unreachable!() };
+                assert_eq!(*hook, "post_build");
+                assert_eq!(mod_path.as_ref().map(|mod_path| mod_path.components.join("::")).as_deref(), Some("blur::gaussian"));
+                let message = error.to_string();
+                assert!(message.contains("in `post_build` of `"), "{message}");
+                assert!(message.contains("post_build failed"), "{message}");
+            }
+            other => panic!("expected ExtensionErr variant, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[cfg(feature = "composite_ext")]
+    #[test]
+    fn composite_extension_runs_children_in_order_and_names_errors() {
+        use crate::extension::{composite::CompositeExtension, extension_error};
+
+        let (first, first_calls) = MockExtension::new();
+        let (failing, failing_calls) = MockExtension::new_failing("enter_mod");
+        let mut bundle = CompositeExtension::new("Inner")
+            .with(first)
+            .with(failing);
+
+        let mut wesl = crate::resolver::build_wesl(&WeslBuildConfig::new("shaders")).unwrap();
+        bundle.init_root("shaders", &mut wesl).unwrap();
+        assert_eq!(*first_calls.lock().unwrap(), ["init_root:shaders"]);
+        assert_eq!(*failing_calls.lock().unwrap(), ["init_root:shaders"]);
+
+        let mut outer = CompositeExtension::new("Outer").with(bundle);
+        let error = outer.enter_mod(Path::new("shaders/foo")).unwrap_err();
+        match extension_error(&outer, "enter_mod", None, error) {
+            WeslBuildError::ExtensionErr { extension_name, hook, error, .. } => {
+                assert_eq!(extension_name, "Outer > Inner > MockExtension");
+                assert_eq!(hook, "enter_mod");
+                assert_eq!(error.to_string(), "enter_mod failed");
+            }
+            other => panic!("expected ExtensionErr variant, got {:?}", other),
+        }
+    }
+}
+
+#[cfg(feature = "size_history_ext")]
+#[test]
+fn test_size_history() {
+    use crate::extension::size_history::{HistoryFormat, ShaderSizeHistoryExtension, SizeHistory};
+
+    let tmp = tempfile::tempdir().unwrap();
+    let shader_root = tmp.path().join("shaders");
+    std::fs::create_dir_all(&shader_root).unwrap();
+    std::fs::write(shader_root.join("blur.wesl"), "@compute @workgroup_size(8) fn main() {}").unwrap();
+    let history_path = tmp.path().join("history/shader_sizes.csv");
+    let config = WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(tmp.path().join("out")).cargo_instructions(false);
+
+    build_shader_dir_with_config(&config, &mut [Box::new(ShaderSizeHistoryExtension::new(&history_path))]).unwrap();
+    std::fs::write(shader_root.join("blur.wesl"), "
+        @group(0) @binding(0) var<storage, read_write> data: array<f32>;
+        @compute @workgroup_size(8) fn main() { data[0] = data[1] * 2.0 + data[2]; }
+    ").unwrap();
+    build_shader_dir_with_config(&config, &mut [Box::new(ShaderSizeHistoryExtension::new(&history_path).with_warn_threshold(10.0))]).unwrap();
+
+    let csv = read_to_string(&history_path).unwrap();
+    assert!(csv.starts_with("build_ms,commit,module,bytes,compile_us\n"), "{csv}");
+    let history = SizeHistory::read(&history_path).unwrap();
+    assert_eq!(history.entries().len(), 2, "{csv}");
+    assert_eq!(history.last(1), &history.entries()[1..]);
+    assert!(history.entries().iter().all(|entry| entry.module == "blur"), "{csv}");
+    let delta = history.delta("blur").unwrap();
+    assert!(delta.bytes() > 0 && delta.percent() > 10.0, "{delta}");
+    assert!(delta.to_string().contains("changed by +"), "{delta}");
+    assert_eq!(history.delta("missing"), None);
+
+    let json_path = tmp.path().join("shader_sizes.txt");
+    let json = ShaderSizeHistoryExtension::new(&json_path).with_format(HistoryFormat::JsonLines);
+    build_shader_dir_with_config(&config, &mut [Box::new(json)]).unwrap();
+    assert!(read_to_string(&json_path).unwrap().starts_with("{\"build_ms\":"));
+}
+
+#[test]
+fn test_warn_artifact_larger_than() {
+    use crate::messages::JsonOutput;
+
+    let tmp = tempfile::tempdir().unwrap();
+    let shader_root = tmp.path().join("shaders");
+    std::fs::create_dir_all(&shader_root).unwrap();
+    std::fs::write(shader_root.join("small.wesl"), "fn main() {}").unwrap();
+    let body = (0..64).map(|i| format!("let x{i} = {i}.0;")).collect::<String>();
+    std::fs::write(shader_root.join("large.wesl"), format!("fn main() {{ {body} }}")).unwrap();
+    let diagnostics = tmp.path().join("diagnostics.json");
+    let config = WeslBuildConfig::new(shader_root.to_str().unwrap())
+        .out_dir(tmp.path().join("out"))
+        .cargo_instructions(false)
+        .json_diagnostics(JsonOutput::File(diagnostics.clone()))
+        .warn_artifact_larger_than(256);
+
+    build_shader_dir_with_config(&config, &mut []).unwrap();
+    let messages = read_to_string(&diagnostics).unwrap();
+    assert!(messages.contains("\"module\":\"large\""), "{messages}");
+    assert!(!messages.contains("\"module\":\"small\""), "{messages}");
+}
+
+#[cfg(feature = "gpu_tests")]
+#[test]
+fn test_gpu_smoke_test() {
+    use crate::extension::gpu_smoke_test::{GpuSmokeTestError, GpuSmokeTestExtension};
+
+    let tmp = tempfile::tempdir().unwrap();
+    let shader_root = tmp.path().join("shaders");
+    std::fs::create_dir_all(&shader_root).unwrap();
+    std::fs::write(shader_root.join("fill.wesl"), "
+        @group(0) @binding(0) var<storage, read_write> data: array<u32>;
+        @compute @workgroup_size(64) fn main(@builtin(global_invocation_id) id: vec3u) { data[id.x] = id.x; }
+    ").unwrap();
+    let config = WeslBuildConfig::new(shader_root.to_str().unwrap()).out_dir(tmp.path().join("out")).cargo_instructions(false);
+
+    // machines without a GPU or software adapter skip it
+    let smoke_test = || GpuSmokeTestExtension::new().with_pipelines(true).with_skip_if_no_adapter(true);
+    build_shader_dir_with_config(&config, &mut [Box::new(smoke_test())]).unwrap();
+
+    let result = build_shader_dir_with_config(&config, &mut [Box::new(GpuSmokeTestExtension::new().with_force_fallback_adapter(true))]);
+    if let Err(WeslBuildError::ExtensionErr { error, .. }) = &result {
+        assert!(matches!(error.downcast_ref(), Some(GpuSmokeTestError::NoAdapter)), "{error}");
+    } else {
+        result.unwrap();
+    }
 }
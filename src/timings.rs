@@ -0,0 +1,198 @@
+use std::{
+    cmp::Reverse,
+    fmt::Write as _,
+    time::{Duration, Instant},
+};
+
+use itertools::Itertools;
+use wesl::{ModulePath, Resolver};
+
+use crate::{WeslBuildConfig, WeslBuildError, artifacts, extension::WeslBuildExtension};
+
+/// The timing table written to the output dir with [`WeslBuildConfig::timings`]
+pub const TIMINGS_FILE_NAME: &str = "wesl_build_timings.txt";
+/// The chrome tracing JSON written to the output dir with [`WeslBuildConfig::chrome_trace`],
+/// open it in `chrome://tracing` or [Perfetto](https://ui.perfetto.dev)
+pub const TIMINGS_TRACE_FILE_NAME: &str = "wesl_build_timings.trace.json";
+
+/// A timed part of the build, a module compile or an extension hook
+struct Span {
+    /// `None` for a module compile
+    extension: Option<String>,
+    hook: &'static str,
+    module: Option<ModulePath>,
+    /// Since the start of the build
+    start: Duration,
+    duration: Duration,
+}
+
+/// Measures module compiles and extension hooks, does nothing unless [`WeslBuildConfig::timings`] or [`WeslBuildConfig::chrome_trace`] is set
+pub(crate) struct Timings {
+    start: Option<Instant>,
+    spans: Vec<Span>,
+}
+
+impl Timings {
+    pub(crate) fn of_config(config: &WeslBuildConfig) -> Self {
+        Self {
+            start: (config.timings || config.chrome_trace).then(Instant::now),
+            spans: Vec::new(),
+        }
+    }
+
+    /// Time compiling the module `mod_path`
+    pub(crate) fn compile<T>(&mut self, mod_path: &ModulePath, compile: impl FnOnce() -> T) -> T {
+        self.time(None, "compile", Some(mod_path), compile)
+    }
+
+    /// Time the `hook` of `ext`, `module` is the module the hook runs for if any
+    pub(crate) fn hook<R: Resolver, E: WeslBuildExtension<R> + ?Sized, T>(
+        &mut self,
+        ext: &mut E,
+        hook: &'static str,
+        module: Option<&ModulePath>,
+        run: impl FnOnce(&mut E) -> T,
+    ) -> T {
+        if self.start.is_none() {
+            return run(ext);
+        }
+        let name = ext.name().into_owned();
+        self.time(Some(name), hook, module, || run(ext))
+    }
+
+    fn time<T>(
+        &mut self,
+        extension: Option<String>,
+        hook: &'static str,
+        module: Option<&ModulePath>,
+        run: impl FnOnce() -> T,
+    ) -> T {
+        let Some(build_start) = self.start else {
+            return run();
+        };
+
+        let start = build_start.elapsed();
+        let result = run();
+        self.spans.push(Span {
+            extension,
+            hook,
+            module: module.cloned(),
+            start,
+            duration: build_start.elapsed() - start,
+        });
+        result
+    }
+
+    /// Write the timing table, and the chrome trace if enabled, to the output dir unless it's a check
+    pub(crate) fn report(&self, config: &WeslBuildConfig) -> Result<(), WeslBuildError> {
+        let Some(build_start) = self.start else {
+            return Ok(());
+        };
+        let table = self.table(build_start.elapsed());
+        #[cfg(feature = "logging")]
+        log::info!("build timings:\n{table}");
+
+        if config.check {
+            return Ok(());
+        }
+        let Some(out_dir) = config.output_dir() else {
+            #[cfg(feature = "logging")]
+            log::warn!("OUT_DIR is not set, so no build timings will be written");
+            return Ok(());
+        };
+        if config.timings {
+            artifacts::write(out_dir.join(TIMINGS_FILE_NAME), table)?;
+        }
+        if config.chrome_trace {
+            artifacts::write(out_dir.join(TIMINGS_TRACE_FILE_NAME), self.chrome_trace())?;
+        }
+
+        Ok(())
+    }
+
+    /// The total time, the compile time of each module and the time of each extension by hook, slowest first
+    fn table(&self, total: Duration) -> String {
+        let mut table = String::new();
+        writeln!(table, "{:<56} {:>12}", "total", millis(total)).expect("writing to a String can't fail");
+
+        let (compiles, hooks): (Vec<_>, Vec<_>) = self.spans.iter().partition(|span| span.extension.is_none());
+        writeln!(table, "{:<56} {:>12}", format!("compile ({} modules)", compiles.len()), millis(total_of(&compiles)))
+            .expect("writing to a String can't fail");
+        for span in compiles.iter().sorted_by_key(|span| Reverse(span.duration)) {
+            let module = span.module.as_ref().map(ToString::to_string).unwrap_or_default();
+            writeln!(table, "  {module:<54} {:>12}", millis(span.duration)).expect("writing to a String can't fail");
+        }
+
+        let by_extension = hooks.into_iter()
+            .into_group_map_by(|span| span.extension.as_deref().unwrap_or_default())
+            .into_iter()
+            .map(|(extension, spans)| (extension, total_of(&spans), spans))
+            .sorted_by_key(|(extension, total, _)| (Reverse(*total), *extension));
+        for (extension, total, spans) in by_extension {
+            writeln!(table, "{extension:<56} {:>12}", millis(total)).expect("writing to a String can't fail");
+
+            let by_hook = spans.into_iter()
+                .into_group_map_by(|span| span.hook)
+                .into_iter()
+                .map(|(hook, spans)| (hook, total_of(&spans), spans.len()))
+                .sorted_by_key(|(hook, total, _)| (Reverse(*total), *hook));
+            for (hook, total, calls) in by_hook {
+                writeln!(table, "  {:<54} {:>12}", format!("{hook} ({calls} calls)"), millis(total))
+                    .expect("writing to a String can't fail");
+            }
+        }
+
+        table
+    }
+
+    /// The spans as chrome tracing JSON, in the [trace event format](https://docs.google.com/document/d/1CvAClvFfyA5R-PhYUmn5OOQtYMH4h6I0nSsKchNAySU)
+    fn chrome_trace(&self) -> String {
+        let events = self.spans.iter().map(|span| {
+            let (name, category) = match &span.extension {
+                Some(extension) => (format!("{extension}::{}", span.hook), "extension"),
+                None => (span.hook.to_owned(), "compile"),
+            };
+            let args = span.module.as_ref()
+                .map(|module| format!(",\"args\":{{\"module\":{}}}", json_string(&module.to_string())))
+                .unwrap_or_default();
+
+            format!(
+                "{{\"name\":{},\"cat\":\"{category}\",\"ph\":\"X\",\"ts\":{},\"dur\":{},\"pid\":1,\"tid\":1{args}}}",
+                json_string(&name),
+                span.start.as_micros(),
+                span.duration.as_micros(),
+            )
+        });
+
+        format!("{{\"traceEvents\":[{}]}}\n", events.format(",\n"))
+    }
+}
+
+fn total_of(spans: &[&Span]) -> Duration {
+    spans.iter().map(|span| span.duration).sum()
+}
+
+fn millis(duration: Duration) -> String {
+    format!("{:.3}ms", duration.as_secs_f64() * 1000.0)
+}
+
+/// Quote and escape a JSON string
+fn json_string(str: &str) -> String {
+    let mut json = String::with_capacity(str.len() + 2);
+    json.push('"');
+    for char in str.chars() {
+        match char {
+            '"' | '\\' => {
+                json.push('\\');
+                json.push(char);
+            }
+            char if char.is_control() => {
+                write!(json, "\\u{:04x}", char as u32).expect("writing to a String can't fail");
+            }
+            char => json.push(char),
+        }
+    }
+    json.push('"');
+
+    json
+}
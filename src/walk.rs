@@ -0,0 +1,161 @@
+use std::{fs, path::Path};
+#[cfg(not(unix))]
+use std::path::PathBuf;
+
+use crate::names::FileNames;
+use crate::{WeslBuildConfig, WeslBuildError};
+
+/// Identifies a dir independent of the path it was reached by
+#[cfg(unix)]
+type DirId = (u64, u64);
+#[cfg(not(unix))]
+type DirId = PathBuf;
+
+#[cfg(unix)]
+fn dir_id(dir: &Path) -> Result<DirId, std::io::Error> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = fs::metadata(dir)?;
+    Ok((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn dir_id(dir: &Path) -> Result<DirId, std::io::Error> {
+    fs::canonicalize(dir)
+}
+
+/// The state of a walk through the shader tree,
+/// tracks the dirs from the root to the current one so symlinks can't send it into a loop
+#[derive(Debug, Clone)]
+pub(crate) struct DirWalk {
+    follow_symlinks: bool,
+    /// Selects the overrides of shaders, see [`ShaderFile`](`crate::platform::ShaderFile`)
+    platform: Option<String>,
+    max_depth: usize,
+    /// How file and dir names become module names
+    file_names: FileNames,
+    /// Globs of the files to walk, see [`WeslBuildConfig::include`]
+    include: Vec<String>,
+    /// Globs of the files to skip, see [`WeslBuildConfig::exclude`]
+    exclude: Vec<String>,
+    ancestors: Vec<DirId>,
+}
+
+impl Default for DirWalk {
+    fn default() -> Self {
+        let file_names = crate::with_build_context(|ctx| ctx.config().file_names).unwrap_or_default();
+        Self::new(false, WeslBuildConfig::DEFAULT_MAX_DEPTH, crate::current_platform(), file_names)
+    }
+}
+
+impl DirWalk {
+    pub(crate) fn new(follow_symlinks: bool, max_depth: usize, platform: Option<String>, file_names: FileNames) -> Self {
+        Self {
+            follow_symlinks,
+            platform,
+            max_depth,
+            file_names,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            ancestors: Vec::new(),
+        }
+    }
+
+    pub(crate) fn of_config(config: &WeslBuildConfig) -> Self {
+        Self {
+            include: config.include.clone(),
+            exclude: config.exclude.clone(),
+            ..Self::new(config.follow_symlinks, config.max_depth, config.build_platform(), config.file_names)
+        }
+    }
+
+    /// Whether the file at the `/` separated `relative_path` in the shader dir is built,
+    /// see [`WeslBuildConfig::builds_file`]
+    pub(crate) fn builds_file(&self, relative_path: &str) -> bool {
+        builds_file(&self.include, &self.exclude, relative_path)
+    }
+
+    /// The platform whose shader overrides are used
+    pub(crate) fn platform(&self) -> Option<&str> {
+        self.platform.as_deref()
+    }
+
+    /// How file and dir names become module names
+    pub(crate) fn file_names(&self) -> FileNames {
+        self.file_names
+    }
+
+    /// Whether `path` is a dir to walk into, symlinks to dirs are only walked when following symlinks
+    pub(crate) fn is_dir(&self, path: &Path) -> Result<bool, std::io::Error> {
+        let metadata = fs::symlink_metadata(path)?;
+        if metadata.is_symlink() {
+            // a broken symlink is not a dir
+            Ok(self.follow_symlinks && fs::metadata(path).is_ok_and(|metadata| metadata.is_dir()))
+        } else {
+            Ok(metadata.is_dir())
+        }
+    }
+
+    /// Enter `dir`, the root first, failing if it is too deep or already being walked
+    pub(crate) fn enter(&mut self, dir: &Path) -> Result<(), WeslBuildError> {
+        // the root is at depth 0
+        if self.ancestors.len() > self.max_depth {
+            return Err(WeslBuildError::MaxDepthExceeded { path: dir.to_owned(), max_depth: self.max_depth });
+        }
+
+        let id = dir_id(dir)?;
+        if self.ancestors.contains(&id) {
+            return Err(WeslBuildError::SymlinkCycle { path: dir.to_owned() });
+        }
+        self.ancestors.push(id);
+
+        Ok(())
+    }
+
+    /// Leave the dir entered last
+    pub(crate) fn exit(&mut self) {
+        self.ancestors.pop();
+    }
+}
+
+/// `path` relative to `root` with `/` separators whatever the platform's separator, as matched by [`glob_match`]
+pub(crate) fn relative_path(path: &Path, root: &Path) -> Result<String, std::path::StripPrefixError> {
+    Ok(path.strip_prefix(root)?.iter()
+        .map(|component| component.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/"))
+}
+
+/// Whether `path` matches one of the `include` globs, or there are none, and none of the `exclude` globs
+pub(crate) fn builds_file(include: &[String], exclude: &[String], path: &str) -> bool {
+    (include.is_empty() || include.iter().any(|glob| glob_match(glob, path)))
+        && !exclude.iter().any(|glob| glob_match(glob, path))
+}
+
+/// Whether the `/` or `\` separated `path` matches `glob`, see [`WeslBuildConfig::include`]
+pub(crate) fn glob_match(glob: &str, path: &str) -> bool {
+    let glob = glob.trim_start_matches("./").trim_start_matches(".\\").split(['/', '\\']).collect::<Vec<_>>();
+    let path = path.split(['/', '\\']).collect::<Vec<_>>();
+    match_components(&glob, &path)
+}
+
+fn match_components(glob: &[&str], path: &[&str]) -> bool {
+    match glob.split_first() {
+        None => path.is_empty(),
+        // any number of dirs, none included
+        Some((&"**", glob)) => (0..=path.len()).any(|skip| match_components(glob, &path[skip..])),
+        Some((component, glob)) => path.split_first().is_some_and(|(name, path)| {
+            match_name(&component.chars().collect::<Vec<_>>(), &name.chars().collect::<Vec<_>>())
+                && match_components(glob, path)
+        }),
+    }
+}
+
+fn match_name(glob: &[char], name: &[char]) -> bool {
+    match glob.split_first() {
+        None => name.is_empty(),
+        Some(('*', glob)) => (0..=name.len()).any(|skip| match_name(glob, &name[skip..])),
+        Some(('?', glob)) => !name.is_empty() && match_name(glob, &name[1..]),
+        Some((c, glob)) => name.first() == Some(c) && match_name(glob, &name[1..]),
+    }
+}
@@ -0,0 +1,139 @@
+//! Build the shaders of every crate of a cargo workspace, configured in their `Cargo.toml` instead of a `build.rs` each
+//!
+//! A member declares its shaders in a `[package.metadata.wesl_build]` section, with the keys of
+//! [`WeslBuildConfig::from_cargo_metadata`]. One build script of the workspace, usually that of the crate that embeds the shaders, calls [`build_workspace_shaders`].
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use serde_json::Value;
+
+use crate::{WeslBuildConfig, WeslBuildError, build_shader_dir_with_config};
+use crate::extension::ExtensionSet;
+use crate::metadata::{MetadataSection, MetadataTable, MetadataValue};
+
+/// The key of the section in `[package.metadata]`
+pub const METADATA_KEY: &str = "wesl_build";
+
+/// The shaders of a workspace member, from its `[package.metadata.wesl_build]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkspaceShaders {
+    /// The name of the package
+    pub package: String,
+    /// The dir of its `Cargo.toml`
+    pub package_dir: PathBuf,
+    /// The section, a JSON object
+    pub metadata: Value,
+}
+
+impl WorkspaceShaders {
+    /// The section as a table
+    fn table(&self) -> Result<MetadataTable, WeslBuildError> {
+        match MetadataValue::from_json(&self.metadata) {
+            Ok(MetadataValue::Table(table)) => Ok(table),
+            Ok(_) => Err(self.error("it must be a table".to_owned())),
+            Err(message) => Err(self.error(message)),
+        }
+    }
+
+    fn error(&self, message: String) -> WeslBuildError {
+        WeslBuildError::MetadataConfigErr { package: self.package.clone(), message }
+    }
+
+    /// The root dir of the shaders, `root` or `src/shaders` in the package dir
+    pub fn root(&self) -> Result<PathBuf, WeslBuildError> {
+        Ok(PathBuf::from(self.config(Path::new(""))?.shader_path))
+    }
+
+    /// The config of the build, the shaders are built to `<out_dir>/<package>`
+    pub fn config(&self, out_dir: &Path) -> Result<WeslBuildConfig, WeslBuildError> {
+        let table = self.table()?;
+        let section = MetadataSection { package: &self.package, package_dir: &self.package_dir, table: &table };
+        Ok(section.config()?.out_dir(out_dir.join(&self.package)))
+    }
+
+    /// The built-in extensions of the section, in order, the files of extensions go to `out_dir`
+    pub fn extensions(&self, out_dir: &Path) -> Result<ExtensionSet, WeslBuildError> {
+        let table = self.table()?;
+        MetadataSection { package: &self.package, package_dir: &self.package_dir, table: &table }.extensions(Some(out_dir))
+    }
+}
+
+/// The workspace members with a `[package.metadata.wesl_build]` section, from `cargo metadata`
+///
+/// Run from a build script it uses the cargo of the build and reads the workspace of the crate.
+pub fn workspace_shaders() -> Result<Vec<WorkspaceShaders>, WeslBuildError> {
+    let mut cargo = Command::new(std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into()));
+    cargo.args(["metadata", "--format-version", "1", "--no-deps"]);
+    if let Some(manifest_dir) = std::env::var_os("CARGO_MANIFEST_DIR") {
+        cargo.current_dir(manifest_dir);
+    }
+    let output = cargo.output()?;
+    if !output.status.success() {
+        return Err(WeslBuildError::CargoMetadataErr(String::from_utf8_lossy(&output.stderr).trim().to_owned()));
+    }
+
+    let metadata = serde_json::from_slice::<Value>(&output.stdout)
+        .map_err(|error| WeslBuildError::CargoMetadataErr(error.to_string()))?;
+    workspace_shaders_of(&metadata)
+}
+
+/// The members with a [`METADATA_KEY`] section in the JSON of `cargo metadata --no-deps`
+pub fn workspace_shaders_of(metadata: &Value) -> Result<Vec<WorkspaceShaders>, WeslBuildError> {
+    let packages = metadata.get("packages").and_then(Value::as_array)
+        .ok_or_else(|| WeslBuildError::CargoMetadataErr("no `packages` in the output".to_owned()))?;
+
+    let mut members = Vec::new();
+    for package in packages {
+        let Some(section) = package.get("metadata").and_then(|metadata| metadata.get(METADATA_KEY)) else {
+            continue;
+        };
+        let name = package.get("name").and_then(Value::as_str).unwrap_or_default().to_owned();
+        let manifest_path = package.get("manifest_path").and_then(Value::as_str)
+            .ok_or_else(|| WeslBuildError::CargoMetadataErr(format!("`{name}` has no `manifest_path`")))?;
+        if !section.is_object() {
+            return Err(WeslBuildError::MetadataConfigErr { package: name, message: "it must be a table".to_owned() });
+        }
+
+        members.push(WorkspaceShaders {
+            package: name,
+            package_dir: Path::new(manifest_path).parent().map(Path::to_owned).unwrap_or_default(),
+            metadata: section.clone(),
+        });
+    }
+
+    Ok(members)
+}
+
+/// Build the shaders of every workspace member with a `[package.metadata.wesl_build]` section, see the [module docs](self)
+///
+/// The shaders of each member are built to `OUT_DIR/<package>`, with the extensions it names.
+/// Returns the members that were built.
+///
+/// ## Example
+/// In `build.rs`:
+/// ```no_run
+/// fn main() {
+///     wesl_build::workspace::build_workspace_shaders().expect("failed to build the shaders of the workspace");
+/// }
+/// ```
+pub fn build_workspace_shaders() -> Result<Vec<WorkspaceShaders>, WeslBuildError> {
+    let out_dir = PathBuf::from(std::env::var_os("OUT_DIR").ok_or(WeslBuildError::MissingOutDir)?);
+    let members = workspace_shaders()?;
+
+    for member in &members {
+        let config = member.config(&out_dir)?;
+        let mut extensions = member.extensions(&out_dir.join(&member.package))?;
+        extensions.disable_from_env();
+
+        #[cfg(feature = "logging")]
+        log::info!("building the shaders of `{}` in {}", member.package, config.shader_path);
+
+        build_shader_dir_with_config(&config, &mut extensions)
+            .map_err(|error| WeslBuildError::WorkspaceMemberErr { package: member.package.clone(), error: Box::new(error) })?;
+    }
+
+    Ok(members)
+}
@@ -1,9 +1,9 @@
-use wesl_build::{WeslBuildError, build_shader_dir, extensions};
+use wesl_build::prelude::*;
 
 fn main() -> Result<(), WeslBuildError> {
     build_shader_dir(
         "../test/src/shaders",
-        wesl::CompileOptions::default(),
+        CompileOptions::default(),
         extensions![],
     )
 }
@@ -4,6 +4,7 @@ use quote::{ToTokens, quote};
 use syn::{Path, parse::{Parse, ParseStream}, parse_macro_input, spanned::Spanned};
 use wesl::{Mangler, Resolver};
 use proc_macro_error2::{OptionExt, ResultExt, abort};
+use wesl_build_names::normalize_name;
 
 struct ShaderPath {
     // used for validation
@@ -19,7 +20,11 @@ impl Parse for ShaderPath {
 // todo use trybuild to test errors, see: https://docs.rs/trybuild/latest/trybuild/index.html
 /// Include a WGSL file compiled with `wesl_build` as a string.
 ///
-/// The argument corresponds to the shaders path from your shader root dir
+/// The argument corresponds to the shaders path from your shader root dir.
+///
+/// Paths starting with `self::` or `super::` are resolved relative to the rust module invoking the macro,
+/// so `include_wesl!(self::blur)` in `src/render/mod.rs` includes the shader `render::blur`
+/// and `include_wesl!(super::blur)` in `src/render/post/mod.rs` does too.
 ///
 /// ## Example
 /// ```
@@ -27,6 +32,8 @@ impl Parse for ShaderPath {
 ///
 /// // ok
 /// include_wesl!(test_mod::test_mod_file);
+/// // ok if called from `src/test_mod.rs` or `src/test_mod/mod.rs`
+/// include_wesl!(self::test_mod_file);
 /// // err: path to module is already based on root(package)
 /// include_wesl!(package::test_mod::test_mod_file);
 /// // err: module not a shader
@@ -37,14 +44,14 @@ impl Parse for ShaderPath {
 #[proc_macro_error2::proc_macro_error]
 #[proc_macro]
 pub fn include_wesl(shader_path: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    let path_str = shader_path.to_string();
     // validate
     let shader_path = parse_macro_input!(shader_path as ShaderPath);
-    let Some(path_first) = shader_path.path.segments.first() else {
+    let segments = &shader_path.path.segments;
+    let Some(path_first) = segments.first() else {
         proc_macro_error2::abort_call_site!("the shader import path must be non-empty");
     };
 
-    let path_last = shader_path.path.segments.last().expect("checked above");
+    let path_last = segments.last().expect("checked above");
 
     // error if `package` path prefix is present
     if path_first.ident == "package" {
@@ -54,23 +61,73 @@ pub fn include_wesl(shader_path: proc_macro::TokenStream) -> proc_macro::TokenSt
         );
     }
 
+    // `self` and `super` prefixes make the path relative to the invoking rust module
+    let relative_prefix_len = segments.iter()
+        .take_while(|seg| seg.ident == "self" || seg.ident == "super")
+        .count();
+    if relative_prefix_len == segments.len() {
+        abort!(path_last.ident.span(), "the shader import path must end in a shader name");
+    }
+    if let Some(misplaced) = segments.iter().skip(relative_prefix_len)
+        .find(|seg| seg.ident == "self" || seg.ident == "super")
+    {
+        abort!(misplaced.ident.span(), "`{}` is only allowed at the start of the path", misplaced.ident);
+    }
+
+    let base_components = if relative_prefix_len == 0 {
+        Vec::new()
+    } else {
+        let caller_components = caller_module_components().unwrap_or_else(|err| {
+            abort!(path_first.ident.span(), "could not find the rust module invoking `include_wesl!`: {}", err;
+                help = "use a path from your shader root dir instead")
+        });
+        let supers = segments.iter().take(relative_prefix_len)
+            .filter(|seg| seg.ident == "super")
+            .count();
+
+        resolve_relative(&caller_components, supers).unwrap_or_else(|| {
+            abort!(segments[relative_prefix_len - 1].ident.span(),
+                "`super` goes above the shader root dir";
+                note = "the invoking rust module is `{}`", caller_components.join("::")
+            )
+        })
+    };
+    let base_len = base_components.len();
+
     let mod_path = wesl::ModulePath::new(
         wesl::syntax::PathOrigin::Absolute,
-        path_str
-            .split("::")
-            .map(|str| str.to_owned())
+        base_components.into_iter()
+            .chain(segments.iter().skip(relative_prefix_len).map(|seg| seg.ident.to_string()))
             .collect::<Vec<_>>(),
     );
     let path_last_name = &path_last.into_token_stream().to_string();
+    // the span of the segment that produced a component, components from `self`/`super` use the last of those
+    let component_span = |component_idx: usize| if component_idx >= base_len {
+        segments[component_idx - base_len + relative_prefix_len].ident.span()
+    } else {
+        segments[relative_prefix_len - 1].ident.span()
+    };
 
     // validate file exists and 
     {
-        // use shader_root dir from WESL_BUILD_DIR_ROOT_PATH to find shader_path
+        // use shader_root dir to find shader_path
         // use span of part of path with error
-        let mut shader_dir: PathBuf = std::env::var_os("WESL_BUILD_DIR_ROOT_PATH")
-            .expect_or_abort("`wesl_build::build_shader_dir` must be run first, to set the WESL_BUILD_DIR_ROOT_PATH environment variable")
-            .into();
-        shader_dir.extend(&mod_path.components);
+        let mut shader_dir = shader_root_dir()
+            .expect_or_abort("could not find the shader root dir, `wesl_build::build_shader_dir` must be run first to set the WESL_BUILD_DIR_ROOT_PATH environment variable");
+        // module names may be the normalized names of the files
+        let normalize = match build_file_names().as_deref() {
+            None | Some("verbatim" | "reject") => false,
+            Some("normalize") => true,
+            Some(unknown) => proc_macro_error2::abort_call_site!(
+                "unknown file names `{}` reported by wesl_build", unknown;
+                help = "make sure wesl_build and wesl_build_import are compatible versions"
+            ),
+        };
+        let file_names = match normalize {
+            true => source_names(&shader_dir, &mod_path.components),
+            false => mod_path.components.clone(),
+        };
+        shader_dir.extend(&file_names);
 
         let shader_exists = shader_exists(&mut shader_dir);
         // depth first dir search, to find error point, like is mod not file or so such file
@@ -82,34 +139,64 @@ pub fn include_wesl(shader_path: proc_macro::TokenStream) -> proc_macro::TokenSt
                 )
             }
 
+            // find the first component of the path that does not exist
             shader_dir.pop();
-            let mut component_idx = mod_path.components.len() - 2;
-
-            loop {
-                let dir_exists = std::fs::exists(&shader_dir).ok().is_some_and(|b| b);
-                if dir_exists || component_idx == 0 {
-                    proc_macro_error2::emit_error!(shader_path.path.segments[component_idx].span(), ""; note = "this is the last component of the path that exists");
-                    abort!(proc_macro_error2::SpanRange {
-                            // span just the not found part of the path
-                            first: shader_path.path.segments[component_idx].ident.span().unwrap().end().into(),
-                            last: path_last.ident.span(),
-                        },
-                        "shader `{}` does not exist", &path_last_name
-                    )
-                } else {
-                    if !shader_dir.pop() {
-                        break;
-                    }
-                    component_idx -= 1;
-                }
+            let mut component_idx = mod_path.components.len() - 1;
+            while component_idx > 0 && !std::fs::exists(&shader_dir).ok().is_some_and(|b| b) {
+                shader_dir.pop();
+                component_idx -= 1;
+            }
+
+            if component_idx > 0 {
+                proc_macro_error2::emit_error!(component_span(component_idx - 1), ""; note = "this is the last component of the path that exists");
+            }
+            let not_found_span = proc_macro_error2::SpanRange {
+                // span just the not found part of the path
+                first: component_span(component_idx),
+                last: path_last.ident.span(),
+            };
+
+            if let Some(suggestion) = suggest_path(shader_dir, &file_names[component_idx..]) {
+                let suggestion = suggestion.iter()
+                    .map(|name| if normalize { normalize_name(name) } else { name.clone() })
+                    .collect::<Vec<_>>();
+                let suggested_path = mod_path.components[..component_idx].iter()
+                    .chain(&suggestion)
+                    .map(String::as_str)
+                    .collect::<Vec<_>>()
+                    .join("::");
+                abort!(not_found_span,
+                    "shader `{}` does not exist", &path_last_name;
+                    help = "did you mean `{}`?", suggested_path
+                )
+            } else {
+                abort!(not_found_span, "shader `{}` does not exist", &path_last_name)
             }
         }
     }
 
-    // !! keep in sync with mangler used in wesl_build !!
-    let name_mangler = wesl::EscapeMangler;
-    // mange name
-    let shader_path = name_mangler.mangle(&mod_path, &path_last_name);
+    // the shaders are at their module paths, or at their mangled names in the flat layout, unless an extension renamed them
+    // !! keep in sync with the `artifact.` keys of the build manifest of wesl_build !!
+    let shader_path = match read_build_manifest(&format!("artifact.{}", mod_path.components.join("::"))) {
+        Some(renamed) => renamed,
+        None => match build_layout().as_deref() {
+            None | Some("tree") => format!("{OUTPUT_DIR_NAME}/{}", mod_path.components.join("/")),
+            // mange name with the mangler reported by wesl_build
+            Some("flat") => match build_mangler_name().as_deref() {
+                None | Some("escape") => wesl::EscapeMangler.mangle(&mod_path, path_last_name),
+                Some("hash") => wesl::HashMangler.mangle(&mod_path, path_last_name),
+                Some("none") => wesl::NoMangler.mangle(&mod_path, path_last_name),
+                Some(unknown) => proc_macro_error2::abort_call_site!(
+                    "unknown mangler `{}` reported by wesl_build", unknown;
+                    help = "make sure wesl_build and wesl_build_import are compatible versions"
+                ),
+            },
+            Some(unknown) => proc_macro_error2::abort_call_site!(
+                "unknown output layout `{}` reported by wesl_build", unknown;
+                help = "make sure wesl_build and wesl_build_import are compatible versions"
+            ),
+        },
+    };
 
     // output is the same as calling [`wasl::include_wesl!`]
     quote! {
@@ -117,6 +204,178 @@ pub fn include_wesl(shader_path: proc_macro::TokenStream) -> proc_macro::TokenSt
     }.into()
 }
 
+/// The name of the manifest `wesl_build::build_shader_dir` writes to `OUT_DIR`
+///
+/// !! keep in sync with `BUILD_MANIFEST_NAME` in wesl_build !!
+const BUILD_MANIFEST_NAME: &str = "wesl_build.manifest";
+
+/// The dir of `OUT_DIR` the shaders are built to in the tree layout
+///
+/// !! keep in sync with `OUTPUT_DIR_NAME` in wesl_build !!
+const OUTPUT_DIR_NAME: &str = "wesl";
+
+/// The shader root dir used if the build did not report one, relative to the crate dir
+const DEFAULT_SHADER_ROOT: &str = "src/shaders";
+
+/// Find the shader root dir, in order of priority:
+/// 1. the `WESL_BUILD_DIR_ROOT_PATH` environment variable exported by `build_shader_dir`
+/// 2. the `root` in the manifest `build_shader_dir` wrote to `OUT_DIR`
+/// 3. `WESL_BUILD_DEFAULT_ROOT_PATH` or `src/shaders` relative to the crate dir, if it exists
+///
+/// The first two are only available after the build script has run,
+/// so the last one keeps rust-analyzer and doc builds working
+fn shader_root_dir() -> Option<PathBuf> {
+    if let Some(root) = std::env::var_os("WESL_BUILD_DIR_ROOT_PATH") {
+        return Some(root.into());
+    }
+
+    if let Some(root) = read_build_manifest("root") {
+        return Some(root.into());
+    }
+
+    let default_root = std::env::var_os("WESL_BUILD_DEFAULT_ROOT_PATH")
+        .map_or_else(|| PathBuf::from(DEFAULT_SHADER_ROOT), PathBuf::from);
+    let default_root = match std::env::var_os("CARGO_MANIFEST_DIR") {
+        Some(manifest_dir) => PathBuf::from(manifest_dir).join(default_root),
+        None => default_root,
+    };
+
+    default_root.is_dir().then_some(default_root)
+}
+
+/// The name of the mangler `build_shader_dir` used, `None` if the build did not report one
+fn build_mangler_name() -> Option<String> {
+    std::env::var("WESL_BUILD_MANGLER").ok()
+        .or_else(|| read_build_manifest("mangler"))
+}
+
+/// The output layout of `build_shader_dir`, `tree` or `flat`, `None` if the build did not report one
+fn build_layout() -> Option<String> {
+    std::env::var("WESL_BUILD_LAYOUT").ok()
+        .or_else(|| read_build_manifest("layout"))
+}
+
+/// Read `key` from the `key=value` manifest `build_shader_dir` wrote to `OUT_DIR`
+fn read_build_manifest(key: &str) -> Option<String> {
+    let out_dir = std::env::var_os("OUT_DIR")?;
+    let manifest = std::fs::read_to_string(PathBuf::from(out_dir).join(BUILD_MANIFEST_NAME)).ok()?;
+
+    manifest.lines()
+        .filter_map(|line| line.split_once('='))
+        .find(|(line_key, _)| *line_key == key)
+        .map(|(_, value)| value.to_owned())
+}
+
+/// The rust module path of the file invoking the macro, relative to the crates `src` dir
+///
+/// e.g. `src/render/blur.rs` is `["render", "blur"]` and `src/render/mod.rs` is `["render"]`
+fn caller_module_components() -> Result<Vec<String>, String> {
+    let caller_file = proc_macro::Span::call_site()
+        .local_file()
+        .ok_or("the invoking file is not on disk")?;
+    let manifest_dir = std::env::var_os("CARGO_MANIFEST_DIR")
+        .ok_or("the CARGO_MANIFEST_DIR environment variable is not set")?;
+
+    let caller_file = std::fs::canonicalize(&caller_file)
+        .map_err(|e| format!("{}: {e}", caller_file.display()))?;
+    let manifest_dir = std::fs::canonicalize(&manifest_dir)
+        .map_err(|e| format!("{}: {e}", PathBuf::from(&manifest_dir).display()))?;
+
+    let relative_file = caller_file.strip_prefix(&manifest_dir)
+        .map_err(|_| format!("{} is not in the crate dir {}", caller_file.display(), manifest_dir.display()))?;
+
+    module_components_of_file(relative_file)
+}
+
+/// The module path of a rust file relative to its crate dir, skipping the crate root dir (`src`, `examples`, ...)
+fn module_components_of_file(relative_file: &std::path::Path) -> Result<Vec<String>, String> {
+    let mut components = relative_file.iter()
+        // skip `src`
+        .skip(1)
+        .map(|component| component.to_str()
+            .map(str::to_owned)
+            .ok_or_else(|| format!("{} is not valid UTF-8", relative_file.display())))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // the file itself is only a module if it is not a crate root or a `mod.rs`
+    if let Some(file_name) = components.pop() {
+        let file_stem = file_name.strip_suffix(".rs").unwrap_or(&file_name);
+        if !matches!(file_stem, "mod" | "lib" | "main") {
+            components.push(file_stem.to_owned());
+        }
+    }
+
+    Ok(components)
+}
+
+/// Resolve a path relative to `base` where `supers` is the number of `super` prefixes
+///
+/// `None` if the path goes above the root.
+///
+/// !! keep in sync with `resolve_relative` in wesl_build !!
+fn resolve_relative(base: &[String], supers: usize) -> Option<Vec<String>> {
+    let keep = base.len().checked_sub(supers)?;
+    Some(base[..keep].to_vec())
+}
+
+/// Find the closest existing path to the `missing` components starting in `existing_dir`,
+/// every component but the last must be a module(dir) the last must be a shader
+fn suggest_path(mut existing_dir: PathBuf, missing: &[String]) -> Option<Vec<String>> {
+    let mut suggestion = Vec::with_capacity(missing.len());
+
+    for (idx, component) in missing.iter().enumerate() {
+        let is_shader = idx == missing.len() - 1;
+        let candidates = std::fs::read_dir(&existing_dir).ok()?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let path = entry.path();
+                let is_dir = entry.file_type().ok()?.is_dir();
+                if is_shader {
+                    let is_shader_file = !is_dir && path.extension()
+                        .is_some_and(|ext| ext == "wesl" || ext == "wgsl");
+                    is_shader_file.then(|| path.file_stem()?.to_str().map(str::to_owned)).flatten()
+                } else {
+                    is_dir.then(|| path.file_name()?.to_str().map(str::to_owned)).flatten()
+                }
+            });
+
+        let closest = candidates
+            .map(|candidate| (edit_distance(component, &candidate), candidate))
+            // allow roughly one typo per three characters
+            .filter(|(distance, _)| *distance <= (component.chars().count() / 3).max(1))
+            .min_by_key(|(distance, _)| *distance)?
+            .1;
+
+        existing_dir.push(&closest);
+        suggestion.push(closest);
+    }
+
+    Some(suggestion)
+}
+
+/// The Levenshtein distance between `a` and `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    // distances from the previous row of a
+    let mut prev_row = (0..=b.len()).collect::<Vec<_>>();
+
+    for (a_idx, a_char) in a.chars().enumerate() {
+        let mut row = Vec::with_capacity(b.len() + 1);
+        row.push(a_idx + 1);
+
+        for (b_idx, b_char) in b.iter().enumerate() {
+            let substitution = prev_row[b_idx] + usize::from(a_char != *b_char);
+            let insertion = row[b_idx] + 1;
+            let deletion = prev_row[b_idx + 1] + 1;
+            row.push(substitution.min(insertion).min(deletion));
+        }
+
+        prev_row = row;
+    }
+
+    prev_row[b.len()]
+}
+
 fn shader_exists(shader_dir: &mut PathBuf) -> bool {
     shader_dir.set_extension("wesl");
     let exists = std::fs::exists(&shader_dir).ok().is_some_and(|b| b)
@@ -128,5 +387,66 @@ fn shader_exists(shader_dir: &mut PathBuf) -> bool {
     // reset extension
     shader_dir.set_extension("");
 
-    exists
+    exists || build_platform().is_some_and(|platform| {
+        // the module of a shader that only exists as an override for the platform, `blur.metal.wesl`
+        let Some(name) = shader_dir.file_name().and_then(|name| name.to_str()) else { return false };
+        ["wesl", "wgsl"].iter()
+            .any(|ext| shader_dir.with_file_name(format!("{name}.{platform}.{ext}")).is_file())
+    })
+}
+
+/// How the names of shader files became module names, see `WeslBuildConfig::file_names`
+fn build_file_names() -> Option<String> {
+    std::env::var("WESL_BUILD_FILE_NAMES").ok()
+        .or_else(|| read_build_manifest("file_names"))
+}
+
+/// The names of the dirs and shader file in `shader_root` whose normalized names are `components`,
+/// a component is kept as is if no name matches it
+fn source_names(shader_root: &std::path::Path, components: &[String]) -> Vec<String> {
+    let mut dir = shader_root.to_owned();
+    let mut names = Vec::with_capacity(components.len());
+    for (idx, component) in components.iter().enumerate() {
+        let is_shader = idx == components.len() - 1;
+        let name = std::fs::read_dir(&dir).into_iter().flatten()
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let path = entry.path();
+                if is_shader {
+                    let is_shader_file = path.extension().is_some_and(|ext| ext == "wesl" || ext == "wgsl");
+                    // overrides like `blur-pass.metal.wesl` are named after their base
+                    let stem = path.file_stem()?.to_str()?.split('.').next()?.to_owned();
+                    is_shader_file.then_some(stem)
+                } else {
+                    entry.file_type().ok()?.is_dir().then(|| path.file_name()?.to_str().map(str::to_owned)).flatten()
+                }
+            })
+            .find(|name| normalize_name(name) == *component)
+            .unwrap_or_else(|| component.clone());
+
+        dir.push(&name);
+        names.push(name);
+    }
+
+    names
+}
+
+/// The platform whose shader overrides were built, see `WeslBuildConfig::platform`
+fn build_platform() -> Option<String> {
+    std::env::var("WESL_BUILD_PLATFORM").ok()
+        .or_else(|| read_build_manifest("platform"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("test_mod", "test_mod"), 0);
+        assert_eq!(edit_distance("test_mdo", "test_mod"), 2);
+        assert_eq!(edit_distance("tset", "test"), 2);
+        assert_eq!(edit_distance("test_mod_fil", "test_mod_file"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
 }
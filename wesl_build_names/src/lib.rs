@@ -0,0 +1,83 @@
+//! The module names of shader files, shared by `wesl_build`, which builds the shaders,
+//! and `wesl_build_import`, which finds them for `include_wesl!`
+
+/// `name` as an identifier: dashes and spaces become `_`, accented latin letters lose their accents,
+/// other characters become their code point, e.g. `光` is `u5149`, and a leading digit gets a `_`
+///
+/// ## Example
+/// ```
+/// use wesl_build_names::normalize_name;
+///
+/// assert_eq!(normalize_name("blur-pass"), "blur_pass");
+/// assert_eq!(normalize_name("café"), "cafe");
+/// assert_eq!(normalize_name("光"), "u5149");
+/// assert_eq!(normalize_name("2d"), "_2d");
+/// ```
+pub fn normalize_name(name: &str) -> String {
+    let mut normalized = String::with_capacity(name.len());
+    for c in name.chars() {
+        match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '_' => normalized.push(c),
+            '-' | ' ' => normalized.push('_'),
+            c => match transliterate(c) {
+                Some(latin) => normalized.push_str(latin),
+                None => normalized.push_str(&format!("u{:x}", u32::from(c))),
+            },
+        }
+    }
+    if normalized.is_empty() || normalized.starts_with(|c: char| c.is_ascii_digit()) {
+        normalized.insert(0, '_');
+    }
+    normalized
+}
+
+/// The ASCII letters of an accented latin letter
+fn transliterate(c: char) -> Option<&'static str> {
+    Some(match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' => "a",
+        'À' | 'Á' | 'Â' | 'Ã' | 'Ä' | 'Å' | 'Ā' => "A",
+        'æ' => "ae",
+        'Æ' => "AE",
+        'ç' | 'ć' | 'č' => "c",
+        'Ç' | 'Ć' | 'Č' => "C",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ę' | 'ě' => "e",
+        'È' | 'É' | 'Ê' | 'Ë' | 'Ē' | 'Ę' | 'Ě' => "E",
+        'ì' | 'í' | 'î' | 'ï' | 'ī' => "i",
+        'Ì' | 'Í' | 'Î' | 'Ï' | 'Ī' => "I",
+        'ł' => "l",
+        'Ł' => "L",
+        'ñ' | 'ń' | 'ň' => "n",
+        'Ñ' | 'Ń' | 'Ň' => "N",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' => "o",
+        'Ò' | 'Ó' | 'Ô' | 'Õ' | 'Ö' | 'Ø' | 'Ō' => "O",
+        'œ' => "oe",
+        'Œ' => "OE",
+        'ř' => "r",
+        'Ř' => "R",
+        'ś' | 'š' => "s",
+        'Ś' | 'Š' => "S",
+        'ß' => "ss",
+        'ť' => "t",
+        'Ť' => "T",
+        'ù' | 'ú' | 'û' | 'ü' | 'ū' | 'ů' => "u",
+        'Ù' | 'Ú' | 'Û' | 'Ü' | 'Ū' | 'Ů' => "U",
+        'ý' | 'ÿ' => "y",
+        'Ý' | 'Ÿ' => "Y",
+        'ź' | 'ż' | 'ž' => "z",
+        'Ź' | 'Ż' | 'Ž' => "Z",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_name() {
+        assert_eq!(normalize_name("blur-pass"), "blur_pass");
+        assert_eq!(normalize_name("Crème brûlée"), "Creme_brulee");
+        assert_eq!(normalize_name("光"), "u5149");
+        assert_eq!(normalize_name("3d"), "_3d");
+    }
+}